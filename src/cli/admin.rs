@@ -0,0 +1,304 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Administration of a running cluster through its HTTP API, for scripts
+//! and for hosts which can reach the cluster but not its storage.
+
+use std::path::Path;
+
+use clap::ArgMatches;
+use config::utils::json::{self, Value};
+
+use crate::cli::http::{self, Target};
+
+pub fn command() -> clap::Command {
+    let stream_type = clap::Arg::new("type")
+        .short('t')
+        .long("type")
+        .default_value("logs")
+        .help("stream type: logs, metrics, traces");
+    clap::Command::new("admin")
+        .about("manage a running cluster through its http api")
+        .subcommand_required(true)
+        .args(http::args().map(|arg| arg.global(true)))
+        .subcommands([
+            clap::Command::new("streams")
+                .about("list or delete streams")
+                .subcommand_required(true)
+                .subcommands([
+                    clap::Command::new("list").arg(stream_type.clone()),
+                    clap::Command::new("delete")
+                        .args([clap::Arg::new("name").required(true), stream_type.clone()]),
+                ]),
+            clap::Command::new("search")
+                .about("run a sql query, print the hits as json lines")
+                .args([
+                    clap::Arg::new("sql").required(true),
+                    stream_type.clone(),
+                    clap::Arg::new("since")
+                        .long("since")
+                        .default_value("15")
+                        .value_parser(clap::value_parser!(i64))
+                        .help("minutes to search back from now"),
+                    clap::Arg::new("size")
+                        .long("size")
+                        .default_value("100")
+                        .value_parser(clap::value_parser!(i64)),
+                ]),
+            clap::Command::new("compact")
+                .about("move a stream to the front of the compaction queue")
+                .args([
+                    clap::Arg::new("name").required(true),
+                    stream_type,
+                    clap::Arg::new("priority")
+                        .long("priority")
+                        .default_value("100")
+                        .value_parser(clap::value_parser!(i64))
+                        .help("higher is compacted first"),
+                ]),
+            clap::Command::new("export-dashboards")
+                .about("write the dashboards of a folder to json files")
+                .args([
+                    clap::Arg::new("folder")
+                        .long("folder")
+                        .default_value("default"),
+                    clap::Arg::new("dir")
+                        .short('d')
+                        .long("dir")
+                        .default_value(".")
+                        .help("directory of the files"),
+                ]),
+            clap::Command::new("users")
+                .about("list, add or delete users")
+                .subcommand_required(true)
+                .subcommands([
+                    clap::Command::new("list"),
+                    clap::Command::new("add").args([
+                        clap::Arg::new("email").required(true),
+                        clap::Arg::new("new-password")
+                            .long("new-password")
+                            .required(true)
+                            .help("password of the new user"),
+                        clap::Arg::new("role").long("role").default_value("admin"),
+                        clap::Arg::new("first-name")
+                            .long("first-name")
+                            .default_value(""),
+                        clap::Arg::new("last-name")
+                            .long("last-name")
+                            .default_value(""),
+                    ]),
+                    clap::Command::new("delete").arg(clap::Arg::new("email").required(true)),
+                ]),
+        ])
+}
+
+pub async fn run(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    match command.subcommand() {
+        Some(("streams", command)) => match command.subcommand() {
+            Some(("list", command)) => list_streams(command).await,
+            Some(("delete", command)) => delete_stream(command).await,
+            _ => unreachable!("subcommand is required"),
+        },
+        Some(("search", command)) => search(command).await,
+        Some(("compact", command)) => compact(command).await,
+        Some(("export-dashboards", command)) => export_dashboards(command).await,
+        Some(("users", command)) => match command.subcommand() {
+            Some(("list", command)) => list_users(command).await,
+            Some(("add", command)) => add_user(command).await,
+            Some(("delete", command)) => delete_user(command).await,
+            _ => unreachable!("subcommand is required"),
+        },
+        _ => unreachable!("subcommand is required"),
+    }
+}
+
+fn arg<'a>(command: &'a ArgMatches, name: &str) -> &'a str {
+    command.get_one::<String>(name).unwrap()
+}
+
+async fn list_streams(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let resp = target
+        .get(&format!("streams?type={}", arg(command, "type")))
+        .await?;
+    println!(
+        "{:<40} {:<10} {:>14} {:>14}",
+        "NAME", "TYPE", "DOCS", "SIZE_MB"
+    );
+    for stream in resp["list"].as_array().into_iter().flatten() {
+        println!(
+            "{:<40} {:<10} {:>14} {:>14.2}",
+            stream["name"].as_str().unwrap_or_default(),
+            stream["stream_type"].as_str().unwrap_or_default(),
+            stream["stats"]["doc_num"].as_i64().unwrap_or_default(),
+            stream["stats"]["storage_size"].as_f64().unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+async fn delete_stream(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let name = arg(command, "name");
+    target
+        .delete(&format!("streams/{name}?type={}", arg(command, "type")))
+        .await?;
+    println!("stream {name} deleted");
+    Ok(())
+}
+
+async fn search(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let end_time = chrono::Utc::now().timestamp_micros();
+    let since = *command.get_one::<i64>("since").unwrap();
+    let body = json::json!({
+        "query": {
+            "sql": arg(command, "sql"),
+            "start_time": end_time - since * 60 * 1_000_000,
+            "end_time": end_time,
+            "from": 0,
+            "size": *command.get_one::<i64>("size").unwrap(),
+        }
+    });
+    let resp = target
+        .post(&format!("_search?type={}", arg(command, "type")), &body)
+        .await?;
+    for hit in resp["hits"].as_array().into_iter().flatten() {
+        println!("{hit}");
+    }
+    eprintln!(
+        "{} hits, took {}ms",
+        resp["total"].as_i64().unwrap_or_default(),
+        resp["took"].as_i64().unwrap_or_default()
+    );
+    Ok(())
+}
+
+async fn compact(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let name = arg(command, "name");
+    let priority = *command.get_one::<i64>("priority").unwrap();
+    target
+        .put(
+            &format!(
+                "streams/{name}/compact_priority?type={}",
+                arg(command, "type")
+            ),
+            &json::json!({ "priority": priority }),
+        )
+        .await?;
+    println!("stream {name} queued for compaction with priority {priority}");
+    Ok(())
+}
+
+async fn export_dashboards(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let dir = Path::new(arg(command, "dir"));
+    std::fs::create_dir_all(dir)?;
+    let resp = target
+        .get(&format!("dashboards?folder={}", arg(command, "folder")))
+        .await?;
+    let mut exported = 0;
+    for dashboard in resp["dashboards"].as_array().into_iter().flatten() {
+        let Some(dashboard) = current_version(dashboard) else {
+            continue;
+        };
+        let id = dashboard["dashboardId"].as_str().unwrap_or_default();
+        let path = dir.join(format!("{id}.json"));
+        std::fs::write(&path, serde_json::to_string_pretty(dashboard)?)?;
+        println!("{}", path.display());
+        exported += 1;
+    }
+    eprintln!("{exported} dashboards exported");
+    Ok(())
+}
+
+/// The dashboard of a listed item, which holds it under its version, in the
+/// form the create dashboard api accepts.
+fn current_version(item: &Value) -> Option<&Value> {
+    let version = item["version"].as_i64()?;
+    item.get(format!("v{version}")).filter(|v| v.is_object())
+}
+
+async fn list_users(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let resp = target.get("users").await?;
+    println!("{:<40} {:<10} {:<30}", "EMAIL", "ROLE", "NAME");
+    for user in resp["data"].as_array().into_iter().flatten() {
+        println!(
+            "{:<40} {:<10} {:<30}",
+            user["email"].as_str().unwrap_or_default(),
+            user["role"].as_str().unwrap_or_default(),
+            format!(
+                "{} {}",
+                user["first_name"].as_str().unwrap_or_default(),
+                user["last_name"].as_str().unwrap_or_default()
+            )
+            .trim(),
+        );
+    }
+    Ok(())
+}
+
+async fn add_user(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let email = arg(command, "email");
+    let body = json::json!({
+        "email": email,
+        "password": arg(command, "new-password"),
+        "role": arg(command, "role"),
+        "first_name": arg(command, "first-name"),
+        "last_name": arg(command, "last-name"),
+    });
+    target.post("users", &body).await?;
+    println!("user {email} added");
+    Ok(())
+}
+
+async fn delete_user(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = Target::new(command);
+    let email = arg(command, "email");
+    target.delete(&format!("users/{email}")).await?;
+    println!("user {email} deleted");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let matches = command()
+            .try_get_matches_from([
+                "admin", "streams", "delete", "app", "-t", "traces", "-o", "acme",
+            ])
+            .unwrap();
+        let (_, streams) = matches.subcommand().unwrap();
+        let (name, delete) = streams.subcommand().unwrap();
+        assert_eq!(name, "delete");
+        assert_eq!(arg(delete, "name"), "app");
+        assert_eq!(arg(delete, "type"), "traces");
+        assert_eq!(arg(delete, "org"), "acme");
+        assert!(command().try_get_matches_from(["admin", "users"]).is_err());
+    }
+
+    #[test]
+    fn test_current_version() {
+        let item = json::json!({"v1": null, "v3": {"dashboardId": "42"}, "version": 3});
+        assert_eq!(current_version(&item).unwrap()["dashboardId"], "42");
+        assert!(current_version(&json::json!({"version": 2, "v2": null})).is_none());
+    }
+}
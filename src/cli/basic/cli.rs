@@ -109,6 +109,8 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
                         .help("the parquet file name"),
                 ),
             clap::Command::new("migrate-schemas").about("migrate from single row to row per schema version"),
+            crate::cli::benchmark::command(),
+            crate::cli::admin::command(),
         ])
         .get_matches();
 
@@ -132,6 +134,15 @@ pub async fn cli() -> Result<bool, anyhow::Error> {
         }
         return Ok(true);
     }
+    // the benchmark and the admin commands work through the http api
+    if name == "benchmark" {
+        crate::cli::benchmark::run(command).await?;
+        return Ok(true);
+    }
+    if name == "admin" {
+        crate::cli::admin::run(command).await?;
+        return Ok(true);
+    }
 
     // init infra, create data dir & tables
     infra::init().await.expect("infra init failed");
@@ -0,0 +1,262 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Load generator, ingests synthetic data into a cluster at a target rate
+//! and reports the latency of the ingestion and of searches over the data.
+
+use std::time::{Duration, Instant};
+
+use clap::ArgMatches;
+use config::utils::json::{self, Value};
+use tokio::task::JoinSet;
+
+use crate::{
+    cli::http::{self, Target},
+    common::utils::synthetic,
+};
+
+/// Searches run over the generated logs, `{stream}` is the stream name.
+const SEARCHES: [(&str, &str); 4] = [
+    ("count", "SELECT count(*) FROM \"{stream}\""),
+    ("filter", "SELECT * FROM \"{stream}\" WHERE status >= 500"),
+    (
+        "aggregate",
+        "SELECT host, avg(took_ms) AS took FROM \"{stream}\" GROUP BY host",
+    ),
+    (
+        "full_text",
+        "SELECT * FROM \"{stream}\" WHERE match_all('login')",
+    ),
+];
+
+pub fn command() -> clap::Command {
+    clap::Command::new("benchmark")
+        .about("ingest synthetic data into a cluster and report ingest and search latencies")
+        .args(http::args())
+        .args([
+            clap::Arg::new("stream")
+                .short('s')
+                .long("stream")
+                .default_value("benchmark")
+                .help("the stream of the logs and the prefix of the traces service"),
+            clap::Arg::new("type")
+                .short('t')
+                .long("type")
+                .default_value("logs")
+                .help("data to generate: logs, metrics, traces or all"),
+            clap::Arg::new("rate")
+                .short('r')
+                .long("rate")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(u64))
+                .help("records per second"),
+            clap::Arg::new("duration")
+                .short('d')
+                .long("duration")
+                .default_value("60")
+                .value_parser(clap::value_parser!(u64))
+                .help("seconds to ingest for"),
+            clap::Arg::new("batch")
+                .short('b')
+                .long("batch")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u64))
+                .help("records per request"),
+            clap::Arg::new("searches")
+                .long("searches")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64))
+                .help("times to run each search after the ingestion, 0 to skip"),
+        ])
+}
+
+pub async fn run(command: &ArgMatches) -> Result<(), anyhow::Error> {
+    let target = std::sync::Arc::new(Target::new(command));
+    let stream = command.get_one::<String>("stream").unwrap().to_string();
+    let data_type = command.get_one::<String>("type").unwrap();
+    let rate = *command.get_one::<u64>("rate").unwrap();
+    let duration = *command.get_one::<u64>("duration").unwrap();
+    let batch = *command.get_one::<u64>("batch").unwrap();
+    let searches = *command.get_one::<u64>("searches").unwrap();
+    if rate == 0 || batch == 0 {
+        return Err(anyhow::anyhow!("rate and batch must be positive"));
+    }
+    let types = match data_type.as_str() {
+        "all" => vec!["logs", "metrics", "traces"],
+        "logs" => vec!["logs"],
+        "metrics" => vec!["metrics"],
+        "traces" => vec!["traces"],
+        _ => return Err(anyhow::anyhow!("unsupported data type: {data_type}")),
+    };
+
+    println!(
+        "ingesting {} at {rate} records/s for {duration}s into {}/{}",
+        types.join(", "),
+        target.url,
+        target.org
+    );
+    let start_time = chrono::Utc::now().timestamp_micros();
+    let mut tasks = JoinSet::new();
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(batch as f64 / rate as f64));
+    let deadline = Instant::now() + Duration::from_secs(duration);
+    let mut sent = 0;
+    while Instant::now() < deadline {
+        interval.tick().await;
+        for data_type in types.iter().copied() {
+            let (path, body) = batch_request(data_type, &stream, batch as usize);
+            let target = target.clone();
+            tasks.spawn(async move {
+                let start = Instant::now();
+                let ret = target.post(&path, &body).await;
+                (data_type, start.elapsed(), ret)
+            });
+        }
+        sent += batch;
+    }
+
+    let mut latencies: Vec<(&str, Vec<Duration>)> = types.iter().map(|t| (*t, vec![])).collect();
+    let mut errors = 0;
+    while let Some(ret) = tasks.join_next().await {
+        let (data_type, took, ret) = ret?;
+        match ret {
+            Ok(_) => {
+                if let Some((_, v)) = latencies.iter_mut().find(|(t, _)| *t == data_type) {
+                    v.push(took);
+                }
+            }
+            Err(e) => {
+                errors += 1;
+                if errors <= 5 {
+                    eprintln!("ingest {data_type} failed: {e}");
+                }
+            }
+        }
+    }
+    println!("sent {sent} records of each type, {errors} requests failed");
+    println!("ingest latency (ms):");
+    for (data_type, mut took) in latencies {
+        print_latencies(data_type, &mut took);
+    }
+
+    if searches == 0 || !types.contains(&"logs") {
+        return Ok(());
+    }
+    // give the ingesters a moment to make the data searchable
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let end_time = chrono::Utc::now().timestamp_micros();
+    println!("search latency (ms):");
+    for (name, sql) in SEARCHES {
+        let body = json::json!({
+            "query": {
+                "sql": sql.replace("{stream}", &stream),
+                "start_time": start_time,
+                "end_time": end_time,
+                "size": 100,
+            }
+        });
+        let mut took = Vec::with_capacity(searches as usize);
+        for _ in 0..searches {
+            let start = Instant::now();
+            match target.post("_search", &body).await {
+                Ok(_) => took.push(start.elapsed()),
+                Err(e) => {
+                    eprintln!("search {name} failed: {e}");
+                    break;
+                }
+            }
+        }
+        print_latencies(name, &mut took);
+    }
+    Ok(())
+}
+
+/// The path and body of a request ingesting `batch` records of the type.
+fn batch_request(data_type: &str, stream: &str, batch: usize) -> (String, Value) {
+    let mut rng = rand::thread_rng();
+    let now = chrono::Utc::now().timestamp_micros();
+    match data_type {
+        "logs" => (
+            format!("{stream}/_json"),
+            Value::Array(
+                (0..batch)
+                    .map(|_| synthetic::web_log(&mut rng, now))
+                    .collect(),
+            ),
+        ),
+        "metrics" => {
+            let mut records = vec![];
+            while records.len() < batch {
+                records.extend(synthetic::metrics(&mut rng, now));
+            }
+            records.truncate(batch);
+            ("ingest/metrics/_json".to_string(), Value::Array(records))
+        }
+        // a trace has a few spans, count the traces as the records
+        _ => (
+            "v1/traces".to_string(),
+            synthetic::traces(&mut rng, now, batch),
+        ),
+    }
+}
+
+fn print_latencies(name: &str, took: &mut [Duration]) {
+    if took.is_empty() {
+        println!("  {name:<10} no successful requests");
+        return;
+    }
+    took.sort();
+    println!(
+        "  {name:<10} n={:<6} p50={:<8.1} p90={:<8.1} p99={:<8.1} max={:.1}",
+        took.len(),
+        millis(percentile(took, 50.0)),
+        millis(percentile(took, 90.0)),
+        millis(percentile(took, 99.0)),
+        millis(took[took.len() - 1]),
+    );
+}
+
+/// The nearest-rank percentile of the sorted values.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let took = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&took, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&took, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&took, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&took[..1], 50.0), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_batch_request() {
+        let (path, body) = batch_request("logs", "bench", 10);
+        assert_eq!(path, "bench/_json");
+        assert_eq!(body.as_array().unwrap().len(), 10);
+        let (path, body) = batch_request("metrics", "bench", 5);
+        assert_eq!(path, "ingest/metrics/_json");
+        assert_eq!(body.as_array().unwrap().len(), 5);
+    }
+}
@@ -53,6 +53,10 @@ impl Context for Export {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         };
 
         let req = search::Request {
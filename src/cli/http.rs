@@ -0,0 +1,122 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Client of the HTTP API of a running cluster, shared by the subcommands
+//! which work against a cluster rather than its storage.
+
+use clap::ArgMatches;
+use config::{
+    utils::json::{self, Value},
+    CONFIG,
+};
+use reqwest::{header, Client, Method};
+
+/// The args selecting the cluster, the user and the organization.
+pub fn args() -> [clap::Arg; 4] {
+    [
+        clap::Arg::new("url")
+            .long("url")
+            .default_value("http://localhost:5080")
+            .help("the cluster to connect to"),
+        clap::Arg::new("user")
+            .short('u')
+            .long("user")
+            .help("user email, default is the root user"),
+        clap::Arg::new("password")
+            .short('p')
+            .long("password")
+            .help("user password, default is the root user's"),
+        clap::Arg::new("org")
+            .short('o')
+            .long("org")
+            .default_value("default"),
+    ]
+}
+
+pub struct Target {
+    client: Client,
+    pub url: String,
+    pub org: String,
+    user: String,
+    password: String,
+}
+
+impl Target {
+    /// Builds the target from the matches of [`args`].
+    pub fn new(command: &ArgMatches) -> Self {
+        Self {
+            client: Client::new(),
+            url: command
+                .get_one::<String>("url")
+                .unwrap()
+                .trim_end_matches('/')
+                .to_string(),
+            org: command.get_one::<String>("org").unwrap().to_string(),
+            user: command
+                .get_one::<String>("user")
+                .cloned()
+                .unwrap_or_else(|| CONFIG.auth.root_user_email.clone()),
+            password: command
+                .get_one::<String>("password")
+                .cloned()
+                .unwrap_or_else(|| CONFIG.auth.root_user_password.clone()),
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Value, anyhow::Error> {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Value, anyhow::Error> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, body: &Value) -> Result<Value, anyhow::Error> {
+        self.request(Method::PUT, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<Value, anyhow::Error> {
+        self.request(Method::DELETE, path, None).await
+    }
+
+    /// Sends a request to `/api/{org}/{path}`, returns the body of a
+    /// successful response or the status and body as the error.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Value, anyhow::Error> {
+        let mut req = self
+            .client
+            .request(method, format!("{}/api/{}/{path}", self.url, self.org))
+            .basic_auth(&self.user, Some(&self.password));
+        if let Some(body) = body {
+            req = req
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(json::to_vec(body)?);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        let body = resp.bytes().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "{status}: {}",
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        Ok(json::from_slice(&body).unwrap_or_default())
+    }
+}
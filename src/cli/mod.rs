@@ -13,5 +13,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod admin;
 pub mod basic;
+pub mod benchmark;
 pub mod data;
+pub mod http;
@@ -97,6 +97,9 @@ async fn register() -> Result<()> {
         if is_compactor(&node.role) {
             super::add_node_to_consistent_hash(&node, &Role::Compactor).await;
         }
+        if is_ingester(&node.role) {
+            super::add_node_to_consistent_hash(&node, &Role::Ingester).await;
+        }
         node_ids.push(node.id);
         w.insert(node.uuid.clone(), node);
     }
@@ -143,6 +146,9 @@ async fn register() -> Result<()> {
     if is_compactor(&node.role) {
         super::add_node_to_consistent_hash(&node, &Role::Compactor).await;
     }
+    if is_ingester(&node.role) {
+        super::add_node_to_consistent_hash(&node, &Role::Ingester).await;
+    }
 
     let mut w = super::NODES.write().await;
     w.insert(LOCAL_NODE_UUID.clone(), node);
@@ -25,13 +25,18 @@ use vector_enrichment::TableRegistry;
 use crate::{
     common::meta::{
         alerts,
+        correlation::CorrelationRule,
         dashboards::reports,
         functions::{StreamFunctionsList, Transform},
         maxmind::MaxmindClient,
-        organization::OrganizationSetting,
+        monitors::Monitor,
+        organization::{OrgFeatureFlags, OrganizationSetting},
         prom::ClusterLeader,
+        snmp::{SnmpMib, SnmpTrapRoute},
         syslog::SyslogRoute,
+        threat_intel::IndicatorList,
         user::User,
+        webhooks::Webhook,
     },
     service::{enrichment::StreamTable, enrichment_table::geoip::Geoip},
 };
@@ -52,6 +57,8 @@ pub static USERS_RUM_TOKEN: Lazy<Arc<RwHashMap<String, User>>> =
 pub static ROOT_USER: Lazy<RwHashMap<String, User>> = Lazy::new(DashMap::default);
 pub static ORGANIZATION_SETTING: Lazy<Arc<RwAHashMap<String, OrganizationSetting>>> =
     Lazy::new(|| Arc::new(tokio::sync::RwLock::new(HashMap::new())));
+pub static ORGANIZATION_FEATURES: Lazy<RwHashMap<String, OrgFeatureFlags>> =
+    Lazy::new(DashMap::default);
 pub static PASSWORD_HASH: Lazy<RwHashMap<String, String>> = Lazy::new(DashMap::default);
 pub static METRIC_CLUSTER_MAP: Lazy<Arc<RwAHashMap<String, Vec<String>>>> =
     Lazy::new(|| Arc::new(tokio::sync::RwLock::new(HashMap::new())));
@@ -63,10 +70,22 @@ pub static ALERTS_TEMPLATES: Lazy<RwHashMap<String, alerts::templates::Template>
     Lazy::new(Default::default);
 pub static ALERTS_DESTINATIONS: Lazy<RwHashMap<String, alerts::destinations::Destination>> =
     Lazy::new(Default::default);
+pub static WEBHOOKS: Lazy<RwHashMap<String, Webhook>> = Lazy::new(Default::default);
+/// Last time a quota_exceeded event was sent for an org, in microseconds.
+pub static WEBHOOK_QUOTA_EVENTS: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
+pub static CORRELATION_RULES: Lazy<RwHashMap<String, CorrelationRule>> =
+    Lazy::new(Default::default);
+pub static THREAT_INTEL_LISTS: Lazy<RwHashMap<String, IndicatorList>> = Lazy::new(Default::default);
 pub static DASHBOARD_REPORTS: Lazy<RwHashMap<String, reports::Report>> =
     Lazy::new(Default::default);
 pub static SYSLOG_ROUTES: Lazy<RwHashMap<String, SyslogRoute>> = Lazy::new(Default::default);
 pub static SYSLOG_ENABLED: Lazy<Arc<RwLock<bool>>> = Lazy::new(|| Arc::new(RwLock::new(false)));
+pub static MONITORS: Lazy<RwHashMap<String, Monitor>> = Lazy::new(Default::default);
+pub static SNMP_TRAP_ROUTES: Lazy<RwHashMap<String, SnmpTrapRoute>> = Lazy::new(Default::default);
+pub static SNMP_MIBS: Lazy<RwHashMap<String, SnmpMib>> = Lazy::new(Default::default);
+// oid -> object name, compiled from all uploaded MIBs
+pub static SNMP_OID_NAMES: Lazy<Arc<RwLock<HashMap<String, String>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
 pub static ENRICHMENT_TABLES: Lazy<RwHashMap<String, StreamTable>> = Lazy::new(Default::default);
 pub static ENRICHMENT_REGISTRY: Lazy<Arc<TableRegistry>> =
     Lazy::new(|| Arc::new(TableRegistry::default()));
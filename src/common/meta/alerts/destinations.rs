@@ -25,7 +25,7 @@ use super::templates::Template;
 pub struct Destination {
     #[serde(default)]
     pub name: String,
-    /// Required for `Http` destination_type
+    /// Required for `Http`, `MsTeams` and `GoogleChat` destination_type
     #[serde(default)]
     pub url: String,
     /// Required for `Http` destination_type
@@ -35,10 +35,27 @@ pub struct Destination {
     pub skip_tls_verify: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub headers: Option<HashMap<String, String>>,
+    /// `MsTeams`, `GoogleChat` and `Email` destinations use a default template
+    /// when empty
+    #[serde(default)]
     pub template: String,
     /// Required when `destination_type` is `Email`
     #[serde(default)]
     pub emails: Vec<String>,
+    /// Bot token of the Slack app, required when `destination_type` is `Slack`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub slack_bot_token: String,
+    /// Channel the Slack app posts to, required when `destination_type` is
+    /// `Slack`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub slack_channel: String,
+    /// Signing secret of the Slack app, enables the buttons of the
+    /// notifications, `ZO_SLACK_SIGNING_SECRET` when empty
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub slack_signing_secret: String,
     #[serde(rename = "type")]
     #[serde(default)]
     pub destination_type: DestinationType,
@@ -51,6 +68,12 @@ pub enum DestinationType {
     Http,
     #[serde(rename = "email")]
     Email,
+    #[serde(rename = "slack")]
+    Slack,
+    #[serde(rename = "msteams")]
+    MsTeams,
+    #[serde(rename = "google_chat")]
+    GoogleChat,
 }
 
 impl Destination {
@@ -63,6 +86,9 @@ impl Destination {
             headers: self.headers.clone(),
             template,
             emails: self.emails.clone(),
+            slack_bot_token: self.slack_bot_token.clone(),
+            slack_channel: self.slack_channel.clone(),
+            slack_signing_secret: self.slack_signing_secret.clone(),
             destination_type: self.destination_type.clone(),
         }
     }
@@ -79,6 +105,12 @@ pub struct DestinationWithTemplate {
     pub headers: Option<HashMap<String, String>>,
     pub template: Template,
     pub emails: Vec<String>,
+    #[serde(default)]
+    pub slack_bot_token: String,
+    #[serde(default)]
+    pub slack_channel: String,
+    #[serde(default)]
+    pub slack_signing_secret: String,
     pub destination_type: DestinationType,
 }
 
@@ -18,6 +18,8 @@ use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::common::meta::forecast::Forecast;
+
 pub mod destinations;
 pub mod templates;
 
@@ -50,6 +52,68 @@ pub struct Alert {
     /// Timezone offset in minutes.
     /// The negative secs means the Western Hemisphere
     pub tz_offset: i32,
+    /// No notification is sent until this time, in microseconds
+    #[serde(default)]
+    pub silenced_until: i64,
+    /// Who acknowledged the alert, cleared when it is saved again
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged: Option<AlertAck>,
+    /// Recipients of the email destinations of the alert, the recipients of
+    /// the destinations are used when empty
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub throttle: Option<AlertThrottle>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flap_detection: Option<FlapDetection>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct AlertAck {
+    pub user: String,
+    /// Microseconds
+    pub at: i64,
+}
+
+/// At most `max_notifications` notifications of the alert are sent in any
+/// `period` minutes, the others are dropped.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AlertThrottle {
+    pub max_notifications: usize,
+    /// Minutes
+    pub period: i64,
+}
+
+/// A scheduled alert changing between firing and not firing `max_changes`
+/// times in `window` minutes is flapping, a single notification says so and
+/// the others are dropped until it settles.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FlapDetection {
+    pub max_changes: usize,
+    /// Minutes
+    pub window: i64,
+}
+
+/// What the notification dispatcher remembers of an alert to throttle it and
+/// detect its flapping.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertNotifyState {
+    /// When the notifications of the throttle period were sent, in
+    /// microseconds
+    #[serde(default)]
+    pub sent_at: Vec<i64>,
+    /// When the alert started or stopped firing in the flap detection window,
+    /// in microseconds
+    #[serde(default)]
+    pub changes_at: Vec<i64>,
+    #[serde(default)]
+    pub firing: bool,
+    #[serde(default)]
+    pub flapping: bool,
 }
 
 impl PartialEq for Alert {
@@ -76,6 +140,11 @@ impl Default for Alert {
             description: "".to_string(),
             enabled: false,
             tz_offset: 0, // UTC
+            silenced_until: 0,
+            acknowledged: None,
+            emails: vec![],
+            throttle: None,
+            flap_detection: None,
         }
     }
 }
@@ -116,6 +185,11 @@ pub struct QueryCondition {
     pub promql: Option<String>,              // (cpu usage / cpu total)
     pub promql_condition: Option<Condition>, // value >= 80
     pub aggregation: Option<Aggregation>,
+    /// Forecast of the time buckets the SQL returns, the alert fires when a
+    /// series crosses the condition of the forecast within its horizon
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<Forecast>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
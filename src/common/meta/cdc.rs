@@ -0,0 +1,70 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json::Value;
+use serde::{Deserialize, Serialize};
+
+/// The logs stream of each org receiving the changes of its metadata.
+pub const CDC_STREAM: &str = "_cdc";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CdcObjectType {
+    /// Id is `{stream_type}/{stream_name}`, the payload is the list of fields.
+    Schema,
+    /// Id is `{stream_type}/{stream_name}`.
+    StreamSettings,
+    /// Id is `{stream_type}/{stream_name}/{alert_name}`.
+    Alert,
+    /// Id is the email, the payload never contains the credentials.
+    User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CdcOperation {
+    Create,
+    Update,
+    Delete,
+}
+
+impl CdcOperation {
+    pub fn from_change(before: Option<&Value>, after: Option<&Value>) -> Self {
+        match (before, after) {
+            (None, _) => CdcOperation::Create,
+            (Some(_), None) => CdcOperation::Delete,
+            (Some(_), Some(_)) => CdcOperation::Update,
+        }
+    }
+}
+
+/// A record of the `_cdc` stream. The payloads are kept as JSON strings, so
+/// they are not flattened into columns and can be replayed as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CdcEvent {
+    #[serde(skip)]
+    pub org_id: String,
+    /// Written as the timestamp column of the record, in microseconds.
+    #[serde(skip)]
+    pub timestamp: i64,
+    pub object_type: CdcObjectType,
+    pub object_id: String,
+    pub operation: CdcOperation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    pub node: String,
+}
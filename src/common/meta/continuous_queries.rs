@@ -0,0 +1,81 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::meta::stream::StreamType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A SQL query run over the new records of a stream every `interval`, its
+/// results are ingested into the `destination` log stream. The query runs as
+/// the user who created it, with their permissions on the streams.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ContinuousQuery {
+    #[serde(default)]
+    pub name: String,
+    /// Type of the stream the query reads
+    #[serde(default)]
+    pub stream_type: StreamType,
+    /// Aggregation over the stream, e.g. the errors per service per minute.
+    /// Each run only reads the records of its window, the results without a
+    /// `_timestamp` get the start of the window. A window returning more than
+    /// 10000 rows fails.
+    pub sql: String,
+    /// Log stream the results are ingested into
+    pub destination: String,
+    /// Seconds between two runs, the windows are aligned to it
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Seconds a window waits for the late records before it runs
+    #[serde(default = "default_delay")]
+    pub delay: i64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// End of the last window which ran, in microseconds
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<i64>,
+    /// Window whose results are being ingested, a run interrupted meanwhile
+    /// checks the destination before ingesting them again
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<PendingWindow>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub created_by: String,
+}
+
+/// The results of a window about to be ingested
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PendingWindow {
+    /// Start of the window, in microseconds
+    pub start: i64,
+    /// Time range of the results, in microseconds
+    pub min_ts: i64,
+    pub max_ts: i64,
+}
+
+fn default_interval() -> i64 {
+    60
+}
+
+fn default_delay() -> i64 {
+    30
+}
+
+fn default_enabled() -> bool {
+    true
+}
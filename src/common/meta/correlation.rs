@@ -0,0 +1,90 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::alerts::Condition;
+
+/// Stream of each org the findings of the correlation rules are written to.
+pub const DETECTIONS_STREAM: &str = "_detections";
+
+/// A sequence of events which raises a finding when all of its steps happen
+/// for the same entity within the window, e.g. five failed logins followed by
+/// a successful one from the same ip.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct CorrelationRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Field holding the entity the events are correlated by, e.g. `client_ip`.
+    pub entity_field: String,
+    /// Seconds between the first and the last event of a sequence.
+    pub window: i64,
+    /// Steps in the order they have to happen.
+    pub steps: Vec<CorrelationStep>,
+    /// Alert destinations notified of the findings.
+    #[serde(default)]
+    pub destinations: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct CorrelationStep {
+    #[serde(default)]
+    pub name: String,
+    /// Logs stream the events of the step are ingested into.
+    pub stream: String,
+    /// All of them have to match, an empty list matches every event.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    /// Events needed before the sequence moves to the next step.
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+impl CorrelationRule {
+    pub fn uses_stream(&self, stream_name: &str) -> bool {
+        self.enabled && self.steps.iter().any(|s| s.stream == stream_name)
+    }
+}
+
+/// A completed sequence, written into the [DETECTIONS_STREAM] and sent to the
+/// destinations of the rule.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub rule: String,
+    #[serde(default)]
+    pub description: String,
+    pub entity_field: String,
+    pub entity: String,
+    /// Time of the first and last event of the sequence in microseconds.
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// Names of the steps, comma separated.
+    pub steps: String,
+    /// Events of the sequence.
+    pub events: usize,
+}
@@ -52,6 +52,17 @@ pub struct Folder {
     pub folder_id: String,
     pub name: String,
     pub description: String,
+    /// The folder containing this one, empty for the top level folders.
+    #[serde(default)]
+    pub parent_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveFolder {
+    /// The new parent, empty to move the folder to the top level.
+    #[serde(default)]
+    pub parent_id: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -68,6 +79,23 @@ pub struct FolderList {
 
 pub const DEFAULT_FOLDER: &str = "default";
 
+/// Trims the tags and drops the empty and duplicated ones.
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut ret: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !ret.iter().any(|t| t == tag) {
+            ret.push(tag.to_string());
+        }
+    }
+    ret
+}
+
+/// Whether `tags` has all the `wanted` ones.
+pub fn has_tags(tags: &[String], wanted: &[String]) -> bool {
+    wanted.iter().all(|w| tags.contains(w))
+}
+
 pub fn datetime_now() -> DateTime<FixedOffset> {
     Utc::now().with_timezone(&FixedOffset::east_opt(0).expect(
         "BUG", // This can't possibly fail. Can it?
@@ -39,6 +39,8 @@ pub struct Dashboard {
     pub tabs: Vec<Tab>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub variables: Option<Variables>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
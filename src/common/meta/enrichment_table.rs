@@ -0,0 +1,55 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Url an enrichment table is refreshed from. The CSV file replaces the table
+/// when its content changed, every node then swaps to the new data at once.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct EnrichmentTableSource {
+    /// Name of the enrichment table.
+    #[serde(default)]
+    pub name: String,
+    /// Url of the CSV file.
+    pub url: String,
+    /// Url of the sha256 checksum of the file, a file which doesn't match it
+    /// is rejected.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub sha256_url: String,
+    /// Seconds between two refreshes.
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Sha256 of the file the table was loaded from.
+    #[serde(default)]
+    pub checksum: String,
+    /// Time of the last refresh in microseconds.
+    #[serde(default)]
+    pub refreshed_at: i64,
+    /// Error of the last refresh, empty when it succeeded.
+    #[serde(default)]
+    pub last_error: String,
+}
+
+fn default_interval() -> i64 {
+    86400
+}
+
+impl EnrichmentTableSource {
+    pub fn is_due(&self, now: i64) -> bool {
+        now - self.refreshed_at >= self.interval * 1_000_000
+    }
+}
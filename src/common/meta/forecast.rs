@@ -0,0 +1,104 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json::{Map, Value};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::meta::alerts::Condition;
+
+/// Forecast of the time buckets a query returns, one series per value of its
+/// other columns
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Forecast {
+    /// Column of the time buckets, a histogram() or a timestamp
+    #[serde(default = "default_timestamp_column")]
+    pub timestamp_column: String,
+    pub value_column: String,
+    /// Minutes forecast after the last bucket
+    pub horizon: i64,
+    #[serde(default)]
+    pub method: ForecastMethod,
+    /// Buckets of a season for Holt-Winters, e.g. 24 for the daily season of
+    /// hourly buckets, no season when zero
+    #[serde(default)]
+    pub season: usize,
+    #[serde(default)]
+    pub fill: ForecastFill,
+    /// A series crosses it when a forecast bucket meets it, e.g. disk usage
+    /// >= 100, required by the alerts
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastMethod {
+    #[default]
+    Linear,
+    HoltWinters,
+}
+
+/// Value of the buckets the query returns no row for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastFill {
+    /// No records in the bucket, e.g. counts
+    #[default]
+    Zero,
+    /// On the line between the buckets around, e.g. gauges
+    Linear,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ForecastRequest {
+    /// Query returning the time buckets, e.g. `SELECT histogram(_timestamp,
+    /// '1 hour') AS zo_sql_key, MAX(used_percent) AS used FROM disk GROUP BY
+    /// zo_sql_key`
+    pub sql: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub forecast: Forecast,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ForecastResponse {
+    pub series: Vec<ForecastSeries>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ForecastSeries {
+    /// Values of the other columns of the series
+    #[schema(value_type = Object)]
+    pub labels: Map<String, Value>,
+    pub points: Vec<ForecastPoint>,
+    pub forecast: Vec<ForecastPoint>,
+    /// First forecast bucket meeting the condition
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crosses_at: Option<ForecastPoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ForecastPoint {
+    /// Microseconds
+    pub timestamp: i64,
+    pub value: f64,
+}
+
+fn default_timestamp_column() -> String {
+    "zo_sql_key".to_string()
+}
@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::meta::stream::StreamType;
+use config::{meta::stream::StreamType, utils::json};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use vrl::{
@@ -21,6 +21,10 @@ use vrl::{
     prelude::Function,
 };
 
+/// Functions of this org are shared read-only with all the orgs, an org
+/// function with the same name takes precedence.
+pub const GLOBAL_FUNCTIONS_ORG: &str = "_global";
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Transform {
@@ -36,6 +40,111 @@ pub struct Transform {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub streams: Option<Vec<StreamOrder>>,
+    /// The function comes from the global library. Stored in an org, it only
+    /// records the streams the global function of the same name is applied to.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub global: bool,
+}
+
+/// Pipeline and chain of functions which transformed records at ingestion,
+/// the records carry its id in the `_lineage` column.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Lineage {
+    pub id: i64,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// The streams the records went through, the one which routed them first
+    #[serde(default)]
+    pub pipeline: Vec<LineageStage>,
+    /// In the order they ran
+    pub functions: Vec<LineageFunction>,
+    /// Microseconds, when a record was first stamped with the id
+    pub first_seen: i64,
+    /// Time ranges whose records were reprocessed, the newer records replace
+    /// them
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub superseded: Vec<LineageSuperseded>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct LineageSuperseded {
+    pub job_id: String,
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive
+    pub end_time: i64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReprocessStatus {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// Re-runs the log records stamped with a lineage through the current
+/// functions, to fix forward the records of a faulty function.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ReprocessJob {
+    #[serde(default)]
+    pub id: String,
+    pub stream_name: String,
+    /// Lineage id of the records to reprocess
+    pub lineage: i64,
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive, now when 0
+    #[serde(default)]
+    pub end_time: i64,
+    /// Stream the reprocessed records are written to, the source stream when
+    /// empty
+    #[serde(default)]
+    pub target_stream: String,
+    /// Delete the superseded records once the job is done, otherwise they are
+    /// only marked superseded in their lineage
+    #[serde(default)]
+    pub delete_superseded: bool,
+    #[serde(default)]
+    pub status: ReprocessStatus,
+    /// Ingester running the job
+    #[serde(default)]
+    pub node: String,
+    /// The records before this time are already reprocessed
+    #[serde(default)]
+    pub done_until: i64,
+    /// Records of the window starting at `done_until` already reprocessed
+    #[serde(default)]
+    pub done_offset: usize,
+    #[serde(default)]
+    pub records: i64,
+    #[serde(default)]
+    pub failed: i64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct LineageFunction {
+    pub name: String,
+    pub function: String,
+}
+
+/// A stream of the pipeline, with the revision of the settings its routing,
+/// flattening and drop rules came from.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LineageStage {
+    pub stream_name: String,
+    pub settings_revision: u64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -114,6 +223,31 @@ pub struct StreamFunctionsList {
     pub list: Vec<StreamTransform>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionTestRequest {
+    /// Sample records the function is applied to.
+    #[schema(value_type = Vec<Object>)]
+    pub records: Vec<json::Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionTestResponse {
+    /// One result per sample record, in the order of the request.
+    pub results: Vec<FunctionTestResult>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionTestResult {
+    /// The record as it would be ingested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub output: Option<json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time taken in microseconds.
+    pub took: u64,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VRLConfig {
     pub runtime: VrlRuntime,
@@ -160,6 +294,7 @@ mod tests {
                 stream_type: StreamType::Logs,
                 is_removed: false,
             }]),
+            global: false,
         };
 
         let mod_trans = Transform {
@@ -169,6 +304,7 @@ mod tests {
             params: "row".to_string(),
             num_args: 1,
             streams: None,
+            global: false,
         };
         assert_eq!(trans, mod_trans);
 
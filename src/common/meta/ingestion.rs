@@ -32,6 +32,9 @@ pub struct RecordStatus {
     #[serde(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub error: String,
+    /// Fields moved away from the system columns they were named after.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 pub struct BulkStreamData {
@@ -73,6 +76,65 @@ impl IngestionResponse {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SimulationRequest {
+    /// Records as they would be sent to the `_json` endpoint.
+    #[schema(value_type = Vec<Object>)]
+    pub records: Vec<json::Value>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SimulationResponse {
+    /// One entry per request record, in the order of the request.
+    pub records: Vec<SimulatedRecord>,
+    /// The schema changes the records would make, by target stream.
+    pub schema_changes: HashMap<String, Vec<SchemaFieldChange>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SimulatedRecord {
+    /// The stream the record is routed to.
+    pub stream: String,
+    /// The record as it would be written, unless it is dropped or fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub record: Option<json::Value>,
+    /// The drop rule that matched the record.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropped_by: Option<String>,
+    /// Fields that would be encrypted.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redacted_fields: Vec<String>,
+    /// Fields missing from the user defined schema, kept in the `_all` field.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub undefined_fields: Vec<String>,
+    /// Fields moved away from the system columns they were named after.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaChangeType {
+    /// The field is new to the stream.
+    Added,
+    /// The field type is widened to the new type.
+    Widened,
+    /// The value is cast to the existing type of the field.
+    Cast,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SchemaFieldChange {
+    pub field: String,
+    pub change: SchemaChangeType,
+    pub data_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_type: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StreamSchemaChk {
     pub conforms: bool,
@@ -103,6 +165,8 @@ pub struct BulkResponse {
     pub took: u128,
     pub errors: bool,
     pub items: Vec<HashMap<String, BulkResponseItem>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
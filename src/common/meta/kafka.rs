@@ -0,0 +1,79 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Kafka topics consumed into a log stream by the ingesters.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct KafkaSource {
+    #[serde(default)]
+    pub name: String,
+    /// Bootstrap brokers, `host:port`.
+    pub brokers: Vec<String>,
+    pub topics: Vec<String>,
+    /// Namespace of the stored offsets, sources sharing a group share their
+    /// progress. A source created again with the same group resumes where it
+    /// stopped.
+    #[serde(default)]
+    pub consumer_group: String,
+    #[serde(default)]
+    pub format: KafkaFormat,
+    /// Where partitions without a stored offset start from.
+    #[serde(default)]
+    pub start_from: KafkaStartOffset,
+    pub stream_name: String,
+    #[serde(default)]
+    pub paused: bool,
+    /// Error of the last poll, empty when it succeeded. Stored apart from the
+    /// source, only filled when the source is read.
+    #[serde(default, skip_deserializing, skip_serializing_if = "String::is_empty")]
+    pub last_error: String,
+    /// Stored offsets, only filled when the source is read.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    pub offsets: Vec<KafkaOffset>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaFormat {
+    /// A json object or an array of json objects per message.
+    #[default]
+    Json,
+    /// An OTLP `ExportLogsServiceRequest` protobuf per message, as written by
+    /// the kafka exporter of the OpenTelemetry collector.
+    OtlpProto,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaStartOffset {
+    #[default]
+    Earliest,
+    Latest,
+}
+
+/// The next offset to consume from a partition.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct KafkaOffset {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    /// Time the offset was stored in microseconds.
+    pub updated_at: i64,
+    /// Rejected ingestions of the batch starting at the offset.
+    #[serde(default)]
+    pub failures: u32,
+}
@@ -15,19 +15,36 @@
 
 pub mod alerts;
 pub mod authz;
+pub mod cdc;
+pub mod continuous_queries;
+pub mod correlation;
 pub mod dashboards;
+pub mod enrichment_table;
+pub mod forecast;
 pub mod functions;
 pub mod http;
 pub mod ingestion;
+pub mod kafka;
 pub mod maxmind;
 pub mod middleware_data;
+pub mod monitors;
 pub mod organization;
 pub mod prom;
 pub mod proxy;
+pub mod quality_monitors;
+pub mod revisions;
 pub mod saved_view;
+pub mod search_jobs;
+pub mod search_templates;
 pub mod service;
+pub mod short_url;
+pub mod sigma;
+pub mod slo;
+pub mod snmp;
 pub mod stream;
 pub mod syslog;
 pub mod telemetry;
+pub mod threat_intel;
 pub mod traces;
 pub mod user;
+pub mod webhooks;
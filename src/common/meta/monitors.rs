@@ -0,0 +1,85 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Stream the check results are written to, in the monitor's organization.
+pub const MONITORS_STREAM: &str = "_monitors";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Monitor {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub check_type: CheckType,
+    /// Url for `http`, `host:port` for `tcp` and a host for `icmp` checks
+    pub target: String,
+    /// Seconds between two checks
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Seconds to wait for the target to answer
+    #[serde(default = "default_timeout")]
+    pub timeout: i64,
+    /// Regions the check runs from, empty means every region
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// Expected status code for `http` checks, any 2xx/3xx when empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_status: Option<u16>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq, Eq, Deserialize, Clone, Copy, ToSchema)]
+pub enum CheckType {
+    #[default]
+    #[serde(rename = "http")]
+    Http,
+    #[serde(rename = "tcp")]
+    Tcp,
+    #[serde(rename = "icmp")]
+    Icmp,
+}
+
+impl std::fmt::Display for CheckType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckType::Http => write!(f, "http"),
+            CheckType::Tcp => write!(f, "tcp"),
+            CheckType::Icmp => write!(f, "icmp"),
+        }
+    }
+}
+
+impl Monitor {
+    /// Whether a node of `region` should run this check.
+    pub fn runs_in(&self, region: &str) -> bool {
+        self.regions.is_empty() || self.regions.iter().any(|r| r == region)
+    }
+}
+
+fn default_interval() -> i64 {
+    60
+}
+
+fn default_timeout() -> i64 {
+    10
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -13,7 +13,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::CONFIG;
+use std::collections::HashMap;
+
+use config::{
+    meta::stream::{StreamPartition, StreamSettings, StreamType},
+    CONFIG,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -108,12 +113,75 @@ pub struct OrganizationSetting {
     /// seconds).
     #[serde(default = "default_scrape_interval")]
     pub scrape_interval: u32,
+    /// Rules every search in the org is checked against.
+    #[serde(default)]
+    pub query_policies: Vec<QueryPolicy>,
+    /// Organizations allowed to route records into the streams of this org
+    /// with a `org/stream` routing destination.
+    #[serde(default)]
+    pub routing_sources: Vec<String>,
+    /// Settings the streams created by ingestion start with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_template: Option<StreamTemplate>,
 }
 
 impl Default for OrganizationSetting {
     fn default() -> Self {
         Self {
             scrape_interval: default_scrape_interval(),
+            query_policies: vec![],
+            routing_sources: vec![],
+            stream_template: None,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct QueryPolicy {
+    pub name: String,
+    /// Streams the policy applies to, empty means all streams.
+    #[serde(default)]
+    pub streams: Vec<String>,
+    /// Max time range of a query in hours, 0 means no limit.
+    #[serde(default)]
+    pub max_time_range: i64,
+    /// Only check the max time range for `select *` queries.
+    #[serde(default)]
+    pub select_all_only: bool,
+    /// Fields a query has to filter on.
+    #[serde(default)]
+    pub required_filters: Vec<String>,
+    /// Functions a query can't use.
+    #[serde(default)]
+    pub banned_functions: Vec<String>,
+}
+
+/// Defaults of the new streams of an org, used instead of the built-in ones.
+#[derive(Serialize, ToSchema, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamTemplate {
+    /// Stream types the template applies to, empty means all types.
+    #[serde(default)]
+    pub stream_types: Vec<StreamType>,
+    #[serde(default)]
+    pub full_text_search_keys: Vec<String>,
+    /// Retention in days, 0 means the global retention.
+    #[serde(default)]
+    pub data_retention: i64,
+    #[serde(default)]
+    pub partition_keys: Vec<StreamPartition>,
+}
+
+impl StreamTemplate {
+    pub fn applies_to(&self, stream_type: StreamType) -> bool {
+        self.stream_types.is_empty() || self.stream_types.contains(&stream_type)
+    }
+
+    pub fn settings(&self) -> StreamSettings {
+        StreamSettings {
+            full_text_search_keys: self.full_text_search_keys.clone(),
+            data_retention: self.data_retention,
+            partition_keys: self.partition_keys.clone(),
+            ..Default::default()
         }
     }
 }
@@ -122,3 +190,80 @@ impl Default for OrganizationSetting {
 pub struct OrganizationSettingResponse {
     pub data: OrganizationSetting,
 }
+
+/// Features which can be rolled out to some organizations first.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Running functions on the records ingested into a stream.
+    StreamFunctions,
+    /// Deleting records of a stream matching a query.
+    DeleteRecords,
+    /// Offloading the large field values of the ingested records and
+    /// fetching them back.
+    LargeFields,
+    /// Following the hits of a search as they are found, on `_search_stream`.
+    Tail,
+    /// Running functions in searches, as a query function or called from the
+    /// SQL.
+    QueryFunctions,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::StreamFunctions,
+        Feature::DeleteRecords,
+        Feature::LargeFields,
+        Feature::Tail,
+        Feature::QueryFunctions,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::StreamFunctions => "stream_functions",
+            Feature::DeleteRecords => "delete_records",
+            Feature::LargeFields => "large_fields",
+            Feature::Tail => "tail",
+            Feature::QueryFunctions => "query_functions",
+        }
+    }
+
+    /// Whether the feature is enabled for the organizations which don't set it.
+    pub fn enabled_by_default(&self) -> bool {
+        !CONFIG
+            .common
+            .features_disabled
+            .split(',')
+            .any(|v| v.trim() == self.as_str())
+    }
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The features an organization overrides, the others use their default.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq)]
+pub struct OrgFeatureFlags {
+    #[serde(default)]
+    pub features: HashMap<Feature, bool>,
+}
+
+impl OrgFeatureFlags {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.features
+            .get(&feature)
+            .copied()
+            .unwrap_or_else(|| feature.enabled_by_default())
+    }
+
+    /// All the features with their resolved state.
+    pub fn resolved(&self) -> HashMap<Feature, bool> {
+        Feature::ALL
+            .iter()
+            .map(|f| (*f, self.is_enabled(*f)))
+            .collect()
+    }
+}
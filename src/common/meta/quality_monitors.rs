@@ -0,0 +1,98 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Stream the heartbeats of the shippers are written to, in the stream's
+/// organization.
+pub const HEARTBEATS_STREAM: &str = "_heartbeats";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QualityMonitor {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    #[serde(default)]
+    pub stream_name: String,
+    pub check: QualityCheck,
+    /// Seconds between two checks
+    #[serde(default = "default_interval")]
+    pub interval: i64,
+    /// Alert destinations notified when the check starts failing
+    pub destinations: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Whether the last check failed, so that a failure is only sent once
+    #[serde(default)]
+    pub failing: bool,
+    /// Fields of the stream seen by the last `schema_changed` check, as
+    /// `name:type`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub known_fields: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QualityCheck {
+    /// Fails when no record was ingested for the last `minutes`
+    IngestionStopped { minutes: i64 },
+    /// Fails when the field is null in more than `max_percent` of the records
+    /// of the last `minutes`
+    NullRatio {
+        field: String,
+        max_percent: f64,
+        minutes: i64,
+    },
+    /// Fails when fields were added to or removed from the stream schema
+    SchemaChanged,
+    /// Fails when a shipper which sent heartbeats for the stream in the last
+    /// day didn't send one for the last `minutes`
+    ShipperStopped { minutes: i64 },
+}
+
+impl std::fmt::Display for QualityCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QualityCheck::IngestionStopped { .. } => write!(f, "ingestion_stopped"),
+            QualityCheck::NullRatio { .. } => write!(f, "null_ratio"),
+            QualityCheck::SchemaChanged => write!(f, "schema_changed"),
+            QualityCheck::ShipperStopped { .. } => write!(f, "shipper_stopped"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Heartbeat {
+    /// Identity of the shipper, e.g. its host and agent name
+    pub shipper: String,
+    /// Other fields stored with the heartbeat, like the agent version
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub attributes: json::Map<String, json::Value>,
+}
+
+fn default_interval() -> i64 {
+    300
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -0,0 +1,129 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::str::FromStr;
+
+use config::utils::json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The objects whose changes are kept.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectType {
+    /// Id is `{folder}/{dashboard_id}`.
+    Dashboard,
+    /// Id is `{stream_type}/{stream_name}/{alert_name}`.
+    Alert,
+    /// Id is the function name.
+    Function,
+}
+
+impl ObjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::Dashboard => "dashboard",
+            ObjectType::Alert => "alert",
+            ObjectType::Function => "function",
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ObjectType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dashboard" | "dashboards" => Ok(ObjectType::Dashboard),
+            "alert" | "alerts" => Ok(ObjectType::Alert),
+            "function" | "functions" => Ok(ObjectType::Function),
+            _ => Err(anyhow::anyhow!(
+                "object type must be dashboard, alert or function"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionAction {
+    Saved,
+    Deleted,
+    Restored,
+}
+
+/// The state of an object after a change, deleted objects keep their last
+/// state so they can be restored.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct Revision {
+    /// Time of the change in microseconds, identifies the revision.
+    pub revision: i64,
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub author: String,
+    pub action: RevisionAction,
+    #[schema(value_type = Object)]
+    pub data: Value,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct RevisionInfo {
+    pub revision: i64,
+    pub object_type: ObjectType,
+    pub object_id: String,
+    pub author: String,
+    pub action: RevisionAction,
+}
+
+impl From<&Revision> for RevisionInfo {
+    fn from(r: &Revision) -> Self {
+        Self {
+            revision: r.revision,
+            object_type: r.object_type,
+            object_id: r.object_id.clone(),
+            author: r.author.clone(),
+            action: r.action,
+        }
+    }
+}
+
+/// Revisions, newest first.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct RevisionList {
+    pub list: Vec<RevisionInfo>,
+}
+
+/// A value which differs between two revisions, `path` is a json pointer.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct RevisionChange {
+    pub path: String,
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Value>,
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct RevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub changes: Vec<RevisionChange>,
+}
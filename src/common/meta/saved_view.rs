@@ -16,6 +16,12 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use super::dashboards::DEFAULT_FOLDER;
+
+fn default_folder() -> String {
+    DEFAULT_FOLDER.to_string()
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateViewRequest {
     /// Base64 encoded string, containing all the data for a given view.
@@ -25,6 +31,13 @@ pub struct CreateViewRequest {
 
     /// User-readable name of the view, doesn't need to be unique.
     pub view_name: String,
+
+    /// The dashboard folder the view is in.
+    #[serde(default = "default_folder")]
+    pub folder_id: String,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -36,6 +49,14 @@ pub struct UpdateViewRequest {
 
     /// User-readable name of the view, doesn't need to be unique.
     pub view_name: String,
+
+    /// Moves the view to this folder, it stays in its folder if unset.
+    #[serde(default)]
+    pub folder_id: Option<String>,
+
+    /// Replaces the tags of the view, they are kept if unset.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -44,6 +65,10 @@ pub struct View {
     pub data: serde_json::Value,
     pub view_id: String,
     pub view_name: String,
+    #[serde(default = "default_folder")]
+    pub folder_id: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 /// Save the bandwidth for a given view, without sending the actual data
@@ -53,6 +78,10 @@ pub struct ViewWithoutData {
     pub org_id: String,
     pub view_id: String,
     pub view_name: String,
+    #[serde(default = "default_folder")]
+    pub folder_id: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -0,0 +1,119 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    meta::{search, stream::StreamType},
+    utils::json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchJobStatus {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl SearchJobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            SearchJobStatus::Done | SearchJobStatus::Failed | SearchJobStatus::Cancelled
+        )
+    }
+}
+
+/// A search run in the background by a querier, its hits are written to the
+/// storage one time partition at a time and fetched page by page.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchJob {
+    pub id: String,
+    pub stream_type: StreamType,
+    #[schema(value_type = SearchRequest)]
+    pub request: search::Request,
+    #[serde(default)]
+    pub status: SearchJobStatus,
+    /// Querier running the job
+    #[serde(default)]
+    pub node: String,
+    /// Time partitions searched one after the other, microseconds
+    #[serde(default)]
+    pub partitions: Vec<[i64; 2]>,
+    /// Hits found in each of the partitions searched so far
+    #[serde(default)]
+    pub partition_hits: Vec<usize>,
+    #[serde(default)]
+    pub total: usize,
+    #[serde(default)]
+    pub scan_size: usize,
+    #[serde(default)]
+    pub scan_records: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub error: String,
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl SearchJob {
+    /// Percent of the partitions searched
+    pub fn progress(&self) -> f64 {
+        if self.status == SearchJobStatus::Done {
+            return 100.0;
+        }
+        if self.partitions.is_empty() {
+            return 0.0;
+        }
+        (self.partition_hits.len() * 100) as f64 / self.partitions.len() as f64
+    }
+}
+
+/// The job with its progress, returned by the status requests
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchJobState {
+    #[serde(flatten)]
+    pub job: SearchJob,
+    pub progress: f64,
+}
+
+impl From<SearchJob> for SearchJobState {
+    fn from(job: SearchJob) -> Self {
+        let progress = job.progress();
+        SearchJobState { job, progress }
+    }
+}
+
+/// A page of the hits found so far
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct SearchJobResults {
+    pub id: String,
+    pub status: SearchJobStatus,
+    pub progress: f64,
+    pub from: usize,
+    pub size: usize,
+    /// Hits found so far
+    pub total: usize,
+    #[schema(value_type = Vec<Object>)]
+    pub hits: Vec<json::Value>,
+}
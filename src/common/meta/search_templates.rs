@@ -0,0 +1,90 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::{meta::stream::StreamType, utils::json::Value};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A stored query invoked by name, the `{{param}}` placeholders of its SQL
+/// are replaced by the quoted values of the declared parameters.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct SearchTemplate {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub sql: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    #[serde(default)]
+    pub params: Vec<TemplateParam>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct TemplateParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub param_type: TemplateParamType,
+    /// Used when the parameter is not given, it is required without one.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateParamType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+}
+
+impl std::fmt::Display for TemplateParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateParamType::String => write!(f, "string"),
+            TemplateParamType::Number => write!(f, "number"),
+            TemplateParamType::Boolean => write!(f, "boolean"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct SearchTemplateList {
+    pub list: Vec<SearchTemplate>,
+}
+
+/// The parameters and the time range of a template invocation.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default)]
+pub struct SearchTemplateRequest {
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub params: HashMap<String, Value>,
+    pub start_time: i64,
+    /// Now when not set.
+    #[serde(default)]
+    pub end_time: i64,
+    #[serde(default)]
+    pub from: usize,
+    #[serde(default)]
+    pub size: Option<usize>,
+}
@@ -0,0 +1,25 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+
+/// A short link to a long url, like the search behind an alert notification.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShortUrl {
+    pub url: String,
+    pub created_at: i64,
+    /// The link stops resolving after this time, in microseconds.
+    pub expires_at: i64,
+}
@@ -0,0 +1,146 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Maps the fields of the Sigma rules of a log source to the fields of a
+/// stream. An empty `product`, `category` or `service` matches any value, the
+/// profile matching the most attributes of the `logsource` of a rule is used.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct MappingProfile {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub product: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub service: String,
+    /// Logs stream the alerts of the rules search.
+    pub stream_name: String,
+    /// Sigma field to stream field, the fields which aren't mapped are
+    /// formatted like the keys of the ingested records.
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+}
+
+impl MappingProfile {
+    /// How many attributes of the log source the profile matches, `None` when
+    /// it doesn't apply to it.
+    pub fn score(&self, product: &str, category: &str, service: &str) -> Option<usize> {
+        let mut score = 0;
+        for (want, got) in [
+            (&self.product, product),
+            (&self.category, category),
+            (&self.service, service),
+        ] {
+            if want.is_empty() {
+                continue;
+            }
+            if !want.eq_ignore_ascii_case(got) {
+                return None;
+            }
+            score += 1;
+        }
+        Some(score)
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct SigmaImportRequest {
+    /// Sigma rules as YAML documents separated by `---`. Detection rules
+    /// become scheduled SQL alerts, `event_count` correlations become
+    /// aggregating alerts and `temporal_ordered` ones correlation rules.
+    pub rules: String,
+    /// Alert destinations of the imported rules.
+    pub destinations: Vec<String>,
+    /// Levels imported, e.g. `high` and `critical`, all of them when empty.
+    #[serde(default)]
+    pub levels: Vec<String>,
+    /// Minutes searched by the alerts of the rules without a timeframe.
+    #[serde(default = "default_period")]
+    pub period: i64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Replaces the alerts and correlation rules of the same name.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+fn default_period() -> i64 {
+    5
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default)]
+pub struct SigmaImportResponse {
+    pub imported: Vec<ImportedRule>,
+    pub failed: Vec<FailedRule>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct ImportedRule {
+    pub title: String,
+    pub kind: ImportedKind,
+    /// Name of the alert or correlation rule created.
+    pub name: String,
+    pub stream_name: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportedKind {
+    Alert,
+    Correlation,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct FailedRule {
+    pub title: String,
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping_profile_score() {
+        let profile = MappingProfile {
+            name: "windows".to_string(),
+            description: "".to_string(),
+            product: "windows".to_string(),
+            category: "".to_string(),
+            service: "security".to_string(),
+            stream_name: "winlog".to_string(),
+            fields: HashMap::new(),
+        };
+        assert_eq!(profile.score("Windows", "", "security"), Some(2));
+        assert_eq!(
+            profile.score("windows", "process_creation", "security"),
+            Some(2)
+        );
+        assert_eq!(profile.score("windows", "", "sysmon"), None);
+        assert_eq!(profile.score("linux", "", "security"), None);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::meta::stream::StreamType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Stream of the org the evaluations of its SLOs are recorded into
+pub const SLO_HISTORY_STREAM: &str = "_slo_history";
+
+/// A service level objective over the events of a stream, the share of good
+/// events should stay above `target` percent over the last `window` days.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Slo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// SQL condition of the bad events, e.g. `status >= 500`
+    pub error_filter: String,
+    /// SQL condition of the events the SLI counts, all the events when empty
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub total_filter: String,
+    /// Percent of good events, e.g. 99.9
+    pub target: f64,
+    /// Days
+    #[serde(default = "default_window")]
+    pub window: i64,
+    /// Alert destinations notified when a burn rate rule starts firing
+    pub destinations: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Multi-window burn rate rules generated from the target and window
+    #[serde(default)]
+    pub rules: Vec<BurnRateRule>,
+    /// Rules which fired at the last evaluation, so that they are only
+    /// notified once
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub firing: Vec<String>,
+    /// End of the events the last evaluation recorded into the history, in
+    /// microseconds
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_evaluated_at: Option<i64>,
+}
+
+/// Fires when the error budget burns faster than `burn_rate` over both the
+/// long and the short window, the short window makes it stop soon after the
+/// errors stop.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BurnRateRule {
+    pub name: String,
+    /// `page` or `ticket`
+    pub severity: String,
+    /// Minutes
+    pub long_window: i64,
+    /// Minutes
+    pub short_window: i64,
+    pub burn_rate: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SloStatus {
+    pub name: String,
+    pub target: f64,
+    pub window: i64,
+    pub total: i64,
+    pub errors: i64,
+    /// Percent of good events over the window, 100 without events
+    pub sli: f64,
+    /// Share of the error budget left, negative once it is exhausted
+    pub error_budget_remaining: f64,
+    pub rules: Vec<BurnRateStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct BurnRateStatus {
+    pub name: String,
+    pub severity: String,
+    pub burn_rate: f64,
+    pub long_burn_rate: f64,
+    pub short_burn_rate: f64,
+    pub firing: bool,
+}
+
+/// Error budget and burn rates over time, one point per `interval`
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SloHistory {
+    pub name: String,
+    pub target: f64,
+    pub window: i64,
+    /// Seconds
+    pub interval: i64,
+    pub points: Vec<SloHistoryPoint>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SloHistoryPoint {
+    /// Start of the interval, in microseconds
+    pub timestamp: i64,
+    /// Events of the interval
+    pub total: i64,
+    /// Bad events of the interval
+    pub errors: i64,
+    /// Burn rate over the interval
+    pub burn_rate: f64,
+    /// Share of the error budget of the window ending with the interval
+    /// which is left
+    pub error_budget_remaining: f64,
+    /// Highest long window burn rate of each rule over the interval
+    #[serde(default)]
+    pub rules: HashMap<String, f64>,
+}
+
+fn default_window() -> i64 {
+    30
+}
+
+fn default_enabled() -> bool {
+    true
+}
@@ -0,0 +1,78 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Routes SNMP traps to a stream, matched by community (v1/v2c) or by the
+/// authoritative engine id of the sender (v3).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SnmpTrapRoute {
+    #[serde(default)]
+    pub org_id: String,
+    #[serde(default)]
+    pub stream_name: String,
+    #[serde(default)]
+    pub community: String,
+    /// Hex encoded engine id, e.g. `80001f8880e9630000d61ff449`
+    #[serde(default)]
+    pub engine_id: String,
+    /// The users allowed to send v3 traps with the engine id
+    #[serde(default)]
+    pub users: Vec<SnmpUser>,
+    #[serde(default)]
+    pub id: String,
+}
+
+/// An SNMPv3 user, its traps are authenticated with the key derived from the
+/// password and the engine id of the route.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SnmpUser {
+    pub name: String,
+    #[serde(default)]
+    pub auth_protocol: SnmpAuthProtocol,
+    pub auth_password: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SnmpAuthProtocol {
+    Md5,
+    #[default]
+    Sha,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SnmpTrapRoutes {
+    pub routes: Vec<SnmpTrapRoute>,
+}
+
+/// A MIB module in SMI text form, used to resolve trap and varbind OIDs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SnmpMib {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SnmpMibList {
+    pub list: Vec<String>,
+}
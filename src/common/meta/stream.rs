@@ -17,7 +17,9 @@ use std::sync::Arc;
 
 use arrow_schema::Field;
 use config::{
-    meta::stream::{StreamSettings, StreamStats, StreamType},
+    meta::stream::{
+        StreamOwner, StreamPartition, StreamQuotaUsage, StreamSettings, StreamStats, StreamType,
+    },
     utils::json,
 };
 use datafusion::arrow::datatypes::Schema;
@@ -37,6 +39,29 @@ pub struct Stream {
     pub settings: StreamSettings,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metrics_meta: Option<Metadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<StreamOwner>,
+    /// The owning user is no longer a member of the org.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub owner_stale: bool,
+    /// Usage of the quotas summed over the ingesters as of their last sync,
+    /// only for a stream with quotas.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_usage: Option<StreamQuotaUsage>,
+}
+
+/// A key of the ingested records changed by the normalization
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct KeyMapping {
+    /// The key as it was received
+    pub original: String,
+    /// The column the value is stored in, empty when the value was dropped
+    /// by a collision
+    pub key: String,
+    /// When the mapping was first seen, in microseconds
+    pub first_seen: i64,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -44,6 +69,15 @@ pub struct StreamProperty {
     pub name: String,
     #[serde(rename = "type")]
     pub prop_type: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub unit: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -59,11 +93,62 @@ pub struct StreamSchema {
     pub schema: Schema,
 }
 
+/// Stats of a stream over time, sizes are in MB like the current stats.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamStatsHistory {
+    pub name: String,
+    pub stream_type: StreamType,
+    pub points: Vec<StreamStatsPoint>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamStatsPoint {
+    pub timestamp: i64,
+    pub doc_num: i64,
+    pub file_num: i64,
+    pub storage_size: f64,
+    pub compressed_size: f64,
+}
+
+/// Difference between the stats computed from the file_list and the stored
+/// stats of a stream.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamStatsDrift {
+    pub stream: String,
+    pub doc_num: i64,
+    pub file_num: i64,
+    pub storage_size: f64,
+    pub compressed_size: f64,
+}
+
+impl StreamStatsDrift {
+    pub fn is_empty(&self) -> bool {
+        self.doc_num == 0
+            && self.file_num == 0
+            && self.storage_size == 0.0
+            && self.compressed_size == 0.0
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct ListStream {
     pub list: Vec<Stream>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListStreamGroups {
+    /// The label the streams are grouped by.
+    pub label: String,
+    pub groups: Vec<StreamGroup>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamGroup {
+    /// The value of the label, empty for the streams without the label.
+    pub value: String,
+    pub list: Vec<Stream>,
+}
+
 #[derive(Clone, Debug)]
 pub struct StreamParams {
     pub org_id: faststr::FastStr,
@@ -99,6 +184,278 @@ pub struct StreamDeleteFields {
     pub fields: Vec<String>,
 }
 
+/// Keys of the `_extra` column to promote to columns.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamPromoteFields {
+    pub fields: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamRename {
+    pub new_name: String,
+}
+
+/// Rename of a stream, the compactor owning the stream moves its files in the
+/// background and resumes after a restart.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamRenameJob {
+    pub new_name: String,
+    #[serde(default)]
+    pub created_at: i64,
+    /// Files already moved to the new stream
+    #[serde(default)]
+    pub moved_files: usize,
+    /// The new name was checked free and taken by the job, the schema found
+    /// under it is the one the job copied
+    #[serde(default)]
+    pub reserved: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamClone {
+    pub new_name: String,
+}
+
+/// Settings applied to many streams at once, picked by name or by a glob
+/// pattern like `k8s-*`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamSettingsBulk {
+    #[serde(default)]
+    pub streams: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    pub settings: StreamSettingsPatch,
+}
+
+/// Settings changed on every stream, the ones left out keep their value.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamSettingsPatch {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_text_search_keys: Option<Vec<String>>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_retention: Option<i64>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition_keys: Option<Vec<StreamPartition>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamSettingsBulkResult {
+    pub stream_name: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Records of a stream that are deleted before the compactor rewrites their
+/// files, searches skip them in the meantime.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Tombstone {
+    #[serde(default)]
+    pub id: String,
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive
+    pub end_time: i64,
+    /// SQL condition of the deleted records, e.g. `user_id = 'abc'`
+    #[serde(default)]
+    pub filter: String,
+    #[serde(default)]
+    pub created_at: i64,
+    /// The files before this time are already rewritten
+    #[serde(default)]
+    pub done_until: i64,
+}
+
+/// Time range of a stream whose files can't be deleted, by the retention or
+/// the compactor, until the hold is released.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct LegalHold {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive
+    pub end_time: i64,
+    /// Case or matter the data is held for
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+impl LegalHold {
+    /// Whether records between the two times, inclusive, are held.
+    pub fn covers(&self, min_ts: i64, max_ts: i64) -> bool {
+        max_ts >= self.start_time && min_ts < self.end_time
+    }
+}
+
+/// Files pinned by a legal hold.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct LegalHoldFiles {
+    pub hold: LegalHold,
+    pub files: Vec<String>,
+    pub records: i64,
+    pub compressed_size: i64,
+}
+
+/// Scan cost of a file, or of the files of a partition, over all the
+/// queriers.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FileScanStats {
+    /// File key, or partition path when grouped by partition
+    pub key: String,
+    pub files: usize,
+    pub searches: u64,
+    /// Milliseconds spent downloading the files into the cache
+    pub load_ms: u64,
+    /// Milliseconds the scans spent reading the files
+    pub read_ms: u64,
+    /// Compressed bytes read by the scans
+    pub bytes_read: u64,
+    /// Bytes decompressed by the scans, estimated from the compression ratio
+    pub decompressed_bytes: u64,
+}
+
+impl From<proto::cluster_rpc::ScanCost> for FileScanStats {
+    fn from(item: proto::cluster_rpc::ScanCost) -> Self {
+        Self {
+            key: item.key,
+            files: item.files as usize,
+            searches: item.searches,
+            load_ms: item.load_ms,
+            read_ms: item.read_ms,
+            bytes_read: item.bytes_read,
+            decompressed_bytes: item.decompressed_bytes,
+        }
+    }
+}
+
+impl From<FileScanStats> for proto::cluster_rpc::ScanCost {
+    fn from(item: FileScanStats) -> Self {
+        Self {
+            key: item.key,
+            files: item.files as u64,
+            searches: item.searches,
+            load_ms: item.load_ms,
+            read_ms: item.read_ms,
+            bytes_read: item.bytes_read,
+            decompressed_bytes: item.decompressed_bytes,
+        }
+    }
+}
+
+/// Rewrite of the historical files of a stream with the current settings.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RewriteJob {
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive
+    pub end_time: i64,
+    #[serde(default)]
+    pub created_at: i64,
+    /// The files before this time are already rewritten
+    #[serde(default)]
+    pub done_until: i64,
+    /// Percentage of the time range already rewritten
+    #[serde(default)]
+    pub progress: f64,
+    /// Splits the files into the current partition keys instead of merging
+    /// them, started when the partition keys change
+    #[serde(default)]
+    pub repartition: bool,
+    /// Files already split into the current partition keys
+    #[serde(default)]
+    pub repartitioned_files: usize,
+    /// Partition keys whose type changed, like the number of hash buckets, the
+    /// directories of their files name the key but may not match it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_keys: Vec<String>,
+    /// The partition keys changed during a requested rewrite, the files are
+    /// split into them once the rewrite is done
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub repartition_pending: bool,
+}
+
+/// Partition layout of a stream and the progress of moving the historical
+/// files to it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct RepartitionStatus {
+    /// Fields of the enabled partition keys, in the order of the directories
+    pub partition_keys: Vec<String>,
+    /// Whether historical files are still being moved to the current layout
+    pub in_progress: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<RewriteJob>,
+}
+
+/// Expired files of a stream moved to the archive bucket instead of being
+/// deleted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamArchive {
+    pub id: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// Microseconds, inclusive
+    pub start_time: i64,
+    /// Microseconds, exclusive
+    pub end_time: i64,
+    /// Files archived, they are listed by the manifest of the archive kept in
+    /// the archive bucket
+    pub files: usize,
+    pub records: i64,
+    pub original_size: i64,
+    pub compressed_size: i64,
+    pub created_at: i64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveRestoreRequest {
+    /// Stream the files are restored into, the archived stream when empty
+    #[serde(default)]
+    pub stream_name: Option<String>,
+}
+
+/// Archive restored into a stream, the restored range is kept by a legal hold
+/// until it is released.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveRestore {
+    pub stream_name: String,
+    pub files: usize,
+    pub records: i64,
+    pub legal_hold_id: String,
+}
+
+/// Place of a stream in the compaction queue.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct CompactPriority {
+    pub org_id: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    /// Manual priority, higher is compacted first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    pub query_count: u64,
+    pub small_file_ratio: f64,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CompactPriorityRequest {
+    pub priority: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,6 +468,20 @@ mod tests {
         assert_eq!(stats, stats_frm_str);
     }
 
+    #[test]
+    fn test_legal_hold_covers() {
+        let hold = LegalHold {
+            start_time: 100,
+            end_time: 200,
+            ..Default::default()
+        };
+        assert!(hold.covers(50, 100));
+        assert!(hold.covers(150, 160));
+        assert!(hold.covers(199, 300));
+        assert!(!hold.covers(50, 99));
+        assert!(!hold.covers(200, 300));
+    }
+
     #[test]
     fn test_stream_params() {
         let params = StreamParams::new("org_id", "stream_name", StreamType::Logs);
@@ -27,10 +27,50 @@ pub struct SyslogRoute {
     #[serde(default)]
     #[schema(value_type = Vec<String>)]
     pub subnets: Vec<IpNetwork>,
+    /// Glob patterns matched against the hostname of the messages, `*` matches
+    /// any characters. Only the messages from the subnets of the routes are
+    /// matched, a route matching the hostname wins over the other ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hostnames: Vec<String>,
     #[serde(default)]
     pub id: String,
 }
 
+impl SyslogRoute {
+    /// Whether the hostname matches one of the hostname patterns of the route.
+    pub fn matches_hostname(&self, hostname: &str) -> bool {
+        self.hostnames
+            .iter()
+            .any(|pattern| glob_match(&pattern.to_lowercase(), &hostname.to_lowercase()))
+    }
+}
+
+/// Matches `input` against a glob `pattern` where `*` matches any characters
+/// and `?` a single one.
+fn glob_match(pattern: &str, input: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let input = input.chars().collect::<Vec<_>>();
+    let (mut p, mut i) = (0, 0);
+    // position of the last `*` and the input position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while i < input.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == input[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, i));
+            p += 1;
+        } else if let Some((sp, si)) = star {
+            p = sp + 1;
+            i = si + 1;
+            star = Some((sp, si + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct SyslogRoutes {
     pub routes: Vec<SyslogRoute>,
@@ -41,3 +81,25 @@ pub struct SyslogRoutes {
 pub struct SyslogServer {
     pub state: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_hostname() {
+        let route = SyslogRoute {
+            org_id: "default".to_string(),
+            stream_name: "firewall".to_string(),
+            subnets: vec![],
+            hostnames: vec!["fw-*.corp".to_string(), "edge?".to_string()],
+            id: "".to_string(),
+        };
+        assert!(route.matches_hostname("fw-01.corp"));
+        assert!(route.matches_hostname("FW-02.Corp"));
+        assert!(route.matches_hostname("edge1"));
+        assert!(!route.matches_hostname("edge10"));
+        assert!(!route.matches_hostname("fw-01.corp.example"));
+        assert!(!route.matches_hostname("switch-01"));
+    }
+}
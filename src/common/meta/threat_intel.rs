@@ -0,0 +1,86 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Field set on the records hitting an indicator, the names of the lists hit.
+pub const THREAT_INTEL_MATCH_FIELD: &str = "threat_intel_match";
+/// Field set on the records hitting an indicator, the values which hit.
+pub const THREAT_INTEL_INDICATOR_FIELD: &str = "threat_intel_indicator";
+
+/// A list of indicators of compromise the ingested logs are checked against.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct IndicatorList {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub indicator_type: IndicatorType,
+    /// Indicators maintained through the API.
+    #[serde(default)]
+    pub indicators: Vec<String>,
+    /// Url of a feed with one indicator per line, `#` starts a comment. It is
+    /// fetched every `ZO_THREAT_INTEL_FEED_INTERVAL` in addition to the
+    /// indicators above.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub feed_url: String,
+    /// Fields checked, all the string fields when empty.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Logs streams checked, all of them when empty.
+    #[serde(default)]
+    pub streams: Vec<String>,
+    /// Alert destinations notified of the hits.
+    #[serde(default)]
+    pub destinations: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl IndicatorList {
+    pub fn applies_to(&self, stream_name: &str) -> bool {
+        self.enabled && (self.streams.is_empty() || self.streams.iter().any(|s| s == stream_name))
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorType {
+    /// Addresses or networks in CIDR notation.
+    Ip,
+    /// Domains, their subdomains hit too.
+    Domain,
+    /// File hashes, compared case insensitive.
+    Hash,
+}
+
+/// The body sent to the destinations of a list.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct IndicatorHit {
+    pub org_id: String,
+    pub list: String,
+    pub indicator_type: IndicatorType,
+    pub indicator: String,
+    pub stream_name: String,
+    pub field: String,
+    /// Time of the record in microseconds.
+    pub timestamp: i64,
+}
@@ -0,0 +1,115 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::{meta::stream::StreamType, utils::json::Value};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// An org level subscription to the stream lifecycle events, every event is
+/// POSTed as a [StreamEvent] to the url.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct Webhook {
+    #[serde(default)]
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Events sent to the url, all of them when empty.
+    #[serde(default)]
+    pub events: Vec<StreamEventType>,
+    #[serde(default)]
+    pub skip_tls_verify: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Webhook {
+    pub fn subscribes(&self, event: StreamEventType) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEventType {
+    StreamCreated,
+    StreamDeleted,
+    SchemaChanged,
+    RetentionChanged,
+    QuotaExceeded,
+    AlertChanged,
+}
+
+impl StreamEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEventType::StreamCreated => "stream_created",
+            StreamEventType::StreamDeleted => "stream_deleted",
+            StreamEventType::SchemaChanged => "schema_changed",
+            StreamEventType::RetentionChanged => "retention_changed",
+            StreamEventType::QuotaExceeded => "quota_exceeded",
+            StreamEventType::AlertChanged => "alert_changed",
+        }
+    }
+}
+
+impl std::fmt::Display for StreamEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The body sent to the webhooks.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct StreamEvent {
+    pub event: StreamEventType,
+    pub org_id: String,
+    /// Not set by the org wide events, like an exceeded quota.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_type: Option<StreamType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_name: Option<String>,
+    /// Time of the event in microseconds.
+    pub timestamp: i64,
+    /// What changed, depends on the event.
+    #[serde(default)]
+    pub details: Value,
+}
+
+impl StreamEvent {
+    pub fn new(
+        event: StreamEventType,
+        org_id: &str,
+        stream_type: StreamType,
+        stream_name: &str,
+        details: Value,
+    ) -> Self {
+        Self {
+            event,
+            org_id: org_id.to_string(),
+            stream_type: Some(stream_type),
+            stream_name: Some(stream_name.to_string()),
+            timestamp: chrono::Utc::now().timestamp_micros(),
+            details,
+        }
+    }
+}
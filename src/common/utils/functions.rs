@@ -19,23 +19,55 @@ use config::{GEO_IP_ASN_ENRICHMENT_TABLE, GEO_IP_CITY_ENRICHMENT_TABLE};
 use vector_enrichment::{Table, TableRegistry};
 
 use crate::common::{
-    infra::config::{ENRICHMENT_TABLES, GEOIP_ASN_TABLE, GEOIP_CITY_TABLE},
-    meta::{functions::VRLCompilerConfig, organization::DEFAULT_ORG},
+    infra::config::{ENRICHMENT_TABLES, GEOIP_ASN_TABLE, GEOIP_CITY_TABLE, QUERY_FUNCTIONS},
+    meta::{
+        functions::{Transform, VRLCompilerConfig, GLOBAL_FUNCTIONS_ORG},
+        organization::DEFAULT_ORG,
+    },
 };
 
 pub async fn get_all_transform_keys(org_id: &str) -> Vec<String> {
-    let org_key = &format!("{}/", org_id);
-
-    crate::common::infra::config::QUERY_FUNCTIONS
-        .clone()
-        .iter()
-        .filter_map(|transform| {
-            let key = transform.key();
-            key.strip_prefix(org_key).map(|x| x.to_string())
-        })
+    get_all_transforms(org_id)
+        .into_iter()
+        .map(|transform| transform.name)
         .collect()
 }
 
+/// The query functions usable in an org, its own functions take precedence
+/// over the global functions with the same name.
+pub fn get_all_transforms(org_id: &str) -> Vec<Transform> {
+    let org_key = format!("{org_id}/");
+    let global_key = format!("{GLOBAL_FUNCTIONS_ORG}/");
+    let mut transforms = HashMap::new();
+    let mut global_transforms = HashMap::new();
+    for transform in QUERY_FUNCTIONS.clone().iter() {
+        let key = transform.key();
+        if let Some(name) = key.strip_prefix(&org_key) {
+            // references to global functions are resolved below
+            if !transform.global {
+                transforms.insert(name.to_string(), transform.value().clone());
+            }
+        } else if let Some(name) = key.strip_prefix(&global_key) {
+            global_transforms.insert(name.to_string(), transform.value().clone());
+        }
+    }
+    for (name, transform) in global_transforms {
+        transforms.entry(name).or_insert(transform);
+    }
+    transforms.into_values().collect()
+}
+
+/// The body of a stream function, a reference to a global function resolves to
+/// the current definition of the global function.
+pub fn resolve_function(transform: &Transform) -> Option<String> {
+    if !transform.global {
+        return Some(transform.function.clone());
+    }
+    QUERY_FUNCTIONS
+        .get(&format!("{GLOBAL_FUNCTIONS_ORG}/{}", transform.name))
+        .map(|global| global.function.clone())
+}
+
 pub fn init_vrl_runtime() -> vrl::compiler::runtime::Runtime {
     vrl::compiler::runtime::Runtime::new(vrl::prelude::state::RuntimeState::default())
 }
@@ -56,6 +56,27 @@ pub(crate) fn get_folder(query: &Query<HashMap<String, String>>) -> String {
     }
 }
 
+/// The comma separated `tags` query param.
+#[inline(always)]
+pub(crate) fn get_tags(query: &Query<HashMap<String, String>>) -> Vec<String> {
+    match query.get("tags") {
+        Some(s) => crate::common::meta::dashboards::normalize_tags(
+            s.split(',').map(|v| v.to_string()).collect(),
+        ),
+        None => vec![],
+    }
+}
+
+/// The user making the request, set by the auth middleware.
+#[inline(always)]
+pub(crate) fn get_user_id(headers: &HeaderMap) -> String {
+    headers
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
 // Extractor for request headers
 pub struct RequestHeaderExtractor<'a> {
     headers: &'a HeaderMap,
@@ -18,4 +18,5 @@ pub mod functions;
 pub mod http;
 pub mod jwt;
 pub mod stream;
+pub mod synthetic;
 pub mod zo_logger;
@@ -0,0 +1,181 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generators of realistic looking records, used to load test a cluster
+//! and to fill the sample streams.
+
+use config::utils::json::{self, Value};
+use rand::{seq::SliceRandom, Rng};
+
+const HOSTS: [&str; 4] = ["web-1", "web-2", "web-3", "web-4"];
+const METHODS: [&str; 4] = ["GET", "GET", "POST", "PUT"];
+const PATHS: [&str; 6] = [
+    "/",
+    "/api/orders",
+    "/api/users",
+    "/api/cart",
+    "/static/app.js",
+    "/login",
+];
+const AGENTS: [&str; 3] = [
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 Chrome/120.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_2) AppleWebKit/605.1.15 Safari/605.1.15",
+    "curl/8.4.0",
+];
+const SERVICES: [&str; 4] = ["frontend", "checkout", "cart", "payment"];
+
+/// A web server access log, timestamp in microseconds.
+pub fn web_log<R: Rng>(rng: &mut R, ts: i64) -> Value {
+    let status = *[200, 200, 200, 200, 201, 304, 404, 500]
+        .choose(rng)
+        .unwrap();
+    let method = *METHODS.choose(rng).unwrap();
+    let path = *PATHS.choose(rng).unwrap();
+    let bytes = rng.gen_range(200..20_000);
+    let ip = format!(
+        "10.{}.{}.{}",
+        rng.gen_range(0..255),
+        rng.gen_range(0..255),
+        rng.gen_range(1..255)
+    );
+    json::json!({
+        "_timestamp": ts,
+        "host": HOSTS.choose(rng).unwrap(),
+        "client_ip": ip,
+        "method": method,
+        "path": path,
+        "status": status,
+        "bytes": bytes,
+        "took_ms": rng.gen_range(1..if status >= 500 { 3000 } else { 300 }),
+        "user_agent": AGENTS.choose(rng).unwrap(),
+        "log": format!("{ip} - - \"{method} {path} HTTP/1.1\" {status} {bytes}"),
+    })
+}
+
+/// A log line of a container running in kubernetes, timestamp in microseconds.
+pub fn k8s_log<R: Rng>(rng: &mut R, ts: i64) -> Value {
+    let service = *SERVICES.choose(rng).unwrap();
+    let (level, message) = *[
+        ("info", "request completed"),
+        ("info", "request completed"),
+        ("info", "cache refreshed"),
+        ("warn", "slow downstream response"),
+        ("error", "connection reset by peer"),
+    ]
+    .choose(rng)
+    .unwrap();
+    json::json!({
+        "_timestamp": ts,
+        "kubernetes_namespace_name": "shop",
+        "kubernetes_pod_name": format!("{service}-{}", hex_id(rng, 3)),
+        "kubernetes_container_name": service,
+        "kubernetes_host": format!("node-{}", rng.gen_range(1..4)),
+        "level": level,
+        "log": format!("level={level} service={service} msg=\"{message}\""),
+    })
+}
+
+/// Gauges and counters of the web servers, one record per host.
+pub fn metrics<R: Rng>(rng: &mut R, ts: i64) -> Vec<Value> {
+    let mut records = Vec::with_capacity(HOSTS.len() * 2);
+    for host in HOSTS {
+        records.push(json::json!({
+            "__name__": "cpu_usage",
+            "__type__": "gauge",
+            "host": host,
+            "_timestamp": ts,
+            "value": rng.gen_range(0.05..0.95),
+        }));
+        records.push(json::json!({
+            "__name__": "http_requests_total",
+            "__type__": "counter",
+            "host": host,
+            "_timestamp": ts,
+            "value": (ts / 1_000_000) as f64 + rng.gen_range(0.0..100.0),
+        }));
+    }
+    records
+}
+
+/// An OTLP/JSON export request with `traces` traces of a few spans each.
+pub fn traces<R: Rng>(rng: &mut R, ts: i64, traces: usize) -> Value {
+    let mut resource_spans = Vec::with_capacity(traces);
+    for _ in 0..traces {
+        let trace_id = hex_id(rng, 16);
+        let mut parent_id = String::new();
+        let mut start = ts * 1000;
+        let mut spans = vec![];
+        for service in SERVICES.iter().take(rng.gen_range(1..=SERVICES.len())) {
+            let span_id = hex_id(rng, 8);
+            let duration = rng.gen_range(100_000..50_000_000_i64);
+            spans.push((
+                *service,
+                json::json!({
+                    "traceId": trace_id,
+                    "spanId": span_id,
+                    "parentSpanId": parent_id,
+                    "name": format!("{service} handle request"),
+                    "kind": 2,
+                    "startTimeUnixNano": start.to_string(),
+                    "endTimeUnixNano": (start + duration).to_string(),
+                    "attributes": [],
+                    "status": {"code": if rng.gen_bool(0.02) { 2 } else { 1 }},
+                }),
+            ));
+            parent_id = span_id;
+            start += duration / 10;
+        }
+        for (service, span) in spans {
+            resource_spans.push(json::json!({
+                "resource": {
+                    "attributes": [{"key": "service.name", "value": {"stringValue": service}}]
+                },
+                "scopeSpans": [{"scope": {}, "spans": [span]}],
+            }));
+        }
+    }
+    json::json!({ "resourceSpans": resource_spans })
+}
+
+fn hex_id<R: Rng>(rng: &mut R, bytes: usize) -> String {
+    (0..bytes)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_records() {
+        let mut rng = rand::thread_rng();
+        let log = web_log(&mut rng, 1_700_000_000_000_000);
+        assert_eq!(log["_timestamp"], 1_700_000_000_000_000_i64);
+        assert!(log["status"].as_i64().is_some());
+        let log = k8s_log(&mut rng, 1_700_000_000_000_000);
+        assert!(log["kubernetes_pod_name"].as_str().is_some());
+
+        let metrics = metrics(&mut rng, 1_700_000_000_000_000);
+        assert_eq!(metrics.len(), HOSTS.len() * 2);
+
+        let traces = traces(&mut rng, 1_700_000_000_000_000, 3);
+        let spans = traces["resourceSpans"].as_array().unwrap();
+        assert!(spans.len() >= 3);
+        let span = &spans[0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(span["parentSpanId"], "");
+    }
+}
@@ -259,7 +259,11 @@ pub static SMTP_CLIENT: Lazy<Option<AsyncSmtpTransport<Tokio1Executor>>> = Lazy:
     if !CONFIG.smtp.smtp_enabled {
         None
     } else {
-        let tls_parameters = TlsParameters::new(CONFIG.smtp.smtp_host.clone()).unwrap();
+        let tls_parameters = TlsParameters::builder(CONFIG.smtp.smtp_host.clone())
+            .dangerous_accept_invalid_certs(CONFIG.smtp.smtp_skip_tls_verify)
+            .dangerous_accept_invalid_hostnames(CONFIG.smtp.smtp_skip_tls_verify)
+            .build()
+            .unwrap();
         let mut transport_builder =
             AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&CONFIG.smtp.smtp_host)
                 .port(CONFIG.smtp.smtp_port);
@@ -302,9 +306,15 @@ pub struct Config {
     pub nats: Nats,
     pub s3: S3,
     pub tcp: TCP,
+    pub netflow: Netflow,
+    pub kafka: Kafka,
+    pub snmp: Snmp,
+    pub monitors: Monitors,
     pub prom: Prometheus,
+    pub loki: Loki,
     pub profiling: Pyroscope,
     pub smtp: Smtp,
+    pub slack: Slack,
     pub rum: RUM,
     pub chrome: Chrome,
     pub tokio_console: TokioConsole,
@@ -356,8 +366,30 @@ pub struct Smtp {
     pub smtp_reply_to: String,
     #[env_config(name = "ZO_SMTP_FROM_EMAIL", default = "")]
     pub smtp_from_email: String,
-    #[env_config(name = "ZO_SMTP_ENCRYPTION", default = "")]
+    #[env_config(
+        name = "ZO_SMTP_ENCRYPTION",
+        default = "",
+        help = "starttls, ssltls for implicit TLS, or empty for none"
+    )]
     pub smtp_encryption: String,
+    #[env_config(
+        name = "ZO_SMTP_SKIP_TLS_VERIFY",
+        default = false,
+        help = "Accept invalid certificates of the SMTP server"
+    )]
+    pub smtp_skip_tls_verify: bool,
+}
+
+#[derive(EnvConfig)]
+pub struct Slack {
+    #[env_config(
+        name = "ZO_SLACK_SIGNING_SECRET",
+        default = "",
+        help = "Signing secret of the Slack app of the Slack destinations without one, enables the buttons of their alert notifications"
+    )]
+    pub signing_secret: String,
+    #[env_config(name = "ZO_SLACK_API_URL", default = "https://slack.com/api")]
+    pub api_url: String,
 }
 
 #[derive(EnvConfig)]
@@ -385,6 +417,12 @@ pub struct Auth {
     pub cookie_same_site_lax: bool,
     #[env_config(name = "ZO_COOKIE_SECURE_ONLY", default = false)]
     pub cookie_secure_only: bool,
+    #[env_config(
+        name = "ZO_ENCRYPTION_MASTER_KEY",
+        default = "",
+        help = "Base64 of the 32 bytes key wrapping the org keys of the encrypted fields, required to encrypt fields"
+    )]
+    pub encryption_master_key: String,
 }
 
 #[derive(EnvConfig)]
@@ -423,6 +461,94 @@ pub struct TCP {
     pub tcp_port: u16,
     #[env_config(name = "ZO_UDP_PORT", default = 5514)]
     pub udp_port: u16,
+    #[env_config(name = "ZO_TCP_TLS_PORT", default = 6514)]
+    pub tls_port: u16,
+    #[env_config(
+        name = "ZO_TCP_TLS_CERT_PATH",
+        default = "",
+        help = "PEM certificate chain of the syslog TLS listener, the listener is started when both cert and key are set"
+    )]
+    pub tls_cert_path: String,
+    #[env_config(name = "ZO_TCP_TLS_KEY_PATH", default = "")]
+    pub tls_key_path: String,
+    #[env_config(name = "ZO_TCP_MAX_FRAME_SIZE", default = 65536)] // bytes
+    pub max_frame_size: usize,
+}
+
+#[derive(EnvConfig)]
+pub struct Netflow {
+    #[env_config(name = "ZO_NETFLOW_ENABLED", default = false)]
+    pub enabled: bool,
+    #[env_config(name = "ZO_NETFLOW_PORT", default = 2055)]
+    pub port: u16,
+    #[env_config(name = "ZO_NETFLOW_ORG", default = "default")]
+    pub org_id: String,
+    #[env_config(name = "ZO_NETFLOW_STREAM", default = "netflow")]
+    pub stream_name: String,
+    #[env_config(name = "ZO_NETFLOW_TEMPLATE_TTL", default = 1800)] // seconds
+    pub template_ttl: i64,
+    #[env_config(
+        name = "ZO_NETFLOW_MAX_TEMPLATES",
+        default = 10000,
+        help = "Maximum number of templates cached over all the exporters"
+    )]
+    pub max_templates: usize,
+}
+
+#[derive(EnvConfig)]
+pub struct Kafka {
+    #[env_config(
+        name = "ZO_KAFKA_CONSUMER_ENABLED",
+        default = false,
+        help = "Consume the kafka sources of the organizations on the ingesters"
+    )]
+    pub consumer_enabled: bool,
+    #[env_config(name = "ZO_KAFKA_POLL_INTERVAL", default = 5)] // seconds
+    pub poll_interval: u64,
+    #[env_config(name = "ZO_KAFKA_MAX_FETCH_BYTES", default = 1048576)]
+    pub max_fetch_bytes: i32,
+    #[env_config(name = "ZO_KAFKA_MAX_WAIT_MS", default = 500)]
+    pub max_wait_ms: i32,
+    #[env_config(
+        name = "ZO_KAFKA_POLL_CONCURRENCY",
+        default = 8,
+        help = "Partitions consumed at the same time by an ingester"
+    )]
+    pub poll_concurrency: usize,
+    #[env_config(
+        name = "ZO_KAFKA_MAX_POLL_SECS",
+        default = 60,
+        help = "Time a partition is consumed for before the lock is released, it stops earlier at the end of the partition"
+    )]
+    pub max_poll_secs: u64,
+    #[env_config(
+        name = "ZO_KAFKA_MAX_ATTEMPTS",
+        default = 5,
+        help = "Rejected ingestions of a batch before it is skipped, 0 retries forever"
+    )]
+    pub max_attempts: u32,
+}
+
+#[derive(EnvConfig)]
+pub struct Snmp {
+    #[env_config(name = "ZO_SNMP_TRAP_ENABLED", default = false)]
+    pub trap_enabled: bool,
+    #[env_config(name = "ZO_SNMP_TRAP_PORT", default = 1162)]
+    pub trap_port: u16,
+}
+
+#[derive(EnvConfig)]
+pub struct Monitors {
+    #[env_config(name = "ZO_MONITORS_ENABLED", default = false)]
+    pub enabled: bool,
+    #[env_config(
+        name = "ZO_MONITORS_REGION",
+        default = "",
+        help = "Region of this node, monitors limited to other regions are skipped"
+    )]
+    pub region: String,
+    #[env_config(name = "ZO_MONITORS_MIN_INTERVAL", default = 10)] // seconds
+    pub min_interval: i64,
 }
 
 #[derive(EnvConfig)]
@@ -466,6 +592,12 @@ pub struct Common {
     pub web_url: String,
     #[env_config(name = "ZO_BASE_URI", default = "")] // /abc
     pub base_uri: String,
+    #[env_config(
+        name = "ZO_SHORT_URL_RETENTION_DAYS",
+        default = 30,
+        help = "Days the short links of alert notifications keep resolving"
+    )]
+    pub short_url_retention_days: i64,
     #[env_config(name = "ZO_DATA_DIR", default = "./data/openobserve/")]
     pub data_dir: String,
     #[env_config(name = "ZO_DATA_WAL_DIR", default = "")] // ./data/openobserve/wal/
@@ -484,6 +616,12 @@ pub struct Common {
     pub wal_line_mode_enabled: bool,
     #[env_config(name = "ZO_COLUMN_TIMESTAMP", default = "_timestamp")]
     pub column_timestamp: String,
+    #[env_config(
+        name = "ZO_RESERVED_FIELD_PREFIX",
+        default = "user",
+        help = "Prefix of ingested fields named like a system column, _id is stored as user_id"
+    )]
+    pub reserved_field_prefix: String,
     #[env_config(name = "ZO_WIDENING_SCHEMA_EVOLUTION", default = true)]
     pub widening_schema_evolution: bool,
     #[env_config(name = "ZO_SKIP_SCHEMA_VALIDATION", default = false)]
@@ -496,6 +634,12 @@ pub struct Common {
     pub feature_distinct_extra_fields: String,
     #[env_config(name = "ZO_FEATURE_QUICK_MODE_FIELDS", default = "")]
     pub feature_quick_mode_fields: String,
+    #[env_config(
+        name = "ZO_QUERY_HTTP_LOOKUP_HOSTS",
+        default = "",
+        help = "Hosts the http_lookup function can call, split by comma, empty disables the function"
+    )]
+    pub query_http_lookup_hosts: String,
     #[env_config(name = "ZO_FEATURE_FILELIST_DEDUP_ENABLED", default = false)]
     pub feature_filelist_dedup_enabled: bool,
     #[env_config(name = "ZO_FEATURE_QUERY_QUEUE_ENABLED", default = true)]
@@ -514,6 +658,10 @@ pub struct Common {
     pub bloom_filter_enabled: bool,
     #[env_config(name = "ZO_BLOOM_FILTER_DISABLED_ON_SEARCH", default = false)]
     pub bloom_filter_disabled_on_search: bool,
+    #[env_config(name = "ZO_HISTOGRAM_SKETCH_ENABLED", default = true)]
+    pub histogram_sketch_enabled: bool,
+    #[env_config(name = "ZO_HISTOGRAM_SKETCH_SEVERITY_FIELD", default = "severity")]
+    pub histogram_sketch_severity_field: String,
     #[env_config(name = "ZO_BLOOM_FILTER_ON_ALL_FIELDS", default = false)]
     pub bloom_filter_on_all_fields: bool,
     #[env_config(name = "ZO_BLOOM_FILTER_DEFAULT_FIELDS", default = "")]
@@ -566,6 +714,16 @@ pub struct Common {
     pub usage_reporting_creds: String,
     #[env_config(name = "ZO_USAGE_BATCH_SIZE", default = 2000)]
     pub usage_batch_size: usize,
+    #[env_config(
+        name = "ZO_CDC_ENABLED",
+        default = false,
+        help = "Write the changes of schemas, stream settings, alerts and users into the _cdc stream of each org"
+    )]
+    pub cdc_enabled: bool,
+    #[env_config(name = "ZO_CDC_BATCH_SIZE", default = 100)]
+    pub cdc_batch_size: usize,
+    #[env_config(name = "ZO_CDC_FLUSH_INTERVAL", default = 10)] // seconds
+    pub cdc_flush_interval: u64,
     #[env_config(name = "ZO_MMDB_DATA_DIR")] // ./data/openobserve/mmdb/
     pub mmdb_data_dir: String,
     #[env_config(name = "ZO_MMDB_DISABLE_DOWNLOAD", default = "false")]
@@ -588,7 +746,7 @@ pub struct Common {
     )]
     pub mmdb_geolite_citydb_sha256_url: String,
     #[env_config(
-        name = "ZO_MMDB_GEOLITE_CITYDB_SHA256_URL",
+        name = "ZO_MMDB_GEOLITE_ASNDB_SHA256_URL",
         default = "https://geoip.zinclabs.dev/GeoLite2-ASN.sha256"
     )]
     pub mmdb_geolite_asndb_sha256_url: String,
@@ -639,6 +797,32 @@ pub struct Common {
     pub report_user_password: String,
     #[env_config(name = "ZO_CONCATENATED_SCHEMA_FIELD_NAME", default = "_all")]
     pub all_fields_name: String,
+    #[env_config(
+        name = "ZO_FEATURES_DISABLED",
+        default = "",
+        help = "Features disabled unless a superuser enables them for an organization, use comma to split"
+    )]
+    pub features_disabled: String,
+    #[env_config(
+        name = "ZO_SAMPLE_DATA_ENABLED",
+        default = false,
+        help = "Create sample streams, dashboards and alerts and keep generating data into them"
+    )]
+    pub sample_data_enabled: bool,
+    #[env_config(name = "ZO_SAMPLE_DATA_ORG", default = "default")]
+    pub sample_data_org: String,
+    #[env_config(
+        name = "ZO_SAMPLE_DATA_INTERVAL",
+        default = 10,
+        help = "Seconds between two batches of sample data"
+    )]
+    pub sample_data_interval: u64,
+    #[env_config(
+        name = "ZO_SAMPLE_DATA_RECORDS",
+        default = 100,
+        help = "Records per sample log stream in a batch"
+    )]
+    pub sample_data_records: usize,
 }
 
 #[derive(EnvConfig)]
@@ -682,12 +866,100 @@ pub struct Limit {
     pub query_timeout: u64,
     #[env_config(name = "ZO_QUERY_FULL_MODE_LIMIT", default = 1000)]
     pub query_full_mode_limit: usize,
+    #[env_config(
+        name = "ZO_QUERY_DEDUP_MAX_HITS",
+        default = 10000,
+        help = "Maximum number of hits a dedup query collapses before paging them"
+    )]
+    pub query_dedup_max_hits: usize,
     #[env_config(name = "ZO_QUERY_PARTITION_BY_SECS", default = 10)] // seconds
     pub query_partition_by_secs: usize,
     #[env_config(name = "ZO_QUERY_PARTITION_MIN_SECS", default = 600)] // seconds
     pub query_partition_min_secs: i64,
     #[env_config(name = "ZO_QUERY_GROUP_BASE_SPEED", default = 1024)] // MB/s/core
     pub query_group_base_speed: usize,
+    #[env_config(
+        name = "ZO_QUERY_HOT_DATA_DAYS",
+        default = 0,
+        help = "Days of data searched by default, older data is only searched with tiers=all, 0 searches all the data"
+    )] // days
+    pub query_hot_data_days: i64,
+    #[env_config(
+        name = "ZO_QUERY_ESTIMATE_PARTITIONS",
+        default = 3,
+        help = "Hours sampled to estimate the total hits of a limited query, 0 disables the estimate"
+    )]
+    pub query_estimate_partitions: usize,
+    #[env_config(
+        name = "ZO_QUERY_PARTIAL_CHUNK_FILES",
+        default = 50,
+        help = "Files searched between checkpoints by queries returning partial results on timeout"
+    )]
+    pub query_partial_chunk_files: usize,
+    #[env_config(
+        name = "ZO_QUERY_FILE_STATS_MAX_FILES",
+        default = 10000,
+        help = "Files whose scan costs are kept by each querier, 0 disables the stats"
+    )]
+    pub query_file_stats_max_files: usize,
+    #[env_config(
+        name = "ZO_QUERY_STREAM_PAGE_SIZE",
+        default = 10000,
+        help = "Hits searched at a time by streaming searches"
+    )]
+    pub query_stream_page_size: usize,
+    #[env_config(
+        name = "ZO_QUERY_RESULT_CACHE_ENABLED",
+        default = false,
+        help = "Cache the complete histogram buckets of the aggregation queries, only the new buckets are searched again"
+    )]
+    pub query_result_cache_enabled: bool,
+    #[env_config(
+        name = "ZO_QUERY_RESULT_CACHE_MAX_ENTRIES",
+        default = 1000,
+        help = "Queries whose results are cached by each node, the least recently used are dropped"
+    )]
+    pub query_result_cache_max_entries: usize,
+    #[env_config(
+        name = "ZO_QUERY_RESULT_CACHE_DELAY",
+        default = 5,
+        help = "Buckets newer than these minutes are not cached, their data may still be ingested"
+    )] // minutes
+    pub query_result_cache_delay: i64,
+    #[env_config(
+        name = "ZO_SEARCH_JOB_CHECK_INTERVAL",
+        default = 10,
+        help = "Seconds between two checks of the queriers for pending search jobs, 0 disables the search jobs"
+    )]
+    pub search_job_check_interval: u64,
+    #[env_config(
+        name = "ZO_SEARCH_JOB_MAX_HITS",
+        default = 1000000,
+        help = "Hits kept by a search job at most"
+    )]
+    pub search_job_max_hits: usize,
+    #[env_config(
+        name = "ZO_SEARCH_JOB_RETENTION",
+        default = 72,
+        help = "Hours the finished search jobs and their hits are kept"
+    )]
+    pub search_job_retention: i64,
+    #[env_config(
+        name = "ZO_SEARCH_JOB_MAX_CONCURRENT",
+        default = 2,
+        help = "Search jobs run by each querier at the same time at most"
+    )]
+    pub search_job_max_concurrent: usize,
+    #[env_config(
+        name = "ZO_SEARCH_JOB_MAX_SORTED_HITS",
+        default = 100000,
+        help = "Hits kept by a search job which can't be split by time, an aggregation or a query ordered by another field than the timestamp, its hits are searched at once"
+    )]
+    pub search_job_max_sorted_hits: usize,
+    #[env_config(name = "ZO_QUERY_HTTP_LOOKUP_TIMEOUT", default = 5)] // seconds
+    pub query_http_lookup_timeout: u64,
+    #[env_config(name = "ZO_QUERY_HTTP_LOOKUP_CACHE_TTL", default = 300)] // seconds
+    pub query_http_lookup_cache_ttl: i64,
     #[env_config(name = "ZO_INGEST_ALLOWED_UPTO", default = 5)] // in hours - in past
     pub ingest_allowed_upto: i64,
     #[env_config(name = "ZO_INGEST_FLATTEN_LEVEL", default = 3)] // default flatten level
@@ -714,8 +986,24 @@ pub struct Limit {
     pub http_worker_max_blocking: usize,
     #[env_config(name = "ZO_CALCULATE_STATS_INTERVAL", default = 600)] // seconds
     pub calculate_stats_interval: u64,
+    #[env_config(name = "ZO_STATS_HISTORY_INTERVAL", default = 3600)] // seconds, 0 disables
+    pub stats_history_interval: u64,
+    #[env_config(name = "ZO_STATS_HISTORY_RETENTION_DAYS", default = 90)]
+    pub stats_history_retention_days: i64,
+    #[env_config(name = "ZO_STATS_REBUILD_INTERVAL", default = 86400)] // seconds, 0 disables
+    pub stats_rebuild_interval: u64,
+    #[env_config(name = "ZO_LARGE_FIELD_PREVIEW_SIZE", default = 1024)] // bytes
+    pub large_field_preview_size: usize,
     #[env_config(name = "ZO_ENRICHMENT_TABLE_LIMIT", default = 10)] // size in mb
     pub enrichment_table_limit: usize,
+    #[env_config(
+        name = "ZO_ENRICHMENT_SOURCE_CHECK_INTERVAL",
+        default = 60,
+        help = "Seconds between two checks for enrichment tables whose source url is due for a refresh, 0 disables the refreshes"
+    )]
+    pub enrichment_source_check_interval: u64,
+    #[env_config(name = "ZO_ENRICHMENT_SOURCE_TIMEOUT", default = 120)] // seconds
+    pub enrichment_source_timeout: u64,
     #[env_config(name = "ZO_ACTIX_REQ_TIMEOUT", default = 30)] // seconds
     pub request_timeout: u64,
     #[env_config(name = "ZO_ACTIX_KEEP_ALIVE", default = 30)] // seconds
@@ -724,6 +1012,76 @@ pub struct Limit {
     pub shutdown_timeout: u64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_INTERVAL", default = 60)] // seconds
     pub alert_schedule_interval: i64,
+    #[env_config(
+        name = "ZO_REVISIONS_MAX",
+        default = 20,
+        help = "Revisions kept of each dashboard, alert and function, 0 disables the history"
+    )]
+    pub revisions_max: usize,
+    #[env_config(
+        name = "ZO_STREAM_OWNER_CHECK_INTERVAL",
+        default = 3600,
+        help = "Seconds between two checks for streams whose owner no longer exists, 0 disables the check"
+    )]
+    pub stream_owner_check_interval: u64,
+    #[env_config(
+        name = "ZO_REPROCESS_CHECK_INTERVAL",
+        default = 10,
+        help = "Seconds between two checks of the ingesters for pending reprocess jobs, 0 disables reprocessing"
+    )]
+    pub reprocess_check_interval: u64,
+    #[env_config(
+        name = "ZO_REPROCESS_BATCH_SIZE",
+        default = 1000,
+        help = "Records fetched per search by a reprocess job"
+    )]
+    pub reprocess_batch_size: i64,
+    #[env_config(name = "ZO_WEBHOOK_TIMEOUT", default = 10)] // seconds
+    pub webhook_timeout: u64,
+    #[env_config(
+        name = "ZO_WEBHOOK_QUOTA_EVENT_INTERVAL",
+        default = 3600,
+        help = "Seconds between two quota_exceeded webhook events of an organization"
+    )]
+    pub webhook_quota_event_interval: i64,
+    #[env_config(
+        name = "ZO_STREAM_QUOTA_SYNC_INTERVAL",
+        default = 10,
+        help = "Seconds between two syncs of the stream quota usage of an ingester with the other ingesters"
+    )]
+    pub stream_quota_sync_interval: u64,
+    #[env_config(
+        name = "ZO_CORRELATION_MAX_SEQUENCES",
+        default = 100000,
+        help = "Sequences of the correlation rules an ingester keeps in progress, and events it keeps to forward to another node, new ones are skipped above it"
+    )]
+    pub correlation_max_sequences: usize,
+    #[env_config(
+        name = "ZO_CORRELATION_CLEAN_INTERVAL",
+        default = 60,
+        help = "Seconds between two removals of the correlation sequences whose window passed"
+    )]
+    pub correlation_clean_interval: u64,
+    #[env_config(
+        name = "ZO_CORRELATION_FORWARD_INTERVAL",
+        default = 1,
+        help = "Seconds between two batches of correlation events forwarded to the ingester owning their sequence"
+    )]
+    pub correlation_forward_interval: u64,
+    #[env_config(
+        name = "ZO_THREAT_INTEL_FEED_INTERVAL",
+        default = 3600,
+        help = "Seconds between two fetches of the feeds of the threat intel indicator lists"
+    )]
+    pub threat_intel_feed_interval: u64,
+    #[env_config(name = "ZO_THREAT_INTEL_FEED_TIMEOUT", default = 30)] // seconds
+    pub threat_intel_feed_timeout: u64,
+    #[env_config(
+        name = "ZO_THREAT_INTEL_ALERT_INTERVAL",
+        default = 300,
+        help = "Seconds an indicator of a threat intel list is not notified again after a hit"
+    )]
+    pub threat_intel_alert_interval: i64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_CONCURRENCY", default = 5)]
     pub alert_schedule_concurrency: i64,
     #[env_config(name = "ZO_ALERT_SCHEDULE_TIMEOUT", default = 90)] // seconds
@@ -764,6 +1122,18 @@ pub struct Limit {
         default = 600
     )] // seconds
     pub meta_transaction_lock_timeout: usize,
+    #[env_config(
+        name = "ZO_META_SQLITE_MMAP_SIZE",
+        help = "Size of the sqlite metadata database, which holds the file_list index, memory-mapped by each connection instead of read into the heap, 0 disables it",
+        default = 4096
+    )] // MB
+    pub sqlite_mmap_size: usize,
+    #[env_config(
+        name = "ZO_META_SQLITE_CACHE_SIZE",
+        help = "Size of the page cache of each sqlite connection, which keeps the hot ranges of the file_list index",
+        default = 16
+    )] // MB
+    pub sqlite_cache_size: usize,
     #[env_config(name = "ZO_DISTINCT_VALUES_INTERVAL", default = 10)] // seconds
     pub distinct_values_interval: u64,
     #[env_config(name = "ZO_DISTINCT_VALUES_HOURLY", default = false)]
@@ -790,6 +1160,38 @@ pub struct Compact {
     pub delete_files_delay_hours: i64,
     #[env_config(name = "ZO_COMPACT_BLOCKED_ORGS", default = "")] // use comma to split
     pub blocked_orgs: String,
+    #[env_config(
+        name = "ZO_COMPACT_SHARD_BACKLOG_HOURS",
+        default = 6,
+        help = "When a stream is this many hours behind, its next periods are published as jobs any compactor can claim, 0 disables it"
+    )] // hours
+    pub shard_backlog_hours: i64,
+    #[env_config(
+        name = "ZO_COMPACT_SHARD_MAX_JOBS",
+        default = 24,
+        help = "Maximum number of periods published as jobs for one stream"
+    )]
+    pub shard_max_jobs: i64,
+    #[env_config(name = "ZO_COMPACT_JOB_LEASE_SECS", default = 3600)] // seconds
+    pub job_lease_secs: i64,
+    #[env_config(
+        name = "ZO_COMPACT_REWRITE_MAX_PERIODS",
+        default = 6,
+        help = "Maximum number of periods of a stream rewritten by a rewrite job in one compaction run"
+    )]
+    pub rewrite_max_periods: i64,
+    #[env_config(
+        name = "ZO_COMPACT_FILE_LIST_MANIFEST_ENABLED",
+        default = true,
+        help = "Roll up the file_list of each finished day into a manifest which is loaded on startup instead of the hourly files"
+    )]
+    pub file_list_manifest_enabled: bool,
+    #[env_config(
+        name = "ZO_COMPACT_FILE_LIST_MANIFEST_PAGE_SIZE",
+        default = 10000,
+        help = "Number of file keys read from a manifest and added to the file_list at once"
+    )]
+    pub file_list_manifest_page_size: usize,
 }
 
 #[derive(EnvConfig)]
@@ -963,6 +1365,46 @@ pub struct S3 {
     pub allow_invalid_certificates: bool,
     #[env_config(name = "ZO_S3_SYNC_TO_CACHE_INTERVAL", default = 600)] // seconds
     pub sync_to_cache_interval: u64,
+    #[env_config(
+        name = "ZO_S3_MULTIPART_PART_SIZE",
+        default = 16,
+        help = "Files larger than one part are uploaded and downloaded in parts of this size, in MB"
+    )]
+    pub multipart_part_size: usize,
+    #[env_config(
+        name = "ZO_S3_MULTIPART_CONCURRENCY",
+        default = 8,
+        help = "Number of parts of one file uploaded or downloaded at the same time"
+    )]
+    pub multipart_concurrency: usize,
+    #[env_config(
+        name = "ZO_S3_MAX_BANDWIDTH",
+        default = 0,
+        help = "Maximum bytes per second transferred from and to the object storage by this node, in MB, 0 means unlimited"
+    )]
+    pub max_bandwidth: usize,
+    #[env_config(
+        name = "ZO_S3_ARCHIVE_BUCKET_NAME",
+        default = "",
+        help = "Cold bucket the expired files of archived streams are moved to, archival is disabled when empty"
+    )]
+    pub archive_bucket_name: String,
+    #[env_config(name = "ZO_S3_ARCHIVE_BUCKET_PREFIX", default = "")]
+    pub archive_bucket_prefix: String,
+    #[env_config(
+        name = "ZO_S3_ARCHIVE_PROVIDER",
+        default = "",
+        help = "Provider of the archive bucket, the archive uses the server url, region and credentials of the main bucket when empty"
+    )]
+    pub archive_provider: String,
+    #[env_config(name = "ZO_S3_ARCHIVE_SERVER_URL", default = "")]
+    pub archive_server_url: String,
+    #[env_config(name = "ZO_S3_ARCHIVE_REGION_NAME", default = "")]
+    pub archive_region_name: String,
+    #[env_config(name = "ZO_S3_ARCHIVE_ACCESS_KEY", default = "")]
+    pub archive_access_key: String,
+    #[env_config(name = "ZO_S3_ARCHIVE_SECRET_KEY", default = "")]
+    pub archive_secret_key: String,
 }
 
 #[derive(Debug, EnvConfig)]
@@ -973,6 +1415,26 @@ pub struct Prometheus {
     pub ha_replica_label: String,
 }
 
+#[derive(Debug, EnvConfig)]
+pub struct Loki {
+    #[env_config(
+        name = "ZO_LOKI_STREAM_LABEL",
+        default = "job",
+        help = "Loki label whose value is used as the log stream name"
+    )]
+    pub stream_label: String,
+    #[env_config(name = "ZO_LOKI_DEFAULT_STREAM", default = "default")]
+    pub default_stream: String,
+    #[env_config(
+        name = "ZO_LOKI_PARTITION_LABELS",
+        default = "",
+        help = "Comma separated Loki labels used as partition keys of the streams they create"
+    )]
+    pub partition_labels: String,
+    #[env_config(name = "ZO_LOKI_MESSAGE_FIELD", default = "message")]
+    pub message_field: String,
+}
+
 #[derive(Debug, EnvConfig)]
 pub struct RUM {
     #[env_config(name = "ZO_RUM_ENABLED", default = false)]
@@ -1066,12 +1528,23 @@ pub fn init() -> Config {
 }
 
 fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
+    if !cfg.auth.encryption_master_key.is_empty()
+        && crate::utils::base64::decode_raw(&cfg.auth.encryption_master_key)
+            .map_or(true, |v| v.len() != 32)
+    {
+        return Err(anyhow::anyhow!(
+            "ZO_ENCRYPTION_MASTER_KEY should be the base64 of 32 bytes"
+        ));
+    }
     if cfg.limit.file_push_interval == 0 {
         cfg.limit.file_push_interval = 60;
     }
     if cfg.limit.req_cols_per_record_limit == 0 {
         cfg.limit.req_cols_per_record_limit = 1000;
     }
+    if cfg.limit.search_job_max_concurrent == 0 {
+        cfg.limit.search_job_max_concurrent = 1;
+    }
 
     // check max_file_size_on_disk to MB
     if cfg.limit.max_file_size_on_disk == 0 {
@@ -1150,12 +1623,33 @@ fn check_common_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
             "Data retention is not allowed to be less than 3 days."
         ));
     }
+    if cfg.limit.stats_history_retention_days <= 0 {
+        cfg.limit.stats_history_retention_days = 90;
+    } else if chrono::Duration::try_days(cfg.limit.stats_history_retention_days).is_none() {
+        return Err(anyhow::anyhow!(
+            "Stats history retention of {} days is out of range.",
+            cfg.limit.stats_history_retention_days
+        ));
+    }
+    if cfg.compact.job_lease_secs <= 0 {
+        cfg.compact.job_lease_secs = 3600;
+    }
+    if cfg.compact.rewrite_max_periods <= 0 {
+        cfg.compact.rewrite_max_periods = 6;
+    }
+    if cfg.compact.file_list_manifest_page_size == 0 {
+        cfg.compact.file_list_manifest_page_size = 10000;
+    }
     if cfg.compact.delete_files_delay_hours < 1 {
         return Err(anyhow::anyhow!(
             "Delete files delay is not allowed to be less than 1 hour."
         ));
     }
 
+    if cfg.common.sample_data_interval == 0 {
+        cfg.common.sample_data_interval = 10;
+    }
+
     // If the default scrape interval is less than 5s, raise an error
     if cfg.common.default_scrape_interval < 5 {
         return Err(anyhow::anyhow!(
@@ -1311,6 +1805,12 @@ fn check_memory_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     if cfg.limit.query_full_mode_limit == 0 {
         cfg.limit.query_full_mode_limit = 1000;
     }
+    if cfg.limit.query_dedup_max_hits == 0 {
+        cfg.limit.query_dedup_max_hits = 10000;
+    }
+    if cfg.limit.query_stream_page_size == 0 {
+        cfg.limit.query_stream_page_size = 10000;
+    }
     Ok(())
 }
 
@@ -1396,10 +1896,33 @@ fn check_s3_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
         }
     }
     cfg.s3.provider = cfg.s3.provider.to_lowercase();
+
+    // s3 requires every part except the last one to be at least 5MB
+    if cfg.s3.multipart_part_size < 5 {
+        cfg.s3.multipart_part_size = 5;
+    }
+    cfg.s3.multipart_part_size *= 1024 * 1024;
+    if cfg.s3.multipart_concurrency == 0 {
+        cfg.s3.multipart_concurrency = 1;
+    }
+    cfg.s3.max_bandwidth *= 1024 * 1024;
     if cfg.s3.provider.eq("swift") {
         std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
     }
 
+    if !cfg.s3.archive_bucket_prefix.is_empty() && !cfg.s3.archive_bucket_prefix.ends_with('/') {
+        cfg.s3.archive_bucket_prefix = format!("{}/", cfg.s3.archive_bucket_prefix);
+    }
+    // the archive bucket lives with the main one unless it has its own provider
+    if cfg.s3.archive_provider.is_empty() {
+        cfg.s3.archive_provider = cfg.s3.provider.clone();
+        cfg.s3.archive_server_url = cfg.s3.server_url.clone();
+        cfg.s3.archive_region_name = cfg.s3.region_name.clone();
+        cfg.s3.archive_access_key = cfg.s3.access_key.clone();
+        cfg.s3.archive_secret_key = cfg.s3.secret_key.clone();
+    }
+    cfg.s3.archive_provider = cfg.s3.archive_provider.to_lowercase();
+
     Ok(())
 }
 
@@ -1456,6 +1979,14 @@ mod tests {
         let ret = check_common_config(&mut cfg);
         assert!(ret.is_err());
 
+        let mut retention_cfg = Config::init().unwrap();
+        retention_cfg.limit.stats_history_retention_days = 0;
+        check_common_config(&mut retention_cfg).unwrap();
+        assert_eq!(retention_cfg.limit.stats_history_retention_days, 90);
+        let mut retention_cfg = Config::init().unwrap();
+        retention_cfg.limit.stats_history_retention_days = i64::MAX;
+        assert!(check_common_config(&mut retention_cfg).is_err());
+
         cfg.common.data_dir = "".to_string();
         let ret = check_path_config(&mut cfg);
         assert!(ret.is_ok());
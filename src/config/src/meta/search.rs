@@ -21,6 +21,7 @@ use utoipa::ToSchema;
 
 use crate::{
     ider,
+    meta::stream::FieldMetadata,
     utils::{base64, json},
 };
 
@@ -117,6 +118,21 @@ pub struct Query {
     pub query_fn: Option<String>,
     #[serde(default)]
     pub skip_wal: bool,
+    #[serde(default)]
+    pub nested: bool,
+    /// Fraction of the files scanned, e.g. `0.01`. The files are picked
+    /// deterministically, counts and sums are scaled by it and returned with
+    /// their error margins. 0 scans everything.
+    #[serde(default)]
+    pub sample: f64,
+    /// Return the aggregates of the files searched so far when the query
+    /// times out instead of failing, with the `completeness` of the result
+    #[serde(default)]
+    pub partial: bool,
+    /// Estimate the total hits when the hits reach `size` and
+    /// `track_total_hits` is off, from the matches in a few sampled hours
+    #[serde(default)]
+    pub estimate_total: bool,
 }
 
 fn default_size() -> usize {
@@ -140,6 +156,10 @@ impl Default for Query {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         }
     }
 }
@@ -202,6 +222,55 @@ pub struct Response {
     pub trace_id: String,
     #[serde(skip_serializing_if = "String::is_empty")]
     pub function_error: String,
+    /// Set when a part of the requested data was not searched
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub warning: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_estimate: Option<TotalEstimate>,
+    /// Character offsets of the searched terms per field, aligned with `hits`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<Object>)]
+    pub highlights: Vec<HashMap<String, Vec<[usize; 2]>>>,
+    /// Set when the query scanned a sample of the files
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SampleInfo>,
+    /// Percent of the files searched, set when a partial query timed out. The
+    /// WAL files are always searched in full and count as searched.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completeness: Option<f64>,
+    /// Percent of the time range whose buckets came from the result cache
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_ratio: Option<f64>,
+    /// Description and semantic tags of the returned fields which have some
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub field_metadata: HashMap<String, FieldMetadata>,
+}
+
+/// The sample a query scanned, its scaled counts come with a `{column}_error`
+/// column holding the margin of error at 95% confidence
+#[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
+pub struct SampleInfo {
+    pub rate: f64,
+    /// Fraction of the records scanned, the results are scaled by it
+    #[serde(default)]
+    pub fraction: f64,
+    /// Files scanned
+    pub files: usize,
+}
+
+/// Total hits of a limited query estimated from a sample of its time range
+#[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
+pub struct TotalEstimate {
+    pub value: usize,
+    /// False when too few records matched in the sample to trust the value
+    pub confident: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
@@ -238,6 +307,13 @@ impl Response {
             response_type: "".to_string(),
             trace_id: "".to_string(),
             function_error: "".to_string(),
+            warning: "".to_string(),
+            total_estimate: None,
+            highlights: Vec::new(),
+            sample: None,
+            completeness: None,
+            cache_ratio: None,
+            field_metadata: HashMap::new(),
         }
     }
 
@@ -287,6 +363,10 @@ impl Response {
         self.scan_records = val;
     }
 
+    pub fn set_warning(&mut self, warning: String) {
+        self.warning = warning;
+    }
+
     pub fn set_trace_id(&mut self, trace_id: String) {
         self.trace_id = trace_id;
     }
@@ -348,6 +428,13 @@ pub struct ScanStats {
     pub records: i64,
     pub original_size: i64,
     pub compressed_size: i64,
+    /// Files left unsearched by a partial query that timed out
+    #[serde(default)]
+    pub skipped_files: i64,
+    /// Records of the files a sampled query picked its files from, set by
+    /// the leader
+    #[serde(default)]
+    pub sample_records: i64,
 }
 
 impl ScanStats {
@@ -360,6 +447,8 @@ impl ScanStats {
         self.records += other.records;
         self.original_size += other.original_size;
         self.compressed_size += other.compressed_size;
+        self.skipped_files += other.skipped_files;
+        self.sample_records += other.sample_records;
     }
 
     pub fn format_to_mb(&mut self) {
@@ -385,6 +474,9 @@ impl From<Request> for cluster_rpc::SearchRequest {
             uses_zo_fn: req.query.uses_zo_fn,
             query_fn: req.query.query_fn.unwrap_or_default(),
             skip_wal: req.query.skip_wal,
+            nested: req.query.nested,
+            sample: req.query.sample,
+            partial: req.query.partial,
         };
 
         let job = cluster_rpc::Job {
@@ -409,6 +501,7 @@ impl From<Request> for cluster_rpc::SearchRequest {
             stream_type: "".to_string(),
             timeout: req.timeout,
             work_group: "".to_string(),
+            can_decrypt: false,
         }
     }
 }
@@ -420,6 +513,7 @@ impl From<&ScanStats> for cluster_rpc::ScanStats {
             records: req.records,
             original_size: req.original_size,
             compressed_size: req.compressed_size,
+            skipped_files: req.skipped_files,
         }
     }
 }
@@ -431,6 +525,8 @@ impl From<&cluster_rpc::ScanStats> for ScanStats {
             records: req.records,
             original_size: req.original_size,
             compressed_size: req.compressed_size,
+            skipped_files: req.skipped_files,
+            ..Default::default()
         }
     }
 }
@@ -520,6 +616,10 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                nested: false,
+                sample: 0.0,
+                partial: false,
+                estimate_total: false,
             },
             aggs: HashMap::new(),
             encoding: "base64".into(),
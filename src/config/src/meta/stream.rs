@@ -19,12 +19,13 @@ use byteorder::{ByteOrder, LittleEndian};
 use chrono::Duration;
 use hashbrown::HashMap;
 use proto::cluster_rpc;
-use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use utoipa::ToSchema;
 
 use super::usage::Stats;
 use crate::{
     utils::{
+        flatten::{ArrayFlatten, FlattenOptions, KeyNormalization, KEY_SEPARATOR},
         hash::{gxhash, Sum64},
         json,
         json::{Map, Value},
@@ -195,6 +196,27 @@ pub struct StreamStats {
     pub compressed_size: f64,
 }
 
+/// Ingestion of a stream counted against its quotas, on one ingester or
+/// summed over all of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StreamQuotaUsage {
+    /// Events the token bucket can still take, negative after a batch larger
+    /// than the bucket
+    pub tokens: f64,
+    /// Last refill of the token bucket, in microseconds
+    pub refilled_at: i64,
+    /// Start of the UTC day counted by `day_size`, in microseconds
+    pub day_start: i64,
+    /// Megabytes ingested since `day_start`
+    pub day_size: f64,
+    /// Megabytes ingested since `day_start` by the other ingesters, as of the
+    /// last sync
+    #[serde(default)]
+    pub cluster_day_size: f64,
+    /// Events rejected by the quotas since `day_start`
+    pub rejected: i64,
+}
+
 impl StreamStats {
     /// Returns true iff [start, end] time range intersects with the stream's
     /// time range.
@@ -398,6 +420,7 @@ impl std::fmt::Display for PartitionTimeLevel {
 }
 
 #[derive(Clone, Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
 pub struct StreamSettings {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     #[serde(default)]
@@ -417,6 +440,98 @@ pub struct StreamSettings {
     pub flatten_level: Option<i64>,
     #[serde(skip_serializing_if = "Option::None")]
     pub defined_schema_fields: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub virtual_fields: Vec<VirtualField>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub default_display_fields: Vec<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub field_display: HashMap<String, FieldDisplay>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub nested_fields: Vec<String>,
+    #[serde(default)]
+    pub max_field_size: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub binary_fields: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub encrypt_fields: Vec<String>,
+    /// Days of data searched unless a query asks for all the tiers, 0 uses
+    /// the global default
+    #[serde(default)]
+    pub hot_data_days: i64,
+    /// Records matching one of the rules are dropped before they are written
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub drop_rules: Vec<DropRule>,
+    /// Free-form labels, e.g. `team` or `env`, used to filter and group the
+    /// stream list
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub owner: Option<StreamOwner>,
+    /// Keep the records as they were received, before the functions ran, in a
+    /// compressed column fetched back by record id
+    #[serde(default)]
+    pub retain_original: bool,
+    /// Days during which the records can't be deleted, by the retention, a
+    /// delete or the stream deletion, from the time the lock is set. Once set
+    /// it can only be extended until it expires
+    #[serde(default)]
+    pub retention_lock_days: i64,
+    /// When the retention lock expires, in microseconds, it is set by the
+    /// server when the lock is set or extended
+    #[serde(default)]
+    pub retention_lock_until: i64,
+    /// Move the expired files to the archive bucket instead of deleting them,
+    /// on retention and on the stream deletion
+    #[serde(default)]
+    pub archive: bool,
+    /// Stamp the records with the id of the function chain which transformed
+    /// them, in the `_lineage` column
+    #[serde(default)]
+    pub lineage: bool,
+    /// Description and semantic tags of the fields, by field name
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub field_metadata: HashMap<String, FieldMetadata>,
+    /// Events accepted per second by an ingester, 0 for no limit
+    #[serde(default)]
+    pub max_events_per_sec: u64,
+    /// Megabytes accepted per UTC day by an ingester, 0 for no limit
+    #[serde(default)]
+    pub max_mb_per_day: u64,
+    /// How the arrays of the ingested records are flattened
+    #[serde(default)]
+    pub flatten_arrays: ArrayFlatten,
+    /// Joins the nested keys of the ingested records, `_` when empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default)]
+    pub flatten_separator: String,
+    /// How the keys of the ingested records are made valid column names
+    #[serde(default)]
+    pub key_normalization: KeyNormalization,
+    /// Which resource and scope attributes of the OTLP logs become columns
+    #[serde(default)]
+    pub otlp_attributes: OtlpAttributes,
+    /// Most columns of the schema, the new keys past it are kept as JSON in
+    /// the `_extra` column, 0 for no limit
+    #[serde(default)]
+    pub max_fields: usize,
+    /// Keys promoted from the `_extra` column, they are columns even past
+    /// `max_fields`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub promoted_fields: Vec<String>,
+    /// How the WAL and the compactor write the parquet files of the stream
+    #[serde(default)]
+    pub parquet: ParquetOptions,
 }
 
 impl Serialize for StreamSettings {
@@ -461,100 +576,444 @@ impl Serialize for StreamSettings {
                 state.skip_field("flatten_level")?;
             }
         }
+        if self.virtual_fields.is_empty() {
+            state.skip_field("virtual_fields")?;
+        } else {
+            state.serialize_field("virtual_fields", &self.virtual_fields)?;
+        }
+        if self.default_display_fields.is_empty() {
+            state.skip_field("default_display_fields")?;
+        } else {
+            state.serialize_field("default_display_fields", &self.default_display_fields)?;
+        }
+        if self.field_display.is_empty() {
+            state.skip_field("field_display")?;
+        } else {
+            state.serialize_field("field_display", &self.field_display)?;
+        }
+        if self.nested_fields.is_empty() {
+            state.skip_field("nested_fields")?;
+        } else {
+            state.serialize_field("nested_fields", &self.nested_fields)?;
+        }
+        if self.max_field_size == 0 {
+            state.skip_field("max_field_size")?;
+        } else {
+            state.serialize_field("max_field_size", &self.max_field_size)?;
+        }
+        if self.binary_fields.is_empty() {
+            state.skip_field("binary_fields")?;
+        } else {
+            state.serialize_field("binary_fields", &self.binary_fields)?;
+        }
+        if self.encrypt_fields.is_empty() {
+            state.skip_field("encrypt_fields")?;
+        } else {
+            state.serialize_field("encrypt_fields", &self.encrypt_fields)?;
+        }
+        if self.hot_data_days == 0 {
+            state.skip_field("hot_data_days")?;
+        } else {
+            state.serialize_field("hot_data_days", &self.hot_data_days)?;
+        }
+        if self.drop_rules.is_empty() {
+            state.skip_field("drop_rules")?;
+        } else {
+            state.serialize_field("drop_rules", &self.drop_rules)?;
+        }
+        if self.labels.is_empty() {
+            state.skip_field("labels")?;
+        } else {
+            state.serialize_field("labels", &self.labels)?;
+        }
+        match self.owner.as_ref() {
+            Some(owner) => {
+                state.serialize_field("owner", owner)?;
+            }
+            None => {
+                state.skip_field("owner")?;
+            }
+        }
+        if !self.retain_original {
+            state.skip_field("retain_original")?;
+        } else {
+            state.serialize_field("retain_original", &self.retain_original)?;
+        }
+        if self.retention_lock_days == 0 {
+            state.skip_field("retention_lock_days")?;
+        } else {
+            state.serialize_field("retention_lock_days", &self.retention_lock_days)?;
+        }
+        if self.retention_lock_until == 0 {
+            state.skip_field("retention_lock_until")?;
+        } else {
+            state.serialize_field("retention_lock_until", &self.retention_lock_until)?;
+        }
+        if !self.archive {
+            state.skip_field("archive")?;
+        } else {
+            state.serialize_field("archive", &self.archive)?;
+        }
+        if !self.lineage {
+            state.skip_field("lineage")?;
+        } else {
+            state.serialize_field("lineage", &self.lineage)?;
+        }
+        if self.field_metadata.is_empty() {
+            state.skip_field("field_metadata")?;
+        } else {
+            state.serialize_field("field_metadata", &self.field_metadata)?;
+        }
+        if self.max_events_per_sec == 0 {
+            state.skip_field("max_events_per_sec")?;
+        } else {
+            state.serialize_field("max_events_per_sec", &self.max_events_per_sec)?;
+        }
+        if self.max_mb_per_day == 0 {
+            state.skip_field("max_mb_per_day")?;
+        } else {
+            state.serialize_field("max_mb_per_day", &self.max_mb_per_day)?;
+        }
+        if self.flatten_arrays == ArrayFlatten::default() {
+            state.skip_field("flatten_arrays")?;
+        } else {
+            state.serialize_field("flatten_arrays", &self.flatten_arrays)?;
+        }
+        if self.flatten_separator.is_empty() {
+            state.skip_field("flatten_separator")?;
+        } else {
+            state.serialize_field("flatten_separator", &self.flatten_separator)?;
+        }
+        if self.key_normalization.is_default() {
+            state.skip_field("key_normalization")?;
+        } else {
+            state.serialize_field("key_normalization", &self.key_normalization)?;
+        }
+        if self.otlp_attributes.is_default() {
+            state.skip_field("otlp_attributes")?;
+        } else {
+            state.serialize_field("otlp_attributes", &self.otlp_attributes)?;
+        }
+        if self.max_fields == 0 {
+            state.skip_field("max_fields")?;
+        } else {
+            state.serialize_field("max_fields", &self.max_fields)?;
+        }
+        if self.promoted_fields.is_empty() {
+            state.skip_field("promoted_fields")?;
+        } else {
+            state.serialize_field("promoted_fields", &self.promoted_fields)?;
+        }
+        if self.parquet.is_default() {
+            state.skip_field("parquet")?;
+        } else {
+            state.serialize_field("parquet", &self.parquet)?;
+        }
         state.end()
     }
 }
 
 impl From<&str> for StreamSettings {
     fn from(data: &str) -> Self {
-        let settings: json::Value = json::from_slice(data.as_bytes()).unwrap();
-
-        let mut partition_keys = Vec::new();
-        if let Some(value) = settings.get("partition_keys") {
-            let mut v: Vec<_> = value.as_object().unwrap().into_iter().collect();
-            v.sort_by(|a, b| a.0.cmp(b.0));
-            for (_, value) in v.iter() {
-                match value {
-                    json::Value::String(v) => {
-                        partition_keys.push(StreamPartition::new(v));
-                    }
-                    json::Value::Object(v) => {
-                        let val: StreamPartition =
-                            json::from_value(json::Value::Object(v.to_owned())).unwrap();
-                        partition_keys.push(val);
-                    }
-                    _ => {}
-                }
-            }
+        let (settings, errors) = Self::parse(data);
+        if !errors.is_empty() {
+            log::warn!("stream settings are malformed: {}", errors.join(", "));
         }
+        settings
+    }
+}
 
-        let mut partition_time_level = None;
-        if let Some(value) = settings.get("partition_time_level") {
-            partition_time_level = Some(PartitionTimeLevel::from(value.as_str().unwrap()));
-        }
+impl StreamSettings {
+    /// Whether the retention lock still protects the records at `now`, in
+    /// microseconds.
+    pub fn is_retention_locked(&self, now: i64) -> bool {
+        self.retention_lock_days > 0 && self.retention_lock_until > now
+    }
 
-        let mut full_text_search_keys = Vec::new();
-        let fts = settings.get("full_text_search_keys");
-        if let Some(value) = fts {
-            let v: Vec<_> = value.as_array().unwrap().iter().collect();
-            for item in v {
-                full_text_search_keys.push(item.as_str().unwrap().to_string())
-            }
+    /// Sets the expiry of the retention lock when it is set or extended at
+    /// `now`, it never moves back. The lock of the `old` settings is kept.
+    pub fn set_retention_lock_until(&mut self, old: &StreamSettings, now: i64) {
+        self.retention_lock_until = old.retention_lock_until;
+        if self.retention_lock_days > 0 && self.retention_lock_days != old.retention_lock_days {
+            let days = Duration::try_days(self.retention_lock_days)
+                .and_then(|d| d.num_microseconds())
+                .unwrap_or(i64::MAX);
+            self.retention_lock_until = self.retention_lock_until.max(now.saturating_add(days));
         }
+    }
 
-        let mut bloom_filter_fields = Vec::new();
-        let fts = settings.get("bloom_filter_fields");
-        if let Some(value) = fts {
-            let v: Vec<_> = value.as_array().unwrap().iter().collect();
-            for item in v {
-                bloom_filter_fields.push(item.as_str().unwrap().to_string())
-            }
+    /// Whether the ingestion of the stream is limited by a quota.
+    pub fn has_quota(&self) -> bool {
+        self.max_events_per_sec > 0 || self.max_mb_per_day > 0
+    }
+
+    /// Options flattening the ingested records, the stream falls back to the
+    /// global flatten level when it doesn't set one.
+    pub fn flatten_options(&self) -> FlattenOptions {
+        FlattenOptions {
+            max_level: match self.flatten_level {
+                Some(level) => level.max(0) as u32,
+                None => CONFIG.limit.ingest_flatten_level,
+            },
+            arrays: self.flatten_arrays,
+            separator: if self.flatten_separator.is_empty() {
+                KEY_SEPARATOR.to_string()
+            } else {
+                self.flatten_separator.clone()
+            },
+            keys: self.key_normalization.clone(),
         }
+    }
 
-        let mut data_retention = 0;
-        if let Some(v) = settings.get("data_retention") {
-            data_retention = v.as_i64().unwrap();
+    /// Parses the settings stored in the schema metadata. A malformed value
+    /// falls back to the default of its field and is reported in the returned
+    /// errors instead of failing the whole settings.
+    pub fn parse(data: &str) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let settings: Map<String, Value> = match json::from_str(data) {
+            Ok(v) => v,
+            Err(e) => {
+                errors.push(format!("settings: {e}"));
+                return (Self::default(), errors);
+            }
         };
-
-        let mut defined_schema_fields: Option<Vec<String>> = None;
-        if let Some(value) = settings.get("defined_schema_fields") {
-            defined_schema_fields = Some(
-                value
-                    .as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|item| item.as_str().unwrap().to_string())
-                    .collect(),
-            );
-        }
-
-        let mut routing: HashMap<String, Vec<RoutingCondition>> = HashMap::new();
-        let routes = settings.get("routing");
-        if let Some(value) = routes {
-            let v: Vec<_> = value.as_object().unwrap().iter().collect();
-            for item in v {
-                routing.insert(
-                    item.0.to_string(),
-                    json::from_value(item.1.clone()).unwrap(),
-                );
+        let mut partition_keys = Vec::new();
+        match settings.get("partition_keys") {
+            // partition keys are stored as {"L0": key, "L1": key}
+            Some(Value::Object(v)) => {
+                let mut v: Vec<_> = v.iter().collect();
+                v.sort_by(|a, b| a.0.cmp(b.0));
+                for (level, value) in v {
+                    match value {
+                        Value::String(v) => partition_keys.push(StreamPartition::new(v)),
+                        value => match json::from_value::<StreamPartition>(value.clone()) {
+                            Ok(v) => partition_keys.push(v),
+                            Err(e) => errors.push(format!("partition_keys.{level}: {e}")),
+                        },
+                    }
+                }
             }
+            None | Some(Value::Null) => {}
+            Some(_) => errors.push("partition_keys: expected an object".to_string()),
         }
 
-        let flatten_level = settings.get("flatten_level").map(|v| v.as_i64().unwrap());
+        let partition_time_level = match settings.get("partition_time_level") {
+            Some(Value::String(v)) => Some(PartitionTimeLevel::from(v.as_str())),
+            None | Some(Value::Null) => None,
+            Some(_) => {
+                errors.push("partition_time_level: expected a string".to_string());
+                None
+            }
+        };
 
-        Self {
+        let settings = Self {
             partition_keys,
             partition_time_level,
-            full_text_search_keys,
-            bloom_filter_fields,
-            data_retention,
-            routing: Some(routing),
-            flatten_level,
-            defined_schema_fields,
+            full_text_search_keys: parse_field(&settings, "full_text_search_keys", &mut errors),
+            bloom_filter_fields: parse_field(&settings, "bloom_filter_fields", &mut errors),
+            data_retention: parse_field(&settings, "data_retention", &mut errors),
+            routing: Some(parse_field(&settings, "routing", &mut errors)),
+            flatten_level: parse_field(&settings, "flatten_level", &mut errors),
+            defined_schema_fields: parse_field(&settings, "defined_schema_fields", &mut errors),
+            virtual_fields: parse_field(&settings, "virtual_fields", &mut errors),
+            default_display_fields: parse_field(&settings, "default_display_fields", &mut errors),
+            field_display: parse_field(&settings, "field_display", &mut errors),
+            nested_fields: parse_field(&settings, "nested_fields", &mut errors),
+            max_field_size: parse_field(&settings, "max_field_size", &mut errors),
+            binary_fields: parse_field(&settings, "binary_fields", &mut errors),
+            encrypt_fields: parse_field(&settings, "encrypt_fields", &mut errors),
+            hot_data_days: parse_field(&settings, "hot_data_days", &mut errors),
+            drop_rules: parse_field(&settings, "drop_rules", &mut errors),
+            labels: parse_field(&settings, "labels", &mut errors),
+            owner: parse_field(&settings, "owner", &mut errors),
+            retain_original: parse_field(&settings, "retain_original", &mut errors),
+            retention_lock_days: parse_field(&settings, "retention_lock_days", &mut errors),
+            retention_lock_until: parse_field(&settings, "retention_lock_until", &mut errors),
+            archive: parse_field(&settings, "archive", &mut errors),
+            lineage: parse_field(&settings, "lineage", &mut errors),
+            field_metadata: parse_field(&settings, "field_metadata", &mut errors),
+            max_events_per_sec: parse_field(&settings, "max_events_per_sec", &mut errors),
+            max_mb_per_day: parse_field(&settings, "max_mb_per_day", &mut errors),
+            flatten_arrays: parse_field(&settings, "flatten_arrays", &mut errors),
+            flatten_separator: parse_field(&settings, "flatten_separator", &mut errors),
+            key_normalization: parse_field(&settings, "key_normalization", &mut errors),
+            otlp_attributes: parse_field(&settings, "otlp_attributes", &mut errors),
+            max_fields: parse_field(&settings, "max_fields", &mut errors),
+            promoted_fields: parse_field(&settings, "promoted_fields", &mut errors),
+            parquet: parse_field(&settings, "parquet", &mut errors),
+        };
+        (settings, errors)
+    }
+}
+
+fn parse_field<T: DeserializeOwned + Default>(
+    settings: &Map<String, Value>,
+    name: &str,
+    errors: &mut Vec<String>,
+) -> T {
+    match settings.get(name) {
+        None | Some(Value::Null) => T::default(),
+        Some(v) => json::from_value(v.clone()).unwrap_or_else(|e| {
+            errors.push(format!("{name}: {e}"));
+            T::default()
+        }),
+    }
+}
+
+/// Who to contact about a stream, e.g. before changing its retention or
+/// deleting it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StreamOwner {
+    /// Email of the owning user, it must be a member of the org
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub team: String,
+    /// How to reach the owner, e.g. a chat channel or a mailing list
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub contact: String,
+}
+
+/// Drops the records where every condition matches, e.g. the access logs of
+/// health checks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DropRule {
+    pub name: String,
+    pub conditions: Vec<DropCondition>,
+}
+
+/// Matches the records where the value of `column` matches the `regex`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DropCondition {
+    pub column: String,
+    pub regex: String,
+}
+
+/// A named SQL expression evaluated at query time and exposed as a column of the stream,
+/// e.g. `duration_ms = duration_ns / 1e6`.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct VirtualField {
+    pub name: String,
+    pub expr: String,
+}
+
+/// How a field should be rendered in result tables. `link` is a URL template where
+/// `{field_name}` placeholders are filled from the same row.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldDisplay {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub unit: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub link: String,
+}
+
+/// What a field holds. Tags are semantic markers like `pii` or `duration_ms`,
+/// the unit of the field is set in its display.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct FieldMetadata {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Where the resource and scope attributes of the OTLP logs go. By default
+/// every attribute is a column, which explodes the schema of streams fed by
+/// many services; with a nested column only the promoted attributes are
+/// columns and the others are kept as JSON in the nested column.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OtlpAttributes {
+    /// Attributes kept as columns, a trailing `*` matches a prefix like
+    /// `k8s.*`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub promote: Vec<String>,
+    /// Column holding the other attributes as
+    /// `{"resource": {..}, "scope": {..}}`, all the attributes are columns
+    /// when empty
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub nested_column: String,
+}
+
+impl OtlpAttributes {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the attribute is a column of its own.
+    pub fn is_promoted(&self, key: &str) -> bool {
+        self.nested_column.is_empty()
+            || self.promote.iter().any(|p| match p.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => p == key,
+            })
+    }
+}
+
+/// Parquet writer options of a stream, the defaults suit most streams but
+/// high cardinality streams and low cardinality ones compress best with
+/// different settings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ParquetOptions {
+    /// ZSTD compression level from 1 to 22, 0 for the default level
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_zero")]
+    pub zstd_level: i32,
+    /// Most rows of a row group, 0 for `ZO_PARQUET_MAX_ROW_GROUP_SIZE`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_zero")]
+    pub row_group_size: usize,
+    /// Columns written with dictionary encoding, even the full text search and
+    /// bloom filter ones which are written without it by default
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dictionary_fields: Vec<String>,
+    /// Columns written without dictionary encoding
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub no_dictionary_fields: Vec<String>,
+}
+
+impl ParquetOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0..=22).contains(&self.zstd_level) {
+            return Err(format!(
+                "zstd_level {} is out of range, it should be from 1 to 22, or 0 for the default",
+                self.zstd_level
+            ));
+        }
+        if let Some(field) = self
+            .dictionary_fields
+            .iter()
+            .find(|f| self.no_dictionary_fields.contains(f))
+        {
+            return Err(format!(
+                "field [{field}] is in both dictionary_fields and no_dictionary_fields"
+            ));
         }
+        Ok(())
     }
 }
 
+fn is_zero<T: Default + PartialEq>(v: &T) -> bool {
+    *v == T::default()
+}
+
 #[derive(Clone, Debug, Default, Hash, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct StreamPartition {
     pub field: String,
@@ -626,6 +1085,14 @@ pub struct Routing {
     pub routing: Vec<RoutingCondition>,
 }
 
+impl Routing {
+    /// The org and stream of a destination written as `org/stream`, `None` when
+    /// the records stay in the org of the source stream.
+    pub fn cross_org_destination(&self) -> Option<(&str, &str)> {
+        self.destination.split_once('/')
+    }
+}
+
 // Code Duplicated from alerts
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct RoutingCondition {
@@ -788,4 +1255,114 @@ mod tests {
         assert_eq!(part.get_partition_key("test2"), "field=18");
         assert_eq!(part.get_partition_key("test3"), "field=6");
     }
+
+    #[test]
+    fn test_stream_settings_display() {
+        let mut settings = StreamSettings {
+            default_display_fields: vec!["level".to_string(), "message".to_string()],
+            ..Default::default()
+        };
+        settings.field_display.insert(
+            "duration".to_string(),
+            FieldDisplay {
+                display_name: "Duration".to_string(),
+                unit: "ms".to_string(),
+                ..Default::default()
+            },
+        );
+        let data = json::to_string(&settings).unwrap();
+        let resp = StreamSettings::from(data.as_str());
+        assert_eq!(resp.default_display_fields, settings.default_display_fields);
+        assert_eq!(resp.field_display, settings.field_display);
+        assert!(resp.virtual_fields.is_empty());
+    }
+
+    #[test]
+    fn test_stream_settings_parse_malformed() {
+        let data = r#"{"partition_keys":{"L0":"host","L1":3},"full_text_search_keys":"log","data_retention":7,"flatten_level":null}"#;
+        let (settings, errors) = StreamSettings::parse(data);
+        assert_eq!(settings.partition_keys, vec![StreamPartition::new("host")]);
+        assert!(settings.full_text_search_keys.is_empty());
+        assert_eq!(settings.data_retention, 7);
+        assert_eq!(settings.flatten_level, None);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("partition_keys.L1"));
+        assert!(errors[1].starts_with("full_text_search_keys"));
+
+        let (settings, errors) = StreamSettings::parse("not json");
+        assert!(settings.partition_keys.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_stream_settings_deny_unknown_fields() {
+        assert!(json::from_str::<StreamSettings>(r#"{"data_retention":7}"#).is_ok());
+        assert!(json::from_str::<StreamSettings>(r#"{"data_retentoin":7}"#).is_err());
+    }
+
+    #[test]
+    fn test_retention_lock() {
+        let day = Duration::try_days(1).unwrap().num_microseconds().unwrap();
+        let old = StreamSettings::default();
+        let mut settings = StreamSettings::default();
+        assert!(!settings.is_retention_locked(10 * day));
+        settings.retention_lock_days = 7;
+        settings.set_retention_lock_until(&old, 10 * day);
+        assert_eq!(settings.retention_lock_until, 17 * day);
+        assert!(settings.is_retention_locked(16 * day));
+        assert!(!settings.is_retention_locked(17 * day));
+
+        // saving the settings again doesn't move the expiry
+        let old = settings.clone();
+        settings.set_retention_lock_until(&old, 12 * day);
+        assert_eq!(settings.retention_lock_until, 17 * day);
+        // extending the lock does
+        settings.retention_lock_days = 30;
+        settings.set_retention_lock_until(&old, 12 * day);
+        assert_eq!(settings.retention_lock_until, 42 * day);
+    }
+
+    #[test]
+    fn test_parquet_options_validate() {
+        let mut options = ParquetOptions {
+            zstd_level: 19,
+            dictionary_fields: vec!["level".to_string()],
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+        options.no_dictionary_fields = vec!["level".to_string()];
+        assert!(options.validate().is_err());
+        options.no_dictionary_fields.clear();
+        options.zstd_level = 23;
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_otlp_attributes_is_promoted() {
+        let mut attrs = OtlpAttributes::default();
+        assert!(attrs.is_promoted("k8s.pod.name"));
+        attrs.nested_column = "attributes".to_string();
+        attrs.promote = vec!["service.name".to_string(), "k8s.*".to_string()];
+        assert!(attrs.is_promoted("service.name"));
+        assert!(attrs.is_promoted("k8s.pod.name"));
+        assert!(!attrs.is_promoted("service.version"));
+        assert!(!attrs.is_promoted("host.name"));
+    }
+
+    #[test]
+    fn test_routing_cross_org_destination() {
+        let route = Routing {
+            destination: "soc/security_events".to_string(),
+            routing: vec![],
+        };
+        assert_eq!(
+            route.cross_org_destination(),
+            Some(("soc", "security_events"))
+        );
+        let route = Routing {
+            destination: "security_events".to_string(),
+            routing: vec![],
+        };
+        assert_eq!(route.cross_org_destination(), None);
+    }
 }
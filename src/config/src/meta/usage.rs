@@ -40,6 +40,12 @@ pub enum TriggerDataType {
     Report,
     #[serde(rename = "alert")]
     Alert,
+    #[serde(rename = "quality_monitor")]
+    QualityMonitor,
+    #[serde(rename = "continuous_query")]
+    ContinuousQuery,
+    #[serde(rename = "slo")]
+    Slo,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -116,6 +116,18 @@ pub static INGEST_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static INGEST_DROPPED_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_dropped_records",
+            "Records dropped by the drop rules of the stream. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type", "rule"],
+    )
+    .expect("Metric created")
+});
 pub static INGEST_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     IntCounterVec::new(
         Opts::new("ingest_bytes", "Ingested bytes. ".to_owned() + HELP_SUFFIX)
@@ -199,6 +211,30 @@ pub static INGEST_MEMTABLE_FILES: Lazy<IntGaugeVec> = Lazy::new(|| {
 });
 
 // querier memory cache stats
+pub static QUERY_PENDING_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_pending_nums",
+            "Searches waiting in the query queue.".to_owned(),
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_RUNNING_NUMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_running_nums",
+            "Searches running after leaving the query queue.".to_owned(),
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
 pub static QUERY_MEMORY_CACHE_LIMIT_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new(
@@ -564,6 +600,9 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_BYTES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_DROPPED_RECORDS.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(INGEST_WAL_USED_BYTES.clone()))
         .expect("Metric registered");
@@ -584,6 +623,12 @@ fn register_metrics(registry: &Registry) {
         .expect("Metric registered");
 
     // querier stats
+    registry
+        .register(Box::new(QUERY_PENDING_NUMS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RUNNING_NUMS.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(QUERY_MEMORY_CACHE_LIMIT_BYTES.clone()))
         .expect("Metric registered");
@@ -13,38 +13,152 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use serde::{Deserialize, Serialize};
 use serde_json::value::{Map, Value};
+use utoipa::ToSchema;
+
+pub const KEY_SEPARATOR: &str = "_";
+
+/// Rows a record can be exploded into, a larger record keeps its arrays.
+const MAX_EXPLODED_ROWS: usize = 1000;
+
+/// Elements of an array given their own keys, a longer array is kept as a
+/// JSON string so that a record can't create columns without limit.
+const MAX_INDEXED_ELEMENTS: usize = 32;
+
+/// How the arrays of a record are flattened
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrayFlatten {
+    /// The array is kept as a JSON string
+    #[default]
+    String,
+    /// Each element gets its own key, suffixed by its index, up to
+    /// `MAX_INDEXED_ELEMENTS` elements
+    Index,
+    /// The record is repeated for each element of the array, see [`explode`]
+    Explode,
+}
+
+/// How the keys are made valid column names, they are always lower cased
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct KeyNormalization {
+    /// Replaces the characters other than letters, digits and `_`, `_` when
+    /// empty
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub replacement: String,
+    /// Longest key, the longer ones are truncated, 0 for no limit
+    #[serde(default)]
+    pub max_length: usize,
+    #[serde(default)]
+    pub collision: KeyCollision,
+    /// Stores the keys changed by the normalization, they are listed by the
+    /// key mappings of the stream
+    #[serde(default)]
+    pub report: bool,
+}
+
+impl KeyNormalization {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What happens when two keys of a record are normalized to the same key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCollision {
+    /// The last value is kept
+    #[default]
+    Overwrite,
+    /// The first value is kept, the next ones are dropped
+    KeepFirst,
+    /// The next values get the key with a `_1`, `_2`, ... suffix
+    Suffix,
+}
 
-const KEY_SEPARATOR: &str = "_";
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlattenOptions {
+    /// Levels of objects flattened, the deeper ones are kept as JSON strings,
+    /// 0 for no limit
+    pub max_level: u32,
+    pub arrays: ArrayFlatten,
+    /// Joins the key of an object to the keys of its fields
+    pub separator: String,
+    pub keys: KeyNormalization,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        Self {
+            max_level: 0,
+            arrays: ArrayFlatten::String,
+            separator: KEY_SEPARATOR.to_string(),
+            keys: KeyNormalization::default(),
+        }
+    }
+}
+
+/// The flattened record and the keys changed by the normalization, as
+/// `(original, key)` pairs, `key` is empty when the value was dropped.
+struct Flattened {
+    map: Map<String, Value>,
+    renamed: Vec<(String, String)>,
+}
 
 #[inline]
 pub fn flatten(to_flatten: Value) -> Result<Value, anyhow::Error> {
     flatten_with_level(to_flatten, 0)
 }
 
+#[inline]
+pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, anyhow::Error> {
+    flatten_with_options(
+        to_flatten,
+        &FlattenOptions {
+            max_level,
+            ..Default::default()
+        },
+    )
+}
+
 /// Flattens the provided JSON object (`current`).
 ///
 /// It will return an error if flattening the object would make two keys to be
 /// the same, overwriting a value. It will alre return an error if the JSON
 /// value passed it's not an object.
 ///
+/// The arrays are flattened as set in `options`, with [`ArrayFlatten::Explode`]
+/// the ones left by [`explode`] are kept as JSON strings.
+///
 /// # Errors
 /// Will return `Err` if `to_flatten` it's not an object, or if flattening the
 /// object would result in two or more keys colliding.
-pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, anyhow::Error> {
+pub fn flatten_with_options(
+    to_flatten: Value,
+    options: &FlattenOptions,
+) -> Result<Value, anyhow::Error> {
+    flatten_with_renames(to_flatten, options).map(|(value, _)| value)
+}
+
+/// Same as [`flatten_with_options`], also returning the keys changed by the
+/// normalization as `(original, key)` pairs, `key` is empty when the value was
+/// dropped by a collision.
+pub fn flatten_with_renames(
+    to_flatten: Value,
+    options: &FlattenOptions,
+) -> Result<(Value, Vec<(String, String)>), anyhow::Error> {
     // quick check to see if we have an object`
     let to_flatten = match to_flatten {
         Value::Object(v) => {
-            if v.is_empty() || !v.iter().any(|(_k, v)| v.is_object() || v.is_array()) {
-                if v.iter().all(|(k, _v)| check_key(k)) {
-                    return Ok(Value::Object(v));
-                }
-                let mut formatted_map = Map::<String, Value>::with_capacity(v.len());
-                for (mut k, v) in v.into_iter() {
-                    format_key(&mut k);
-                    formatted_map.insert(k, v);
-                }
-                return Ok(Value::Object(formatted_map));
+            if (v.is_empty() || !v.iter().any(|(_k, v)| v.is_object() || v.is_array()))
+                && v.iter().all(|(k, _v)| {
+                    check_key(k)
+                        && (options.keys.max_length == 0 || k.len() <= options.keys.max_length)
+                })
+            {
+                return Ok((Value::Object(v), vec![]));
             }
             Value::Object(v)
         }
@@ -53,103 +167,238 @@ pub fn flatten_with_level(to_flatten: Value, max_level: u32) -> Result<Value, an
         }
     };
 
-    let mut flat = Map::<String, Value>::new();
-    flatten_value(to_flatten, "".to_owned(), max_level, 0, &mut flat).map(|_x| Value::Object(flat))
+    let mut flat = Flattened {
+        map: Map::new(),
+        renamed: Vec::new(),
+    };
+    flatten_value(
+        to_flatten,
+        "".to_owned(),
+        "".to_owned(),
+        options,
+        0,
+        &mut flat,
+    )
+    .map(|_x| (Value::Object(flat.map), flat.renamed))
+}
+
+/// Repeats the record for each element of its arrays, one row per
+/// combination of the elements of the arrays. Returns the record as it is
+/// unless the arrays are exploded, or when it would give more than
+/// `MAX_EXPLODED_ROWS` rows.
+pub fn explode(value: Value, options: &FlattenOptions) -> Vec<Value> {
+    if options.arrays != ArrayFlatten::Explode || !value.is_object() {
+        return vec![value];
+    }
+    match explode_value(&value, options.max_level, 0) {
+        Some(rows) => rows,
+        None => vec![value],
+    }
+}
+
+fn explode_value(value: &Value, max_level: u32, depth: u32) -> Option<Vec<Value>> {
+    match value {
+        Value::Object(map) => {
+            if max_level > 0 && depth >= max_level {
+                return Some(vec![value.clone()]);
+            }
+            let mut rows = vec![Map::with_capacity(map.len())];
+            for (k, v) in map {
+                let values = explode_value(v, max_level, depth + 1)?;
+                if rows.len() * values.len() > MAX_EXPLODED_ROWS {
+                    return None;
+                }
+                rows = rows
+                    .into_iter()
+                    .flat_map(|row| {
+                        values.iter().map(move |v| {
+                            let mut row = row.clone();
+                            row.insert(k.clone(), v.clone());
+                            row
+                        })
+                    })
+                    .collect();
+            }
+            Some(rows.into_iter().map(Value::Object).collect())
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            let mut rows = Vec::with_capacity(arr.len());
+            for v in arr {
+                rows.extend(explode_value(v, max_level, depth)?);
+                if rows.len() > MAX_EXPLODED_ROWS {
+                    return None;
+                }
+            }
+            Some(rows)
+        }
+        _ => Some(vec![value.clone()]),
+    }
 }
 
-/// Flattens the passed JSON value (`current`), whose path is `parent_key` and
-/// its 0-based depth is `depth`.  The result is stored in the JSON object
-/// `flattened`.
+/// Flattens the passed JSON value (`current`), whose path is `parent_key`,
+/// `original_key` before the normalization, and its 0-based depth is `depth`.
+/// The result is stored in `flattened`.
 fn flatten_value(
     current: Value,
     parent_key: String,
-    max_level: u32,
+    original_key: String,
+    options: &FlattenOptions,
     depth: u32,
-    flattened: &mut Map<String, Value>,
+    flattened: &mut Flattened,
 ) -> Result<(), anyhow::Error> {
     match current {
         Value::Object(map) => {
-            flatten_object(map, &parent_key, max_level, depth, flattened)?;
+            flatten_object(map, &parent_key, &original_key, options, depth, flattened)?;
         }
         Value::Array(arr) => {
-            flatten_array(arr, &parent_key, max_level, depth, flattened)?;
+            flatten_array(arr, &parent_key, &original_key, options, depth, flattened)?;
         }
         _ => {
-            flattened.insert(parent_key, current);
+            insert_value(parent_key, original_key, current, &options.keys, flattened);
         }
     }
     Ok(())
 }
 
 /// Flattens the passed object (`current`), whose path is `parent_key` and its
-/// 0-based depth is `depth`.  The result is stored in the JSON object
-/// `flattened`.
+/// 0-based depth is `depth`.  The result is stored in `flattened`.
 fn flatten_object(
     current: Map<String, Value>,
     parent_key: &str,
-    max_level: u32,
+    original_key: &str,
+    options: &FlattenOptions,
     depth: u32,
-    flattened: &mut Map<String, Value>,
+    flattened: &mut Flattened,
 ) -> Result<(), anyhow::Error> {
     if current.is_empty() {
         return Ok(());
     }
-    if max_level > 0 && depth >= max_level {
+    if options.max_level > 0 && depth >= options.max_level {
         let v = Value::String(Value::Object(current).to_string());
-        flatten_value(v, parent_key.to_string(), max_level, depth, flattened)?;
+        flatten_value(
+            v,
+            parent_key.to_string(),
+            original_key.to_string(),
+            options,
+            depth,
+            flattened,
+        )?;
         return Ok(());
     }
-    for (mut k, v) in current.into_iter() {
-        format_key(&mut k);
-        let parent_key = if depth > 0 {
-            format!("{}{}{}", parent_key, KEY_SEPARATOR, k)
+    for (k, v) in current.into_iter() {
+        let mut key = k.clone();
+        format_key_with(&mut key, &options.keys.replacement);
+        let (parent_key, original_key) = if depth > 0 {
+            (
+                format!("{}{}{}", parent_key, options.separator, key),
+                format!("{}{}{}", original_key, options.separator, k),
+            )
         } else {
-            k
+            (key, k)
         };
-        flatten_value(v, parent_key, max_level, depth + 1, flattened)?;
+        flatten_value(v, parent_key, original_key, options, depth + 1, flattened)?;
     }
     Ok(())
 }
 
 /// Flattens the passed array (`current`), whose path is `parent_key` and its
-/// 0-based depth is `depth`.  The result is stored in the JSON object
-/// `flattened`.
+/// 0-based depth is `depth`.  The result is stored in `flattened`.
 fn flatten_array(
     current: Vec<Value>,
     parent_key: &str,
-    max_level: u32,
+    original_key: &str,
+    options: &FlattenOptions,
     depth: u32,
-    flattened: &mut Map<String, Value>,
+    flattened: &mut Flattened,
 ) -> Result<(), anyhow::Error> {
     if current.is_empty() {
         return Ok(());
     }
-    // for (i, obj) in current.iter().enumerate() {
-    //     let parent_key = format!("{}{}{}", parent_key, KEY_SEPARATOR, i);
-    //     flatten_value(obj, parent_key, depth + 1, flattened)?;
-    // }
-    let v = Value::String(Value::Array(current.to_vec()).to_string());
-    flatten_value(v, parent_key.to_string(), max_level, depth, flattened)?;
+    if options.arrays == ArrayFlatten::Index
+        && current.len() <= MAX_INDEXED_ELEMENTS
+        && (options.max_level == 0 || depth < options.max_level)
+    {
+        for (i, v) in current.into_iter().enumerate() {
+            let parent_key = format!("{}{}{}", parent_key, options.separator, i);
+            let original_key = format!("{}{}{}", original_key, options.separator, i);
+            flatten_value(v, parent_key, original_key, options, depth + 1, flattened)?;
+        }
+        return Ok(());
+    }
+    let v = Value::String(Value::Array(current).to_string());
+    flatten_value(
+        v,
+        parent_key.to_string(),
+        original_key.to_string(),
+        options,
+        depth,
+        flattened,
+    )?;
     Ok(())
 }
 
+/// Inserts a value at its normalized key, truncated to the max length and
+/// resolving the collisions with the keys already inserted.
+fn insert_value(
+    mut key: String,
+    original_key: String,
+    value: Value,
+    keys: &KeyNormalization,
+    flattened: &mut Flattened,
+) {
+    if keys.max_length > 0 && key.chars().count() > keys.max_length {
+        key = key.chars().take(keys.max_length).collect();
+    }
+    if flattened.map.contains_key(&key) {
+        match keys.collision {
+            KeyCollision::Overwrite => {}
+            KeyCollision::KeepFirst => {
+                flattened.renamed.push((original_key, String::new()));
+                return;
+            }
+            KeyCollision::Suffix => {
+                let mut n = 1;
+                while flattened.map.contains_key(&format!("{key}_{n}")) {
+                    n += 1;
+                }
+                key = format!("{key}_{n}");
+            }
+        }
+    }
+    if key != original_key {
+        flattened.renamed.push((original_key, key.clone()));
+    }
+    flattened.map.insert(key, value);
+}
+
 /// We need every character in the key to be lowercase alphanumeric or
 /// underscore
 pub fn format_key(key: &mut String) {
+    format_key_with(key, KEY_SEPARATOR)
+}
+
+/// Same as [`format_key`], the other characters are replaced by
+/// `replacement`, `_` when empty.
+pub fn format_key_with(key: &mut String, replacement: &str) {
     if check_key(key) {
         return;
     }
-    let mut key_chars = key.chars().collect::<Vec<_>>();
-    for c in key_chars.iter_mut() {
-        if c.is_lowercase() || c.is_numeric() {
-            continue;
+    let replacement = if replacement.is_empty() {
+        KEY_SEPARATOR
+    } else {
+        replacement
+    };
+    let mut formatted = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c.is_lowercase() || c.is_numeric() || c == '_' {
+            formatted.push(c);
         } else if c.is_uppercase() {
-            *c = c.to_lowercase().next().unwrap();
+            formatted.push(c.to_lowercase().next().unwrap());
         } else {
-            *c = '_';
+            formatted.push_str(replacement);
         }
     }
-    *key = key_chars.into_iter().collect::<String>();
+    *key = formatted;
 }
 
 fn check_key(key: &str) -> bool {
@@ -495,4 +744,141 @@ mod tests {
         let output = flatten_with_level(input, 5).unwrap();
         assert_eq!(output, expected_output_level4);
     }
+
+    #[test]
+    fn test_flatten_with_options() {
+        let input = json!({
+            "involvedObject": {"kind": "Pod", "name": "api-1"},
+            "ports": [80, {"name": "metrics", "port": 9090}],
+        });
+        let options = FlattenOptions {
+            arrays: ArrayFlatten::Index,
+            separator: "__".to_string(),
+            ..Default::default()
+        };
+        let output = flatten_with_options(input.clone(), &options).unwrap();
+        assert_eq!(
+            output,
+            json!({
+                "involvedobject__kind": "Pod",
+                "involvedobject__name": "api-1",
+                "ports__0": 80,
+                "ports__1__name": "metrics",
+                "ports__1__port": 9090,
+            })
+        );
+
+        // the objects and arrays past the max level are kept as strings
+        let options = FlattenOptions {
+            max_level: 1,
+            ..options
+        };
+        let output = flatten_with_options(input, &options).unwrap();
+        assert_eq!(
+            output,
+            json!({
+                "involvedobject": "{\"kind\":\"Pod\",\"name\":\"api-1\"}",
+                "ports": "[80,{\"name\":\"metrics\",\"port\":9090}]",
+            })
+        );
+
+        // a long array doesn't get a key per element
+        let options = FlattenOptions {
+            arrays: ArrayFlatten::Index,
+            ..Default::default()
+        };
+        let values = json!(vec![1; MAX_INDEXED_ELEMENTS + 1]);
+        let output = flatten_with_options(json!({ "values": values }), &options).unwrap();
+        assert_eq!(output, json!({ "values": values.to_string() }));
+    }
+
+    #[test]
+    fn test_explode() {
+        let options = FlattenOptions {
+            arrays: ArrayFlatten::Explode,
+            ..Default::default()
+        };
+        let input = json!({"host": "a", "events": [{"code": 1}, {"code": 2}], "tags": ["x", "y"]});
+        let rows = explode(input, &options);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows[0],
+            json!({"host": "a", "events": {"code": 1}, "tags": "x"})
+        );
+        assert_eq!(
+            rows[3],
+            json!({"host": "a", "events": {"code": 2}, "tags": "y"})
+        );
+        let row = flatten_with_options(rows[0].clone(), &options).unwrap();
+        assert_eq!(row, json!({"host": "a", "events_code": 1, "tags": "x"}));
+
+        // too many rows, the record keeps its arrays
+        let input = json!({"a": (0..40).collect::<Vec<_>>(), "b": (0..40).collect::<Vec<_>>()});
+        assert_eq!(explode(input.clone(), &options), vec![input.clone()]);
+        assert_eq!(
+            explode(input.clone(), &FlattenOptions::default()),
+            vec![input]
+        );
+    }
+
+    #[test]
+    fn test_key_normalization() {
+        let input = json!({"Field-Name": 1, "field_name": 2, "kubernetes.io/name": "api"});
+        let options = FlattenOptions {
+            keys: KeyNormalization {
+                max_length: 12,
+                collision: KeyCollision::Suffix,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (output, renamed) = flatten_with_renames(input.clone(), &options).unwrap();
+        assert_eq!(
+            output,
+            json!({"field_name": 1, "field_name_1": 2, "kubernetes_i": "api"})
+        );
+        assert_eq!(
+            renamed,
+            vec![
+                ("Field-Name".to_string(), "field_name".to_string()),
+                ("field_name".to_string(), "field_name_1".to_string()),
+                ("kubernetes.io/name".to_string(), "kubernetes_i".to_string()),
+            ]
+        );
+
+        let options = FlattenOptions {
+            keys: KeyNormalization {
+                replacement: "__".to_string(),
+                collision: KeyCollision::KeepFirst,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (output, renamed) = flatten_with_renames(input.clone(), &options).unwrap();
+        assert_eq!(
+            output,
+            json!({"field__name": 1, "field_name": 2, "kubernetes__io__name": "api"})
+        );
+        assert_eq!(renamed.len(), 2);
+
+        let options = FlattenOptions {
+            keys: KeyNormalization {
+                collision: KeyCollision::KeepFirst,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (output, renamed) = flatten_with_renames(input, &options).unwrap();
+        assert_eq!(
+            output,
+            json!({"field_name": 1, "kubernetes_io_name": "api"})
+        );
+        assert_eq!(renamed[1], ("field_name".to_string(), "".to_string()));
+
+        // the valid keys are kept as they are
+        let input = json!({"field_name": 1, "other": 2});
+        let (output, renamed) = flatten_with_renames(input.clone(), &options).unwrap();
+        assert_eq!(output, input);
+        assert!(renamed.is_empty());
+    }
 }
@@ -26,5 +26,6 @@ pub mod rand;
 pub mod record_batch_ext;
 pub mod schema;
 pub mod schema_ext;
+pub mod sketch;
 pub mod str;
 pub mod time;
@@ -20,12 +20,25 @@ use arrow_schema::Schema;
 use futures::TryStreamExt;
 use parquet::{
     arrow::{arrow_reader::ArrowReaderMetadata, AsyncArrowWriter, ParquetRecordBatchStreamBuilder},
-    basic::{Compression, Encoding},
-    file::{metadata::KeyValue, properties::WriterProperties},
+    basic::{Compression, Encoding, ZstdLevel},
+    file::{
+        footer::{decode_footer, decode_metadata},
+        metadata::KeyValue,
+        properties::WriterProperties,
+        FOOTER_SIZE,
+    },
     format::SortingColumn,
 };
 
-use crate::{config::*, ider, meta::stream::FileMeta};
+use crate::{
+    config::*,
+    ider,
+    meta::stream::{FileMeta, ParquetOptions},
+    utils::{
+        json,
+        sketch::{HistogramSketch, SKETCH_METADATA_KEY},
+    },
+};
 
 pub fn new_parquet_writer<'a>(
     buf: &'a mut Vec<u8>,
@@ -33,20 +46,84 @@ pub fn new_parquet_writer<'a>(
     bloom_filter_fields: &'a [String],
     full_text_search_fields: &'a [String],
     metadata: &'a FileMeta,
+) -> AsyncArrowWriter<&'a mut Vec<u8>> {
+    new_parquet_writer_with_sketch(
+        buf,
+        schema,
+        bloom_filter_fields,
+        full_text_search_fields,
+        metadata,
+        None,
+    )
+}
+
+/// Same as [`new_parquet_writer`], also storing the histogram sketch of the data in the
+/// file metadata.
+pub fn new_parquet_writer_with_sketch<'a>(
+    buf: &'a mut Vec<u8>,
+    schema: &'a Arc<Schema>,
+    bloom_filter_fields: &'a [String],
+    full_text_search_fields: &'a [String],
+    metadata: &'a FileMeta,
+    sketch: Option<&HistogramSketch>,
+) -> AsyncArrowWriter<&'a mut Vec<u8>> {
+    new_parquet_writer_with_options(
+        buf,
+        schema,
+        bloom_filter_fields,
+        full_text_search_fields,
+        metadata,
+        sketch,
+        &ParquetOptions::default(),
+    )
+}
+
+/// Same as [`new_parquet_writer_with_sketch`], using the parquet options of the stream
+/// over the defaults.
+pub fn new_parquet_writer_with_options<'a>(
+    buf: &'a mut Vec<u8>,
+    schema: &'a Arc<Schema>,
+    bloom_filter_fields: &'a [String],
+    full_text_search_fields: &'a [String],
+    metadata: &'a FileMeta,
+    sketch: Option<&HistogramSketch>,
+    options: &ParquetOptions,
 ) -> AsyncArrowWriter<&'a mut Vec<u8>> {
     let sort_column_id = schema
         .index_of(&CONFIG.common.column_timestamp)
         .expect("Not found timestamp field");
-    let row_group_size = if CONFIG.limit.parquet_max_row_group_size > 0 {
+    let row_group_size = if options.row_group_size > 0 {
+        options.row_group_size
+    } else if CONFIG.limit.parquet_max_row_group_size > 0 {
         CONFIG.limit.parquet_max_row_group_size
     } else {
         PARQUET_MAX_ROW_GROUP_SIZE
     };
+    let mut key_value_metadata = vec![
+        KeyValue::new("min_ts".to_string(), metadata.min_ts.to_string()),
+        KeyValue::new("max_ts".to_string(), metadata.max_ts.to_string()),
+        KeyValue::new("records".to_string(), metadata.records.to_string()),
+        KeyValue::new(
+            "original_size".to_string(),
+            metadata.original_size.to_string(),
+        ),
+    ];
+    if let Some(sketch) = sketch {
+        key_value_metadata.push(KeyValue::new(
+            SKETCH_METADATA_KEY.to_string(),
+            json::to_string(sketch).unwrap(),
+        ));
+    }
+    // the level is validated with the stream settings
+    let zstd_level = match options.zstd_level {
+        0 => ZstdLevel::default(),
+        level => ZstdLevel::try_new(level).unwrap_or_default(),
+    };
     let mut writer_props = WriterProperties::builder()
         .set_write_batch_size(PARQUET_BATCH_SIZE) // in bytes
         .set_data_page_size_limit(PARQUET_PAGE_SIZE) // maximum size of a data page in bytes
         .set_max_row_group_size(row_group_size) // maximum number of rows in a row group
-        .set_compression(Compression::ZSTD(Default::default()))
+        .set_compression(Compression::ZSTD(zstd_level))
         .set_dictionary_enabled(true)
         .set_encoding(Encoding::PLAIN)
         .set_sorting_columns(Some(
@@ -60,15 +137,7 @@ pub fn new_parquet_writer<'a>(
             CONFIG.common.column_timestamp.as_str().into(),
             Encoding::DELTA_BINARY_PACKED,
         )
-        .set_key_value_metadata(Some(vec![
-            KeyValue::new("min_ts".to_string(), metadata.min_ts.to_string()),
-            KeyValue::new("max_ts".to_string(), metadata.max_ts.to_string()),
-            KeyValue::new("records".to_string(), metadata.records.to_string()),
-            KeyValue::new(
-                "original_size".to_string(),
-                metadata.original_size.to_string(),
-            ),
-        ]));
+        .set_key_value_metadata(Some(key_value_metadata));
     for field in SQL_FULL_TEXT_SEARCH_FIELDS.iter() {
         writer_props = writer_props.set_column_dictionary_enabled(field.as_str().into(), false);
     }
@@ -107,6 +176,12 @@ pub fn new_parquet_writer<'a>(
                 .set_column_bloom_filter_enabled(field.into(), true); // take the field ownership
         }
     }
+    for field in options.dictionary_fields.iter() {
+        writer_props = writer_props.set_column_dictionary_enabled(field.as_str().into(), true);
+    }
+    for field in options.no_dictionary_fields.iter() {
+        writer_props = writer_props.set_column_dictionary_enabled(field.as_str().into(), false);
+    }
     let writer_props = writer_props.build();
     AsyncArrowWriter::try_new(
         buf,
@@ -175,6 +250,34 @@ pub async fn read_metadata_from_file(path: &PathBuf) -> Result<FileMeta, anyhow:
     Ok(meta)
 }
 
+/// Reads the histogram sketch from the footer of a parquet file, `footer` has to hold at
+/// least the file metadata and the 8 bytes trailer.
+pub fn read_sketch_from_footer(footer: &[u8]) -> Result<Option<HistogramSketch>, anyhow::Error> {
+    if footer.len() < FOOTER_SIZE {
+        return Err(anyhow::anyhow!("parquet footer is too short"));
+    }
+    let (data, trailer) = footer.split_at(footer.len() - FOOTER_SIZE);
+    let metadata_len = decode_footer(trailer.try_into()?)?;
+    if metadata_len > data.len() {
+        return Err(anyhow::anyhow!(
+            "parquet footer needs {} bytes of metadata",
+            metadata_len
+        ));
+    }
+    let metadata = decode_metadata(&data[data.len() - metadata_len..])?;
+    let Some(key_value_metadata) = metadata.file_metadata().key_value_metadata() else {
+        return Ok(None);
+    };
+    match key_value_metadata
+        .iter()
+        .find(|kv| kv.key == SKETCH_METADATA_KEY)
+        .and_then(|kv| kv.value.as_ref())
+    {
+        Some(v) => Ok(Some(json::from_str(v)?)),
+        None => Ok(None),
+    }
+}
+
 pub fn generate_filename_with_time_range(min_ts: i64, max_ts: i64) -> String {
     format!(
         "{}.{}.{}{}",
@@ -185,6 +288,27 @@ pub fn generate_filename_with_time_range(min_ts: i64, max_ts: i64) -> String {
     )
 }
 
+/// Generates the name of a file written against the schema version which
+/// starts at `schema_start_dt`
+pub fn generate_filename_with_schema_version(schema_start_dt: i64) -> String {
+    format!(
+        "{}.v{}{}",
+        ider::generate(),
+        schema_start_dt,
+        FILE_EXT_PARQUET
+    )
+}
+
+/// Returns the start_dt of the schema version the file was written against,
+/// `None` if the file name has no schema version.
+pub fn parse_schema_version_from_filename(mut name: &str) -> Option<i64> {
+    if let Some(v) = name.rfind('/') {
+        name = &name[v + 1..];
+    }
+    let (_, version) = name.strip_suffix(FILE_EXT_PARQUET)?.rsplit_once('.')?;
+    version.strip_prefix('v')?.parse::<i64>().ok()
+}
+
 pub fn parse_time_range_from_filename(mut name: &str) -> (i64, i64) {
     if let Some(v) = name.rfind('/') {
         name = &name[v + 1..];
@@ -197,3 +321,23 @@ pub fn parse_time_range_from_filename(mut name: &str) -> (i64, i64) {
     let max_ts = columns[1].parse::<i64>().unwrap_or(0);
     (min_ts, max_ts)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_schema_version_from_filename() {
+        let name = generate_filename_with_schema_version(1708012800000000);
+        assert_eq!(
+            parse_schema_version_from_filename(&format!("files/default/logs/default/{name}")),
+            Some(1708012800000000)
+        );
+        assert_eq!(
+            parse_schema_version_from_filename("files/default/logs/default/7128.parquet"),
+            None
+        );
+        let name = generate_filename_with_time_range(1, 2);
+        assert_eq!(parse_schema_version_from_filename(&name), None);
+    }
+}
@@ -17,15 +17,16 @@ use std::sync::Arc;
 
 use arrow::{
     array::{
-        make_builder, new_null_array, ArrayBuilder, ArrayRef, BooleanBuilder, Float64Builder,
-        Int64Builder, NullBuilder, StringBuilder, UInt64Builder,
+        make_builder, new_null_array, Array, ArrayBuilder, ArrayRef, BinaryArray, BinaryBuilder,
+        BooleanBuilder, Float64Builder, Int64Builder, NullBuilder, StringArray, StringBuilder,
+        UInt64Builder,
     },
     record_batch::RecordBatch,
 };
 use arrow_schema::{ArrowError, DataType, Schema};
 use hashbrown::HashSet;
 
-use super::schema_ext::SchemaExt;
+use super::{base64, schema_ext::SchemaExt};
 use crate::FxIndexMap;
 
 const USIZE_SIZE: usize = std::mem::size_of::<usize>();
@@ -141,6 +142,20 @@ pub fn convert_json_to_record_batch(
                     let b = builder.as_any_mut().downcast_mut::<NullBuilder>().unwrap();
                     b.append_null();
                 }
+                DataType::Binary => {
+                    let b = builder
+                        .as_any_mut()
+                        .downcast_mut::<BinaryBuilder>()
+                        .unwrap();
+                    if v.is_null() {
+                        b.append_null();
+                    } else {
+                        // binary values are ingested as base64 strings
+                        let data = base64::decode_raw(v.as_str().unwrap_or_default())
+                            .map_err(|e| ArrowError::ParseError(e.to_string()))?;
+                        b.append_value(data);
+                    }
+                }
                 _ => {
                     return Err(ArrowError::SchemaError(
                         "Cannot convert json to RecordBatch from non-basic type value".to_string(),
@@ -189,6 +204,12 @@ pub fn convert_json_to_record_batch(
                             .unwrap()
                             .append_null();
                     }
+                    DataType::Binary => {
+                        b.as_any_mut()
+                            .downcast_mut::<BinaryBuilder>()
+                            .unwrap()
+                            .append_null();
+                    }
                     _ => {}
                 }
             }
@@ -206,3 +227,73 @@ pub fn convert_json_to_record_batch(
 
     RecordBatch::try_new(schema.clone(), cols)
 }
+
+// encode binary columns as base64 strings, the same format they are ingested in
+pub fn encode_binary_columns(batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|f| f.data_type() == &DataType::Binary)
+    {
+        return Ok(batch.clone());
+    }
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut cols: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+    for (field, col) in schema.fields().iter().zip(batch.columns()) {
+        if field.data_type() != &DataType::Binary {
+            fields.push(field.clone());
+            cols.push(col.clone());
+            continue;
+        }
+        let values = col.as_any().downcast_ref::<BinaryArray>().unwrap();
+        let array = values
+            .iter()
+            .map(|v| v.map(base64::encode_raw))
+            .collect::<StringArray>();
+        fields.push(Arc::new(
+            field.as_ref().clone().with_data_type(DataType::Utf8),
+        ));
+        cols.push(Arc::new(array));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), cols)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::Field;
+
+    use super::*;
+
+    #[test]
+    fn test_binary_columns() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "payload",
+            DataType::Binary,
+            true,
+        )]));
+        let data = vec![
+            Arc::new(serde_json::json!({"payload": "aGVsbG8="})),
+            Arc::new(serde_json::json!({})),
+        ];
+        let batch = convert_json_to_record_batch(&schema, &data).unwrap();
+        let values = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        assert_eq!(values.value(0), b"hello");
+        assert!(values.is_null(1));
+
+        let batch = encode_binary_columns(&batch).unwrap();
+        let values = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(values.value(0), "aGVsbG8=");
+
+        let data = vec![Arc::new(serde_json::json!({"payload": "not base64!"}))];
+        assert!(convert_json_to_record_batch(&schema, &data).is_err());
+    }
+}
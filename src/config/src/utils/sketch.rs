@@ -0,0 +1,169 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use arrow::{
+    array::{Array, Int64Array, StringArray},
+    record_batch::RecordBatch,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::CONFIG;
+
+/// Key of the sketch in the parquet key value metadata.
+pub const SKETCH_METADATA_KEY: &str = "zo_histogram_sketch";
+
+/// Width of a sketch bucket in microseconds.
+pub const SKETCH_BUCKET: i64 = 60_000_000;
+
+/// Pre-aggregated counts of a parquet file, used to answer histograms without a scan.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistogramSketch {
+    /// records per bucket, keyed by the bucket start in microseconds
+    pub counts: BTreeMap<i64, u64>,
+    /// records per value of the severity field
+    #[serde(default)]
+    pub severity: BTreeMap<String, u64>,
+}
+
+impl HistogramSketch {
+    /// Builds the sketch of the batches, `None` if they have no timestamp column.
+    pub fn from_batches(batches: &[RecordBatch]) -> Option<Self> {
+        let mut sketch = Self::default();
+        for batch in batches {
+            let Some(timestamps) = batch
+                .column_by_name(&CONFIG.common.column_timestamp)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+            else {
+                return None;
+            };
+            for ts in timestamps.iter().flatten() {
+                *sketch
+                    .counts
+                    .entry(ts - ts.rem_euclid(SKETCH_BUCKET))
+                    .or_default() += 1;
+            }
+            if let Some(severity) = batch
+                .column_by_name(&CONFIG.common.histogram_sketch_severity_field)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            {
+                for (i, v) in severity.iter().enumerate() {
+                    if timestamps.is_valid(i) {
+                        *sketch
+                            .severity
+                            .entry(v.unwrap_or_default().to_string())
+                            .or_default() += 1;
+                    }
+                }
+            }
+        }
+        Some(sketch)
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (bucket, count) in other.counts.iter() {
+            *self.counts.entry(*bucket).or_default() += count;
+        }
+        for (severity, count) in other.severity.iter() {
+            *self.severity.entry(severity.clone()).or_default() += count;
+        }
+    }
+
+    /// Counts the records in `[start, end)` by bins of `interval` aligned on `origin`, all
+    /// in microseconds. Returns `None` when the sketch can't answer exactly.
+    pub fn histogram(
+        &self,
+        start: i64,
+        end: i64,
+        interval: i64,
+        origin: i64,
+    ) -> Option<BTreeMap<i64, u64>> {
+        if interval <= 0
+            || interval % SKETCH_BUCKET != 0
+            || start % SKETCH_BUCKET != 0
+            || end % SKETCH_BUCKET != 0
+            || origin % SKETCH_BUCKET != 0
+        {
+            return None;
+        }
+        let mut bins = BTreeMap::new();
+        for (bucket, count) in self.counts.range(start..end) {
+            let bin = origin + (bucket - origin).div_euclid(interval) * interval;
+            *bins.entry(bin).or_default() += count;
+        }
+        Some(bins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_histogram_sketch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(&CONFIG.common.column_timestamp, DataType::Int64, false),
+            Field::new(
+                &CONFIG.common.histogram_sketch_severity_field,
+                DataType::Utf8,
+                true,
+            ),
+        ]));
+        let minute = SKETCH_BUCKET;
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![
+                    1,
+                    minute - 1,
+                    minute,
+                    3 * minute + 5,
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("info"),
+                    Some("error"),
+                    Some("info"),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut sketch = HistogramSketch::from_batches(&[batch]).unwrap();
+        assert_eq!(
+            sketch.counts,
+            BTreeMap::from([(0, 2), (minute, 1), (3 * minute, 1)])
+        );
+        assert_eq!(sketch.severity.get("info"), Some(&2));
+        assert_eq!(sketch.severity.get(""), Some(&1));
+
+        sketch.merge(&sketch.clone());
+        assert_eq!(
+            sketch.histogram(0, 10 * minute, 2 * minute, 0),
+            Some(BTreeMap::from([(0, 6), (2 * minute, 2)]))
+        );
+        assert_eq!(
+            sketch.histogram(minute, 2 * minute, minute, 0),
+            Some(BTreeMap::from([(minute, 2)]))
+        );
+        // the sketch can't split a bucket
+        assert_eq!(sketch.histogram(1, 10 * minute, minute, 0), None);
+        assert_eq!(sketch.histogram(0, 10 * minute, minute / 2, 0), None);
+    }
+}
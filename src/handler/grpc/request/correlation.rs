@@ -0,0 +1,41 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use async_trait::async_trait;
+use config::CONFIG;
+use proto::cluster_rpc::{correlation_server::Correlation, CorrelationRequest, EmptyResponse};
+use tonic::{Request, Response, Status};
+
+use crate::service::correlation;
+
+#[derive(Debug, Default)]
+pub struct CorrelationServerImpl;
+
+#[async_trait]
+impl Correlation for CorrelationServerImpl {
+    async fn observe(
+        &self,
+        request: Request<CorrelationRequest>,
+    ) -> Result<Response<EmptyResponse>, Status> {
+        let org_id = request
+            .metadata()
+            .get(&CONFIG.grpc.org_header_key)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .ok_or_else(|| Status::invalid_argument("missing org id"))?;
+        correlation::observe_forwarded(&org_id, request.into_inner().events);
+        Ok(Response::new(EmptyResponse {}))
+    }
+}
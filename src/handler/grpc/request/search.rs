@@ -14,7 +14,6 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use config::metrics;
-use infra::errors;
 #[cfg(feature = "enterprise")]
 use o2_enterprise::enterprise::{
     common::infra::config::O2_CONFIG,
@@ -22,12 +21,12 @@ use o2_enterprise::enterprise::{
 };
 use proto::cluster_rpc::{
     search_server::Search, CancelQueryRequest, CancelQueryResponse, QueryStatusRequest,
-    QueryStatusResponse, SearchRequest, SearchResponse,
+    QueryStatusResponse, ScanCostsRequest, ScanCostsResponse, SearchRequest, SearchResponse,
 };
 use tonic::{Request, Response, Status};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::service::search as SearchService;
+use crate::service::{error::ServiceError, search as SearchService};
 
 #[derive(Clone, Debug)]
 #[cfg(feature = "enterprise")]
@@ -166,12 +165,7 @@ impl Search for Searcher {
                 metrics::GRPC_INCOMING_REQUESTS
                     .with_label_values(&["/_search", "500", &org_id, "", &stream_type])
                     .inc();
-                let message = if let errors::Error::ErrorCode(code) = err {
-                    code.to_json()
-                } else {
-                    err.to_string()
-                };
-                Err(Status::internal(message))
+                Err(ServiceError::from(err).into())
             }
         }
     }
@@ -231,12 +225,7 @@ impl Search for Searcher {
                 metrics::GRPC_INCOMING_REQUESTS
                     .with_label_values(&["/_search", "500", &org_id, "", &stream_type])
                     .inc();
-                let message = if let errors::Error::ErrorCode(code) = err {
-                    code.to_json()
-                } else {
-                    err.to_string()
-                };
-                Err(Status::internal(message))
+                Err(ServiceError::from(err).into())
             }
         }
     }
@@ -282,4 +271,20 @@ impl Search for Searcher {
     ) -> Result<Response<CancelQueryResponse>, Status> {
         Err(Status::unimplemented("Not Supported"))
     }
+
+    async fn scan_costs(
+        &self,
+        req: Request<ScanCostsRequest>,
+    ) -> Result<Response<ScanCostsResponse>, Status> {
+        let req = req.into_inner();
+        let items = SearchService::scan_costs::list_local(
+            &req.org_id,
+            req.stream_type.as_str().into(),
+            &req.stream_name,
+            req.by_partition,
+        );
+        Ok(Response::new(ScanCostsResponse {
+            items: items.into_iter().map(Into::into).collect(),
+        }))
+    }
 }
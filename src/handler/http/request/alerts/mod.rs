@@ -15,17 +15,18 @@
 
 use std::{collections::HashMap, io::Error};
 
-use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::{
         meta::{alerts::Alert, http::HttpResponse as MetaHttpResponse},
-        utils::http::get_stream_type_from_request,
+        utils::http::{get_stream_type_from_request, get_user_id},
     },
     service::alerts,
 };
 
 pub mod destinations;
+pub mod slack;
 pub mod templates;
 
 /// CreateAlert
@@ -50,6 +51,7 @@ pub mod templates;
 pub async fn save_alert(
     path: web::Path<(String, String)>,
     alert: web::Json<Alert>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name) = path.into_inner();
 
@@ -57,7 +59,8 @@ pub async fn save_alert(
     let mut alert = alert.into_inner();
     alert.trigger_condition.frequency *= 60;
 
-    match alerts::save(&org_id, &stream_name, "", alert, true).await {
+    let user_id = get_user_id(req.headers());
+    match alerts::save(&org_id, &stream_name, "", alert, true, &user_id).await {
         Ok(_) => Ok(MetaHttpResponse::ok("Alert saved")),
         Err(e) => Ok(MetaHttpResponse::bad_request(e)),
     }
@@ -86,6 +89,7 @@ pub async fn save_alert(
 pub async fn update_alert(
     path: web::Path<(String, String, String)>,
     alert: web::Json<Alert>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name, name) = path.into_inner();
 
@@ -94,7 +98,8 @@ pub async fn update_alert(
     alert.trigger_condition.frequency *= 60;
 
     let name = name.trim();
-    match alerts::save(&org_id, &stream_name, name, alert, false).await {
+    let user_id = get_user_id(req.headers());
+    match alerts::save(&org_id, &stream_name, name, alert, false, &user_id).await {
         Ok(_) => Ok(MetaHttpResponse::ok("Alert Updated")),
         Err(e) => Ok(MetaHttpResponse::bad_request(e)),
     }
@@ -279,12 +284,10 @@ async fn delete_alert(
             return Ok(MetaHttpResponse::bad_request(e));
         }
     };
-    match alerts::delete(&org_id, stream_type, &stream_name, &name).await {
+    let user_id = get_user_id(req.headers());
+    match alerts::delete(&org_id, stream_type, &stream_name, &name, &user_id).await {
         Ok(_) => Ok(MetaHttpResponse::ok("Alert deleted")),
-        Err(e) => match e {
-            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
-            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
-        },
+        Err(e) => Ok(e.into()),
     }
 }
 
@@ -329,10 +332,93 @@ async fn enable_alert(
     resp.insert("enabled".to_string(), enable);
     match alerts::enable(&org_id, stream_type, &stream_name, &name, enable).await {
         Ok(_) => Ok(MetaHttpResponse::json(resp)),
-        Err(e) => match e {
-            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
-            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// SilenceAlert
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Alerts",
+    operation_id = "SilenceAlert",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("alert_name" = String, Path, description = "Alert name"),
+        ("minutes" = i64, Query, description = "Minutes without notification, 60 by default, 0 lifts the silence"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/{stream_name}/alerts/{alert_name}/silence")]
+async fn silence_alert(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(MetaHttpResponse::bad_request(e));
+        }
+    };
+    let minutes = match query.get("minutes") {
+        Some(v) => match v.parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => return Ok(MetaHttpResponse::bad_request("minutes must be a number")),
         },
+        None => alerts::slack::SILENCE_MINUTES,
+    };
+    let user_id = get_user_id(req.headers());
+    match alerts::silence(&org_id, stream_type, &stream_name, &name, minutes, &user_id).await {
+        Ok(_) if minutes == 0 => Ok(MetaHttpResponse::ok("Alert silence lifted")),
+        Ok(_) => Ok(MetaHttpResponse::ok("Alert silenced")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// AcknowledgeAlert
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Alerts",
+    operation_id = "AcknowledgeAlert",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("alert_name" = String, Path, description = "Alert name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/{stream_name}/alerts/{alert_name}/ack")]
+async fn acknowledge_alert(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or_default(),
+        Err(e) => {
+            return Ok(MetaHttpResponse::bad_request(e));
+        }
+    };
+    let user_id = get_user_id(req.headers());
+    match alerts::acknowledge(&org_id, stream_type, &stream_name, &name, &user_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Alert acknowledged")),
+        Err(e) => Ok(e.into()),
     }
 }
 
@@ -370,9 +456,6 @@ async fn trigger_alert(
     };
     match alerts::trigger(&org_id, stream_type, &stream_name, &name).await {
         Ok(_) => Ok(MetaHttpResponse::ok("Alert triggered")),
-        Err(e) => match e {
-            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
-            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
-        },
+        Err(e) => Ok(e.into()),
     }
 }
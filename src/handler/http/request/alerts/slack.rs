@@ -0,0 +1,77 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use config::utils::json;
+
+use crate::{common::meta::http::HttpResponse as MetaHttpResponse, service::alerts::slack};
+
+/// SlackInteractions
+///
+/// Request URL of the interactivity of the Slack app, called when a button of
+/// an alert notification is clicked. Slack can't log in, the requests are
+/// authenticated by their signature instead.
+#[utoipa::path(
+    path = "/slack/interactions",
+    tag = "Alerts",
+    operation_id = "SlackInteractions",
+    request_body(content = String, description = "Form with the interaction payload", content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/interactions")]
+pub async fn interactions(body: web::Bytes, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    let Some((_, payload)) = url::form_urlencoded::parse(&body).find(|(k, _)| k == "payload")
+    else {
+        return Ok(MetaHttpResponse::bad_request("payload is missing"));
+    };
+    let payload: json::Value = match json::from_str(&payload) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    // the alert of the payload tells the apps which could have signed it
+    let now = Utc::now().timestamp();
+    let verified = slack::interaction_secrets(&payload)
+        .await
+        .iter()
+        .any(|secret| {
+            slack::verify_signature(
+                secret,
+                header("X-Slack-Request-Timestamp"),
+                &body,
+                header("X-Slack-Signature"),
+                now,
+            )
+        });
+    if !verified {
+        return Ok(MetaHttpResponse::forbidden("invalid slack signature"));
+    }
+    match slack::handle_interaction(&payload).await {
+        Ok(line) => Ok(MetaHttpResponse::ok(line)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::io::Error;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        stream::{ArchiveRestore, ArchiveRestoreRequest, StreamArchive},
+    },
+    service::compact::archive,
+};
+
+/// ListArchives
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Archives",
+    operation_id = "ListArchives",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<StreamArchive>),
+    )
+)]
+#[get("/{org_id}/archives")]
+pub async fn list_archives(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match archive::list(&org_id).await {
+        Ok(archives) => Ok(MetaHttpResponse::json(archives)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// RestoreArchive
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Archives",
+    operation_id = "RestoreArchive",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Archive id"),
+    ),
+    request_body(content = ArchiveRestoreRequest, description = "Stream to restore into", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ArchiveRestore),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "Conflict", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/archives/{id}/_restore")]
+pub async fn restore_archive(
+    path: web::Path<(String, String)>,
+    body: web::Json<ArchiveRestoreRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    let user_email = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match archive::restore(&org_id, &id, body.into_inner().stream_name, user_email).await {
+        Ok(restore) => Ok(MetaHttpResponse::json(restore)),
+        Err(e) => Ok(e.into()),
+    }
+}
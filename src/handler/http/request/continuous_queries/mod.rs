@@ -0,0 +1,208 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{continuous_queries::ContinuousQuery, http::HttpResponse as MetaHttpResponse},
+        utils::http::get_user_id,
+    },
+    service::continuous_queries,
+};
+
+/// CreateContinuousQuery
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "CreateContinuousQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ContinuousQuery, description = "Continuous query data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "The user can't read the stream or write the destination", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "The name is taken", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/continuous_queries")]
+pub async fn save_continuous_query(
+    path: web::Path<String>,
+    query: web::Json<ContinuousQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_id = get_user_id(req.headers());
+    match continuous_queries::save(&org_id, query.into_inner(), &user_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Continuous query saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateContinuousQuery
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "UpdateContinuousQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Continuous query name"),
+    ),
+    request_body(content = ContinuousQuery, description = "Continuous query data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "The user can't read the stream or write the destination", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/continuous_queries/{name}")]
+pub async fn update_continuous_query(
+    path: web::Path<(String, String)>,
+    query: web::Json<ContinuousQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let user_id = get_user_id(req.headers());
+    match continuous_queries::update(&org_id, &name, query.into_inner(), &user_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Continuous query updated")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// EnableContinuousQuery
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "EnableContinuousQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Continuous query name"),
+        ("value" = bool, Query, description = "Enable or disable the continuous query"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/continuous_queries/{name}/enable")]
+async fn enable_continuous_query(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let enable = match query.get("value") {
+        Some(v) => v.parse::<bool>().unwrap_or_default(),
+        None => false,
+    };
+    let mut resp = HashMap::new();
+    resp.insert("enabled".to_string(), enable);
+    match continuous_queries::enable(&org_id, &name, enable).await {
+        Ok(_) => Ok(MetaHttpResponse::json(resp)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetContinuousQuery
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "GetContinuousQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Continuous query name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ContinuousQuery),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/continuous_queries/{name}")]
+async fn get_continuous_query(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match continuous_queries::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListContinuousQueries
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "ListContinuousQueries",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<ContinuousQuery>),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/continuous_queries")]
+async fn list_continuous_queries(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match continuous_queries::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteContinuousQuery
+#[utoipa::path(
+    context_path = "/api",
+    tag = "ContinuousQueries",
+    operation_id = "DeleteContinuousQuery",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Continuous query name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/continuous_queries/{name}")]
+async fn delete_continuous_query(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match continuous_queries::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Continuous query deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{correlation::CorrelationRule, http::HttpResponse as MetaHttpResponse},
+    service::correlation,
+};
+
+/// CreateCorrelationRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Correlation Rules",
+    operation_id = "CreateCorrelationRule",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = CorrelationRule, description = "Correlation rule data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/correlation_rules")]
+pub async fn save_correlation_rule(
+    path: web::Path<String>,
+    rule: web::Json<CorrelationRule>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match correlation::save(&org_id, "", rule.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Correlation rule saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateCorrelationRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Correlation Rules",
+    operation_id = "UpdateCorrelationRule",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Correlation rule name"),
+      ),
+    request_body(content = CorrelationRule, description = "Correlation rule data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/correlation_rules/{name}")]
+pub async fn update_correlation_rule(
+    path: web::Path<(String, String)>,
+    rule: web::Json<CorrelationRule>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match correlation::save(&org_id, name.trim(), rule.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Correlation rule saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetCorrelationRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Correlation Rules",
+    operation_id = "GetCorrelationRule",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Correlation rule name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = CorrelationRule),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/correlation_rules/{name}")]
+async fn get_correlation_rule(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match correlation::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListCorrelationRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Correlation Rules",
+    operation_id = "ListCorrelationRules",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<CorrelationRule>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/correlation_rules")]
+async fn list_correlation_rules(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match correlation::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteCorrelationRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Correlation Rules",
+    operation_id = "DeleteCorrelationRule",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Correlation rule name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/correlation_rules/{name}")]
+async fn delete_correlation_rule(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match correlation::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Correlation rule deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
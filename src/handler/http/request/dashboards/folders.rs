@@ -13,11 +13,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
 use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 
-use crate::{common::meta::dashboards::Folder, service::dashboards::folders};
+use crate::{
+    common::meta::{
+        dashboards::{Folder, FolderList, MoveFolder},
+        http::HttpResponse as MetaHttpResponse,
+    },
+    service::dashboards::folders,
+};
 
 /// CreateFolder
 #[utoipa::path(
@@ -49,7 +55,10 @@ pub async fn create_folder(
     folder: web::Json<Folder>,
 ) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
-    folders::save_folder(&org_id, folder.into_inner(), false).await
+    match folders::save_folder(&org_id, folder.into_inner(), false).await {
+        Ok(folder) => Ok(HttpResponse::Ok().json(folder)),
+        Err(e) => Ok(e.into()),
+    }
 }
 
 /// UpdateFolder
@@ -83,7 +92,10 @@ pub async fn update_folder(
     folder: web::Json<Folder>,
 ) -> Result<HttpResponse, Error> {
     let (org_id, folder_id) = path.into_inner();
-    folders::update_folder(&org_id, &folder_id, folder.into_inner()).await
+    match folders::update_folder(&org_id, &folder_id, folder.into_inner()).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Dashboard folder updated")),
+        Err(e) => Ok(e.into()),
+    }
 }
 
 /// ListFolders
@@ -96,6 +108,7 @@ pub async fn update_folder(
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("parent" = Option<String>, Query, description = "Only the folders under this one, empty for the top level folders"),
     ),
     responses(
         (status = StatusCode::OK, body = FolderList),
@@ -125,15 +138,18 @@ pub async fn list_folders(
                 _permitted = stream_list;
             }
             Err(e) => {
-                return Ok(crate::common::meta::http::HttpResponse::forbidden(
-                    e.to_string(),
-                ));
+                return Ok(MetaHttpResponse::forbidden(e.to_string()));
             }
         }
         // Get List of allowed objects ends
     }
 
-    folders::list_folders(&org_id, _permitted).await
+    let query = web::Query::<HashMap<String, String>>::from_query(_req.query_string()).unwrap();
+    match folders::list_folders(&org_id, _permitted, query.get("parent").map(|v| v.as_str())).await
+    {
+        Ok(list) => Ok(HttpResponse::Ok().json(FolderList { list })),
+        Err(e) => Ok(e.into()),
+    }
 }
 
 /// GetFolder
@@ -156,7 +172,10 @@ pub async fn list_folders(
 #[get("/{org_id}/folders/{folder_id}")]
 pub async fn get_folder(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
     let (org_id, folder_id) = path.into_inner();
-    folders::get_folder(&org_id, &folder_id).await
+    match folders::get_folder(&org_id, &folder_id).await {
+        Ok(folder) => Ok(HttpResponse::Ok().json(folder)),
+        Err(e) => Ok(e.into()),
+    }
 }
 
 /// DeleteFolder
@@ -180,5 +199,45 @@ pub async fn get_folder(path: web::Path<(String, String)>) -> Result<HttpRespons
 #[delete("/{org_id}/folders/{folder_id}")]
 async fn delete_folder(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
     let (org_id, folder_id) = path.into_inner();
-    folders::delete_folder(&org_id, &folder_id).await
+    match folders::delete_folder(&org_id, &folder_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Dashboard folder deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// MoveFolder
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Dashboards",
+    operation_id = "MoveFolder",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("folder_id" = String, Path, description = "Folder ID"),
+    ),
+    request_body(
+        content = MoveFolder,
+        description = "The new parent folder",
+        example = json!({
+            "parentId": "Parent folder id, empty for the top level",
+        }),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Folder moved", body = HttpResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid parent", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Folder not found", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/folders/{folder_id}/move")]
+pub async fn move_folder(
+    path: web::Path<(String, String)>,
+    body: web::Json<MoveFolder>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, folder_id) = path.into_inner();
+    match folders::move_folder(&org_id, &folder_id, body.parent_id.trim()).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Dashboard folder moved")),
+        Err(e) => Ok(e.into()),
+    }
 }
@@ -18,7 +18,10 @@ use std::{collections::HashMap, io::Error};
 use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse, Responder};
 
 use crate::{
-    common::meta::{dashboards::MoveDashboard, http::HttpResponse as MetaHttpResponse},
+    common::{
+        meta::{dashboards::MoveDashboard, http::HttpResponse as MetaHttpResponse},
+        utils::http::{get_tags, get_user_id},
+    },
     service::dashboards,
 };
 
@@ -56,8 +59,9 @@ pub async fn create_dashboard(
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
+    let user_id = get_user_id(req.headers());
     let folder = get_folder(req);
-    dashboards::create_dashboard(&org_id, &folder, body).await
+    dashboards::create_dashboard(&org_id, &folder, body, &user_id).await
 }
 
 /// UpdateDashboard
@@ -89,8 +93,9 @@ async fn update_dashboard(
     req: HttpRequest,
 ) -> impl Responder {
     let (org_id, dashboard_id) = path.into_inner();
+    let user_id = get_user_id(req.headers());
     let folder = get_folder(req);
-    dashboards::update_dashboard(&org_id, &dashboard_id, &folder, body).await
+    dashboards::update_dashboard(&org_id, &dashboard_id, &folder, body, &user_id).await
 }
 
 /// ListDashboards
@@ -103,6 +108,8 @@ async fn update_dashboard(
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("folder" = Option<String>, Query, description = "Folder id, default is the default folder"),
+        ("tags" = Option<String>, Query, description = "Comma separated tags the dashboards must all have"),
     ),
     responses(
         (status = StatusCode::OK, body = Dashboards),
@@ -110,8 +117,10 @@ async fn update_dashboard(
 )]
 #[get("/{org_id}/dashboards")]
 async fn list_dashboards(org_id: web::Path<String>, req: HttpRequest) -> impl Responder {
-    let folder = get_folder(req);
-    dashboards::list_dashboards(&org_id.into_inner(), &folder).await
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let folder = crate::common::utils::http::get_folder(&query);
+    let tags = get_tags(&query);
+    dashboards::list_dashboards(&org_id.into_inner(), &folder, &tags).await
 }
 
 /// GetDashboard
@@ -159,8 +168,9 @@ async fn get_dashboard(path: web::Path<(String, String)>, req: HttpRequest) -> i
 #[delete("/{org_id}/dashboards/{dashboard_id}")]
 async fn delete_dashboard(path: web::Path<(String, String)>, req: HttpRequest) -> impl Responder {
     let (org_id, dashboard_id) = path.into_inner();
+    let user_id = get_user_id(req.headers());
     let folder_id = get_folder(req);
-    dashboards::delete_dashboard(&org_id, &dashboard_id, &folder_id).await
+    dashboards::delete_dashboard(&org_id, &dashboard_id, &folder_id, &user_id).await
 }
 
 /// MoveDashboard
@@ -16,13 +16,15 @@
 use std::io::Error;
 
 use actix_multipart::Multipart;
-use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use config::{CONFIG, SIZE_IN_MB};
 use hashbrown::HashMap;
 
 use crate::{
-    common::meta::http::HttpResponse as MetaHttpResponse,
-    service::enrichment_table::save_enrichment_data,
+    common::meta::{
+        enrichment_table::EnrichmentTableSource, http::HttpResponse as MetaHttpResponse,
+    },
+    service::enrichment_table::{save_enrichment_data, source},
 };
 
 /// CreateEnrichmentTable
@@ -93,3 +95,114 @@ pub async fn save_enrichment_table(
         )),
     }
 }
+
+/// SaveEnrichmentTableSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "SaveEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    request_body(content = EnrichmentTableSource, description = "Url the table is refreshed from", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn save_enrichment_table_source(
+    path: web::Path<(String, String)>,
+    body: web::Json<EnrichmentTableSource>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    match source::save(&org_id, &table_name, body.into_inner()).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Enrichment table source saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetEnrichmentTableSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "GetEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = EnrichmentTableSource),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn get_enrichment_table_source(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    match source::get(&org_id, &table_name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListEnrichmentTableSources
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "ListEnrichmentTableSources",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<EnrichmentTableSource>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/enrichment_table_sources")]
+pub async fn list_enrichment_table_sources(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match source::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteEnrichmentTableSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "DeleteEnrichmentTableSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("table_name" = String, Path, description = "Table name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/enrichment_tables/{table_name}/source")]
+pub async fn delete_enrichment_table_source(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, table_name) = path.into_inner();
+    match source::delete(&org_id, &table_name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Enrichment table source deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -19,8 +19,11 @@ use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
 
 use crate::common::{
     meta,
-    meta::functions::{StreamOrder, Transform},
-    utils::http::get_stream_type_from_request,
+    meta::{
+        functions::{FunctionTestRequest, StreamOrder, Transform},
+        organization::Feature,
+    },
+    utils::http::{get_stream_type_from_request, get_user_id},
 };
 
 /// CreateFunction
@@ -44,12 +47,14 @@ use crate::common::{
 pub async fn save_function(
     path: web::Path<String>,
     func: web::Json<Transform>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
     let mut transform = func.into_inner();
     transform.name = transform.name.trim().to_string();
     transform.function = transform.function.trim().to_string();
-    crate::service::functions::save_function(org_id, transform).await
+    let user_id = get_user_id(req.headers());
+    crate::service::functions::save_function(org_id, transform, &user_id).await
 }
 
 /// ListFunctions
@@ -118,9 +123,13 @@ async fn list_functions(
     )
 )]
 #[delete("/{org_id}/functions/{name}")]
-async fn delete_function(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+async fn delete_function(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
     let (org_id, name) = path.into_inner();
-    crate::service::functions::delete_function(org_id, name).await
+    let user_id = get_user_id(req.headers());
+    crate::service::functions::delete_function(org_id, name, &user_id).await
 }
 
 /// UpdateFunction
@@ -145,13 +154,43 @@ async fn delete_function(path: web::Path<(String, String)>) -> Result<HttpRespon
 pub async fn update_function(
     path: web::Path<(String, String)>,
     func: web::Json<Transform>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, name) = path.into_inner();
     let name = name.trim();
     let mut transform = func.into_inner();
     transform.name = transform.name.trim().to_string();
     transform.function = transform.function.trim().to_string();
-    crate::service::functions::update_function(&org_id, name, transform).await
+    let user_id = get_user_id(req.headers());
+    crate::service::functions::update_function(&org_id, name, transform, &user_id).await
+}
+
+/// TestFunction
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Functions",
+    operation_id = "testFunction",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Function name"),
+    ),
+    request_body(content = FunctionTestRequest, description = "Sample records", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = FunctionTestResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/functions/{name}/_test")]
+pub async fn test_function(
+    path: web::Path<(String, String)>,
+    body: web::Json<FunctionTestRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    crate::service::functions::test_function(&org_id, name.trim(), body.into_inner().records).await
 }
 
 /// ListStreamFunctions
@@ -248,6 +287,7 @@ async fn delete_stream_function(
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
         (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Feature not enabled", content_type = "application/json", body = HttpResponse),
     )
 )]
 #[put("/{org_id}/streams/{stream_name}/functions/{name}")]
@@ -257,6 +297,9 @@ pub async fn add_function_to_stream(
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name, name) = path.into_inner();
+    if let Err(e) = crate::service::organization::check_feature(&org_id, Feature::StreamFunctions) {
+        return Ok(e.into());
+    }
     let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
     let stream_type = match get_stream_type_from_request(&query) {
         Ok(v) => v.unwrap_or_default(),
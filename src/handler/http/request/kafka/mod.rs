@@ -0,0 +1,130 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, kafka::KafkaSource},
+    service::kafka,
+};
+
+/// SaveKafkaSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Kafka",
+    operation_id = "SaveKafkaSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Source name"),
+    ),
+    request_body(content = KafkaSource, description = "Topics consumed into a log stream", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/kafka_sources/{name}")]
+pub async fn save_kafka_source(
+    path: web::Path<(String, String)>,
+    body: web::Json<KafkaSource>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match kafka::save(&org_id, &name, body.into_inner()).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Kafka source saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetKafkaSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Kafka",
+    operation_id = "GetKafkaSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Source name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = KafkaSource),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/kafka_sources/{name}")]
+pub async fn get_kafka_source(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match kafka::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListKafkaSources
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Kafka",
+    operation_id = "ListKafkaSources",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<KafkaSource>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/kafka_sources")]
+pub async fn list_kafka_sources(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match kafka::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteKafkaSource
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Kafka",
+    operation_id = "DeleteKafkaSource",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Source name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/kafka_sources/{name}")]
+pub async fn delete_kafka_source(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match kafka::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Kafka source deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        stream::{LegalHold, LegalHoldFiles},
+    },
+    service::compact::legal_holds,
+};
+
+/// CreateLegalHold
+#[utoipa::path(
+    context_path = "/api",
+    tag = "LegalHolds",
+    operation_id = "CreateLegalHold",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = LegalHold, description = "Stream and time range to hold", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = LegalHold),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/legal_holds")]
+pub async fn create_legal_hold(
+    path: web::Path<String>,
+    hold: web::Json<LegalHold>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_email = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match legal_holds::create(&org_id, hold.into_inner(), user_email).await {
+        Ok(hold) => Ok(MetaHttpResponse::json(hold)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListLegalHolds
+#[utoipa::path(
+    context_path = "/api",
+    tag = "LegalHolds",
+    operation_id = "ListLegalHolds",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<LegalHold>),
+    )
+)]
+#[get("/{org_id}/legal_holds")]
+pub async fn list_legal_holds(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    Ok(MetaHttpResponse::json(legal_holds::list(&org_id)))
+}
+
+/// ListLegalHoldFiles
+#[utoipa::path(
+    context_path = "/api",
+    tag = "LegalHolds",
+    operation_id = "ListLegalHoldFiles",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Legal hold id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = LegalHoldFiles),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/legal_holds/{id}/files")]
+pub async fn list_legal_hold_files(
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match legal_holds::files(&org_id, &id).await {
+        Ok(files) => Ok(MetaHttpResponse::json(files)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ReleaseLegalHold
+#[utoipa::path(
+    context_path = "/api",
+    tag = "LegalHolds",
+    operation_id = "ReleaseLegalHold",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Legal hold id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/legal_holds/{id}")]
+pub async fn release_legal_hold(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match legal_holds::release(&org_id, &id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Legal hold released")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -23,6 +23,7 @@ use crate::{
         http::HttpResponse as MetaHttpResponse,
         ingestion::{
             GCPIngestionRequest, IngestionRequest, KinesisFHIngestionResponse, KinesisFHRequest,
+            SimulationRequest,
         },
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
@@ -176,6 +177,41 @@ pub async fn json(
     )
 }
 
+/// _simulate runs records through the ingestion pipeline without writing them
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsIngestionSimulate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = SimulationRequest, description = "Sample records", content_type = "application/json", example = json!({"records": [{"level": "error", "message": "connection refused"}]})),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SimulationResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/_simulate")]
+pub async fn simulate(
+    path: web::Path<(String, String)>,
+    body: web::Json<SimulationRequest>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    Ok(
+        match logs::simulate::simulate(&org_id, &stream_name, body.into_inner().records).await {
+            Ok(v) => MetaHttpResponse::json(v),
+            Err(e) => {
+                log::error!("Error simulating ingestion: {:?}", e);
+                MetaHttpResponse::bad_request(e)
+            }
+        },
+    )
+}
+
 /// _kinesis_firehose ingestion API
 #[utoipa::path(
     context_path = "/api",
@@ -303,3 +339,74 @@ pub async fn otlp_logs_write(
         )))
     }
 }
+
+/// Loki push API compatible ingestion
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LokiPush",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = String, description = "Loki PushRequest, snappy compressed protobuf or json", content_type = "application/x-protobuf"),
+    responses(
+        (status = 204, description = "Success"),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/loki/api/v1/push")]
+pub async fn loki_push(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let user_email = req.headers().get("user_id").unwrap().to_str().unwrap();
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(CONTENT_TYPE_PROTO);
+    let streams = if content_type.starts_with(CONTENT_TYPE_JSON) {
+        logs::loki::decode_json(&body)
+    } else if content_type.eq(CONTENT_TYPE_PROTO) {
+        logs::loki::decode_protobuf(&body)
+    } else {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            format!("Unsupported content type: {content_type}"),
+        )));
+    };
+    let streams = match streams {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            )));
+        }
+    };
+    Ok(
+        match logs::loki::push(&org_id, streams, **thread_id, user_email).await {
+            Ok(v) => match v.code {
+                200 => HttpResponse::NoContent().finish(),
+                // the agents retry on 429 and the server errors only
+                code => HttpResponse::build(
+                    http::StatusCode::from_u16(code).unwrap_or(http::StatusCode::BAD_REQUEST),
+                )
+                .json(v),
+            },
+            Err(e) => {
+                log::error!("Error processing loki push request: {:?}", e);
+                HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                ))
+            }
+        },
+    )
+}
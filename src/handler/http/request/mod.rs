@@ -14,23 +14,38 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod alerts;
+pub mod archives;
 pub mod authz;
 pub mod clusters;
+pub mod continuous_queries;
+pub mod correlation;
 pub mod dashboards;
 pub mod enrichment_table;
 pub mod functions;
+pub mod kafka;
 pub mod kv;
+pub mod legal_holds;
 pub mod logs;
 pub mod metrics;
+pub mod monitors;
 pub mod organization;
 pub mod prom;
+pub mod quality_monitors;
+pub mod reprocess;
+pub mod revisions;
 pub mod rum;
 pub mod search;
+pub mod short_url;
+pub mod sigma;
+pub mod slo;
+pub mod snmp;
 pub mod status;
 pub mod stream;
 pub mod syslog;
+pub mod threat_intel;
 pub mod traces;
 pub mod users;
+pub mod webhooks;
 
 pub const CONTENT_TYPE_JSON: &str = "application/json";
 pub const CONTENT_TYPE_PROTO: &str = "application/x-protobuf";
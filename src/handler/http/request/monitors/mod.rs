@@ -0,0 +1,172 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, http, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, monitors::Monitor},
+    service::monitors,
+};
+
+/// CreateMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Monitors",
+    operation_id = "CreateMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = Monitor, description = "Monitor data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/monitors")]
+pub async fn save_monitor(
+    path: web::Path<String>,
+    monitor: web::Json<Monitor>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let monitor = monitor.into_inner();
+    match monitors::save(&org_id, "", monitor, true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Monitor saved")),
+        Err(e) => match e {
+            (http::StatusCode::BAD_REQUEST, e) => Ok(MetaHttpResponse::bad_request(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// UpdateMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Monitors",
+    operation_id = "UpdateMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("monitor_name" = String, Path, description = "Monitor name"),
+      ),
+    request_body(content = Monitor, description = "Monitor data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/monitors/{monitor_name}")]
+pub async fn update_monitor(
+    path: web::Path<(String, String)>,
+    monitor: web::Json<Monitor>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let monitor = monitor.into_inner();
+    let name = name.trim();
+    match monitors::save(&org_id, name, monitor, false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Monitor saved")),
+        Err(e) => match e {
+            (http::StatusCode::BAD_REQUEST, e) => Ok(MetaHttpResponse::bad_request(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// GetMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Monitors",
+    operation_id = "GetMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("monitor_name" = String, Path, description = "Monitor name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = Monitor),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/monitors/{monitor_name}")]
+async fn get_monitor(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match monitors::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(MetaHttpResponse::not_found(e)),
+    }
+}
+
+/// ListMonitors
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Monitors",
+    operation_id = "ListMonitors",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<Monitor>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/monitors")]
+async fn list_monitors(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match monitors::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// DeleteMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Monitors",
+    operation_id = "DeleteMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("monitor_name" = String, Path, description = "Monitor name"),
+    ),
+    responses(
+        (status = 200, description = "Success",   content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound",  content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/monitors/{monitor_name}")]
+async fn delete_monitor(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match monitors::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Monitor deleted")),
+        Err(e) => match e {
+            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
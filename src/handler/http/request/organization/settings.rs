@@ -15,7 +15,7 @@
 
 use std::io::Error as StdErr;
 
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use config::utils::json;
 use infra::errors::{DbError, Error};
 #[cfg(feature = "enterprise")]
@@ -26,11 +26,14 @@ use {
 };
 
 use crate::{
-    common::meta::{
-        http::HttpResponse as MetaHttpResponse,
-        organization::{OrganizationSetting, OrganizationSettingResponse},
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            organization::{OrgFeatureFlags, OrganizationSetting, OrganizationSettingResponse},
+        },
+        utils::auth::is_root_user,
     },
-    service::db::organization::{get_org_setting, set_org_setting},
+    service::db::organization::{get_features, get_org_setting, set_features, set_org_setting},
 };
 
 /// Organization specific settings
@@ -60,6 +63,14 @@ async fn create(
             "scrape_interval should be a positive value",
         ));
     }
+    if let Err(e) = crate::service::search::policy::validate(&settings.query_policies) {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    if let Some(template) = settings.stream_template.as_ref() {
+        if let Err(e) = crate::service::stream::validate_stream_template(template) {
+            return Ok(e.into());
+        }
+    }
 
     let org_id = path.into_inner();
     match set_org_setting(&org_id, &settings).await {
@@ -106,6 +117,70 @@ async fn get(path: web::Path<String>) -> Result<HttpResponse, StdErr> {
     }
 }
 
+/// Retrieve the features enabled for the organization
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationFeaturesGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = OrgFeatureFlags),
+    )
+)]
+#[get("/{org_id}/settings/features")]
+async fn get_feature_flags(path: web::Path<String>) -> Result<HttpResponse, StdErr> {
+    let org_id = path.into_inner();
+    let features = get_features(&org_id).resolved();
+    Ok(MetaHttpResponse::json(OrgFeatureFlags { features }))
+}
+
+/// Enable or disable features for the organization, the features it doesn't
+/// set use the defaults of the cluster
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Organizations",
+    operation_id = "OrganizationFeaturesUpdate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = OrgFeatureFlags, description = "Organization feature flags", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/settings/features")]
+async fn set_feature_flags(
+    path: web::Path<String>,
+    flags: web::Json<OrgFeatureFlags>,
+    req: HttpRequest,
+) -> Result<HttpResponse, StdErr> {
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can change the organization features",
+        ));
+    }
+    let org_id = path.into_inner();
+    match set_features(&org_id, &flags).await {
+        Ok(()) => Ok(MetaHttpResponse::ok("organization features updated")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
 #[cfg(feature = "enterprise")]
 #[post("/{org_id}/settings/logo")]
 async fn upload_logo(mut payload: Multipart) -> Result<HttpResponse, StdErr> {
@@ -0,0 +1,257 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse};
+use config::meta::stream::StreamType;
+
+use crate::{
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            quality_monitors::{Heartbeat, QualityMonitor},
+        },
+        utils::http::get_stream_type_from_request,
+    },
+    service::quality_monitors,
+};
+
+/// CreateQualityMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "CreateQualityMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+      ),
+    request_body(content = QualityMonitor, description = "Quality monitor data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/quality_monitors")]
+pub async fn save_quality_monitor(
+    path: web::Path<(String, String)>,
+    monitor: web::Json<QualityMonitor>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let mut monitor = monitor.into_inner();
+    monitor.stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match quality_monitors::save(&org_id, &stream_name, "", monitor, true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Quality monitor saved")),
+        Err(e) => match e {
+            (http::StatusCode::BAD_REQUEST, e) => Ok(MetaHttpResponse::bad_request(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// UpdateQualityMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "UpdateQualityMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("monitor_name" = String, Path, description = "Quality monitor name"),
+      ),
+    request_body(content = QualityMonitor, description = "Quality monitor data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/quality_monitors/{monitor_name}")]
+pub async fn update_quality_monitor(
+    path: web::Path<(String, String, String)>,
+    monitor: web::Json<QualityMonitor>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let mut monitor = monitor.into_inner();
+    monitor.stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let name = name.trim();
+    match quality_monitors::save(&org_id, &stream_name, name, monitor, false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Quality monitor saved")),
+        Err(e) => match e {
+            (http::StatusCode::BAD_REQUEST, e) => Ok(MetaHttpResponse::bad_request(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// GetQualityMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "GetQualityMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("monitor_name" = String, Path, description = "Quality monitor name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = QualityMonitor),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/quality_monitors/{monitor_name}")]
+async fn get_quality_monitor(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match quality_monitors::get(&org_id, stream_type, &stream_name, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(MetaHttpResponse::not_found(e)),
+    }
+}
+
+/// ListQualityMonitors
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "ListQualityMonitors",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<QualityMonitor>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/quality_monitors")]
+async fn list_quality_monitors(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match quality_monitors::list(&org_id, stream_type, &stream_name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// DeleteQualityMonitor
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "DeleteQualityMonitor",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("monitor_name" = String, Path, description = "Quality monitor name"),
+    ),
+    responses(
+        (status = 200, description = "Success",   content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound",  content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/quality_monitors/{monitor_name}")]
+async fn delete_quality_monitor(
+    path: web::Path<(String, String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, name) = path.into_inner();
+    let stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match quality_monitors::delete(&org_id, stream_type, &stream_name, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Quality monitor deleted")),
+        Err(e) => match e {
+            (http::StatusCode::NOT_FOUND, e) => Ok(MetaHttpResponse::not_found(e)),
+            (_, e) => Ok(MetaHttpResponse::internal_error(e)),
+        },
+    }
+}
+
+/// Heartbeat
+#[utoipa::path(
+    context_path = "/api",
+    tag = "QualityMonitors",
+    operation_id = "ShipperHeartbeat",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = Heartbeat, description = "Shipper identity", content_type = "application/json", example = json!({"shipper": "web-1/fluent-bit", "version": "2.2.0"})),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/{stream_name}/_heartbeat")]
+pub async fn heartbeat(
+    path: web::Path<(String, String)>,
+    heartbeat: web::Json<Heartbeat>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let stream_type = match stream_type_from_request(&req) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match quality_monitors::heartbeat(&org_id, stream_type, &stream_name, heartbeat.into_inner())
+        .await
+    {
+        Ok(_) => Ok(MetaHttpResponse::ok("Heartbeat received")),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+fn stream_type_from_request(req: &HttpRequest) -> Result<StreamType, Error> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    get_stream_type_from_request(&query).map(|v| v.unwrap_or_default())
+}
@@ -0,0 +1,108 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::io::Error;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::{functions::ReprocessJob, http::HttpResponse as MetaHttpResponse},
+    service::logs::reprocess,
+};
+
+/// CreateReprocessJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Reprocess",
+    operation_id = "CreateReprocessJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = ReprocessJob, description = "Lineage and time range of the records to reprocess", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ReprocessJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/reprocess")]
+pub async fn create_reprocess_job(
+    path: web::Path<String>,
+    job: web::Json<ReprocessJob>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_email = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match reprocess::create(&org_id, job.into_inner(), user_email).await {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListReprocessJobs
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Reprocess",
+    operation_id = "ListReprocessJobs",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<ReprocessJob>),
+    )
+)]
+#[get("/{org_id}/reprocess")]
+pub async fn list_reprocess_jobs(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match reprocess::list(&org_id).await {
+        Ok(jobs) => Ok(MetaHttpResponse::json(jobs)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetReprocessJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Reprocess",
+    operation_id = "GetReprocessJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Reprocess job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ReprocessJob),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/reprocess/{id}")]
+pub async fn get_reprocess_job(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match reprocess::get(&org_id, &id).await {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => Ok(e.into()),
+    }
+}
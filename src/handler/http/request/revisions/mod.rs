@@ -0,0 +1,222 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            revisions::{ObjectType, Revision, RevisionDiff, RevisionList},
+        },
+        utils::http::get_user_id,
+    },
+    service::revisions,
+};
+
+/// The object type of the path and the `id` query param.
+fn get_object(
+    object_type: &str,
+    query: &HashMap<String, String>,
+) -> Result<(ObjectType, String), HttpResponse> {
+    let object_type = object_type
+        .parse::<ObjectType>()
+        .map_err(MetaHttpResponse::bad_request)?;
+    match query.get("id") {
+        Some(id) if !id.is_empty() => Ok((object_type, id.to_string())),
+        _ => Err(MetaHttpResponse::bad_request(
+            "'id' query param is required",
+        )),
+    }
+}
+
+/// ListRevisions
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Revisions",
+    operation_id = "RevisionsList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("object_type" = String, Path, description = "dashboard, alert or function"),
+        ("id" = String, Query, description = "Object id: {folder}/{dashboard_id}, {stream_type}/{stream_name}/{alert_name} or the function name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RevisionList),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/revisions/{object_type}")]
+async fn list(
+    path: web::Path<(String, String)>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, object_type) = path.into_inner();
+    let (object_type, object_id) = match get_object(&object_type, &query) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    match revisions::list(&org_id, object_type, &object_id).await {
+        Ok(list) => Ok(MetaHttpResponse::json(RevisionList { list })),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetRevision
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Revisions",
+    operation_id = "RevisionGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("object_type" = String, Path, description = "dashboard, alert or function"),
+        ("revision" = i64, Path, description = "Revision"),
+        ("id" = String, Query, description = "Object id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Revision),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/revisions/{object_type}/{revision}")]
+async fn get(
+    path: web::Path<(String, String, i64)>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, object_type, revision) = path.into_inner();
+    let (object_type, object_id) = match get_object(&object_type, &query) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    match revisions::get(&org_id, object_type, &object_id, revision).await {
+        Ok(revision) => Ok(MetaHttpResponse::json(revision)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DiffRevisions
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Revisions",
+    operation_id = "RevisionDiff",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("object_type" = String, Path, description = "dashboard, alert or function"),
+        ("revision" = i64, Path, description = "Revision to diff from"),
+        ("id" = String, Query, description = "Object id"),
+        ("to" = Option<i64>, Query, description = "Revision to diff to, default is the latest"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RevisionDiff),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/revisions/{object_type}/{revision}/diff")]
+async fn diff(
+    path: web::Path<(String, String, i64)>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, object_type, revision) = path.into_inner();
+    let (object_type, object_id) = match get_object(&object_type, &query) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let to = match query.get("to").map(|v| v.parse::<i64>()).transpose() {
+        Ok(v) => v,
+        Err(_) => return Ok(MetaHttpResponse::bad_request("'to' must be a revision")),
+    };
+    match revisions::diff(&org_id, object_type, &object_id, revision, to).await {
+        Ok(diff) => Ok(MetaHttpResponse::json(diff)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// RestoreRevision
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Revisions",
+    operation_id = "RevisionRestore",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("object_type" = String, Path, description = "dashboard, alert or function"),
+        ("revision" = i64, Path, description = "Revision to restore"),
+        ("id" = String, Query, description = "Object id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/revisions/{object_type}/{revision}/restore")]
+async fn restore(
+    path: web::Path<(String, String, i64)>,
+    query: web::Query<HashMap<String, String>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, object_type, revision) = path.into_inner();
+    let (object_type, object_id) = match get_object(&object_type, &query) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let user_id = get_user_id(req.headers());
+    match revisions::restore(&org_id, object_type, &object_id, revision, &user_id).await {
+        Ok(_) => Ok(MetaHttpResponse::ok(format!("{object_type} restored"))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListDeletedObjects
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Revisions",
+    operation_id = "RecycleBinList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("object_type" = String, Path, description = "dashboard, alert or function"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RevisionList),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/recycle_bin/{object_type}")]
+async fn list_deleted(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, object_type) = path.into_inner();
+    let object_type = match object_type.parse::<ObjectType>() {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match revisions::list_deleted(&org_id, object_type).await {
+        Ok(list) => Ok(MetaHttpResponse::json(RevisionList { list })),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use config::meta::stream::StreamType;
+
+use crate::{
+    common::{
+        meta::{forecast::ForecastRequest, http::HttpResponse as MetaHttpResponse},
+        utils::http::get_stream_type_from_request,
+    },
+    service::forecast,
+};
+
+/// Forecast
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "Forecast",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+    ),
+    request_body(content = ForecastRequest, description = "Query of the time buckets and their forecast", content_type = "application/json", example = json!({
+        "sql": "SELECT histogram(_timestamp, '1 hour') AS zo_sql_key, host, MAX(used_percent) AS used FROM disk GROUP BY zo_sql_key, host",
+        "start_time": 1675182660872049i64,
+        "end_time": 1675785660872049i64,
+        "forecast": {
+            "value_column": "used",
+            "horizon": 10080,
+            "method": "linear",
+            "condition": {"column": "used", "operator": ">=", "value": 100}
+        }
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = ForecastResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_forecast")]
+pub async fn forecast(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    req: web::Json<ForecastRequest>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let stream_name = match config::meta::sql::Sql::new(&req.sql) {
+        Ok(v) => v.source,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if !super::check_stream_permission(&org_id, &user_id, stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    match forecast::search(&org_id, stream_type, Some(user_id), &req).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
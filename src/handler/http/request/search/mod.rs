@@ -16,7 +16,7 @@
 use std::{collections::HashMap, io::Error};
 
 use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
-use chrono::Duration;
+use chrono::{Duration, TimeZone, Utc};
 use config::{
     ider,
     meta::{
@@ -27,24 +27,31 @@ use config::{
     utils::{base64, json},
     CONFIG, DISTINCT_FIELDS,
 };
-use infra::{errors, schema::STREAM_SCHEMAS};
+use futures::StreamExt;
+use infra::schema::STREAM_SCHEMAS;
 use opentelemetry::{global, trace::TraceContextExt};
 use tracing::{Instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
     common::{
-        meta::{self, http::HttpResponse as MetaHttpResponse},
+        meta::{self, http::HttpResponse as MetaHttpResponse, organization::Feature},
         utils::{
             functions,
             http::{get_stream_type_from_request, RequestHeaderExtractor},
         },
     },
-    service::{search as SearchService, usage::report_request_usage_stats},
+    service::{
+        cdc, error::ServiceError, search as SearchService, stream,
+        usage::report_request_usage_stats,
+    },
 };
 
+pub mod forecast;
 pub mod job;
 pub mod saved_view;
+pub mod search_job;
+pub mod templates;
 
 /// SearchStreamData
 #[utoipa::path(
@@ -56,6 +63,8 @@ pub mod saved_view;
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("tiers" = Option<String>, Query, description = "hot (default) skips the data older than the hot days of the stream, all searches everything"),
+        ("highlight" = Option<bool>, Query, description = "Return the offsets of the searched terms in every hit"),
     ),
     request_body(content = SearchRequest, description = "Search query", content_type = "application/json", example = json!({
         "query": {
@@ -117,8 +126,33 @@ pub async fn search(
     in_req: HttpRequest,
     body: web::Bytes,
 ) -> Result<HttpResponse, Error> {
-    let start = std::time::Instant::now();
     let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    // handle encoding for query and aggs
+    let mut req: config::meta::search::Request = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+
+    search_request(org_id, &in_req, stream_type, req).await
+}
+
+/// Runs a decoded search request, the search templates are run by it too.
+pub(crate) async fn search_request(
+    org_id: String,
+    in_req: &HttpRequest,
+    stream_type: StreamType,
+    mut req: config::meta::search::Request,
+) -> Result<HttpResponse, Error> {
+    let start = std::time::Instant::now();
 
     let mut http_span = None;
     let trace_id = if CONFIG.common.tracing_enabled {
@@ -136,19 +170,6 @@ pub async fn search(
     };
 
     let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
-    let stream_type = match get_stream_type_from_request(&query) {
-        Ok(v) => v.unwrap_or(StreamType::Logs),
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
-    };
-
-    // handle encoding for query and aggs
-    let mut req: config::meta::search::Request = match json::from_slice(&body) {
-        Ok(v) => v,
-        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
-    };
-    if let Err(e) = req.decode() {
-        return Ok(MetaHttpResponse::bad_request(e));
-    }
 
     let user_id = in_req.headers().get("user_id").unwrap();
     let mut rpc_req: proto::cluster_rpc::SearchRequest = req.to_owned().into();
@@ -167,55 +188,65 @@ pub async fn search(
     };
 
     // Check permissions on stream
-    #[cfg(feature = "enterprise")]
+    if !check_stream_permission(
+        &org_id,
+        user_id.to_str().unwrap(),
+        stream_type,
+        &stream_name,
+    )
+    .await
     {
-        use crate::common::{
-            infra::config::USERS,
-            utils::auth::{is_root_user, AuthExtractor},
-        };
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
 
-        if !is_root_user(user_id.to_str().unwrap()) {
-            let user: meta::user::User = USERS
-                .get(&format!("{org_id}/{}", user_id.to_str().unwrap()))
-                .unwrap()
-                .clone();
-
-            if user.is_external
-                && !crate::handler::http::auth::validator::check_permissions(
-                    user_id.to_str().unwrap(),
-                    AuthExtractor {
-                        auth: "".to_string(),
-                        method: "GET".to_string(),
-                        o2_type: format!("{}:{}", stream_type, stream_name),
-                        org_id: org_id.clone(),
-                        bypass_check: false,
-                        parent_id: "".to_string(),
-                    },
-                    Some(user.role),
+    // the cold tier is only searched when the query asks for all the tiers
+    let tier_warning = match query.get("tiers").map(|v| v.as_str()) {
+        None | Some("hot") => {
+            let hot_start = match stream::hot_tier_start(&org_id, &stream_name, stream_type).await {
+                Ok(v) => v,
+                Err(e) => return Ok(e.into()),
+            };
+            let cut = hot_start.map(|hot_start| {
+                (
+                    hot_start,
+                    stream::cut_to_hot_tier(req.query.start_time, req.query.end_time, hot_start),
                 )
-                .await
-            {
-                return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+            });
+            match cut {
+                None | Some((_, stream::HotTierCut::Hot)) => None,
+                Some((hot_start, cut)) => {
+                    let warning = format!(
+                        "data before {} is in the cold tier and was not searched, use tiers=all to search it",
+                        Utc.timestamp_nanos(hot_start * 1000).to_rfc3339()
+                    );
+                    if cut == stream::HotTierCut::Cold {
+                        let mut res = config::meta::search::Response::new(
+                            req.query.from as usize,
+                            req.query.size as usize,
+                        );
+                        res.set_trace_id(trace_id);
+                        res.set_warning(warning);
+                        return Ok(HttpResponse::Ok().json(res));
+                    }
+                    req.query.start_time = hot_start;
+                    Some(warning)
+                }
             }
         }
-        // Check permissions on stream ends
-    }
-
-    let mut query_fn = req.query.query_fn.and_then(|v| base64::decode_url(&v).ok());
-
-    if let Some(vrl_function) = &query_fn {
-        if !vrl_function.trim().ends_with('.') {
-            query_fn = Some(format!("{} \n .", vrl_function));
+        Some("all") => None,
+        Some(v) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "invalid tiers: {v}, expected hot or all"
+            )));
         }
-    }
-    req.query.query_fn = query_fn;
+    };
 
-    for fn_name in functions::get_all_transform_keys(&org_id).await {
-        if req.query.sql.contains(&format!("{}(", fn_name)) {
-            req.query.uses_zo_fn = true;
-            break;
-        }
-    }
+    let highlight = query
+        .get("highlight")
+        .map(|v| v.parse::<bool>().unwrap_or_default())
+        .unwrap_or_default();
+
+    prepare_functions(&org_id, &mut req).await;
 
     // get a local search queue lock
     #[cfg(not(feature = "enterprise"))]
@@ -268,6 +299,19 @@ pub async fn search(
                 .inc();
             res.set_trace_id(trace_id);
             res.set_local_took(start.elapsed().as_millis() as usize, took_wait);
+            if let Some(warning) = tier_warning {
+                res.set_warning(warning);
+            }
+            if highlight {
+                SearchService::highlight::highlight(
+                    &org_id,
+                    stream_type,
+                    &stream_name,
+                    &req.query.sql,
+                    &mut res,
+                )
+                .await;
+            }
 
             let req_stats = RequestStats {
                 records: res.hits.len() as i64,
@@ -292,11 +336,12 @@ pub async fn search(
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_search",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
@@ -305,33 +350,174 @@ pub async fn search(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_search",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search error: {:?}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
+            Ok(err.into_http_response(Some(trace_id)))
+        }
+    }
+}
+
+/// External users need a permission on the stream to search it, the `_cdc`
+/// stream is searched by the admins only
+async fn check_stream_permission(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> bool {
+    if !cdc::can_read(org_id, user_id, stream_type, stream_name).await {
+        return false;
+    }
+
+    #[cfg(feature = "enterprise")]
+    {
+        use crate::common::{
+            infra::config::USERS,
+            utils::auth::{is_root_user, AuthExtractor},
+        };
+
+        if is_root_user(user_id) {
+            return true;
+        }
+        let user: meta::user::User = USERS.get(&format!("{org_id}/{user_id}")).unwrap().clone();
+        !user.is_external
+            || crate::handler::http::auth::validator::check_permissions(
+                user_id,
+                AuthExtractor {
+                    auth: "".to_string(),
+                    method: "GET".to_string(),
+                    o2_type: format!("{}:{}", stream_type, stream_name),
+                    org_id: org_id.to_string(),
+                    bypass_check: false,
+                    parent_id: "".to_string(),
                 },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+                Some(user.role),
+            )
+            .await
+    }
+    #[cfg(not(feature = "enterprise"))]
+    true
+}
+
+/// Decodes the VRL function of the query and flags the SQL calling the
+/// transform functions of the org
+async fn prepare_functions(org_id: &str, req: &mut config::meta::search::Request) {
+    let mut query_fn = req
+        .query
+        .query_fn
+        .take()
+        .and_then(|v| base64::decode_url(&v).ok());
+
+    if let Some(vrl_function) = &query_fn {
+        if !vrl_function.trim().ends_with('.') {
+            query_fn = Some(format!("{} \n .", vrl_function));
+        }
+    }
+    req.query.query_fn = query_fn;
+
+    for fn_name in functions::get_all_transform_keys(org_id).await {
+        if req.query.sql.contains(&format!("{}(", fn_name)) {
+            req.query.uses_zo_fn = true;
+            break;
         }
     }
 }
 
+/// SearchStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchSQLStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("tiers" = Option<String>, Query, description = "hot (default) skips the data older than the hot days of the stream, all searches everything"),
+    ),
+    request_body(content = SearchRequest, description = "Search query, size limits the hits streamed and zero streams all of them", content_type = "application/json", example = json!({
+        "query": {
+            "sql": "select * from k8s ",
+            "start_time": 1675182660872049i64,
+            "end_time": 1675185660872049i64,
+            "size": 0
+        }
+    })),
+    responses(
+        (status = 200, description = "One hit per line, an error ends the stream with a line holding it", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Feature not enabled", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search_stream")]
+pub async fn search_stream(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    if let Err(e) = crate::service::organization::check_feature(&org_id, Feature::Tail) {
+        return Ok(e.into());
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let mut req: config::meta::search::Request = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    let stream_name = match config::meta::sql::Sql::new(&req.query.sql) {
+        Ok(v) => v.source,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    if !check_stream_permission(&org_id, &user_id, stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    match query.get("tiers").map(|v| v.as_str()) {
+        None | Some("hot") => {
+            match stream::hot_tier_start(&org_id, &stream_name, stream_type).await {
+                Ok(Some(hot_start)) => {
+                    req.query.start_time = req.query.start_time.max(hot_start);
+                }
+                Ok(None) => {}
+                Err(e) => return Ok(e.into()),
+            }
+        }
+        Some("all") => {}
+        Some(v) => {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "invalid tiers: {v}, expected hot or all"
+            )));
+        }
+    }
+    prepare_functions(&org_id, &mut req).await;
+
+    let hits =
+        SearchService::streaming::search(ider::uuid(), org_id, stream_type, Some(user_id), req);
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(hits.map(Ok::<_, Error>)))
+}
+
 /// SearchAround
 #[utoipa::path(
     context_path = "/api",
@@ -409,6 +595,21 @@ pub async fn around(
         Ok(v) => v.unwrap_or(StreamType::Logs),
         Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
     };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    if !check_stream_permission(
+        &org_id,
+        user_id.as_deref().unwrap_or_default(),
+        stream_type,
+        &stream_name,
+    )
+    .await
+    {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
 
     let around_key = match query.get("key") {
         Some(v) => v.parse::<i64>().unwrap_or(0),
@@ -480,19 +681,16 @@ pub async fn around(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
         clusters: vec![],
         timeout,
     };
-    let user_id = in_req
-        .headers()
-        .get("user_id")
-        .unwrap()
-        .to_str()
-        .ok()
-        .map(|v| v.to_string());
     let search_fut = SearchService::search(&trace_id, &org_id, stream_type, user_id.clone(), &req);
     let search_res = if !CONFIG.common.tracing_enabled && CONFIG.common.tracing_search_enabled {
         search_fut.instrument(http_span.clone().unwrap()).await
@@ -502,11 +700,12 @@ pub async fn around(
     let resp_forward = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
@@ -515,29 +714,14 @@ pub async fn around(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(err.into_http_response(Some(trace_id)));
         }
     };
 
@@ -558,6 +742,10 @@ pub async fn around(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -573,11 +761,12 @@ pub async fn around(
     let resp_backward = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
@@ -586,29 +775,14 @@ pub async fn around(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(err.into_http_response(Some(trace_id)));
         }
     };
 
@@ -676,6 +850,226 @@ pub async fn around(
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// SearchContext
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchContext",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "stream_name name"),
+        ("timestamp" = i64, Query, description = "timestamp of the record, microseconds"),
+        ("fields" = Option<String>, Query, description = "identity fields of the source, split by comma, the value of every field is given by a query param of the same name, eg: fields=host,container&host=web-1&container=api"),
+        ("before" = Option<usize>, Query, description = "number of events before the record, default 10"),
+        ("after" = Option<usize>, Query, description = "number of events after the record, including the record itself, default 10"),
+        ("window" = Option<i64>, Query, description = "seconds searched on each side of the record, default 900"),
+        ("timeout" = Option<i64>, Query, description = "timeout, seconds"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_context")]
+pub async fn context(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let start = std::time::Instant::now();
+    let (org_id, stream_name) = path.into_inner();
+    let trace_id = ider::uuid();
+
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    if !check_stream_permission(
+        &org_id,
+        user_id.as_deref().unwrap_or_default(),
+        stream_type,
+        &stream_name,
+    )
+    .await
+    {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    let timestamp = match query.get("timestamp").map(|v| v.parse::<i64>()) {
+        Some(Ok(v)) if v > 0 => v,
+        _ => return Ok(MetaHttpResponse::bad_request("timestamp is empty")),
+    };
+    let mut source = Vec::new();
+    for field in query
+        .get("fields")
+        .map(|v| v.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()))
+        .into_iter()
+        .flatten()
+    {
+        if !field
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+        {
+            return Ok(MetaHttpResponse::bad_request(format!(
+                "invalid field: {field}"
+            )));
+        }
+        match query.get(field) {
+            Some(value) => source.push((field, value.as_str())),
+            None => {
+                return Ok(MetaHttpResponse::bad_request(format!(
+                    "value of field {field} is empty"
+                )));
+            }
+        }
+    }
+    let max_size = CONFIG.limit.query_full_mode_limit;
+    let before = query
+        .get("before")
+        .map_or(10, |v| v.parse::<usize>().unwrap_or(10))
+        .min(max_size);
+    let after = query
+        .get("after")
+        .map_or(10, |v| v.parse::<usize>().unwrap_or(10))
+        .min(max_size);
+    let window = query
+        .get("window")
+        .map_or(900, |v| v.parse::<i64>().unwrap_or(900))
+        .max(1);
+    let window = Duration::try_seconds(window)
+        .and_then(|v| v.num_microseconds())
+        .unwrap_or(i64::MAX / 2);
+    let timeout = query
+        .get("timeout")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+
+    let context_sql = build_context_sql(&stream_name, &source);
+
+    // get a local search queue lock
+    let locker = SearchService::QUEUE_LOCKER.clone();
+    let _locker = locker.lock().await;
+
+    // the events leading up to the record, and the record with the ones following it
+    let sides = [
+        (before, timestamp.saturating_sub(window), timestamp, "DESC"),
+        (after, timestamp, timestamp.saturating_add(window), "ASC"),
+    ];
+    let mut results = Vec::with_capacity(sides.len());
+    for (size, start_time, end_time, order) in sides {
+        if size == 0 {
+            results.push(config::meta::search::Response::default());
+            continue;
+        }
+        let req = config::meta::search::Request {
+            query: config::meta::search::Query {
+                sql: context_sql.clone(),
+                size,
+                start_time,
+                end_time,
+                sort_by: Some(format!("{} {order}", CONFIG.common.column_timestamp)),
+                ..Default::default()
+            },
+            aggs: HashMap::new(),
+            encoding: config::meta::search::RequestEncoding::Empty,
+            clusters: vec![],
+            timeout,
+        };
+        match SearchService::search(&trace_id, &org_id, stream_type, user_id.clone(), &req).await {
+            Ok(res) => results.push(res),
+            Err(err) => {
+                let err = ServiceError::from(err);
+                metrics::HTTP_INCOMING_REQUESTS
+                    .with_label_values(&[
+                        "/api/org/_context",
+                        err.status_code().as_str(),
+                        &org_id,
+                        &stream_name,
+                        stream_type.to_string().as_str(),
+                    ])
+                    .inc();
+                log::error!("search context error: {:?}", err);
+                return Ok(err.into_http_response(Some(trace_id)));
+            }
+        }
+    }
+
+    // merge, oldest first
+    let resp_after = results.pop().unwrap();
+    let resp_before = results.pop().unwrap();
+    let mut resp = config::meta::search::Response::new(0, before + after);
+    resp.hits = resp_before.hits.into_iter().rev().collect();
+    resp.hits.extend(resp_after.hits);
+    resp.total = resp.hits.len();
+    resp.scan_size = resp_before.scan_size + resp_after.scan_size;
+    resp.scan_records = resp_before.scan_records + resp_after.scan_records;
+    resp.took = resp_before.took + resp_after.took;
+    resp.set_trace_id(trace_id);
+
+    let time = start.elapsed().as_secs_f64();
+    metrics::HTTP_RESPONSE_TIME
+        .with_label_values(&[
+            "/api/org/_context",
+            "200",
+            &org_id,
+            &stream_name,
+            stream_type.to_string().as_str(),
+        ])
+        .observe(time);
+    metrics::HTTP_INCOMING_REQUESTS
+        .with_label_values(&[
+            "/api/org/_context",
+            "200",
+            &org_id,
+            &stream_name,
+            stream_type.to_string().as_str(),
+        ])
+        .inc();
+
+    let req_stats = RequestStats {
+        records: resp.hits.len() as i64,
+        response_time: time,
+        size: resp.scan_size as f64,
+        request_body: Some(context_sql),
+        user_email: user_id,
+        min_ts: Some(timestamp.saturating_sub(window)),
+        max_ts: Some(timestamp.saturating_add(window)),
+        ..Default::default()
+    };
+    report_request_usage_stats(
+        req_stats,
+        &org_id,
+        &stream_name,
+        stream_type,
+        UsageType::SearchAround,
+        0,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Builds the sql selecting the events with the given identity field values
+fn build_context_sql(stream_name: &str, source: &[(&str, &str)]) -> String {
+    let sql = format!("SELECT * FROM \"{stream_name}\"");
+    if source.is_empty() {
+        return sql;
+    }
+    let conditions = source
+        .iter()
+        .map(|(field, value)| format!("\"{field}\" = '{}'", value.replace('\'', "''")))
+        .collect::<Vec<_>>();
+    format!("{sql} WHERE {}", conditions.join(" AND "))
+}
+
 /// SearchTopNValues
 #[utoipa::path(
     context_path = "/api",
@@ -734,6 +1128,9 @@ pub async fn values(
         Some(v) => v.to_str().unwrap(),
         None => "",
     };
+    if !check_stream_permission(&org_id, user_id, stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
     let mut http_span = None;
     let trace_id = if CONFIG.common.tracing_enabled {
         let ctx = global::get_text_map_propagator(|propagator| {
@@ -917,6 +1314,10 @@ async fn values_v1(
             uses_zo_fn: uses_fn,
             query_fn: query_fn.clone(),
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -961,11 +1362,12 @@ async fn values_v1(
     let resp_search = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_values/v1",
-                    "500",
+                    err.status_code().as_str(),
                     org_id,
                     stream_name,
                     stream_type.to_string().as_str(),
@@ -974,29 +1376,14 @@ async fn values_v1(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_values/v1",
-                    "500",
+                    err.status_code().as_str(),
                     org_id,
                     stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search values error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(err.into_http_response(Some(trace_id)));
         }
     };
 
@@ -1128,6 +1515,10 @@ async fn values_v2(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -1149,11 +1540,12 @@ async fn values_v2(
     let resp_search = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_values/v2",
-                    "500",
+                    err.status_code().as_str(),
                     org_id,
                     stream_name,
                     stream_type.to_string().as_str(),
@@ -1162,29 +1554,14 @@ async fn values_v2(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_values/v2",
-                    "500",
+                    err.status_code().as_str(),
                     org_id,
                     stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search values error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code_with_trace_id(
-                            code,
-                            Some(trace_id),
-                        )),
-                    _ => HttpResponse::InternalServerError().json(
-                        meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                    ),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(err.into_http_response(Some(trace_id)));
         }
     };
 
@@ -1343,11 +1720,12 @@ pub async fn search_partition(
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_search_partition",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
@@ -1356,22 +1734,154 @@ pub async fn search_partition(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_search_partition",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search error: {:?}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError().json(
-                    meta::http::HttpResponse::error_code_with_trace_id(code, Some(trace_id)),
-                ),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+            Ok(err.into_http_response(Some(trace_id)))
         }
     }
 }
+
+/// GetLargeField
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetLargeField",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("reference" = String, Path, description = "Reference of the offloaded value, {stream_type}/{stream_name}/{YYYY/MM/DD}/{hash}"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "text/plain", body = String),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/large_fields/{reference:.*}")]
+pub async fn get_large_field(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, reference) = path.into_inner();
+    if let Err(e) = crate::service::organization::check_feature(&org_id, Feature::LargeFields) {
+        return Ok(e.into());
+    }
+    match crate::service::large_fields::get(&org_id, &reference).await {
+        Ok(data) => Ok(HttpResponse::Ok().content_type("text/plain").body(data)),
+        Err(e) => Ok(MetaHttpResponse::not_found(e)),
+    }
+}
+
+/// GetOriginalRecord
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetOriginalRecord",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("id" = String, Path, description = "Original record id, the `_original_id` of the record"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_original/{id}")]
+pub async fn get_original_record(
+    path: web::Path<(String, String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, id) = path.into_inner();
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    if !check_stream_permission(&org_id, &user_id, StreamType::Logs, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    match crate::service::original::get(&org_id, &user_id, &stream_name, &id).await {
+        Ok(Some(record)) => Ok(MetaHttpResponse::json(record)),
+        Ok(None) => Ok(MetaHttpResponse::not_found(format!(
+            "original record {id} not found"
+        ))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetRecord
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetRecord",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("id" = String, Path, description = "Record id, the `_record_id` of the record"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Object),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/_doc/{id}")]
+pub async fn get_record(
+    path: web::Path<(String, String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, id) = path.into_inner();
+    let user_id = in_req
+        .headers()
+        .get("user_id")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    if !check_stream_permission(&org_id, &user_id, StreamType::Logs, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    match crate::service::record_id::get(&org_id, &user_id, &stream_name, &id).await {
+        Ok(Some(record)) => Ok(MetaHttpResponse::json(record)),
+        Ok(None) => Ok(MetaHttpResponse::not_found(format!(
+            "record {id} not found"
+        ))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_context_sql() {
+        assert_eq!(
+            build_context_sql("default", &[]),
+            "SELECT * FROM \"default\""
+        );
+        assert_eq!(
+            build_context_sql("default", &[("host", "web-1"), ("container", "it's")]),
+            "SELECT * FROM \"default\" WHERE \"host\" = 'web-1' AND \"container\" = 'it''s'"
+        );
+    }
+}
@@ -13,22 +13,28 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 
 use crate::{
     common::{
+        infra::config::USERS,
         meta::{
             authz::Authz,
+            dashboards::DEFAULT_FOLDER,
             http::HttpResponse as MetaHttpResponse,
             saved_view::{
                 CreateViewRequest, CreateViewResponse, DeleteViewResponse, UpdateViewRequest, View,
             },
         },
-        utils::auth::{remove_ownership, set_ownership},
+        utils::{
+            auth::{is_root_user, remove_ownership, set_ownership, AuthExtractor},
+            http::get_tags,
+        },
     },
-    service::db::saved_view,
+    handler::http::auth::validator::check_permissions,
+    service::db::{dashboards::folders, saved_view},
 };
 
 // GetSavedView
@@ -83,6 +89,8 @@ pub async fn get_view(path: web::Path<(String, String)>) -> Result<HttpResponse,
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("folder" = Option<String>, Query, description = "Only list the views in this folder"),
+        ("tags" = Option<String>, Query, description = "Comma separated tags the views must all have"),
     ),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = ViewsWithoutData, example = json!([{
@@ -96,9 +104,20 @@ pub async fn get_view(path: web::Path<(String, String)>) -> Result<HttpResponse,
     )
 )]
 #[get("/{org_id}/savedviews")]
-pub async fn get_views(path: web::Path<String>) -> Result<HttpResponse, Error> {
+pub async fn get_views(
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
-    match saved_view::get_views_list_only(&org_id).await {
+    let folder = query.get("folder").map(|f| f.as_str());
+    if let Some(folder_id) = folder {
+        if !can_use_folder(&org_id, user_id(&req), folder_id, "LIST").await {
+            return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+        }
+    }
+    let tags = get_tags(&query);
+    match saved_view::get_views_list_only(&org_id, folder, &tags).await {
         Ok(views) => Ok(MetaHttpResponse::json(views)),
         Err(e) => Ok(MetaHttpResponse::bad_request(e)),
     }
@@ -171,8 +190,12 @@ pub async fn delete_view(path: web::Path<(String, String)>) -> Result<HttpRespon
 pub async fn create_view(
     path: web::Path<String>,
     view: web::Json<CreateViewRequest>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let org_id = path.into_inner();
+    if let Some(resp) = check_folder(&org_id, user_id(&req), &view.folder_id).await {
+        return Ok(resp);
+    }
 
     match saved_view::set_view(&org_id, &view).await {
         Ok(created_view) => {
@@ -218,8 +241,14 @@ pub async fn create_view(
 pub async fn update_view(
     path: web::Path<(String, String)>,
     view: web::Json<UpdateViewRequest>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, view_id) = path.into_inner();
+    if let Some(folder_id) = &view.folder_id {
+        if let Some(resp) = check_folder(&org_id, user_id(&req), folder_id).await {
+            return Ok(resp);
+        }
+    }
 
     match saved_view::update_view(&org_id, &view_id, &view).await {
         Ok(updated_view) => Ok(MetaHttpResponse::json(updated_view)),
@@ -227,6 +256,51 @@ pub async fn update_view(
     }
 }
 
+/// Views can only be put in existing folders the user can create objects in,
+/// the default folder is created on demand.
+async fn check_folder(org_id: &str, user_id: &str, folder_id: &str) -> Option<HttpResponse> {
+    if folder_id != DEFAULT_FOLDER && folders::get(org_id, folder_id).await.is_err() {
+        return Some(MetaHttpResponse::not_found(format!(
+            "Folder {folder_id} not found"
+        )));
+    }
+    if !can_use_folder(org_id, user_id, folder_id, "POST").await {
+        return Some(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+    None
+}
+
+/// Checks the permission of the user on the folder, as it is checked for the
+/// dashboards in it.
+async fn can_use_folder(org_id: &str, user_id: &str, folder_id: &str, method: &str) -> bool {
+    if is_root_user(user_id) {
+        return true;
+    }
+    let role = USERS
+        .get(&format!("{org_id}/{user_id}"))
+        .map(|user| user.role.clone());
+    check_permissions(
+        user_id,
+        AuthExtractor {
+            auth: "".to_string(),
+            method: method.to_string(),
+            o2_type: format!("dfolder:{folder_id}"),
+            org_id: org_id.to_string(),
+            bypass_check: false,
+            parent_id: "".to_string(),
+        },
+        role,
+    )
+    .await
+}
+
+fn user_id(req: &HttpRequest) -> &str {
+    req.headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use actix_web::{test, App};
@@ -238,6 +312,8 @@ mod tests {
         let payload = CreateViewRequest {
             data: "base64-encoded-data".into(),
             view_name: "query-for-blah".into(),
+            folder_id: DEFAULT_FOLDER.to_string(),
+            tags: vec![],
         };
         let app = test::init_service(App::new().service(create_view)).await;
         let req = test::TestRequest::post()
@@ -0,0 +1,274 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use config::{
+    meta::{search, stream::StreamType},
+    utils::json,
+};
+
+use super::{check_stream_permission, prepare_functions};
+use crate::{
+    common::{
+        meta::{http::HttpResponse as MetaHttpResponse, search_jobs::SearchJobState},
+        utils::http::get_stream_type_from_request,
+    },
+    service::search::jobs,
+};
+
+/// SubmitSearchJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SubmitSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+    ),
+    request_body(content = SearchRequest, description = "Search query, size limits the hits kept and zero keeps up to ZO_SEARCH_JOB_MAX_HITS", content_type = "application/json", example = json!({
+        "query": {
+            "sql": "select * from k8s where code = 500",
+            "start_time": 1659182660872049i64,
+            "end_time": 1675185660872049i64,
+            "size": 0
+        }
+    })),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/search_jobs")]
+pub async fn submit_job(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let mut req: search::Request = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(MetaHttpResponse::bad_request(e));
+    }
+    let user_id = get_user_id(&in_req);
+
+    let stream_name = match config::meta::sql::Sql::new(&req.query.sql) {
+        Ok(v) => v.source,
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if !check_stream_permission(&org_id, user_id, stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    prepare_functions(&org_id, &mut req).await;
+    match jobs::create(&org_id, stream_type, req, user_id).await {
+        Ok(job) => Ok(MetaHttpResponse::json(SearchJobState::from(job))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+fn get_user_id(in_req: &HttpRequest) -> &str {
+    in_req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// ListSearchJobs
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "ListSearchJobs",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "The jobs of the user, all of them for the admins", content_type = "application/json", body = Vec<SearchJobState>),
+    )
+)]
+#[get("/{org_id}/search_jobs")]
+pub async fn list_jobs(
+    path: web::Path<String>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match jobs::list(&org_id, get_user_id(&in_req)).await {
+        Ok(jobs) => Ok(MetaHttpResponse::json(jobs)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSearchJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Search job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchJobState),
+        (status = 403, description = "Created by another user", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/search_jobs/{id}")]
+pub async fn get_job(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match jobs::get(&org_id, &id, get_user_id(&in_req)).await {
+        Ok(job) => Ok(MetaHttpResponse::json(SearchJobState::from(job))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSearchJobResults
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetSearchJobResults",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Search job id"),
+        ("from" = Option<usize>, Query, description = "First hit of the page, 0 by default"),
+        ("size" = Option<usize>, Query, description = "Hits of the page, 100 by default"),
+    ),
+    responses(
+        (status = 200, description = "The hits found so far, also while the job runs", content_type = "application/json", body = SearchJobResults),
+        (status = 403, description = "Created by another user or unauthorized", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/search_jobs/{id}/results")]
+pub async fn get_job_results(
+    path: web::Path<(String, String)>,
+    query: web::Query<HashMap<String, String>>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    let user_id = get_user_id(&in_req);
+    let job = match jobs::get(&org_id, &id, user_id).await {
+        Ok(job) => job,
+        Err(e) => return Ok(e.into()),
+    };
+
+    // the access to the stream may have been revoked since the job was created
+    let stream_name = config::meta::sql::Sql::new(&job.request.query.sql)
+        .map(|v| v.source)
+        .unwrap_or_default();
+    if !check_stream_permission(&org_id, user_id, job.stream_type, &stream_name).await {
+        return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+    }
+
+    let from = query
+        .get("from")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let size = query
+        .get("size")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(100)
+        .min(config::CONFIG.limit.query_stream_page_size);
+    match jobs::results(&org_id, &job, from, size).await {
+        Ok(results) => Ok(MetaHttpResponse::json(results)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// CancelSearchJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "CancelSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Search job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchJobState),
+        (status = 403, description = "Created by another user", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "Already finished", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/search_jobs/{id}/cancel")]
+pub async fn cancel_job(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match jobs::cancel(&org_id, &id, get_user_id(&in_req)).await {
+        Ok(job) => Ok(MetaHttpResponse::json(SearchJobState::from(job))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteSearchJob
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "DeleteSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Search job id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Created by another user", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/search_jobs/{id}")]
+pub async fn delete_job(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, id) = path.into_inner();
+    match jobs::delete(&org_id, &id, get_user_id(&in_req)).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Search job deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::meta::{
+        http::HttpResponse as MetaHttpResponse,
+        search_templates::{SearchTemplate, SearchTemplateList, SearchTemplateRequest},
+    },
+    service::search_templates,
+};
+
+/// CreateSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "CreateSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = SearchTemplate, description = "Search template data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/search_templates")]
+pub async fn save_template(
+    path: web::Path<String>,
+    template: web::Json<SearchTemplate>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match search_templates::save(&org_id, "", template.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Search template saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "UpdateSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+      ),
+    request_body(content = SearchTemplate, description = "Search template data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/search_templates/{name}")]
+pub async fn update_template(
+    path: web::Path<(String, String)>,
+    template: web::Json<SearchTemplate>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match search_templates::save(&org_id, name.trim(), template.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Search template saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "GetSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = SearchTemplate),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/search_templates/{name}")]
+async fn get_template(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match search_templates::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListSearchTemplates
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "ListSearchTemplates",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SearchTemplateList),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/search_templates")]
+async fn list_templates(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match search_templates::list(&org_id).await {
+        Ok(list) => Ok(MetaHttpResponse::json(SearchTemplateList { list })),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteSearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "DeleteSearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/search_templates/{name}")]
+async fn delete_template(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match search_templates::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Search template deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// SearchTemplate
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search Templates",
+    operation_id = "SearchTemplate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Search template name"),
+        ("tiers" = Option<String>, Query, description = "hot (default) skips the data older than the hot days of the stream, all searches everything"),
+        ("highlight" = Option<bool>, Query, description = "Return the offsets of the searched terms in every hit"),
+    ),
+    request_body(content = SearchTemplateRequest, description = "Template parameters and time range", content_type = "application/json", example = json!({
+        "params": {"service": "checkout", "min_code": 500},
+        "start_time": 1675182660872049i64,
+        "end_time": 1675185660872049i64,
+        "size": 10
+    })),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = SearchResponse),
+        (status = 400, description = "Failure",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search/template/{name}")]
+pub async fn search_template(
+    path: web::Path<(String, String)>,
+    body: web::Json<SearchTemplateRequest>,
+    in_req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let (stream_type, req) =
+        match search_templates::build_request(&org_id, &name, body.into_inner()).await {
+            Ok(v) => v,
+            Err(e) => return Ok(e.into()),
+        };
+    super::search_request(org_id, &in_req, stream_type, req).await
+}
@@ -0,0 +1,52 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{get, http::header, web, HttpResponse};
+
+use crate::{common::meta::http::HttpResponse as MetaHttpResponse, service::short_url};
+
+/// ResolveShortUrl
+///
+/// Redirects to the url behind a short link, e.g. the search of an alert
+/// notification. Needs no credentials, the link is signed and the target page
+/// asks for them.
+#[utoipa::path(
+    path = "/short/{org_id}/{token}",
+    tag = "Meta",
+    operation_id = "ResolveShortUrl",
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("token" = String, Path, description = "Signed short link token"),
+    ),
+    responses(
+        (status = 302, description = "Redirect to the linked url"),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{token}")]
+pub async fn resolve(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, token) = path.into_inner();
+    match short_url::resolve(&org_id, &token).await {
+        Ok(Some(url)) => Ok(HttpResponse::Found()
+            .append_header((header::LOCATION, url))
+            .finish()),
+        Ok(None) => Ok(MetaHttpResponse::not_found(
+            "short url not found or expired",
+        )),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
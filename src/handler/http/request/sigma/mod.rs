@@ -0,0 +1,198 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+
+use crate::{
+    common::{
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            sigma::{MappingProfile, SigmaImportRequest, SigmaImportResponse},
+        },
+        utils::http::get_user_id,
+    },
+    service::sigma,
+};
+
+/// CreateMappingProfile
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "CreateMappingProfile",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = MappingProfile, description = "Mapping profile data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/sigma/profiles")]
+pub async fn save_mapping_profile(
+    path: web::Path<String>,
+    profile: web::Json<MappingProfile>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match sigma::save_profile(&org_id, "", profile.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Mapping profile saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateMappingProfile
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "UpdateMappingProfile",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Mapping profile name"),
+      ),
+    request_body(content = MappingProfile, description = "Mapping profile data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/sigma/profiles/{name}")]
+pub async fn update_mapping_profile(
+    path: web::Path<(String, String)>,
+    profile: web::Json<MappingProfile>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match sigma::save_profile(&org_id, name.trim(), profile.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Mapping profile saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetMappingProfile
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "GetMappingProfile",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Mapping profile name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = MappingProfile),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/sigma/profiles/{name}")]
+async fn get_mapping_profile(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match sigma::get_profile(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListMappingProfiles
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "ListMappingProfiles",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<MappingProfile>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/sigma/profiles")]
+async fn list_mapping_profiles(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match sigma::list_profiles(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteMappingProfile
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "DeleteMappingProfile",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Mapping profile name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/sigma/profiles/{name}")]
+async fn delete_mapping_profile(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match sigma::delete_profile(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Mapping profile deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ImportSigmaRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sigma",
+    operation_id = "ImportSigmaRules",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = SigmaImportRequest, description = "Sigma rules to import", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SigmaImportResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/sigma/import")]
+pub async fn import_sigma_rules(
+    path: web::Path<String>,
+    body: web::Json<SigmaImportRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_id = get_user_id(req.headers());
+    match sigma::import(&org_id, &user_id, body.into_inner()).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, io::Error};
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, slo::Slo},
+    service::slo,
+};
+
+/// CreateSlo
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "CreateSlo",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = Slo, description = "Slo data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "The name is taken", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/slo")]
+pub async fn save_slo(path: web::Path<String>, slo: web::Json<Slo>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match slo::save(&org_id, "", slo.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Slo saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateSlo
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "UpdateSlo",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Slo name"),
+    ),
+    request_body(content = Slo, description = "Slo data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/slo/{name}")]
+pub async fn update_slo(
+    path: web::Path<(String, String)>,
+    slo: web::Json<Slo>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match slo::save(&org_id, &name, slo.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Slo updated")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSlo
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "GetSlo",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Slo name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Slo),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/slo/{name}")]
+async fn get_slo(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match slo::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListSlos
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "ListSlos",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<Slo>),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/slo")]
+async fn list_slos(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match slo::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteSlo
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "DeleteSlo",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Slo name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/slo/{name}")]
+async fn delete_slo(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match slo::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Slo deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSloStatus
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "GetSloStatus",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Slo name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SloStatus),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/slo/{name}/status")]
+async fn get_slo_status(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match slo::status(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetSloHistory
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Slo",
+    operation_id = "GetSloHistory",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Slo name"),
+        ("start_time" = Option<i64>, Query, description = "Start time in microseconds, a window of the slo before end_time by default"),
+        ("end_time" = Option<i64>, Query, description = "End time in microseconds, now by default"),
+        ("interval" = Option<i64>, Query, description = "Seconds between two points, picked from the time range by default"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = SloHistory),
+        (status = 400, description = "Error", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/slo/{name}/history")]
+async fn get_slo_history(
+    path: web::Path<(String, String)>,
+    query: web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    let start_time = query
+        .get("start_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    let end_time = query
+        .get("end_time")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    let interval = query
+        .get("interval")
+        .map_or(0, |v| v.parse::<i64>().unwrap_or(0));
+    match slo::history(&org_id, &name, start_time, end_time, interval).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
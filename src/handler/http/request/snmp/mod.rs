@@ -0,0 +1,191 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::snmp::{SnmpMib, SnmpTrapRoute},
+    service::snmp,
+};
+
+/// CreateSnmpTrapRoute
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "CreateSnmpTrapRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(
+        content = SnmpTrapRoute,
+        description = "SnmpTrapRoute details",
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "Route created", body = SnmpTrapRoute),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid route", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/snmp/routes")]
+pub async fn create_route(details: web::Json<SnmpTrapRoute>) -> Result<HttpResponse, Error> {
+    snmp::create_route(details.into_inner()).await
+}
+
+/// UpdateSnmpTrapRoute
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "UpdateSnmpTrapRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Route ID"),
+    ),
+    request_body(
+        content = SnmpTrapRoute,
+        description = "SnmpTrapRoute details",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "SnmpTrapRoute updated", body = SnmpTrapRoute),
+        (status = StatusCode::NOT_FOUND, description = "SnmpTrapRoute not found", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Failed to update the SnmpTrapRoute", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/snmp/routes/{id}")]
+pub async fn update_route(
+    path: web::Path<(String, String)>,
+    details: web::Json<SnmpTrapRoute>,
+) -> Result<HttpResponse, Error> {
+    let (_, id) = path.into_inner();
+    snmp::update_route(&id, &mut details.into_inner()).await
+}
+
+/// ListSnmpTrapRoutes
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "ListSnmpTrapRoutes",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SnmpTrapRoutes),
+    ),
+)]
+#[get("/{org_id}/snmp/routes")]
+pub async fn list_routes() -> Result<HttpResponse, Error> {
+    snmp::list_routes().await
+}
+
+/// DeleteSnmpTrapRoute
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "DeleteSnmpTrapRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("id" = String, Path, description = "Route ID"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Route deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Route not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/snmp/routes/{id}")]
+pub async fn delete_route(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (_, id) = path.into_inner();
+    snmp::delete_route(&id).await
+}
+
+/// SaveSnmpMib
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "SaveSnmpMib",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(
+        content = SnmpMib,
+        description = "MIB module name and text",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "MIB saved", body = HttpResponse),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid MIB", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/snmp/mibs")]
+pub async fn save_mib(details: web::Json<SnmpMib>) -> Result<HttpResponse, Error> {
+    snmp::save_mib(details.into_inner()).await
+}
+
+/// ListSnmpMibs
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "ListSnmpMibs",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SnmpMibList),
+    ),
+)]
+#[get("/{org_id}/snmp/mibs")]
+pub async fn list_mibs() -> Result<HttpResponse, Error> {
+    snmp::list_mibs().await
+}
+
+/// DeleteSnmpMib
+#[utoipa::path(
+    context_path = "/api",
+    tag = "SNMP Traps",
+    operation_id = "DeleteSnmpMib",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "MIB name"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "MIB deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "MIB not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/snmp/mibs/{name}")]
+pub async fn delete_mib(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (_, name) = path.into_inner();
+    snmp::delete_mib(&name).await
+}
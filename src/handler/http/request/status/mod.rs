@@ -54,6 +54,7 @@ use crate::{
     common::{
         infra::{cluster, config::*},
         meta::{functions::ZoFunction, http::HttpResponse as MetaHttpResponse, user::AuthTokens},
+        utils::{auth::is_root_user, http::get_user_id},
     },
     service::{db, search::datafusion::DEFAULT_FUNCTIONS},
 };
@@ -504,6 +505,47 @@ async fn flush_node() -> Result<HttpResponse, Error> {
     }
 }
 
+/// Compares the version of the stream schemas cached on this node with the
+/// schemas in the db, `?repair=true` reloads the streams which differ. Only
+/// the root user checks every org, the other users check an org they belong
+/// to with `?org_id=`.
+#[get("/schema_consistency")]
+async fn schema_consistency(req: HttpRequest) -> Result<HttpResponse, Error> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let org_id = query.get("org_id").map(|v| v.as_str());
+    let user_id = get_user_id(req.headers());
+    if !is_root_user(&user_id) {
+        match org_id {
+            Some(org_id) if USERS.contains_key(&format!("{org_id}/{user_id}")) => {}
+            _ => {
+                return Ok(MetaHttpResponse::forbidden(
+                    "org_id of an organization of the user is required",
+                ));
+            }
+        }
+    }
+    let repair = query
+        .get("repair")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+    match db::schema::check_consistency(org_id, repair).await {
+        Ok(inconsistent) => Ok(MetaHttpResponse::json(json::json!({
+            "node": LOCAL_NODE_UUID.clone(),
+            "consistent": inconsistent.is_empty(),
+            "repaired": repair && !inconsistent.is_empty(),
+            "inconsistent": inconsistent,
+        }))),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// The load of this node per role, for Kubernetes HPA or KEDA scalers
+#[get("/autoscaling")]
+async fn autoscaling() -> Result<HttpResponse, Error> {
+    Ok(MetaHttpResponse::json(
+        crate::service::autoscaling::get_signals().await,
+    ))
+}
+
 #[get("/stream_fields/{org_id}/{stream_type}/{stream_name}")]
 async fn stream_fields(path: web::Path<(String, String, String)>) -> Result<HttpResponse, Error> {
     let (org_id, stream_type, stream_name) = path.into_inner();
@@ -18,26 +18,573 @@ use std::{
     io::{Error, ErrorKind},
 };
 
-use actix_web::{delete, get, http, put, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, http, post, put, web, HttpRequest, HttpResponse, Responder};
 use config::meta::stream::{StreamSettings, StreamType};
 
 use crate::{
     common::{
         meta::{
             self,
+            functions::Lineage,
             http::HttpResponse as MetaHttpResponse,
-            stream::{ListStream, StreamDeleteFields},
+            organization::Feature,
+            stream::{
+                CompactPriorityRequest, FileScanStats, KeyMapping, ListStream, ListStreamGroups,
+                RepartitionStatus, RewriteJob, StreamClone, StreamDeleteFields,
+                StreamPromoteFields, StreamProperty, StreamRename, StreamSettingsBulk,
+                StreamSettingsBulkResult, Tombstone,
+            },
         },
-        utils::http::get_stream_type_from_request,
+        utils::{auth::is_root_user, http::get_stream_type_from_request},
+    },
+    service::{
+        compact::{priority, rewrite, stats::rebuild_stream_stats, tombstones},
+        db,
+        error::ServiceError,
+        key_mappings, lineage, organization,
+        search::scan_costs,
+        stream,
     },
-    service::stream,
 };
 
-/// GetSchema
+/// GetSchema
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSchema",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success, the ETag header is the settings revision", content_type = "application/json", body = Stream),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/schema")]
+async fn schema(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                    http::StatusCode::BAD_REQUEST.into(),
+                    e.to_string(),
+                )),
+            );
+        }
+    };
+    let stream_type = stream_type.unwrap_or(StreamType::Logs);
+    match stream::get_stream(&org_id, &stream_name, stream_type).await {
+        Ok((stream, revision)) => Ok(HttpResponse::Ok()
+            .insert_header((http::header::ETAG, stream::settings_etag(revision)))
+            .json(stream)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetStreamStatsHistory
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamStatsHistory",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, default is logs"),
+        ("period" = Option<String>, Query, description = "How far back to look, e.g. 7d, default is 30d"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = StreamStatsHistory),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/{stream_name}/stats")]
+async fn stats_history(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let period = query.get("period").map(|v| v.as_str()).unwrap_or("30d");
+    match stream::get_stream_stats_history(&org_id, &stream_name, stream_type, period).await {
+        Ok(history) => Ok(MetaHttpResponse::json(history)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// RebuildStreamStats
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "RebuildStreamStats",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<StreamStatsDrift>),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/stats/rebuild")]
+async fn rebuild_stats(path: web::Path<String>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !is_root_user(user_id) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can rebuild stream stats",
+        ));
+    }
+    match rebuild_stream_stats(Some(&org_id)).await {
+        Ok(drifts) => Ok(MetaHttpResponse::json(drifts)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// ListCompactPriority
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompactPriorityList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<CompactPriority>),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/compact_priority")]
+async fn list_compact_priority(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match priority::list_by_org(&org_id).await {
+        Ok(items) => Ok(MetaHttpResponse::json(items)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// UpdateCompactPriority
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompactPriorityUpdate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = CompactPriorityRequest, description = "Manual priority, higher is compacted first", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/compact_priority")]
+async fn update_compact_priority(
+    path: web::Path<(String, String)>,
+    body: web::Json<CompactPriorityRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    set_compact_priority(&org_id, &stream_name, Some(body.priority), req).await
+}
+
+/// DeleteCompactPriority
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamCompactPriorityDelete",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/compact_priority")]
+async fn delete_compact_priority(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    set_compact_priority(&org_id, &stream_name, None, req).await
+}
+
+async fn set_compact_priority(
+    org_id: &str,
+    stream_name: &str,
+    value: Option<i64>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    // the compactors are shared by all the organizations
+    if !is_root_request(&req) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can change the compaction priority",
+        ));
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let schema = match infra::schema::get(org_id, stream_name, stream_type).await {
+        Ok(schema) => schema,
+        Err(e) => return Ok(MetaHttpResponse::internal_error(e)),
+    };
+    if schema.fields().is_empty() {
+        return Ok(MetaHttpResponse::not_found("stream not found"));
+    }
+    match priority::set_priority(org_id, stream_type, stream_name, value).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("compaction priority updated")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+fn is_root_request(req: &HttpRequest) -> bool {
+    let user_id = req
+        .headers()
+        .get("user_id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    is_root_user(user_id)
+}
+
+/// CreateStreamRewrite
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRewriteCreate",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = RewriteJob, description = "Time range of the rewritten files", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RewriteJob),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/rewrite")]
+async fn create_rewrite(
+    path: web::Path<(String, String)>,
+    body: web::Json<RewriteJob>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    // the compactors are shared by all the organizations
+    if !is_root_request(&req) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can rewrite the stream files",
+        ));
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match rewrite::create(
+        &org_id,
+        stream_type,
+        &stream_name,
+        body.start_time,
+        body.end_time,
+    )
+    .await
+    {
+        Ok(job) => Ok(MetaHttpResponse::json(job)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// GetStreamRewrite
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRewriteGet",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RewriteJob),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/rewrite")]
+async fn get_rewrite(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match db::compact::rewrite::get(&org_id, stream_type, &stream_name).await {
+        Ok(Some(job)) => Ok(MetaHttpResponse::json(job)),
+        Ok(None) => Ok(MetaHttpResponse::not_found("no rewrite in progress")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// CancelStreamRewrite
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRewriteCancel",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Forbidden", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/streams/{stream_name}/rewrite")]
+async fn cancel_rewrite(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    if !is_root_request(&req) {
+        return Ok(MetaHttpResponse::forbidden(
+            "Only root user can rewrite the stream files",
+        ));
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match db::compact::rewrite::delete(&org_id, stream_type, &stream_name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("rewrite canceled")),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+/// GetStreamRepartitionStatus
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRepartitionStatus",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = RepartitionStatus),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/repartition")]
+async fn get_repartition_status(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match stream::get_repartition_status(&org_id, &stream_name, stream_type).await {
+        Ok(status) => Ok(MetaHttpResponse::json(status)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListStreamFields
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamFields",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("tag" = Option<String>, Query, description = "Only the fields with this tag, e.g. pii"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<StreamProperty>),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/fields")]
+async fn list_fields(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let tag = query.get("tag").map(|v| v.as_str());
+    match stream::list_fields(&org_id, &stream_name, stream_type, tag).await {
+        Ok(fields) => Ok(MetaHttpResponse::json(fields)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListStreamLineages
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamLineages",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<Lineage>),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/lineage")]
+async fn list_lineages(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match lineage::list(&org_id, stream_type, &stream_name).await {
+        Ok(lineages) => Ok(MetaHttpResponse::json(lineages)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetStreamLineage
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamLineage",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("id" = i64, Path, description = "Lineage id"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Lineage),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/lineage/{id}")]
+async fn get_lineage(
+    path: web::Path<(String, String, i64)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name, id) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match lineage::get(&org_id, stream_type, &stream_name, id).await {
+        Ok(lineage) => Ok(MetaHttpResponse::json(lineage)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListStreamKeyMappings
 #[utoipa::path(
     context_path = "/api",
     tag = "Streams",
-    operation_id = "StreamSchema",
+    operation_id = "StreamKeyMappings",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "The keys changed by the normalization, stored when the key_normalization of the stream sets report", content_type = "application/json", body = Vec<KeyMapping>),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/key_mappings")]
+async fn list_key_mappings(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match key_mappings::list(&org_id, stream_type, &stream_name).await {
+        Ok(mappings) => Ok(MetaHttpResponse::json(mappings)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteStreamRecords
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamDeleteRecords",
     security(
         ("Authorization"= [])
     ),
@@ -45,31 +592,145 @@ use crate::{
         ("org_id" = String, Path, description = "Organization name"),
         ("stream_name" = String, Path, description = "Stream name"),
     ),
+    request_body(content = Tombstone, description = "Time range and filter of the deleted records", content_type = "application/json"),
     responses(
-        (status = 200, description = "Success", content_type = "application/json", body = Stream),
+        (status = 200, description = "Success", content_type = "application/json", body = Tombstone),
         (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 403, description = "Feature not enabled or unauthorized", content_type = "application/json", body = HttpResponse),
     )
 )]
-#[get("/{org_id}/streams/{stream_name}/schema")]
-async fn schema(
+#[post("/{org_id}/streams/{stream_name}/delete_records")]
+async fn delete_records(
     path: web::Path<(String, String)>,
+    body: web::Json<Tombstone>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
     let (org_id, stream_name) = path.into_inner();
+    if let Err(e) = organization::check_feature(&org_id, Feature::DeleteRecords) {
+        return Ok(e.into());
+    }
     let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
     let stream_type = match get_stream_type_from_request(&query) {
-        Ok(v) => v,
-        Err(e) => {
-            return Ok(
-                HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                )),
-            );
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+
+    // deleting records needs the permission to delete the stream
+    #[cfg(feature = "enterprise")]
+    {
+        use crate::common::{infra::config::USERS, utils::auth::AuthExtractor};
+        let user_id = req.headers().get("user_id").unwrap().to_str().unwrap();
+        if !is_root_user(user_id) {
+            let Some(user) = USERS.get(&format!("{org_id}/{user_id}")) else {
+                return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+            };
+            let role = user.role.clone();
+            drop(user);
+            if !crate::handler::http::auth::validator::check_permissions(
+                user_id,
+                AuthExtractor {
+                    auth: "".to_string(),
+                    method: "DELETE".to_string(),
+                    o2_type: format!("{}:{}", stream_type, stream_name),
+                    org_id: org_id.clone(),
+                    bypass_check: false,
+                    parent_id: "".to_string(),
+                },
+                Some(role),
+            )
+            .await
+            {
+                return Ok(MetaHttpResponse::forbidden("Unauthorized Access"));
+            }
+        }
+    }
+
+    match tombstones::create(&org_id, stream_type, &stream_name, body.into_inner()).await {
+        Ok(tombstone) => Ok(MetaHttpResponse::json(tombstone)),
+        Err(e) => Ok(MetaHttpResponse::bad_request(e)),
+    }
+}
+
+/// ListStreamTombstones
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamTombstoneList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<Tombstone>),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/tombstones")]
+async fn list_tombstones(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    Ok(MetaHttpResponse::json(
+        db::compact::tombstones::list_by_stream(&org_id, stream_type, &stream_name),
+    ))
+}
+
+/// ListStreamFileStats
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamFileStats",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("by" = Option<String>, Query, description = "file (default) or partition"),
+        ("size" = Option<usize>, Query, description = "Number of items, default 20"),
+    ),
+    responses(
+        (status = 200, description = "Slowest files or partitions searched by the queriers", content_type = "application/json", body = Vec<FileScanStats>),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/streams/{stream_name}/file_stats")]
+async fn list_file_stats(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let by_partition = match query.get("by").map(|v| v.as_str()) {
+        None | Some("file") => false,
+        Some("partition") => true,
+        Some(_) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "by must be file or partition",
+            ));
         }
     };
-    let stream_type = stream_type.unwrap_or(StreamType::Logs);
-    stream::get_stream(&org_id, &stream_name, stream_type).await
+    let size = query
+        .get("size")
+        .map_or(20, |v| v.parse::<usize>().unwrap_or(20));
+    match scan_costs::list(&org_id, stream_type, &stream_name, by_partition, size).await {
+        Ok(items) => Ok(MetaHttpResponse::json(items)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
 }
 
 /// UpdateStreamSettings
@@ -83,11 +744,13 @@ async fn schema(
     params(
         ("org_id" = String, Path, description = "Organization name"),
         ("stream_name" = String, Path, description = "Stream name"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the settings revision the update is based on"),
     ),
     request_body(content = StreamSettings, description = "Stream settings", content_type = "application/json"),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
         (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "Settings were modified since the If-Match revision", content_type = "application/json", body = HttpResponse),
     )
 )]
 #[put("/{org_id}/streams/{stream_name}/settings")]
@@ -124,7 +787,124 @@ async fn settings(
         }
     };
     let stream_type = stream_type.unwrap_or(StreamType::Logs);
-    stream::save_stream_settings(&org_id, &stream_name, stream_type, settings.into_inner()).await
+    let revision = match req.headers().get(http::header::IF_MATCH) {
+        None => None,
+        Some(v) => match v
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().trim_matches('"').parse::<u64>().ok())
+        {
+            Some(revision) => Some(revision),
+            None => {
+                return Ok(MetaHttpResponse::bad_request(
+                    "If-Match must be the ETag of the stream settings",
+                ));
+            }
+        },
+    };
+    match stream::save_stream_settings(
+        &org_id,
+        &stream_name,
+        stream_type,
+        settings.into_inner(),
+        revision,
+    )
+    .await
+    {
+        Ok(revision) => Ok(HttpResponse::Ok()
+            .insert_header((http::header::ETAG, stream::settings_etag(revision)))
+            .json(MetaHttpResponse::message(
+                http::StatusCode::OK.into(),
+                "".to_string(),
+            ))),
+        Err(e @ ServiceError::Conflict(_)) => {
+            // send back the current revision so the client can retry on top of it
+            let revision = stream::settings_revision(&org_id, &stream_name, stream_type).await;
+            let mut resp: HttpResponse = e.into();
+            if let Ok(etag) = http::header::HeaderValue::from_str(&stream::settings_etag(revision))
+            {
+                resp.headers_mut().insert(http::header::ETAG, etag);
+            }
+            Ok(resp)
+        }
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateStreamSettingsBulk
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSettingsBulk",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = StreamSettingsBulk, description = "Streams and the settings applied to them", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success, the result of each stream", content_type = "application/json", body = Vec<StreamSettingsBulkResult>),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/settings/bulk")]
+async fn settings_bulk(
+    path: web::Path<String>,
+    body: web::Json<StreamSettingsBulk>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    if stream_type == StreamType::EnrichmentTables || stream_type == StreamType::Index {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Stream type '{}' not allowed",
+            stream_type
+        )));
+    }
+    match stream::save_stream_settings_bulk(&org_id, stream_type, body.into_inner()).await {
+        Ok(results) => Ok(MetaHttpResponse::json(results)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// RepairStreamSettings
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamSettingsRepair",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = 200, description = "Success, the errors which were repaired", content_type = "application/json", body = Vec<String>),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/settings/repair")]
+async fn repair_settings(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match stream::repair_stream_settings(&org_id, &stream_name, stream_type).await {
+        Ok(errors) => Ok(MetaHttpResponse::json(errors)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
 }
 
 /// DeleteStreamFields
@@ -183,6 +963,55 @@ async fn delete_fields(
     }
 }
 
+/// PromoteStreamFields
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamPromoteFields",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = StreamPromoteFields, description = "Keys of the _extra column to promote to columns", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/streams/{stream_name}/promote_fields")]
+async fn promote_fields(
+    path: web::Path<(String, String)>,
+    fields: web::Json<StreamPromoteFields>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    match stream::promote_fields(
+        &org_id,
+        &stream_name,
+        stream_type,
+        &fields.into_inner().fields,
+    )
+    .await
+    {
+        Ok(revision) => Ok(HttpResponse::Ok()
+            .insert_header((http::header::ETAG, stream::settings_etag(revision)))
+            .json(MetaHttpResponse::message(
+                http::StatusCode::OK.into(),
+                "fields promoted".to_string(),
+            ))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
 /// DeleteStream
 #[utoipa::path(
     context_path = "/api",
@@ -219,7 +1048,88 @@ async fn delete(
         }
     };
     let stream_type = stream_type.unwrap_or(StreamType::Logs);
-    stream::delete_stream(&org_id, &stream_name, stream_type).await
+    match stream::delete_stream(&org_id, &stream_name, stream_type).await {
+        Ok(()) => Ok(MetaHttpResponse::ok("stream deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// RenameStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamRename",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = StreamRename, description = "New stream name", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success, the compactor moves the stream in the background", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "Stream not found", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "The new name is taken, the stream is busy or used by other objects", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/rename")]
+async fn rename(
+    path: web::Path<(String, String)>,
+    body: web::Json<StreamRename>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let new_name = body.into_inner().new_name;
+    match stream::rename_stream(&org_id, &stream_name, &new_name, stream_type).await {
+        Ok(()) => Ok(MetaHttpResponse::ok("stream rename started")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// CloneStream
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Streams",
+    operation_id = "StreamClone",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(content = StreamClone, description = "Name of the new stream", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Failure", content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "Stream not found", content_type = "application/json", body = HttpResponse),
+        (status = 409, description = "The new name is taken", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/streams/{stream_name}/clone")]
+async fn clone(
+    path: web::Path<(String, String)>,
+    body: web::Json<StreamClone>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(MetaHttpResponse::bad_request(e)),
+    };
+    let new_name = body.into_inner().new_name;
+    match stream::clone_stream(&org_id, &stream_name, &new_name, stream_type).await {
+        Ok(()) => Ok(MetaHttpResponse::ok("stream cloned")),
+        Err(e) => Ok(e.into()),
+    }
 }
 
 /// ListStreams
@@ -232,6 +1142,11 @@ async fn delete(
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("type" = Option<String>, Query, description = "Stream type"),
+        ("fetchSchema" = Option<bool>, Query, description = "Include the stream schemas"),
+        ("include_internal" = Option<bool>, Query, description = "Include the metadata streams and the usage org streams, false by default"),
+        ("labels" = Option<String>, Query, description = "Only list the streams with all these labels, e.g. team:payments,env:prod"),
+        ("group_by" = Option<String>, Query, description = "Group the streams by the value of this label, the response is a ListStreamGroups"),
     ),
     responses(
         (status = 200, description = "Success", content_type = "application/json", body = ListStream),
@@ -266,32 +1181,64 @@ async fn list(org_id: web::Path<String>, req: HttpRequest) -> impl Responder {
         },
         None => false,
     };
+    let include_internal = match query.get("include_internal") {
+        Some(s) => match s.to_lowercase().as_str() {
+            "true" => true,
+            "false" => false,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    " 'include_internal' query param with value 'true' or 'false' allowed",
+                ));
+            }
+        },
+        // asking for an internal stream type lists its streams
+        None => matches!(
+            stream_type,
+            Some(StreamType::Metadata | StreamType::Index | StreamType::Filelist)
+        ),
+    };
+    let labels = match query.get("labels") {
+        Some(s) => match stream::parse_label_filters(s) {
+            Ok(labels) => labels,
+            Err(e) => return Ok(e.into()),
+        },
+        None => vec![],
+    };
     let mut _stream_list_from_rbac = None;
     // Get List of allowed objects
     #[cfg(feature = "enterprise")]
     {
         let user_id = req.headers().get("user_id").unwrap();
-        if let Some(s_type) = &stream_type {
-            if !s_type.eq(&StreamType::EnrichmentTables) && !s_type.eq(&StreamType::Metadata) {
-                match crate::handler::http::auth::validator::list_objects_for_user(
-                    &org_id,
-                    user_id.to_str().unwrap(),
-                    "GET",
-                    &s_type.to_string(),
-                )
-                .await
-                {
-                    Ok(stream_list) => {
-                        _stream_list_from_rbac = stream_list;
-                    }
-                    Err(e) => {
-                        return Ok(crate::common::meta::http::HttpResponse::forbidden(
-                            e.to_string(),
-                        ));
-                    }
+        // without a type all the streams are listed, each one is checked against
+        // the permissions of its own type
+        let stream_types = match &stream_type {
+            Some(s_type) => vec![*s_type],
+            None => vec![StreamType::Logs, StreamType::Metrics, StreamType::Traces],
+        };
+        let mut permitted = vec![];
+        for s_type in stream_types {
+            if s_type.eq(&StreamType::EnrichmentTables) || s_type.eq(&StreamType::Metadata) {
+                continue;
+            }
+            match crate::handler::http::auth::validator::list_objects_for_user(
+                &org_id,
+                user_id.to_str().unwrap(),
+                "GET",
+                &s_type.to_string(),
+            )
+            .await
+            {
+                Ok(Some(stream_list)) => permitted.extend(stream_list),
+                Ok(None) => permitted.push(format!("{}:_all_{}", s_type, org_id)),
+                Err(e) => {
+                    return Ok(crate::common::meta::http::HttpResponse::forbidden(
+                        e.to_string(),
+                    ));
                 }
             }
         }
+        _stream_list_from_rbac = Some(permitted);
         // Get List of allowed objects ends
     }
 
@@ -300,8 +1247,16 @@ async fn list(org_id: web::Path<String>, req: HttpRequest) -> impl Responder {
         stream_type,
         fetch_schema,
         _stream_list_from_rbac,
+        include_internal,
     )
     .await;
     indices.sort_by(|a, b| a.name.cmp(&b.name));
-    Ok(HttpResponse::Ok().json(ListStream { list: indices }))
+    let indices = stream::filter_by_labels(indices, &labels);
+    match query.get("group_by") {
+        Some(label) => Ok(HttpResponse::Ok().json(ListStreamGroups {
+            label: label.to_string(),
+            groups: stream::group_by_label(indices, label),
+        })),
+        None => Ok(HttpResponse::Ok().json(ListStream { list: indices })),
+    }
 }
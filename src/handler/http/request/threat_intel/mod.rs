@@ -0,0 +1,161 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, threat_intel::IndicatorList},
+    service::threat_intel,
+};
+
+/// CreateIndicatorList
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Threat Intel",
+    operation_id = "CreateIndicatorList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = IndicatorList, description = "Indicator list data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/threat_intel")]
+pub async fn save_indicator_list(
+    path: web::Path<String>,
+    list: web::Json<IndicatorList>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match threat_intel::save(&org_id, "", list.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Indicator list saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateIndicatorList
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Threat Intel",
+    operation_id = "UpdateIndicatorList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Indicator list name"),
+      ),
+    request_body(content = IndicatorList, description = "Indicator list data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/threat_intel/{name}")]
+pub async fn update_indicator_list(
+    path: web::Path<(String, String)>,
+    list: web::Json<IndicatorList>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match threat_intel::save(&org_id, name.trim(), list.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Indicator list saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetIndicatorList
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Threat Intel",
+    operation_id = "GetIndicatorList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Indicator list name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = IndicatorList),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/threat_intel/{name}")]
+async fn get_indicator_list(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match threat_intel::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListIndicatorLists
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Threat Intel",
+    operation_id = "ListIndicatorLists",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<IndicatorList>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/threat_intel")]
+async fn list_indicator_lists(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match threat_intel::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteIndicatorList
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Threat Intel",
+    operation_id = "DeleteIndicatorList",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Indicator list name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/threat_intel/{name}")]
+async fn delete_indicator_list(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match threat_intel::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Indicator list deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -17,7 +17,6 @@ use std::{collections::HashMap, io::Error};
 
 use actix_web::{get, http, post, web, HttpRequest, HttpResponse};
 use config::{ider, meta::stream::StreamType, metrics, utils::json, CONFIG};
-use infra::errors;
 use opentelemetry::{global, trace::TraceContextExt};
 use serde::Serialize;
 use tracing::Instrument;
@@ -29,7 +28,7 @@ use crate::{
         utils::http::RequestHeaderExtractor,
     },
     handler::http::request::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO},
-    service::{search as SearchService, traces::otlp_http},
+    service::{error::ServiceError, search as SearchService, traces::otlp_http},
 };
 
 /// TracesIngest
@@ -251,6 +250,10 @@ pub async fn get_latest_traces(
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         },
         aggs: HashMap::new(),
         encoding: config::meta::search::RequestEncoding::Empty,
@@ -276,11 +279,12 @@ pub async fn get_latest_traces(
     let resp_search = match search_res {
         Ok(res) => res,
         Err(err) => {
+            let err = ServiceError::from(err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/traces/latest",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "default",
                     stream_type.to_string().as_str(),
@@ -289,25 +293,14 @@ pub async fn get_latest_traces(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/traces/latest",
-                    "500",
+                    err.status_code().as_str(),
                     &org_id,
                     "default",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("get traces latest data error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => match code {
-                    errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                        .json(meta::http::HttpResponse::error_code(code)),
-                    _ => HttpResponse::InternalServerError()
-                        .json(meta::http::HttpResponse::error_code(code)),
-                },
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(err.into());
         }
     };
     if resp_search.hits.is_empty() {
@@ -368,11 +361,12 @@ pub async fn get_latest_traces(
         let resp_search = match search_res {
             Ok(res) => res,
             Err(err) => {
+                let err = ServiceError::from(err);
                 let time = start.elapsed().as_secs_f64();
                 metrics::HTTP_RESPONSE_TIME
                     .with_label_values(&[
                         "/api/org/traces/latest",
-                        "500",
+                        err.status_code().as_str(),
                         &org_id,
                         &stream_name,
                         stream_type.to_string().as_str(),
@@ -381,25 +375,14 @@ pub async fn get_latest_traces(
                 metrics::HTTP_INCOMING_REQUESTS
                     .with_label_values(&[
                         "/api/org/traces/latest",
-                        "500",
+                        err.status_code().as_str(),
                         &org_id,
                         &stream_name,
                         stream_type.to_string().as_str(),
                     ])
                     .inc();
                 log::error!("get traces latest data error: {:?}", err);
-                return Ok(match err {
-                    errors::Error::ErrorCode(code) => match code {
-                        errors::ErrorCodes::SearchCancelQuery(_) => HttpResponse::TooManyRequests()
-                            .json(meta::http::HttpResponse::error_code(code)),
-                        _ => HttpResponse::InternalServerError()
-                            .json(meta::http::HttpResponse::error_code(code)),
-                    },
-                    _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                        http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                        err.to_string(),
-                    )),
-                });
+                return Ok(err.into());
             }
         };
 
@@ -0,0 +1,161 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Error;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, webhooks::Webhook},
+    service::webhooks,
+};
+
+/// CreateWebhook
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Webhooks",
+    operation_id = "CreateWebhook",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    request_body(content = Webhook, description = "Webhook data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/webhooks")]
+pub async fn save_webhook(
+    path: web::Path<String>,
+    webhook: web::Json<Webhook>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match webhooks::save(&org_id, "", webhook.into_inner(), true).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Webhook saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// UpdateWebhook
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Webhooks",
+    operation_id = "UpdateWebhook",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Webhook name"),
+      ),
+    request_body(content = Webhook, description = "Webhook data", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 400, description = "Error",    content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/webhooks/{name}")]
+pub async fn update_webhook(
+    path: web::Path<(String, String)>,
+    webhook: web::Json<Webhook>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match webhooks::save(&org_id, name.trim(), webhook.into_inner(), false).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Webhook saved")),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// GetWebhook
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Webhooks",
+    operation_id = "GetWebhook",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Webhook name"),
+      ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = Webhook),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/webhooks/{name}")]
+async fn get_webhook(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match webhooks::get(&org_id, &name).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// ListWebhooks
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Webhooks",
+    operation_id = "ListWebhooks",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+      ),
+    responses(
+        (status = 200, description = "Success", content_type = "application/json", body = Vec<Webhook>),
+        (status = 400, description = "Error",   content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/webhooks")]
+async fn list_webhooks(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    match webhooks::list(&org_id).await {
+        Ok(data) => Ok(MetaHttpResponse::json(data)),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// DeleteWebhook
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Webhooks",
+    operation_id = "DeleteWebhook",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("name" = String, Path, description = "Webhook name"),
+    ),
+    responses(
+        (status = 200, description = "Success",  content_type = "application/json", body = HttpResponse),
+        (status = 404, description = "NotFound", content_type = "application/json", body = HttpResponse),
+        (status = 500, description = "Failure",  content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/webhooks/{name}")]
+async fn delete_webhook(path: web::Path<(String, String)>) -> Result<HttpResponse, Error> {
+    let (org_id, name) = path.into_inner();
+    match webhooks::delete(&org_id, &name).await {
+        Ok(_) => Ok(MetaHttpResponse::ok("Webhook deleted")),
+        Err(e) => Ok(e.into()),
+    }
+}
@@ -196,6 +196,16 @@ pub fn get_basic_routes(cfg: &mut web::ServiceConfig) {
             .wrap(cors.clone())
             .service(users::authentication),
     );
+    cfg.service(
+        web::scope("/short")
+            .wrap(cors.clone())
+            .service(short_url::resolve),
+    );
+    cfg.service(
+        web::scope("/slack")
+            .wrap(cors.clone())
+            .service(alerts::slack::interactions),
+    );
 
     cfg.service(
         web::scope("/node")
@@ -206,7 +216,9 @@ pub fn get_basic_routes(cfg: &mut web::ServiceConfig) {
             .service(status::cache_status)
             .service(status::enable_node)
             .service(status::flush_node)
-            .service(status::stream_fields),
+            .service(status::stream_fields)
+            .service(status::schema_consistency)
+            .service(status::autoscaling),
     );
 
     cfg.service(
@@ -303,6 +315,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(organization::org::organizations)
             .service(organization::settings::get)
             .service(organization::settings::create)
+            .service(organization::settings::get_feature_flags)
+            .service(organization::settings::set_feature_flags)
             .service(organization::settings::upload_logo)
             .service(organization::settings::delete_logo)
             .service(organization::settings::set_logo_text)
@@ -322,13 +336,37 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(organization::es::org_data_stream_create)
             .service(stream::schema)
             .service(stream::settings)
+            .service(stream::settings_bulk)
+            .service(stream::repair_settings)
             .service(stream::delete_fields)
+            .service(stream::promote_fields)
             .service(stream::delete)
+            .service(stream::rename)
+            .service(stream::clone)
             .service(stream::list)
+            .service(stream::stats_history)
+            .service(stream::rebuild_stats)
+            .service(stream::list_compact_priority)
+            .service(stream::update_compact_priority)
+            .service(stream::delete_compact_priority)
+            .service(stream::create_rewrite)
+            .service(stream::get_rewrite)
+            .service(stream::cancel_rewrite)
+            .service(stream::get_repartition_status)
+            .service(stream::list_fields)
+            .service(stream::list_lineages)
+            .service(stream::get_lineage)
+            .service(stream::list_key_mappings)
+            .service(stream::delete_records)
+            .service(stream::list_tombstones)
+            .service(stream::list_file_stats)
             .service(logs::ingest::bulk)
             .service(logs::ingest::multi)
             .service(logs::ingest::json)
+            .service(logs::ingest::simulate)
+            .service(quality_monitors::heartbeat)
             .service(logs::ingest::otlp_logs_write)
+            .service(logs::ingest::loki_push)
             .service(traces::traces_write)
             .service(traces::otlp_traces_write)
             .service(traces::get_latest_traces)
@@ -348,21 +386,44 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(prom::format_query_get)
             .service(prom::format_query_post)
             .service(enrichment_table::save_enrichment_table)
+            .service(enrichment_table::save_enrichment_table_source)
+            .service(enrichment_table::get_enrichment_table_source)
+            .service(enrichment_table::list_enrichment_table_sources)
+            .service(enrichment_table::delete_enrichment_table_source)
             .service(search::search)
             .service(search::job::cancel_query)
             .service(search::job::query_status)
             .service(search::search_partition)
+            .service(search::search_stream)
+            .service(search::forecast::forecast)
+            .service(search::search_job::submit_job)
+            .service(search::search_job::list_jobs)
+            .service(search::search_job::get_job)
+            .service(search::search_job::get_job_results)
+            .service(search::search_job::cancel_job)
+            .service(search::search_job::delete_job)
             .service(search::around)
+            .service(search::context)
             .service(search::values)
+            .service(search::get_large_field)
+            .service(search::get_original_record)
+            .service(search::get_record)
             .service(search::saved_view::create_view)
             .service(search::saved_view::update_view)
             .service(search::saved_view::get_view)
             .service(search::saved_view::get_views)
             .service(search::saved_view::delete_view)
+            .service(search::templates::save_template)
+            .service(search::templates::update_template)
+            .service(search::templates::get_template)
+            .service(search::templates::list_templates)
+            .service(search::templates::delete_template)
+            .service(search::templates::search_template)
             .service(functions::save_function)
             .service(functions::list_functions)
             .service(functions::delete_function)
             .service(functions::update_function)
+            .service(functions::test_function)
             .service(functions::add_function_to_stream)
             .service(functions::list_stream_functions)
             .service(functions::delete_stream_function)
@@ -372,11 +433,17 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(dashboards::get_dashboard)
             .service(dashboards::delete_dashboard)
             .service(dashboards::move_dashboard)
+            .service(revisions::list)
+            .service(revisions::get)
+            .service(revisions::diff)
+            .service(revisions::restore)
+            .service(revisions::list_deleted)
             .service(dashboards::folders::create_folder)
             .service(dashboards::folders::list_folders)
             .service(dashboards::folders::update_folder)
             .service(dashboards::folders::get_folder)
             .service(dashboards::folders::delete_folder)
+            .service(dashboards::folders::move_folder)
             .service(dashboards::reports::create_report)
             .service(dashboards::reports::update_report)
             .service(dashboards::reports::get_report)
@@ -391,6 +458,8 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(alerts::list_stream_alerts)
             .service(alerts::delete_alert)
             .service(alerts::enable_alert)
+            .service(alerts::silence_alert)
+            .service(alerts::acknowledge_alert)
             .service(alerts::trigger_alert)
             .service(alerts::templates::save_template)
             .service(alerts::templates::update_template)
@@ -411,6 +480,70 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(syslog::delete_route)
             .service(syslog::update_route)
             .service(syslog::toggle_state)
+            .service(snmp::list_routes)
+            .service(snmp::create_route)
+            .service(snmp::delete_route)
+            .service(snmp::update_route)
+            .service(snmp::list_mibs)
+            .service(snmp::save_mib)
+            .service(snmp::delete_mib)
+            .service(monitors::save_monitor)
+            .service(monitors::update_monitor)
+            .service(monitors::get_monitor)
+            .service(monitors::list_monitors)
+            .service(monitors::delete_monitor)
+            .service(quality_monitors::save_quality_monitor)
+            .service(quality_monitors::update_quality_monitor)
+            .service(quality_monitors::get_quality_monitor)
+            .service(quality_monitors::list_quality_monitors)
+            .service(quality_monitors::delete_quality_monitor)
+            .service(continuous_queries::save_continuous_query)
+            .service(continuous_queries::update_continuous_query)
+            .service(continuous_queries::enable_continuous_query)
+            .service(continuous_queries::get_continuous_query)
+            .service(continuous_queries::list_continuous_queries)
+            .service(continuous_queries::delete_continuous_query)
+            .service(slo::save_slo)
+            .service(slo::update_slo)
+            .service(slo::get_slo)
+            .service(slo::list_slos)
+            .service(slo::delete_slo)
+            .service(slo::get_slo_status)
+            .service(slo::get_slo_history)
+            .service(webhooks::save_webhook)
+            .service(webhooks::update_webhook)
+            .service(webhooks::get_webhook)
+            .service(webhooks::list_webhooks)
+            .service(webhooks::delete_webhook)
+            .service(legal_holds::create_legal_hold)
+            .service(legal_holds::list_legal_holds)
+            .service(legal_holds::list_legal_hold_files)
+            .service(legal_holds::release_legal_hold)
+            .service(archives::list_archives)
+            .service(archives::restore_archive)
+            .service(reprocess::create_reprocess_job)
+            .service(reprocess::list_reprocess_jobs)
+            .service(reprocess::get_reprocess_job)
+            .service(kafka::save_kafka_source)
+            .service(kafka::get_kafka_source)
+            .service(kafka::list_kafka_sources)
+            .service(kafka::delete_kafka_source)
+            .service(correlation::save_correlation_rule)
+            .service(correlation::update_correlation_rule)
+            .service(correlation::get_correlation_rule)
+            .service(correlation::list_correlation_rules)
+            .service(correlation::delete_correlation_rule)
+            .service(threat_intel::save_indicator_list)
+            .service(threat_intel::update_indicator_list)
+            .service(threat_intel::get_indicator_list)
+            .service(threat_intel::list_indicator_lists)
+            .service(threat_intel::delete_indicator_list)
+            .service(sigma::save_mapping_profile)
+            .service(sigma::update_mapping_profile)
+            .service(sigma::get_mapping_profile)
+            .service(sigma::list_mapping_profiles)
+            .service(sigma::delete_mapping_profile)
+            .service(sigma::import_sigma_rules)
             .service(enrichment_table::save_enrichment_table)
             .service(metrics::ingest::otlp_metrics_write)
             .service(logs::ingest::otlp_logs_write)
@@ -424,6 +557,7 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(traces::get_latest_traces)
             .service(logs::ingest::multi)
             .service(logs::ingest::json)
+            .service(quality_monitors::heartbeat)
             .service(logs::ingest::handle_kinesis_request)
             .service(logs::ingest::handle_gcp_request)
             .service(organization::org::create_org)
@@ -22,6 +22,7 @@ use crate::{common::meta, handler::http::request};
 #[openapi(
     paths(
         request::status::healthz,
+        request::short_url::resolve,
         request::users::list,
         request::users::save,
         request::users::update,
@@ -36,14 +37,39 @@ use crate::{common::meta, handler::http::request};
         request::organization::org::create_user_rumtoken,
         request::organization::settings::get,
         request::organization::settings::create,
+        request::organization::settings::get_feature_flags,
+        request::organization::settings::set_feature_flags,
         request::stream::list,
         request::stream::schema,
         request::stream::settings,
+        request::stream::settings_bulk,
+        request::stream::repair_settings,
         request::stream::delete_fields,
+        request::stream::promote_fields,
         request::stream::delete,
+        request::stream::rename,
+        request::stream::clone,
+        request::stream::stats_history,
+        request::stream::rebuild_stats,
+        request::stream::list_compact_priority,
+        request::stream::update_compact_priority,
+        request::stream::delete_compact_priority,
+        request::stream::create_rewrite,
+        request::stream::get_rewrite,
+        request::stream::cancel_rewrite,
+        request::stream::get_repartition_status,
+        request::stream::list_fields,
+        request::stream::list_lineages,
+        request::stream::get_lineage,
+        request::stream::list_key_mappings,
+        request::stream::delete_records,
+        request::stream::list_tombstones,
+        request::stream::list_file_stats,
         request::logs::ingest::bulk,
         request::logs::ingest::multi,
         request::logs::ingest::json,
+        request::logs::ingest::simulate,
+        request::logs::ingest::loki_push,
         request::traces::traces_write,
         request::traces::get_latest_traces,
         request::metrics::ingest::json,
@@ -56,22 +82,45 @@ use crate::{common::meta, handler::http::request};
         request::prom::label_values,
         request::prom::format_query_get,
         request::enrichment_table::save_enrichment_table,
+        request::enrichment_table::save_enrichment_table_source,
+        request::enrichment_table::get_enrichment_table_source,
+        request::enrichment_table::list_enrichment_table_sources,
+        request::enrichment_table::delete_enrichment_table_source,
         request::rum::ingest::log,
         request::rum::ingest::data,
         request::rum::ingest::sessionreplay,
         request::search::search,
         request::search::search_partition,
+        request::search::search_stream,
+        request::search::forecast::forecast,
+        request::search::search_job::submit_job,
+        request::search::search_job::list_jobs,
+        request::search::search_job::get_job,
+        request::search::search_job::get_job_results,
+        request::search::search_job::cancel_job,
+        request::search::search_job::delete_job,
         request::search::around,
+        request::search::context,
         request::search::values,
+        request::search::get_large_field,
+        request::search::get_original_record,
+        request::search::get_record,
         request::search::saved_view::create_view,
         request::search::saved_view::delete_view,
         request::search::saved_view::get_view,
         request::search::saved_view::get_views,
         request::search::saved_view::update_view,
+        request::search::templates::save_template,
+        request::search::templates::update_template,
+        request::search::templates::get_template,
+        request::search::templates::list_templates,
+        request::search::templates::delete_template,
+        request::search::templates::search_template,
         request::functions::list_functions,
         request::functions::update_function,
         request::functions::save_function,
         request::functions::delete_function,
+        request::functions::test_function,
         request::functions::list_stream_functions,
         request::functions::add_function_to_stream,
         request::functions::delete_stream_function,
@@ -85,7 +134,13 @@ use crate::{common::meta, handler::http::request};
         request::dashboards::folders::list_folders,
         request::dashboards::folders::get_folder,
         request::dashboards::folders::update_folder,
+        request::dashboards::folders::move_folder,
         request::dashboards::move_dashboard,
+        request::revisions::list,
+        request::revisions::get,
+        request::revisions::diff,
+        request::revisions::restore,
+        request::revisions::list_deleted,
         request::alerts::save_alert,
         request::alerts::update_alert,
         request::alerts::list_stream_alerts,
@@ -93,6 +148,9 @@ use crate::{common::meta, handler::http::request};
         request::alerts::get_alert,
         request::alerts::delete_alert,
         request::alerts::enable_alert,
+        request::alerts::silence_alert,
+        request::alerts::acknowledge_alert,
+        request::alerts::slack::interactions,
         request::alerts::trigger_alert,
         request::alerts::templates::list_templates,
         request::alerts::templates::get_template,
@@ -112,6 +170,71 @@ use crate::{common::meta, handler::http::request};
         request::syslog::update_route,
         request::syslog::list_routes,
         request::syslog::delete_route,
+        request::snmp::create_route,
+        request::snmp::update_route,
+        request::snmp::list_routes,
+        request::snmp::delete_route,
+        request::snmp::save_mib,
+        request::snmp::list_mibs,
+        request::snmp::delete_mib,
+        request::monitors::save_monitor,
+        request::monitors::update_monitor,
+        request::monitors::get_monitor,
+        request::monitors::list_monitors,
+        request::monitors::delete_monitor,
+        request::quality_monitors::save_quality_monitor,
+        request::quality_monitors::update_quality_monitor,
+        request::quality_monitors::get_quality_monitor,
+        request::quality_monitors::list_quality_monitors,
+        request::quality_monitors::delete_quality_monitor,
+        request::quality_monitors::heartbeat,
+        request::continuous_queries::save_continuous_query,
+        request::continuous_queries::update_continuous_query,
+        request::continuous_queries::enable_continuous_query,
+        request::continuous_queries::get_continuous_query,
+        request::continuous_queries::list_continuous_queries,
+        request::continuous_queries::delete_continuous_query,
+        request::slo::save_slo,
+        request::slo::update_slo,
+        request::slo::get_slo,
+        request::slo::list_slos,
+        request::slo::delete_slo,
+        request::slo::get_slo_status,
+        request::slo::get_slo_history,
+        request::webhooks::save_webhook,
+        request::webhooks::update_webhook,
+        request::webhooks::get_webhook,
+        request::webhooks::list_webhooks,
+        request::webhooks::delete_webhook,
+        request::legal_holds::create_legal_hold,
+        request::legal_holds::list_legal_holds,
+        request::legal_holds::list_legal_hold_files,
+        request::legal_holds::release_legal_hold,
+        request::archives::list_archives,
+        request::archives::restore_archive,
+        request::reprocess::create_reprocess_job,
+        request::reprocess::list_reprocess_jobs,
+        request::reprocess::get_reprocess_job,
+        request::kafka::save_kafka_source,
+        request::kafka::get_kafka_source,
+        request::kafka::list_kafka_sources,
+        request::kafka::delete_kafka_source,
+        request::correlation::save_correlation_rule,
+        request::correlation::update_correlation_rule,
+        request::correlation::get_correlation_rule,
+        request::correlation::list_correlation_rules,
+        request::correlation::delete_correlation_rule,
+        request::threat_intel::save_indicator_list,
+        request::threat_intel::update_indicator_list,
+        request::threat_intel::get_indicator_list,
+        request::threat_intel::list_indicator_lists,
+        request::threat_intel::delete_indicator_list,
+        request::sigma::save_mapping_profile,
+        request::sigma::update_mapping_profile,
+        request::sigma::get_mapping_profile,
+        request::sigma::list_mapping_profiles,
+        request::sigma::delete_mapping_profile,
+        request::sigma::import_sigma_rules,
         request::clusters::list_clusters,
     ),
     components(
@@ -120,12 +243,41 @@ use crate::{common::meta, handler::http::request};
             StreamType,
             meta::stream::Stream,
             meta::stream::StreamProperty,
+            meta::stream::KeyMapping,
             meta::stream::StreamDeleteFields,
+            meta::stream::StreamPromoteFields,
+            meta::stream::StreamRename,
+            meta::stream::StreamClone,
             meta::stream::ListStream,
+            meta::stream::ListStreamGroups,
+            meta::stream::StreamGroup,
+            meta::stream::StreamStatsHistory,
+            meta::stream::StreamStatsPoint,
+            meta::stream::StreamStatsDrift,
+            meta::stream::CompactPriority,
+            meta::stream::CompactPriorityRequest,
+            meta::stream::RewriteJob,
+            meta::stream::RepartitionStatus,
+            meta::stream::StreamSettingsBulk,
+            meta::stream::StreamSettingsPatch,
+            meta::stream::StreamSettingsBulkResult,
+            meta::stream::Tombstone,
+            meta::stream::FileScanStats,
             config::meta::stream::StreamSettings,
             config::meta::stream::StreamPartition,
             config::meta::stream::StreamPartitionType,
+            config::meta::stream::VirtualField,
+            config::meta::stream::FieldDisplay,
+            config::meta::stream::FieldMetadata,
+            config::meta::stream::OtlpAttributes,
+            config::meta::stream::ParquetOptions,
+            config::utils::flatten::ArrayFlatten,
+            config::utils::flatten::KeyNormalization,
+            config::utils::flatten::KeyCollision,
+            config::meta::stream::DropRule,
+            config::meta::stream::DropCondition,
             config::meta::stream::StreamStats,
+            config::meta::stream::StreamQuotaUsage,
             config::meta::stream::PartitionTimeLevel,
             meta::ingestion::RecordStatus,
             meta::ingestion::StreamStatus,
@@ -146,13 +298,22 @@ use crate::{common::meta, handler::http::request};
             meta::dashboards::v1::VariableList,
             meta::dashboards::Folder,
             meta::dashboards::MoveDashboard,
+            meta::dashboards::MoveFolder,
             meta::dashboards::FolderList,
+            meta::revisions::ObjectType,
+            meta::revisions::RevisionAction,
+            meta::revisions::Revision,
+            meta::revisions::RevisionInfo,
+            meta::revisions::RevisionList,
+            meta::revisions::RevisionChange,
+            meta::revisions::RevisionDiff,
             config::meta::search::Query,
             config::meta::search::Request,
             config::meta::search::RequestEncoding,
             config::meta::search::Response,
             config::meta::search::ResponseTook,
             config::meta::search::ResponseNodeTook,
+            config::meta::search::TotalEstimate,
             config::meta::search::SearchPartitionRequest,
             config::meta::search::SearchPartitionResponse,
             config::meta::search::CancelQueryResponse,
@@ -167,7 +328,15 @@ use crate::{common::meta, handler::http::request};
             meta::saved_view::DeleteViewResponse,
             meta::saved_view::CreateViewResponse,
             meta::saved_view::UpdateViewRequest,
+            meta::search_templates::SearchTemplate,
+            meta::search_templates::TemplateParam,
+            meta::search_templates::TemplateParamType,
+            meta::search_templates::SearchTemplateList,
+            meta::search_templates::SearchTemplateRequest,
             meta::alerts::Alert,
+            meta::alerts::AlertAck,
+            meta::alerts::AlertThrottle,
+            meta::alerts::FlapDetection,
             meta::alerts::Condition,
             meta::alerts::Operator,
             meta::alerts::Aggregation,
@@ -176,6 +345,17 @@ use crate::{common::meta, handler::http::request};
             meta::alerts::TriggerCondition,
             meta::alerts::AlertFrequencyType,
             meta::alerts::QueryCondition,
+            meta::forecast::Forecast,
+            meta::forecast::ForecastMethod,
+            meta::forecast::ForecastFill,
+            meta::forecast::ForecastRequest,
+            meta::forecast::ForecastResponse,
+            meta::forecast::ForecastSeries,
+            meta::forecast::ForecastPoint,
+            meta::search_jobs::SearchJob,
+            meta::search_jobs::SearchJobStatus,
+            meta::search_jobs::SearchJobState,
+            meta::search_jobs::SearchJobResults,
             meta::alerts::destinations::Destination,
             meta::alerts::destinations::DestinationWithTemplate,
             meta::alerts::destinations::HTTPType,
@@ -186,6 +366,24 @@ use crate::{common::meta, handler::http::request};
             meta::functions::StreamFunctionsList,
             meta::functions::StreamTransform,
             meta::functions::StreamOrder,
+            meta::functions::Lineage,
+            meta::functions::LineageFunction,
+            meta::functions::LineageStage,
+            meta::functions::LineageSuperseded,
+            meta::functions::ReprocessJob,
+            meta::functions::ReprocessStatus,
+            meta::kafka::KafkaSource,
+            meta::kafka::KafkaFormat,
+            meta::kafka::KafkaStartOffset,
+            meta::kafka::KafkaOffset,
+            meta::functions::FunctionTestRequest,
+            meta::functions::FunctionTestResponse,
+            meta::functions::FunctionTestResult,
+            meta::ingestion::SimulationRequest,
+            meta::ingestion::SimulationResponse,
+            meta::ingestion::SimulatedRecord,
+            meta::ingestion::SchemaFieldChange,
+            meta::ingestion::SchemaChangeType,
             meta::user::UserRequest,
             meta::user::UpdateUser,
             meta::user::UserRole,
@@ -203,6 +401,10 @@ use crate::{common::meta, handler::http::request};
             meta::organization::PasscodeResponse,
             meta::organization::OrganizationSetting,
             meta::organization::OrganizationSettingResponse,
+            meta::organization::Feature,
+            meta::organization::OrgFeatureFlags,
+            meta::organization::QueryPolicy,
+            meta::organization::StreamTemplate,
             meta::organization::RumIngestionResponse,
             meta::organization::RumIngestionToken,
             request::status::HealthzResponse,
@@ -212,6 +414,46 @@ use crate::{common::meta, handler::http::request};
             meta::ingestion::BulkResponseError,
             meta::syslog::SyslogRoute,
             meta::syslog::SyslogRoutes,
+            meta::snmp::SnmpTrapRoute,
+            meta::snmp::SnmpTrapRoutes,
+            meta::snmp::SnmpUser,
+            meta::snmp::SnmpAuthProtocol,
+            meta::snmp::SnmpMib,
+            meta::snmp::SnmpMibList,
+            meta::monitors::Monitor,
+            meta::monitors::CheckType,
+            meta::quality_monitors::QualityMonitor,
+            meta::quality_monitors::QualityCheck,
+            meta::quality_monitors::Heartbeat,
+            meta::continuous_queries::ContinuousQuery,
+            meta::continuous_queries::PendingWindow,
+            meta::slo::Slo,
+            meta::slo::BurnRateRule,
+            meta::slo::SloStatus,
+            meta::slo::BurnRateStatus,
+            meta::slo::SloHistory,
+            meta::slo::SloHistoryPoint,
+            meta::webhooks::Webhook,
+            meta::webhooks::StreamEventType,
+            meta::webhooks::StreamEvent,
+            meta::stream::LegalHold,
+            meta::stream::LegalHoldFiles,
+            meta::stream::StreamArchive,
+            meta::stream::ArchiveRestoreRequest,
+            meta::stream::ArchiveRestore,
+            meta::correlation::CorrelationRule,
+            meta::correlation::CorrelationStep,
+            meta::correlation::Finding,
+            meta::threat_intel::IndicatorList,
+            meta::threat_intel::IndicatorType,
+            meta::threat_intel::IndicatorHit,
+            meta::sigma::MappingProfile,
+            meta::sigma::SigmaImportRequest,
+            meta::sigma::SigmaImportResponse,
+            meta::sigma::ImportedRule,
+            meta::sigma::ImportedKind,
+            meta::sigma::FailedRule,
+            meta::enrichment_table::EnrichmentTableSource,
             meta::prom::Metadata,
             meta::prom::MetricType,
          ),
@@ -222,8 +464,10 @@ use crate::{common::meta, handler::http::request};
         (name = "Auth", description = "User login authentication"),
         (name = "Logs", description = "Logs data ingestion operations"),
         (name = "Dashboards", description = "Dashboard operations"),
+        (name = "Revisions", description = "History of the dashboards, alerts and functions"),
         (name = "Search", description = "Search/Query operations"),
         (name = "Saved Views", description = "Collection of saved search views for easy retrieval"),
+        (name = "Search Templates", description = "Stored queries with parameters invoked by name"),
         (name = "Alerts", description = "Alerts retrieval & management operations"),
         (name = "Functions", description = "Functions retrieval & management operations"),
         (name = "Organizations", description = "Organizations retrieval & management operations"),
@@ -233,6 +477,19 @@ use crate::{common::meta, handler::http::request};
         (name = "Metrics", description = "Metrics data ingestion operations"),
         (name = "Traces", description = "Traces data ingestion operations"),
         (name = "Syslog Routes", description = "Syslog Routes retrieval & management operations"),
+        (name = "SNMP Traps", description = "SNMP trap routes & MIB management operations"),
+        (name = "Monitors", description = "Synthetic uptime checks retrieval & management operations"),
+        (name = "QualityMonitors", description = "Stream data quality monitors retrieval & management operations"),
+        (name = "ContinuousQueries", description = "Queries run over the new records of a stream into a derived stream"),
+        (name = "Slo", description = "Service level objectives and their error budget"),
+        (name = "Webhooks", description = "Webhooks notified of the stream lifecycle events"),
+        (name = "LegalHolds", description = "Time ranges of streams kept from deletion"),
+        (name = "Archives", description = "Expired stream files kept in the cold bucket and restored on demand"),
+        (name = "Reprocess", description = "Log records of a function lineage run again through the current functions"),
+        (name = "Kafka", description = "Kafka topics consumed into log streams"),
+        (name = "Correlation Rules", description = "Sequences of events across streams raising findings"),
+        (name = "Threat Intel", description = "Indicator lists the ingested logs are checked against"),
+        (name = "Sigma", description = "Sigma rules imported as alerts and correlation rules"),
         (name = "Clusters", description = "Super cluster operations"),
     ),
     info(
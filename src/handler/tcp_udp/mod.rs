@@ -13,13 +13,20 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::net::SocketAddr;
+
 use bytes::BytesMut;
+use config::CONFIG;
 use tokio::{
-    io::AsyncReadExt,
+    io::{AsyncRead, AsyncReadExt},
     net::{TcpListener, UdpSocket},
 };
+use tokio_rustls::TlsAcceptor;
 
-use crate::{job::syslog_server::BROADCASTER, service::logs::syslog};
+use crate::{
+    job::syslog_server::BROADCASTER,
+    service::logs::{netflow, snmp, syslog},
+};
 
 pub static STOP_SRV: &str = "ZO_STOP_TCP_UDP";
 
@@ -56,51 +63,186 @@ pub async fn udp_server(socket: UdpSocket) {
     }
 }
 
-pub async fn tcp_server(listener: TcpListener) {
-    let sender = BROADCASTER.read().await;
-    let mut tcp_receiver_rx = sender.subscribe();
-    let mut buf_tcp = vec![0u8; 1460];
+pub async fn netflow_server(socket: UdpSocket) {
+    // max udp payload, exporters may pack many flows in one datagram
+    let mut buf_udp = vec![0u8; 65535];
     loop {
-        let (mut stream, _) = match listener.accept().await {
+        let (recv_len, addr) = match socket.recv_from(&mut buf_udp).await {
             Ok(val) => val,
             Err(e) => {
-                log::error!("Error while accepting TCP connection: {}", e);
+                log::error!("Error while reading from NetFlow UDP socket: {}", e);
                 continue;
             }
         };
+        if let Err(e) = netflow::ingest(&buf_udp[..recv_len], addr).await {
+            log::error!("Error while ingesting NetFlow packet from {}: {}", addr, e);
+        }
+    }
+}
 
-        match stream.peer_addr() {
-            Ok(addr) => {
-                let len = match stream.read(&mut buf_tcp).await {
-                    Ok(val) => val,
-                    Err(e) => {
-                        log::error!("Error while reading from TCP stream: {}", e);
-                        continue;
-                    }
-                };
-                let message = BytesMut::from(&buf_tcp[..len]);
-                let input_str = match String::from_utf8(message.to_vec()) {
-                    Ok(val) => val,
-                    Err(e) => {
-                        log::error!("Error while converting TCP message to UTF8 string: {}", e);
-                        continue;
-                    }
-                };
-                if input_str != STOP_SRV {
-                    let _ = syslog::ingest(&input_str, addr).await;
-                }
-                if let Ok(val) = tcp_receiver_rx.try_recv() {
-                    if !val {
-                        log::warn!("TCP server - received the stop signal, exiting.");
-                        drop(listener);
-                        break;
-                    }
-                };
-            }
+pub async fn snmp_trap_server(socket: UdpSocket) {
+    let mut buf_udp = vec![0u8; 65535];
+    loop {
+        let (recv_len, addr) = match socket.recv_from(&mut buf_udp).await {
+            Ok(val) => val,
             Err(e) => {
-                log::error!("Error while writing to TCP stream: {}", e);
+                log::error!("Error while reading from SNMP trap UDP socket: {}", e);
                 continue;
             }
         };
+        if let Err(e) = snmp::ingest(&buf_udp[..recv_len], addr).await {
+            log::error!("Error while ingesting SNMP trap from {}: {}", addr, e);
+        }
+    }
+}
+
+pub async fn tcp_server(listener: TcpListener) {
+    let mut tcp_receiver_rx = BROADCASTER.read().await.subscribe();
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(val) => val,
+                Err(e) => {
+                    log::error!("Error while accepting TCP connection: {}", e);
+                    continue;
+                }
+            },
+            Ok(false) = tcp_receiver_rx.recv() => {
+                log::warn!("TCP server - received the stop signal, exiting.");
+                drop(listener);
+                break;
+            }
+        };
+        tokio::task::spawn(read_syslog_frames(stream, addr));
+    }
+}
+
+pub async fn tls_server(listener: TcpListener, acceptor: TlsAcceptor) {
+    let mut tls_receiver_rx = BROADCASTER.read().await.subscribe();
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(val) => val,
+                Err(e) => {
+                    log::error!("Error while accepting TLS connection: {}", e);
+                    continue;
+                }
+            },
+            Ok(false) = tls_receiver_rx.recv() => {
+                log::warn!("TLS server - received the stop signal, exiting.");
+                drop(listener);
+                break;
+            }
+        };
+        let acceptor = acceptor.clone();
+        tokio::task::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => read_syslog_frames(stream, addr).await,
+                Err(e) => log::error!("Error during TLS handshake with {}: {}", addr, e),
+            }
+        });
+    }
+}
+
+/// Reads the syslog messages sent over one connection until it's closed.
+async fn read_syslog_frames<R: AsyncRead + Unpin>(mut reader: R, addr: SocketAddr) {
+    let mut buf = BytesMut::with_capacity(4096);
+    loop {
+        while let Some(frame) = next_frame(&mut buf) {
+            ingest_frame(&frame, addr).await;
+        }
+        if buf.len() > CONFIG.tcp.max_frame_size {
+            log::error!(
+                "Syslog frame from {} exceeds {} bytes, closing the connection",
+                addr,
+                CONFIG.tcp.max_frame_size
+            );
+            return;
+        }
+        match reader.read_buf(&mut buf).await {
+            Ok(0) => {
+                // the last message doesn't need a trailing newline
+                if !buf.is_empty() {
+                    ingest_frame(&buf, addr).await;
+                }
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Error while reading from TCP stream: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn ingest_frame(frame: &[u8], addr: SocketAddr) {
+    let input_str = String::from_utf8_lossy(frame);
+    if input_str.is_empty() || input_str == STOP_SRV {
+        return;
+    }
+    if let Err(e) = syslog::ingest(&input_str, addr).await {
+        log::error!("Error while ingesting syslog message from {}: {}", addr, e);
+    }
+}
+
+/// Splits the next message off the buffer, supporting both the octet counting
+/// (`MSG-LEN SP MSG`) and the newline delimited framing of RFC 6587. Returns
+/// `None` when the buffer doesn't hold a complete message yet.
+fn next_frame(buf: &mut BytesMut) -> Option<BytesMut> {
+    let separators = buf
+        .iter()
+        .take_while(|b| matches!(b, b'\n' | b'\r' | b'\0'))
+        .count();
+    let _ = buf.split_to(separators);
+    if buf.is_empty() {
+        return None;
+    }
+
+    let digits = buf.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits == buf.len() {
+        // the length prefix may be incomplete
+        return None;
+    }
+    if digits > 0 && buf[digits] == b' ' {
+        let len = std::str::from_utf8(&buf[..digits])
+            .unwrap()
+            .parse::<usize>()
+            .unwrap_or(usize::MAX);
+        if buf.len() - digits - 1 < len {
+            return None;
+        }
+        let mut frame = buf.split_to(digits + 1 + len);
+        return Some(frame.split_off(digits + 1));
+    }
+
+    let end = buf.iter().position(|b| *b == b'\n')?;
+    let mut frame = buf.split_to(end + 1);
+    frame.truncate(end);
+    if frame.last() == Some(&b'\r') {
+        frame.truncate(end - 1);
+    }
+    Some(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_frame() {
+        let mut buf = BytesMut::from(&b"<13>first\n<13>second\r\n10 <13>third\n11 <13>fou"[..]);
+        assert_eq!(next_frame(&mut buf).unwrap(), &b"<13>first"[..]);
+        assert_eq!(next_frame(&mut buf).unwrap(), &b"<13>second"[..]);
+        assert_eq!(next_frame(&mut buf).unwrap(), &b"<13>third\n"[..]);
+        assert!(next_frame(&mut buf).is_none());
+        buf.extend_from_slice(b"rth\n");
+        assert_eq!(next_frame(&mut buf).unwrap(), &b"<13>fourth\n"[..]);
+        assert!(next_frame(&mut buf).is_none());
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from(&b"12"[..]);
+        assert!(next_frame(&mut buf).is_none());
+        assert_eq!(buf, &b"12"[..]);
     }
 }
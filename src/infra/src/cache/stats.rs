@@ -14,13 +14,14 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use config::{
-    meta::stream::{FileMeta, StreamStats, StreamType},
+    meta::stream::{FileMeta, StreamQuotaUsage, StreamStats, StreamType},
     RwHashMap,
 };
 use once_cell::sync::Lazy;
 
 const STREAM_STATS_MEM_SIZE: usize = std::mem::size_of::<StreamStats>();
 static STATS: Lazy<RwHashMap<String, StreamStats>> = Lazy::new(Default::default);
+static QUOTA_USAGE: Lazy<RwHashMap<String, StreamQuotaUsage>> = Lazy::new(Default::default);
 
 #[inline]
 pub fn get_stats() -> RwHashMap<String, StreamStats> {
@@ -84,6 +85,58 @@ pub fn incr_stream_stats(key: &str, val: &FileMeta) -> Result<(), anyhow::Error>
     Ok(())
 }
 
+#[inline]
+pub fn get_stream_quota_usage(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Option<StreamQuotaUsage> {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    QUOTA_USAGE.get(&key).map(|v| *v.value())
+}
+
+/// Runs `f` on the quota usage of the stream, holding the entry so concurrent
+/// requests of the stream are counted one after the other.
+#[inline]
+pub fn update_stream_quota_usage<R>(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    f: impl FnOnce(&mut StreamQuotaUsage) -> R,
+) -> R {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    let mut usage = QUOTA_USAGE.entry(key).or_default();
+    f(&mut usage)
+}
+
+/// Sets the quota usage of the stream unless it is counted already.
+#[inline]
+pub fn init_stream_quota_usage(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    val: StreamQuotaUsage,
+) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    QUOTA_USAGE.entry(key).or_insert(val);
+}
+
+/// The quota usage of every stream counted here, keyed by
+/// org_id/stream_type/stream_name.
+#[inline]
+pub fn list_stream_quota_usage() -> Vec<(String, StreamQuotaUsage)> {
+    QUOTA_USAGE
+        .iter()
+        .map(|v| (v.key().clone(), *v.value()))
+        .collect()
+}
+
+#[inline]
+pub fn remove_stream_quota_usage(org_id: &str, stream_name: &str, stream_type: StreamType) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    QUOTA_USAGE.remove(&key);
+}
+
 #[inline]
 pub fn get_stream_stats_len() -> usize {
     STATS.len()
@@ -59,6 +59,7 @@ fn connect_rw() -> Pool<Sqlite> {
         .busy_timeout(Duration::from_secs(30))
         // .disable_statement_logging()
         .create_if_missing(true);
+    let db_opts = with_memory_limits(db_opts);
 
     SqlitePoolOptions::new()
         .min_connections(1)
@@ -77,6 +78,7 @@ fn connect_ro() -> Pool<Sqlite> {
         .busy_timeout(Duration::from_secs(30))
         // .disable_statement_logging()
         .read_only(true);
+    let db_opts = with_memory_limits(db_opts);
     SqlitePoolOptions::new()
         .min_connections(CONFIG.limit.sql_min_db_connections)
         .max_connections(CONFIG.limit.sql_max_db_connections)
@@ -84,6 +86,21 @@ fn connect_ro() -> Pool<Sqlite> {
         .connect_lazy_with(db_opts)
 }
 
+/// The file_list can hold hundreds of millions of rows, so the database is
+/// read through a memory map and only the hot pages are kept in the page cache
+/// of each connection.
+fn with_memory_limits(db_opts: SqliteConnectOptions) -> SqliteConnectOptions {
+    // a negative cache_size is in KiB instead of pages
+    let db_opts = db_opts.pragma(
+        "cache_size",
+        format!("-{}", CONFIG.limit.sqlite_cache_size * 1024),
+    );
+    db_opts.pragma(
+        "mmap_size",
+        (CONFIG.limit.sqlite_mmap_size * 1024 * 1024).to_string(),
+    )
+}
+
 pub struct SqliteDbChannel {
     pub watch_tx: EventChannel,
 }
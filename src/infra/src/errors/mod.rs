@@ -84,6 +84,8 @@ pub enum DbError {
     KeyNotExists(String),
     #[error("error {0} performing operation on key {1}")]
     DBOperError(String, String),
+    #[error("key {0} was modified, expected revision {1} but found {2}")]
+    RevisionConflict(String, u64, u64),
 }
 
 #[derive(ThisError, Debug)]
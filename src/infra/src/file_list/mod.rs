@@ -91,6 +91,17 @@ pub trait FileList: Sync + Send + 'static {
     async fn set_stream_stats(&self, org_id: &str, streams: &[(String, StreamStats)])
     -> Result<()>;
     async fn reset_stream_stats(&self) -> Result<()>;
+    async fn add_stream_stats_history(
+        &self,
+        ts: i64,
+        streams: &[(String, StreamStats)],
+    ) -> Result<()>;
+    async fn get_stream_stats_history(
+        &self,
+        stream: &str,
+        time_range: (i64, i64),
+    ) -> Result<Vec<(i64, StreamStats)>>;
+    async fn clean_stream_stats_history(&self, before: i64) -> Result<()>;
     async fn reset_stream_stats_min_ts(
         &self,
         org_id: &str,
@@ -226,6 +237,24 @@ pub async fn reset_stream_stats_min_ts(org_id: &str, stream: &str, min_ts: i64)
         .await
 }
 
+#[inline]
+pub async fn add_stream_stats_history(ts: i64, streams: &[(String, StreamStats)]) -> Result<()> {
+    CLIENT.add_stream_stats_history(ts, streams).await
+}
+
+#[inline]
+pub async fn get_stream_stats_history(
+    stream: &str,
+    time_range: (i64, i64),
+) -> Result<Vec<(i64, StreamStats)>> {
+    CLIENT.get_stream_stats_history(stream, time_range).await
+}
+
+#[inline]
+pub async fn clean_stream_stats_history(before: i64) -> Result<()> {
+    CLIENT.clean_stream_stats_history(before).await
+}
+
 #[inline]
 pub async fn len() -> usize {
     CLIENT.len().await
@@ -291,6 +320,29 @@ impl From<&StatsRecord> for StreamStats {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StatsHistoryRecord {
+    pub ts: i64,
+    pub file_num: i64,
+    pub records: i64,
+    pub original_size: i64,
+    pub compressed_size: i64,
+}
+
+impl From<&StatsHistoryRecord> for StreamStats {
+    fn from(record: &StatsHistoryRecord) -> Self {
+        Self {
+            created_at: 0,
+            doc_time_min: 0,
+            doc_time_max: 0,
+            doc_num: record.records,
+            file_num: record.file_num,
+            storage_size: record.original_size as f64,
+            compressed_size: record.compressed_size as f64,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
 pub struct FileDeletedRecord {
     pub stream: String,
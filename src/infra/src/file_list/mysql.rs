@@ -592,6 +592,74 @@ UPDATE stream_stats
         Ok(())
     }
 
+    async fn add_stream_stats_history(
+        &self,
+        ts: i64,
+        streams: &[(String, StreamStats)],
+    ) -> Result<()> {
+        if streams.is_empty() {
+            return Ok(());
+        }
+        let chunks = streams.chunks(100);
+        for streams in chunks {
+            let pool = CLIENT.clone();
+            let mut tx = pool.begin().await?;
+            let mut query_builder: QueryBuilder<MySql> = QueryBuilder::new(
+                "INSERT INTO stream_stats_history (org, stream, ts, file_num, records, original_size, compressed_size)",
+            );
+            query_builder.push_values(streams, |mut b, (stream_key, stats)| {
+                let org_id = stream_key[..stream_key.find('/').unwrap()].to_string();
+                b.push_bind(org_id)
+                    .push_bind(stream_key.to_string())
+                    .push_bind(ts)
+                    .push_bind(stats.file_num)
+                    .push_bind(stats.doc_num)
+                    .push_bind(stats.storage_size as i64)
+                    .push_bind(stats.compressed_size as i64);
+            });
+            if let Err(e) = query_builder.build().execute(&mut *tx).await {
+                if let Err(e) = tx.rollback().await {
+                    log::error!(
+                        "[MYSQL] rollback stream_stats_history batch add error: {}",
+                        e
+                    );
+                }
+                return Err(e.into());
+            };
+            if let Err(e) = tx.commit().await {
+                log::error!("[MYSQL] commit stream_stats_history batch add error: {}", e);
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_stream_stats_history(
+        &self,
+        stream: &str,
+        time_range: (i64, i64),
+    ) -> Result<Vec<(i64, StreamStats)>> {
+        let pool = CLIENT.clone();
+        let ret = sqlx::query_as::<_, super::StatsHistoryRecord>(
+            r#"SELECT ts, file_num, records, original_size, compressed_size FROM stream_stats_history WHERE stream = ? AND ts >= ? AND ts <= ? ORDER BY ts;"#,
+        )
+        .bind(stream)
+        .bind(time_range.0)
+        .bind(time_range.1)
+        .fetch_all(&pool)
+        .await?;
+        Ok(ret.iter().map(|r| (r.ts, r.into())).collect())
+    }
+
+    async fn clean_stream_stats_history(&self, before: i64) -> Result<()> {
+        let pool = CLIENT.clone();
+        sqlx::query(r#"DELETE FROM stream_stats_history WHERE ts < ?;"#)
+            .bind(before)
+            .execute(&pool)
+            .await?;
+        Ok(())
+    }
+
     async fn len(&self) -> usize {
         let pool = CLIENT.clone();
         let ret = match sqlx::query(r#"SELECT CAST(COUNT(*) AS SIGNED) AS num FROM file_list;"#)
@@ -677,6 +745,24 @@ CREATE TABLE IF NOT EXISTS stream_stats
     .execute(&pool)
     .await?;
 
+    sqlx::query(
+        r#"
+CREATE TABLE IF NOT EXISTS stream_stats_history
+(
+    id       BIGINT not null primary key AUTO_INCREMENT,
+    org      VARCHAR(100) not null,
+    stream   VARCHAR(256) not null,
+    ts       BIGINT not null,
+    file_num BIGINT not null,
+    records  BIGINT not null,
+    original_size   BIGINT not null,
+    compressed_size BIGINT not null
+);
+        "#,
+    )
+    .execute(&pool)
+    .await?;
+
     Ok(())
 }
 
@@ -711,6 +797,14 @@ pub async fn create_table_index() -> Result<()> {
             "stream_stats",
             "CREATE UNIQUE INDEX stream_stats_stream_idx on stream_stats (stream);",
         ),
+        (
+            "stream_stats_history",
+            "CREATE INDEX stream_stats_history_stream_ts_idx on stream_stats_history (stream, ts);",
+        ),
+        (
+            "stream_stats_history",
+            "CREATE INDEX stream_stats_history_ts_idx on stream_stats_history (ts);",
+        ),
     ];
     for (table, sql) in sqls {
         if let Err(e) = sqlx::query(sql).execute(&pool).await {
@@ -79,6 +79,9 @@ pub enum TriggerModule {
     Report,
     #[default]
     Alert,
+    QualityMonitor,
+    ContinuousQuery,
+    Slo,
 }
 
 #[derive(sqlx::FromRow, Debug, Clone, Default)]
@@ -16,7 +16,10 @@
 use chrono::Utc;
 use config::{
     meta::stream::{PartitionTimeLevel, StreamSettings, StreamType},
-    utils::json,
+    utils::{
+        hash::{fnv, Sum64},
+        json,
+    },
     RwAHashMap, CONFIG,
 };
 use datafusion::arrow::datatypes::{DataType, Field, Schema};
@@ -32,6 +35,10 @@ pub static STREAM_SCHEMAS_LATEST: Lazy<RwAHashMap<String, Schema>> = Lazy::new(D
 pub static STREAM_SCHEMAS_FIELDS: Lazy<RwAHashMap<String, (i64, Vec<String>)>> =
     Lazy::new(Default::default);
 pub static STREAM_SETTINGS: Lazy<RwAHashMap<String, StreamSettings>> = Lazy::new(Default::default);
+/// Version of the cached schema of each stream, see [schema_version]
+pub static STREAM_SCHEMAS_VERSION: Lazy<RwAHashMap<String, u64>> = Lazy::new(Default::default);
+
+pub const SETTINGS_REVISION_KEY: &str = "settings_revision";
 
 pub fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
     format!("/schema/{org_id}/{stream_type}/{stream_name}")
@@ -134,6 +141,30 @@ pub async fn get_settings(
         .and_then(|schema| unwrap_stream_settings(&schema))
 }
 
+/// Returns the version of the schema versions of a stream, a fingerprint of
+/// the fields and metadata which is the same on every node holding the same
+/// schema.
+pub fn schema_version(schemas: &[Schema]) -> u64 {
+    let mut buf = String::new();
+    for schema in schemas {
+        for field in schema.fields() {
+            buf.push_str(&format!(
+                "{}:{}:{},",
+                field.name(),
+                field.data_type(),
+                field.is_nullable()
+            ));
+        }
+        let mut metadata = schema.metadata().iter().collect::<Vec<_>>();
+        metadata.sort();
+        for (k, v) in metadata {
+            buf.push_str(&format!("{k}={v};"));
+        }
+        buf.push('|');
+    }
+    fnv::new().sum64(&buf)
+}
+
 pub fn unwrap_stream_settings(schema: &Schema) -> Option<StreamSettings> {
     if schema.metadata().is_empty() {
         return None;
@@ -258,13 +289,16 @@ pub async fn set(
     }
 }
 
+/// Merges the schema into the stored one, returns the merged schema, the fields
+/// whose type changed and the stored schema it was merged into, `None` when
+/// the merge created the stream.
 pub async fn merge(
     org_id: &str,
     stream_name: &str,
     stream_type: StreamType,
     schema: &Schema,
     min_ts: Option<i64>,
-) -> Result<Option<(Schema, Vec<Field>)>, anyhow::Error> {
+) -> Result<Option<(Schema, Vec<Field>, Option<Schema>)>, anyhow::Error> {
     let start_dt = min_ts;
     let key = mk_key(org_id, stream_type, stream_name);
     #[cfg(feature = "enterprise")]
@@ -285,7 +319,8 @@ pub async fn merge(
                         json::to_vec(&vec![{
                             // there is no schema, just set the new schema
                             let schema_metadata = inferred_schema.metadata();
-                            tx.send(Some((inferred_schema.clone(), vec![]))).unwrap();
+                            tx.send(Some((inferred_schema.clone(), vec![], None)))
+                                .unwrap();
                             if schema_metadata.contains_key("created_at")
                                 && schema_metadata.contains_key("start_dt")
                             {
@@ -327,8 +362,12 @@ pub async fn merge(
                     let (is_schema_changed, field_datatype_delta, merged_fields) =
                         get_merge_schema_changes(latest_schema, &inferred_schema);
                     if !is_schema_changed {
-                        tx.send(Some((latest_schema.clone(), field_datatype_delta)))
-                            .unwrap();
+                        tx.send(Some((
+                            latest_schema.clone(),
+                            field_datatype_delta,
+                            Some(latest_schema.clone()),
+                        )))
+                        .unwrap();
                         return Ok(None); // no change, return
                     }
                     let metadata = latest_schema.metadata().clone();
@@ -342,15 +381,24 @@ pub async fn merge(
                         let mut new_metadata = latest_schema.metadata().clone();
                         new_metadata.insert("start_dt".to_string(), start_dt.unwrap().to_string());
                         let new_schema = vec![final_schema.clone().with_metadata(new_metadata)];
-                        tx.send(Some((final_schema, field_datatype_delta))).unwrap();
+                        tx.send(Some((
+                            final_schema,
+                            field_datatype_delta,
+                            Some(latest_schema.clone()),
+                        )))
+                        .unwrap();
                         Ok(Some((
                             Some(json::to_vec(&prev_schema).unwrap().into()),
                             Some((key, json::to_vec(&new_schema).unwrap().into(), start_dt)),
                         )))
                     } else {
                         // just update the latest schema
-                        tx.send(Some((final_schema.clone(), field_datatype_delta)))
-                            .unwrap();
+                        tx.send(Some((
+                            final_schema.clone(),
+                            field_datatype_delta,
+                            Some(latest_schema.clone()),
+                        )))
+                        .unwrap();
                         Ok(Some((
                             Some(json::to_vec(&vec![final_schema]).unwrap().into()),
                             None,
@@ -378,11 +426,34 @@ pub async fn merge(
     Ok(rx.await?)
 }
 
+/// Returns the revision of the settings of the schema, which is increased by
+/// every [update_setting_with_revision]
+pub fn get_settings_revision(schema: &Schema) -> u64 {
+    schema
+        .metadata()
+        .get(SETTINGS_REVISION_KEY)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_default()
+}
+
 pub async fn update_setting(
     org_id: &str,
     stream_name: &str,
     stream_type: StreamType,
     metadata: std::collections::HashMap<String, String>,
+) -> Result<(), anyhow::Error> {
+    update_setting_with_revision(org_id, stream_name, stream_type, metadata, None).await
+}
+
+/// Updates the metadata of the latest schema, when `revision` is set the update
+/// fails with [DbError::RevisionConflict] unless the settings are still at
+/// that revision.
+pub async fn update_setting_with_revision(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    metadata: std::collections::HashMap<String, String>,
+    revision: Option<u64>,
 ) -> Result<(), anyhow::Error> {
     let key = mk_key(org_id, stream_type, stream_name);
     #[cfg(feature = "enterprise")]
@@ -406,10 +477,24 @@ pub async fn update_setting(
                     }
                 }
             };
+            let current_revision = get_settings_revision(&latest_schema);
+            if let Some(revision) = revision {
+                if revision != current_revision {
+                    return Err(Error::DbError(DbError::RevisionConflict(
+                        key,
+                        revision,
+                        current_revision,
+                    )));
+                }
+            }
             let mut schema_metadata = latest_schema.metadata().clone();
             for (k, v) in metadata.iter() {
                 schema_metadata.insert(k.clone(), v.clone());
             }
+            schema_metadata.insert(
+                SETTINGS_REVISION_KEY.to_string(),
+                (current_revision + 1).to_string(),
+            );
             let start_dt = match schema_metadata.get("created_at") {
                 Some(v) => v.parse().unwrap(),
                 None => Utc::now().timestamp_micros(),
@@ -546,6 +631,42 @@ pub async fn delete_fields(
     Ok(())
 }
 
+/// Writes the schema versions of a stream under another name, the latest
+/// version last so the watchers load the whole history.
+pub async fn copy(
+    org_id: &str,
+    stream_type: StreamType,
+    from: &str,
+    to: &str,
+) -> Result<(), anyhow::Error> {
+    let versions = get_versions(org_id, from, stream_type).await?;
+    if versions.is_empty() {
+        return Err(anyhow::anyhow!("stream {from} not found"));
+    }
+    let key = mk_key(org_id, stream_type, to);
+    let db = infra_db::get_db().await;
+    let last = versions.len() - 1;
+    for (i, schema) in versions.into_iter().enumerate() {
+        let start_dt = schema
+            .metadata()
+            .get("start_dt")
+            .and_then(|v| v.parse::<i64>().ok());
+        let need_watch = if i == last {
+            infra_db::NEED_WATCH
+        } else {
+            infra_db::NO_NEED_WATCH
+        };
+        db.put(
+            &key,
+            json::to_vec(&vec![schema]).unwrap().into(),
+            need_watch,
+            start_dt,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 pub async fn delete(
     org_id: &str,
     stream_name: &str,
@@ -673,4 +794,47 @@ mod tests {
     fn test_is_widening_conversion() {
         assert!(is_widening_conversion(&DataType::Int8, &DataType::Int32));
     }
+
+    #[test]
+    fn test_get_settings_revision() {
+        let schema = Schema::empty();
+        assert_eq!(get_settings_revision(&schema), 0);
+        let schema = schema.with_metadata(std::collections::HashMap::from([(
+            SETTINGS_REVISION_KEY.to_string(),
+            "7".to_string(),
+        )]));
+        assert_eq!(get_settings_revision(&schema), 7);
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let fields = vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("log", DataType::Utf8, true),
+        ];
+        let metadata = std::collections::HashMap::from([
+            ("created_at".to_string(), "1".to_string()),
+            ("start_dt".to_string(), "1".to_string()),
+            ("settings".to_string(), "{}".to_string()),
+        ]);
+        let schema = Schema::new(fields.clone()).with_metadata(metadata.clone());
+        let same = Schema::new(fields.clone()).with_metadata(metadata.into_iter().collect());
+        assert_eq!(
+            schema_version(&[schema.clone()]),
+            schema_version(&[same.clone()])
+        );
+
+        let widened = Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("log", DataType::LargeUtf8, true),
+        ]);
+        assert_ne!(
+            schema_version(&[schema.clone()]),
+            schema_version(&[widened])
+        );
+        assert_ne!(
+            schema_version(&[schema.clone()]),
+            schema_version(&[schema.clone(), same])
+        );
+    }
 }
@@ -0,0 +1,50 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Cold bucket the expired files of archived streams are moved to, the files
+//! keep their key under the archive bucket prefix.
+
+use config::CONFIG;
+use object_store::{limit::LimitStore, ObjectStore};
+use once_cell::sync::Lazy;
+
+use crate::storage::remote::init_archive_client;
+
+static ARCHIVE: Lazy<LimitStore<Box<dyn ObjectStore>>> = Lazy::new(init_archive_client);
+
+/// Whether an archive bucket is configured.
+#[inline]
+pub fn is_enabled() -> bool {
+    !CONFIG.s3.archive_bucket_name.is_empty()
+}
+
+fn format_key(key: &str) -> String {
+    format!("{}{}", CONFIG.s3.archive_bucket_prefix, key)
+}
+
+pub async fn get(file: &str) -> Result<bytes::Bytes, anyhow::Error> {
+    let data = ARCHIVE.get(&format_key(file).into()).await?;
+    let data = data.bytes().await?;
+    Ok(data)
+}
+
+pub async fn put(file: &str, data: bytes::Bytes) -> Result<(), anyhow::Error> {
+    ARCHIVE.put(&format_key(file).into(), data).await?;
+    Ok(())
+}
+
+pub async fn del(file: &str) -> Result<(), anyhow::Error> {
+    ARCHIVE.delete(&format_key(file).into()).await?;
+    Ok(())
+}
@@ -18,6 +18,7 @@ use futures::{StreamExt, TryStreamExt};
 use object_store::ObjectStore;
 use once_cell::sync::Lazy;
 
+pub mod archive;
 pub mod local;
 pub mod remote;
 
@@ -74,6 +75,14 @@ pub async fn get(file: &str) -> Result<bytes::Bytes, anyhow::Error> {
     Ok(data)
 }
 
+pub async fn get_range(
+    file: &str,
+    range: std::ops::Range<usize>,
+) -> Result<bytes::Bytes, anyhow::Error> {
+    let data = DEFAULT.get_range(&file.into(), range).await?;
+    Ok(data)
+}
+
 pub async fn put(file: &str, data: bytes::Bytes) -> Result<(), anyhow::Error> {
     DEFAULT.put(&file.into(), data).await?;
     Ok(())
@@ -13,20 +13,31 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::ops::Range;
+use std::{
+    ops::Range,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use config::{metrics, CONFIG};
-use futures::stream::BoxStream;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
 use object_store::{
-    limit::LimitStore, path::Path, Error, GetOptions, GetResult, ListResult, MultipartId,
-    ObjectMeta, ObjectStore, PutOptions, PutResult, Result,
+    limit::LimitStore, path::Path, Error, GetOptions, GetResult, GetResultPayload, ListResult,
+    MultipartId, ObjectMeta, ObjectStore, PutOptions, PutResult, Result,
 };
-use tokio::io::AsyncWrite;
+use once_cell::sync::Lazy;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use crate::storage::{format_key, CONCURRENT_REQUESTS};
 
+static BANDWIDTH: Lazy<BandwidthLimiter> =
+    Lazy::new(|| BandwidthLimiter::new(CONFIG.s3.max_bandwidth));
+
 pub struct Remote {
     client: LimitStore<Box<dyn object_store::ObjectStore>>,
 }
@@ -34,7 +45,7 @@ pub struct Remote {
 impl Default for Remote {
     fn default() -> Self {
         Self {
-            client: LimitStore::new(init_client(), CONCURRENT_REQUESTS),
+            client: LimitStore::new(init_client(&Bucket::main()), CONCURRENT_REQUESTS),
         }
     }
 }
@@ -62,11 +73,14 @@ impl ObjectStore for Remote {
         let start = std::time::Instant::now();
         let file = location.to_string();
         let data_size = bytes.len();
-        match self
-            .client
-            .put(&(format_key(&file, true).into()), bytes)
-            .await
-        {
+        let key = format_key(&file, true).into();
+        let result = if data_size > CONFIG.s3.multipart_part_size {
+            self.put_in_parts(&key, bytes).await
+        } else {
+            BANDWIDTH.acquire(data_size).await;
+            self.client.put(&key, bytes).await.map(|_| ())
+        };
+        match result {
             Ok(_) => {
                 // metrics
                 let columns = file.split('/').collect::<Vec<&str>>();
@@ -108,7 +122,21 @@ impl ObjectStore for Remote {
     async fn get(&self, location: &Path) -> Result<GetResult> {
         let start = std::time::Instant::now();
         let file = location.to_string();
-        let result = self.client.get(&(format_key(&file, true).into())).await?;
+        let key = format_key(&file, true).into();
+        // the size tells whether the object is fetched in parts before any
+        // of its data is requested
+        let meta = self.client.head(&key).await?;
+        let result = if meta.size > CONFIG.s3.multipart_part_size {
+            let data = self.get_in_parts(&key, meta.size).await?;
+            GetResult {
+                payload: GetResultPayload::Stream(stream::once(async move { Ok(data) }).boxed()),
+                range: 0..meta.size,
+                meta,
+            }
+        } else {
+            BANDWIDTH.acquire(meta.size).await;
+            self.client.get(&key).await?
+        };
 
         // metrics
         let data_len = result.meta.size;
@@ -160,6 +188,7 @@ impl ObjectStore for Remote {
     async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
         let start = std::time::Instant::now();
         let file = location.to_string();
+        BANDWIDTH.acquire(range.len()).await;
         let data = self
             .client
             .get_range(&(format_key(&file, true).into()), range)
@@ -226,7 +255,147 @@ impl ObjectStore for Remote {
     }
 }
 
-fn init_aws_config() -> object_store::Result<object_store::aws::AmazonS3> {
+impl Remote {
+    /// Uploads the data as a multipart upload, the object store keeps several
+    /// parts in flight while the next ones are written. Flushing the writer
+    /// every `multipart_concurrency` parts waits for the parts in flight.
+    async fn put_in_parts(&self, key: &Path, bytes: Bytes) -> Result<()> {
+        let (multipart_id, mut writer) = self.client.put_multipart(key).await?;
+        for (i, part) in bytes.chunks(CONFIG.s3.multipart_part_size).enumerate() {
+            BANDWIDTH.acquire(part.len()).await;
+            let mut written = writer.write_all(part).await;
+            if written.is_ok() && (i + 1) % CONFIG.s3.multipart_concurrency == 0 {
+                written = writer.flush().await;
+            }
+            if let Err(e) = written {
+                self.abort_upload(key, &multipart_id).await;
+                return Err(io_error(e));
+            }
+        }
+        if let Err(e) = writer.shutdown().await {
+            self.abort_upload(key, &multipart_id).await;
+            return Err(io_error(e));
+        }
+        Ok(())
+    }
+
+    async fn abort_upload(&self, key: &Path, multipart_id: &MultipartId) {
+        if let Err(e) = self.client.abort_multipart(key, multipart_id).await {
+            log::error!("s3 abort multipart upload {} error: {:?}", key, e);
+        }
+    }
+
+    /// Downloads the object with concurrent range requests.
+    async fn get_in_parts(&self, key: &Path, size: usize) -> Result<Bytes> {
+        let part_size = CONFIG.s3.multipart_part_size;
+        let ranges = (0..size)
+            .step_by(part_size)
+            .map(|start| start..std::cmp::min(start + part_size, size));
+        let parts = stream::iter(ranges)
+            .map(|range| async move {
+                BANDWIDTH.acquire(range.len()).await;
+                self.client.get_range(key, range).await
+            })
+            .buffered(CONFIG.s3.multipart_concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut data = BytesMut::with_capacity(size);
+        for part in parts {
+            data.extend_from_slice(&part);
+        }
+        Ok(data.freeze())
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::Generic {
+        store: "remote",
+        source: Box::new(e),
+    }
+}
+
+/// Token bucket shared by all the transfers of this node. A transfer larger
+/// than the available budget goes into debt and the following ones wait.
+struct BandwidthLimiter {
+    bytes_per_sec: usize,
+    state: Mutex<(f64, Instant)>, // available bytes, last refill
+}
+
+impl BandwidthLimiter {
+    fn new(bytes_per_sec: usize) -> Self {
+        Self {
+            bytes_per_sec,
+            state: Mutex::new((bytes_per_sec as f64, Instant::now())),
+        }
+    }
+
+    async fn acquire(&self, bytes: usize) {
+        if self.bytes_per_sec == 0 || bytes == 0 {
+            return;
+        }
+        let wait = self.reserve(bytes, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut state = self.state.lock().unwrap();
+        let (available, last) = &mut *state;
+        // refill, allowing at most one second of burst
+        *available =
+            (*available + now.saturating_duration_since(*last).as_secs_f64() * rate).min(rate);
+        *last = now;
+        *available -= bytes as f64;
+        if *available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-*available / rate)
+        }
+    }
+}
+
+/// Location and credentials of a bucket.
+struct Bucket {
+    provider: &'static str,
+    name: &'static str,
+    server_url: &'static str,
+    region_name: &'static str,
+    access_key: &'static str,
+    secret_key: &'static str,
+}
+
+impl Bucket {
+    fn main() -> Self {
+        Self {
+            provider: &CONFIG.s3.provider,
+            name: &CONFIG.s3.bucket_name,
+            server_url: &CONFIG.s3.server_url,
+            region_name: &CONFIG.s3.region_name,
+            access_key: &CONFIG.s3.access_key,
+            secret_key: &CONFIG.s3.secret_key,
+        }
+    }
+
+    fn archive() -> Self {
+        Self {
+            provider: &CONFIG.s3.archive_provider,
+            name: &CONFIG.s3.archive_bucket_name,
+            server_url: &CONFIG.s3.archive_server_url,
+            region_name: &CONFIG.s3.archive_region_name,
+            access_key: &CONFIG.s3.archive_access_key,
+            secret_key: &CONFIG.s3.archive_secret_key,
+        }
+    }
+}
+
+/// Returns a client of the archive bucket, the keys are not prefixed.
+pub(crate) fn init_archive_client() -> LimitStore<Box<dyn object_store::ObjectStore>> {
+    LimitStore::new(init_client(&Bucket::archive()), CONCURRENT_REQUESTS)
+}
+
+fn init_aws_config(bucket: &Bucket) -> object_store::Result<object_store::aws::AmazonS3> {
     let mut opts = object_store::ClientOptions::default()
         .with_connect_timeout(std::time::Duration::from_secs(CONFIG.s3.connect_timeout))
         .with_timeout(std::time::Duration::from_secs(CONFIG.s3.request_timeout))
@@ -242,24 +411,24 @@ fn init_aws_config() -> object_store::Result<object_store::aws::AmazonS3> {
         CONFIG.s3.feature_force_hosted_style || CONFIG.s3.feature_force_path_style;
     let mut builder = object_store::aws::AmazonS3Builder::from_env()
         .with_client_options(opts)
-        .with_bucket_name(&CONFIG.s3.bucket_name)
+        .with_bucket_name(bucket.name)
         .with_virtual_hosted_style_request(force_hosted_style);
-    if !CONFIG.s3.server_url.is_empty() {
-        builder = builder.with_endpoint(&CONFIG.s3.server_url);
+    if !bucket.server_url.is_empty() {
+        builder = builder.with_endpoint(bucket.server_url);
     }
-    if !CONFIG.s3.region_name.is_empty() {
-        builder = builder.with_region(&CONFIG.s3.region_name);
+    if !bucket.region_name.is_empty() {
+        builder = builder.with_region(bucket.region_name);
     }
-    if !CONFIG.s3.access_key.is_empty() {
-        builder = builder.with_access_key_id(&CONFIG.s3.access_key);
+    if !bucket.access_key.is_empty() {
+        builder = builder.with_access_key_id(bucket.access_key);
     }
-    if !CONFIG.s3.secret_key.is_empty() {
-        builder = builder.with_secret_access_key(&CONFIG.s3.secret_key);
+    if !bucket.secret_key.is_empty() {
+        builder = builder.with_secret_access_key(bucket.secret_key);
     }
     builder.build()
 }
 
-fn init_azure_config() -> object_store::Result<object_store::azure::MicrosoftAzure> {
+fn init_azure_config(bucket: &Bucket) -> object_store::Result<object_store::azure::MicrosoftAzure> {
     let mut builder = object_store::azure::MicrosoftAzureBuilder::from_env()
         .with_client_options(
             object_store::ClientOptions::default()
@@ -267,17 +436,17 @@ fn init_azure_config() -> object_store::Result<object_store::azure::MicrosoftAzu
                 .with_timeout(std::time::Duration::from_secs(CONFIG.s3.request_timeout))
                 .with_allow_invalid_certificates(CONFIG.s3.allow_invalid_certificates),
         )
-        .with_container_name(&CONFIG.s3.bucket_name);
-    if !CONFIG.s3.access_key.is_empty() {
-        builder = builder.with_account(&CONFIG.s3.access_key);
+        .with_container_name(bucket.name);
+    if !bucket.access_key.is_empty() {
+        builder = builder.with_account(bucket.access_key);
     }
-    if !CONFIG.s3.secret_key.is_empty() {
-        builder = builder.with_access_key(&CONFIG.s3.secret_key);
+    if !bucket.secret_key.is_empty() {
+        builder = builder.with_access_key(bucket.secret_key);
     }
     builder.build()
 }
 
-fn init_gcp_config() -> object_store::Result<object_store::gcp::GoogleCloudStorage> {
+fn init_gcp_config(bucket: &Bucket) -> object_store::Result<object_store::gcp::GoogleCloudStorage> {
     let mut builder = object_store::gcp::GoogleCloudStorageBuilder::from_env()
         .with_client_options(
             object_store::ClientOptions::default()
@@ -285,42 +454,62 @@ fn init_gcp_config() -> object_store::Result<object_store::gcp::GoogleCloudStora
                 .with_timeout(std::time::Duration::from_secs(CONFIG.s3.request_timeout))
                 .with_allow_invalid_certificates(CONFIG.s3.allow_invalid_certificates),
         )
-        .with_bucket_name(&CONFIG.s3.bucket_name);
-    if !CONFIG.s3.access_key.is_empty() {
-        builder = builder.with_service_account_path(&CONFIG.s3.access_key);
+        .with_bucket_name(bucket.name);
+    if !bucket.access_key.is_empty() {
+        builder = builder.with_service_account_path(bucket.access_key);
     }
     builder.build()
 }
 
-fn init_client() -> Box<dyn object_store::ObjectStore> {
+fn init_client(bucket: &Bucket) -> Box<dyn object_store::ObjectStore> {
     if CONFIG.common.print_key_config {
         log::info!("s3 init config: {:?}", CONFIG.s3);
     }
 
-    match CONFIG.s3.provider.as_str() {
-        "aws" | "s3" => match init_aws_config() {
+    match bucket.provider {
+        "aws" | "s3" => match init_aws_config(bucket) {
             Ok(client) => Box::new(client),
             Err(e) => {
                 panic!("s3 init config error: {:?}", e);
             }
         },
-        "azure" => match init_azure_config() {
+        "azure" => match init_azure_config(bucket) {
             Ok(client) => Box::new(client),
             Err(e) => {
                 panic!("azure init config error: {:?}", e);
             }
         },
-        "gcs" | "gcp" => match init_gcp_config() {
+        "gcs" | "gcp" => match init_gcp_config(bucket) {
             Ok(client) => Box::new(client),
             Err(e) => {
                 panic!("gcp init config error: {:?}", e);
             }
         },
-        _ => match init_aws_config() {
+        _ => match init_aws_config(bucket) {
             Ok(client) => Box::new(client),
             Err(e) => {
-                panic!("{} init config error: {:?}", CONFIG.s3.provider, e);
+                panic!("{} init config error: {:?}", bucket.provider, e);
             }
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_limiter_reserve() {
+        let limiter = BandwidthLimiter::new(100);
+        let now = Instant::now();
+        // one second of burst is available
+        assert_eq!(limiter.reserve(100, now), Duration::ZERO);
+        // the next transfer has to wait for its bytes
+        assert_eq!(limiter.reserve(50, now), Duration::from_millis(500));
+        // after a second the debt is paid and half of the budget is back
+        assert_eq!(
+            limiter.reserve(50, now + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+    }
+}
@@ -90,16 +90,12 @@ impl Entry {
         })
     }
 
-    pub fn into_batch(&self, schema: Arc<Schema>) -> Result<Option<Arc<RecordBatchEntry>>> {
+    pub fn into_batch(&self, schema: Arc<Schema>) -> Result<Arc<RecordBatchEntry>> {
         let batch =
             convert_json_to_record_batch(&schema, &self.data).context(ArrowJsonEncodeSnafu)?;
 
         let arrow_size = batch.size();
-        Ok(Some(RecordBatchEntry::new(
-            batch,
-            self.data_size,
-            arrow_size,
-        )))
+        Ok(RecordBatchEntry::new(batch, self.data_size, arrow_size))
     }
 }
 
@@ -13,7 +13,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use arrow_schema::Schema;
 use config::metrics;
@@ -27,8 +33,8 @@ use crate::{
 
 pub(crate) struct MemTable {
     streams: RwMap<Arc<str>, Stream>, // key: schema name, val: stream
-    json_bytes_written: usize,
-    arrow_bytes_written: usize,
+    json_bytes_written: AtomicUsize,
+    arrow_bytes_written: AtomicUsize,
 }
 
 impl MemTable {
@@ -36,18 +42,27 @@ impl MemTable {
         metrics::INGEST_MEMTABLE_FILES.with_label_values(&[]).inc();
         Self {
             streams: RwMap::default(),
-            json_bytes_written: 0,
-            arrow_bytes_written: 0,
+            json_bytes_written: AtomicUsize::new(0),
+            arrow_bytes_written: AtomicUsize::new(0),
         }
     }
 
-    pub(crate) async fn write(&mut self, schema: Arc<Schema>, entry: Entry) -> Result<()> {
+    /// Appends an already converted arrow segment. The memtable only needs
+    /// a shared reference, writers are serialized by the wal lock.
+    pub(crate) async fn write(
+        &self,
+        schema: Arc<Schema>,
+        entry: Entry,
+        batch: Arc<RecordBatchEntry>,
+    ) -> Result<()> {
         let mut rw = self.streams.write().await;
         let partition = rw.entry(entry.stream.clone()).or_insert_with(Stream::new);
         let json_size = entry.data_size;
-        let arrow_size = partition.write(schema, entry).await?;
-        self.arrow_bytes_written += arrow_size;
-        self.json_bytes_written += json_size;
+        let arrow_size = partition.write(schema, entry, batch).await?;
+        self.arrow_bytes_written
+            .fetch_add(arrow_size, Ordering::Relaxed);
+        self.json_bytes_written
+            .fetch_add(json_size, Ordering::Relaxed);
         Ok(())
     }
 
@@ -84,6 +99,9 @@ impl MemTable {
 
     // Return the number of bytes written (json format size, arrow format size)
     pub(crate) fn size(&self) -> (usize, usize) {
-        (self.json_bytes_written, self.arrow_bytes_written)
+        (
+            self.json_bytes_written.load(Ordering::Relaxed),
+            self.arrow_bytes_written.load(Ordering::Relaxed),
+        )
     }
 }
@@ -50,26 +50,40 @@ impl Partition {
         }
     }
 
-    pub(crate) async fn write(&mut self, entry: Entry) -> Result<usize> {
+    pub(crate) async fn write(
+        &mut self,
+        entry: Entry,
+        batch: Arc<RecordBatchEntry>,
+    ) -> Result<usize> {
         let mut rw = self.files.write().await;
         let partition = rw
             .entry(entry.partition_key.clone())
             .or_insert_with(PartitionFile::new);
-        partition.write(self.schema.clone(), entry)
+        Ok(partition.write(entry, batch))
     }
 
     pub(crate) async fn read(
         &self,
         time_range: Option<(i64, i64)>,
     ) -> Result<(Arc<Schema>, Vec<Arc<RecordBatchEntry>>)> {
-        let r = self.files.read().await;
-        let mut batches = Vec::with_capacity(r.len());
-        for file in r.values() {
-            batches.extend(file.read(time_range)?);
+        let snapshots = self.snapshot().await;
+        let mut batches = Vec::with_capacity(snapshots.len());
+        for (_, data) in snapshots.iter() {
+            batches.extend(filter_by_time_range(data, time_range));
         }
         Ok((self.schema.clone(), batches))
     }
 
+    /// Takes a snapshot of the segments of every hour. The lock is only held
+    /// while cloning the handles, so readers and flushes never hold it while
+    /// they scan or encode data.
+    async fn snapshot(&self) -> Vec<(Arc<str>, Arc<Vec<Arc<RecordBatchEntry>>>)> {
+        let r = self.files.read().await;
+        r.iter()
+            .map(|(hour, file)| (hour.clone(), file.data.clone()))
+            .collect()
+    }
+
     pub(crate) async fn persist(
         &self,
         thread_id: usize,
@@ -83,14 +97,14 @@ impl Partition {
         path.push(stream_type);
         path.push(stream_name);
         path.push(thread_id.to_string());
-        let r = self.files.read().await;
-        let mut paths = Vec::with_capacity(r.len());
-        for (hour, data) in r.iter() {
-            if data.data.is_empty() {
+        let snapshots = self.snapshot().await;
+        let mut paths = Vec::with_capacity(snapshots.len());
+        for (hour, data) in snapshots.iter() {
+            if data.is_empty() {
                 continue;
             }
             let mut file_meta = FileMeta::default();
-            data.data.iter().for_each(|r| {
+            data.iter().for_each(|r| {
                 file_meta.original_size += r.data_json_size as i64;
                 file_meta.records += r.data.num_rows() as i64;
                 if file_meta.min_ts == 0 || file_meta.min_ts > r.min_ts {
@@ -104,13 +118,13 @@ impl Partition {
                 json_size: file_meta.original_size,
                 arrow_size: 0,
                 file_num: 1,
-                batch_num: data.data.len(),
+                batch_num: data.len(),
             };
             // write into parquet buf
             let mut buf_parquet = Vec::new();
             let mut writer =
                 new_parquet_writer(&mut buf_parquet, &self.schema, &[], &[], &file_meta);
-            for batch in data.data.iter() {
+            for batch in data.iter() {
                 persist_stat.arrow_size += batch.data_arrow_size;
                 writer
                     .write(&batch.data)
@@ -153,38 +167,108 @@ impl Partition {
 }
 
 struct PartitionFile {
-    data: Vec<Arc<RecordBatchEntry>>,
+    // segments are immutable, readers clone the handle of the list and
+    // writers copy it on write only when a snapshot is still alive
+    data: Arc<Vec<Arc<RecordBatchEntry>>>,
 }
 
 impl PartitionFile {
     fn new() -> Self {
-        Self { data: Vec::new() }
+        Self {
+            data: Arc::new(Vec::new()),
+        }
     }
 
-    fn write(&mut self, schema: Arc<Schema>, entry: Entry) -> Result<usize> {
-        let Some(batch) = entry.into_batch(schema)? else {
-            return Ok(0);
-        };
+    fn write(&mut self, entry: Entry, batch: Arc<RecordBatchEntry>) -> usize {
         let arrow_size = batch.data_arrow_size;
-        self.data.push(batch);
+        Arc::make_mut(&mut self.data).push(batch);
         metrics::INGEST_MEMTABLE_ARROW_BYTES
             .with_label_values(&[])
             .add(arrow_size as i64);
         metrics::INGEST_MEMTABLE_BYTES
             .with_label_values(&[])
             .add(entry.data_size as i64);
-        Ok(arrow_size)
+        arrow_size
+    }
+}
+
+fn filter_by_time_range(
+    data: &[Arc<RecordBatchEntry>],
+    time_range: Option<(i64, i64)>,
+) -> Vec<Arc<RecordBatchEntry>> {
+    match time_range {
+        None | Some((0, 0)) => data.to_vec(),
+        Some((min_ts, max_ts)) => data
+            .iter()
+            .filter(|r| r.min_ts <= max_ts && r.max_ts >= min_ts)
+            .cloned()
+            .collect(),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{array::Int64Array, record_batch::RecordBatch};
+    use arrow_schema::{DataType, Field};
 
-    fn read(&self, time_range: Option<(i64, i64)>) -> Result<Vec<Arc<RecordBatchEntry>>> {
-        match time_range {
-            None | Some((0, 0)) => Ok(self.data.clone()),
-            Some((min_ts, max_ts)) => Ok(self
-                .data
-                .iter()
-                .filter(|r| r.min_ts <= max_ts && r.max_ts >= min_ts)
-                .cloned()
-                .collect()),
+    use super::*;
+
+    fn schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![Field::new(
+            &CONFIG.common.column_timestamp,
+            DataType::Int64,
+            false,
+        )]))
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            stream: "default".into(),
+            schema_key: "key".into(),
+            partition_key: "2024/01/01/00".into(),
+            data: vec![],
+            data_size: 10,
         }
     }
+
+    fn batch(ts: i64) -> Arc<RecordBatchEntry> {
+        let data =
+            RecordBatch::try_new(schema(), vec![Arc::new(Int64Array::from(vec![ts]))]).unwrap();
+        RecordBatchEntry::new(data, 10, 10)
+    }
+
+    #[test]
+    fn test_write_after_snapshot() {
+        let mut file = PartitionFile::new();
+        file.write(entry(), batch(1));
+        let snapshot = file.data.clone();
+        // the writer copies the list, the snapshot keeps the one it took
+        file.write(entry(), batch(2));
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].min_ts, 1);
+        assert_eq!(file.data.len(), 2);
+        assert!(!Arc::ptr_eq(&snapshot, &file.data));
+
+        // without a snapshot alive the list is written in place
+        drop(snapshot);
+        let data = Arc::as_ptr(&file.data);
+        file.write(entry(), batch(3));
+        assert_eq!(Arc::as_ptr(&file.data), data);
+        assert_eq!(file.data.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_read_snapshot_isolation() {
+        let mut partition = Partition::new(schema());
+        partition.write(entry(), batch(1)).await.unwrap();
+        let snapshots = partition.snapshot().await;
+        partition.write(entry(), batch(2)).await.unwrap();
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].1.len(), 1);
+        let (_, batches) = partition.read(None).await.unwrap();
+        assert_eq!(batches.len(), 2);
+        let (_, batches) = partition.read(Some((2, 2))).await.unwrap();
+        assert_eq!(batches.len(), 1);
+    }
 }
@@ -36,14 +36,19 @@ impl Stream {
         }
     }
 
-    pub(crate) async fn write(&mut self, schema: Arc<Schema>, entry: Entry) -> Result<usize> {
+    pub(crate) async fn write(
+        &mut self,
+        schema: Arc<Schema>,
+        entry: Entry,
+        batch: Arc<RecordBatchEntry>,
+    ) -> Result<usize> {
         let mut arrow_size = 0;
         let mut rw = self.partitions.write().await;
         let partition = rw.entry(entry.schema_key.clone()).or_insert_with(|| {
             arrow_size += schema.size();
             Partition::new(schema)
         });
-        arrow_size += partition.write(entry).await?;
+        arrow_size += partition.write(entry, batch).await?;
         Ok(arrow_size)
     }
 
@@ -161,10 +161,13 @@ impl Writer {
             return Ok(());
         }
         let entry_bytes = entry.into_bytes()?;
+        // convert into an arrow segment before taking any lock, the memtable
+        // only appends the immutable segment so readers are barely blocked
+        let batch = entry.into_batch(schema.clone())?;
         let mut wal = self.wal.lock().await;
-        let mut mem = self.memtable.write().await;
+        let mem_size = self.memtable.read().await.size();
         if self.check_wal_threshold(wal.size(), entry_bytes.len())
-            || self.check_mem_threshold(mem.size(), entry.data_size)
+            || self.check_mem_threshold(mem_size, entry.data_size)
         {
             // sync wal before rotation
             wal.sync().context(WalSnafu)?;
@@ -192,7 +195,7 @@ impl Writer {
 
             // rotation memtable
             let new_mem = MemTable::new();
-            let old_mem = std::mem::replace(&mut *mem, new_mem);
+            let old_mem = std::mem::replace(&mut *self.memtable.write().await, new_mem);
             // update created_at
             self.created_at
                 .store(Utc::now().timestamp_micros(), Ordering::Release);
@@ -214,8 +217,10 @@ impl Writer {
         // write into wal
         wal.write(&entry_bytes, false).context(WalSnafu)?;
 
-        // write into memtable
-        mem.write(schema, entry).await?;
+        // write into memtable, the wal lock keeps writers serialized so a
+        // shared reference is enough and readers are not blocked
+        let mem = self.memtable.read().await;
+        mem.write(schema, entry, batch).await?;
         Ok(())
     }
 
@@ -0,0 +1,32 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::CONFIG;
+use tokio::time;
+
+use crate::service::cdc;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !CONFIG.common.cdc_enabled {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(CONFIG.common.cdc_flush_interval));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        cdc::flush().await;
+    }
+}
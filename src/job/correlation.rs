@@ -0,0 +1,51 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{cluster, CONFIG};
+use tokio::time;
+
+use crate::service::correlation;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+    tokio::task::spawn(async move { run_forward().await });
+    if CONFIG.limit.correlation_clean_interval == 0 {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.correlation_clean_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        let left = correlation::clean_expired();
+        log::debug!("[CORRELATION] {left} sequences in progress");
+    }
+}
+
+/// Forwards the events of the sequences owned by other ingesters.
+async fn run_forward() {
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.correlation_forward_interval.max(1),
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        correlation::forward().await;
+    }
+}
@@ -0,0 +1,38 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{cluster, CONFIG};
+use tokio::time;
+
+use crate::service::enrichment_table::source;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if CONFIG.limit.enrichment_source_check_interval == 0
+        || !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE)
+    {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.enrichment_source_check_interval,
+    ));
+    loop {
+        interval.tick().await;
+        match source::refresh_due().await {
+            Ok(refreshed) => log::debug!("[ENRICHMENT] {refreshed} sources refreshed"),
+            Err(e) => log::error!("[ENRICHMENT] refresh sources error: {e}"),
+        }
+    }
+}
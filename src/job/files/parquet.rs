@@ -478,6 +478,7 @@ async fn merge_files(
     let bloom_filter_fields =
         stream::get_stream_setting_bloom_filter_fields(latest_schema).unwrap();
     let full_text_search_fields = stream::get_stream_setting_fts_fields(latest_schema).unwrap();
+    let parquet_options = stream::get_stream_setting_parquet_options(latest_schema);
     let mut buf = Vec::new();
     let mut fts_buf = Vec::new();
     let start = std::time::Instant::now();
@@ -489,8 +490,10 @@ async fn merge_files(
         Arc::new(file_schema.unwrap()),
         &bloom_filter_fields,
         &full_text_search_fields,
+        &parquet_options,
         new_file_size,
         &mut fts_buf,
+        None,
     )
     .await
     {
@@ -51,35 +51,29 @@ async fn run_download_files() {
     let city_fname = format!("{}{}", &CONFIG.common.mmdb_data_dir, MMDB_CITY_FILE_NAME);
     let asn_fname = format!("{}{}", &CONFIG.common.mmdb_data_dir, MMDB_ASN_FILE_NAME);
 
-    let download_city_files =
-        is_digest_different(&city_fname, &CONFIG.common.mmdb_geolite_citydb_sha256_url)
-            .await
-            .unwrap_or_else(|e| {
-                log::error!("Error checking digest difference: {e}");
-                false
-            });
-
-    let download_asn_files =
-        is_digest_different(&asn_fname, &CONFIG.common.mmdb_geolite_asndb_sha256_url)
-            .await
-            .unwrap_or_else(|e| {
-                log::error!("Error checking digest difference: {e}");
-                false
-            });
-
-    if download_city_files {
-        match download_file(&client, &CONFIG.common.mmdb_geolite_citydb_url, &city_fname).await {
-            Ok(()) => {}
-            Err(e) => log::error!("failed to download the files {}", e),
-        }
-    }
-
-    if download_asn_files {
-        match download_file(&client, &CONFIG.common.mmdb_geolite_asndb_url, &asn_fname).await {
-            Ok(()) => {}
-            Err(e) => log::error!("failed to download the files {}", e),
-        }
-    }
+    let download_city_files = refresh_file(
+        &client,
+        &CONFIG.common.mmdb_geolite_citydb_url,
+        &CONFIG.common.mmdb_geolite_citydb_sha256_url,
+        &city_fname,
+    )
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("failed to refresh {city_fname}: {e}");
+        false
+    });
+
+    let download_asn_files = refresh_file(
+        &client,
+        &CONFIG.common.mmdb_geolite_asndb_url,
+        &CONFIG.common.mmdb_geolite_asndb_sha256_url,
+        &asn_fname,
+    )
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("failed to refresh {asn_fname}: {e}");
+        false
+    });
 
     let client = Lazy::get(&CLIENT_INITIALIZED);
 
@@ -102,6 +96,52 @@ async fn run_download_files() {
     Lazy::force(&CLIENT_INITIALIZED);
 }
 
+/// Downloads the file when the remote checksum differs from the local one.
+/// The download only replaces the file once its checksum is verified, the
+/// rename swaps it atomically so readers never see a partial file. Returns
+/// whether the file was replaced.
+async fn refresh_file(
+    client: &Client,
+    url: &str,
+    sha256_url: &str,
+    path: &str,
+) -> Result<bool, anyhow::Error> {
+    let remote_sha = remote_digest(client, sha256_url).await?;
+    let local_sha = try_digest(Path::new(path)).unwrap_or_default();
+    if remote_sha.eq_ignore_ascii_case(local_sha.trim()) {
+        return Ok(false);
+    }
+
+    let tmp_path = format!("{path}.download");
+    download_file(client, url, &tmp_path)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let downloaded_sha = try_digest(Path::new(&tmp_path))?;
+    if !remote_sha.eq_ignore_ascii_case(&downloaded_sha) {
+        _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(anyhow::anyhow!(
+            "checksum mismatch, expected {remote_sha} got {downloaded_sha}"
+        ));
+    }
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(true)
+}
+
+/// The checksum published for the file, which may be followed by its name.
+async fn remote_digest(client: &Client, sha256_url: &str) -> Result<String, anyhow::Error> {
+    let content = client
+        .get(sha256_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    match content.split_whitespace().next() {
+        Some(sha) => Ok(sha.to_string()),
+        None => Err(anyhow::anyhow!("empty checksum at {sha256_url}")),
+    }
+}
+
 /// Update the global maxdb client object
 pub async fn update_global_maxmind_client(fname: &str) {
     match MaxmindClient::new_with_path(fname) {
@@ -125,22 +165,13 @@ pub async fn update_global_maxmind_client(fname: &str) {
     }
 }
 
-pub async fn is_digest_different(
-    local_file_path: &str,
-    remote_sha256sum_path: &str,
-) -> Result<bool, anyhow::Error> {
-    let response = reqwest::get(remote_sha256sum_path).await?;
-    let remote_file_sha = response.text().await?;
-    let local_file_sha = try_digest(Path::new(local_file_path)).unwrap_or_default();
-    Ok(remote_file_sha.trim() != local_file_sha.trim())
-}
-
 pub async fn download_file(client: &Client, url: &str, path: &str) -> Result<(), String> {
     // Reqwest setup
     let res = client
         .get(url)
         .send()
         .await
+        .and_then(|res| res.error_for_status())
         .or(Err(format!("Failed to GET from '{}'", &url)))?;
     let total_size = res
         .content_length()
@@ -161,6 +192,9 @@ pub async fn download_file(client: &Client, url: &str, path: &str) -> Result<(),
         let new = min(downloaded + (chunk.len() as u64), total_size);
         downloaded = new;
     }
+    file.flush()
+        .await
+        .or(Err("Error while writing to file".to_string()))?;
 
     Ok(())
 }
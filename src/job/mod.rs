@@ -28,15 +28,29 @@ use crate::{
 };
 
 mod alert_manager;
+mod cdc;
 mod compact;
+mod correlation;
+mod enrichment_sources;
 pub(crate) mod file_list;
 pub(crate) mod files;
+mod kafka;
 mod metrics;
 mod mmdb_downloader;
+mod monitors;
+mod netflow_server;
 mod prom;
+mod reprocess;
+mod sample_data;
+mod search_jobs;
+mod short_urls;
+mod snmp_trap_server;
 mod stats;
+mod stream_owners;
+mod stream_quota;
 pub(crate) mod syslog_server;
 mod telemetry;
+mod threat_intel;
 
 pub async fn init() -> Result<(), anyhow::Error> {
     let email_regex = Regex::new(
@@ -108,12 +122,22 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { db::schema::watch().await });
     tokio::task::spawn(async move { db::functions::watch().await });
     tokio::task::spawn(async move { db::compact::retention::watch().await });
+    tokio::task::spawn(async move { db::compact::tombstones::watch().await });
+    tokio::task::spawn(async move { db::compact::legal_holds::watch().await });
+    tokio::task::spawn(async move { db::compact::rename::watch().await });
     tokio::task::spawn(async move { db::metrics::watch_prom_cluster_leader().await });
     tokio::task::spawn(async move { db::alerts::templates::watch().await });
     tokio::task::spawn(async move { db::alerts::destinations::watch().await });
+    tokio::task::spawn(async move { db::webhooks::watch().await });
+    tokio::task::spawn(async move { db::query_cache::watch().await });
+    tokio::task::spawn(async move { db::correlation::watch().await });
+    tokio::task::spawn(async move { db::threat_intel::watch().await });
+    tokio::task::spawn(async move { db::enrichment_table::watch_sources().await });
     tokio::task::spawn(async move { db::alerts::watch().await });
     tokio::task::spawn(async move { db::dashboards::reports::watch().await });
     tokio::task::spawn(async move { db::organization::watch().await });
+    tokio::task::spawn(async move { db::organization::watch_features().await });
+    tokio::task::spawn(async move { db::monitors::watch().await });
     #[cfg(feature = "enterprise")]
     tokio::task::spawn(async move { db::ofga::watch().await });
 
@@ -127,6 +151,15 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::compact::retention::cache()
         .await
         .expect("compact delete cache failed");
+    db::compact::tombstones::cache()
+        .await
+        .expect("compact tombstones cache failed");
+    db::compact::legal_holds::cache()
+        .await
+        .expect("compact legal holds cache failed");
+    db::compact::rename::cache()
+        .await
+        .expect("compact rename cache failed");
     db::metrics::cache_prom_cluster_leader()
         .await
         .expect("prom cluster leader cache failed");
@@ -138,7 +171,15 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::alerts::destinations::cache()
         .await
         .expect("alerts destinations cache failed");
+    db::webhooks::cache().await.expect("webhooks cache failed");
+    db::correlation::cache()
+        .await
+        .expect("correlation rules cache failed");
+    db::threat_intel::cache()
+        .await
+        .expect("threat intel lists cache failed");
     db::alerts::cache().await.expect("alerts cache failed");
+    db::monitors::cache().await.expect("monitors cache failed");
     db::dashboards::reports::cache()
         .await
         .expect("reports cache failed");
@@ -146,6 +187,12 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::syslog::cache_syslog_settings()
         .await
         .expect("syslog settings cache failed");
+    db::snmp::cache()
+        .await
+        .expect("snmp trap routes cache failed");
+    db::snmp::cache_mibs()
+        .await
+        .expect("snmp mibs cache failed");
 
     // cache file list
     if !CONFIG.common.meta_store_external {
@@ -196,6 +243,20 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { metrics::run().await });
     tokio::task::spawn(async move { prom::run().await });
     tokio::task::spawn(async move { alert_manager::run().await });
+    tokio::task::spawn(async move { netflow_server::run().await });
+    tokio::task::spawn(async move { snmp_trap_server::run().await });
+    tokio::task::spawn(async move { monitors::run().await });
+    tokio::task::spawn(async move { sample_data::run().await });
+    tokio::task::spawn(async move { stream_owners::run().await });
+    tokio::task::spawn(async move { stream_quota::run().await });
+    tokio::task::spawn(async move { cdc::run().await });
+    tokio::task::spawn(async move { correlation::run().await });
+    tokio::task::spawn(async move { threat_intel::run().await });
+    tokio::task::spawn(async move { enrichment_sources::run().await });
+    tokio::task::spawn(async move { kafka::run().await });
+    tokio::task::spawn(async move { reprocess::run().await });
+    tokio::task::spawn(async move { search_jobs::run().await });
+    tokio::task::spawn(async move { short_urls::run().await });
 
     #[cfg(feature = "enterprise")]
     o2_enterprise::enterprise::openfga::authorizer::authz::init_open_fga().await;
@@ -213,6 +274,10 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { db::syslog::watch().await });
     tokio::task::spawn(async move { db::syslog::watch_syslog_settings().await });
 
+    // SNMP trap routes and MIBs
+    tokio::task::spawn(async move { db::snmp::watch().await });
+    tokio::task::spawn(async move { db::snmp::watch_mibs().await });
+
     let start_syslog = *SYSLOG_ENABLED.read();
     if start_syslog {
         syslog_server::run(start_syslog, true)
@@ -0,0 +1,62 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Utc;
+use config::{cluster, CONFIG};
+use hashbrown::HashMap;
+use tokio::time;
+
+use crate::{common::infra::config::MONITORS, service::monitors};
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !CONFIG.monitors.enabled || !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+
+    log::info!("Starting monitors for region [{}]", CONFIG.monitors.region);
+    // key -> last run timestamp in seconds
+    let mut last_runs: HashMap<String, i64> = HashMap::new();
+    let mut interval = time::interval(time::Duration::from_secs(1));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        let now = Utc::now().timestamp();
+        last_runs.retain(|key, _| MONITORS.contains_key(key));
+        for item in MONITORS.iter() {
+            let monitor = item.value();
+            if !monitor.enabled || !monitor.runs_in(&CONFIG.monitors.region) {
+                continue;
+            }
+            let last_run = last_runs.entry(item.key().to_string()).or_default();
+            if now - *last_run < monitor.interval {
+                continue;
+            }
+            *last_run = now;
+
+            let org_id = item.key().split('/').next().unwrap_or_default().to_string();
+            let monitor = monitor.clone();
+            tokio::task::spawn(async move {
+                if let Err(e) = monitors::run_check(&org_id, &monitor).await {
+                    log::error!(
+                        "[MONITOR] failed to record check {}/{}: {}",
+                        org_id,
+                        monitor.name,
+                        e
+                    );
+                }
+            });
+        }
+    }
+}
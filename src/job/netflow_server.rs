@@ -0,0 +1,42 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use config::{cluster, CONFIG};
+use tokio::{net::UdpSocket, time};
+
+use crate::{handler::tcp_udp::netflow_server, service::logs::netflow};
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !CONFIG.netflow.enabled || !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+
+    let addr: SocketAddr = format!("0.0.0.0:{}", CONFIG.netflow.port).parse()?;
+    log::info!("Starting NetFlow UDP server at {addr}");
+    let socket = UdpSocket::bind(addr).await?;
+    tokio::task::spawn(async move { netflow_server(socket).await });
+
+    // exporters resend templates periodically, drop the ones that went away
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.netflow.template_ttl.max(60) as u64,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        netflow::clean_templates();
+    }
+}
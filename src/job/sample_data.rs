@@ -0,0 +1,60 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{cluster, CONFIG};
+use tokio::time;
+
+use crate::service::sample_data;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !CONFIG.common.sample_data_enabled || !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+
+    let org_id = &CONFIG.common.sample_data_org;
+    log::info!("[SAMPLE_DATA] generating sample data for org {org_id}");
+    let mut assets_created = false;
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.common.sample_data_interval,
+    ));
+    loop {
+        interval.tick().await;
+        match sample_data::claim_generator().await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                log::error!("[SAMPLE_DATA] claim generator error: {e}");
+                continue;
+            }
+        }
+        if let Err(e) = sample_data::generate(
+            org_id,
+            CONFIG.common.sample_data_records,
+            CONFIG.common.sample_data_interval,
+        )
+        .await
+        {
+            log::error!("[SAMPLE_DATA] generate error: {e}");
+            continue;
+        }
+        // the streams exist after the first batch, so the alert can be saved
+        if !assets_created {
+            match sample_data::create_assets(org_id).await {
+                Ok(()) => assets_created = true,
+                Err(e) => log::error!("[SAMPLE_DATA] create assets error: {e}"),
+            }
+        }
+    }
+}
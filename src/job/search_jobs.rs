@@ -0,0 +1,38 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{cluster, CONFIG};
+use tokio::time;
+
+use crate::service::search::jobs;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if CONFIG.limit.search_job_check_interval == 0
+        || !cluster::is_querier(&cluster::LOCAL_NODE_ROLE)
+    {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.search_job_check_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if let Err(e) = jobs::run_pending().await {
+            log::error!("[SEARCH JOB] run pending jobs error: {}", e);
+        }
+    }
+}
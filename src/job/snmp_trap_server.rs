@@ -0,0 +1,33 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use config::{cluster, CONFIG};
+use tokio::net::UdpSocket;
+
+use crate::handler::tcp_udp::snmp_trap_server;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !CONFIG.snmp.trap_enabled || !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+
+    let addr: SocketAddr = format!("0.0.0.0:{}", CONFIG.snmp.trap_port).parse()?;
+    log::info!("Starting SNMP trap UDP server at {addr}");
+    let socket = UdpSocket::bind(addr).await?;
+    tokio::task::spawn(async move { snmp_trap_server(socket).await });
+    Ok(())
+}
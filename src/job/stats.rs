@@ -19,15 +19,45 @@ use config::{
 };
 use tokio::time;
 
-use crate::service::{compact::stats::update_stats_from_file_list, db, usage};
+use crate::service::{
+    compact::{
+        self,
+        stats::{
+            rebuild_stream_stats, snapshot_stream_stats, stats_job_owned_by_other_node,
+            update_stats_from_file_list,
+        },
+    },
+    db, usage,
+};
 
 pub async fn run() -> Result<(), anyhow::Error> {
     // tokio::task::spawn(async move { usage_report_stats().await });
     tokio::task::spawn(async move { file_list_update_stats().await });
     tokio::task::spawn(async move { cache_stream_stats().await });
+    tokio::task::spawn(async move { stream_stats_history().await });
+    tokio::task::spawn(async move { stream_stats_rebuild().await });
+    tokio::task::spawn(async move { sync_query_counts().await });
     Ok(())
 }
 
+// save the query counts of this node, the compactors use them for priority
+async fn sync_query_counts() -> Result<(), anyhow::Error> {
+    if !is_querier(&super::cluster::LOCAL_NODE_ROLE) {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.calculate_stats_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if let Err(e) = compact::priority::sync_query_counts().await {
+            log::error!("[STATS] run sync query counts error: {}", e);
+        }
+    }
+}
+
 async fn _usage_report_stats() -> Result<(), anyhow::Error> {
     if !is_compactor(&super::cluster::LOCAL_NODE_ROLE) || !CONFIG.common.usage_enabled {
         return Ok(());
@@ -97,3 +127,58 @@ async fn cache_stream_stats() -> Result<(), anyhow::Error> {
         }
     }
 }
+
+// keep a history of stream_stats for capacity planning
+async fn stream_stats_history() -> Result<(), anyhow::Error> {
+    if CONFIG.limit.stats_history_interval == 0 {
+        return Ok(());
+    }
+    if (CONFIG.common.meta_store_external || !is_querier(&super::cluster::LOCAL_NODE_ROLE))
+        && !is_compactor(&super::cluster::LOCAL_NODE_ROLE)
+    {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.stats_history_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if let Err(e) = snapshot_stream_stats().await {
+            log::error!("[STATS] run stream stats history error: {}", e);
+        } else {
+            log::debug!("[STATS] run stream stats history success");
+        }
+    }
+}
+
+// reconcile stream_stats with the file_list to fix the drift
+async fn stream_stats_rebuild() -> Result<(), anyhow::Error> {
+    if CONFIG.limit.stats_rebuild_interval == 0 {
+        return Ok(());
+    }
+    if (CONFIG.common.meta_store_external || !is_querier(&super::cluster::LOCAL_NODE_ROLE))
+        && !is_compactor(&super::cluster::LOCAL_NODE_ROLE)
+    {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.stats_rebuild_interval,
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if stats_job_owned_by_other_node().await {
+            continue;
+        }
+        match rebuild_stream_stats(None).await {
+            Err(e) => log::error!("[STATS] run stream stats rebuild error: {}", e),
+            Ok(drifts) => log::info!(
+                "[STATS] run stream stats rebuild success, {} streams drifted",
+                drifts.len()
+            ),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{cluster, CONFIG};
+use tokio::time;
+
+use crate::service::stream;
+
+pub async fn run() -> Result<(), anyhow::Error> {
+    if CONFIG.limit.stream_owner_check_interval == 0
+        || !cluster::is_querier(&cluster::LOCAL_NODE_ROLE)
+    {
+        return Ok(());
+    }
+
+    let mut interval = time::interval(time::Duration::from_secs(
+        CONFIG.limit.stream_owner_check_interval,
+    ));
+    // the first tick completes immediately, the streams are checked at startup
+    loop {
+        interval.tick().await;
+        let stale = stream::check_stale_owners().await;
+        for key in stale.iter() {
+            log::warn!("[STREAM] owner of stream {key} no longer exists");
+        }
+        if !stale.is_empty() {
+            log::info!(
+                "[STREAM] {} streams have an owner that no longer exists",
+                stale.len()
+            );
+        }
+    }
+}
@@ -14,8 +14,10 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-    io::Write,
+    fs::File,
+    io::{BufReader, Write},
     net::{SocketAddr, TcpStream},
+    sync::Arc,
 };
 
 use config::CONFIG;
@@ -24,10 +26,11 @@ use tokio::{
     net::{TcpListener, UdpSocket},
     sync::{broadcast, RwLock},
 };
+use tokio_rustls::{rustls, TlsAcceptor};
 
 use crate::{
     common::infra::config::SYSLOG_ENABLED,
-    handler::tcp_udp::{tcp_server, udp_server, STOP_SRV},
+    handler::tcp_udp::{tcp_server, tls_server, udp_server, STOP_SRV},
     service::db::syslog::toggle_syslog_setting,
 };
 
@@ -52,6 +55,15 @@ pub async fn run(start_srv: bool, is_init: bool) -> Result<(), anyhow::Error> {
         tokio::task::spawn(async move {
             _ = udp_server(udp_socket).await;
         });
+        if !CONFIG.tcp.tls_cert_path.is_empty() && !CONFIG.tcp.tls_key_path.is_empty() {
+            let tls_addr: SocketAddr = format!("{bind_addr}:{}", CONFIG.tcp.tls_port).parse()?;
+            let acceptor = tls_acceptor()?;
+            let tls_listener: TcpListener = TcpListener::bind(tls_addr).await?;
+            log::info!("Starting syslog TLS server");
+            tokio::task::spawn(async move {
+                _ = tls_server(tls_listener, acceptor).await;
+            });
+        }
         toggle_syslog_setting(start_srv).await.unwrap();
     } else if server_running && !start_srv {
         // stop running server
@@ -71,6 +83,18 @@ pub async fn run(start_srv: bool, is_init: bool) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+fn tls_acceptor() -> Result<TlsAcceptor, anyhow::Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(&CONFIG.tcp.tls_cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key =
+        rustls_pemfile::private_key(&mut BufReader::new(File::open(&CONFIG.tcp.tls_key_path)?))?
+            .ok_or_else(|| anyhow::anyhow!("no private key in {}", CONFIG.tcp.tls_key_path))?;
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::run;
@@ -43,6 +43,7 @@ use openobserve::{
         grpc::{
             auth::check_auth,
             request::{
+                correlation::CorrelationServerImpl,
                 event::Eventer,
                 file_list::Filelister,
                 logs::LogsServer,
@@ -54,7 +55,7 @@ use openobserve::{
         http::router::*,
     },
     job, router,
-    service::{db, metadata, search::SEARCH_SERVER, usage},
+    service::{cdc, db, metadata, search::SEARCH_SERVER, usage},
 };
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
@@ -65,8 +66,9 @@ use opentelemetry_proto::tonic::collector::{
 };
 use opentelemetry_sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource};
 use proto::cluster_rpc::{
-    event_server::EventServer, filelist_server::FilelistServer, metrics_server::MetricsServer,
-    search_server::SearchServer, usage_server::UsageServer,
+    correlation_server::CorrelationServer, event_server::EventServer,
+    filelist_server::FilelistServer, metrics_server::MetricsServer, search_server::SearchServer,
+    usage_server::UsageServer,
 };
 #[cfg(feature = "profiling")]
 use pyroscope::PyroscopeAgent;
@@ -248,6 +250,8 @@ async fn main() -> Result<(), anyhow::Error> {
 
     // flush useage report
     usage::flush().await;
+    // flush metadata changes
+    cdc::flush().await;
 
     // leave the cluster
     _ = cluster::leave().await;
@@ -317,6 +321,11 @@ fn init_common_grpc_server(
     let logs_svc = LogsServiceServer::new(LogsServer)
         .send_compressed(CompressionEncoding::Gzip)
         .accept_compressed(CompressionEncoding::Gzip);
+    let correlation_svc = CorrelationServer::new(CorrelationServerImpl)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024);
     let tracer = TraceServer::default();
     let trace_svc = TraceServiceServer::new(tracer)
         .send_compressed(CompressionEncoding::Gzip)
@@ -334,6 +343,7 @@ fn init_common_grpc_server(
             .add_service(trace_svc)
             .add_service(usage_svc)
             .add_service(logs_svc)
+            .add_service(correlation_svc)
             .serve_with_shutdown(gaddr, async {
                 shutdown_rx.await.ok();
                 log::info!("gRPC server starts shutting down");
@@ -50,6 +50,7 @@ fn main() -> Result<()> {
         .compile(
             &[
                 "proto/cluster/common.proto",
+                "proto/cluster/correlation.proto",
                 "proto/cluster/event.proto",
                 "proto/cluster/filelist.proto",
                 "proto/cluster/metrics.proto",
@@ -112,5 +113,7 @@ fn main() -> Result<()> {
         &["proto"],
     )?;
 
+    prost_build::Config::new().compile_protos(&["proto/loki/push.proto"], &["proto"])?;
+
     Ok(())
 }
@@ -21,6 +21,10 @@ pub mod prometheus_rpc {
     include!(concat!(env!("OUT_DIR"), "/prometheus.rs"));
 }
 
+pub mod loki_rpc {
+    include!(concat!(env!("OUT_DIR"), "/logproto.rs"));
+}
+
 impl From<Vec<serde_json::Value>> for cluster_rpc::UsageData {
     fn from(usages: Vec<serde_json::Value>) -> Self {
         Self {
@@ -27,7 +27,7 @@ use cron::Schedule;
 
 use crate::{
     common::meta::{alerts::AlertFrequencyType, dashboards::reports::ReportFrequencyType},
-    service::{db, usage::publish_triggers_usage},
+    service::{continuous_queries, db, quality_monitors, slo, usage::publish_triggers_usage},
 };
 
 pub async fn run() -> Result<(), anyhow::Error> {
@@ -56,6 +56,13 @@ pub async fn handle_triggers(trigger: db::scheduler::Trigger) -> Result<(), anyh
     match trigger.module {
         db::scheduler::TriggerModule::Report => handle_report_triggers(trigger).await,
         db::scheduler::TriggerModule::Alert => handle_alert_triggers(trigger).await,
+        db::scheduler::TriggerModule::QualityMonitor => {
+            handle_quality_monitor_triggers(trigger).await
+        }
+        db::scheduler::TriggerModule::ContinuousQuery => {
+            handle_continuous_query_triggers(trigger).await
+        }
+        db::scheduler::TriggerModule::Slo => handle_slo_triggers(trigger).await,
     }
 }
 
@@ -171,6 +178,7 @@ async fn handle_alert_triggers(trigger: db::scheduler::Trigger) -> Result<(), an
             }
         }
     } else {
+        super::throttle::resolved(&alert).await;
         db::scheduler::update_trigger(new_trigger).await?;
         trigger_data_stream.status = TriggerDataStatus::ConditionNotSatisfied;
     }
@@ -303,3 +311,187 @@ async fn handle_report_triggers(trigger: db::scheduler::Trigger) -> Result<(), a
 
     Ok(())
 }
+
+async fn handle_quality_monitor_triggers(
+    trigger: db::scheduler::Trigger,
+) -> Result<(), anyhow::Error> {
+    let columns = trigger.module_key.split('/').collect::<Vec<&str>>();
+    assert_eq!(columns.len(), 3);
+    let org_id = &trigger.org;
+    let stream_type: StreamType = columns[0].into();
+    let stream_name = columns[1];
+    let monitor_name = columns[2];
+
+    let mut monitor =
+        db::quality_monitors::get(org_id, stream_type, stream_name, monitor_name).await?;
+    let mut new_trigger = db::scheduler::Trigger {
+        next_run_at: Utc::now().timestamp_micros(),
+        is_realtime: false,
+        is_silenced: false,
+        status: db::scheduler::TriggerStatus::Waiting,
+        retries: 0,
+        ..trigger.clone()
+    };
+    new_trigger.next_run_at += Duration::try_seconds(monitor.interval)
+        .unwrap()
+        .num_microseconds()
+        .unwrap();
+    if !monitor.enabled {
+        db::scheduler::update_trigger(new_trigger).await?;
+        return Ok(());
+    }
+
+    let mut trigger_data_stream = TriggerData {
+        org: trigger.org.clone(),
+        module: TriggerDataType::QualityMonitor,
+        key: trigger.module_key.clone(),
+        next_run_at: new_trigger.next_run_at,
+        is_realtime: trigger.is_realtime,
+        is_silenced: trigger.is_silenced,
+        status: TriggerDataStatus::Completed,
+        start_time: trigger.start_time,
+        end_time: trigger.end_time,
+        retries: trigger.retries,
+        error: None,
+    };
+
+    match quality_monitors::run(org_id, &mut monitor).await {
+        Ok(_) => {
+            db::scheduler::update_trigger(new_trigger).await?;
+        }
+        Err(e) => {
+            db::scheduler::update_status(
+                &new_trigger.org,
+                new_trigger.module,
+                &new_trigger.module_key,
+                db::scheduler::TriggerStatus::Waiting,
+                trigger.retries + 1,
+            )
+            .await?;
+            trigger_data_stream.status = TriggerDataStatus::Failed;
+            trigger_data_stream.error = Some(format!("error running quality monitor: {e}"));
+        }
+    }
+    trigger_data_stream.end_time = Utc::now().timestamp_micros();
+    publish_triggers_usage(trigger_data_stream).await;
+
+    Ok(())
+}
+
+async fn handle_continuous_query_triggers(
+    trigger: db::scheduler::Trigger,
+) -> Result<(), anyhow::Error> {
+    let org_id = &trigger.org;
+    let mut query = db::continuous_queries::get(org_id, &trigger.module_key).await?;
+    let mut new_trigger = db::scheduler::Trigger {
+        next_run_at: Utc::now().timestamp_micros(),
+        is_realtime: false,
+        is_silenced: false,
+        status: db::scheduler::TriggerStatus::Waiting,
+        retries: 0,
+        ..trigger.clone()
+    };
+    if !query.enabled {
+        new_trigger.next_run_at += Duration::try_seconds(query.interval)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+        db::scheduler::update_trigger(new_trigger).await?;
+        return Ok(());
+    }
+
+    let mut trigger_data_stream = TriggerData {
+        org: trigger.org.clone(),
+        module: TriggerDataType::ContinuousQuery,
+        key: trigger.module_key.clone(),
+        next_run_at: new_trigger.next_run_at,
+        is_realtime: trigger.is_realtime,
+        is_silenced: trigger.is_silenced,
+        status: TriggerDataStatus::Completed,
+        start_time: trigger.start_time,
+        end_time: trigger.end_time,
+        retries: trigger.retries,
+        error: None,
+    };
+
+    match continuous_queries::run(org_id, &mut query).await {
+        Ok(next_run_at) => {
+            new_trigger.next_run_at = next_run_at;
+            trigger_data_stream.next_run_at = next_run_at;
+            db::scheduler::update_trigger(new_trigger).await?;
+        }
+        Err(e) => {
+            db::scheduler::update_status(
+                &new_trigger.org,
+                new_trigger.module,
+                &new_trigger.module_key,
+                db::scheduler::TriggerStatus::Waiting,
+                trigger.retries + 1,
+            )
+            .await?;
+            trigger_data_stream.status = TriggerDataStatus::Failed;
+            trigger_data_stream.error = Some(format!("error running continuous query: {e}"));
+        }
+    }
+    trigger_data_stream.end_time = Utc::now().timestamp_micros();
+    publish_triggers_usage(trigger_data_stream).await;
+
+    Ok(())
+}
+
+async fn handle_slo_triggers(trigger: db::scheduler::Trigger) -> Result<(), anyhow::Error> {
+    let org_id = &trigger.org;
+    let mut slo = db::slo::get(org_id, &trigger.module_key).await?;
+    let mut new_trigger = db::scheduler::Trigger {
+        next_run_at: Utc::now().timestamp_micros(),
+        is_realtime: false,
+        is_silenced: false,
+        status: db::scheduler::TriggerStatus::Waiting,
+        retries: 0,
+        ..trigger.clone()
+    };
+    new_trigger.next_run_at += Duration::try_seconds(slo::EVALUATION_INTERVAL)
+        .unwrap()
+        .num_microseconds()
+        .unwrap();
+    if !slo.enabled {
+        db::scheduler::update_trigger(new_trigger).await?;
+        return Ok(());
+    }
+
+    let mut trigger_data_stream = TriggerData {
+        org: trigger.org.clone(),
+        module: TriggerDataType::Slo,
+        key: trigger.module_key.clone(),
+        next_run_at: new_trigger.next_run_at,
+        is_realtime: trigger.is_realtime,
+        is_silenced: trigger.is_silenced,
+        status: TriggerDataStatus::Completed,
+        start_time: trigger.start_time,
+        end_time: trigger.end_time,
+        retries: trigger.retries,
+        error: None,
+    };
+
+    match slo::run(org_id, &mut slo).await {
+        Ok(_) => {
+            db::scheduler::update_trigger(new_trigger).await?;
+        }
+        Err(e) => {
+            db::scheduler::update_status(
+                &new_trigger.org,
+                new_trigger.module,
+                &new_trigger.module_key,
+                db::scheduler::TriggerStatus::Waiting,
+                trigger.retries + 1,
+            )
+            .await?;
+            trigger_data_stream.status = TriggerDataStatus::Failed;
+            trigger_data_stream.error = Some(format!("error evaluating slo: {e}"));
+        }
+    }
+    trigger_data_stream.end_time = Utc::now().timestamp_micros();
+    publish_triggers_usage(trigger_data_stream).await;
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json::{self, Value};
+
+use crate::common::meta::alerts::{destinations::DestinationType, templates::Template};
+
+/// Body of the template used by the chat destinations without one, it renders
+/// the title, text and link of the card.
+const DEFAULT_TEMPLATE_BODY: &str = r#"{"title": "{alert_name} fired on {stream_name}", "text": "{alert_count} results {alert_operator} {alert_threshold} on {stream_type} stream {stream_name} between {alert_start_time} and {alert_end_time}", "url": "{alert_url}"}"#;
+
+/// HTML body of the template used by the email destinations without one.
+const DEFAULT_EMAIL_TEMPLATE_BODY: &str = r#"<h3>{alert_name} fired on {stream_name}</h3>
+<p>{alert_count} results {alert_operator} {alert_threshold} on {stream_type} stream {stream_name} between {alert_start_time} and {alert_end_time}.</p>
+<p><a href="{alert_url}">View in OpenObserve</a></p>"#;
+
+/// Template used by the destinations of the type when they don't set one,
+/// `None` when the type needs a template.
+pub fn default_template(destination_type: &DestinationType) -> Option<Template> {
+    match destination_type {
+        DestinationType::MsTeams | DestinationType::GoogleChat => Some(Template {
+            name: "default".to_string(),
+            body: DEFAULT_TEMPLATE_BODY.to_string(),
+            is_default: Some(true),
+            template_type: destination_type.clone(),
+        }),
+        DestinationType::Email => Some(Template {
+            name: "default".to_string(),
+            body: DEFAULT_EMAIL_TEMPLATE_BODY.to_string(),
+            is_default: Some(true),
+            template_type: DestinationType::Email,
+        }),
+        _ => None,
+    }
+}
+
+/// What a card shows, read from a rendered template.
+#[derive(Debug, PartialEq)]
+struct Card {
+    title: String,
+    text: String,
+    url: String,
+}
+
+/// A template rendering a JSON object sets the `title`, `text` and `url` of
+/// the card, any other rendering is the text of the card.
+fn parse(msg: &str, title: &str) -> Card {
+    let field = |v: &json::Map<String, Value>, name: &str| {
+        v.get(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    match json::from_str::<Value>(msg) {
+        Ok(Value::Object(v)) if v.contains_key("text") => {
+            let card_title = field(&v, "title");
+            Card {
+                title: if card_title.is_empty() {
+                    title.to_string()
+                } else {
+                    card_title
+                },
+                text: field(&v, "text"),
+                url: field(&v, "url"),
+            }
+        }
+        _ => Card {
+            title: title.to_string(),
+            text: msg.to_string(),
+            url: String::new(),
+        },
+    }
+}
+
+/// Adaptive Card message of the Microsoft Teams webhooks.
+pub fn teams(msg: &str, title: &str) -> Value {
+    let card = parse(msg, title);
+    let actions = if card.url.is_empty() {
+        vec![]
+    } else {
+        vec![json::json!({
+            "type": "Action.OpenUrl",
+            "title": "View in OpenObserve",
+            "url": card.url,
+        })]
+    };
+    json::json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "body": [
+                    {
+                        "type": "TextBlock",
+                        "text": card.title,
+                        "weight": "Bolder",
+                        "size": "Medium",
+                        "wrap": true,
+                    },
+                    {
+                        "type": "TextBlock",
+                        "text": card.text,
+                        "wrap": true,
+                    },
+                ],
+                "actions": actions,
+            },
+        }],
+    })
+}
+
+/// `cardsV2` message of the Google Chat webhooks.
+pub fn google_chat(msg: &str, title: &str) -> Value {
+    let card = parse(msg, title);
+    let mut widgets = vec![json::json!({"textParagraph": {"text": card.text}})];
+    if !card.url.is_empty() {
+        widgets.push(json::json!({
+            "buttonList": {
+                "buttons": [{
+                    "text": "View in OpenObserve",
+                    "onClick": {"openLink": {"url": card.url}},
+                }],
+            },
+        }));
+    }
+    json::json!({
+        "text": card.title,
+        "cardsV2": [{
+            "cardId": "openobserve-alert",
+            "card": {
+                "header": {"title": card.title},
+                "sections": [{"widgets": widgets}],
+            },
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cards() {
+        let msg = r#"{"title": "cpu fired", "text": "3 results", "url": "http://o2/short/x"}"#;
+        let card = teams(msg, "cpu");
+        let content = &card["attachments"][0]["content"];
+        assert_eq!(content["body"][0]["text"], "cpu fired");
+        assert_eq!(content["body"][1]["text"], "3 results");
+        assert_eq!(content["actions"][0]["url"], "http://o2/short/x");
+
+        let card = google_chat("plain text", "cpu");
+        let section = &card["cardsV2"][0]["card"]["sections"][0];
+        assert_eq!(card["cardsV2"][0]["card"]["header"]["title"], "cpu");
+        assert_eq!(section["widgets"][0]["textParagraph"]["text"], "plain text");
+        assert_eq!(section["widgets"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_default_template() {
+        assert!(default_template(&DestinationType::Http).is_none());
+        let template = default_template(&DestinationType::GoogleChat).unwrap();
+        let body = template.body.replace("{alert_url}", "http://o2/x");
+        assert_eq!(parse(&body, "").url, "http://o2/x");
+    }
+}
@@ -25,7 +25,7 @@ use crate::{
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::db,
+    service::{alerts::cards, db},
 };
 
 pub async fn save(
@@ -36,7 +36,7 @@ pub async fn save(
 ) -> Result<(), (http::StatusCode, anyhow::Error)> {
     // First validate the `destination` according to its `destination_type`
     match destination.destination_type {
-        DestinationType::Http => {
+        DestinationType::Http | DestinationType::MsTeams | DestinationType::GoogleChat => {
             if destination.url.is_empty() {
                 return Err((
                     http::StatusCode::BAD_REQUEST,
@@ -58,6 +58,14 @@ pub async fn save(
                 ));
             }
         }
+        DestinationType::Slack => {
+            if destination.slack_bot_token.is_empty() || destination.slack_channel.is_empty() {
+                return Err((
+                    http::StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Slack bot token and channel need to be specified"),
+                ));
+            }
+        }
     }
 
     if !name.is_empty() {
@@ -76,9 +84,12 @@ pub async fn save(
         ));
     }
 
-    if db::alerts::templates::get(org_id, &destination.template)
-        .await
-        .is_err()
+    let has_default_template = destination.template.is_empty()
+        && cards::default_template(&destination.destination_type).is_some();
+    if !has_default_template
+        && db::alerts::templates::get(org_id, &destination.template)
+            .await
+            .is_err()
     {
         return Err((
             http::StatusCode::BAD_REQUEST,
@@ -127,7 +138,10 @@ pub async fn get_with_template(
     name: &str,
 ) -> Result<DestinationWithTemplate, anyhow::Error> {
     let dest = get(org_id, name).await?;
-    let template = db::alerts::templates::get(org_id, &dest.template).await?;
+    let template = match cards::default_template(&dest.destination_type) {
+        Some(template) if dest.template.is_empty() => template,
+        _ => db::alerts::templates::get(org_id, &dest.template).await?,
+    };
     Ok(dest.with_template(template))
 }
 
@@ -18,7 +18,6 @@ use std::{
     str::FromStr,
 };
 
-use actix_web::http;
 use arrow_schema::DataType;
 use chrono::{Duration, Local, TimeZone, Utc};
 use config::{
@@ -26,12 +25,13 @@ use config::{
     meta::stream::StreamType,
     utils::{
         base64,
-        json::{Map, Value},
+        json::{self, Map, Value},
     },
     CONFIG, SMTP_CLIENT,
 };
 use cron::Schedule;
-use lettre::{message::SinglePart, AsyncTransport, Message};
+use infra::dist_lock;
+use lettre::{message::SinglePart, Address, AsyncTransport, Message};
 
 use super::promql;
 use crate::{
@@ -39,26 +39,60 @@ use crate::{
         meta::{
             alerts::{
                 destinations::{DestinationType, DestinationWithTemplate, HTTPType},
-                AggFunction, Alert, AlertFrequencyType, Condition, Operator, QueryCondition,
-                QueryType,
+                AggFunction, Alert, AlertAck, AlertFrequencyType, Condition, Operator,
+                QueryCondition, QueryType,
             },
             authz::Authz,
+            cdc::CdcObjectType,
+            revisions::{ObjectType, RevisionAction},
+            webhooks::{StreamEvent, StreamEventType},
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::{db, search as SearchService},
+    service::{
+        cdc, db, error::ServiceError, forecast, record_id::RECORD_ID_COLUMN, revisions,
+        search as SearchService, short_url, webhooks,
+    },
 };
 
 pub mod alert_manager;
+pub mod cards;
 pub mod destinations;
+pub mod slack;
 pub mod templates;
+pub mod throttle;
+
+fn lock_key(org_id: &str, stream_type: StreamType, stream_name: &str, name: &str) -> String {
+    format!("/alerts/{org_id}/{stream_type}/{stream_name}/{name}")
+}
 
 pub async fn save(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+    alert: Alert,
+    create: bool,
+    user_id: &str,
+) -> Result<(), anyhow::Error> {
+    // silencing or acknowledging the alert changes it under the same lock
+    let key = if name.is_empty() {
+        lock_key(org_id, alert.stream_type, stream_name, &alert.name)
+    } else {
+        lock_key(org_id, alert.stream_type, stream_name, name.trim())
+    };
+    let locker = dist_lock::lock(&key, 0).await?;
+    let ret = save_alert(org_id, stream_name, name, alert, create, user_id).await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn save_alert(
     org_id: &str,
     stream_name: &str,
     name: &str,
     mut alert: Alert,
     create: bool,
+    user_id: &str,
 ) -> Result<(), anyhow::Error> {
     if !name.is_empty() {
         alert.name = name.trim().to_string();
@@ -68,22 +102,56 @@ pub async fn save(
     alert.stream_name = stream_name.to_string();
     alert.row_template = alert.row_template.trim().to_string();
 
-    match db::alerts::get(org_id, stream_type, stream_name, &alert.name).await {
-        Ok(Some(_)) => {
-            if create {
-                return Err(anyhow::anyhow!("Alert already exists"));
+    let old_alert =
+        match db::alerts::get_stored(org_id, stream_type, stream_name, &alert.name).await {
+            Ok(Some(old_alert)) => {
+                if create {
+                    return Err(anyhow::anyhow!("Alert already exists"));
+                }
+                Some(old_alert)
             }
-        }
-        Ok(None) => {
-            if !create {
-                return Err(anyhow::anyhow!("Alert not found"));
+            Ok(None) => {
+                if !create {
+                    return Err(anyhow::anyhow!("Alert not found"));
+                }
+                None
             }
+            Err(e) => {
+                return Err(e);
+            }
+        };
+    // a silence outlives the changes of the alert, an acknowledgement doesn't
+    if let Some(old_alert) = &old_alert {
+        alert.silenced_until = old_alert.silenced_until;
+    }
+
+    validate(org_id, &mut alert).await?;
+
+    // save the alert
+    match db::alerts::set(org_id, stream_type, stream_name, &alert, create).await {
+        Ok(_) => {
+            if name.is_empty() {
+                set_ownership(org_id, "alerts", Authz::new(&alert.name)).await;
+            }
+            revisions::record(
+                org_id,
+                ObjectType::Alert,
+                &revisions::alert_id(stream_type, stream_name, &alert.name),
+                user_id,
+                RevisionAction::Saved,
+                &alert,
+            )
+            .await;
+            record_alert_change(org_id, old_alert.as_ref(), Some(&alert)).await;
+            notify_alert_changed(org_id, stream_type, stream_name, &alert.name, "saved");
+            Ok(())
         }
-        Err(e) => {
-            return Err(e);
-        }
+        Err(e) => Err(e),
     }
+}
 
+/// Checks the alert and fills its defaults, the alert is evaluated once.
+pub async fn validate(org_id: &str, alert: &mut Alert) -> Result<(), anyhow::Error> {
     if alert.trigger_condition.frequency_type == AlertFrequencyType::Cron {
         // Check the cron expression
         Schedule::from_str(&alert.trigger_condition.cron)?;
@@ -108,6 +176,41 @@ pub async fn save(
             return Err(anyhow::anyhow!("Alert destination {dest} not found"));
         };
     }
+    for email in alert.emails.iter() {
+        if email.parse::<Address>().is_err() {
+            return Err(anyhow::anyhow!("Alert email {email} is invalid"));
+        }
+    }
+    if let Some(throttle) = alert.throttle.as_ref() {
+        if throttle.max_notifications == 0 || throttle.period <= 0 {
+            return Err(anyhow::anyhow!(
+                "Alert throttle needs a positive max_notifications and period"
+            ));
+        }
+    }
+    if let Some(flap) = alert.flap_detection.as_ref() {
+        if alert.is_real_time {
+            return Err(anyhow::anyhow!(
+                "Flap detection is only supported by scheduled alerts"
+            ));
+        }
+        if flap.max_changes < 2 || flap.window <= 0 {
+            return Err(anyhow::anyhow!(
+                "Alert flap detection needs a max_changes of at least 2 and a positive window"
+            ));
+        }
+    }
+    if let Some(forecast) = alert.query_condition.forecast.as_ref() {
+        if alert.is_real_time || alert.query_condition.query_type != QueryType::SQL {
+            return Err(anyhow::anyhow!(
+                "Forecast is only supported by scheduled alerts with SQL query type"
+            ));
+        }
+        if forecast.condition.is_none() {
+            return Err(anyhow::anyhow!("Alert forecast should have a condition"));
+        }
+        forecast::validate(forecast)?;
+    }
 
     // before saving alert check alert context attributes
     if alert.context_attributes.is_some() {
@@ -122,9 +225,9 @@ pub async fn save(
     }
 
     // before saving alert check column type to decide numeric condition
-    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
-    if stream_name.is_empty() || schema.fields().is_empty() {
-        return Err(anyhow::anyhow!("Stream {stream_name} not found"));
+    let schema = infra::schema::get(org_id, &alert.stream_name, alert.stream_type).await?;
+    if alert.stream_name.is_empty() || schema.fields().is_empty() {
+        return Err(anyhow::anyhow!("Stream {} not found", alert.stream_name));
     }
 
     if alert.is_real_time && alert.query_condition.query_type != QueryType::Custom {
@@ -162,17 +265,7 @@ pub async fn save(
 
     // test the alert
     _ = &alert.evaluate(None).await?;
-
-    // save the alert
-    match db::alerts::set(org_id, stream_type, stream_name, &alert, create).await {
-        Ok(_) => {
-            if name.is_empty() {
-                set_ownership(org_id, "alerts", Authz::new(&alert.name)).await;
-            }
-            Ok(())
-        }
-        Err(e) => Err(e),
-    }
+    Ok(())
 }
 
 pub async fn get(
@@ -218,22 +311,35 @@ pub async fn delete(
     stream_type: StreamType,
     stream_name: &str,
     name: &str,
-) -> Result<(), (http::StatusCode, anyhow::Error)> {
-    if db::alerts::get(org_id, stream_type, stream_name, name)
-        .await
-        .is_err()
-    {
-        return Err((
-            http::StatusCode::NOT_FOUND,
-            anyhow::anyhow!("Alert not found"),
-        ));
-    }
+    user_id: &str,
+) -> Result<(), ServiceError> {
+    let Ok(Some(alert)) = db::alerts::get(org_id, stream_type, stream_name, name).await else {
+        return Err(ServiceError::not_found("Alert not found"));
+    };
     match db::alerts::delete(org_id, stream_type, stream_name, name).await {
         Ok(_) => {
             remove_ownership(org_id, "alerts", Authz::new(name)).await;
+            revisions::record(
+                org_id,
+                ObjectType::Alert,
+                &revisions::alert_id(stream_type, stream_name, name),
+                user_id,
+                RevisionAction::Deleted,
+                &alert,
+            )
+            .await;
+            record_alert_change(org_id, Some(&alert), None).await;
+            notify_alert_changed(org_id, stream_type, stream_name, name, "deleted");
+            if let Err(e) =
+                db::alerts::notify_state::delete(org_id, stream_type, stream_name, name).await
+            {
+                log::error!(
+                    "[ALERT] {org_id}/{stream_type}/{stream_name}/{name} delete notify state error: {e}"
+                );
+            }
             Ok(())
         }
-        Err(e) => Err((http::StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -243,20 +349,132 @@ pub async fn enable(
     stream_name: &str,
     name: &str,
     value: bool,
-) -> Result<(), (http::StatusCode, anyhow::Error)> {
-    let mut alert = match db::alerts::get(org_id, stream_type, stream_name, name).await {
-        Ok(Some(alert)) => alert,
-        _ => {
-            return Err((
-                http::StatusCode::NOT_FOUND,
-                anyhow::anyhow!("Alert not found"),
-            ));
+) -> Result<(), ServiceError> {
+    let (old_alert, alert) = update(org_id, stream_type, stream_name, name, |alert| {
+        alert.enabled = value;
+    })
+    .await?;
+    record_alert_change(org_id, Some(&old_alert), Some(&alert)).await;
+    let action = if value { "enabled" } else { "disabled" };
+    notify_alert_changed(org_id, stream_type, stream_name, name, action);
+    Ok(())
+}
+
+/// Changes the stored alert under its lock, so that a concurrent change of
+/// the alert isn't overwritten. Returns the alert before and after the change.
+async fn update(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+    change: impl FnOnce(&mut Alert),
+) -> Result<(Alert, Alert), ServiceError> {
+    let locker = dist_lock::lock(&lock_key(org_id, stream_type, stream_name, name), 0).await?;
+    let ret = match db::alerts::get_stored(org_id, stream_type, stream_name, name).await {
+        Ok(Some(old_alert)) => {
+            let mut alert = old_alert.clone();
+            change(&mut alert);
+            db::alerts::set(org_id, stream_type, stream_name, &alert, false)
+                .await
+                .map(|_| (old_alert, alert))
+                .map_err(ServiceError::from)
         }
+        _ => Err(ServiceError::not_found("Alert not found")),
     };
-    alert.enabled = value;
-    db::alerts::set(org_id, stream_type, stream_name, &alert, false)
-        .await
-        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+/// Stops the notifications of the alert for the given minutes, the alert
+/// keeps being evaluated. Zero minutes lifts the silence.
+pub async fn silence(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+    minutes: i64,
+    user_id: &str,
+) -> Result<(), ServiceError> {
+    if minutes < 0 {
+        return Err(ServiceError::bad_request("minutes can't be negative"));
+    }
+    let Some(silence) = Duration::try_minutes(minutes).and_then(|d| d.num_microseconds()) else {
+        return Err(ServiceError::bad_request("minutes is too large"));
+    };
+    let silenced_until = if minutes == 0 {
+        0
+    } else {
+        Utc::now().timestamp_micros() + silence
+    };
+    let (old_alert, alert) = update(org_id, stream_type, stream_name, name, |alert| {
+        alert.silenced_until = silenced_until;
+    })
+    .await?;
+    record_alert_change(org_id, Some(&old_alert), Some(&alert)).await;
+    let action = if minutes == 0 {
+        log::info!(
+            "[ALERT] {org_id}/{stream_type}/{stream_name}/{name} silence lifted by {user_id}"
+        );
+        "unsilenced"
+    } else {
+        log::info!(
+            "[ALERT] {org_id}/{stream_type}/{stream_name}/{name} silenced for {minutes} minutes by {user_id}"
+        );
+        "silenced"
+    };
+    notify_alert_changed(org_id, stream_type, stream_name, name, action);
+    Ok(())
+}
+
+/// Records who is taking care of the alert.
+pub async fn acknowledge(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+    user_id: &str,
+) -> Result<(), ServiceError> {
+    let ack = AlertAck {
+        user: user_id.to_string(),
+        at: Utc::now().timestamp_micros(),
+    };
+    let (old_alert, alert) = update(org_id, stream_type, stream_name, name, |alert| {
+        alert.acknowledged = Some(ack);
+    })
+    .await?;
+    record_alert_change(org_id, Some(&old_alert), Some(&alert)).await;
+    notify_alert_changed(org_id, stream_type, stream_name, name, "acknowledged");
+    Ok(())
+}
+
+async fn record_alert_change(org_id: &str, before: Option<&Alert>, after: Option<&Alert>) {
+    let Some(alert) = after.or(before) else {
+        return;
+    };
+    cdc::record(
+        org_id,
+        CdcObjectType::Alert,
+        &revisions::alert_id(alert.stream_type, &alert.stream_name, &alert.name),
+        before.and_then(|v| json::to_value(v).ok()),
+        after.and_then(|v| json::to_value(v).ok()),
+    )
+    .await;
+}
+
+fn notify_alert_changed(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+    action: &str,
+) {
+    webhooks::notify(StreamEvent::new(
+        StreamEventType::AlertChanged,
+        org_id,
+        stream_type,
+        stream_name,
+        json::json!({ "alert": name, "action": action }),
+    ));
 }
 
 pub async fn trigger(
@@ -264,20 +482,17 @@ pub async fn trigger(
     stream_type: StreamType,
     stream_name: &str,
     name: &str,
-) -> Result<(), (http::StatusCode, anyhow::Error)> {
+) -> Result<(), ServiceError> {
     let alert = match db::alerts::get(org_id, stream_type, stream_name, name).await {
         Ok(Some(alert)) => alert,
         _ => {
-            return Err((
-                http::StatusCode::NOT_FOUND,
-                anyhow::anyhow!("Alert not found"),
-            ));
+            return Err(ServiceError::not_found("Alert not found"));
         }
     };
     alert
         .send_notification(&[])
         .await
-        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+        .map_err(ServiceError::from)
 }
 
 impl Alert {
@@ -296,20 +511,82 @@ impl Alert {
         &self,
         rows: &[Map<String, Value>],
     ) -> Result<(), anyhow::Error> {
+        if self.silenced_until > Utc::now().timestamp_micros() {
+            log::info!(
+                "[ALERT] {}/{}/{}/{} is silenced, skip the notification",
+                self.org_id,
+                self.stream_type,
+                self.stream_name,
+                self.name
+            );
+            return Ok(());
+        }
+        let fired_at = Utc::now().timestamp_micros();
+        match throttle::fired(self, fired_at).await {
+            throttle::Decision::Send => {}
+            throttle::Decision::StartedFlapping => {
+                self.send_flapping_notification().await;
+                return Ok(());
+            }
+            decision => {
+                log::info!(
+                    "[ALERT] {}/{}/{}/{} is {decision:?}, skip the notification",
+                    self.org_id,
+                    self.stream_type,
+                    self.stream_name,
+                    self.name
+                );
+                return Ok(());
+            }
+        }
+        let (mut sent, mut ret) = (false, Ok(()));
         for dest in self.destinations.iter() {
-            let dest = destinations::get_with_template(&self.org_id, dest).await?;
-            if let Err(e) = send_notification(self, &dest, rows).await {
-                log::error!(
+            let dest = match destinations::get_with_template(&self.org_id, dest).await {
+                Ok(dest) => dest,
+                Err(e) => {
+                    ret = Err(e);
+                    break;
+                }
+            };
+            match send_notification(self, &dest, rows).await {
+                Ok(_) => sent = true,
+                Err(e) => log::error!(
                     "Error sending notification for {}/{}/{}/{} err: {}",
                     self.org_id,
                     self.stream_type,
                     self.stream_name,
                     self.name,
                     e
+                ),
+            }
+        }
+        if !sent {
+            throttle::unsent(self, fired_at).await;
+        }
+        ret
+    }
+
+    async fn send_flapping_notification(&self) {
+        let Some(flap) = self.flap_detection.as_ref() else {
+            return;
+        };
+        let subject = format!("{} is flapping", self.name);
+        let msg = format!(
+            "Alert {} on {} stream {} changed between firing and not firing {} times in {} minutes, its notifications are dropped until it settles",
+            self.name, self.stream_type, self.stream_name, flap.max_changes, flap.window
+        );
+        for dest in self.destinations.iter() {
+            if let Err(e) = send_raw_notification(&self.org_id, dest, &subject, msg.clone()).await {
+                log::error!(
+                    "Error sending flapping notification for {}/{}/{}/{} err: {}",
+                    self.org_id,
+                    self.stream_type,
+                    self.stream_name,
+                    self.name,
+                    e
                 );
             }
         }
-        Ok(())
     }
 }
 
@@ -441,7 +718,11 @@ impl QueryCondition {
             query: config::meta::search::Query {
                 sql: sql.clone(),
                 from: 0,
-                size: 100,
+                size: if self.forecast.is_some() {
+                    forecast::MAX_ROWS + 1
+                } else {
+                    100
+                },
                 start_time: now
                     - Duration::try_minutes(alert.trigger_condition.period)
                         .unwrap()
@@ -457,6 +738,10 @@ impl QueryCondition {
                 query_context: None,
                 query_fn: None,
                 skip_wal: false,
+                nested: false,
+                sample: 0.0,
+                partial: false,
+                estimate_total: false,
             },
             aggs: HashMap::new(),
             encoding: config::meta::search::RequestEncoding::Empty,
@@ -473,6 +758,22 @@ impl QueryCondition {
                     return Ok(None);
                 }
             };
+        // the alert fires with the series whose forecast crosses the condition
+        if let Some(forecast) = self.forecast.as_ref() {
+            forecast::check_rows(resp.hits.len())?;
+            let rows = resp
+                .hits
+                .iter()
+                .filter_map(|hit| hit.as_object().cloned())
+                .collect::<Vec<_>>();
+            let series = forecast::forecast_rows(forecast, &rows, now).await;
+            let rows = forecast::crossing_rows(forecast, &series);
+            return if rows.is_empty() || rows.len() < alert.trigger_condition.threshold as usize {
+                Ok(None)
+            } else {
+                Ok(Some(rows))
+            };
+        }
         if resp.total < alert.trigger_condition.threshold as usize {
             Ok(None)
         } else {
@@ -797,16 +1098,50 @@ pub async fn send_notification(
     dest: &DestinationWithTemplate,
     rows: &[Map<String, Value>],
 ) -> Result<(), anyhow::Error> {
+    // the emails are sent as HTML, the values can't add markup to them
+    let html = dest.destination_type == DestinationType::Email;
     let rows_tpl_val = if alert.row_template.is_empty() {
         vec!["".to_string()]
     } else {
-        process_row_template(&alert.row_template, alert, rows)
+        process_row_template(&alert.row_template, alert, rows, html)
     };
-    let msg: String = process_dest_template(&dest.template.body, alert, rows, &rows_tpl_val).await;
+    let msg: String =
+        process_dest_template(&dest.template.body, alert, rows, &rows_tpl_val, html).await;
 
     match dest.destination_type {
         DestinationType::Http => send_http_notification(dest, msg.clone()).await,
-        DestinationType::Email => send_email_notification(&alert.name, dest, msg).await,
+        DestinationType::Email => {
+            send_email_notification(&alert.name, dest, &alert.emails, msg).await
+        }
+        DestinationType::Slack => slack::send(dest, &msg, Some(alert)).await,
+        DestinationType::MsTeams => {
+            send_http_notification(dest, cards::teams(&msg, &alert.name).to_string()).await
+        }
+        DestinationType::GoogleChat => {
+            send_http_notification(dest, cards::google_chat(&msg, &alert.name).to_string()).await
+        }
+    }
+}
+
+/// Sends a message which isn't rendered by the template of the destination,
+/// used by the detections which aren't alerts.
+pub async fn send_raw_notification(
+    org_id: &str,
+    dest: &str,
+    subject: &str,
+    msg: String,
+) -> Result<(), anyhow::Error> {
+    let dest = destinations::get_with_template(org_id, dest).await?;
+    match dest.destination_type {
+        DestinationType::Http => send_http_notification(&dest, msg).await,
+        DestinationType::Email => send_email_notification(subject, &dest, &[], msg).await,
+        DestinationType::Slack => slack::send(&dest, &msg, None).await,
+        DestinationType::MsTeams => {
+            send_http_notification(&dest, cards::teams(&msg, subject).to_string()).await
+        }
+        DestinationType::GoogleChat => {
+            send_http_notification(&dest, cards::google_chat(&msg, subject).to_string()).await
+        }
     }
 }
 
@@ -858,23 +1193,30 @@ pub async fn send_http_notification(
     Ok(())
 }
 
+/// Sends the email to `recipients`, or to the recipients of the destination
+/// when empty.
 pub async fn send_email_notification(
     alert_name: &str,
     dest: &DestinationWithTemplate,
+    recipients: &[String],
     msg: String,
 ) -> Result<(), anyhow::Error> {
     if !CONFIG.smtp.smtp_enabled {
         return Err(anyhow::anyhow!("SMTP configuration not enabled"));
     }
 
-    let mut recepients = vec![];
-    for recepient in &dest.emails {
-        recepients.push(recepient);
-    }
+    let recepients = if recipients.is_empty() {
+        dest.emails.as_slice()
+    } else {
+        recipients
+    };
 
     let mut email = Message::builder()
         .from(CONFIG.smtp.smtp_from_email.parse()?)
         .subject(format!("Openobserve Alert - {}", alert_name));
+    if !CONFIG.smtp.smtp_reply_to.is_empty() {
+        email = email.reply_to(CONFIG.smtp.smtp_reply_to.parse()?);
+    }
 
     for recepient in recepients {
         email = email.to(recepient.parse()?);
@@ -889,7 +1231,12 @@ pub async fn send_email_notification(
     }
 }
 
-fn process_row_template(tpl: &String, alert: &Alert, rows: &[Map<String, Value>]) -> Vec<String> {
+fn process_row_template(
+    tpl: &String,
+    alert: &Alert,
+    rows: &[Map<String, Value>],
+    html: bool,
+) -> Vec<String> {
     let alert_type = if alert.is_real_time {
         "realtime"
     } else {
@@ -909,7 +1256,7 @@ fn process_row_template(tpl: &String, alert: &Alert, rows: &[Map<String, Value>]
             } else {
                 value.to_string()
             };
-            process_variable_replace(&mut resp, key, &VarValue::Str(&value));
+            process_variable_replace(&mut resp, key, &VarValue::Str(&escape(&value, html)));
 
             // calculate start and end time
             if key == &CONFIG.common.column_timestamp {
@@ -973,6 +1320,17 @@ fn process_row_template(tpl: &String, alert: &Alert, rows: &[Map<String, Value>]
             .replace("{alert_start_time}", &alert_start_time_str)
             .replace("{alert_end_time}", &alert_end_time_str);
 
+        // deep link to the record that triggered the alert, empty for rows
+        // that aren't raw records
+        let record_url = match row.get(RECORD_ID_COLUMN).and_then(|v| v.as_str()) {
+            Some(id) if alert.stream_type == StreamType::Logs => format!(
+                "{}{}/api/{}/{}/_doc/{}",
+                CONFIG.common.web_url, CONFIG.common.base_uri, alert.org_id, alert.stream_name, id
+            ),
+            _ => String::new(),
+        };
+        resp = resp.replace("{record_url}", &record_url);
+
         if let Some(contidion) = &alert.query_condition.promql_condition {
             resp = resp
                 .replace("{alert_promql_operator}", &contidion.operator.to_string())
@@ -996,6 +1354,7 @@ async fn process_dest_template(
     alert: &Alert,
     rows: &[Map<String, Value>],
     rows_tpl_val: &[String],
+    html: bool,
 ) -> String {
     // format values
     let alert_count = rows.len();
@@ -1157,11 +1516,29 @@ async fn process_dest_template(
         )
     };
 
+    // link the notification to a signed short url of the search, it falls back to
+    // the full url when the link can't be stored
+    let alert_url = if tpl.contains("{alert_url}") {
+        match short_url::create(&alert.org_id, &alert_url).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!(
+                    "Error creating the short url of alert {}/{} err: {e}",
+                    alert.org_id,
+                    alert.name
+                );
+                alert_url
+            }
+        }
+    } else {
+        alert_url
+    };
+
     let mut resp = tpl
-        .replace("{org_name}", &alert.org_id)
+        .replace("{org_name}", &escape(&alert.org_id, html))
         .replace("{stream_type}", &alert.stream_type.to_string())
-        .replace("{stream_name}", &alert.stream_name)
-        .replace("{alert_name}", &alert.name)
+        .replace("{stream_name}", &escape(&alert.stream_name, html))
+        .replace("{alert_name}", &escape(&alert.name, html))
         .replace("{alert_type}", alert_type)
         .replace(
             "{alert_period}",
@@ -1169,7 +1546,7 @@ async fn process_dest_template(
         )
         .replace(
             "{alert_operator}",
-            &alert.trigger_condition.operator.to_string(),
+            &escape(&alert.trigger_condition.operator.to_string(), html),
         )
         .replace(
             "{alert_threshold}",
@@ -1178,24 +1555,34 @@ async fn process_dest_template(
         .replace("{alert_count}", &alert_count.to_string())
         .replace("{alert_start_time}", &alert_start_time_str)
         .replace("{alert_end_time}", &alert_end_time_str)
-        .replace("{alert_url}", &alert_url);
+        .replace("{alert_url}", &escape(&alert_url, html));
 
     if let Some(contidion) = &alert.query_condition.promql_condition {
         resp = resp
-            .replace("{alert_promql_operator}", &contidion.operator.to_string())
-            .replace("{alert_promql_value}", &contidion.value.to_string());
+            .replace(
+                "{alert_promql_operator}",
+                &escape(&contidion.operator.to_string(), html),
+            )
+            .replace(
+                "{alert_promql_value}",
+                &escape(&contidion.value.to_string(), html),
+            );
     }
 
     process_variable_replace(&mut resp, "rows", &VarValue::Vector(rows_tpl_val));
     for (key, value) in vars.iter() {
         if resp.contains(&format!("{{{key}}}")) {
             let val = value.iter().cloned().collect::<Vec<_>>();
-            process_variable_replace(&mut resp, key, &VarValue::Str(&val.join(", ")));
+            process_variable_replace(
+                &mut resp,
+                key,
+                &VarValue::Str(&escape(&val.join(", "), html)),
+            );
         }
     }
     if let Some(attrs) = &alert.context_attributes {
         for (key, value) in attrs.iter() {
-            process_variable_replace(&mut resp, key, &VarValue::Str(value));
+            process_variable_replace(&mut resp, key, &VarValue::Str(&escape(value, html)));
         }
     }
 
@@ -1224,6 +1611,25 @@ fn process_variable_replace(tpl: &mut String, var_name: &str, var_val: &VarValue
     }
 }
 
+/// Escapes the value for an HTML message when `html` is set.
+fn escape(val: &str, html: bool) -> String {
+    if !html {
+        return val.to_string();
+    }
+    let mut escaped = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn format_variable_value(val: String) -> String {
     val.replace('\n', "\\n")
         .replace('\r', "\\r")
@@ -1268,6 +1674,15 @@ impl<'a> VarValue<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<b>cpu</b> & \"mem\" 'x'", true),
+            "&lt;b&gt;cpu&lt;/b&gt; &amp; &quot;mem&quot; &#39;x&#39;"
+        );
+        assert_eq!(escape("<b>cpu</b>", false), "<b>cpu</b>");
+    }
+
     #[tokio::test]
     async fn test_alert_create() {
         let org_id = "default";
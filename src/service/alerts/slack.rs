@@ -0,0 +1,345 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    meta::stream::StreamType,
+    utils::json::{self, Value},
+    CONFIG,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::common::meta::alerts::{
+    destinations::{DestinationType, DestinationWithTemplate},
+    Alert,
+};
+
+/// Action of the button acknowledging the alert.
+pub const ACTION_ACK: &str = "ack";
+/// Action of the button silencing the alert for an hour.
+pub const ACTION_SILENCE: &str = "silence_1h";
+/// Minutes the silence button silences the alert.
+pub const SILENCE_MINUTES: i64 = 60;
+
+/// Slack refuses the requests older than 5 minutes, so do we.
+const MAX_REQUEST_AGE_SECS: i64 = 300;
+
+/// The text of the rendered template, the `text` of a JSON template written
+/// for the incoming webhooks or the whole message.
+fn message_text(msg: &str) -> String {
+    match json::from_str::<Value>(msg) {
+        Ok(Value::Object(v)) => match v.get("text") {
+            Some(Value::String(text)) => text.to_string(),
+            _ => msg.to_string(),
+        },
+        _ => msg.to_string(),
+    }
+}
+
+/// Identifies the alert in the value of the buttons.
+fn alert_key(alert: &Alert) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        alert.org_id, alert.stream_type, alert.stream_name, alert.name
+    )
+}
+
+/// Parses the value of a button back to org, stream type, stream and alert.
+pub fn parse_alert_key(key: &str) -> Option<(&str, StreamType, &str, &str)> {
+    let mut parts = key.splitn(4, '/');
+    let org_id = parts.next()?;
+    let stream_type = parts.next()?;
+    let stream_name = parts.next()?;
+    let name = parts.next()?;
+    if org_id.is_empty() || stream_name.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((org_id, StreamType::from(stream_type), stream_name, name))
+}
+
+/// The signing secret of the Slack app of the destination.
+fn signing_secret(dest: &DestinationWithTemplate) -> &str {
+    if dest.slack_signing_secret.is_empty() {
+        &CONFIG.slack.signing_secret
+    } else {
+        &dest.slack_signing_secret
+    }
+}
+
+/// Blocks of the message, the buttons are only added for an alert and when the
+/// app can call back.
+fn blocks(text: &str, alert: Option<&Alert>, interactive: bool) -> Value {
+    let mut blocks = vec![json::json!({
+        "type": "section",
+        "text": {"type": "mrkdwn", "text": text},
+    })];
+    if let Some(alert) = alert {
+        if interactive {
+            let key = alert_key(alert);
+            blocks.push(json::json!({
+                "type": "actions",
+                "elements": [
+                    {
+                        "type": "button",
+                        "text": {"type": "plain_text", "text": "Acknowledge"},
+                        "style": "primary",
+                        "action_id": ACTION_ACK,
+                        "value": key,
+                    },
+                    {
+                        "type": "button",
+                        "text": {"type": "plain_text", "text": "Silence 1h"},
+                        "action_id": ACTION_SILENCE,
+                        "value": key,
+                    },
+                ],
+            }));
+        }
+    }
+    Value::Array(blocks)
+}
+
+/// Posts the message to the channel of the destination with the bot token.
+pub async fn send(
+    dest: &DestinationWithTemplate,
+    msg: &str,
+    alert: Option<&Alert>,
+) -> Result<(), anyhow::Error> {
+    let text = message_text(msg);
+    let body = json::json!({
+        "channel": dest.slack_channel,
+        "text": text,
+        "blocks": blocks(&text, alert, !signing_secret(dest).is_empty()),
+    });
+    let resp = reqwest::Client::new()
+        .post(format!("{}/chat.postMessage", CONFIG.slack.api_url))
+        .bearer_auth(&dest.slack_bot_token)
+        .json(&body)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!("slack error status: {}", resp.status()));
+    }
+    // slack answers 200 with ok false on errors
+    let resp: Value = resp.json().await?;
+    if resp.get("ok").and_then(|v| v.as_bool()) != Some(true) {
+        return Err(anyhow::anyhow!(
+            "slack error: {}",
+            resp.get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the `X-Slack-Signature` of a request, the hex HMAC-SHA256 of
+/// `v0:{timestamp}:{body}` keyed by the signing secret.
+pub fn verify_signature(
+    secret: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+    now: i64,
+) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    let Ok(ts) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - ts).abs() > MAX_REQUEST_AGE_SECS {
+        return false;
+    }
+    let Some(signature) = signature
+        .strip_prefix("v0=")
+        .and_then(|v| hex::decode(v).ok())
+    else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("v0:{timestamp}:").as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// The action clicked and the alert it applies to.
+fn interaction_action(payload: &Value) -> Result<(&str, &str), anyhow::Error> {
+    let action = payload
+        .get("actions")
+        .and_then(|v| v.get(0))
+        .ok_or_else(|| anyhow::anyhow!("no action in the payload"))?;
+    let action_id = action
+        .get("action_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let key = action
+        .get("value")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    Ok((action_id, key))
+}
+
+/// The signing secrets of the Slack apps the alert of the interaction posts
+/// with, each destination can use its own app. The payload isn't verified
+/// yet, it only tells which secrets to check the signature with.
+pub async fn interaction_secrets(payload: &Value) -> Vec<String> {
+    let Some((org_id, stream_type, stream_name, name)) = interaction_action(payload)
+        .ok()
+        .and_then(|(_, key)| parse_alert_key(key))
+    else {
+        return vec![];
+    };
+    let Ok(Some(alert)) = super::get(org_id, stream_type, stream_name, name).await else {
+        return vec![];
+    };
+    let mut secrets = vec![];
+    for dest in alert.destinations.iter() {
+        let Ok(dest) = super::destinations::get_with_template(org_id, dest).await else {
+            continue;
+        };
+        if dest.destination_type != DestinationType::Slack {
+            continue;
+        }
+        let secret = signing_secret(&dest);
+        if !secret.is_empty() && !secrets.iter().any(|s| s == secret) {
+            secrets.push(secret.to_string());
+        }
+    }
+    secrets
+}
+
+/// Applies the button clicked in a notification, the payload is the JSON of
+/// the `payload` form field sent by Slack. Returns the line appended to the
+/// message.
+pub async fn handle_interaction(payload: &Value) -> Result<String, anyhow::Error> {
+    let (action_id, key) = interaction_action(payload)?;
+    let Some((org_id, stream_type, stream_name, name)) = parse_alert_key(key) else {
+        return Err(anyhow::anyhow!("invalid alert: {key}"));
+    };
+    let user = payload
+        .get("user")
+        .and_then(|u| u.get("username").or_else(|| u.get("name")))
+        .and_then(|v| v.as_str())
+        .map(|v| format!("slack:{v}"))
+        .unwrap_or_else(|| "slack".to_string());
+
+    let line = match action_id {
+        ACTION_ACK => {
+            super::acknowledge(org_id, stream_type, stream_name, name, &user).await?;
+            format!(":white_check_mark: acknowledged by {user}")
+        }
+        ACTION_SILENCE => {
+            super::silence(
+                org_id,
+                stream_type,
+                stream_name,
+                name,
+                SILENCE_MINUTES,
+                &user,
+            )
+            .await?;
+            format!(":no_bell: silenced for 1h by {user}")
+        }
+        _ => return Err(anyhow::anyhow!("unknown action: {action_id}")),
+    };
+
+    // append the outcome to the notification
+    if let Some(response_url) = payload.get("response_url").and_then(|v| v.as_str()) {
+        let text = payload
+            .get("message")
+            .and_then(|m| m.get("text"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let text = format!("{text}\n{line}");
+        let body = json::json!({
+            "replace_original": true,
+            "text": text,
+            "blocks": blocks(&text, None, false),
+        });
+        if let Err(e) = reqwest::Client::new()
+            .post(response_url)
+            .json(&body)
+            .send()
+            .await
+        {
+            log::error!("Error updating the slack message of alert {key}: {e}");
+        }
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        let secret = "8f742231b10e8888abcd99yyyzzz85a5";
+        let body = b"payload=%7B%7D";
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(b"v0:1531420618:");
+        mac.update(body);
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert!(verify_signature(
+            secret,
+            "1531420618",
+            body,
+            &signature,
+            1531420618
+        ));
+        assert!(!verify_signature(
+            secret,
+            "1531420618",
+            b"payload=x",
+            &signature,
+            1531420618
+        ));
+        assert!(!verify_signature(
+            "other",
+            "1531420618",
+            body,
+            &signature,
+            1531420618
+        ));
+        // replayed after 5 minutes
+        assert!(!verify_signature(
+            secret,
+            "1531420618",
+            body,
+            &signature,
+            1531421000
+        ));
+        assert!(!verify_signature(
+            "",
+            "1531420618",
+            body,
+            &signature,
+            1531420618
+        ));
+    }
+
+    #[test]
+    fn test_message_text() {
+        assert_eq!(message_text(r#"{"text": "cpu high"}"#), "cpu high");
+        assert_eq!(message_text("*cpu* high"), "*cpu* high");
+        assert_eq!(
+            parse_alert_key("default/logs/k8s/cpu high"),
+            Some(("default", StreamType::Logs, "k8s", "cpu high"))
+        );
+        assert_eq!(parse_alert_key("default/logs"), None);
+    }
+}
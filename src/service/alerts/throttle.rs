@@ -0,0 +1,253 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::{Duration, Utc};
+use infra::dist_lock;
+
+use crate::{
+    common::meta::alerts::{Alert, AlertNotifyState},
+    service::db,
+};
+
+/// What the dispatcher does with a firing of an alert.
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Send,
+    /// The alert sent its most notifications of the throttle period
+    Throttled,
+    /// The alert started flapping, the flapping notification is sent instead
+    StartedFlapping,
+    /// The alert is still flapping
+    Flapping,
+}
+
+fn minutes_ago(now: i64, minutes: i64) -> i64 {
+    now - Duration::try_minutes(minutes)
+        .unwrap_or_default()
+        .num_microseconds()
+        .unwrap_or_default()
+}
+
+/// Records a firing of the alert at `now` and decides whether it is notified.
+pub fn on_fired(alert: &Alert, state: &mut AlertNotifyState, now: i64) -> Decision {
+    if let Some(flap) = alert.flap_detection.as_ref() {
+        if !state.firing {
+            state.firing = true;
+            state.changes_at.push(now);
+        }
+        let start = minutes_ago(now, flap.window);
+        state.changes_at.retain(|t| *t > start);
+        if state.changes_at.len() >= flap.max_changes {
+            if state.flapping {
+                return Decision::Flapping;
+            }
+            state.flapping = true;
+            return Decision::StartedFlapping;
+        }
+        state.flapping = false;
+    }
+    if let Some(throttle) = alert.throttle.as_ref() {
+        let start = minutes_ago(now, throttle.period);
+        state.sent_at.retain(|t| *t > start);
+        if state.sent_at.len() >= throttle.max_notifications {
+            return Decision::Throttled;
+        }
+        state.sent_at.push(now);
+    }
+    Decision::Send
+}
+
+/// Records an evaluation at `now` which didn't fire, returns whether the state
+/// changed.
+pub fn on_resolved(alert: &Alert, state: &mut AlertNotifyState, now: i64) -> bool {
+    let Some(flap) = alert.flap_detection.as_ref() else {
+        return false;
+    };
+    if !state.firing {
+        return false;
+    }
+    state.firing = false;
+    state.changes_at.push(now);
+    let start = minutes_ago(now, flap.window);
+    state.changes_at.retain(|t| *t > start);
+    true
+}
+
+/// Forgets the notification sent at `sent_at`, returns whether the state
+/// changed.
+pub fn on_unsent(state: &mut AlertNotifyState, sent_at: i64) -> bool {
+    let len = state.sent_at.len();
+    state.sent_at.retain(|t| *t != sent_at);
+    state.sent_at.len() != len
+}
+
+fn lock_key(alert: &Alert) -> String {
+    format!(
+        "/alert_notify_state/{}/{}/{}/{}",
+        alert.org_id, alert.stream_type, alert.stream_name, alert.name
+    )
+}
+
+/// Changes the notify state of the alert under its lock, the ingesters firing
+/// a real-time alert at once would lose each other's changes otherwise. The
+/// change returns its value and whether the state changed.
+async fn update<T>(
+    alert: &Alert,
+    change: impl FnOnce(&mut AlertNotifyState) -> (T, bool),
+) -> Result<T, anyhow::Error> {
+    let locker = dist_lock::lock(&lock_key(alert), 0).await?;
+    let ret: Result<T, anyhow::Error> = async {
+        let mut state = db::alerts::notify_state::get(
+            &alert.org_id,
+            alert.stream_type,
+            &alert.stream_name,
+            &alert.name,
+        )
+        .await?;
+        let (value, changed) = change(&mut state);
+        if changed {
+            db::alerts::notify_state::set(
+                &alert.org_id,
+                alert.stream_type,
+                &alert.stream_name,
+                &alert.name,
+                &state,
+            )
+            .await?;
+        }
+        Ok(value)
+    }
+    .await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+/// Records a firing of the alert at `now` and decides whether it is notified,
+/// alerts without a throttle or flap detection are always notified.
+pub async fn fired(alert: &Alert, now: i64) -> Decision {
+    if alert.throttle.is_none() && alert.flap_detection.is_none() {
+        return Decision::Send;
+    }
+    match update(alert, |state| (on_fired(alert, state, now), true)).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            log::error!(
+                "[ALERT] {}/{}/{}/{} update notify state error: {e}",
+                alert.org_id,
+                alert.stream_type,
+                alert.stream_name,
+                alert.name
+            );
+            Decision::Send
+        }
+    }
+}
+
+/// Records that the notification of the firing at `fired_at` reached no
+/// destination, it doesn't count toward the throttle.
+pub async fn unsent(alert: &Alert, fired_at: i64) {
+    if alert.throttle.is_none() {
+        return;
+    }
+    if let Err(e) = update(alert, |state| ((), on_unsent(state, fired_at))).await {
+        log::error!(
+            "[ALERT] {}/{}/{}/{} update notify state error: {e}",
+            alert.org_id,
+            alert.stream_type,
+            alert.stream_name,
+            alert.name
+        );
+    }
+}
+
+/// Records an evaluation of the alert which didn't fire.
+pub async fn resolved(alert: &Alert) {
+    if alert.flap_detection.is_none() {
+        return;
+    }
+    let now = Utc::now().timestamp_micros();
+    if let Err(e) = update(alert, |state| ((), on_resolved(alert, state, now))).await {
+        log::error!(
+            "[ALERT] {}/{}/{}/{} update notify state error: {e}",
+            alert.org_id,
+            alert.stream_type,
+            alert.stream_name,
+            alert.name
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::alerts::{AlertThrottle, FlapDetection};
+
+    const MINUTE: i64 = 60_000_000;
+
+    #[test]
+    fn test_throttle() {
+        let alert = Alert {
+            throttle: Some(AlertThrottle {
+                max_notifications: 2,
+                period: 10,
+            }),
+            ..Default::default()
+        };
+        let mut state = AlertNotifyState::default();
+        assert_eq!(on_fired(&alert, &mut state, 0), Decision::Send);
+        assert_eq!(on_fired(&alert, &mut state, MINUTE), Decision::Send);
+        assert_eq!(
+            on_fired(&alert, &mut state, 2 * MINUTE),
+            Decision::Throttled
+        );
+        assert_eq!(on_fired(&alert, &mut state, 11 * MINUTE), Decision::Send);
+        // a notification which wasn't sent frees its place
+        assert!(on_unsent(&mut state, 11 * MINUTE));
+        assert!(!on_unsent(&mut state, 11 * MINUTE));
+        assert_eq!(on_fired(&alert, &mut state, 12 * MINUTE), Decision::Send);
+        assert_eq!(on_fired(&alert, &mut state, 13 * MINUTE), Decision::Send);
+        assert_eq!(
+            on_fired(&alert, &mut state, 14 * MINUTE),
+            Decision::Throttled
+        );
+    }
+
+    #[test]
+    fn test_flap_detection() {
+        let alert = Alert {
+            flap_detection: Some(FlapDetection {
+                max_changes: 4,
+                window: 10,
+            }),
+            ..Default::default()
+        };
+        let mut state = AlertNotifyState::default();
+        assert_eq!(on_fired(&alert, &mut state, 0), Decision::Send);
+        assert_eq!(on_fired(&alert, &mut state, MINUTE), Decision::Send);
+        assert!(on_resolved(&alert, &mut state, 2 * MINUTE));
+        assert!(!on_resolved(&alert, &mut state, 3 * MINUTE));
+        assert_eq!(on_fired(&alert, &mut state, 4 * MINUTE), Decision::Send);
+        assert!(on_resolved(&alert, &mut state, 5 * MINUTE));
+        assert_eq!(
+            on_fired(&alert, &mut state, 6 * MINUTE),
+            Decision::StartedFlapping
+        );
+        assert!(on_resolved(&alert, &mut state, 7 * MINUTE));
+        assert_eq!(on_fired(&alert, &mut state, 8 * MINUTE), Decision::Flapping);
+        // the changes leave the window
+        assert_eq!(on_fired(&alert, &mut state, 30 * MINUTE), Decision::Send);
+        assert!(!state.flapping);
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    cluster::{is_compactor, is_ingester, is_querier, LOCAL_NODE_ROLE},
+    metrics,
+    utils::file::scan_files,
+    CONFIG,
+};
+use prometheus::core::Collector;
+use serde::Serialize;
+
+/// Streams whose compaction is behind by more than this many hours count as
+/// delayed.
+const COMPACT_DELAYED_HOURS: i64 = 2;
+
+/// The load of the local node for the roles it has, polled by autoscalers
+/// such as the KEDA metrics-api scaler. The roles the node doesn't have are
+/// omitted.
+#[derive(Serialize, Debug, Default)]
+pub struct ScalingSignals {
+    pub node: String,
+    pub roles: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ingester: Option<IngesterSignals>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub querier: Option<QuerierSignals>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compactor: Option<CompactorSignals>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct IngesterSignals {
+    pub memtable_bytes: i64,
+    pub memtable_max_bytes: i64,
+    /// Share of the memtable limit in use, ingestion is rejected at 1.0.
+    pub memtable_pressure: f64,
+    pub wal_bytes: i64,
+    /// WAL files waiting to be uploaded to the storage.
+    pub wal_pending_files: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct QuerierSignals {
+    /// Searches waiting in the query queue.
+    pub queue_depth: i64,
+    pub running_queries: i64,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct CompactorSignals {
+    pub max_delay_hours: i64,
+    /// Streams compacted more than a couple of hours behind.
+    pub delayed_streams: usize,
+}
+
+pub async fn get_signals() -> ScalingSignals {
+    let roles = &*LOCAL_NODE_ROLE;
+    ScalingSignals {
+        node: CONFIG.common.instance_name.clone(),
+        roles: roles.iter().map(|r| r.to_string()).collect(),
+        ingester: if is_ingester(roles) {
+            Some(ingester_signals().await)
+        } else {
+            None
+        },
+        querier: is_querier(roles).then(querier_signals),
+        compactor: is_compactor(roles).then(compactor_signals),
+    }
+}
+
+async fn ingester_signals() -> IngesterSignals {
+    let memtable_bytes = metrics::INGEST_MEMTABLE_ARROW_BYTES
+        .with_label_values(&[])
+        .get();
+    let memtable_max_bytes = CONFIG.limit.mem_table_max_size as i64;
+    let wal_pending_files = scan_files(
+        format!("{}files/", CONFIG.common.data_wal_dir),
+        "parquet",
+        None,
+    )
+    .await
+    .map(|files| files.len())
+    .unwrap_or_default();
+    IngesterSignals {
+        memtable_bytes,
+        memtable_max_bytes,
+        memtable_pressure: pressure(memtable_bytes, memtable_max_bytes),
+        wal_bytes: gauge_values(&metrics::INGEST_WAL_USED_BYTES).sum(),
+        wal_pending_files,
+    }
+}
+
+fn querier_signals() -> QuerierSignals {
+    QuerierSignals {
+        queue_depth: metrics::QUERY_PENDING_NUMS.with_label_values(&[]).get(),
+        running_queries: metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).get(),
+    }
+}
+
+fn compactor_signals() -> CompactorSignals {
+    compactor_delay(gauge_values(&metrics::COMPACT_DELAY_HOURS))
+}
+
+fn compactor_delay(delays: impl Iterator<Item = i64>) -> CompactorSignals {
+    delays.fold(CompactorSignals::default(), |mut acc, hours| {
+        acc.max_delay_hours = acc.max_delay_hours.max(hours);
+        if hours > COMPACT_DELAYED_HOURS {
+            acc.delayed_streams += 1;
+        }
+        acc
+    })
+}
+
+fn pressure(used: i64, limit: i64) -> f64 {
+    if limit <= 0 {
+        return 0.0;
+    }
+    ((used as f64 / limit as f64) * 1000.0).round() / 1000.0
+}
+
+/// The values of a gauge for all its label values.
+fn gauge_values(gauge: &prometheus::IntGaugeVec) -> impl Iterator<Item = i64> {
+    gauge
+        .collect()
+        .into_iter()
+        .flat_map(|mf| mf.get_metric().to_vec())
+        .map(|m| m.get_gauge().get_value() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pressure() {
+        assert_eq!(pressure(0, 0), 0.0);
+        assert_eq!(pressure(512, 1024), 0.5);
+        assert_eq!(pressure(2048, 1024), 2.0);
+    }
+
+    #[test]
+    fn test_compactor_delay() {
+        let signals = compactor_delay(vec![0, 1, 3, 12].into_iter());
+        assert_eq!(
+            signals,
+            CompactorSignals {
+                max_delay_hours: 12,
+                delayed_streams: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Change data capture of the metadata. The changes of the schemas, stream
+//! settings, alerts and users are written with their state before and after
+//! the change into the `_cdc` stream of the org, so they can be replayed to
+//! reconcile another cluster or backed up by external tooling.
+
+use arrow_schema::Schema;
+use chrono::Utc;
+use config::{
+    meta::stream::StreamType,
+    utils::json::{self, Map, Value},
+    CONFIG,
+};
+use infra::dist_lock;
+use proto::cluster_rpc;
+
+use crate::{
+    common::{
+        meta::{
+            cdc::{CdcEvent, CdcObjectType, CdcOperation, CDC_STREAM},
+            user::{DBUser, UserResponse, UserRole},
+        },
+        utils::auth::is_root_user,
+    },
+    service::{db, usage::ingestion_service, users},
+};
+
+const FLUSH_LOCK_KEY: &str = "/cdc_flush";
+
+/// Stores the change with the metadata, it is written into the `_cdc` stream
+/// by the periodic flush. Nothing is recorded when the state didn't change.
+pub async fn record(
+    org_id: &str,
+    object_type: CdcObjectType,
+    object_id: &str,
+    before: Option<Value>,
+    after: Option<Value>,
+) {
+    if !CONFIG.common.cdc_enabled || before == after {
+        return;
+    }
+    let event = CdcEvent {
+        org_id: org_id.to_string(),
+        timestamp: Utc::now().timestamp_micros(),
+        object_type,
+        object_id: object_id.to_string(),
+        operation: CdcOperation::from_change(before.as_ref(), after.as_ref()),
+        before: before.map(|v| v.to_string()),
+        after: after.map(|v| v.to_string()),
+        node: CONFIG.common.instance_name.to_string(),
+    };
+
+    if let Err(e) = db::cdc::put(org_id, &to_record(&event)).await {
+        log::error!(
+            "[CDC] record {:?} change of {object_id} in org {org_id} error: {e}",
+            event.object_type
+        );
+    }
+}
+
+/// The `_cdc` stream is readable by the admins of the org only, the changes
+/// of the users and alerts are not meant for every member.
+pub async fn can_read(
+    org_id: &str,
+    user_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> bool {
+    if stream_type != StreamType::Logs || stream_name != CDC_STREAM || is_root_user(user_id) {
+        return true;
+    }
+    users::get_user(Some(org_id), user_id)
+        .await
+        .is_some_and(|user| user.role == UserRole::Admin)
+}
+
+/// Records the change of the fields of a stream schema. The changes of the
+/// `_cdc` stream itself are skipped, writing them would change it again.
+pub async fn record_schema(
+    org_id: &str,
+    object_id: &str,
+    stream_name: &str,
+    before: Option<&Schema>,
+    after: Option<&Schema>,
+) {
+    if stream_name == CDC_STREAM {
+        return;
+    }
+    record(
+        org_id,
+        CdcObjectType::Schema,
+        object_id,
+        before.map(schema_fields),
+        after.map(schema_fields),
+    )
+    .await
+}
+
+/// Records the change of a user in each of the orgs the user belongs to
+/// before or after the change.
+pub async fn record_user(before: Option<&DBUser>, after: Option<&DBUser>) {
+    for (org_id, email, before, after) in user_changes(before, after) {
+        record(&org_id, CdcObjectType::User, &email, before, after).await;
+    }
+}
+
+/// Writes the stored changes into the `_cdc` stream of their org, one node at
+/// a time so that they are written once. The changes are removed once written,
+/// the ones which failed are retried by the next flush.
+pub async fn flush() {
+    let locker = match dist_lock::lock(FLUSH_LOCK_KEY, 0).await {
+        Ok(locker) => locker,
+        Err(e) => {
+            log::error!("[CDC] flush lock error: {e}");
+            return;
+        }
+    };
+    if let Err(e) = ingest_events().await {
+        log::error!("[CDC] flush error: {e}");
+    }
+    if let Err(e) = dist_lock::unlock(&locker).await {
+        log::error!("[CDC] flush unlock error: {e}");
+    }
+}
+
+async fn ingest_events() -> Result<(), anyhow::Error> {
+    let events = db::cdc::list().await?;
+    let mut groups: Vec<(&str, Vec<(&str, &Value)>)> = Vec::new();
+    for (key, org_id, record) in events.iter() {
+        match groups.iter_mut().find(|(org, _)| org == org_id) {
+            Some((_, records)) => records.push((key, record)),
+            None => groups.push((org_id, vec![(key, record)])),
+        }
+    }
+    for (org_id, records) in groups {
+        for batch in records.chunks(CONFIG.common.cdc_batch_size.max(1)) {
+            let req = cluster_rpc::UsageRequest {
+                stream_name: CDC_STREAM.to_owned(),
+                data: Some(cluster_rpc::UsageData::from(
+                    batch.iter().map(|(_, r)| (*r).clone()).collect::<Vec<_>>(),
+                )),
+            };
+            if let Err(e) = ingestion_service::ingest(org_id, req).await {
+                // the changes of the org are kept in order, the rest waits
+                log::error!("[CDC] ingest events of org {org_id} error: {e}");
+                break;
+            }
+            for (key, _) in batch {
+                db::cdc::delete(key).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_record(event: &CdcEvent) -> Value {
+    let mut record = match json::to_value(event) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    record.insert(
+        CONFIG.common.column_timestamp.to_string(),
+        event.timestamp.into(),
+    );
+    Value::Object(record)
+}
+
+/// The fields of a schema with their types.
+fn schema_fields(schema: &Schema) -> Value {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().to_string(), Value::from(f.data_type().to_string())))
+        .collect::<Map<_, _>>();
+    Value::Object(fields)
+}
+
+/// The `(org, email, before, after)` states of the user in each org, without
+/// the password, salt and tokens.
+fn user_changes(
+    before: Option<&DBUser>,
+    after: Option<&DBUser>,
+) -> Vec<(String, String, Option<Value>, Option<Value>)> {
+    let view = |user: Option<&DBUser>, org_id: &str| {
+        let user = user?;
+        let org = user.organizations.iter().find(|o| o.name == org_id)?;
+        json::to_value(UserResponse {
+            email: user.email.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            role: org.role.clone(),
+            is_external: user.is_external,
+        })
+        .ok()
+    };
+    let mut orgs = before
+        .iter()
+        .chain(after.iter())
+        .flat_map(|u| u.organizations.iter().map(|o| o.name.clone()))
+        .collect::<Vec<_>>();
+    orgs.sort();
+    orgs.dedup();
+    let Some(email) = after.or(before).map(|u| u.email.clone()) else {
+        return vec![];
+    };
+    orgs.into_iter()
+        .map(|org_id| {
+            let before = view(before, &org_id);
+            let after = view(after, &org_id);
+            (org_id, email.clone(), before, after)
+        })
+        .filter(|(_, _, before, after)| before != after)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::user::{UserOrg, UserRole};
+
+    fn db_user(orgs: &[(&str, UserRole)]) -> DBUser {
+        DBUser {
+            email: "alice@example.com".to_string(),
+            first_name: "alice".to_string(),
+            last_name: "".to_string(),
+            password: "secret".to_string(),
+            salt: "salt".to_string(),
+            organizations: orgs
+                .iter()
+                .map(|(name, role)| UserOrg {
+                    name: name.to_string(),
+                    token: "token".to_string(),
+                    rum_token: None,
+                    role: role.clone(),
+                })
+                .collect(),
+            is_external: false,
+        }
+    }
+
+    #[test]
+    fn test_user_changes() {
+        let before = db_user(&[("default", UserRole::Member), ("ops", UserRole::Admin)]);
+        let after = db_user(&[("default", UserRole::Admin), ("ops", UserRole::Admin)]);
+        let changes = user_changes(Some(&before), Some(&after));
+        assert_eq!(changes.len(), 1);
+        let (org_id, email, old, new) = &changes[0];
+        assert_eq!(org_id, "default");
+        assert_eq!(email, "alice@example.com");
+        assert!(old.is_some() && new.is_some());
+        assert!(!new.as_ref().unwrap().to_string().contains("secret"));
+
+        let removed = db_user(&[("ops", UserRole::Admin)]);
+        let changes = user_changes(Some(&after), Some(&removed));
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].3.is_none());
+
+        assert_eq!(user_changes(None, Some(&removed)).len(), 1);
+    }
+
+    #[test]
+    fn test_operation_from_change() {
+        let v = Value::from(1);
+        assert_eq!(
+            CdcOperation::from_change(None, Some(&v)),
+            CdcOperation::Create
+        );
+        assert_eq!(
+            CdcOperation::from_change(Some(&v), Some(&v)),
+            CdcOperation::Update
+        );
+        assert_eq!(
+            CdcOperation::from_change(Some(&v), None),
+            CdcOperation::Delete
+        );
+    }
+}
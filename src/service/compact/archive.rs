@@ -0,0 +1,377 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::HashSet;
+
+use chrono::Utc;
+use config::{
+    meta::stream::{FileKey, PartitionTimeLevel, StreamType},
+    utils::{json, parquet::read_schema_from_bytes},
+    CONFIG,
+};
+use futures::{StreamExt, TryStreamExt};
+use infra::{cache, storage};
+
+use crate::{
+    common::meta::stream::{ArchiveRestore, LegalHold, StreamArchive},
+    service::{
+        db,
+        error::{Result, ServiceError},
+        file_list, format_stream_name,
+    },
+};
+
+/// Whether the expired files of the stream go to the archive bucket instead of
+/// being deleted.
+pub async fn is_archived(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    if !storage::archive::is_enabled() {
+        return false;
+    }
+    if db::compact::archive::is_delete(org_id, stream_type, stream_name).await {
+        return true;
+    }
+    infra::schema::get_settings(org_id, stream_name, stream_type)
+        .await
+        .is_some_and(|s| s.archive)
+}
+
+/// The id of the archive of a time range of a stream, an archive retried after
+/// a failure extends the first one
+fn archive_id(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    time_range: (i64, i64),
+) -> String {
+    let key = format!(
+        "{org_id}/{stream_type}/{stream_name}/{}/{}",
+        time_range.0, time_range.1
+    );
+    blake3::hash(key.as_bytes()).to_hex()[..16].to_string()
+}
+
+fn manifest_key(org_id: &str, id: &str) -> String {
+    format!("archives/{org_id}/{id}.json")
+}
+
+async fn get_manifest(org_id: &str, id: &str) -> Result<Vec<FileKey>, anyhow::Error> {
+    let data = storage::archive::get(&manifest_key(org_id, id)).await?;
+    Ok(json::from_slice(&data)?)
+}
+
+/// Copies the files of the time range to the archive bucket and lists them in
+/// the manifest of the archive, the caller deletes them afterwards.
+pub async fn archive(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    time_range: (i64, i64),
+) -> Result<(), anyhow::Error> {
+    let files = file_list::query(
+        org_id,
+        stream_name,
+        stream_type,
+        PartitionTimeLevel::Unset,
+        time_range.0,
+        time_range.1,
+        true,
+    )
+    .await?;
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    // the files copied by a previous attempt are listed by its manifest
+    let id = archive_id(org_id, stream_type, stream_name, time_range);
+    let mut manifest =
+        match db::compact::archive::get(org_id, stream_type, stream_name, &id).await? {
+            Some(_) => get_manifest(org_id, &id).await?,
+            None => vec![],
+        };
+    let files = unarchived(files, &manifest);
+
+    futures::stream::iter(files.iter())
+        .map(|file| async move {
+            let data = storage::get(&file.key).await?;
+            storage::archive::put(&file.key, data).await
+        })
+        .buffer_unordered(CONFIG.limit.query_thread_num)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let copied = files.len();
+    manifest.extend(files);
+    storage::archive::put(&manifest_key(org_id, &id), json::to_vec(&manifest)?.into()).await?;
+    let archive = StreamArchive {
+        id,
+        stream_type,
+        stream_name: stream_name.to_string(),
+        start_time: time_range.0,
+        end_time: time_range.1,
+        files: manifest.len(),
+        records: manifest.iter().map(|f| f.meta.records).sum(),
+        original_size: manifest.iter().map(|f| f.meta.original_size).sum(),
+        compressed_size: manifest.iter().map(|f| f.meta.compressed_size).sum(),
+        created_at: Utc::now().timestamp_micros(),
+    };
+    db::compact::archive::set(org_id, &archive).await?;
+    log::info!(
+        "[COMPACT] archived {copied} files of {org_id}/{stream_type}/{stream_name} time range: [{},{}]",
+        time_range.0,
+        time_range.1,
+    );
+    Ok(())
+}
+
+/// The files not listed by the manifest of the archive yet.
+fn unarchived(files: Vec<FileKey>, manifest: &[FileKey]) -> Vec<FileKey> {
+    let archived = manifest.iter().map(|f| &f.key).collect::<HashSet<_>>();
+    files
+        .into_iter()
+        .filter(|f| !archived.contains(&f.key))
+        .collect()
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<StreamArchive>> {
+    Ok(db::compact::archive::list(org_id).await?)
+}
+
+pub async fn get(org_id: &str, id: &str) -> Result<StreamArchive> {
+    list(org_id)
+        .await?
+        .into_iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| ServiceError::not_found(format!("archive {id} not found")))
+}
+
+/// Copies the archived files back into a stream, the archived one by default.
+/// The restored range is kept by a legal hold, otherwise the retention would
+/// expire it again, and released once the investigation is done. A restore
+/// retried after a failure skips the files already restored.
+pub async fn restore(
+    org_id: &str,
+    id: &str,
+    stream_name: Option<String>,
+    user_email: &str,
+) -> Result<ArchiveRestore> {
+    let archive = get(org_id, id).await?;
+    let stream_type = archive.stream_type;
+    let stream_name = stream_name
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| archive.stream_name.clone());
+    if format_stream_name(&stream_name) != stream_name {
+        return Err(ServiceError::bad_request(format!(
+            "invalid stream name: {stream_name}"
+        )));
+    }
+    if db::compact::retention::is_deleting_stream(org_id, stream_type, &stream_name, None) {
+        return Err(ServiceError::Conflict(format!(
+            "stream [{stream_name}] is being deleted"
+        )));
+    }
+
+    let files = get_manifest(org_id, id).await?;
+    if files.is_empty() {
+        return Err(ServiceError::bad_request(format!(
+            "archive {id} has no files"
+        )));
+    }
+    let restored = file_list::query(
+        org_id,
+        &stream_name,
+        stream_type,
+        PartitionTimeLevel::Unset,
+        archive.start_time,
+        archive.end_time,
+        false,
+    )
+    .await?
+    .into_iter()
+    .map(|f| f.key)
+    .collect::<HashSet<_>>();
+
+    let mut events = Vec::with_capacity(files.len());
+    for (file, key) in to_restore(org_id, &archive, &stream_name, &files, &restored) {
+        let data = storage::archive::get(&file.key).await?;
+        // the stream may be gone with its schema, rebuild it from the files
+        let schema = read_schema_from_bytes(&data).await?;
+        db::schema::merge(
+            org_id,
+            &stream_name,
+            stream_type,
+            &schema,
+            Some(archive.start_time),
+        )
+        .await?;
+        storage::put(&key, data).await?;
+        events.push(FileKey::new(&key, file.meta.clone(), false));
+    }
+
+    // the hold placed by a previous attempt is kept
+    let hold = restore_hold(&archive, &stream_name);
+    let hold = match db::compact::legal_holds::list_by_stream(org_id, stream_type, &stream_name)
+        .into_iter()
+        .find(|h| h.reason == hold.reason)
+    {
+        Some(hold) => hold,
+        None => super::legal_holds::create(org_id, hold, user_email).await?,
+    };
+    super::merge::write_file_list(org_id, &events).await?;
+
+    // the stats job adds the restored files, the searches of the restored range
+    // shouldn't wait for it
+    let min_ts = files
+        .iter()
+        .map(|f| f.meta.min_ts)
+        .min()
+        .unwrap_or_default();
+    let mut stats = cache::stats::get_stream_stats(org_id, &stream_name, stream_type);
+    if min_ts > 0 && (stats.doc_time_min == 0 || min_ts < stats.doc_time_min) {
+        stats.doc_time_min = min_ts;
+        cache::stats::set_stream_stats(org_id, &stream_name, stream_type, stats);
+    }
+    log::info!(
+        "[COMPACT] restored archive {id} into {org_id}/{stream_type}/{stream_name}, files: {}",
+        events.len()
+    );
+
+    Ok(ArchiveRestore {
+        stream_name,
+        files: files.len(),
+        records: archive.records,
+        legal_hold_id: hold.id,
+    })
+}
+
+/// The archived files to copy into the stream with their keys there, those
+/// restored by a previous attempt are left out.
+fn to_restore<'a>(
+    org_id: &str,
+    archive: &StreamArchive,
+    stream_name: &str,
+    files: &'a [FileKey],
+    restored: &HashSet<String>,
+) -> Vec<(&'a FileKey, String)> {
+    let stream_type = archive.stream_type;
+    let from = format!("files/{org_id}/{stream_type}/{}/", archive.stream_name);
+    let to = format!("files/{org_id}/{stream_type}/{stream_name}/");
+    files
+        .iter()
+        .map(|file| (file, file.key.replacen(&from, &to, 1)))
+        .filter(|(_, key)| !restored.contains(key))
+        .collect()
+}
+
+/// The legal hold keeping the restored range from the retention.
+fn restore_hold(archive: &StreamArchive, stream_name: &str) -> LegalHold {
+    LegalHold {
+        stream_type: archive.stream_type,
+        stream_name: stream_name.to_string(),
+        start_time: archive.start_time,
+        end_time: archive.end_time,
+        reason: format!("restored from archive {}", archive.id),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    fn file(stream_name: &str, name: &str, min_ts: i64, max_ts: i64) -> FileKey {
+        let meta = FileMeta {
+            min_ts,
+            max_ts,
+            records: 10,
+            ..Default::default()
+        };
+        FileKey::new(
+            &format!("files/default/logs/{stream_name}/2024/01/01/00/{name}.parquet"),
+            meta,
+            false,
+        )
+    }
+
+    fn archive() -> StreamArchive {
+        StreamArchive {
+            id: archive_id("default", StreamType::Logs, "app", (1000, 5000)),
+            stream_type: StreamType::Logs,
+            stream_name: "app".to_string(),
+            start_time: 1000,
+            end_time: 5000,
+            files: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_archive_retry() {
+        // a retried archive of the same range extends the first one
+        let id = archive_id("default", StreamType::Logs, "app", (1000, 5000));
+        assert_eq!(id, archive().id);
+        assert_ne!(
+            id,
+            archive_id("default", StreamType::Logs, "app", (1000, 6000))
+        );
+        assert_ne!(
+            id,
+            archive_id("default", StreamType::Logs, "web", (1000, 5000))
+        );
+
+        // only the files the failed attempt didn't copy are copied
+        let manifest = vec![file("app", "a", 1000, 2000)];
+        let files = vec![file("app", "a", 1000, 2000), file("app", "b", 2000, 4999)];
+        let pending = unarchived(files.clone(), &manifest);
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].key.ends_with("/b.parquet"));
+        assert!(unarchived(files.clone(), &files).is_empty());
+    }
+
+    #[test]
+    fn test_restore() {
+        let archive = archive();
+        let files = vec![file("app", "a", 1000, 2000), file("app", "b", 2000, 4999)];
+
+        let plan = to_restore("default", &archive, "app", &files, &HashSet::new());
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].1, files[0].key);
+
+        // into another stream, skipping what a previous attempt restored
+        let restored =
+            HashSet::from(["files/default/logs/app_restored/2024/01/01/00/a.parquet".to_string()]);
+        let plan = to_restore("default", &archive, "app_restored", &files, &restored);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(
+            plan[0].1,
+            "files/default/logs/app_restored/2024/01/01/00/b.parquet"
+        );
+    }
+
+    #[test]
+    fn test_restore_is_held_from_retention() {
+        let archive = archive();
+        let hold = restore_hold(&archive, "app_restored");
+        assert_eq!(hold.stream_name, "app_restored");
+        assert_eq!(hold.reason, format!("restored from archive {}", archive.id));
+        // the retention skips the files the hold covers
+        for f in [file("app", "a", 1000, 2000), file("app", "b", 2000, 4999)] {
+            assert!(hold.covers(f.meta.min_ts, f.meta.max_ts));
+        }
+        // the files expired after the range are deleted as usual
+        assert!(!hold.covers(5000, 6000));
+        assert!(!hold.covers(0, 999));
+    }
+}
@@ -163,7 +163,13 @@ pub async fn run_merge(offset: i64) -> Result<(), anyhow::Error> {
 
     // write new sync offset
     offset = offset_time_hour + Duration::try_hours(1).unwrap().num_microseconds().unwrap();
-    db::compact::file_list::set_offset(offset).await
+    db::compact::file_list::set_offset(offset).await?;
+
+    // the day is done, roll up its file_list into the daily manifest
+    if offset_time.hour() == 23 {
+        write_manifest(&offset_time.format("%Y/%m/%d").to_string()).await;
+    }
+    Ok(())
 }
 
 pub async fn run_delete() -> Result<(), anyhow::Error> {
@@ -174,11 +180,14 @@ pub async fn run_delete() -> Result<(), anyhow::Error> {
 
     for day in days {
         let mut t = DateTime::parse_from_rfc3339(&format!("{day}T00:00:00Z"))?.with_timezone(&Utc);
+        let manifest_day = t.format("%Y/%m/%d").to_string();
         for _hour in 0..24 {
             let offset = t.timestamp_micros();
             merge_file_list(offset).await?;
             t += Duration::try_hours(1).unwrap();
         }
+        // the merge replaced the file_list of the day, rebuild its manifest
+        write_manifest(&manifest_day).await;
 
         // delete day
         db::compact::file_list::del_delete(&day).await?;
@@ -187,6 +196,15 @@ pub async fn run_delete() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+async fn write_manifest(day: &str) {
+    if !CONFIG.compact.file_list_manifest_enabled {
+        return;
+    }
+    if let Err(e) = db::file_list::manifest::write(day).await {
+        log::error!("[COMPACT] file_list write manifest of {day} failed: {e}");
+    }
+}
+
 /// merge and delete the small file list keys in this hour from etcd
 /// upload new file list into storage
 async fn merge_file_list(offset: i64) -> Result<(), anyhow::Error> {
@@ -0,0 +1,120 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Utc;
+use config::{
+    ider,
+    meta::stream::{PartitionTimeLevel, StreamType},
+    utils::time::BASE_TIME,
+};
+
+use crate::{
+    common::meta::stream::{LegalHold, LegalHoldFiles},
+    service::{
+        db,
+        error::{Result, ServiceError},
+        file_list,
+    },
+};
+
+/// Places a legal hold on a time range of a stream, the files of the range are
+/// kept until the hold is released.
+pub async fn create(org_id: &str, mut hold: LegalHold, user_email: &str) -> Result<LegalHold> {
+    if hold.stream_name.is_empty() {
+        return Err(ServiceError::bad_request("stream_name is required"));
+    }
+    let now = Utc::now().timestamp_micros();
+    if hold.start_time == 0 {
+        hold.start_time = BASE_TIME.timestamp_micros();
+    }
+    if hold.end_time == 0 {
+        hold.end_time = i64::MAX;
+    }
+    if hold.start_time >= hold.end_time {
+        return Err(ServiceError::bad_request(
+            "start_time should be less than end_time",
+        ));
+    }
+    let schema = infra::schema::get(org_id, &hold.stream_name, hold.stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(ServiceError::not_found(format!(
+            "stream {} not found",
+            hold.stream_name
+        )));
+    }
+    hold.id = ider::generate();
+    hold.created_by = user_email.to_string();
+    hold.created_at = now;
+    db::compact::legal_holds::set(org_id, &hold).await?;
+    Ok(hold)
+}
+
+/// Releases the hold, its files are deleted again by the retention and the
+/// compactor.
+pub async fn release(org_id: &str, id: &str) -> Result<()> {
+    let hold = get(org_id, id)?;
+    db::compact::legal_holds::delete(org_id, &hold).await?;
+    Ok(())
+}
+
+pub fn get(org_id: &str, id: &str) -> Result<LegalHold> {
+    db::compact::legal_holds::list(org_id)
+        .into_iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| ServiceError::not_found(format!("legal hold {id} not found")))
+}
+
+pub fn list(org_id: &str) -> Vec<LegalHold> {
+    db::compact::legal_holds::list(org_id)
+}
+
+/// Returns the files pinned by the hold.
+pub async fn files(org_id: &str, id: &str) -> Result<LegalHoldFiles> {
+    let hold = get(org_id, id)?;
+    let files = file_list::query(
+        org_id,
+        &hold.stream_name,
+        hold.stream_type,
+        PartitionTimeLevel::Unset,
+        hold.start_time,
+        hold.end_time.min(Utc::now().timestamp_micros()),
+        false,
+    )
+    .await?;
+    let files = files
+        .into_iter()
+        .filter(|f| hold.covers(f.meta.min_ts, f.meta.max_ts))
+        .collect::<Vec<_>>();
+    Ok(LegalHoldFiles {
+        records: files.iter().map(|f| f.meta.records).sum(),
+        compressed_size: files.iter().map(|f| f.meta.compressed_size).sum(),
+        files: files.into_iter().map(|f| f.key).collect(),
+        hold,
+    })
+}
+
+/// Whether a legal hold of the stream covers records between the two times,
+/// inclusive.
+pub fn is_held(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    min_ts: i64,
+    max_ts: i64,
+) -> bool {
+    db::compact::legal_holds::list_by_stream(org_id, stream_type, stream_name)
+        .iter()
+        .any(|h| h.covers(min_ts, max_ts))
+}
@@ -15,7 +15,7 @@
 
 use std::{collections::HashMap, io::Write, sync::Arc};
 
-use ::datafusion::{arrow::datatypes::Schema, common::FileType, error::DataFusionError};
+use ::datafusion::{common::FileType, error::DataFusionError};
 use bytes::Bytes;
 use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
 use config::{
@@ -23,20 +23,32 @@ use config::{
     ider,
     meta::stream::{FileKey, FileMeta, PartitionTimeLevel, StreamStats, StreamType},
     metrics,
-    utils::{json, parquet::parse_file_key_columns},
-    CONFIG, FILE_EXT_PARQUET,
+    utils::{
+        json,
+        parquet::{generate_filename_with_schema_version, parse_file_key_columns},
+    },
+    CONFIG,
 };
 use infra::{
     cache, dist_lock, file_list as infra_file_list,
     schema::{unwrap_partition_time_level, unwrap_stream_settings},
     storage,
 };
-use tokio::{sync::Semaphore, task::JoinHandle};
+use tokio::{
+    sync::{oneshot, Semaphore},
+    task::JoinHandle,
+};
 
 use crate::{
-    common::infra::cluster::get_node_by_uuid,
+    common::{infra::cluster::get_node_by_uuid, meta::stream::Tombstone},
     job::files::parquet::generate_index_on_compactor,
-    service::{db, file_list, search::datafusion, stream},
+    service::{
+        compact::{legal_holds, tombstones},
+        db::{self, compact::jobs::JobStatus},
+        file_list,
+        search::datafusion,
+        stream,
+    },
 };
 
 /// compactor run steps on a stream:
@@ -94,13 +106,11 @@ pub async fn merge_by_stream(
     }
 
     // get schema
-    let mut schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
     let stream_settings = unwrap_stream_settings(&schema).unwrap_or_default();
     let partition_time_level =
         unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
     let stream_created = stream::stream_created(&schema).unwrap_or_default();
-    std::mem::take(&mut schema.metadata);
-    let schema = Arc::new(schema);
     if offset == 0 {
         offset = stream_created
     }
@@ -191,6 +201,140 @@ pub async fn merge_by_stream(
                 offset_time_hour + Duration::try_hours(1).unwrap().num_microseconds().unwrap() - 1,
             )
         };
+    // the periods ahead of a stream far behind are shared with all compactors
+    if let Err(e) = publish_jobs(
+        org_id,
+        stream_type,
+        stream_name,
+        partition_offset_start,
+        partition_offset_end + 1 - partition_offset_start,
+        time_now_hour,
+    )
+    .await
+    {
+        log::error!(
+            "[COMPACTOR] publish jobs [{}/{}/{}] error: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            e
+        );
+    }
+    let job =
+        db::compact::jobs::get(org_id, stream_type, stream_name, partition_offset_start).await;
+    let lease = if matches!(job, Some(JobStatus::Pending | JobStatus::Running { .. })) {
+        if !claim_job(org_id, stream_type, stream_name, partition_offset_start).await? {
+            return Ok(()); // another compactor is merging this period
+        }
+        Some(JobLease::keep(
+            org_id,
+            stream_type,
+            stream_name,
+            partition_offset_start,
+        ))
+    } else {
+        None
+    };
+    let stream_stats = if job == Some(JobStatus::Done) {
+        StreamStats::default() // merged by another compactor
+    } else {
+        let ret = merge_by_period(
+            org_id,
+            stream_type,
+            stream_name,
+            partition_time_level,
+            (partition_offset_start, partition_offset_end),
+            true,
+            false,
+            None,
+        )
+        .await;
+        if let Some(lease) = lease {
+            lease.stop().await;
+        }
+        ret?
+    };
+
+    // write new offset
+    let offset = offset
+        + Duration::try_seconds(CONFIG.compact.step_secs)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+    db::compact::files::set_offset(
+        org_id,
+        stream_type,
+        stream_name,
+        offset,
+        Some(&LOCAL_NODE_UUID.clone()),
+    )
+    .await?;
+    if job.is_some() {
+        db::compact::jobs::delete(org_id, stream_type, stream_name, partition_offset_start).await?;
+    }
+
+    // rewrite the files of the deleted records which the compactor passed
+    if let Err(e) = apply_tombstones(
+        org_id,
+        stream_type,
+        stream_name,
+        partition_time_level,
+        partition_offset_end + 1 - partition_offset_start,
+        offset,
+    )
+    .await
+    {
+        log::error!(
+            "[COMPACTOR] apply tombstones [{}/{}/{}] error: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            e
+        );
+    }
+
+    // update stream stats
+    if stream_stats.doc_num != 0 {
+        infra_file_list::set_stream_stats(
+            org_id,
+            &[(
+                format!("{org_id}/{stream_type}/{stream_name}"),
+                stream_stats,
+            )],
+        )
+        .await?;
+    }
+
+    // metrics
+    let time = start.elapsed().as_secs_f64();
+    metrics::COMPACT_USED_TIME
+        .with_label_values(&[org_id, stream_type.to_string().as_str()])
+        .inc_by(time);
+    metrics::COMPACT_DELAY_HOURS
+        .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
+        .set(
+            (time_now_hour - offset_time_hour)
+                / Duration::try_hours(1).unwrap().num_microseconds().unwrap(),
+        );
+
+    Ok(())
+}
+
+/// merge the files of one hour(day) of a stream, returns the stats of the
+/// merged files. With `rewrite`, the single files are rewritten too. With a
+/// tombstone, every file holding records in its time range is rewritten
+/// without the deleted records.
+#[allow(clippy::too_many_arguments)]
+async fn merge_by_period(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    partition_time_level: PartitionTimeLevel,
+    (partition_offset_start, partition_offset_end): (i64, i64),
+    with_lookback: bool,
+    rewrite: bool,
+    tombstone: Option<&Tombstone>,
+) -> Result<StreamStats, anyhow::Error> {
     let mut files = file_list::query(
         org_id,
         stream_name,
@@ -204,7 +348,7 @@ pub async fn merge_by_stream(
     .map_err(|e| anyhow::anyhow!("query file list failed: {}", e))?;
 
     // check lookback files
-    if CONFIG.compact.lookback_hours > 0 {
+    if with_lookback && CONFIG.compact.lookback_hours > 0 {
         let lookback_offset = Duration::try_hours(CONFIG.compact.lookback_hours)
             .unwrap()
             .num_microseconds()
@@ -229,6 +373,24 @@ pub async fn merge_by_stream(
         files.extend(lookback_files);
     }
 
+    if let Some(tombstone) = tombstone {
+        files.retain(|f| {
+            f.meta.max_ts >= tombstone.start_time && f.meta.min_ts < tombstone.end_time
+        });
+    }
+    // the files under a legal hold are kept as they are
+    files.retain(|f| {
+        !legal_holds::is_held(
+            org_id,
+            stream_type,
+            stream_name,
+            f.meta.min_ts,
+            f.meta.max_ts,
+        )
+    });
+    let delete_filter = tombstone.map(tombstones::delete_condition);
+    let rewrite = rewrite || tombstone.is_some();
+
     log::debug!(
         "[COMPACTOR] merge_by_stream [{}/{}/{}] time range: [{},{}], files: {}",
         org_id,
@@ -240,25 +402,7 @@ pub async fn merge_by_stream(
     );
 
     if files.is_empty() {
-        // this hour is no data, and check if pass allowed_upto, then just write new
-        // offset if offset > 0 && offset_time_hour +
-        // Duration::try_hours(CONFIG.limit.allowed_upto).unwrap().num_microseconds().unwrap() <
-        // time_now_hour { -- no check it
-        // }
-        let offset = offset
-            + Duration::try_seconds(CONFIG.compact.step_secs)
-                .unwrap()
-                .num_microseconds()
-                .unwrap();
-        db::compact::files::set_offset(
-            org_id,
-            stream_type,
-            stream_name,
-            offset,
-            Some(&LOCAL_NODE_UUID.clone()),
-        )
-        .await?;
-        return Ok(());
+        return Ok(StreamStats::default());
     }
 
     // do partition by partition key
@@ -279,9 +423,11 @@ pub async fn merge_by_stream(
     for (prefix, files_with_size) in partition_files_with_size.into_iter() {
         let org_id = org_id.to_string();
         let stream_name = stream_name.to_string();
-        let schema = schema.clone();
+        let delete_filter = delete_filter.clone();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let task: JoinHandle<Result<(), anyhow::Error>> = tokio::task::spawn(async move {
+        let task: JoinHandle<anyhow::Result<Vec<FileKey>>> = tokio::task::spawn(async move {
+            // files removed from the file list, their stats are taken out of the stream
+            let mut removed_files = Vec::new();
             // sort by file size
             let mut files_with_size = files_with_size.to_owned();
             files_with_size.sort_by(|a, b| a.meta.original_size.cmp(&b.meta.original_size));
@@ -295,9 +441,11 @@ pub async fn merge_by_stream(
                     &org_id,
                     stream_type,
                     &stream_name,
-                    schema.clone(),
                     &prefix,
                     &files_with_size,
+                    rewrite,
+                    delete_filter.as_deref(),
+                    &mut removed_files,
                 )
                 .await
                 {
@@ -327,7 +475,6 @@ pub async fn merge_by_stream(
                     deleted: false,
                 });
                 for file in new_file_list.iter() {
-                    stream_stats = stream_stats - file.meta.clone();
                     events.push(FileKey {
                         key: file.key.clone(),
                         meta: FileMeta::default(),
@@ -350,70 +497,476 @@ pub async fn merge_by_stream(
 
                 // delete files from file list
                 files_with_size.retain(|f| !&new_file_list.contains(f));
+                removed_files.extend(new_file_list);
             }
             drop(permit);
-            Ok(())
+            Ok(removed_files)
         });
         tasks.push(task);
     }
 
     for task in tasks {
-        task.await??;
+        for file in task.await?? {
+            stream_stats = stream_stats - file.meta;
+        }
     }
 
-    // write new offset
-    let offset = offset
-        + Duration::try_seconds(CONFIG.compact.step_secs)
+    Ok(stream_stats)
+}
+
+/// Publishes the periods following the current one as jobs when the stream is
+/// far behind, so that every compactor node can merge a part of the backlog.
+async fn publish_jobs(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period_start: i64,
+    period_len: i64,
+    time_now_hour: i64,
+) -> Result<(), anyhow::Error> {
+    let hour = Duration::try_hours(1).unwrap().num_microseconds().unwrap();
+    if CONFIG.compact.shard_backlog_hours <= 0
+        || time_now_hour - period_start < CONFIG.compact.shard_backlog_hours * hour
+    {
+        return Ok(());
+    }
+    // keep away from the periods which may still receive files
+    let horizon = time_now_hour
+        - hour
+        - Duration::try_seconds(CONFIG.limit.max_file_retention_time as i64)
             .unwrap()
             .num_microseconds()
-            .unwrap();
-    db::compact::files::set_offset(
-        org_id,
-        stream_type,
-        stream_name,
-        offset,
-        Some(&LOCAL_NODE_UUID.clone()),
-    )
-    .await?;
+            .unwrap()
+            * 3;
+    let mut period = period_start + period_len;
+    for _ in 0..CONFIG.compact.shard_max_jobs {
+        if period + period_len > horizon {
+            break;
+        }
+        if db::compact::jobs::get(org_id, stream_type, stream_name, period)
+            .await
+            .is_none()
+        {
+            db::compact::jobs::set(
+                org_id,
+                stream_type,
+                stream_name,
+                period,
+                &JobStatus::Pending,
+            )
+            .await?;
+        }
+        period += period_len;
+    }
+    Ok(())
+}
 
-    // update stream stats
-    if stream_stats.doc_num != 0 {
-        infra_file_list::set_stream_stats(
+async fn is_claimable(status: &JobStatus) -> bool {
+    match status {
+        JobStatus::Pending => true,
+        JobStatus::Running { node, expires_at } => {
+            *expires_at < Utc::now().timestamp_micros() || get_node_by_uuid(node).await.is_none()
+        }
+        JobStatus::Done => false,
+    }
+}
+
+/// Takes the lease of a published period, returns false when another node
+/// holds it or the job is gone.
+async fn claim_job(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period: i64,
+) -> Result<bool, anyhow::Error> {
+    let lock_key = format!("/compact/jobs/{org_id}/{stream_type}/{stream_name}/{period}");
+    let locker = dist_lock::lock(&lock_key, 0).await?;
+    // check the job again under the lock, maybe other node claimed it first
+    let claimable = match db::compact::jobs::get(org_id, stream_type, stream_name, period).await {
+        Some(status) => is_claimable(&status).await,
+        None => false,
+    };
+    let ret = if claimable {
+        db::compact::jobs::set(
             org_id,
-            &[(
-                format!("{org_id}/{stream_type}/{stream_name}"),
-                stream_stats,
-            )],
+            stream_type,
+            stream_name,
+            period,
+            &JobStatus::Running {
+                node: LOCAL_NODE_UUID.clone(),
+                expires_at: lease_expires_at(),
+            },
         )
-        .await?;
+        .await
+    } else {
+        Ok(())
+    };
+    dist_lock::unlock(&locker).await?;
+    drop(locker);
+    ret?;
+    Ok(claimable)
+}
+
+fn lease_expires_at() -> i64 {
+    Utc::now().timestamp_micros() + CONFIG.compact.job_lease_secs * 1_000_000
+}
+
+/// Extends the lease of a claimed period while it is merged, so a merge
+/// running longer than the lease isn't taken over by another compactor.
+struct JobLease {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl JobLease {
+    fn keep(org_id: &str, stream_type: StreamType, stream_name: &str, period: i64) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let (org_id, stream_name) = (org_id.to_string(), stream_name.to_string());
+        let interval =
+            std::time::Duration::from_secs((CONFIG.compact.job_lease_secs / 3).max(1) as u64);
+        let handle = tokio::task::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+                match renew_job(&org_id, stream_type, &stream_name, period).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log::warn!(
+                            "[COMPACTOR] lease of job [{org_id}/{stream_type}/{stream_name}] period {period} was lost"
+                        );
+                        break;
+                    }
+                    Err(e) => log::error!(
+                        "[COMPACTOR] renew lease of job [{org_id}/{stream_type}/{stream_name}] period {period} error: {e}"
+                    ),
+                }
+            }
+        });
+        Self { stop, handle }
     }
 
-    // metrics
-    let time = start.elapsed().as_secs_f64();
-    metrics::COMPACT_USED_TIME
-        .with_label_values(&[org_id, stream_type.to_string().as_str()])
-        .inc_by(time);
-    metrics::COMPACT_DELAY_HOURS
-        .with_label_values(&[org_id, stream_name, stream_type.to_string().as_str()])
-        .set(
-            (time_now_hour - offset_time_hour)
-                / Duration::try_hours(1).unwrap().num_microseconds().unwrap(),
-        );
+    /// Stops renewing, the job can be updated once it returns.
+    async fn stop(self) {
+        _ = self.stop.send(());
+        _ = self.handle.await;
+    }
+}
+
+/// Extends the lease if this node still holds it.
+async fn renew_job(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period: i64,
+) -> Result<bool, anyhow::Error> {
+    let lock_key = format!("/compact/jobs/{org_id}/{stream_type}/{stream_name}/{period}");
+    let locker = dist_lock::lock(&lock_key, 0).await?;
+    let held = matches!(
+        db::compact::jobs::get(org_id, stream_type, stream_name, period).await,
+        Some(JobStatus::Running { node, .. }) if LOCAL_NODE_UUID.eq(&node)
+    );
+    let ret = if held {
+        db::compact::jobs::set(
+            org_id,
+            stream_type,
+            stream_name,
+            period,
+            &JobStatus::Running {
+                node: LOCAL_NODE_UUID.clone(),
+                expires_at: lease_expires_at(),
+            },
+        )
+        .await
+    } else {
+        Ok(())
+    };
+    dist_lock::unlock(&locker).await?;
+    ret?;
+    Ok(held)
+}
 
+/// Merges the periods published by other compactors, one job at a time so the
+/// remaining ones are left to the other nodes.
+pub async fn merge_jobs() -> Result<(), anyhow::Error> {
+    let jobs = db::compact::jobs::list().await?;
+    for job in jobs {
+        if !is_claimable(&job.status).await
+            || !claim_job(&job.org_id, job.stream_type, &job.stream_name, job.period).await?
+        {
+            continue;
+        }
+        let org_id = job.org_id.as_str();
+        let stream_name = job.stream_name.as_str();
+        let schema = infra::schema::get(org_id, stream_name, job.stream_type).await?;
+        if schema.fields().is_empty() {
+            // the stream was deleted
+            db::compact::jobs::delete(org_id, job.stream_type, stream_name, job.period).await?;
+            continue;
+        }
+        let stream_settings = unwrap_stream_settings(&schema).unwrap_or_default();
+        let partition_time_level =
+            unwrap_partition_time_level(stream_settings.partition_time_level, job.stream_type);
+        let period_len = period_len(partition_time_level);
+        let lease = JobLease::keep(org_id, job.stream_type, stream_name, job.period);
+        let ret = merge_by_period(
+            org_id,
+            job.stream_type,
+            stream_name,
+            partition_time_level,
+            (job.period, job.period + period_len - 1),
+            false,
+            false,
+            None,
+        )
+        .await;
+        lease.stop().await;
+        let status = match ret {
+            Ok(stream_stats) => {
+                if stream_stats.doc_num != 0 {
+                    infra_file_list::set_stream_stats(
+                        org_id,
+                        &[(
+                            format!("{org_id}/{}/{stream_name}", job.stream_type),
+                            stream_stats,
+                        )],
+                    )
+                    .await?;
+                }
+                JobStatus::Done
+            }
+            Err(e) => {
+                log::error!(
+                    "[COMPACTOR] merge job [{}/{}/{}] period {} error: {}",
+                    org_id,
+                    job.stream_type,
+                    stream_name,
+                    job.period,
+                    e
+                );
+                JobStatus::Pending // let any compactor retry it
+            }
+        };
+        db::compact::jobs::set(org_id, job.stream_type, stream_name, job.period, &status).await?;
+    }
     Ok(())
 }
 
+/// Rewrites the periods of the tombstones which the compactor has passed,
+/// a tombstone is removed once all its periods are rewritten.
+async fn apply_tombstones(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    partition_time_level: PartitionTimeLevel,
+    period_len: i64,
+    offset: i64,
+) -> Result<(), anyhow::Error> {
+    // the periods in the lookback window can still be merged with new files
+    let merged_until = offset
+        - Duration::try_hours(CONFIG.compact.lookback_hours)
+            .unwrap()
+            .num_microseconds()
+            .unwrap();
+    for mut tombstone in db::compact::tombstones::list_by_stream(org_id, stream_type, stream_name) {
+        let mut period = tombstone.start_time.max(tombstone.done_until);
+        period -= period % period_len;
+        while period < tombstone.end_time && period + period_len <= merged_until {
+            let stream_stats = merge_by_period(
+                org_id,
+                stream_type,
+                stream_name,
+                partition_time_level,
+                (period, period + period_len - 1),
+                false,
+                true,
+                Some(&tombstone),
+            )
+            .await?;
+            if stream_stats.doc_num != 0 {
+                infra_file_list::set_stream_stats(
+                    org_id,
+                    &[(
+                        format!("{org_id}/{stream_type}/{stream_name}"),
+                        stream_stats,
+                    )],
+                )
+                .await?;
+            }
+            period += period_len;
+            tombstone.done_until = period;
+            db::compact::tombstones::set(org_id, stream_type, stream_name, &tombstone).await?;
+        }
+        if tombstone.done_until >= tombstone.end_time {
+            log::info!(
+                "[COMPACTOR] tombstone [{}/{}/{}] {} applied",
+                org_id,
+                stream_type,
+                stream_name,
+                tombstone.id
+            );
+            db::compact::tombstones::delete(org_id, stream_type, stream_name, &tombstone.id)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the historical files of the streams merged by this node with the
+/// current settings, a few periods per run so that the regular compaction
+/// keeps going.
+pub async fn rewrite_jobs() -> Result<(), anyhow::Error> {
+    for (org_id, stream_type, stream_name, mut job) in db::compact::rewrite::list().await? {
+        let Some((offset, node)) =
+            db::compact::files::get_offset_from_cache(&org_id, stream_type, &stream_name).await
+        else {
+            continue;
+        };
+        if LOCAL_NODE_UUID.ne(&node) {
+            continue; // another compactor merges this stream
+        }
+        let schema = infra::schema::get(&org_id, &stream_name, stream_type).await?;
+        if schema.fields().is_empty() {
+            // the stream was deleted
+            db::compact::rewrite::delete(&org_id, stream_type, &stream_name).await?;
+            continue;
+        }
+        let stream_settings = unwrap_stream_settings(&schema).unwrap_or_default();
+        let partition_time_level =
+            unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+        let period_len = period_len(partition_time_level);
+        // the periods in the lookback window can still be merged with new files
+        let merged_until = offset
+            - Duration::try_hours(CONFIG.compact.lookback_hours)
+                .unwrap()
+                .num_microseconds()
+                .unwrap();
+        let periods = rewrite_periods(
+            (job.start_time, job.end_time),
+            job.done_until,
+            period_len,
+            merged_until,
+            CONFIG.compact.rewrite_max_periods as usize,
+        );
+        for &period in periods.iter() {
+            let mut stats = Vec::with_capacity(2);
+            let mut merge = !job.repartition;
+            if job.repartition {
+                let (files, stream_stats) = super::repartition::repartition_by_period(
+                    &org_id,
+                    stream_type,
+                    &stream_name,
+                    partition_time_level,
+                    &stream_settings,
+                    &job.changed_keys,
+                    (period, period + period_len - 1),
+                )
+                .await?;
+                job.repartitioned_files += files;
+                stats.push(stream_stats);
+                merge = files > 0;
+            }
+            // the files split by the repartition are merged in their new partitions
+            if merge {
+                stats.push(
+                    merge_by_period(
+                        &org_id,
+                        stream_type,
+                        &stream_name,
+                        partition_time_level,
+                        (period, period + period_len - 1),
+                        false,
+                        !job.repartition,
+                        None,
+                    )
+                    .await?,
+                );
+            }
+            for stream_stats in stats {
+                if stream_stats.doc_num != 0 {
+                    infra_file_list::set_stream_stats(
+                        &org_id,
+                        &[(
+                            format!("{org_id}/{stream_type}/{stream_name}"),
+                            stream_stats,
+                        )],
+                    )
+                    .await?;
+                }
+            }
+            job.done_until = period + period_len;
+            job.progress = rewrite_progress((job.start_time, job.end_time), job.done_until);
+        }
+        if job.done_until >= job.end_time {
+            log::info!(
+                "[COMPACTOR] rewrite [{}/{}/{}] finished",
+                org_id,
+                stream_type,
+                stream_name
+            );
+            db::compact::rewrite::delete(&org_id, stream_type, &stream_name).await?;
+            if job.repartition_pending {
+                super::rewrite::repartition(&org_id, stream_type, &stream_name, &job.changed_keys)
+                    .await?;
+            }
+        } else if !periods.is_empty() {
+            db::compact::rewrite::set(&org_id, stream_type, &stream_name, &job).await?;
+        }
+    }
+    Ok(())
+}
+
+/// The periods of the time range rewritten by one run, from the one holding
+/// `done_until`. The periods in the lookback window, ending after
+/// `merged_until`, are left for a later run.
+fn rewrite_periods(
+    (start_time, end_time): (i64, i64),
+    done_until: i64,
+    period_len: i64,
+    merged_until: i64,
+    max_periods: usize,
+) -> Vec<i64> {
+    let mut period = start_time.max(done_until);
+    period -= period % period_len;
+    let mut periods = Vec::new();
+    while period < end_time && period + period_len <= merged_until && periods.len() < max_periods {
+        periods.push(period);
+        period += period_len;
+    }
+    periods
+}
+
+/// Percentage of the time range rewritten
+fn rewrite_progress((start_time, end_time): (i64, i64), done_until: i64) -> f64 {
+    ((done_until.min(end_time) - start_time).max(0) as f64 / (end_time - start_time) as f64) * 100.0
+}
+
+/// Returns the length of a partition period in microseconds
+fn period_len(partition_time_level: PartitionTimeLevel) -> i64 {
+    if partition_time_level == PartitionTimeLevel::Daily {
+        Duration::try_hours(24).unwrap().num_microseconds().unwrap()
+    } else {
+        Duration::try_hours(1).unwrap().num_microseconds().unwrap()
+    }
+}
+
 /// merge some small files into one big file, upload to storage, returns the big
-/// file key and merged files
+/// file key and merged files. Files whose records are all deleted are removed
+/// from the file list and added to `removed_files`
 async fn merge_files(
     org_id: &str,
     stream_type: StreamType,
     stream_name: &str,
-    schema: Arc<Schema>,
     prefix: &str,
     files_with_size: &[FileKey],
+    rewrite: bool,
+    delete_filter: Option<&str>,
+    removed_files: &mut Vec<FileKey>,
 ) -> Result<(String, FileMeta, Vec<FileKey>), anyhow::Error> {
-    if files_with_size.len() <= 1 {
+    // a single file is only rewritten on demand
+    let min_files = if rewrite { 1 } else { 2 };
+    if files_with_size.len() < min_files {
         return Ok((String::from(""), FileMeta::default(), Vec::new()));
     }
 
@@ -421,7 +974,9 @@ async fn merge_files(
     let mut new_file_list = Vec::new();
     let mut deleted_files = Vec::new();
     for file in files_with_size.iter() {
-        if new_file_size + file.meta.original_size > CONFIG.compact.max_file_size as i64 {
+        if !new_file_list.is_empty()
+            && new_file_size + file.meta.original_size > CONFIG.compact.max_file_size as i64
+        {
             break;
         }
         new_file_size += file.meta.original_size;
@@ -435,7 +990,7 @@ async fn merge_files(
             .inc_by(file.meta.original_size as u64);
     }
     // no files need to merge
-    if new_file_list.len() <= 1 {
+    if new_file_list.len() < min_files {
         return Ok((String::from(""), FileMeta::default(), Vec::new()));
     }
 
@@ -468,7 +1023,7 @@ async fn merge_files(
     if !deleted_files.is_empty() {
         new_file_list.retain(|f| !deleted_files.contains(&f.key));
     }
-    if new_file_list.len() <= 1 {
+    if new_file_list.len() < min_files {
         return Ok((String::from(""), FileMeta::default(), retain_file_list));
     }
 
@@ -476,17 +1031,22 @@ async fn merge_files(
     let schema_versions = infra::schema::get_versions(org_id, stream_name, stream_type).await?;
     let schema_latest = schema_versions.last().unwrap();
     let schema_latest_id = schema_versions.len() - 1;
+    // the merged file is written against the latest schema, so that it is read
+    // without casts whichever versions the small files had
+    let schema = Arc::new(schema_latest.clone().with_metadata(HashMap::new()));
+    let schema_start_dt: i64 = schema_latest
+        .metadata()
+        .get("start_dt")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_default();
     let bloom_filter_fields =
         stream::get_stream_setting_bloom_filter_fields(schema_latest).unwrap();
     let full_text_search_fields = stream::get_stream_setting_fts_fields(schema_latest).unwrap();
+    let parquet_options = stream::get_stream_setting_parquet_options(schema_latest);
     if CONFIG.common.widening_schema_evolution && schema_versions.len() > 1 {
         for file in &new_file_list {
             // get the schema version of the file
-            let schema_ver_id = match db::schema::filter_schema_version_id(
-                &schema_versions,
-                file.meta.min_ts,
-                file.meta.max_ts,
-            ) {
+            let schema_ver_id = match db::schema::file_schema_version_id(&schema_versions, file) {
                 Some(id) => id,
                 None => {
                     log::error!(
@@ -542,6 +1102,7 @@ async fn merge_files(
                 Arc::new(schema),
                 &bloom_filter_fields,
                 &full_text_search_fields,
+                &parquet_options,
                 diff_fields,
                 FileType::PARQUET,
             )
@@ -566,8 +1127,10 @@ async fn merge_files(
         schema.clone(),
         &bloom_filter_fields,
         &full_text_search_fields,
+        &parquet_options,
         new_file_size,
         &mut fts_buf,
+        delete_filter,
     )
     .await
     .map_err(|e| {
@@ -581,6 +1144,24 @@ async fn merge_files(
         );
         DataFusionError::Plan(format!("merge_parquet_files err: {:?}", e))
     })?;
+    if new_file_meta.records == 0 && delete_filter.is_some() {
+        // all the records are deleted, just remove the files
+        let events = retain_file_list
+            .iter()
+            .map(|file| FileKey {
+                key: file.key.clone(),
+                meta: FileMeta::default(),
+                deleted: true,
+            })
+            .collect::<Vec<_>>();
+        write_file_list(org_id, &events).await?;
+        log::info!(
+            "[COMPACT] deleted all the records of {} files",
+            retain_file_list.len()
+        );
+        removed_files.extend(retain_file_list.iter().cloned());
+        return Ok((String::from(""), FileMeta::default(), retain_file_list));
+    }
     new_file_meta.original_size = new_file_size;
     new_file_meta.compressed_size = buf.len() as i64;
     if new_file_meta.records == 0 {
@@ -592,8 +1173,10 @@ async fn merge_files(
         ));
     }
 
-    let id = ider::generate();
-    let new_file_key = format!("{prefix}/{id}{}", FILE_EXT_PARQUET);
+    let new_file_key = format!(
+        "{prefix}/{}",
+        generate_filename_with_schema_version(schema_start_dt)
+    );
     log::info!(
         "[COMPACT] merge file succeeded, {} files into a new file: {}, original_size: {}, compressed_size: {}, took: {:?}",
         retain_file_list.len(),
@@ -656,14 +1239,49 @@ async fn merge_files(
     }
 }
 
-async fn write_file_list(org_id: &str, events: &[FileKey]) -> Result<(), anyhow::Error> {
+pub(crate) async fn write_file_list(org_id: &str, events: &[FileKey]) -> Result<(), anyhow::Error> {
     if events.is_empty() {
         return Ok(());
     }
     if CONFIG.common.meta_store_external {
-        write_file_list_db_only(org_id, events).await
+        write_file_list_db_only(org_id, events).await?;
     } else {
-        write_file_list_s3(org_id, events).await
+        write_file_list_s3(org_id, events).await?;
+    }
+    invalidate_query_cache(events).await;
+    Ok(())
+}
+
+/// The files of the time ranges changed, the cached search results over them
+/// may be stale
+pub(crate) async fn invalidate_query_cache(events: &[FileKey]) {
+    let mut ranges: HashMap<String, (i64, i64)> = HashMap::new();
+    for event in events.iter().filter(|v| v.meta.max_ts > 0) {
+        let Ok((stream_key, _date_key, _file_name)) = parse_file_key_columns(&event.key) else {
+            continue;
+        };
+        let range = ranges
+            .entry(stream_key)
+            .or_insert((event.meta.min_ts, event.meta.max_ts));
+        range.0 = range.0.min(event.meta.min_ts);
+        range.1 = range.1.max(event.meta.max_ts);
+    }
+    for (stream_key, (start_time, end_time)) in ranges {
+        let columns = stream_key.splitn(3, '/').collect::<Vec<_>>();
+        if columns.len() < 3 {
+            continue;
+        }
+        if let Err(e) = db::query_cache::invalidate(
+            columns[0],
+            StreamType::from(columns[1]),
+            columns[2],
+            start_time,
+            end_time + 1,
+        )
+        .await
+        {
+            log::error!("[COMPACT] invalidate query cache for {stream_key} failed: {e}");
+        }
     }
 }
 
@@ -797,6 +1415,43 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_rewrite_periods() {
+        let hour = period_len(PartitionTimeLevel::Hourly);
+        let range = (hour * 10 + 5, hour * 20);
+        // starts at the period holding the start of the range
+        assert_eq!(
+            rewrite_periods(range, 0, hour, hour * 100, 3),
+            vec![hour * 10, hour * 11, hour * 12]
+        );
+        // resumes where the previous run stopped
+        assert_eq!(
+            rewrite_periods(range, hour * 13, hour, hour * 100, 3),
+            vec![hour * 13, hour * 14, hour * 15]
+        );
+        // stops at the end of the range
+        assert_eq!(
+            rewrite_periods(range, hour * 18, hour, hour * 100, 3),
+            vec![hour * 18, hour * 19]
+        );
+        assert!(rewrite_periods(range, hour * 20, hour, hour * 100, 3).is_empty());
+        // the periods in the lookback window wait
+        assert_eq!(
+            rewrite_periods(range, 0, hour, hour * 12, 3),
+            vec![hour * 10, hour * 11]
+        );
+        assert!(rewrite_periods(range, 0, hour, hour * 10, 3).is_empty());
+        assert_eq!(period_len(PartitionTimeLevel::Daily), hour * 24);
+    }
+
+    #[test]
+    fn test_rewrite_progress() {
+        let range = (1000, 2000);
+        assert_eq!(rewrite_progress(range, 0), 0.0);
+        assert_eq!(rewrite_progress(range, 1500), 50.0);
+        assert_eq!(rewrite_progress(range, 3000), 100.0);
+    }
+
     #[tokio::test]
     async fn test_compact() {
         infra_db::create_table().await.unwrap();
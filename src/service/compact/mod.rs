@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use chrono::{Datelike, Duration, TimeZone, Timelike, Utc};
 use config::{
@@ -30,11 +30,17 @@ use crate::{
     service::db,
 };
 
+pub mod archive;
 mod file_list;
 pub mod file_list_deleted;
+pub mod legal_holds;
 mod merge;
+pub mod priority;
+mod repartition;
 pub mod retention;
+pub mod rewrite;
 pub mod stats;
+pub mod tombstones;
 
 pub(crate) static QUEUE_LOCKER: Lazy<Arc<Mutex<bool>>> =
     Lazy::new(|| Arc::new(Mutex::const_new(false)));
@@ -100,6 +106,10 @@ pub async fn run_retention() -> Result<(), anyhow::Error> {
                 for stream_name in streams {
                     let schema = infra::schema::get(&org_id, &stream_name, stream_type).await?;
                     let stream = super::stream::stream_res(&stream_name, stream_type, schema, None);
+                    // nothing is deleted until the retention lock expires
+                    if stream.settings.is_retention_locked(now.timestamp_micros()) {
+                        continue;
+                    }
                     let stream_data_retention_end = if stream.settings.data_retention > 0 {
                         let date =
                             now - Duration::try_days(stream.settings.data_retention).unwrap();
@@ -189,6 +199,13 @@ pub async fn run_merge() -> Result<(), anyhow::Error> {
         StreamType::Metadata,
         StreamType::Index,
     ];
+    // the compactor owning a stream being renamed moves its files instead
+    let renaming = db::compact::rename::list()
+        .await?
+        .into_iter()
+        .map(|(org_id, stream_type, stream_name, _)| (org_id, stream_type, stream_name))
+        .collect::<HashSet<_>>();
+    let mut streams = Vec::new();
     for org_id in orgs {
         // check backlist
         if !db::file_list::BLOCKED_ORGS.is_empty()
@@ -197,9 +214,8 @@ pub async fn run_merge() -> Result<(), anyhow::Error> {
             continue;
         }
         for stream_type in stream_types {
-            let streams = db::schema::list_streams_from_cache(&org_id, stream_type).await;
-            let mut tasks = Vec::with_capacity(streams.len());
-            for stream_name in streams {
+            let stream_names = db::schema::list_streams_from_cache(&org_id, stream_type).await;
+            for stream_name in stream_names {
                 let Some(node) =
                     get_node_from_consistent_hash(&stream_name, &Role::Compactor).await
                 else {
@@ -242,30 +258,62 @@ pub async fn run_merge() -> Result<(), anyhow::Error> {
                     );
                     continue;
                 }
+                if renaming.contains(&(org_id.clone(), stream_type, stream_name.clone())) {
+                    continue;
+                }
 
-                let org_id = org_id.clone();
-                let permit = semaphore.clone().acquire_owned().await.unwrap();
-                let task = tokio::task::spawn(async move {
-                    if let Err(e) = merge::merge_by_stream(&org_id, stream_type, &stream_name).await
-                    {
-                        log::error!(
-                            "[COMPACTOR] merge_by_stream [{}/{}/{}] error: {}",
-                            org_id,
-                            stream_type,
-                            stream_name,
-                            e
-                        );
-                    }
-                    drop(permit);
-                });
-                tasks.push(task);
-            }
-            for task in tasks {
-                task.await?;
+                streams.push((org_id.clone(), stream_type, stream_name));
             }
         }
     }
 
+    // the streams people query and with the most small files go first
+    let streams = match priority::sort_streams(streams.clone()).await {
+        Ok(items) => items
+            .into_iter()
+            .map(|v| (v.org_id, v.stream_type, v.stream_name))
+            .collect(),
+        Err(e) => {
+            log::error!("[COMPACTOR] sort streams by priority error: {}", e);
+            streams
+        }
+    };
+    let mut tasks = Vec::with_capacity(streams.len());
+    for (org_id, stream_type, stream_name) in streams {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let task = tokio::task::spawn(async move {
+            if let Err(e) = merge::merge_by_stream(&org_id, stream_type, &stream_name).await {
+                log::error!(
+                    "[COMPACTOR] merge_by_stream [{}/{}/{}] error: {}",
+                    org_id,
+                    stream_type,
+                    stream_name,
+                    e
+                );
+            }
+            drop(permit);
+        });
+        tasks.push(task);
+    }
+    for task in tasks {
+        task.await?;
+    }
+
+    // merge the periods published by the compactors of streams far behind
+    if let Err(e) = merge::merge_jobs().await {
+        log::error!("[COMPACTOR] merge jobs error: {}", e);
+    }
+
+    // rewrite the historical files a few periods at a time
+    if let Err(e) = merge::rewrite_jobs().await {
+        log::error!("[COMPACTOR] rewrite jobs error: {}", e);
+    }
+
+    // move the files of the streams being renamed
+    if let Err(e) = crate::service::stream::run_rename_jobs().await {
+        log::error!("[COMPACTOR] rename jobs error: {}", e);
+    }
+
     // after compact, compact file list from storage
     if !CONFIG.common.meta_store_external {
         let last_file_list_offset = db::compact::file_list::get_offset().await?;
@@ -0,0 +1,168 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::stream::{StreamStats, StreamType},
+    RwHashMap, CONFIG,
+};
+use hashbrown::HashMap;
+use infra::cache::stats;
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{infra::cluster::get_node_by_uuid, meta::stream::CompactPriority},
+    service::db,
+};
+
+/// Searches on this node per stream since the last sync, key:
+/// org_id/stream_type/stream_name
+static QUERY_COUNTS: Lazy<RwHashMap<String, u64>> = Lazy::new(Default::default);
+
+pub fn record_query(org_id: &str, stream_type: StreamType, stream_name: &str) {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    *QUERY_COUNTS.entry(key).or_default() += 1;
+}
+
+/// Saves the query counts of this node and halves them, so that the recent
+/// queries weigh more than the old ones.
+pub async fn sync_query_counts() -> Result<(), anyhow::Error> {
+    let counts = QUERY_COUNTS
+        .iter()
+        .map(|v| (v.key().clone(), *v.value()))
+        .collect::<HashMap<_, _>>();
+    db::compact::priority::set_query_counts(&LOCAL_NODE_UUID, &counts).await?;
+    QUERY_COUNTS.retain(|_, v| {
+        *v /= 2;
+        *v > 0
+    });
+    Ok(())
+}
+
+/// Returns the query counts of all the nodes, key: org_id/stream_type/stream_name
+async fn query_counts() -> Result<HashMap<String, u64>, anyhow::Error> {
+    let mut counts = HashMap::new();
+    for (node, node_counts) in db::compact::priority::list_query_counts().await? {
+        if get_node_by_uuid(&node).await.is_none() {
+            // the node left the cluster
+            db::compact::priority::delete_query_counts(&node).await?;
+            continue;
+        }
+        for (key, count) in node_counts {
+            *counts.entry(key).or_default() += count;
+        }
+    }
+    Ok(counts)
+}
+
+/// Share of the stream that is still made of small files, 0 when every file
+/// already has the size of a compacted file.
+fn small_file_ratio(stats: &StreamStats) -> f64 {
+    if stats.file_num <= 0 || CONFIG.compact.max_file_size == 0 {
+        return 0.0;
+    }
+    let avg_file_size = stats.storage_size / stats.file_num as f64;
+    1.0 - (avg_file_size / CONFIG.compact.max_file_size as f64).min(1.0)
+}
+
+/// Streams with small files come first, the ones people query the most
+/// before the others.
+fn score(query_count: u64, small_file_ratio: f64) -> f64 {
+    small_file_ratio * (1.0 + (query_count as f64).ln_1p())
+}
+
+/// Sorts the streams in the order they should be compacted. A manual priority
+/// always wins over the computed score, streams without one have priority 0.
+pub async fn sort_streams(
+    streams: Vec<(String, StreamType, String)>,
+) -> Result<Vec<CompactPriority>, anyhow::Error> {
+    let overrides = db::compact::priority::list_overrides().await?;
+    let counts = query_counts().await?;
+    let mut items = streams
+        .into_iter()
+        .map(|(org_id, stream_type, stream_name)| {
+            let key = format!("{org_id}/{stream_type}/{stream_name}");
+            let query_count = counts.get(&key).copied().unwrap_or_default();
+            let small_file_ratio =
+                small_file_ratio(&stats::get_stream_stats(&org_id, &stream_name, stream_type));
+            CompactPriority {
+                priority: overrides.get(&key).copied(),
+                score: score(query_count, small_file_ratio),
+                query_count,
+                small_file_ratio,
+                org_id,
+                stream_type,
+                stream_name,
+            }
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| {
+        b.priority
+            .unwrap_or_default()
+            .cmp(&a.priority.unwrap_or_default())
+            .then(b.score.total_cmp(&a.score))
+    });
+    Ok(items)
+}
+
+/// Returns the compaction queue of the streams of an organization.
+pub async fn list_by_org(org_id: &str) -> Result<Vec<CompactPriority>, anyhow::Error> {
+    let mut streams = Vec::new();
+    for stream_type in [
+        StreamType::Logs,
+        StreamType::Metrics,
+        StreamType::Traces,
+        StreamType::EnrichmentTables,
+        StreamType::Metadata,
+        StreamType::Index,
+    ] {
+        for stream_name in db::schema::list_streams_from_cache(org_id, stream_type).await {
+            streams.push((org_id.to_string(), stream_type, stream_name));
+        }
+    }
+    sort_streams(streams).await
+}
+
+/// Sets the manual priority of a stream, `None` goes back to the computed
+/// score.
+pub async fn set_priority(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    priority: Option<i64>,
+) -> Result<(), anyhow::Error> {
+    let stream_type = stream_type.to_string();
+    match priority {
+        Some(priority) => {
+            db::compact::priority::set_override(org_id, &stream_type, stream_name, priority).await
+        }
+        None => db::compact::priority::delete_override(org_id, &stream_type, stream_name).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        // nothing to compact
+        assert_eq!(score(100, 0.0), 0.0);
+        // queried streams first
+        assert!(score(100, 0.5) > score(0, 0.5));
+        // then the ones with more small files
+        assert!(score(0, 0.9) > score(0, 0.5));
+    }
+}
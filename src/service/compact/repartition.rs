@@ -0,0 +1,277 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use arrow::{
+    array::{Array, Int64Array, UInt32Array},
+    compute::take_record_batch,
+    record_batch::RecordBatch,
+    util::display::array_value_to_string,
+};
+use bytes::Bytes;
+use config::{
+    meta::stream::{
+        FileKey, FileMeta, PartitionTimeLevel, StreamPartition, StreamSettings, StreamStats,
+        StreamType,
+    },
+    utils::parquet::{
+        generate_filename_with_time_range, new_parquet_writer_with_options, parse_file_key_columns,
+        read_recordbatch_from_bytes,
+    },
+    CONFIG,
+};
+use infra::storage;
+
+use super::merge::write_file_list;
+use crate::{
+    job::files::parquet::generate_index_on_compactor,
+    service::{file_list, format_partition_key},
+};
+
+/// Rewrites the files of the period which aren't laid out by the enabled
+/// partition keys of the settings, splitting their records into the
+/// partitions of the keys. The files of the `changed_keys` are always read, as
+/// their directories name the key whatever its type. Returns the number of
+/// rewritten files and the stats taken out of the stream with them.
+pub(crate) async fn repartition_by_period(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    partition_time_level: PartitionTimeLevel,
+    settings: &StreamSettings,
+    changed_keys: &[String],
+    (partition_offset_start, partition_offset_end): (i64, i64),
+) -> Result<(usize, StreamStats), anyhow::Error> {
+    let keys = settings
+        .partition_keys
+        .iter()
+        .filter(|k| !k.disabled)
+        .collect::<Vec<_>>();
+    let any_changed = keys.iter().any(|k| changed_keys.contains(&k.field));
+    let files = file_list::query(
+        org_id,
+        stream_name,
+        stream_type,
+        partition_time_level,
+        partition_offset_start,
+        partition_offset_end,
+        true,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("query file list failed: {}", e))?;
+
+    let mut rewritten = 0;
+    let mut stream_stats = StreamStats::default();
+    for file in files {
+        if (!any_changed && is_partitioned_by(&file.key, &keys))
+            || super::legal_holds::is_held(
+                org_id,
+                stream_type,
+                stream_name,
+                file.meta.min_ts,
+                file.meta.max_ts,
+            )
+        {
+            continue;
+        }
+        let events = split_file(org_id, stream_type, stream_name, &file, &keys, settings).await?;
+        if events.is_empty() {
+            continue;
+        }
+        write_file_list(org_id, &events).await?;
+        // the old file is removed by the file list deletion job, the new files are
+        // counted by the stats job
+        stream_stats = stream_stats - file.meta;
+        rewritten += 1;
+    }
+    if rewritten > 0 {
+        log::info!(
+            "[COMPACTOR] repartition [{}/{}/{}] time range: [{},{}], rewritten files: {}",
+            org_id,
+            stream_type,
+            stream_name,
+            partition_offset_start,
+            partition_offset_end,
+            rewritten,
+        );
+    }
+    Ok((rewritten, stream_stats))
+}
+
+/// Whether the directories of the file key are the partition keys, in order.
+fn is_partitioned_by(key: &str, keys: &[&StreamPartition]) -> bool {
+    let Ok((_, _, file_name)) = parse_file_key_columns(key) else {
+        return true;
+    };
+    let mut dirs = file_name.split('/').collect::<Vec<_>>();
+    dirs.pop();
+    dirs.len() == keys.len()
+        && dirs
+            .iter()
+            .zip(keys.iter())
+            .all(|(dir, key)| dir.split_once('=').map(|(f, _)| f) == Some(&key.field))
+}
+
+/// Splits the records of the file by partition and writes a file per
+/// partition, returns the file list events adding them and deleting the file.
+/// Nothing is written when all the records stay in the directory of the file,
+/// e.g. when they don't have the fields of the partition keys.
+async fn split_file(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    file: &FileKey,
+    keys: &[&StreamPartition],
+    settings: &StreamSettings,
+) -> Result<Vec<FileKey>, anyhow::Error> {
+    let (stream_key, date_key, file_name) = parse_file_key_columns(&file.key)?;
+    let file_dir = file_name
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or_default();
+    let data = storage::get(&file.key).await?;
+    let (schema, batches) = read_recordbatch_from_bytes(&data).await?;
+
+    let mut partitions: HashMap<String, Vec<RecordBatch>> = HashMap::new();
+    for batch in batches {
+        let mut rows: HashMap<String, Vec<u32>> = HashMap::new();
+        for row in 0..batch.num_rows() {
+            rows.entry(partition_dir(&batch, row, keys)?)
+                .or_default()
+                .push(row as u32);
+        }
+        for (dir, indices) in rows {
+            let batch = take_record_batch(&batch, &UInt32Array::from(indices))?;
+            partitions.entry(dir).or_default().push(batch);
+        }
+    }
+
+    if partitions.len() == 1 && partitions.contains_key(file_dir) {
+        return Ok(vec![]);
+    }
+
+    let total_records = file.meta.records.max(1);
+    let mut events = Vec::with_capacity(partitions.len() + 1);
+    for (dir, batches) in partitions {
+        let mut meta = FileMeta::default();
+        for batch in batches.iter() {
+            let ts = batch
+                .column_by_name(&CONFIG.common.column_timestamp)
+                .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+                .ok_or_else(|| anyhow::anyhow!("file {} has no timestamp column", file.key))?;
+            let min_ts = arrow::compute::min(ts).unwrap_or_default();
+            let max_ts = arrow::compute::max(ts).unwrap_or_default();
+            if meta.min_ts == 0 || meta.min_ts > min_ts {
+                meta.min_ts = min_ts;
+            }
+            meta.max_ts = meta.max_ts.max(max_ts);
+            meta.records += batch.num_rows() as i64;
+        }
+        meta.original_size = file.meta.original_size * meta.records / total_records;
+
+        let mut buf = Vec::new();
+        let mut writer = new_parquet_writer_with_options(
+            &mut buf,
+            &schema,
+            &settings.bloom_filter_fields,
+            &settings.full_text_search_keys,
+            &meta,
+            None,
+            &settings.parquet,
+        );
+        for batch in batches.iter() {
+            writer.write(batch).await?;
+        }
+        writer.close().await?;
+        meta.compressed_size = buf.len() as i64;
+
+        let file_name = generate_filename_with_time_range(meta.min_ts, meta.max_ts);
+        let new_key = if dir.is_empty() {
+            format!("files/{stream_key}/{date_key}/{file_name}")
+        } else {
+            format!("files/{stream_key}/{date_key}/{dir}/{file_name}")
+        };
+        storage::put(&new_key, Bytes::from(buf)).await?;
+        if CONFIG.common.inverted_index_enabled && stream_type == StreamType::Logs {
+            let (index_key, index_meta) = generate_index_on_compactor(
+                std::slice::from_ref(file),
+                batches,
+                new_key.clone(),
+                org_id,
+                stream_name,
+            )
+            .await?;
+            if !index_key.is_empty() {
+                events.push(FileKey {
+                    key: index_key,
+                    meta: index_meta,
+                    deleted: false,
+                });
+            }
+        }
+        events.push(FileKey {
+            key: new_key,
+            meta,
+            deleted: false,
+        });
+    }
+    events.push(FileKey {
+        key: file.key.clone(),
+        meta: FileMeta::default(),
+        deleted: true,
+    });
+    events.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(events)
+}
+
+/// The partition directories of a row, the same way the ingester names them.
+fn partition_dir(
+    batch: &RecordBatch,
+    row: usize,
+    keys: &[&StreamPartition],
+) -> Result<String, anyhow::Error> {
+    let mut dirs = Vec::with_capacity(keys.len());
+    for key in keys {
+        let Some(column) = batch.column_by_name(&key.field) else {
+            continue;
+        };
+        if column.is_null(row) {
+            continue;
+        }
+        let value = array_value_to_string(column, row)?;
+        dirs.push(format_partition_key(&key.get_partition_key(&value)));
+    }
+    Ok(dirs.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_partitioned_by() {
+        let host = StreamPartition::new("host");
+        let app = StreamPartition::new_hash("app", 16);
+        let key =
+            "files/default/logs/app/2024/01/02/03/host=web1/app=3/7099303408192061440.parquet";
+        assert!(is_partitioned_by(key, &[&host, &app]));
+        assert!(!is_partitioned_by(key, &[&host]));
+        assert!(!is_partitioned_by(key, &[&app, &host]));
+        let key = "files/default/logs/app/2024/01/02/03/7099303408192061440.parquet";
+        assert!(is_partitioned_by(key, &[]));
+        assert!(!is_partitioned_by(key, &[&host]));
+    }
+}
@@ -30,7 +30,7 @@ use infra::{cache, dist_lock, file_list as infra_file_list, storage};
 
 use crate::{
     common::infra::cluster::get_node_by_uuid,
-    service::{db, file_list},
+    service::{db, file_list, large_fields},
 };
 
 pub async fn delete_by_stream(
@@ -48,19 +48,32 @@ pub async fn delete_by_stream(
     let created_at: DateTime<Utc> = Utc.timestamp_nanos(created_at * 1000);
     let lifecycle_start = created_at.format("%Y-%m-%d").to_string();
     let lifecycle_start = lifecycle_start.as_str();
+    // keep the days from the first legal hold on, until it is released
+    let held_from = db::compact::legal_holds::list_by_stream(org_id, stream_type, stream_name)
+        .iter()
+        .filter(|h| h.end_time > stats.doc_time_min)
+        .map(|h| h.start_time)
+        .min()
+        .map(|t| Utc.timestamp_nanos(t * 1000).format("%Y-%m-%d").to_string());
+    let lifecycle_end = match held_from.as_deref() {
+        Some(held_from) if held_from.lt(lifecycle_end) => held_from,
+        _ => lifecycle_end,
+    };
     if lifecycle_start.ge(lifecycle_end) {
         return Ok(()); // created_at is after lifecycle_end, just skip
     }
 
     // Hack for 1970-01-01
     if lifecycle_start.le("1970-01-01") {
-        let lifecycle_end = created_at + Duration::try_days(1).unwrap();
-        let lifecycle_end = lifecycle_end.format("%Y-%m-%d").to_string();
+        let day_end = created_at + Duration::try_days(1).unwrap();
+        let day_end = day_end.format("%Y-%m-%d").to_string();
+        // the held days are kept here too
+        let lifecycle_end = day_end.as_str().min(lifecycle_end);
         return db::compact::retention::delete_stream(
             org_id,
             stream_type,
             stream_name,
-            Some((lifecycle_start, lifecycle_end.as_str())),
+            Some((lifecycle_start, lifecycle_end)),
         )
         .await;
     }
@@ -103,9 +116,21 @@ pub async fn delete_all(
     drop(locker);
     ret?;
 
+    // a legal hold placed after the stream deletion was planned keeps it pending
+    if !db::compact::legal_holds::list_by_stream(org_id, stream_type, stream_name).is_empty() {
+        log::info!(
+            "[COMPACT] stream {org_id}/{stream_type}/{stream_name} is under a legal hold, skip deleting all"
+        );
+        return Ok(());
+    }
+
     let start_time = BASE_TIME.timestamp_micros();
     let end_time = Utc::now().timestamp_micros();
 
+    if super::archive::is_archived(org_id, stream_type, stream_name).await {
+        super::archive::archive(org_id, stream_type, stream_name, (start_time, end_time)).await?;
+    }
+
     if is_local_disk_storage() {
         let data_dir = format!(
             "{}files/{org_id}/{stream_type}/{stream_name}",
@@ -153,6 +178,10 @@ pub async fn delete_all(
         }
     }
 
+    if let Err(e) = large_fields::delete(org_id, stream_type, stream_name, None).await {
+        log::error!("[COMPACT] delete large fields failed: {}", e);
+    }
+
     // delete from file list
     delete_from_file_list(org_id, stream_type, stream_name, (start_time, end_time)).await?;
     log::info!(
@@ -164,6 +193,7 @@ pub async fn delete_all(
 
     // mark delete done
     db::compact::retention::delete_stream_done(org_id, stream_type, stream_name, None).await?;
+    db::compact::archive::delete_done(org_id, stream_type, stream_name).await?;
     log::info!(
         "deleted stream all: {}/{}/{}",
         org_id,
@@ -214,6 +244,25 @@ pub async fn delete_by_date(
         DateTime::parse_from_rfc3339(&format!("{}T00:00:00Z", date_range.1))?.with_timezone(&Utc);
     let time_range = { (date_start.timestamp_micros(), date_end.timestamp_micros()) };
 
+    // a legal hold placed after the delete was planned keeps it pending
+    if super::legal_holds::is_held(
+        org_id,
+        stream_type,
+        stream_name,
+        time_range.0,
+        time_range.1 - 1,
+    ) {
+        log::info!(
+            "[COMPACT] stream {org_id}/{stream_type}/{stream_name}/{:?} is under a legal hold, skip deleting",
+            date_range
+        );
+        return Ok(());
+    }
+
+    if super::archive::is_archived(org_id, stream_type, stream_name).await {
+        super::archive::archive(org_id, stream_type, stream_name, time_range).await?;
+    }
+
     if is_local_disk_storage() {
         while date_start <= date_end {
             let data_dir = format!(
@@ -270,6 +319,10 @@ pub async fn delete_by_date(
         }
     }
 
+    if let Err(e) = large_fields::delete(org_id, stream_type, stream_name, Some(time_range)).await {
+        log::error!("[COMPACT] delete large fields failed: {}", e);
+    }
+
     // delete from file list
     delete_from_file_list(org_id, stream_type, stream_name, time_range).await?;
 
@@ -347,6 +400,13 @@ async fn delete_from_file_list(
 
     // write file list to storage
     write_file_list(file_list_days, hours_files).await?;
+    // the deleted files carry no time range, drop the whole range
+    if let Err(e) =
+        db::query_cache::invalidate(org_id, stream_type, stream_name, time_range.0, time_range.1)
+            .await
+    {
+        log::error!("[COMPACT] invalidate query cache for {stream_name} failed: {e}");
+    }
 
     // update stream stats
     if stream_stats.doc_num != 0 {
@@ -363,15 +423,18 @@ async fn delete_from_file_list(
     Ok(())
 }
 
-async fn write_file_list(
+pub(crate) async fn write_file_list(
     file_list_days: HashSet<String>,
     hours_files: HashMap<String, Vec<FileKey>>,
 ) -> Result<(), anyhow::Error> {
+    let events = hours_files.values().flatten().cloned().collect::<Vec<_>>();
     if CONFIG.common.meta_store_external {
-        write_file_list_db_only(hours_files).await
+        write_file_list_db_only(hours_files).await?;
     } else {
-        write_file_list_s3(file_list_days, hours_files).await
+        write_file_list_s3(file_list_days, hours_files).await?;
     }
+    super::merge::invalidate_query_cache(&events).await;
+    Ok(())
 }
 
 async fn write_file_list_db_only(
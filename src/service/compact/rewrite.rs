@@ -0,0 +1,151 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::Utc;
+use config::meta::stream::StreamType;
+use infra::cache::stats;
+
+use crate::{common::meta::stream::RewriteJob, service::db};
+
+/// Starts rewriting the files of the stream in the time range with the
+/// current settings, the compactor of the stream does it in the background.
+pub async fn create(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<RewriteJob, anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    let (start_time, end_time) = time_range(start_time, end_time, now)?;
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(anyhow::anyhow!("stream {stream_name} not found"));
+    }
+    if db::compact::rewrite::get(org_id, stream_type, stream_name)
+        .await?
+        .is_some()
+    {
+        return Err(anyhow::anyhow!(
+            "stream {stream_name} is already being rewritten"
+        ));
+    }
+    let job = RewriteJob {
+        start_time,
+        end_time,
+        created_at: now,
+        done_until: 0,
+        progress: 0.0,
+        repartition: false,
+        repartitioned_files: 0,
+        changed_keys: vec![],
+        repartition_pending: false,
+    };
+    db::compact::rewrite::set(org_id, stream_type, stream_name, &job).await?;
+    Ok(job)
+}
+
+/// Starts moving the historical files of the stream to the current partition
+/// keys, `changed_keys` being the keys whose type changed. A repartition in
+/// progress is restarted, as the files it already split follow the old keys,
+/// while a requested rewrite is finished first. Returns `None` when the stream
+/// has no data yet.
+pub async fn repartition(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    changed_keys: &[String],
+) -> Result<Option<RewriteJob>, anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    let stats = stats::get_stream_stats(org_id, stream_name, stream_type);
+    if stats.doc_time_min == 0 {
+        return Ok(None);
+    }
+    let mut start_time = stats.doc_time_min;
+    let mut keys = changed_keys.to_vec();
+    if let Some(mut job) = db::compact::rewrite::get(org_id, stream_type, stream_name).await? {
+        keys = merge_keys(changed_keys, &job.changed_keys);
+        if !job.repartition {
+            job.repartition_pending = true;
+            job.changed_keys = keys;
+            db::compact::rewrite::set(org_id, stream_type, stream_name, &job).await?;
+            return Ok(Some(job));
+        }
+        start_time = start_time.min(job.start_time);
+    }
+    let job = RewriteJob {
+        start_time,
+        end_time: now,
+        created_at: now,
+        done_until: 0,
+        progress: 0.0,
+        repartition: true,
+        repartitioned_files: 0,
+        changed_keys: keys,
+        repartition_pending: false,
+    };
+    db::compact::rewrite::set(org_id, stream_type, stream_name, &job).await?;
+    Ok(Some(job))
+}
+
+/// The time range of a rewrite, an unset or future end is now.
+fn time_range(start_time: i64, end_time: i64, now: i64) -> Result<(i64, i64), anyhow::Error> {
+    let end_time = if end_time == 0 || end_time > now {
+        now
+    } else {
+        end_time
+    };
+    if start_time >= end_time {
+        return Err(anyhow::anyhow!("start_time should be less than end_time"));
+    }
+    Ok((start_time, end_time))
+}
+
+/// The changed keys of a repartition along with the ones of the job it
+/// replaces, whose files aren't all split yet.
+fn merge_keys(changed_keys: &[String], pending: &[String]) -> Vec<String> {
+    let mut keys = changed_keys
+        .iter()
+        .chain(pending.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_range() {
+        assert_eq!(time_range(100, 0, 1000).unwrap(), (100, 1000));
+        assert_eq!(time_range(100, 5000, 1000).unwrap(), (100, 1000));
+        assert_eq!(time_range(100, 500, 1000).unwrap(), (100, 500));
+        assert!(time_range(500, 500, 1000).is_err());
+        assert!(time_range(2000, 0, 1000).is_err());
+    }
+
+    #[test]
+    fn test_merge_keys() {
+        let keys = |v: &[&str]| v.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            merge_keys(&keys(&["host", "app"]), &keys(&["app", "region"])),
+            keys(&["app", "host", "region"])
+        );
+        assert!(merge_keys(&[], &[]).is_empty());
+    }
+}
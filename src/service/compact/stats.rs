@@ -13,28 +13,50 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::{cluster::LOCAL_NODE_UUID, CONFIG};
+use chrono::{Duration, Utc};
+use config::{cluster::LOCAL_NODE_UUID, meta::stream::StreamStats, CONFIG};
+use hashbrown::HashMap;
 use infra::{dist_lock, file_list as infra_file_list};
-use tokio::time;
+use once_cell::sync::Lazy;
+use tokio::{sync::Mutex, time};
 
-use crate::{common::infra::cluster::get_node_by_uuid, service::db};
+use crate::{
+    common::{infra::cluster::get_node_by_uuid, meta::stream::StreamStatsDrift},
+    service::db,
+};
+
+/// Held while the stored stats are changed from the file_list, so a rebuild
+/// and a run of the stats job don't both apply the same files. The dist lock
+/// covers the other nodes, this one the tasks of the node as the dist lock is
+/// off in local mode.
+static STATS_LOCKER: Lazy<Mutex<()>> = Lazy::new(|| Mutex::const_new(()));
+
+const STATS_LOCK_KEY: &str = "/compact/stream_stats/update";
 
 pub async fn update_stats_from_file_list() -> Result<Option<(i64, i64)>, anyhow::Error> {
-    // get last offset
-    let (mut offset, node) = db::compact::stats::get_offset().await;
+    let (_, node) = db::compact::stats::get_offset().await;
     if !node.is_empty() && LOCAL_NODE_UUID.ne(&node) && get_node_by_uuid(&node).await.is_some() {
         return Ok(None);
     }
 
     // before starting, set current node to lock the job
-    if CONFIG.common.meta_store_external && (node.is_empty() || LOCAL_NODE_UUID.ne(&node)) {
-        offset = match update_stats_lock_node().await {
-            Ok(Some(offset)) => offset,
-            Ok(None) => return Ok(None),
-            Err(e) => return Err(e),
-        }
+    if CONFIG.common.meta_store_external
+        && (node.is_empty() || LOCAL_NODE_UUID.ne(&node))
+        && update_stats_lock_node().await?.is_none()
+    {
+        return Ok(None);
     }
 
+    let _guard = STATS_LOCKER.lock().await;
+    let locker = dist_lock::lock(STATS_LOCK_KEY, 0).await?;
+    let ret = apply_stats_from_file_list().await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn apply_stats_from_file_list() -> Result<Option<(i64, i64)>, anyhow::Error> {
+    // read the offset under the lock, a rebuild may have run meanwhile
+    let (offset, _) = db::compact::stats::get_offset().await;
     // get latest offset
     let latest_pk = infra_file_list::get_max_pk_value().await?;
     let pk_value = if offset == 0 && latest_pk == 0 {
@@ -59,6 +81,144 @@ pub async fn update_stats_from_file_list() -> Result<Option<(i64, i64)>, anyhow:
     Ok(pk_value)
 }
 
+/// Saves the current stats of every stream as a point of the stats history.
+/// Only the node owning the stats job takes snapshots.
+pub async fn snapshot_stream_stats() -> Result<(), anyhow::Error> {
+    if stats_job_owned_by_other_node().await {
+        return Ok(());
+    }
+
+    // align the points so all the streams share the same timestamps
+    let interval = CONFIG.limit.stats_history_interval as i64 * 1_000_000;
+    let ts = Utc::now().timestamp_micros() / interval * interval;
+    let orgs = db::schema::list_organizations_from_cache().await;
+    for org_id in orgs {
+        let stream_stats = infra_file_list::get_stream_stats(&org_id, None, None).await?;
+        infra_file_list::add_stream_stats_history(ts, &stream_stats).await?;
+    }
+
+    let retention = Duration::try_days(CONFIG.limit.stats_history_retention_days)
+        .ok_or_else(|| anyhow::anyhow!("invalid stats history retention days"))?;
+    infra_file_list::clean_stream_stats_history((Utc::now() - retention).timestamp_micros())
+        .await?;
+    Ok(())
+}
+
+/// Recomputes the stats of every stream from the file_list and fixes the
+/// stored stats that drifted, e.g. after deletes or compaction. Returns the
+/// drift found, as `file_list - stored`.
+pub async fn rebuild_stream_stats(
+    org_id: Option<&str>,
+) -> Result<Vec<StreamStatsDrift>, anyhow::Error> {
+    // the stats job waits, it would add files the rebuild already counted
+    let _guard = STATS_LOCKER.lock().await;
+    let locker = dist_lock::lock(STATS_LOCK_KEY, 0).await?;
+    let ret = rebuild(org_id).await;
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn rebuild(org_id: Option<&str>) -> Result<Vec<StreamStatsDrift>, anyhow::Error> {
+    // only count the files the stats job already applied, newer files are
+    // added by its next run
+    let (offset, _) = db::compact::stats::get_offset().await;
+    if offset == 0 {
+        return Err(anyhow::anyhow!("stream stats are not calculated yet"));
+    }
+
+    let orgs = match org_id {
+        Some(org_id) => vec![org_id.to_string()],
+        None => db::schema::list_organizations_from_cache().await,
+    };
+    let mut drifts = Vec::new();
+    for org_id in orgs {
+        let real = infra_file_list::stats(&org_id, None, None, Some((0, offset)))
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let stored = infra_file_list::get_stream_stats(&org_id, None, None).await?;
+
+        let fixes = reconcile(stored, real);
+        if !fixes.deltas.is_empty() {
+            infra_file_list::set_stream_stats(&org_id, &fixes.deltas).await?;
+        }
+        for (stream, min_ts) in fixes.min_ts_resets {
+            infra_file_list::reset_stream_stats_min_ts(&org_id, &stream, min_ts).await?;
+        }
+        for drift in fixes.drifts {
+            log::warn!("[STATS] stream stats drift found: {:?}", drift);
+            drifts.push(drift);
+        }
+    }
+
+    // refresh the memory cache of this node, others pick it up on their next run
+    db::file_list::remote::cache_stats().await?;
+    Ok(drifts)
+}
+
+/// The changes bringing the stored stats of an org to the ones of its file_list.
+#[derive(Debug, Default)]
+struct StatsFixes {
+    /// Added to the stored stats, which are running totals
+    deltas: Vec<(String, StreamStats)>,
+    /// Streams whose oldest records were deleted
+    min_ts_resets: Vec<(String, i64)>,
+    drifts: Vec<StreamStatsDrift>,
+}
+
+fn reconcile(
+    stored: Vec<(String, StreamStats)>,
+    mut real: HashMap<String, StreamStats>,
+) -> StatsFixes {
+    let mut fixes = StatsFixes::default();
+    let streams = stored
+        .into_iter()
+        .map(|(stream, stats)| {
+            let real = real.remove(&stream).unwrap_or_default();
+            (stream, stats, real)
+        })
+        .collect::<Vec<_>>();
+    let new_streams = real
+        .into_iter()
+        .map(|(stream, real)| (stream, StreamStats::default(), real));
+    for (stream, stored, real) in streams.into_iter().chain(new_streams) {
+        let drift = StreamStatsDrift {
+            stream: stream.to_string(),
+            doc_num: real.doc_num - stored.doc_num,
+            file_num: real.file_num - stored.file_num,
+            storage_size: real.storage_size - stored.storage_size,
+            compressed_size: real.compressed_size - stored.compressed_size,
+        };
+        if drift.is_empty() && real.doc_time_min == stored.doc_time_min {
+            continue;
+        }
+        fixes.deltas.push((
+            stream.to_string(),
+            StreamStats {
+                created_at: 0,
+                doc_time_min: real.doc_time_min,
+                doc_time_max: real.doc_time_max,
+                doc_num: drift.doc_num,
+                file_num: drift.file_num,
+                storage_size: drift.storage_size,
+                compressed_size: drift.compressed_size,
+            },
+        ));
+        if real.doc_time_min > stored.doc_time_min {
+            fixes.min_ts_resets.push((stream, real.doc_time_min));
+        }
+        if !drift.is_empty() {
+            fixes.drifts.push(drift);
+        }
+    }
+    fixes
+}
+
+pub async fn stats_job_owned_by_other_node() -> bool {
+    let (_, node) = db::compact::stats::get_offset().await;
+    !node.is_empty() && LOCAL_NODE_UUID.ne(&node) && get_node_by_uuid(&node).await.is_some()
+}
+
 async fn update_stats_lock_node() -> Result<Option<i64>, anyhow::Error> {
     let lock_key = "/compact/stream_stats/offset".to_string();
     let locker = dist_lock::lock(&lock_key, 0).await?;
@@ -80,3 +240,128 @@ async fn update_stats_lock_node() -> Result<Option<i64>, anyhow::Error> {
         Ok(Some(offset))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(doc_time_min: i64, doc_num: i64, file_num: i64) -> StreamStats {
+        StreamStats {
+            doc_time_min,
+            doc_time_max: 9000,
+            doc_num,
+            file_num,
+            storage_size: doc_num as f64 * 10.0,
+            compressed_size: doc_num as f64,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let stored = vec![
+            ("default/logs/synced".to_string(), stats(1000, 100, 2)),
+            // files deleted without updating the stats
+            ("default/logs/drifted".to_string(), stats(1000, 100, 2)),
+            // the oldest files expired
+            ("default/logs/expired".to_string(), stats(1000, 100, 2)),
+            ("default/logs/gone".to_string(), stats(1000, 100, 2)),
+        ];
+        let real = HashMap::from([
+            ("default/logs/synced".to_string(), stats(1000, 100, 2)),
+            ("default/logs/drifted".to_string(), stats(1000, 60, 1)),
+            ("default/logs/expired".to_string(), stats(5000, 100, 2)),
+            ("default/logs/new".to_string(), stats(2000, 30, 1)),
+        ]);
+        let mut fixes = reconcile(stored, real);
+        fixes.deltas.sort_by(|a, b| a.0.cmp(&b.0));
+        fixes.drifts.sort_by(|a, b| a.stream.cmp(&b.stream));
+
+        let deltas = fixes
+            .deltas
+            .iter()
+            .map(|(stream, s)| (stream.as_str(), s.doc_num, s.file_num))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deltas,
+            vec![
+                ("default/logs/drifted", -40, -1),
+                ("default/logs/expired", 0, 0),
+                ("default/logs/gone", -100, -2),
+                ("default/logs/new", 30, 1),
+            ]
+        );
+        assert_eq!(fixes.deltas[0].1.storage_size, -400.0);
+        assert_eq!(
+            fixes.min_ts_resets,
+            vec![
+                ("default/logs/expired".to_string(), 5000),
+                ("default/logs/new".to_string(), 2000)
+            ]
+        );
+        // a moved min_ts alone isn't a drift
+        let drifted = fixes
+            .drifts
+            .iter()
+            .map(|d| d.stream.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            drifted,
+            vec![
+                "default/logs/drifted",
+                "default/logs/gone",
+                "default/logs/new"
+            ]
+        );
+
+        let stored = vec![("default/logs/synced".to_string(), stats(1000, 100, 2))];
+        let real = HashMap::from([("default/logs/synced".to_string(), stats(1000, 100, 2))]);
+        let fixes = reconcile(stored, real);
+        assert!(fixes.deltas.is_empty() && fixes.drifts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream_stats_history() {
+        infra_file_list::create_table().await.unwrap();
+        let stream = "stats_history_test/logs/app";
+        let stats = |doc_num: i64| StreamStats {
+            doc_num,
+            file_num: 1,
+            ..Default::default()
+        };
+        // far older than the points of the other streams
+        infra_file_list::clean_stream_stats_history(10_000)
+            .await
+            .unwrap();
+        for (ts, doc_num) in [(1000, 10), (2000, 20), (3000, 30)] {
+            infra_file_list::add_stream_stats_history(ts, &[(stream.to_string(), stats(doc_num))])
+                .await
+                .unwrap();
+        }
+
+        let points = infra_file_list::get_stream_stats_history(stream, (0, 10_000))
+            .await
+            .unwrap();
+        assert_eq!(
+            points
+                .iter()
+                .map(|(ts, s)| (*ts, s.doc_num))
+                .collect::<Vec<_>>(),
+            vec![(1000, 10), (2000, 20), (3000, 30)]
+        );
+        let points = infra_file_list::get_stream_stats_history(stream, (1500, 2500))
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 1);
+
+        // the points older than the retention are trimmed
+        infra_file_list::clean_stream_stats_history(2500)
+            .await
+            .unwrap();
+        let points = infra_file_list::get_stream_stats_history(stream, (0, 10_000))
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].0, 3000);
+    }
+}
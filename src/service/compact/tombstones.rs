@@ -0,0 +1,186 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::ops::ControlFlow;
+
+use chrono::Utc;
+use config::{
+    ider,
+    meta::{sql::Sql as MetaSql, stream::StreamType},
+    CONFIG,
+};
+use infra::schema::unwrap_stream_settings;
+use sqlparser::ast::{visit_expressions, Expr};
+
+use crate::{common::meta::stream::Tombstone, service::db};
+
+/// Returns the condition matching the records deleted by the tombstone
+pub fn delete_condition(tombstone: &Tombstone) -> String {
+    let ts = &CONFIG.common.column_timestamp;
+    let filter = if tombstone.filter.trim().is_empty() {
+        "true"
+    } else {
+        tombstone.filter.as_str()
+    };
+    format!(
+        "COALESCE({ts} >= {} AND {ts} < {} AND ({filter}), false)",
+        tombstone.start_time, tombstone.end_time
+    )
+}
+
+/// Returns the condition excluding the records deleted by the tombstones
+/// which overlap the time range, `0` means unbounded.
+pub fn build_search_condition(
+    tombstones: &[Tombstone],
+    time_range: Option<(i64, i64)>,
+) -> Option<String> {
+    let (start, end) = time_range.unwrap_or_default();
+    let conditions = tombstones
+        .iter()
+        .filter(|t| (end == 0 || t.start_time < end) && (start == 0 || t.end_time > start))
+        .map(|t| format!("NOT {}", delete_condition(t)))
+        .collect::<Vec<_>>();
+    if conditions.is_empty() {
+        None
+    } else {
+        Some(conditions.join(" AND "))
+    }
+}
+
+/// Returns the condition hiding the deleted records of the stream from
+/// searches, `None` if the stream has no tombstones.
+pub fn search_condition(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    time_range: Option<(i64, i64)>,
+) -> Option<String> {
+    let tombstones = db::compact::tombstones::list_by_stream(org_id, stream_type, stream_name);
+    build_search_condition(&tombstones, time_range)
+}
+
+/// Records a delete of the stream, the records are hidden from searches
+/// right away and removed from the files by the compactor later.
+pub async fn create(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    mut tombstone: Tombstone,
+) -> Result<Tombstone, anyhow::Error> {
+    if matches!(stream_type, StreamType::Index | StreamType::Metadata) {
+        return Err(anyhow::anyhow!(
+            "records can't be deleted from {stream_type} streams"
+        ));
+    }
+    let now = Utc::now().timestamp_micros();
+    if tombstone.end_time == 0 || tombstone.end_time > now {
+        tombstone.end_time = now;
+    }
+    if tombstone.start_time >= tombstone.end_time {
+        return Err(anyhow::anyhow!("start_time should be less than end_time"));
+    }
+
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(anyhow::anyhow!("stream {stream_name} not found"));
+    }
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    if settings.is_retention_locked(now) {
+        return Err(anyhow::anyhow!(
+            "stream {stream_name} is under a retention lock, its records can't be deleted"
+        ));
+    }
+    let filter = tombstone.filter.trim().to_string();
+    if !filter.is_empty() {
+        let meta = MetaSql::new(&format!("SELECT * FROM tbl WHERE {filter}"))
+            .map_err(|e| anyhow::anyhow!("invalid filter: {e}"))?;
+        if let Some(selection) = meta.selection.as_ref() {
+            let mut missing = None;
+            let _ = visit_expressions(selection, |expr| {
+                let name = match expr {
+                    Expr::Identifier(ident) => Some(&ident.value),
+                    Expr::CompoundIdentifier(idents) => idents.last().map(|v| &v.value),
+                    _ => None,
+                };
+                match name {
+                    Some(name) if schema.field_with_name(name).is_err() => {
+                        missing = Some(name.to_string());
+                        ControlFlow::Break(())
+                    }
+                    _ => ControlFlow::Continue(()),
+                }
+            });
+            if let Some(name) = missing {
+                return Err(anyhow::anyhow!("field {name} not found in the stream"));
+            }
+        }
+    }
+
+    tombstone.id = ider::generate();
+    tombstone.filter = filter;
+    tombstone.created_at = now;
+    tombstone.done_until = 0;
+    db::compact::tombstones::set(org_id, stream_type, stream_name, &tombstone).await?;
+    // the deleted records are hidden from the searches from now on
+    if let Err(e) = db::query_cache::invalidate(
+        org_id,
+        stream_type,
+        stream_name,
+        tombstone.start_time,
+        tombstone.end_time,
+    )
+    .await
+    {
+        log::error!("[COMPACT] invalidate query cache for {stream_name} failed: {e}");
+    }
+    Ok(tombstone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_search_condition() {
+        let tombstones = vec![
+            Tombstone {
+                id: "a".to_string(),
+                start_time: 100,
+                end_time: 200,
+                filter: "user_id = 'abc'".to_string(),
+                ..Default::default()
+            },
+            Tombstone {
+                id: "b".to_string(),
+                start_time: 300,
+                end_time: 400,
+                ..Default::default()
+            },
+        ];
+        let ts = &CONFIG.common.column_timestamp;
+        assert_eq!(
+            build_search_condition(&tombstones, Some((150, 250))),
+            Some(format!(
+                "NOT COALESCE({ts} >= 100 AND {ts} < 200 AND (user_id = 'abc'), false)"
+            ))
+        );
+        assert_eq!(
+            build_search_condition(&tombstones, Some((0, 350))).map(|v| v.matches("NOT ").count()),
+            Some(2)
+        );
+        assert_eq!(build_search_condition(&tombstones, Some((200, 300))), None);
+        assert_eq!(build_search_condition(&[], None), None);
+    }
+}
@@ -0,0 +1,451 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use actix_web::web;
+use chrono::Utc;
+use config::{
+    meta::{search, sql::Sql, stream::StreamType},
+    utils::json::{self, Map, Value},
+    CONFIG,
+};
+
+use crate::{
+    common::{
+        meta::{
+            continuous_queries::{ContinuousQuery, PendingWindow},
+            ingestion::IngestionRequest,
+            user::UserRole,
+        },
+        utils::auth::is_root_user,
+    },
+    service::{
+        db,
+        error::{Result, ServiceError},
+        logs, search as SearchService, users,
+    },
+};
+
+/// Most windows a run catches up, the next run starts right after it
+const MAX_WINDOWS_PER_RUN: usize = 60;
+
+/// Most result rows of a window
+const MAX_ROWS: usize = 10_000;
+
+/// Start of the window of a result ingested into the destination
+const WINDOW_COLUMN: &str = "_cq_window";
+
+pub async fn save(org_id: &str, mut query: ContinuousQuery, user_id: &str) -> Result<()> {
+    query.watermark = None;
+    query.pending = None;
+    query.last_error = None;
+    query.created_by = user_id.to_string();
+    validate(&query)?;
+    check_permissions(org_id, &query).await?;
+    if db::continuous_queries::get(org_id, &query.name)
+        .await
+        .is_ok()
+    {
+        return Err(ServiceError::Conflict(format!(
+            "continuous query [{}] already exists",
+            query.name
+        )));
+    }
+    db::continuous_queries::set(org_id, &query, true).await?;
+    Ok(())
+}
+
+/// Changes the query, which keeps running from its watermark as the user who
+/// changed it.
+pub async fn update(
+    org_id: &str,
+    name: &str,
+    mut query: ContinuousQuery,
+    user_id: &str,
+) -> Result<()> {
+    let old = get(org_id, name).await?;
+    query.name = old.name;
+    query.watermark = old.watermark;
+    query.pending = old.pending;
+    query.last_error = None;
+    query.created_by = user_id.to_string();
+    validate(&query)?;
+    check_permissions(org_id, &query).await?;
+    db::continuous_queries::set(org_id, &query, false).await?;
+    Ok(())
+}
+
+pub async fn enable(org_id: &str, name: &str, value: bool) -> Result<()> {
+    let mut query = get(org_id, name).await?;
+    query.enabled = value;
+    db::continuous_queries::set_without_updating_trigger(org_id, &query).await?;
+    Ok(())
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<ContinuousQuery> {
+    db::continuous_queries::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("continuous query not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<ContinuousQuery>> {
+    Ok(db::continuous_queries::list(org_id).await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<()> {
+    get(org_id, name).await?;
+    db::continuous_queries::delete(org_id, name).await?;
+    Ok(())
+}
+
+fn validate(query: &ContinuousQuery) -> Result<()> {
+    if query.name.is_empty() || query.name.contains('/') {
+        return Err(ServiceError::bad_request(
+            "continuous query name is required and cannot contain '/'",
+        ));
+    }
+    if query.interval < 10 {
+        return Err(ServiceError::bad_request(
+            "interval should be at least 10 seconds",
+        ));
+    }
+    if query.delay < 0 {
+        return Err(ServiceError::bad_request("delay can't be negative"));
+    }
+    if query.destination.is_empty()
+        || super::format_stream_name(&query.destination) != query.destination
+    {
+        return Err(ServiceError::bad_request(format!(
+            "destination stream name [{}] is invalid",
+            query.destination
+        )));
+    }
+    let sql = Sql::new(&query.sql).map_err(|e| ServiceError::bad_request(e.to_string()))?;
+    if query.stream_type == StreamType::Logs && sql.source == query.destination {
+        return Err(ServiceError::bad_request(
+            "the destination can't be the stream the query reads",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the creator of the query is still a member of the org allowed to
+/// read the stream of the query and to write the destination.
+async fn check_permissions(org_id: &str, query: &ContinuousQuery) -> Result<()> {
+    let user_id = query.created_by.as_str();
+    if is_root_user(user_id) {
+        return Ok(());
+    }
+    let Some(user) = users::get_user(Some(org_id), user_id).await else {
+        return Err(ServiceError::Forbidden(format!(
+            "user [{user_id}] is not a member of the organization"
+        )));
+    };
+    let sql = Sql::new(&query.sql).map_err(|e| ServiceError::bad_request(e.to_string()))?;
+    for (stream_type, stream_name, method) in [
+        (query.stream_type, sql.source.as_str(), "GET"),
+        (StreamType::Logs, query.destination.as_str(), "POST"),
+    ] {
+        if !is_allowed(
+            org_id,
+            user_id,
+            &user.role,
+            stream_type,
+            stream_name,
+            method,
+        )
+        .await
+        {
+            return Err(ServiceError::Forbidden(format!(
+                "user [{user_id}] is not allowed to access stream [{stream_name}]"
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "enterprise")]
+async fn is_allowed(
+    org_id: &str,
+    user_id: &str,
+    role: &UserRole,
+    stream_type: StreamType,
+    stream_name: &str,
+    method: &str,
+) -> bool {
+    use crate::common::utils::auth::AuthExtractor;
+
+    crate::handler::http::auth::validator::check_permissions(
+        user_id,
+        AuthExtractor {
+            auth: "".to_string(),
+            method: method.to_string(),
+            o2_type: format!("{stream_type}:{stream_name}"),
+            org_id: org_id.to_string(),
+            bypass_check: false,
+            parent_id: "".to_string(),
+        },
+        Some(role.clone()),
+    )
+    .await
+}
+
+#[cfg(not(feature = "enterprise"))]
+async fn is_allowed(
+    _org_id: &str,
+    _user_id: &str,
+    _role: &UserRole,
+    _stream_type: StreamType,
+    _stream_name: &str,
+    _method: &str,
+) -> bool {
+    true
+}
+
+/// The windows the next run reads, aligned to the interval and ending before
+/// `now - delay`. The first run reads the last full window.
+fn next_windows(watermark: Option<i64>, now: i64, interval: i64, delay: i64) -> Vec<(i64, i64)> {
+    let interval = interval * 1_000_000;
+    let ready = (now - delay * 1_000_000) / interval * interval;
+    let mut start = watermark.unwrap_or(ready - interval);
+    let mut windows = Vec::new();
+    while start + interval <= ready && windows.len() < MAX_WINDOWS_PER_RUN {
+        windows.push((start, start + interval));
+        start += interval;
+    }
+    windows
+}
+
+/// Runs the query over the windows which are ready, ingests the results and
+/// saves the new watermark after each window. Returns the time of the next run
+/// in microseconds.
+pub async fn run(org_id: &str, query: &mut ContinuousQuery) -> Result<i64> {
+    let now = Utc::now().timestamp_micros();
+    let windows = next_windows(query.watermark, now, query.interval, query.delay);
+    let mut ret = check_permissions(org_id, query).await;
+    if ret.is_ok() {
+        for (start, end) in windows.iter() {
+            if let Err(e) = run_window(org_id, query, *start, *end).await {
+                ret = Err(e);
+                break;
+            }
+            query.watermark = Some(*end);
+            query.pending = None;
+            db::continuous_queries::set_without_updating_trigger(org_id, query).await?;
+        }
+    }
+    query.last_error = ret.as_ref().err().map(|e| e.to_string());
+    db::continuous_queries::set_without_updating_trigger(org_id, query).await?;
+    ret?;
+
+    if windows.len() == MAX_WINDOWS_PER_RUN {
+        // still catching up
+        return Ok(now);
+    }
+    let next_end = query.watermark.unwrap_or(now) + query.interval * 1_000_000;
+    Ok(next_end.max(now) + query.delay * 1_000_000)
+}
+
+async fn run_window(org_id: &str, query: &mut ContinuousQuery, start: i64, end: i64) -> Result<()> {
+    // the results of an interrupted run may be in the destination already
+    if let Some(pending) = query.pending.take() {
+        if pending.start == start && is_ingested(org_id, query, &pending).await? {
+            return Ok(());
+        }
+    }
+
+    let req = search::Request {
+        query: search::Query {
+            sql: query.sql.clone(),
+            size: MAX_ROWS + 1,
+            start_time: start,
+            end_time: end,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search(
+        "",
+        org_id,
+        query.stream_type,
+        Some(query.created_by.clone()),
+        &req,
+    )
+    .await
+    .map_err(|e| ServiceError::internal(format!("search error: {e}")))?;
+    if res.hits.len() > MAX_ROWS {
+        return Err(ServiceError::bad_request(format!(
+            "the window [{start}, {end}) returned more than {MAX_ROWS} rows"
+        )));
+    }
+    let rows = res
+        .hits
+        .into_iter()
+        .filter_map(|v| match v {
+            Value::Object(v) => Some(with_timestamp(v, start)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let timestamps = rows
+        .iter()
+        .filter_map(|row| row.get(&CONFIG.common.column_timestamp)?.as_i64());
+    query.pending = Some(PendingWindow {
+        start,
+        min_ts: timestamps.clone().min().unwrap_or(start),
+        max_ts: timestamps.max().unwrap_or(start),
+    });
+    db::continuous_queries::set_without_updating_trigger(org_id, query).await?;
+
+    let body = web::Bytes::from(json::to_vec(&rows).unwrap());
+    let resp = logs::ingest::ingest(
+        org_id,
+        &query.destination,
+        IngestionRequest::JSON(&body),
+        0,
+        &query.created_by,
+    )
+    .await?;
+    if let Some(e) = resp.error {
+        return Err(ServiceError::internal(e));
+    }
+    Ok(())
+}
+
+/// Whether the destination holds results of the pending window
+async fn is_ingested(
+    org_id: &str,
+    query: &ContinuousQuery,
+    pending: &PendingWindow,
+) -> Result<bool> {
+    let req = search::Request {
+        query: search::Query {
+            sql: format!(
+                "SELECT COUNT(*) AS num FROM \"{}\" WHERE \"{WINDOW_COLUMN}\" = {}",
+                query.destination, pending.start
+            ),
+            size: 1,
+            start_time: pending.min_ts,
+            end_time: pending.max_ts + 1,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = match SearchService::search("", org_id, StreamType::Logs, None, &req).await {
+        Ok(res) => res,
+        // the window column doesn't exist before the first results
+        Err(e) => {
+            log::warn!(
+                "[CONTINUOUS QUERY] {org_id}/{} check of the pending window failed: {e}",
+                query.name
+            );
+            return Ok(false);
+        }
+    };
+    Ok(res
+        .hits
+        .first()
+        .and_then(|v| v.get("num"))
+        .and_then(|v| v.as_i64())
+        .unwrap_or_default()
+        > 0)
+}
+
+/// The results without a timestamp get the start of their window, all of them
+/// are marked with it
+fn with_timestamp(mut row: Map<String, Value>, start: i64) -> Map<String, Value> {
+    if !row.contains_key(&CONFIG.common.column_timestamp) {
+        row.insert(CONFIG.common.column_timestamp.clone(), start.into());
+    }
+    row.insert(WINDOW_COLUMN.to_string(), start.into());
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: i64 = 60_000_000;
+
+    fn query(sql: &str, destination: &str) -> ContinuousQuery {
+        ContinuousQuery {
+            name: "errors_per_service".to_string(),
+            stream_type: StreamType::Logs,
+            sql: sql.to_string(),
+            destination: destination.to_string(),
+            interval: 60,
+            delay: 30,
+            enabled: true,
+            watermark: None,
+            pending: None,
+            last_error: None,
+            created_by: "root@example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let sql =
+            "SELECT service, COUNT(*) AS errors FROM app WHERE level = 'error' GROUP BY service";
+        assert!(validate(&query(sql, "app_errors")).is_ok());
+        assert!(validate(&query(sql, "app")).is_err());
+        assert!(validate(&query(sql, "App Errors")).is_err());
+        assert!(validate(&query("", "app_errors")).is_err());
+    }
+
+    #[test]
+    fn test_with_timestamp() {
+        let row = json::json!({"service": "api", "errors": 3});
+        let row = with_timestamp(row.as_object().unwrap().clone(), MINUTE);
+        assert_eq!(row[&CONFIG.common.column_timestamp], MINUTE);
+        assert_eq!(row[WINDOW_COLUMN], MINUTE);
+
+        let row = json::json!({"_timestamp": MINUTE + 10, "errors": 3});
+        let row = with_timestamp(row.as_object().unwrap().clone(), MINUTE);
+        assert_eq!(row["_timestamp"], MINUTE + 10);
+        assert_eq!(row[WINDOW_COLUMN], MINUTE);
+    }
+
+    #[test]
+    fn test_next_windows() {
+        let now = 10 * MINUTE + 45_000_000;
+        // the first run reads the last full window before the delay
+        assert_eq!(
+            next_windows(None, now, 60, 30),
+            vec![(9 * MINUTE, 10 * MINUTE)]
+        );
+        assert_eq!(
+            next_windows(Some(8 * MINUTE), now, 60, 30),
+            vec![(8 * MINUTE, 9 * MINUTE), (9 * MINUTE, 10 * MINUTE)]
+        );
+        assert!(next_windows(Some(10 * MINUTE), now, 60, 30).is_empty());
+        // a window isn't ready until the delay passed its end
+        assert!(next_windows(Some(10 * MINUTE), 11 * MINUTE + 10_000_000, 60, 30).is_empty());
+        assert_eq!(
+            next_windows(Some(0), 1000 * MINUTE, 60, 0).len(),
+            MAX_WINDOWS_PER_RUN
+        );
+    }
+}
@@ -0,0 +1,472 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Correlation rules detecting sequences of events across streams. Every
+//! logs record ingested into a stream used by a rule moves the sequence of
+//! its entity forward, a completed sequence raises a finding which is written
+//! into the `_detections` stream and sent to the destinations of the rule.
+//!
+//! The sequence of an entity is kept in memory by the ingester owning its key
+//! on the consistent hash of the ingesters. The other ingesters forward the
+//! events of the entity to it in batches, so the events are correlated
+//! whichever node ingests them.
+
+use chrono::Utc;
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::cluster::Role,
+    utils::json::{self, Map, Value},
+    RwHashMap, CONFIG,
+};
+use once_cell::sync::Lazy;
+use proto::cluster_rpc;
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request};
+
+use crate::{
+    common::{
+        infra::{cluster, config::CORRELATION_RULES},
+        meta::correlation::{CorrelationRule, Finding, DETECTIONS_STREAM},
+    },
+    service::{alerts, db, error::ServiceError, usage::ingestion_service},
+};
+
+/// Progress of the sequence of an entity, by `org/rule/entity`.
+static SEQUENCES: Lazy<RwHashMap<String, Sequence>> = Lazy::new(Default::default);
+
+/// Events waiting to be forwarded to the ingester owning their sequence, by
+/// `node/org`.
+static FORWARDS: Lazy<RwHashMap<String, Vec<cluster_rpc::CorrelationEvent>>> =
+    Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Sequence {
+    /// Index of the step waiting for events.
+    step: usize,
+    /// Events matched by the current step.
+    hits: usize,
+    /// Events matched by all the steps.
+    events: usize,
+    first_seen: i64,
+    last_seen: i64,
+    /// The sequence starts over after this time, in microseconds.
+    expires_at: i64,
+}
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut rule: CorrelationRule,
+    create: bool,
+) -> Result<(), ServiceError> {
+    if !name.is_empty() {
+        rule.name = name.to_string();
+    }
+    rule.name = rule.name.trim().to_string();
+    validate(&rule).map_err(ServiceError::bad_request)?;
+    for dest in rule.destinations.iter() {
+        if db::alerts::destinations::get(org_id, dest).await.is_err() {
+            return Err(ServiceError::bad_request(format!(
+                "Destination [{dest}] not found"
+            )));
+        }
+    }
+
+    match db::correlation::get(org_id, &rule.name).await {
+        Ok(_) if create => {
+            return Err(ServiceError::bad_request("Correlation rule already exists"));
+        }
+        Err(_) if !create => {
+            return Err(ServiceError::not_found("Correlation rule not found"));
+        }
+        _ => {}
+    }
+    db::correlation::set(org_id, &rule)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<CorrelationRule, ServiceError> {
+    db::correlation::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("Correlation rule not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<CorrelationRule>, ServiceError> {
+    db::correlation::list(org_id)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    if db::correlation::get(org_id, name).await.is_err() {
+        return Err(ServiceError::not_found("Correlation rule not found"));
+    }
+    db::correlation::delete(org_id, name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+fn validate(rule: &CorrelationRule) -> Result<(), String> {
+    if rule.name.is_empty() {
+        return Err("Correlation rule name is required".to_string());
+    }
+    if rule.name.contains('/') {
+        return Err("Correlation rule name cannot contain '/'".to_string());
+    }
+    if rule.entity_field.trim().is_empty() {
+        return Err("Correlation rule entity_field is required".to_string());
+    }
+    if rule.window <= 0 {
+        return Err("Correlation rule window should be greater than 0".to_string());
+    }
+    if rule.steps.is_empty() {
+        return Err("Correlation rule needs at least one step".to_string());
+    }
+    for (i, step) in rule.steps.iter().enumerate() {
+        if step.stream.trim().is_empty() {
+            return Err(format!("Correlation rule step {} has no stream", i + 1));
+        }
+        if step.count == 0 {
+            return Err(format!(
+                "Correlation rule step {} count should be greater than 0",
+                i + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Drops the sequences in progress of the rule `org/rule`.
+pub fn reset_sequences(rule_key: &str) {
+    let prefix = format!("{rule_key}/");
+    SEQUENCES.retain(|k, _| !k.starts_with(&prefix));
+}
+
+/// Drops the sequences whose window passed, returns how many are left.
+pub fn clean_expired() -> usize {
+    let now = Utc::now().timestamp_micros();
+    SEQUENCES.retain(|_, v| v.expires_at >= now);
+    SEQUENCES.len()
+}
+
+/// Moves the sequences of the rules using the stream forward with the record,
+/// called for every logs record before it is written.
+pub async fn observe(org_id: &str, stream_name: &str, record: &Map<String, Value>) {
+    if CORRELATION_RULES.is_empty() {
+        return;
+    }
+    let prefix = format!("{org_id}/");
+    let rules = CORRELATION_RULES
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix) && v.value().uses_stream(stream_name))
+        .map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    if rules.is_empty() {
+        return;
+    }
+    let timestamp = record
+        .get(&CONFIG.common.column_timestamp)
+        .and_then(|v| v.as_i64())
+        .unwrap_or_else(|| Utc::now().timestamp_micros());
+
+    for rule in rules {
+        let entity = match record.get(&rule.entity_field) {
+            None | Some(Value::Null) => continue,
+            Some(Value::String(v)) => v.to_string(),
+            Some(v) => v.to_string(),
+        };
+        let mut matched = Vec::with_capacity(rule.steps.len());
+        for step in rule.steps.iter() {
+            let mut is_match = step.stream == stream_name;
+            for condition in step.conditions.iter() {
+                if !is_match {
+                    break;
+                }
+                is_match = condition.evaluate(record).await;
+            }
+            matched.push(is_match);
+        }
+        if !matched.iter().any(|v| *v) {
+            continue;
+        }
+
+        let key = format!("{org_id}/{}/{entity}", rule.name);
+        match cluster::get_node_from_consistent_hash(&key, &Role::Ingester).await {
+            Some(node) if node != *LOCAL_NODE_UUID => {
+                let mut events = FORWARDS.entry(format!("{node}/{org_id}")).or_default();
+                if events.len() >= CONFIG.limit.correlation_max_sequences {
+                    log::debug!("[CORRELATION] too many events to forward, skip {key}");
+                    continue;
+                }
+                events.push(cluster_rpc::CorrelationEvent {
+                    rule: rule.name.clone(),
+                    entity,
+                    matched,
+                    timestamp,
+                });
+            }
+            _ => observe_local(org_id, rule, entity, &matched, timestamp),
+        }
+    }
+}
+
+/// Moves the sequences owned by this node forward with the events forwarded
+/// by the other ingesters.
+pub fn observe_forwarded(org_id: &str, events: Vec<cluster_rpc::CorrelationEvent>) {
+    for event in events {
+        let Some(rule) = CORRELATION_RULES
+            .get(&format!("{org_id}/{}", event.rule))
+            .map(|v| v.value().clone())
+        else {
+            continue;
+        };
+        // the rule changed since the event was matched
+        if event.matched.len() != rule.steps.len() {
+            continue;
+        }
+        observe_local(org_id, rule, event.entity, &event.matched, event.timestamp);
+    }
+}
+
+fn observe_local(
+    org_id: &str,
+    rule: CorrelationRule,
+    entity: String,
+    matched: &[bool],
+    timestamp: i64,
+) {
+    let key = format!("{org_id}/{}/{entity}", rule.name);
+    if !SEQUENCES.contains_key(&key) && SEQUENCES.len() >= CONFIG.limit.correlation_max_sequences {
+        log::debug!("[CORRELATION] too many sequences in progress, skip {key}");
+        return;
+    }
+    let finding = {
+        let mut sequence = SEQUENCES.entry(key.clone()).or_default();
+        if !advance(&rule, &mut sequence, matched, timestamp) {
+            None
+        } else {
+            Some(Finding {
+                rule: rule.name.clone(),
+                description: rule.description.clone(),
+                entity_field: rule.entity_field.clone(),
+                entity,
+                first_seen: sequence.first_seen,
+                last_seen: sequence.last_seen,
+                steps: rule
+                    .steps
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                events: sequence.events,
+            })
+        }
+    };
+    if let Some(finding) = finding {
+        SEQUENCES.remove(&key);
+        let org_id = org_id.to_string();
+        // the finding is ingested too, don't hold up the current request
+        tokio::task::spawn(async move { report(&org_id, &rule, finding).await });
+    }
+}
+
+/// Sends the events waiting for other ingesters, the events of a node which
+/// can't be reached are dropped.
+pub async fn forward() {
+    let keys = FORWARDS.iter().map(|v| v.key().clone()).collect::<Vec<_>>();
+    for key in keys {
+        let Some((_, events)) = FORWARDS.remove(&key) else {
+            continue;
+        };
+        let (node, org_id) = key.split_once('/').unwrap();
+        if let Err(e) = send_events(node, org_id, events).await {
+            log::error!("[CORRELATION] forward events to node {node} error: {e}");
+        }
+    }
+}
+
+async fn send_events(
+    node: &str,
+    org_id: &str,
+    events: Vec<cluster_rpc::CorrelationEvent>,
+) -> Result<(), anyhow::Error> {
+    let Some(node) = cluster::get_node_by_uuid(node).await else {
+        return Err(anyhow::anyhow!("node is gone"));
+    };
+    let token: MetadataValue<_> = cluster::get_internal_grpc_token().parse()?;
+    let org_id: MetadataValue<_> = org_id.parse()?;
+    let channel = Channel::from_shared(node.grpc_addr)?.connect().await?;
+    let mut client = cluster_rpc::correlation_client::CorrelationClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(CONFIG.grpc.org_header_key.as_str(), org_id.clone());
+            Ok(req)
+        },
+    );
+    client = client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024);
+    client
+        .observe(cluster_rpc::CorrelationRequest { events })
+        .await?;
+    Ok(())
+}
+
+/// Moves the sequence forward with an event matching the steps flagged in
+/// `matched`, returns true when the last step is complete.
+fn advance(
+    rule: &CorrelationRule,
+    sequence: &mut Sequence,
+    matched: &[bool],
+    timestamp: i64,
+) -> bool {
+    if sequence.events > 0 && timestamp > sequence.expires_at {
+        *sequence = Sequence::default();
+    }
+    if !matched[sequence.step] {
+        return false;
+    }
+    if sequence.events == 0 {
+        sequence.first_seen = timestamp;
+        sequence.expires_at = timestamp + rule.window * 1_000_000;
+    }
+    sequence.hits += 1;
+    sequence.events += 1;
+    sequence.last_seen = timestamp;
+    if sequence.hits >= rule.steps[sequence.step].count {
+        sequence.step += 1;
+        sequence.hits = 0;
+    }
+    sequence.step == rule.steps.len()
+}
+
+async fn report(org_id: &str, rule: &CorrelationRule, finding: Finding) {
+    let mut record = match json::to_value(&finding) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    record.insert(
+        CONFIG.common.column_timestamp.to_string(),
+        finding.last_seen.into(),
+    );
+    let req = cluster_rpc::UsageRequest {
+        stream_name: DETECTIONS_STREAM.to_owned(),
+        data: Some(cluster_rpc::UsageData::from(vec![Value::Object(record)])),
+    };
+    if let Err(e) = ingestion_service::ingest(org_id, req).await {
+        log::error!(
+            "[CORRELATION] ingest finding of rule {org_id}/{} error: {e}",
+            rule.name
+        );
+    }
+
+    let msg = json::to_string(&finding).unwrap();
+    let subject = format!("Correlation - {}", rule.name);
+    for dest in rule.destinations.iter() {
+        if let Err(e) = alerts::send_raw_notification(org_id, dest, &subject, msg.clone()).await {
+            log::error!(
+                "[CORRELATION] send finding of rule {org_id}/{} to {dest} error: {e}",
+                rule.name
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::correlation::CorrelationStep;
+
+    fn rule() -> CorrelationRule {
+        let step = |name: &str, count| CorrelationStep {
+            name: name.to_string(),
+            stream: "auth".to_string(),
+            conditions: vec![],
+            count,
+        };
+        CorrelationRule {
+            name: "brute_force".to_string(),
+            description: "".to_string(),
+            entity_field: "client_ip".to_string(),
+            window: 60,
+            steps: vec![step("failed", 3), step("success", 1)],
+            destinations: vec![],
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_advance() {
+        let rule = rule();
+        let failed = [true, false];
+        let success = [false, true];
+        let mut sequence = Sequence::default();
+
+        // a success before enough failures doesn't count
+        assert!(!advance(&rule, &mut sequence, &failed, 1_000_000));
+        assert!(!advance(&rule, &mut sequence, &success, 2_000_000));
+        assert!(!advance(&rule, &mut sequence, &failed, 3_000_000));
+        assert!(!advance(&rule, &mut sequence, &failed, 4_000_000));
+        assert_eq!(sequence.step, 1);
+        assert!(advance(&rule, &mut sequence, &success, 5_000_000));
+        assert_eq!(sequence.events, 4);
+        assert_eq!(sequence.first_seen, 1_000_000);
+        assert_eq!(sequence.last_seen, 5_000_000);
+
+        // the window starts at the first event
+        let mut sequence = Sequence::default();
+        for ts in [1, 2, 3] {
+            advance(&rule, &mut sequence, &failed, ts * 1_000_000);
+        }
+        assert!(!advance(&rule, &mut sequence, &success, 120_000_000));
+        assert_eq!(sequence, Sequence::default());
+    }
+
+    #[test]
+    fn test_observe_forwarded() {
+        let org_id = "test_observe_forwarded";
+        CORRELATION_RULES.insert(format!("{org_id}/brute_force"), rule());
+        let event = |matched: Vec<bool>| cluster_rpc::CorrelationEvent {
+            rule: "brute_force".to_string(),
+            entity: "10.0.0.1".to_string(),
+            matched,
+            timestamp: 1_000_000,
+        };
+        let key = format!("{org_id}/brute_force/10.0.0.1");
+        // matched against another version of the rule
+        observe_forwarded(org_id, vec![event(vec![true])]);
+        assert!(!SEQUENCES.contains_key(&key));
+        observe_forwarded(org_id, vec![event(vec![true, false]); 2]);
+        assert_eq!(SEQUENCES.get(&key).unwrap().hits, 2);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&rule()).is_ok());
+        let mut invalid = rule();
+        invalid.window = 0;
+        assert!(validate(&invalid).is_err());
+        let mut invalid = rule();
+        invalid.steps[0].count = 0;
+        assert!(validate(&invalid).is_err());
+        let mut invalid = rule();
+        invalid.steps.clear();
+        assert!(validate(&invalid).is_err());
+    }
+}
@@ -13,21 +13,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::collections::HashMap;
 
-use actix_web::{http, HttpResponse};
 use config::ider;
 
 use crate::{
     common::{
         meta::{
             authz::Authz,
-            dashboards::{Folder, FolderList, DEFAULT_FOLDER},
-            http::HttpResponse as MetaHttpResponse,
+            dashboards::{Folder, DEFAULT_FOLDER},
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::db,
+    service::{db, error::ServiceError},
 };
 
 #[tracing::instrument(skip(folder))]
@@ -35,41 +33,31 @@ pub async fn save_folder(
     org_id: &str,
     mut folder: Folder,
     is_internal: bool,
-) -> Result<HttpResponse, Error> {
+) -> Result<Folder, ServiceError> {
     folder.name = folder.name.trim().to_string();
     if folder.name.is_empty() {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
-                http::StatusCode::BAD_REQUEST.into(),
-                "folder name not allow empty".to_string(),
-            )),
-        );
+        return Err(ServiceError::bad_request("folder name not allow empty"));
     }
 
     if !is_internal && folder.folder_id == DEFAULT_FOLDER {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
-                http::StatusCode::BAD_REQUEST.into(),
-                "can't update default Dashboard folder".to_string(),
-            )),
-        );
+        return Err(ServiceError::bad_request(
+            "can't update default Dashboard folder",
+        ));
     }
     if folder.folder_id != DEFAULT_FOLDER {
         folder.folder_id = ider::generate();
+    } else {
+        folder.parent_id.clear();
     }
-
-    match db::dashboards::folders::put(org_id, folder).await {
-        Ok(folder) => {
-            set_ownership(org_id, "folders", Authz::new(&folder.folder_id)).await;
-            Ok(HttpResponse::Ok().json(folder))
-        }
-        Err(error) => Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                error.to_string(),
-            )),
-        ),
+    if !folder.parent_id.is_empty() {
+        let folders = folder_map(org_id).await?;
+        check_parent(&folders, &folder.folder_id, &folder.parent_id)
+            .map_err(ServiceError::bad_request)?;
     }
+
+    let folder = db::dashboards::folders::put(org_id, folder).await?;
+    set_ownership(org_id, "folders", folder_authz(&folder)).await;
+    Ok(folder)
 }
 
 #[tracing::instrument(skip(folder))]
@@ -77,107 +65,218 @@ pub async fn update_folder(
     org_id: &str,
     folder_id: &str,
     mut folder: Folder,
-) -> Result<HttpResponse, Error> {
+) -> Result<(), ServiceError> {
     if folder_id.eq(DEFAULT_FOLDER) {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
-                http::StatusCode::BAD_REQUEST.into(),
-                "can't update default Dashboard folder".to_string(),
-            )),
-        );
+        return Err(ServiceError::bad_request(
+            "can't update default Dashboard folder",
+        ));
     }
     folder.folder_id = folder_id.to_string();
-
-    if let Err(error) = db::dashboards::folders::put(org_id, folder).await {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::message(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                error.to_string(),
-            )),
-        );
+    // the folder is moved by move_folder, keep its place
+    match db::dashboards::folders::get(org_id, folder_id).await {
+        Ok(existing) => folder.parent_id = existing.parent_id,
+        Err(_) => return Err(not_found()),
     }
 
-    Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
-        http::StatusCode::OK.into(),
-        "Dashboard folder updated".to_string(),
-    )))
+    db::dashboards::folders::put(org_id, folder).await?;
+    Ok(())
 }
 
+/// Lists the folders the user is permitted to, those under `parent` if set.
 #[tracing::instrument()]
 pub async fn list_folders(
     org_id: &str,
     permitted_folders: Option<Vec<String>>,
-) -> Result<HttpResponse, Error> {
-    if let Ok(folders) = db::dashboards::folders::list(org_id).await {
-        let filtered = match permitted_folders {
-            Some(permitted_folders) => {
-                if permitted_folders.contains(&format!("{}:_all_{}", "dfolder", org_id)) {
-                    folders
-                } else {
-                    folders
-                        .into_iter()
-                        .filter(|folder_loc| {
-                            permitted_folders
-                                .contains(&format!("{}:{}", "dfolder", folder_loc.folder_id))
-                        })
-                        .collect::<Vec<_>>()
-                }
+    parent: Option<&str>,
+) -> Result<Vec<Folder>, ServiceError> {
+    let folders = folder_map(org_id).await?;
+    let mut filtered = match permitted_folders {
+        Some(permitted_folders) => {
+            if permitted_folders.contains(&format!("{}:_all_{}", "dfolder", org_id)) {
+                folders.values().cloned().collect::<Vec<_>>()
+            } else {
+                folders
+                    .values()
+                    .filter(|folder_loc| {
+                        is_permitted(&folders, &folder_loc.folder_id, &permitted_folders)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>()
             }
-            None => folders,
-        };
-
-        Ok(HttpResponse::Ok().json(FolderList { list: filtered }))
-    } else {
-        Ok(HttpResponse::Ok().json(FolderList { list: vec![] }))
+        }
+        None => folders.values().cloned().collect::<Vec<_>>(),
+    };
+    if let Some(parent) = parent {
+        filtered.retain(|f| f.parent_id == parent);
     }
+    filtered.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(filtered)
 }
 
 #[tracing::instrument()]
-pub async fn get_folder(org_id: &str, folder_id: &str) -> Result<HttpResponse, Error> {
-    let resp = if let Ok(folder) = db::dashboards::folders::get(org_id, folder_id).await {
-        HttpResponse::Ok().json(folder)
-    } else {
-        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
-            http::StatusCode::NOT_FOUND.into(),
-            "Dashboard folder not found".to_string(),
-        )));
-    };
-    Ok(resp)
+pub async fn get_folder(org_id: &str, folder_id: &str) -> Result<Folder, ServiceError> {
+    db::dashboards::folders::get(org_id, folder_id)
+        .await
+        .map_err(|_| not_found())
 }
 
 #[tracing::instrument()]
-pub async fn delete_folder(org_id: &str, folder_id: &str) -> Result<HttpResponse, Error> {
-    let dashboards = db::dashboards::list(org_id, folder_id).await.unwrap();
+pub async fn delete_folder(org_id: &str, folder_id: &str) -> Result<(), ServiceError> {
+    let dashboards = db::dashboards::list(org_id, folder_id).await?;
     if !dashboards.is_empty() {
-        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-            http::StatusCode::BAD_REQUEST.into(),
-            "Dashboard folder contains dashboards, please move/delete dashboards from folder "
-                .to_string(),
-        )));
+        return Err(ServiceError::bad_request(
+            "Dashboard folder contains dashboards, please move/delete dashboards from folder ",
+        ));
+    }
+    let folders = folder_map(org_id).await?;
+    if folders.values().any(|f| f.parent_id == folder_id) {
+        return Err(ServiceError::bad_request(
+            "Dashboard folder contains folders, please move/delete them first",
+        ));
     }
 
-    if db::dashboards::folders::get(org_id, folder_id)
-        .await
-        .is_err()
-    {
-        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
-            http::StatusCode::NOT_FOUND.into(),
-            "Dashboard folder not found".to_string(),
-        )));
-    }
-    match db::dashboards::folders::delete(org_id, folder_id).await {
-        Ok(_) => {
-            remove_ownership(org_id, "folders", Authz::new(folder_id)).await;
-            Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
-                http::StatusCode::OK.into(),
-                "Dashboard folder deleted".to_string(),
-            )))
+    let Some(folder) = folders.get(folder_id) else {
+        return Err(not_found());
+    };
+    db::dashboards::folders::delete(org_id, folder_id).await?;
+    remove_ownership(org_id, "folders", folder_authz(folder)).await;
+    Ok(())
+}
+
+/// Puts the folder under `parent_id`, at the top level if it is empty.
+#[tracing::instrument()]
+pub async fn move_folder(
+    org_id: &str,
+    folder_id: &str,
+    parent_id: &str,
+) -> Result<(), ServiceError> {
+    if folder_id.eq(DEFAULT_FOLDER) {
+        return Err(ServiceError::bad_request(
+            "can't move default Dashboard folder",
+        ));
+    }
+    let folders = folder_map(org_id).await?;
+    let Some(folder) = folders.get(folder_id) else {
+        return Err(not_found());
+    };
+    check_parent(&folders, folder_id, parent_id).map_err(ServiceError::bad_request)?;
+    let moved = Folder {
+        parent_id: parent_id.to_string(),
+        ..folder.clone()
+    };
+    let moved = db::dashboards::folders::put(org_id, moved).await?;
+    // the folder inherits the permissions of its new parent only
+    remove_ownership(org_id, "folders", folder_authz(folder)).await;
+    set_ownership(org_id, "folders", folder_authz(&moved)).await;
+    Ok(())
+}
+
+/// The permissions of the parent apply to the subfolders.
+fn folder_authz(folder: &Folder) -> Authz {
+    if folder.parent_id.is_empty() {
+        Authz::new(&folder.folder_id)
+    } else {
+        Authz {
+            obj_id: folder.folder_id.clone(),
+            parent_type: "folders".to_owned(),
+            parent: folder.parent_id.clone(),
+        }
+    }
+}
+
+async fn folder_map(org_id: &str) -> Result<HashMap<String, Folder>, anyhow::Error> {
+    Ok(db::dashboards::folders::list(org_id)
+        .await?
+        .into_iter()
+        .map(|f| (f.folder_id.clone(), f))
+        .collect())
+}
+
+/// Checks the folder can be put under the parent: the parent exists and is
+/// neither the folder nor one of its subfolders.
+fn check_parent(
+    folders: &HashMap<String, Folder>,
+    folder_id: &str,
+    parent_id: &str,
+) -> Result<(), String> {
+    let mut current = parent_id;
+    // bounded, in case the stored folders already have a cycle
+    for _ in 0..=folders.len() {
+        if current.is_empty() {
+            return Ok(());
+        }
+        if current == folder_id {
+            return Err("a folder can't be moved into itself or its subfolders".to_string());
+        }
+        match folders.get(current) {
+            Some(folder) => current = &folder.parent_id,
+            None => return Err(format!("parent folder {current} not found")),
+        }
+    }
+    Err("folders have a cycle".to_string())
+}
+
+/// Whether the user is permitted to the folder or to one of its parents.
+fn is_permitted(folders: &HashMap<String, Folder>, folder_id: &str, permitted: &[String]) -> bool {
+    let mut current = folder_id;
+    for _ in 0..=folders.len() {
+        if current.is_empty() {
+            return false;
+        }
+        if permitted.contains(&format!("{}:{}", "dfolder", current)) {
+            return true;
         }
-        Err(e) => Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                e.to_string(),
-            )),
-        ),
+        match folders.get(current) {
+            Some(folder) => current = &folder.parent_id,
+            None => return false,
+        }
+    }
+    false
+}
+
+fn not_found() -> ServiceError {
+    ServiceError::not_found("Dashboard folder not found")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folders() -> HashMap<String, Folder> {
+        [("a", ""), ("b", "a"), ("c", "b"), ("d", "")]
+            .into_iter()
+            .map(|(id, parent)| {
+                (
+                    id.to_string(),
+                    Folder {
+                        folder_id: id.to_string(),
+                        name: id.to_string(),
+                        description: String::new(),
+                        parent_id: parent.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_parent() {
+        let folders = folders();
+        assert!(check_parent(&folders, "c", "").is_ok());
+        assert!(check_parent(&folders, "c", "d").is_ok());
+        assert!(check_parent(&folders, "d", "c").is_ok());
+        assert!(check_parent(&folders, "a", "a").is_err());
+        assert!(check_parent(&folders, "a", "c").is_err());
+        assert!(check_parent(&folders, "a", "x").is_err());
+    }
+
+    #[test]
+    fn test_is_permitted() {
+        let folders = folders();
+        let permitted = vec!["dfolder:b".to_string()];
+        assert!(is_permitted(&folders, "b", &permitted));
+        assert!(is_permitted(&folders, "c", &permitted));
+        assert!(!is_permitted(&folders, "a", &permitted));
+        assert!(!is_permitted(&folders, "d", &permitted));
     }
 }
@@ -22,12 +22,13 @@ use crate::{
     common::{
         meta::{
             authz::Authz,
-            dashboards::{Dashboards, Folder, DEFAULT_FOLDER},
+            dashboards::{has_tags, Dashboards, Folder, DEFAULT_FOLDER},
             http::HttpResponse as MetaHttpResponse,
+            revisions::{ObjectType, RevisionAction},
         },
         utils::auth::{remove_ownership, set_ownership},
     },
-    service::db::dashboards,
+    service::{db::dashboards, revisions},
 };
 
 pub mod folders;
@@ -38,6 +39,7 @@ pub async fn create_dashboard(
     org_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     // NOTE: Overwrite whatever `dashboard_id` the client has sent us
     // If folder is default folder & doesn't exist then create it
@@ -45,7 +47,7 @@ pub async fn create_dashboard(
     match dashboards::folders::get(org_id, folder_id).await {
         Ok(_) => {
             let dashboard_id = ider::generate();
-            match save_dashboard(org_id, &dashboard_id, folder_id, body).await {
+            match save_dashboard(org_id, &dashboard_id, folder_id, body, user_id).await {
                 Ok(res) => {
                     set_ownership(
                         org_id,
@@ -68,10 +70,13 @@ pub async fn create_dashboard(
                     folder_id: DEFAULT_FOLDER.to_string(),
                     name: DEFAULT_FOLDER.to_string(),
                     description: DEFAULT_FOLDER.to_string(),
+                    parent_id: String::new(),
                 };
-                folders::save_folder(org_id, folder, true).await?;
+                if let Err(e) = folders::save_folder(org_id, folder, true).await {
+                    return Ok(e.into());
+                }
                 let dashboard_id = ider::generate();
-                match save_dashboard(org_id, &dashboard_id, folder_id, body).await {
+                match save_dashboard(org_id, &dashboard_id, folder_id, body, user_id).await {
                     Ok(res) => {
                         set_ownership(
                             org_id,
@@ -108,16 +113,24 @@ pub async fn update_dashboard(
     dashboard_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     // Store new dashboard in the database
-    save_dashboard(org_id, dashboard_id, folder_id, body).await
+    save_dashboard(org_id, dashboard_id, folder_id, body, user_id).await
 }
 
+/// Lists the dashboards of the folder which have all the `tags`.
 #[tracing::instrument]
-pub async fn list_dashboards(org_id: &str, folder_id: &str) -> Result<HttpResponse, io::Error> {
-    Ok(HttpResponse::Ok().json(Dashboards {
-        dashboards: dashboards::list(org_id, folder_id).await.unwrap(),
-    }))
+pub async fn list_dashboards(
+    org_id: &str,
+    folder_id: &str,
+    tags: &[String],
+) -> Result<HttpResponse, io::Error> {
+    let mut list = dashboards::list(org_id, folder_id).await.unwrap();
+    if !tags.is_empty() {
+        list.retain(|d| d.v3.as_ref().is_some_and(|d| has_tags(&d.tags, tags)));
+    }
+    Ok(HttpResponse::Ok().json(Dashboards { dashboards: list }))
 }
 
 #[tracing::instrument]
@@ -139,18 +152,25 @@ pub async fn delete_dashboard(
     org_id: &str,
     dashboard_id: &str,
     folder_id: &str,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
-    if dashboards::get(org_id, dashboard_id, folder_id)
-        .await
-        .is_err()
-    {
+    let Ok(dashboard) = dashboards::get(org_id, dashboard_id, folder_id).await else {
         return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
             http::StatusCode::NOT_FOUND.into(),
             "Dashboard not found".to_string(),
         )));
-    }
+    };
     match dashboards::delete(org_id, dashboard_id, folder_id).await {
         Ok(_) => {
+            revisions::record(
+                org_id,
+                ObjectType::Dashboard,
+                &revisions::dashboard_id(folder_id, dashboard_id),
+                user_id,
+                RevisionAction::Deleted,
+                &revisions::dashboard_data(&dashboard),
+            )
+            .await;
             remove_ownership(
                 org_id,
                 "dashboards",
@@ -180,10 +200,20 @@ async fn save_dashboard(
     dashboard_id: &str,
     folder_id: &str,
     body: web::Bytes,
+    user_id: &str,
 ) -> Result<HttpResponse, io::Error> {
     match dashboards::put(org_id, dashboard_id, folder_id, body).await {
         Ok(dashboard) => {
             tracing::info!(dashboard_id, "Dashboard updated");
+            revisions::record(
+                org_id,
+                ObjectType::Dashboard,
+                &revisions::dashboard_id(folder_id, dashboard_id),
+                user_id,
+                RevisionAction::Saved,
+                &revisions::dashboard_data(&dashboard),
+            )
+            .await;
             Ok(HttpResponse::Ok().json(dashboard))
         }
         Err(error) => {
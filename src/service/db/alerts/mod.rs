@@ -23,6 +23,7 @@ use crate::{
 };
 
 pub mod destinations;
+pub mod notify_state;
 pub mod templates;
 
 pub async fn get(
@@ -44,6 +45,21 @@ pub async fn get(
     if value.is_none() { Ok(None) } else { Ok(value) }
 }
 
+/// Reads the alert from the store instead of the cache, the cache can miss the
+/// latest change of the alert.
+pub async fn get_stored(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<Option<Alert>, anyhow::Error> {
+    let key = format!("/alerts/{org_id}/{stream_type}/{stream_name}/{name}");
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Ok(None),
+    }
+}
+
 pub async fn set(
     org_id: &str,
     stream_type: StreamType,
@@ -0,0 +1,62 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::alerts::AlertNotifyState, service::db};
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str, name: &str) -> String {
+    format!("/alert_notify_state/{org_id}/{stream_type}/{stream_name}/{name}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<AlertNotifyState, anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, name);
+    match db::get(&key).await {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Ok(AlertNotifyState::default()),
+    }
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+    state: &AlertNotifyState,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, name);
+    Ok(db::put(
+        &key,
+        json::to_vec(state).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, name);
+    Ok(db::delete(&key, false, db::NO_NEED_WATCH, None).await?)
+}
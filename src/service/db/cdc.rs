@@ -0,0 +1,49 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{ider, utils::json};
+
+use crate::service::db;
+
+/// The changes waiting to be written into the `_cdc` stream, they are kept
+/// with the metadata so that a restart doesn't lose them
+const KEY: &str = "/cdc_events/";
+
+pub async fn put(org_id: &str, record: &json::Value) -> Result<(), anyhow::Error> {
+    // the snowflake ids keep the changes in order
+    let key = format!("{KEY}{org_id}/{}", ider::generate());
+    Ok(db::put(&key, json::to_vec(record)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+/// Returns the pending changes, oldest first, as (key, org_id, record)
+pub async fn list() -> Result<Vec<(String, String, json::Value)>, anyhow::Error> {
+    let ret = db::list(KEY).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let Some((org_id, _)) = item_key.strip_prefix(KEY).unwrap().split_once('/') else {
+            continue;
+        };
+        match json::from_slice(&item_value) {
+            Ok(record) => items.push((item_key.clone(), org_id.to_string(), record)),
+            Err(e) => log::error!("[CDC] error parsing event {item_key}: {e}"),
+        }
+    }
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(items)
+}
+
+pub async fn delete(key: &str) -> Result<(), anyhow::Error> {
+    Ok(db::delete(key, false, db::NO_NEED_WATCH, None).await?)
+}
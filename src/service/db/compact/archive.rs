@@ -0,0 +1,83 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::stream::StreamArchive, service::db};
+
+pub async fn set(org_id: &str, archive: &StreamArchive) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/compact/archive/{org_id}/{}/{}/{}",
+        archive.stream_type, archive.stream_name, archive.id
+    );
+    Ok(db::put(&key, json::to_vec(archive)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    id: &str,
+) -> Result<Option<StreamArchive>, anyhow::Error> {
+    let key = format!("/compact/archive/{org_id}/{stream_type}/{stream_name}/{id}");
+    match db::get(&key).await {
+        Ok(val) => Ok(Some(json::from_slice(&val)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Returns the archives of an organization, oldest first
+pub async fn list(org_id: &str) -> Result<Vec<StreamArchive>, anyhow::Error> {
+    let key = format!("/compact/archive/{org_id}/");
+    let ret = db::list(&key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (_, item_value) in ret {
+        items.push(json::from_slice::<StreamArchive>(&item_value)?);
+    }
+    items.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(items)
+}
+
+#[inline]
+fn mk_delete_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/compact/archive_delete/{org_id}/{stream_type}/{stream_name}")
+}
+
+// the settings are gone once the stream is deleted, the compactor deleting
+// the files learns from this mark that they go to the archive
+pub async fn set_delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_delete_key(org_id, stream_type, stream_name);
+    Ok(db::put(&key, "OK".into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn is_delete(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    db::get(&mk_delete_key(org_id, stream_type, stream_name))
+        .await
+        .is_ok()
+}
+
+pub async fn delete_done(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_delete_key(org_id, stream_type, stream_name);
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
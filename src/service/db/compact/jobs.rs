@@ -0,0 +1,147 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::meta::stream::StreamType;
+
+use crate::service::db;
+
+/// State of the compaction job of one time period of a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    Pending,
+    Running { node: String, expires_at: i64 },
+    Done,
+}
+
+impl From<&str> for JobStatus {
+    fn from(value: &str) -> Self {
+        if value == "done" {
+            return JobStatus::Done;
+        }
+        match value.split_once(';') {
+            Some((node, expires_at)) => JobStatus::Running {
+                node: node.to_string(),
+                expires_at: expires_at.parse().unwrap_or_default(),
+            },
+            None => JobStatus::Pending,
+        }
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Pending => write!(f, ""),
+            JobStatus::Running { node, expires_at } => write!(f, "{node};{expires_at}"),
+            JobStatus::Done => write!(f, "done"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub org_id: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    pub period: i64,
+    pub status: JobStatus,
+}
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str, period: i64) -> String {
+    format!("/compact/jobs/{org_id}/{stream_type}/{stream_name}/{period}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period: i64,
+) -> Option<JobStatus> {
+    let key = mk_key(org_id, stream_type, stream_name, period);
+    match db::get(&key).await {
+        Ok(ret) => Some(JobStatus::from(String::from_utf8_lossy(&ret).as_ref())),
+        Err(_) => None,
+    }
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period: i64,
+    status: &JobStatus,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, period);
+    Ok(db::put(&key, status.to_string().into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    period: i64,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name, period);
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+pub async fn list() -> Result<Vec<Job>, anyhow::Error> {
+    let key = "/compact/jobs/";
+    let ret = db::list(key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let columns = item_key
+            .strip_prefix(key)
+            .unwrap()
+            .split('/')
+            .collect::<Vec<_>>();
+        if columns.len() != 4 {
+            continue;
+        }
+        let Ok(period) = columns[3].parse() else {
+            continue;
+        };
+        items.push(Job {
+            org_id: columns[0].to_string(),
+            stream_type: StreamType::from(columns[1]),
+            stream_name: columns[2].to_string(),
+            period,
+            status: JobStatus::from(String::from_utf8_lossy(&item_value).as_ref()),
+        });
+    }
+    items.sort_by(|a, b| a.period.cmp(&b.period));
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_status() {
+        for status in [
+            JobStatus::Pending,
+            JobStatus::Running {
+                node: "node-1".to_string(),
+                expires_at: 100,
+            },
+            JobStatus::Done,
+        ] {
+            assert_eq!(JobStatus::from(status.to_string().as_str()), status);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{meta::stream::StreamType, utils::json, RwHashMap};
+use once_cell::sync::Lazy;
+
+use crate::{common::meta::stream::LegalHold, service::db};
+
+// key: org_id/stream_type/stream_name
+static CACHE: Lazy<RwHashMap<String, Vec<LegalHold>>> = Lazy::new(Default::default);
+
+#[inline]
+fn mk_stream_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn set(org_id: &str, hold: &LegalHold) -> Result<(), anyhow::Error> {
+    let stream_key = mk_stream_key(org_id, hold.stream_type, &hold.stream_name);
+    let key = format!("/compact/legal_holds/{stream_key}/{}", hold.id);
+    put_cache(&stream_key, hold);
+    Ok(db::put(&key, json::to_vec(hold)?.into(), db::NEED_WATCH, None).await?)
+}
+
+pub async fn delete(org_id: &str, hold: &LegalHold) -> Result<(), anyhow::Error> {
+    let stream_key = mk_stream_key(org_id, hold.stream_type, &hold.stream_name);
+    remove_cache(&stream_key, &hold.id);
+    db::delete_if_exists(
+        &format!("/compact/legal_holds/{stream_key}/{}", hold.id),
+        false,
+        db::NEED_WATCH,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Returns the legal holds of a stream from the cache
+pub fn list_by_stream(org_id: &str, stream_type: StreamType, stream_name: &str) -> Vec<LegalHold> {
+    CACHE
+        .get(&mk_stream_key(org_id, stream_type, stream_name))
+        .map(|v| v.value().clone())
+        .unwrap_or_default()
+}
+
+/// Returns the legal holds of an organization from the cache
+pub fn list(org_id: &str) -> Vec<LegalHold> {
+    let prefix = format!("{org_id}/");
+    let mut holds = CACHE
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix))
+        .flat_map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    holds.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    holds
+}
+
+fn put_cache(stream_key: &str, hold: &LegalHold) {
+    let mut entry = CACHE.entry(stream_key.to_string()).or_default();
+    match entry.iter_mut().find(|v| v.id == hold.id) {
+        Some(v) => *v = hold.clone(),
+        None => entry.push(hold.clone()),
+    }
+}
+
+fn remove_cache(stream_key: &str, id: &str) {
+    if let Some(mut entry) = CACHE.get_mut(stream_key) {
+        entry.retain(|v| v.id != id);
+    }
+    CACHE.remove_if(stream_key, |_, v| v.is_empty());
+}
+
+// key: org_id/stream_type/stream_name/id
+fn split_key(item_key: &str) -> Option<(&str, &str)> {
+    item_key.rsplit_once('/')
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = "/compact/legal_holds/";
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching legal holds");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_legal_holds: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let Some((stream_key, _)) = split_key(item_key) else {
+                    continue;
+                };
+                let item_value = match db::get(&ev.key).await {
+                    Ok(val) => val,
+                    Err(e) => {
+                        log::error!("Error getting value: {}", e);
+                        continue;
+                    }
+                };
+                match json::from_slice::<LegalHold>(&item_value) {
+                    Ok(hold) => put_cache(stream_key, &hold),
+                    Err(e) => log::error!("Error parsing legal hold: {}", e),
+                }
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                if let Some((stream_key, id)) = split_key(item_key) {
+                    remove_cache(stream_key, id);
+                }
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = "/compact/legal_holds/";
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let Some((stream_key, _)) = split_key(item_key) else {
+            continue;
+        };
+        let hold: LegalHold = json::from_slice(&item_value)?;
+        put_cache(stream_key, &hold);
+    }
+    Ok(())
+}
@@ -13,8 +13,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod archive;
 pub mod file_list;
 pub mod files;
+pub mod jobs;
+pub mod legal_holds;
 pub mod organization;
+pub mod priority;
+pub mod rename;
 pub mod retention;
+pub mod rewrite;
 pub mod stats;
+pub mod tombstones;
@@ -0,0 +1,91 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+use hashbrown::HashMap;
+
+use crate::service::db;
+
+fn mk_override_key(org_id: &str, stream_type: &str, stream_name: &str) -> String {
+    format!("/compact/priority/override/{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn set_override(
+    org_id: &str,
+    stream_type: &str,
+    stream_name: &str,
+    priority: i64,
+) -> Result<(), anyhow::Error> {
+    let key = mk_override_key(org_id, stream_type, stream_name);
+    Ok(db::put(&key, priority.to_string().into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn delete_override(
+    org_id: &str,
+    stream_type: &str,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_override_key(org_id, stream_type, stream_name);
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+/// Returns the manual priorities, key: org_id/stream_type/stream_name
+pub async fn list_overrides() -> Result<HashMap<String, i64>, anyhow::Error> {
+    let key = "/compact/priority/override/";
+    let ret = db::list(key).await?;
+    let mut items = HashMap::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let Ok(priority) = String::from_utf8_lossy(&item_value).parse() else {
+            continue;
+        };
+        items.insert(item_key.to_string(), priority);
+    }
+    Ok(items)
+}
+
+/// Saves the query counts of a node, key: org_id/stream_type/stream_name
+pub async fn set_query_counts(
+    node: &str,
+    counts: &HashMap<String, u64>,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/compact/priority/queries/{node}");
+    let val = json::to_vec(counts)?;
+    Ok(db::put(&key, val.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn delete_query_counts(node: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/compact/priority/queries/{node}");
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+/// Returns the query counts of every node, key: node uuid
+pub async fn list_query_counts() -> Result<HashMap<String, HashMap<String, u64>>, anyhow::Error> {
+    let key = "/compact/priority/queries/";
+    let ret = db::list(key).await?;
+    let mut items = HashMap::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let Ok(counts) = json::from_slice(&item_value) else {
+            continue;
+        };
+        items.insert(item_key.to_string(), counts);
+    }
+    Ok(items)
+}
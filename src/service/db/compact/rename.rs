@@ -0,0 +1,186 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{meta::stream::StreamType, utils::json, RwHashMap};
+use infra::errors::{DbError, Error};
+use once_cell::sync::Lazy;
+
+use crate::{common::meta::stream::StreamRenameJob, service::db};
+
+const PREFIX: &str = "/compact/rename/";
+
+/// The pending renames, the stream key of the old name to the stream key of
+/// the new one. The ingesters don't write to either while it's in here.
+static RENAMING: Lazy<RwHashMap<String, String>> = Lazy::new(Default::default);
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("{PREFIX}{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Option<StreamRenameJob>, anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    match db::get(&key).await {
+        Ok(ret) => Ok(Some(json::from_slice(&ret)?)),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    job: &StreamRenameJob,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let value = json::to_vec(job)?;
+    cache_job(&key, job);
+    Ok(db::put(&key, value.into(), db::NEED_WATCH, None).await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    db::delete_if_exists(&key, false, db::NEED_WATCH).await?;
+    RENAMING.remove(key.strip_prefix(PREFIX).unwrap());
+    Ok(())
+}
+
+/// Whether the stream is renamed or is the new name of a pending rename.
+pub fn is_renaming(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    if RENAMING.is_empty() {
+        return false;
+    }
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    RENAMING.contains_key(&key) || RENAMING.iter().any(|v| v.value() == &key)
+}
+
+fn cache_job(db_key: &str, job: &StreamRenameJob) {
+    let key = db_key.strip_prefix(PREFIX).unwrap();
+    let Some((prefix, _)) = key.rsplit_once('/') else {
+        return;
+    };
+    RENAMING.insert(key.to_string(), format!("{prefix}/{}", job.new_name));
+}
+
+/// Lists the pending renames as (org_id, stream_type, old name, job), a bad
+/// value is skipped so it doesn't block the other renames.
+pub async fn list() -> Result<Vec<(String, StreamType, String, StreamRenameJob)>, anyhow::Error> {
+    let key = PREFIX;
+    let ret = db::list(key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let columns = item_key
+            .strip_prefix(key)
+            .unwrap()
+            .split('/')
+            .collect::<Vec<_>>();
+        if columns.len() != 3 {
+            continue;
+        }
+        let job: StreamRenameJob = match json::from_slice(&item_value) {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("[STREAM] invalid rename job {item_key}: {e}");
+                continue;
+            }
+        };
+        items.push((
+            columns[0].to_string(),
+            StreamType::from(columns[1]),
+            columns[2].to_string(),
+            job,
+        ));
+    }
+    Ok(items)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(PREFIX).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching stream renames");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_stream_renames: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_value: StreamRenameJob = match db::get(&ev.key).await {
+                    Ok(val) => match json::from_slice(&val) {
+                        Ok(val) => val,
+                        Err(e) => {
+                            log::error!("[STREAM] invalid rename job {}: {e}", ev.key);
+                            continue;
+                        }
+                    },
+                    Err(e) => {
+                        log::error!("Error getting value: {}", e);
+                        continue;
+                    }
+                };
+                cache_job(&ev.key, &item_value);
+            }
+            db::Event::Delete(ev) => {
+                RENAMING.remove(ev.key.strip_prefix(PREFIX).unwrap());
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    for (org_id, stream_type, stream_name, job) in list().await? {
+        cache_job(&mk_key(&org_id, stream_type, &stream_name), &job);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_renaming() {
+        let job = StreamRenameJob {
+            new_name: "new_logs".to_string(),
+            ..Default::default()
+        };
+        let key = mk_key("rename_org", StreamType::Logs, "old_logs");
+        cache_job(&key, &job);
+        assert!(is_renaming("rename_org", StreamType::Logs, "old_logs"));
+        assert!(is_renaming("rename_org", StreamType::Logs, "new_logs"));
+        assert!(!is_renaming("rename_org", StreamType::Metrics, "old_logs"));
+        assert!(!is_renaming("rename_org", StreamType::Logs, "other"));
+
+        RENAMING.remove(key.strip_prefix(PREFIX).unwrap());
+        assert!(!is_renaming("rename_org", StreamType::Logs, "old_logs"));
+        assert!(!is_renaming("rename_org", StreamType::Logs, "new_logs"));
+    }
+}
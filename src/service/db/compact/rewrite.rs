@@ -0,0 +1,80 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::stream::RewriteJob, service::db};
+
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/compact/rewrite/{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Option<RewriteJob>, anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    match db::get(&key).await {
+        Ok(ret) => Ok(Some(json::from_slice(&ret)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    job: &RewriteJob,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    Ok(db::put(&key, json::to_vec(job)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    db::delete_if_exists(&key, false, db::NO_NEED_WATCH)
+        .await
+        .map_err(Into::into)
+}
+
+/// Returns all the rewrite jobs, as (org_id, stream_type, stream_name, job)
+pub async fn list() -> Result<Vec<(String, StreamType, String, RewriteJob)>, anyhow::Error> {
+    let key = "/compact/rewrite/";
+    let ret = db::list(key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let columns = item_key
+            .strip_prefix(key)
+            .unwrap()
+            .split('/')
+            .collect::<Vec<_>>();
+        if columns.len() != 3 {
+            continue;
+        }
+        let job: RewriteJob = json::from_slice(&item_value)?;
+        items.push((
+            columns[0].to_string(),
+            StreamType::from(columns[1]),
+            columns[2].to_string(),
+            job,
+        ));
+    }
+    Ok(items)
+}
@@ -0,0 +1,147 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{meta::stream::StreamType, utils::json, RwHashMap};
+use once_cell::sync::Lazy;
+
+use crate::{common::meta::stream::Tombstone, service::db};
+
+// key: org_id/stream_type/stream_name
+static CACHE: Lazy<RwHashMap<String, Vec<Tombstone>>> = Lazy::new(Default::default);
+
+#[inline]
+fn mk_stream_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("{org_id}/{stream_type}/{stream_name}")
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    tombstone: &Tombstone,
+) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/compact/tombstones/{}/{}",
+        mk_stream_key(org_id, stream_type, stream_name),
+        tombstone.id
+    );
+    put_cache(&mk_stream_key(org_id, stream_type, stream_name), tombstone);
+    Ok(db::put(&key, json::to_vec(tombstone)?.into(), db::NEED_WATCH, None).await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    id: &str,
+) -> Result<(), anyhow::Error> {
+    let stream_key = mk_stream_key(org_id, stream_type, stream_name);
+    remove_cache(&stream_key, id);
+    db::delete_if_exists(
+        &format!("/compact/tombstones/{stream_key}/{id}"),
+        false,
+        db::NEED_WATCH,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Returns the tombstones of a stream from the cache
+pub fn list_by_stream(org_id: &str, stream_type: StreamType, stream_name: &str) -> Vec<Tombstone> {
+    CACHE
+        .get(&mk_stream_key(org_id, stream_type, stream_name))
+        .map(|v| v.value().clone())
+        .unwrap_or_default()
+}
+
+fn put_cache(stream_key: &str, tombstone: &Tombstone) {
+    let mut entry = CACHE.entry(stream_key.to_string()).or_default();
+    match entry.iter_mut().find(|v| v.id == tombstone.id) {
+        Some(v) => *v = tombstone.clone(),
+        None => entry.push(tombstone.clone()),
+    }
+}
+
+fn remove_cache(stream_key: &str, id: &str) {
+    if let Some(mut entry) = CACHE.get_mut(stream_key) {
+        entry.retain(|v| v.id != id);
+    }
+    CACHE.remove_if(stream_key, |_, v| v.is_empty());
+}
+
+// key: org_id/stream_type/stream_name/id
+fn split_key(item_key: &str) -> Option<(&str, &str)> {
+    item_key.rsplit_once('/')
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = "/compact/tombstones/";
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching stream tombstones");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_stream_tombstones: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let Some((stream_key, _)) = split_key(item_key) else {
+                    continue;
+                };
+                let item_value = match db::get(&ev.key).await {
+                    Ok(val) => val,
+                    Err(e) => {
+                        log::error!("Error getting value: {}", e);
+                        continue;
+                    }
+                };
+                match json::from_slice::<Tombstone>(&item_value) {
+                    Ok(tombstone) => put_cache(stream_key, &tombstone),
+                    Err(e) => log::error!("Error parsing tombstone: {}", e),
+                }
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                if let Some((stream_key, id)) = split_key(item_key) {
+                    remove_cache(stream_key, id);
+                }
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = "/compact/tombstones/";
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let Some((stream_key, _)) = split_key(item_key) else {
+            continue;
+        };
+        let tombstone: Tombstone = json::from_slice(&item_value)?;
+        put_cache(stream_key, &tombstone);
+    }
+    Ok(())
+}
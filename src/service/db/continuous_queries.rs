@@ -0,0 +1,79 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::continuous_queries::ContinuousQuery, service::db};
+
+pub async fn get(org_id: &str, name: &str) -> Result<ContinuousQuery, anyhow::Error> {
+    let key = format!("/continuous_queries/{org_id}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, query: &ContinuousQuery, create: bool) -> Result<(), anyhow::Error> {
+    set_without_updating_trigger(org_id, query).await?;
+    let trigger = db::scheduler::Trigger {
+        org: org_id.to_string(),
+        module: db::scheduler::TriggerModule::ContinuousQuery,
+        module_key: query.name.clone(),
+        next_run_at: chrono::Utc::now().timestamp_micros(),
+        ..Default::default()
+    };
+    let ret = if create {
+        db::scheduler::push(trigger).await
+    } else {
+        db::scheduler::update_trigger(trigger).await
+    };
+    if let Err(e) = ret {
+        log::error!("Failed to save continuous query trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn set_without_updating_trigger(
+    org_id: &str,
+    query: &ContinuousQuery,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/continuous_queries/{org_id}/{}", query.name);
+    Ok(db::put(
+        &key,
+        json::to_vec(query).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/continuous_queries/{org_id}/{name}");
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    if let Err(e) =
+        db::scheduler::delete(org_id, db::scheduler::TriggerModule::ContinuousQuery, name).await
+    {
+        log::error!("Failed to delete continuous query trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<ContinuousQuery>, anyhow::Error> {
+    let key = format!("/continuous_queries/{org_id}/");
+    let mut items: Vec<ContinuousQuery> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
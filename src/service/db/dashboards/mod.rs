@@ -17,7 +17,7 @@ use actix_web::web;
 use config::utils::json;
 
 use crate::{
-    common::meta::dashboards::{v1, v2, v3, Dashboard, DashboardVersion},
+    common::meta::dashboards::{normalize_tags, v1, v2, v3, Dashboard, DashboardVersion},
     service::db,
 };
 
@@ -104,6 +104,7 @@ pub(crate) async fn put(
             return Err(anyhow::anyhow!("Dashboard should have title"));
         };
         dash.dashboard_id = dashboard_id.to_string();
+        dash.tags = normalize_tags(std::mem::take(&mut dash.tags));
         match db::put(&key, json::to_vec(&dash)?.into(), db::NO_NEED_WATCH, None).await {
             Ok(_) => Ok(Dashboard {
                 v3: Some(dash),
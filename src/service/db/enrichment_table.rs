@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use chrono::Utc;
 use config::{
@@ -23,7 +23,10 @@ use config::{
 use infra::cache::stats;
 use vrl::prelude::NotNan;
 
-use crate::service::search as SearchService;
+use crate::{
+    common::meta::enrichment_table::EnrichmentTableSource,
+    service::{db, search as SearchService},
+};
 
 pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, anyhow::Error> {
     let stats = stats::get_stream_stats(org_id, name, StreamType::EnrichmentTables);
@@ -89,3 +92,93 @@ fn convert_to_vrl(value: &json::Value) -> vrl::value::Value {
         ),
     }
 }
+
+pub async fn get_source(org_id: &str, name: &str) -> Result<EnrichmentTableSource, anyhow::Error> {
+    let key = format!("/enrichment_table_sources/{org_id}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set_source(org_id: &str, source: &EnrichmentTableSource) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_sources/{org_id}/{}", source.name);
+    Ok(db::put(
+        &key,
+        json::to_vec(source).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete_source(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/enrichment_table_sources/{org_id}/{name}");
+    Ok(db::delete(&key, false, db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn list_sources(org_id: &str) -> Result<Vec<EnrichmentTableSource>, anyhow::Error> {
+    let key = format!("/enrichment_table_sources/{org_id}/");
+    let mut items: Vec<EnrichmentTableSource> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
+
+/// All the sources, keyed by org_id/name.
+pub async fn list_all_sources() -> Result<Vec<(String, EnrichmentTableSource)>, anyhow::Error> {
+    let key = "/enrichment_table_sources/";
+    let mut items = Vec::new();
+    for (item_key, item_value) in db::list(key).await? {
+        let item_key = item_key.strip_prefix(key).unwrap().to_string();
+        items.push((item_key, json::from_slice(&item_value)?));
+    }
+    Ok(items)
+}
+
+pub async fn watch_sources() -> Result<(), anyhow::Error> {
+    let key = "/enrichment_table_sources/";
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching enrichment table sources");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_enrichment_table_sources: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: EnrichmentTableSource = if config::CONFIG.common.meta_store_external
+                {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+                let Some((org_id, _)) = item_key.split_once('/') else {
+                    continue;
+                };
+                crate::service::enrichment_table::source::swap_table(org_id, &item_value).await;
+            }
+            db::Event::Delete(_) => {}
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
@@ -15,6 +15,7 @@
 
 use std::io::{BufRead, BufReader};
 
+use chrono::Utc;
 use config::{
     meta::stream::{FileKey, FileMeta, StreamType},
     utils::{
@@ -34,7 +35,7 @@ pub static BROADCAST_QUEUE: Lazy<RwLock<Vec<FileKey>>> =
     Lazy::new(|| RwLock::new(Vec::with_capacity(2048)));
 
 pub async fn set(key: &str, meta: Option<FileMeta>, deleted: bool) -> Result<(), anyhow::Error> {
-    let (_stream_key, date_key, _file_name) = parse_file_key_columns(key)?;
+    let (stream_key, date_key, _file_name) = parse_file_key_columns(key)?;
     let file_data = FileKey::new(key, meta.clone().unwrap_or_default(), deleted);
 
     // write into file_list storage
@@ -62,6 +63,29 @@ pub async fn set(key: &str, meta: Option<FileMeta>, deleted: bool) -> Result<(),
         file.write(write_buf.as_ref()).await;
     }
 
+    // the cached search results only cover data older than the cache delay,
+    // a late file lands in them
+    if let Some(meta) = meta
+        .as_ref()
+        .filter(|_| CONFIG.limit.query_result_cache_enabled)
+    {
+        let delay = CONFIG.limit.query_result_cache_delay * 60 * 1_000_000;
+        let columns = stream_key.splitn(3, '/').collect::<Vec<_>>();
+        if meta.min_ts < Utc::now().timestamp_micros() - delay && columns.len() == 3 {
+            if let Err(e) = super::super::query_cache::invalidate(
+                columns[0],
+                StreamType::from(columns[1]),
+                columns[2],
+                meta.min_ts,
+                meta.max_ts + 1,
+            )
+            .await
+            {
+                log::error!("[FILE_LIST] invalidate query cache for {stream_key} failed: {e}");
+            }
+        }
+    }
+
     // notify other nodes
     if !CONFIG.common.meta_store_external || CONFIG.memory_cache.cache_latest_files {
         let mut q = BROADCAST_QUEUE.write().await;
@@ -0,0 +1,256 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Daily manifests of the file_list.
+//!
+//! A manifest rolls up every file_list file of a finished day into a single
+//! object: the first line is a [ManifestHeader] with the source files and the
+//! stats of each stream, the following lines are the live file keys. Nodes
+//! load the manifest on startup and only read the file_list files written
+//! after it as deltas.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufRead, BufReader, Write},
+};
+
+use bytes::Buf;
+use chrono::Utc;
+use config::{
+    meta::stream::{FileKey, StreamStats},
+    utils::{json, parquet::parse_file_key_columns},
+    CONFIG,
+};
+use infra::{file_list as infra_file_list, storage};
+use serde::{Deserialize, Serialize};
+
+const MANIFEST_PREFIX: &str = "file_list_manifest/";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestHeader {
+    /// The day of the manifest, eg: 2023/06/26
+    pub day: String,
+    pub created_at: i64,
+    /// The file_list files rolled up into the manifest
+    pub sources: Vec<String>,
+    pub files: usize,
+    /// The stats of the files of each stream, keyed by org/stream_type/stream
+    pub stats: BTreeMap<String, StreamStats>,
+}
+
+fn manifest_key(day: &str) -> String {
+    format!("{MANIFEST_PREFIX}{day}/manifest.json.zst")
+}
+
+/// Returns the day of a file_list file, eg:
+/// file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst -> 2023/06/26
+pub fn file_list_day(file: &str) -> Option<String> {
+    let columns = file.split('/').collect::<Vec<_>>();
+    if columns.len() < 6 || columns[0] != "file_list" {
+        return None;
+    }
+    Some(format!("{}/{}/{}", columns[1], columns[2], columns[3]))
+}
+
+/// Returns the hour of a file_list file, eg:
+/// file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst -> 2023/06/26/07
+fn file_list_hour(file: &str) -> Option<String> {
+    let columns = file.split('/').collect::<Vec<_>>();
+    if columns.len() < 6 || columns[0] != "file_list" {
+        return None;
+    }
+    Some(format!(
+        "{}/{}/{}/{}",
+        columns[1], columns[2], columns[3], columns[4]
+    ))
+}
+
+/// Lists the days which have a manifest
+pub async fn list_days() -> Result<Vec<String>, anyhow::Error> {
+    let files = storage::list(MANIFEST_PREFIX).await?;
+    let mut days = files
+        .iter()
+        .filter_map(|file| {
+            file.strip_prefix(MANIFEST_PREFIX)
+                .and_then(|v| v.strip_suffix("/manifest.json.zst"))
+                .map(|v| v.to_string())
+        })
+        .collect::<Vec<_>>();
+    days.sort();
+    Ok(days)
+}
+
+/// Reads the live file keys of the file_list files
+async fn read_file_keys(files: &[&String]) -> Result<HashMap<String, FileKey>, anyhow::Error> {
+    let mut file_keys: HashMap<String, FileKey> = HashMap::new();
+    for file in files {
+        let data = storage::get(file).await?;
+        let uncompress = zstd::decode_all(data.reader())?;
+        for line in BufReader::new(uncompress.reader()).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let item: FileKey = json::from_slice(line.as_bytes())?;
+            match file_keys.get(&item.key) {
+                Some(_) if !item.deleted => {}
+                _ => {
+                    file_keys.insert(item.key.clone(), item);
+                }
+            }
+        }
+        tokio::task::yield_now().await;
+    }
+    file_keys.retain(|_, item| !item.deleted);
+    Ok(file_keys)
+}
+
+/// Rolls up the file_list files of the day into its manifest.
+///
+/// A file key is added and deleted in the file_list of its own hour, so the
+/// day is rolled up an hour at a time and only the file keys of one hour are
+/// held in memory.
+pub async fn write(day: &str) -> Result<Option<ManifestHeader>, anyhow::Error> {
+    let sources = storage::list(&format!("file_list/{day}/")).await?;
+    if sources.is_empty() {
+        delete(day).await?;
+        return Ok(None);
+    }
+    let mut hours: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for file in sources.iter() {
+        hours
+            .entry(file_list_hour(file).unwrap_or_default())
+            .or_default()
+            .push(file);
+    }
+
+    let mut files = 0;
+    let mut stats: BTreeMap<String, StreamStats> = BTreeMap::new();
+    let mut body = zstd::Encoder::new(Vec::new(), 3)?;
+    for hour_files in hours.values() {
+        let file_keys = read_file_keys(hour_files).await?;
+        for item in file_keys.values() {
+            let (stream_key, ..) = parse_file_key_columns(&item.key)?;
+            stats
+                .entry(stream_key)
+                .or_default()
+                .add_file_meta(&item.meta);
+            body.write_all(json::to_vec(item)?.as_slice())?;
+            body.write_all(b"\n")?;
+        }
+        files += file_keys.len();
+    }
+    let body = body.finish()?;
+
+    let header = ManifestHeader {
+        day: day.to_string(),
+        created_at: Utc::now().timestamp_micros(),
+        sources: sources.clone(),
+        files,
+        stats,
+    };
+    // the header is known last but read first, it goes into a zstd frame of
+    // its own ahead of the frame of the file keys
+    let mut buf = zstd::Encoder::new(Vec::new(), 3)?;
+    buf.write_all(json::to_vec(&header)?.as_slice())?;
+    buf.write_all(b"\n")?;
+    let mut compressed_bytes = buf.finish()?;
+    compressed_bytes.extend_from_slice(&body);
+    storage::put(&manifest_key(day), compressed_bytes.into()).await?;
+    log::info!(
+        "[FILE_LIST] manifest of {day} written, {} sources, {} files",
+        header.sources.len(),
+        header.files
+    );
+    Ok(Some(header))
+}
+
+pub async fn delete(day: &str) -> Result<(), anyhow::Error> {
+    let key = manifest_key(day);
+    if storage::list(&key).await?.is_empty() {
+        return Ok(());
+    }
+    storage::del(&[key.as_str()]).await
+}
+
+/// Loads the manifest of the day into the file_list, a page at a time.
+///
+/// `existing` are the file_list files of the day currently in storage; when
+/// one of the sources of the manifest is gone the manifest is stale and
+/// nothing is loaded.
+pub async fn load(
+    day: &str,
+    existing: &HashSet<String>,
+) -> Result<Option<ManifestHeader>, anyhow::Error> {
+    let data = storage::get(&manifest_key(day)).await?;
+    let mut lines = BufReader::new(zstd::stream::read::Decoder::new(data.reader())?).lines();
+    let header: ManifestHeader = match lines.next() {
+        Some(line) => json::from_str(&line?)?,
+        None => return Err(anyhow::anyhow!("manifest of {day} is empty")),
+    };
+    if header.sources.iter().any(|file| !existing.contains(file)) {
+        return Ok(None);
+    }
+
+    let page_size = CONFIG.compact.file_list_manifest_page_size;
+    let mut page = Vec::with_capacity(page_size);
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let item: FileKey = json::from_slice(line.as_bytes())?;
+        if !super::BLOCKED_ORGS.is_empty() {
+            let columns = item.key.split('/').collect::<Vec<&str>>();
+            let org_id = columns.get(1).unwrap_or(&"");
+            if super::BLOCKED_ORGS.contains(org_id) {
+                continue;
+            }
+        }
+        page.push(item);
+        if page.len() >= page_size {
+            infra_file_list::batch_add(&page).await?;
+            page.clear();
+        }
+    }
+    infra_file_list::batch_add(&page).await?;
+    Ok(Some(header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_list_day() {
+        assert_eq!(
+            file_list_day("file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst"),
+            Some("2023/06/26".to_string())
+        );
+        assert_eq!(file_list_day("file_list/2023/06/26"), None);
+        assert_eq!(
+            file_list_hour("file_list/2023/06/26/07/7078998136898850816tVckGD.json.zst"),
+            Some("2023/06/26/07".to_string())
+        );
+        assert_eq!(
+            file_list_day("file_list_manifest/2023/06/26/manifest.json.zst"),
+            None
+        );
+        assert_eq!(
+            manifest_key("2023/06/26"),
+            "file_list_manifest/2023/06/26/manifest.json.zst"
+        );
+    }
+}
@@ -20,6 +20,7 @@ use once_cell::sync::Lazy;
 
 pub mod broadcast;
 pub mod local;
+pub mod manifest;
 pub mod remote;
 
 pub static DEPULICATE_FILES: Lazy<RwHashSet<String>> =
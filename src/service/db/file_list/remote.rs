@@ -14,7 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::{BufRead, BufReader},
     sync::atomic,
 };
@@ -44,6 +44,9 @@ pub async fn cache(prefix: &str, force: bool) -> Result<(), anyhow::Error> {
     let mut stats = ProcessStats::default();
 
     let mut files = storage::list(&prefix).await?;
+    if prefix == "file_list/" && CONFIG.compact.file_list_manifest_enabled {
+        files = cache_manifests(files).await?;
+    }
     files.reverse(); // reverse order let deleted files be the first
     let files_num = files.len();
     log::info!("Load file_list [{prefix}] gets {} files", files_num);
@@ -125,6 +128,55 @@ pub async fn cache(prefix: &str, force: bool) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Loads the daily manifests and returns the file_list files which are not
+/// rolled up into one of them
+async fn cache_manifests(files: Vec<String>) -> Result<Vec<String>, anyhow::Error> {
+    let days = super::manifest::list_days().await?;
+    if days.is_empty() {
+        return Ok(files);
+    }
+
+    let start = std::time::Instant::now();
+    let mut day_files: HashMap<String, HashSet<String>> = HashMap::new();
+    for file in files.iter() {
+        if let Some(day) = super::manifest::file_list_day(file) {
+            day_files.entry(day).or_default().insert(file.to_string());
+        }
+    }
+    let empty = HashSet::new();
+    let mut covered = HashSet::new();
+    let mut loaded_files = 0;
+    for day in days {
+        let existing = day_files.get(&day).unwrap_or(&empty);
+        match super::manifest::load(&day, existing).await {
+            Ok(Some(header)) => {
+                loaded_files += header.files;
+                covered.extend(header.sources);
+            }
+            Ok(None) => {
+                log::warn!("Load file_list manifest [{day}] is stale, load its file_list instead");
+            }
+            Err(e) => {
+                log::error!(
+                    "Load file_list manifest [{day}] error: {e}, load its file_list instead"
+                );
+            }
+        }
+    }
+    let files = files
+        .into_iter()
+        .filter(|file| !covered.contains(file))
+        .collect::<Vec<_>>();
+    log::info!(
+        "Load file_list manifests done, {} files from {} file_list, {} file_list left, took: {}ms",
+        loaded_files,
+        covered.len(),
+        files.len(),
+        start.elapsed().as_millis()
+    );
+    Ok(files)
+}
+
 pub async fn cache_stats() -> Result<(), anyhow::Error> {
     let orgs = db::schema::list_organizations_from_cache().await;
     for org_id in orgs {
@@ -0,0 +1,125 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{
+    common::meta::kafka::{KafkaOffset, KafkaSource},
+    service::db,
+};
+
+pub async fn get(org_id: &str, name: &str) -> Result<KafkaSource, anyhow::Error> {
+    let key = format!("/kafka_sources/{org_id}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, source: &KafkaSource) -> Result<(), anyhow::Error> {
+    let key = format!("/kafka_sources/{org_id}/{}", source.name);
+    Ok(db::put(
+        &key,
+        json::to_vec(source).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/kafka_sources/{org_id}/{name}");
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    let key = format!("/kafka_status/{org_id}/{name}");
+    Ok(db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?)
+}
+
+/// Error of the last poll of the source, kept apart so the pollers never
+/// write the source itself.
+pub async fn get_status(org_id: &str, name: &str) -> Result<String, anyhow::Error> {
+    let key = format!("/kafka_status/{org_id}/{name}");
+    match db::get(&key).await {
+        Ok(val) => Ok(String::from_utf8_lossy(&val).to_string()),
+        Err(_) => Ok(String::new()),
+    }
+}
+
+pub async fn set_status(org_id: &str, name: &str, last_error: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/kafka_status/{org_id}/{name}");
+    if last_error.is_empty() {
+        return Ok(db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?);
+    }
+    Ok(db::put(&key, last_error.to_string().into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<KafkaSource>, anyhow::Error> {
+    let key = format!("/kafka_sources/{org_id}/");
+    let mut items: Vec<KafkaSource> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
+
+/// All the sources, keyed by org_id/name.
+pub async fn list_all() -> Result<Vec<(String, KafkaSource)>, anyhow::Error> {
+    let key = "/kafka_sources/";
+    let mut items = Vec::new();
+    for (item_key, item_value) in db::list(key).await? {
+        let item_key = item_key.strip_prefix(key).unwrap().to_string();
+        items.push((item_key, json::from_slice(&item_value)?));
+    }
+    Ok(items)
+}
+
+pub async fn get_offset(
+    org_id: &str,
+    group: &str,
+    topic: &str,
+    partition: i32,
+) -> Result<Option<KafkaOffset>, anyhow::Error> {
+    let key = format!("/kafka_offsets/{org_id}/{group}/{topic}/{partition}");
+    match db::get(&key).await {
+        Ok(val) => Ok(Some(json::from_slice(&val)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub async fn set_offset(
+    org_id: &str,
+    group: &str,
+    offset: &KafkaOffset,
+) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/kafka_offsets/{org_id}/{group}/{}/{}",
+        offset.topic, offset.partition
+    );
+    Ok(db::put(
+        &key,
+        json::to_vec(offset).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn list_offsets(org_id: &str, group: &str) -> Result<Vec<KafkaOffset>, anyhow::Error> {
+    let key = format!("/kafka_offsets/{org_id}/{group}/");
+    let mut items: Vec<KafkaOffset> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| (&a.topic, a.partition).cmp(&(&b.topic, b.partition)));
+    Ok(items)
+}
@@ -0,0 +1,79 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::HashSet;
+
+use config::{
+    meta::stream::StreamType,
+    utils::{
+        hash::{fnv, Sum64},
+        json,
+    },
+};
+
+use crate::{common::meta::stream::KeyMapping, service::db};
+
+#[inline]
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/key_mapping/{org_id}/{stream_type}/{stream_name}/")
+}
+
+/// Stores the mappings of the stream which are not known already, keeping the
+/// time they were first seen.
+pub async fn set_if_absent(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    mappings: &[KeyMapping],
+) -> Result<(), anyhow::Error> {
+    let prefix = mk_key(org_id, stream_type, stream_name);
+    let known = db::list_keys(&prefix)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    for mapping in mappings {
+        // the original key can hold any character, the mapping is keyed by its hash
+        let id = fnv::new().sum64(&format!("{}/{}", mapping.original, mapping.key));
+        let key = format!("{prefix}{id}");
+        if known.contains(&key) {
+            continue;
+        }
+        db::put(&key, json::to_vec(mapping)?.into(), db::NO_NEED_WATCH, None).await?;
+    }
+    Ok(())
+}
+
+/// Returns the mappings of a stream, by column
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<KeyMapping>, anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let mut items: Vec<KeyMapping> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.key.cmp(&b.key).then_with(|| a.original.cmp(&b.original)));
+    Ok(items)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    Ok(db::delete_if_exists(&key, true, db::NO_NEED_WATCH).await?)
+}
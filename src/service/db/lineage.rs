@@ -0,0 +1,71 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::functions::Lineage, service::db};
+
+#[inline]
+fn mk_key(org_id: &str, stream_type: StreamType, stream_name: &str) -> String {
+    format!("/lineage/{org_id}/{stream_type}/{stream_name}/")
+}
+
+pub async fn set(org_id: &str, lineage: &Lineage) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "{}{}",
+        mk_key(org_id, lineage.stream_type, &lineage.stream_name),
+        lineage.id
+    );
+    Ok(db::put(&key, json::to_vec(lineage)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+/// Stores the lineage unless it is known already, keeping the time it was
+/// first seen.
+pub async fn set_if_absent(org_id: &str, lineage: &Lineage) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "{}{}",
+        mk_key(org_id, lineage.stream_type, &lineage.stream_name),
+        lineage.id
+    );
+    if db::get(&key).await.is_ok() {
+        return Ok(());
+    }
+    Ok(db::put(&key, json::to_vec(lineage)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    id: i64,
+) -> Result<Lineage, anyhow::Error> {
+    let key = format!("{}{id}", mk_key(org_id, stream_type, stream_name));
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+/// Returns the lineages of a stream, oldest first
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<Lineage>, anyhow::Error> {
+    let key = mk_key(org_id, stream_type, stream_name);
+    let mut items: Vec<Lineage> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.first_seen.cmp(&b.first_seen));
+    Ok(items)
+}
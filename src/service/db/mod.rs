@@ -20,22 +20,42 @@ use infra::{db as infra_db, errors::Result};
 use {infra::errors::Error, o2_enterprise::enterprise::common::infra::config::O2_CONFIG};
 
 pub mod alerts;
+pub mod cdc;
 pub mod compact;
+pub mod continuous_queries;
+pub mod correlation;
 pub mod dashboards;
 pub mod enrichment_table;
 pub mod file_list;
 pub mod functions;
 pub mod instance;
+pub mod kafka;
+pub mod key_mappings;
 pub mod kv;
+pub mod lineage;
 pub mod metrics;
+pub mod monitors;
 pub mod ofga;
 pub mod organization;
+pub mod quality_monitors;
+pub mod query_cache;
+pub mod reprocess;
+pub mod revisions;
 pub mod saved_view;
 pub mod scheduler;
 pub mod schema;
+pub mod search_jobs;
+pub mod search_templates;
+pub mod short_url;
+pub mod sigma;
+pub mod slo;
+pub mod snmp;
+pub mod stream_quota;
 pub mod syslog;
+pub mod threat_intel;
 pub mod user;
 pub mod version;
+pub mod webhooks;
 
 pub(crate) use infra_db::{get_coordinator, Event, NEED_WATCH, NO_NEED_WATCH};
 
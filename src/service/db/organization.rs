@@ -21,8 +21,8 @@ use infra::errors::{self, Error};
 
 use crate::{
     common::{
-        infra::config::ORGANIZATION_SETTING,
-        meta::organization::{Organization, OrganizationSetting},
+        infra::config::{ORGANIZATION_FEATURES, ORGANIZATION_SETTING},
+        meta::organization::{OrgFeatureFlags, Organization, OrganizationSetting, QueryPolicy},
     },
     service::db,
 };
@@ -32,6 +32,9 @@ pub const ORG_SETTINGS_KEY_PREFIX: &str = "/organization/setting";
 
 pub const ORG_KEY_PREFIX: &str = "/organization/org";
 
+// DBKey of the feature flags of an org, only superusers can set them
+pub const ORG_FEATURES_KEY_PREFIX: &str = "/organization/features/";
+
 pub async fn set_org_setting(org_name: &str, setting: &OrganizationSetting) -> errors::Result<()> {
     let key = format!("{}/{}", ORG_SETTINGS_KEY_PREFIX, org_name);
     db::put(
@@ -59,6 +62,18 @@ pub async fn get_org_setting(org_id: &str) -> Result<Bytes, Error> {
     }
 }
 
+/// The query policies of an org from the cache, which the watch keeps up to
+/// date, so that searches don't read the settings from the db
+pub async fn get_query_policies(org_id: &str) -> Vec<QueryPolicy> {
+    let key = format!("{}/{}", ORG_SETTINGS_KEY_PREFIX, org_id);
+    ORGANIZATION_SETTING
+        .read()
+        .await
+        .get(&key)
+        .map(|v| v.query_policies.clone())
+        .unwrap_or_default()
+}
+
 /// Cache the existing org settings in the beginning
 pub async fn cache() -> Result<(), anyhow::Error> {
     let prefix = ORG_SETTINGS_KEY_PREFIX;
@@ -72,9 +87,76 @@ pub async fn cache() -> Result<(), anyhow::Error> {
             .insert(key, json_val);
     }
     log::info!("Organization settings Cached");
+
+    let ret = db::list(ORG_FEATURES_KEY_PREFIX).await?;
+    for (key, item_value) in ret {
+        let org_id = key.strip_prefix(ORG_FEATURES_KEY_PREFIX).unwrap();
+        let json_val: OrgFeatureFlags = json::from_slice(&item_value)?;
+        ORGANIZATION_FEATURES.insert(org_id.to_string(), json_val);
+    }
+    log::info!("Organization feature flags Cached");
     Ok(())
 }
 
+pub async fn set_features(org_id: &str, flags: &OrgFeatureFlags) -> errors::Result<()> {
+    let key = format!("{ORG_FEATURES_KEY_PREFIX}{org_id}");
+    db::put(&key, json::to_vec(flags)?.into(), db::NEED_WATCH, None).await?;
+    ORGANIZATION_FEATURES.insert(org_id.to_string(), flags.clone());
+    Ok(())
+}
+
+/// The feature flags of an org from the cache, the defaults if it has none
+pub fn get_features(org_id: &str) -> OrgFeatureFlags {
+    ORGANIZATION_FEATURES
+        .get(org_id)
+        .map(|v| v.value().clone())
+        .unwrap_or_default()
+}
+
+pub async fn watch_features() -> Result<(), anyhow::Error> {
+    let key = ORG_FEATURES_KEY_PREFIX;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching organization feature flags");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_org_features: event channel closed");
+                return Ok(());
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let org_id = ev.key.strip_prefix(key).unwrap();
+                let item_value = if config::CONFIG.common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => val,
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    ev.value.unwrap()
+                };
+                match json::from_slice::<OrgFeatureFlags>(&item_value) {
+                    Ok(flags) => {
+                        ORGANIZATION_FEATURES.insert(org_id.to_string(), flags);
+                    }
+                    Err(e) => log::error!("Error parsing feature flags of {org_id}: {}", e),
+                }
+            }
+            db::Event::Delete(ev) => {
+                let org_id = ev.key.strip_prefix(key).unwrap();
+                ORGANIZATION_FEATURES.remove(org_id);
+            }
+            db::Event::Empty => {}
+        }
+    }
+}
+
 pub async fn watch() -> Result<(), anyhow::Error> {
     let key = ORG_SETTINGS_KEY_PREFIX;
     let cluster_coordinator = db::get_coordinator().await;
@@ -0,0 +1,109 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::{common::meta::quality_monitors::QualityMonitor, service::db};
+
+/// The key of the monitor in the scheduler, `{stream_type}/{stream_name}/{name}`
+pub fn schedule_key(stream_type: StreamType, stream_name: &str, name: &str) -> String {
+    format!("{stream_type}/{stream_name}/{name}")
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<QualityMonitor, anyhow::Error> {
+    let key = format!("/quality_monitors/{org_id}/{stream_type}/{stream_name}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(
+    org_id: &str,
+    monitor: &QualityMonitor,
+    create: bool,
+) -> Result<(), anyhow::Error> {
+    set_without_updating_trigger(org_id, monitor).await?;
+    let trigger = db::scheduler::Trigger {
+        org: org_id.to_string(),
+        module: db::scheduler::TriggerModule::QualityMonitor,
+        module_key: schedule_key(monitor.stream_type, &monitor.stream_name, &monitor.name),
+        next_run_at: chrono::Utc::now().timestamp_micros(),
+        ..Default::default()
+    };
+    let ret = if create {
+        db::scheduler::push(trigger).await
+    } else {
+        db::scheduler::update_trigger(trigger).await
+    };
+    if let Err(e) = ret {
+        log::error!("Failed to save quality monitor trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn set_without_updating_trigger(
+    org_id: &str,
+    monitor: &QualityMonitor,
+) -> Result<(), anyhow::Error> {
+    let key = format!(
+        "/quality_monitors/{org_id}/{}/{}/{}",
+        monitor.stream_type, monitor.stream_name, monitor.name
+    );
+    Ok(db::put(
+        &key,
+        json::to_vec(monitor).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/quality_monitors/{org_id}/{stream_type}/{stream_name}/{name}");
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    if let Err(e) = db::scheduler::delete(
+        org_id,
+        db::scheduler::TriggerModule::QualityMonitor,
+        &schedule_key(stream_type, stream_name, name),
+    )
+    .await
+    {
+        log::error!("Failed to delete quality monitor trigger: {}", e);
+    }
+    Ok(())
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<QualityMonitor>, anyhow::Error> {
+    let key = format!("/quality_monitors/{org_id}/{stream_type}/{stream_name}/");
+    let mut items: Vec<QualityMonitor> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{meta::stream::StreamType, utils::json};
+
+use crate::service::{db, search};
+
+const INVALIDATION_KEY: &str = "/query_cache/invalidation/";
+
+/// Drops the cached search results of the time range of the stream on
+/// every querier
+pub async fn invalidate(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<(), anyhow::Error> {
+    let key = format!("{INVALIDATION_KEY}{org_id}/{stream_type}/{stream_name}");
+    Ok(db::put(
+        &key,
+        json::to_vec(&(start_time, end_time))?.into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = INVALIDATION_KEY;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching query cache invalidations");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_query_cache: event channel closed");
+                break;
+            }
+        };
+        let db::Event::Put(ev) = ev else {
+            continue;
+        };
+        let item_key = ev.key.strip_prefix(key).unwrap();
+        let columns = item_key.splitn(3, '/').collect::<Vec<_>>();
+        if columns.len() < 3 {
+            continue;
+        }
+        let item_value = if config::CONFIG.common.meta_store_external {
+            match db::get(&ev.key).await {
+                Ok(val) => val,
+                Err(e) => {
+                    log::error!("Error getting value: {}", e);
+                    continue;
+                }
+            }
+        } else {
+            ev.value.unwrap()
+        };
+        let (start_time, end_time): (i64, i64) = match json::from_slice(&item_value) {
+            Ok(val) => val,
+            Err(e) => {
+                log::error!("Error getting value: {}", e);
+                continue;
+            }
+        };
+        search::cache::invalidate(
+            columns[0],
+            StreamType::from(columns[1]),
+            columns[2],
+            start_time,
+            end_time,
+        )
+        .await;
+    }
+    Ok(())
+}
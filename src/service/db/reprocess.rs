@@ -0,0 +1,52 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use config::utils::json;
+
+use crate::{common::meta::functions::ReprocessJob, service::db};
+
+pub async fn get(org_id: &str, id: &str) -> Result<ReprocessJob, anyhow::Error> {
+    let val = db::get(&format!("/reprocess/{org_id}/{id}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, job: &ReprocessJob) -> Result<(), anyhow::Error> {
+    let key = format!("/reprocess/{org_id}/{}", job.id);
+    Ok(db::put(&key, json::to_vec(job)?.into(), db::NO_NEED_WATCH, None).await?)
+}
+
+/// Returns the jobs of an organization, oldest first
+pub async fn list(org_id: &str) -> Result<Vec<ReprocessJob>, anyhow::Error> {
+    let key = format!("/reprocess/{org_id}/");
+    let mut items: Vec<ReprocessJob> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(items)
+}
+
+/// Returns the jobs of all the organizations, as (org_id, job)
+pub async fn list_all() -> Result<Vec<(String, ReprocessJob)>, anyhow::Error> {
+    let key = "/reprocess/";
+    let ret = db::list(key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let Some((org_id, _)) = item_key.strip_prefix(key).unwrap().split_once('/') else {
+            continue;
+        };
+        items.push((org_id.to_string(), json::from_slice(&item_value)?));
+    }
+    Ok(items)
+}
@@ -0,0 +1,90 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+use infra::errors::Error;
+
+use crate::{
+    common::meta::revisions::{ObjectType, Revision},
+    service::db,
+};
+
+pub const REVISIONS_KEY_PREFIX: &str = "/revisions";
+
+fn key(org_id: &str, object_type: ObjectType, object_id: &str, revision: i64) -> String {
+    format!("{REVISIONS_KEY_PREFIX}/{org_id}/{object_type}/{object_id}/{revision}")
+}
+
+pub async fn put(org_id: &str, revision: &Revision) -> Result<(), Error> {
+    let key = key(
+        org_id,
+        revision.object_type,
+        &revision.object_id,
+        revision.revision,
+    );
+    db::put(
+        &key,
+        json::to_vec(revision).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await
+}
+
+pub async fn get(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    revision: i64,
+) -> Result<Revision, Error> {
+    let ret = db::get(&key(org_id, object_type, object_id, revision)).await?;
+    json::from_slice(&ret).map_err(|e| Error::Message(e.to_string()))
+}
+
+/// The revisions of an object, newest first.
+pub async fn list(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+) -> Result<Vec<Revision>, Error> {
+    let prefix = format!("{REVISIONS_KEY_PREFIX}/{org_id}/{object_type}/{object_id}/");
+    list_prefix(&prefix).await
+}
+
+/// The revisions of all the objects of the type, newest first.
+pub async fn list_by_type(org_id: &str, object_type: ObjectType) -> Result<Vec<Revision>, Error> {
+    let prefix = format!("{REVISIONS_KEY_PREFIX}/{org_id}/{object_type}/");
+    list_prefix(&prefix).await
+}
+
+async fn list_prefix(prefix: &str) -> Result<Vec<Revision>, Error> {
+    let mut revisions = db::list_values(prefix)
+        .await?
+        .into_iter()
+        .filter_map(|v| json::from_slice::<Revision>(&v).ok())
+        .collect::<Vec<_>>();
+    revisions.sort_by(|a, b| b.revision.cmp(&a.revision));
+    Ok(revisions)
+}
+
+pub async fn delete(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    revision: i64,
+) -> Result<(), Error> {
+    let key = key(org_id, object_type, object_id, revision);
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await
+}
@@ -17,8 +17,11 @@ use config::utils::json;
 use infra::errors::Error;
 
 use crate::{
-    common::meta::saved_view::{
-        CreateViewRequest, UpdateViewRequest, View, ViewWithoutData, ViewsWithoutData,
+    common::meta::{
+        dashboards::{has_tags, normalize_tags},
+        saved_view::{
+            CreateViewRequest, UpdateViewRequest, View, ViewWithoutData, ViewsWithoutData,
+        },
     },
     service::db,
 };
@@ -32,6 +35,8 @@ pub async fn set_view(org_id: &str, view: &CreateViewRequest) -> Result<View, Er
         view_id: view_id.clone(),
         data: view.data.clone(),
         view_name: view.view_name.clone(),
+        folder_id: view.folder_id.clone(),
+        tags: normalize_tags(view.tags.clone()),
     };
     let key = format!("{}/{}/{}", SAVED_VIEWS_KEY_PREFIX, org_id, view_id);
     db::put(
@@ -55,6 +60,14 @@ pub async fn update_view(
         Ok(original_view) => View {
             data: view.data.clone(),
             view_name: view.view_name.clone(),
+            folder_id: view
+                .folder_id
+                .clone()
+                .unwrap_or_else(|| original_view.folder_id.clone()),
+            tags: match &view.tags {
+                Some(tags) => normalize_tags(tags.clone()),
+                None => original_view.tags.clone(),
+            },
             ..original_view
         },
         Err(e) => return Err(e),
@@ -78,13 +91,20 @@ pub async fn get_view(org_id: &str, view_id: &str) -> Result<View, Error> {
 }
 
 /// Return all the saved views but query limited data only, associated with a
-/// provided org_id This will not contain the payload.
-pub async fn get_views_list_only(org_id: &str) -> Result<ViewsWithoutData, Error> {
+/// provided org_id This will not contain the payload. The views can be limited
+/// to a folder and to those having all the `tags`.
+pub async fn get_views_list_only(
+    org_id: &str,
+    folder_id: Option<&str>,
+    tags: &[String],
+) -> Result<ViewsWithoutData, Error> {
     let key = format!("{}/{}", SAVED_VIEWS_KEY_PREFIX, org_id);
     let ret = db::list_values(&key).await?;
     let mut views: Vec<ViewWithoutData> = ret
         .iter()
-        .map(|view| json::from_slice(view).unwrap())
+        .map(|view| json::from_slice::<ViewWithoutData>(view).unwrap())
+        .filter(|view| folder_id.map_or(true, |f| view.folder_id == f))
+        .filter(|view| has_tags(&view.tags, tags))
         .collect();
     views.sort_by_key(|v| v.view_name.clone());
 
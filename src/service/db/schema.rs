@@ -17,12 +17,21 @@ use std::sync::Arc;
 
 use arrow_schema::{Field, Schema};
 use bytes::Bytes;
-use config::{is_local_disk_storage, meta::stream::StreamType, utils::json, CONFIG};
+use config::{
+    is_local_disk_storage,
+    meta::stream::{FileKey, StreamType},
+    utils::{json, parquet::parse_schema_version_from_filename},
+    CONFIG,
+};
 use hashbrown::{HashMap, HashSet};
 use infra::{
     cache,
-    schema::{unwrap_stream_settings, STREAM_SCHEMAS, STREAM_SCHEMAS_LATEST, STREAM_SETTINGS},
+    schema::{
+        schema_version, unwrap_stream_settings, STREAM_SCHEMAS, STREAM_SCHEMAS_LATEST,
+        STREAM_SCHEMAS_VERSION, STREAM_SETTINGS,
+    },
 };
+use serde::Serialize;
 #[cfg(feature = "enterprise")]
 use {
     infra::{errors::Error, schema::mk_key},
@@ -78,7 +87,7 @@ pub async fn merge(
     stream_type: StreamType,
     schema: &Schema,
     min_ts: Option<i64>,
-) -> Result<Option<(Schema, Vec<Field>)>, anyhow::Error> {
+) -> Result<Option<(Schema, Vec<Field>, Option<Schema>)>, anyhow::Error> {
     let ret = infra::schema::merge(org_id, stream_name, stream_type, schema, min_ts).await?;
 
     // super cluster
@@ -103,8 +112,16 @@ pub async fn update_setting(
     stream_name: &str,
     stream_type: StreamType,
     metadata: std::collections::HashMap<String, String>,
+    revision: Option<u64>,
 ) -> Result<(), anyhow::Error> {
-    infra::schema::update_setting(org_id, stream_name, stream_type, metadata.clone()).await?;
+    infra::schema::update_setting_with_revision(
+        org_id,
+        stream_name,
+        stream_type,
+        metadata.clone(),
+        revision,
+    )
+    .await?;
 
     // super cluster
     #[cfg(feature = "enterprise")]
@@ -285,6 +302,29 @@ pub async fn list(
 }
 
 pub async fn watch() -> Result<(), anyhow::Error> {
+    loop {
+        watch_events().await?;
+        if config::cluster::is_offline() {
+            break;
+        }
+        // events may be lost while the channel is down, so reload the cached
+        // schemas that changed before watching again
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        match check_consistency(None, true).await {
+            Ok(ret) if !ret.is_empty() => {
+                log::warn!(
+                    "watch_stream_schema: reloaded {} stale stream schemas",
+                    ret.len()
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("watch_stream_schema: reload stream schemas error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+async fn watch_events() -> Result<(), anyhow::Error> {
     let key = "/schema/";
     let cluster_coordinator = db::get_coordinator().await;
     let mut events = cluster_coordinator.watch(key).await?;
@@ -320,21 +360,13 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                 if schema_versions.is_empty() {
                     continue;
                 }
-
-                let settings =
-                    unwrap_stream_settings(schema_versions.last().unwrap()).unwrap_or_default();
-                let mut w = STREAM_SETTINGS.write().await;
-                w.insert(item_key.to_string(), settings);
-                drop(w);
-                let mut w = STREAM_SCHEMAS_LATEST.write().await;
-                w.insert(
-                    item_key.to_string(),
-                    schema_versions.last().unwrap().clone(),
-                );
-                drop(w);
-                let mut sa = STREAM_SCHEMAS.write().await;
-                sa.insert(item_key.to_string(), schema_versions);
-                drop(sa);
+                let version = schema_version(&schema_versions);
+                let r = STREAM_SCHEMAS_VERSION.read().await;
+                let changed = r.get(item_key) != Some(&version);
+                drop(r);
+                if changed {
+                    set_cache(item_key, schema_versions, version).await;
+                }
 
                 let keys = item_key.split('/').collect::<Vec<&str>>();
                 let org_id = keys[0];
@@ -360,16 +392,9 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                 let org_id = columns[0];
                 let stream_type = StreamType::from(columns[1]);
                 let stream_name = columns[2];
-                let mut w = STREAM_SCHEMAS.write().await;
-                w.remove(item_key);
-                drop(w);
-                let mut w = STREAM_SCHEMAS_LATEST.write().await;
-                w.remove(item_key);
-                drop(w);
-                let mut w = STREAM_SETTINGS.write().await;
-                w.remove(item_key);
-                drop(w);
+                remove_cache(item_key).await;
                 cache::stats::remove_stream_stats(org_id, stream_name, stream_type);
+                cache::stats::remove_stream_quota_usage(org_id, stream_name, stream_type);
                 if let Err(e) =
                     super::compact::files::del_offset(org_id, stream_type, stream_name).await
                 {
@@ -396,11 +421,21 @@ pub async fn watch() -> Result<(), anyhow::Error> {
 }
 
 pub async fn cache() -> Result<(), anyhow::Error> {
-    let db_key = "/schema/";
+    for (item_key, schema_versions) in list_from_db("/schema/").await? {
+        let version = schema_version(&schema_versions);
+        set_cache(&item_key, schema_versions, version).await;
+    }
+    log::info!("Stream schemas Cached");
+    Ok(())
+}
+
+/// Lists the schema versions of the streams under the prefix from the db,
+/// keyed by org_id/stream_type/stream_name
+async fn list_from_db(db_key: &str) -> Result<HashMap<String, Vec<Schema>>, anyhow::Error> {
     let items = db::list(db_key).await?;
     let mut schemas: HashMap<String, Vec<(Bytes, i64)>> = HashMap::with_capacity(items.len());
     for (key, val) in items {
-        let key = key.strip_prefix(db_key).unwrap();
+        let key = key.strip_prefix("/schema/").unwrap();
         let columns = key.split('/').take(4).collect::<Vec<_>>();
         assert_eq!(columns.len(), 4, "BUG");
         let item_key = format!("{}/{}/{}", columns[0], columns[1], columns[2]);
@@ -408,7 +443,8 @@ pub async fn cache() -> Result<(), anyhow::Error> {
         let entry = schemas.entry(item_key).or_insert(Vec::new());
         entry.push((val, start_dt));
     }
-    for (item_key, versions) in schemas.iter_mut() {
+    let mut ret = HashMap::with_capacity(schemas.len());
+    for (item_key, mut versions) in schemas {
         versions.sort_by(|a, b| a.1.cmp(&b.1));
         let mut schema_versions = Vec::with_capacity(versions.len());
         for (val, _) in versions.iter() {
@@ -420,22 +456,107 @@ pub async fn cache() -> Result<(), anyhow::Error> {
         if schema_versions.is_empty() {
             continue;
         }
-        let settings = unwrap_stream_settings(schema_versions.last().unwrap()).unwrap_or_default();
-        let mut w = STREAM_SETTINGS.write().await;
-        w.insert(item_key.to_string(), settings);
-        drop(w);
-        let mut w = STREAM_SCHEMAS_LATEST.write().await;
-        w.insert(
-            item_key.to_string(),
-            schema_versions.last().unwrap().clone(),
-        );
-        drop(w);
-        let mut w = STREAM_SCHEMAS.write().await;
-        w.insert(item_key.to_string(), schema_versions);
-        drop(w);
+        ret.insert(item_key, schema_versions);
     }
-    log::info!("Stream schemas Cached");
-    Ok(())
+    Ok(ret)
+}
+
+async fn set_cache(item_key: &str, schema_versions: Vec<Schema>, version: u64) {
+    let settings = unwrap_stream_settings(schema_versions.last().unwrap()).unwrap_or_default();
+    let mut w = STREAM_SETTINGS.write().await;
+    w.insert(item_key.to_string(), settings);
+    drop(w);
+    let mut w = STREAM_SCHEMAS_LATEST.write().await;
+    w.insert(
+        item_key.to_string(),
+        schema_versions.last().unwrap().clone(),
+    );
+    drop(w);
+    let mut w = STREAM_SCHEMAS.write().await;
+    w.insert(item_key.to_string(), schema_versions);
+    drop(w);
+    let mut w = STREAM_SCHEMAS_VERSION.write().await;
+    w.insert(item_key.to_string(), version);
+    drop(w);
+}
+
+async fn remove_cache(item_key: &str) {
+    let mut w = STREAM_SCHEMAS.write().await;
+    w.remove(item_key);
+    drop(w);
+    let mut w = STREAM_SCHEMAS_LATEST.write().await;
+    w.remove(item_key);
+    drop(w);
+    let mut w = STREAM_SETTINGS.write().await;
+    w.remove(item_key);
+    drop(w);
+    let mut w = STREAM_SCHEMAS_VERSION.write().await;
+    w.remove(item_key);
+    drop(w);
+}
+
+/// A stream whose cached schema differs from the one in the db
+#[derive(Clone, Debug, Serialize)]
+pub struct SchemaInconsistency {
+    pub stream: String,
+    /// Version of the cached schema, none when the stream is not cached
+    pub cached_version: Option<u64>,
+    /// Version of the schema in the db, none when the stream was deleted
+    pub stored_version: Option<u64>,
+}
+
+/// Compares the version of every cached schema with the schema in the db and
+/// returns the streams which differ, reloading them when `repair` is set.
+pub async fn check_consistency(
+    org_id: Option<&str>,
+    repair: bool,
+) -> Result<Vec<SchemaInconsistency>, anyhow::Error> {
+    let (db_key, prefix) = match org_id {
+        Some(org_id) => (format!("/schema/{org_id}/"), format!("{org_id}/")),
+        None => ("/schema/".to_string(), "".to_string()),
+    };
+    let mut stored = list_from_db(&db_key).await?;
+    let r = STREAM_SCHEMAS_VERSION.read().await;
+    let cached = r
+        .iter()
+        .filter(|(key, _)| key.starts_with(&prefix))
+        .map(|(key, version)| (key.to_string(), *version))
+        .collect::<HashMap<_, _>>();
+    drop(r);
+
+    let mut ret = Vec::new();
+    for (key, schema_versions) in stored.iter() {
+        let version = schema_version(schema_versions);
+        let cached_version = cached.get(key).copied();
+        if cached_version != Some(version) {
+            ret.push(SchemaInconsistency {
+                stream: key.to_string(),
+                cached_version,
+                stored_version: Some(version),
+            });
+        }
+    }
+    for (key, version) in cached.iter() {
+        if !stored.contains_key(key) {
+            ret.push(SchemaInconsistency {
+                stream: key.to_string(),
+                cached_version: Some(*version),
+                stored_version: None,
+            });
+        }
+    }
+
+    if repair {
+        for item in ret.iter() {
+            match (item.stored_version, stored.remove(&item.stream)) {
+                (Some(version), Some(schema_versions)) => {
+                    set_cache(&item.stream, schema_versions, version).await
+                }
+                _ => remove_cache(&item.stream).await,
+            }
+        }
+    }
+    Ok(ret)
 }
 
 pub async fn cache_enrichment_tables() -> Result<(), anyhow::Error> {
@@ -494,6 +615,21 @@ pub async fn cache_enrichment_tables() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Returns the schema version of a file, the files rewritten by the compactor
+/// carry their version in the name, the others are matched by time.
+pub fn file_schema_version_id(schemas: &[Schema], file: &FileKey) -> Option<usize> {
+    if let Some(start_dt) = parse_schema_version_from_filename(&file.key) {
+        let start_dt = start_dt.to_string();
+        if let Some(id) = schemas
+            .iter()
+            .position(|schema| schema.metadata().get("start_dt") == Some(&start_dt))
+        {
+            return Some(id);
+        }
+    }
+    filter_schema_version_id(schemas, file.meta.min_ts, file.meta.max_ts)
+}
+
 pub fn filter_schema_version_id(schemas: &[Schema], _start_dt: i64, end_dt: i64) -> Option<usize> {
     for (i, schema) in schemas.iter().enumerate() {
         let metadata = schema.metadata();
@@ -0,0 +1,89 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::search_jobs::SearchJob, service::db};
+
+/// Index of the jobs left to run, the queriers list it instead of the jobs
+const QUEUE_KEY: &str = "/search_jobs_queue/";
+
+pub async fn get(org_id: &str, id: &str) -> Result<SearchJob, anyhow::Error> {
+    let val = db::get(&format!("/search_jobs/{org_id}/{id}")).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, job: &SearchJob) -> Result<(), anyhow::Error> {
+    let key = format!("/search_jobs/{org_id}/{}", job.id);
+    db::put(&key, json::to_vec(job)?.into(), db::NO_NEED_WATCH, None).await?;
+    let queue_key = format!("{QUEUE_KEY}{org_id}/{}", job.id);
+    if job.status.is_finished() {
+        db::delete(&queue_key, false, db::NO_NEED_WATCH, None).await?;
+    } else if job.node.is_empty() {
+        // queued once, when the job is created
+        db::put(&queue_key, "".into(), db::NO_NEED_WATCH, None).await?;
+    }
+    Ok(())
+}
+
+pub async fn delete(org_id: &str, id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/search_jobs/{org_id}/{id}");
+    db::delete(&key, false, db::NO_NEED_WATCH, None).await?;
+    let queue_key = format!("{QUEUE_KEY}{org_id}/{id}");
+    Ok(db::delete(&queue_key, false, db::NO_NEED_WATCH, None).await?)
+}
+
+/// Returns the jobs of an organization, newest first
+pub async fn list(org_id: &str) -> Result<Vec<SearchJob>, anyhow::Error> {
+    let key = format!("/search_jobs/{org_id}/");
+    let mut items: Vec<SearchJob> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        match json::from_slice(&item_value) {
+            Ok(job) => items.push(job),
+            Err(e) => log::error!("[SEARCH JOB] error parsing job: {e}"),
+        }
+    }
+    items.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(items)
+}
+
+/// Returns the jobs of all the organizations, as (org_id, job)
+pub async fn list_all() -> Result<Vec<(String, SearchJob)>, anyhow::Error> {
+    let key = "/search_jobs/";
+    let ret = db::list(key).await?;
+    let mut items = Vec::with_capacity(ret.len());
+    for (item_key, item_value) in ret {
+        let Some((org_id, _)) = item_key.strip_prefix(key).unwrap().split_once('/') else {
+            continue;
+        };
+        match json::from_slice(&item_value) {
+            Ok(job) => items.push((org_id.to_string(), job)),
+            Err(e) => log::error!("[SEARCH JOB] error parsing job {item_key}: {e}"),
+        }
+    }
+    Ok(items)
+}
+
+/// Returns the jobs which are not finished, as (org_id, id)
+pub async fn list_queued() -> Result<Vec<(String, String)>, anyhow::Error> {
+    let ret = db::list_keys(QUEUE_KEY).await?;
+    Ok(ret
+        .into_iter()
+        .filter_map(|item_key| {
+            let (org_id, id) = item_key.strip_prefix(QUEUE_KEY)?.split_once('/')?;
+            Some((org_id.to_string(), id.to_string()))
+        })
+        .collect())
+}
@@ -0,0 +1,91 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use config::utils::json;
+use infra::errors::{DbError, Error};
+
+use crate::{common::meta::short_url::ShortUrl, service::db};
+
+pub async fn get(org_id: &str, id: &str) -> Result<ShortUrl, anyhow::Error> {
+    let key = format!("/short_urls/{org_id}/{id}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, id: &str, short_url: &ShortUrl) -> Result<(), anyhow::Error> {
+    let key = format!("/short_urls/{org_id}/{id}");
+    Ok(db::put(
+        &key,
+        json::to_vec(short_url).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(org_id: &str, id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/short_urls/{org_id}/{id}");
+    Ok(db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?)
+}
+
+/// All the short urls, keyed by org_id/id.
+pub async fn list_all() -> Result<Vec<(String, ShortUrl)>, anyhow::Error> {
+    let key = "/short_urls/";
+    let mut items = Vec::new();
+    for (item_key, item_value) in db::list(key).await? {
+        let item_key = item_key.strip_prefix(key).unwrap().to_string();
+        match json::from_slice(&item_value) {
+            Ok(v) => items.push((item_key, v)),
+            Err(e) => log::error!("[SHORT URL] invalid short url {item_key}: {e}"),
+        }
+    }
+    Ok(items)
+}
+
+/// The key signing the short urls of the org, `None` if it was never created.
+/// The key is stored hex encoded, the meta store keeps text values.
+pub async fn get_key(org_id: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let key = format!("/short_url_keys/{org_id}");
+    match db::get(&key).await {
+        Ok(val) => Ok(Some(hex::decode(&val)?)),
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Stores the key unless the org has one already and returns the key of the
+/// org, so the nodes racing to create it all sign with the first one stored.
+pub async fn create_key(org_id: &str, signing_key: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let key = format!("/short_url_keys/{org_id}");
+    let value = Bytes::from(hex::encode(signing_key));
+    let client = infra::db::get_db().await;
+    client
+        .get_for_update(
+            &key,
+            db::NO_NEED_WATCH,
+            None,
+            Box::new(move |existing| {
+                Ok(match existing {
+                    Some(_) => None,
+                    None => Some((Some(value), None)),
+                })
+            }),
+        )
+        .await?;
+    get_key(org_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("short url key of org {org_id} not stored"))
+}
@@ -0,0 +1,50 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::json;
+
+use crate::{common::meta::sigma::MappingProfile, service::db};
+
+pub async fn get(org_id: &str, name: &str) -> Result<MappingProfile, anyhow::Error> {
+    let key = format!("/sigma_profiles/{org_id}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, profile: &MappingProfile) -> Result<(), anyhow::Error> {
+    let key = format!("/sigma_profiles/{org_id}/{}", profile.name);
+    Ok(db::put(
+        &key,
+        json::to_vec(profile).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/sigma_profiles/{org_id}/{name}");
+    Ok(db::delete(&key, false, db::NO_NEED_WATCH, None).await?)
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<MappingProfile>, anyhow::Error> {
+    let key = format!("/sigma_profiles/{org_id}/");
+    let mut items: Vec<MappingProfile> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
@@ -0,0 +1,191 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::{utils::json, CONFIG};
+
+use crate::{
+    common::{
+        infra::config::{SNMP_MIBS, SNMP_TRAP_ROUTES},
+        meta::snmp::{SnmpMib, SnmpTrapRoute},
+    },
+    service::{db, snmp::mib},
+};
+
+const ROUTE_PREFIX: &str = "/snmp/route/";
+const MIB_PREFIX: &str = "/snmp/mib/";
+
+#[tracing::instrument(name = "service:db:snmp:list")]
+pub async fn list() -> Result<Vec<SnmpTrapRoute>, anyhow::Error> {
+    Ok(db::list(ROUTE_PREFIX)
+        .await?
+        .values()
+        .map(|val| json::from_slice(val).unwrap())
+        .collect())
+}
+
+#[tracing::instrument(name = "service:db:snmp:set", skip_all)]
+pub async fn set(route: &SnmpTrapRoute) -> Result<(), anyhow::Error> {
+    Ok(db::put(
+        &format!("{ROUTE_PREFIX}{}", route.id),
+        json::to_vec(route).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+#[tracing::instrument(name = "service:db:snmp:get")]
+pub async fn get(id: &str) -> Result<SnmpTrapRoute, anyhow::Error> {
+    let val = db::get(&format!("{ROUTE_PREFIX}{id}")).await?;
+    Ok(json::from_slice(&val).unwrap())
+}
+
+#[tracing::instrument(name = "service:db:snmp:delete")]
+pub async fn delete(id: &str) -> Result<(), anyhow::Error> {
+    Ok(db::delete(&format!("{ROUTE_PREFIX}{id}"), false, db::NEED_WATCH, None).await?)
+}
+
+#[tracing::instrument(name = "service:db:snmp:set_mib", skip_all)]
+pub async fn set_mib(mib: &SnmpMib) -> Result<(), anyhow::Error> {
+    Ok(db::put(
+        &format!("{MIB_PREFIX}{}", mib.name),
+        json::to_vec(mib).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+#[tracing::instrument(name = "service:db:snmp:delete_mib")]
+pub async fn delete_mib(name: &str) -> Result<(), anyhow::Error> {
+    Ok(db::delete(&format!("{MIB_PREFIX}{name}"), false, db::NEED_WATCH, None).await?)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = ROUTE_PREFIX;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching snmp trap routes");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_snmp_routes: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_value: SnmpTrapRoute = if CONFIG.common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+
+                SNMP_TRAP_ROUTES.insert(item_value.id.to_owned(), item_value);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                SNMP_TRAP_ROUTES.remove(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let ret = db::list(ROUTE_PREFIX).await?;
+    for (_, item_value) in ret {
+        let json_val: SnmpTrapRoute = json::from_slice(&item_value).unwrap();
+        SNMP_TRAP_ROUTES.insert(json_val.id.to_owned(), json_val);
+    }
+    log::info!("SnmpTrapRoutes Cached");
+    Ok(())
+}
+
+pub async fn watch_mibs() -> Result<(), anyhow::Error> {
+    let key = MIB_PREFIX;
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching snmp mibs");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_snmp_mibs: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_value: SnmpMib = if CONFIG.common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+
+                SNMP_MIBS.insert(item_value.name.to_owned(), item_value);
+                mib::compile();
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                SNMP_MIBS.remove(item_key);
+                mib::compile();
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache_mibs() -> Result<(), anyhow::Error> {
+    let ret = db::list(MIB_PREFIX).await?;
+    for (_, item_value) in ret {
+        let json_val: SnmpMib = json::from_slice(&item_value).unwrap();
+        SNMP_MIBS.insert(json_val.name.to_owned(), json_val);
+    }
+    mib::compile();
+    log::info!("SnmpMibs Cached");
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    meta::stream::{StreamQuotaUsage, StreamType},
+    utils::json,
+};
+
+use crate::service::db;
+
+/// The quota usage each ingester persists for a stream, so it survives
+/// restarts and the other nodes can add it up.
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    node: &str,
+) -> Result<Option<StreamQuotaUsage>, anyhow::Error> {
+    let key = format!("/stream_quota/{org_id}/{stream_type}/{stream_name}/{node}");
+    match db::get(&key).await {
+        Ok(val) => Ok(Some(json::from_slice(&val)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub async fn set(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    node: &str,
+    usage: &StreamQuotaUsage,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/stream_quota/{org_id}/{stream_type}/{stream_name}/{node}");
+    Ok(db::put(
+        &key,
+        json::to_vec(usage).unwrap().into(),
+        db::NO_NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+/// The usage of every ingester of the stream, keyed by node uuid.
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<(String, StreamQuotaUsage)>, anyhow::Error> {
+    let key = format!("/stream_quota/{org_id}/{stream_type}/{stream_name}/");
+    let mut items = Vec::new();
+    for (item_key, item_value) in db::list(&key).await? {
+        let node = item_key.strip_prefix(&key).unwrap().to_string();
+        items.push((node, json::from_slice(&item_value)?));
+    }
+    Ok(items)
+}
+
+/// The usage of every ingester of every stream, keyed by
+/// org_id/stream_type/stream_name/node.
+pub async fn list_all() -> Result<Vec<(String, StreamQuotaUsage)>, anyhow::Error> {
+    let key = "/stream_quota/";
+    let mut items = Vec::new();
+    for (item_key, item_value) in db::list(key).await? {
+        let item_key = item_key.strip_prefix(key).unwrap().to_string();
+        match json::from_slice(&item_value) {
+            Ok(v) => items.push((item_key, v)),
+            Err(e) => log::error!("[QUOTA] invalid quota usage {item_key}: {e}"),
+        }
+    }
+    Ok(items)
+}
+
+pub async fn delete_node(key: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/stream_quota/{key}");
+    Ok(db::delete_if_exists(&key, false, db::NO_NEED_WATCH).await?)
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<(), anyhow::Error> {
+    let key = format!("/stream_quota/{org_id}/{stream_type}/{stream_name}/");
+    Ok(db::delete_if_exists(&key, true, db::NO_NEED_WATCH).await?)
+}
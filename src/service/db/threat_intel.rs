@@ -0,0 +1,132 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use config::utils::json;
+use itertools::Itertools;
+
+use crate::{
+    common::{infra::config::THREAT_INTEL_LISTS, meta::threat_intel::IndicatorList},
+    service::db,
+};
+
+pub async fn get(org_id: &str, name: &str) -> Result<IndicatorList, anyhow::Error> {
+    let map_key = format!("{org_id}/{name}");
+    if let Some(val) = THREAT_INTEL_LISTS.get(&map_key) {
+        return Ok(val.value().clone());
+    }
+
+    let key = format!("/threat_intel/{org_id}/{name}");
+    let val = db::get(&key).await?;
+    Ok(json::from_slice(&val)?)
+}
+
+pub async fn set(org_id: &str, list: &IndicatorList) -> Result<(), anyhow::Error> {
+    let key = format!("/threat_intel/{org_id}/{}", list.name);
+    Ok(db::put(
+        &key,
+        json::to_vec(list).unwrap().into(),
+        db::NEED_WATCH,
+        None,
+    )
+    .await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/threat_intel/{org_id}/{name}");
+    Ok(db::delete(&key, false, db::NEED_WATCH, None).await?)
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<IndicatorList>, anyhow::Error> {
+    if !THREAT_INTEL_LISTS.is_empty() {
+        let prefix = format!("{org_id}/");
+        return Ok(THREAT_INTEL_LISTS
+            .iter()
+            .filter(|v| v.key().starts_with(&prefix))
+            .map(|v| v.value().clone())
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .collect());
+    }
+
+    let key = format!("/threat_intel/{org_id}/");
+    let mut items: Vec<IndicatorList> = Vec::new();
+    for item_value in db::list_values(&key).await? {
+        items.push(json::from_slice(&item_value)?);
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(items)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = "/threat_intel/";
+    let cluster_coordinator = db::get_coordinator().await;
+    let mut events = cluster_coordinator.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching threat intel lists");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_threat_intel: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            db::Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: IndicatorList = if config::CONFIG.common.meta_store_external {
+                    match db::get(&ev.key).await {
+                        Ok(val) => match json::from_slice(&val) {
+                            Ok(val) => val,
+                            Err(e) => {
+                                log::error!("Error getting value: {}", e);
+                                continue;
+                            }
+                        },
+                        Err(e) => {
+                            log::error!("Error getting value: {}", e);
+                            continue;
+                        }
+                    }
+                } else {
+                    json::from_slice(&ev.value.unwrap()).unwrap()
+                };
+                THREAT_INTEL_LISTS.insert(item_key.to_owned(), item_value);
+                crate::service::threat_intel::compile(item_key);
+            }
+            db::Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                THREAT_INTEL_LISTS.remove(item_key);
+                crate::service::threat_intel::compile(item_key);
+            }
+            db::Event::Empty => {}
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = "/threat_intel/";
+    let ret = db::list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let json_val: IndicatorList = json::from_slice(&item_value).unwrap();
+        THREAT_INTEL_LISTS.insert(item_key.to_owned(), json_val);
+        crate::service::threat_intel::compile(item_key);
+    }
+    log::info!("Threat intel lists Cached");
+    Ok(())
+}
@@ -23,7 +23,7 @@ use crate::{
         infra::config::{ROOT_USER, USERS, USERS_RUM_TOKEN},
         meta::user::{DBUser, User, UserOrg, UserRole},
     },
-    service::db,
+    service::{cdc, db},
 };
 
 pub async fn get(org_id: Option<&str>, name: &str) -> Result<Option<User>, anyhow::Error> {
@@ -90,6 +90,11 @@ pub async fn get_db_user(name: &str) -> Result<DBUser, anyhow::Error> {
 }
 
 pub async fn set(user: DBUser) -> Result<(), anyhow::Error> {
+    let before = if CONFIG.common.cdc_enabled {
+        get_db_user(&user.email).await.ok()
+    } else {
+        None
+    };
     let key = format!("/user/{}", user.email);
     db::put(
         &key,
@@ -98,6 +103,7 @@ pub async fn set(user: DBUser) -> Result<(), anyhow::Error> {
         None,
     )
     .await?;
+    cdc::record_user(before.as_ref(), Some(&user)).await;
 
     // cache user
     for org in user.organizations {
@@ -128,9 +134,14 @@ pub async fn set(user: DBUser) -> Result<(), anyhow::Error> {
 }
 
 pub async fn delete(name: &str) -> Result<(), anyhow::Error> {
+    let before = if CONFIG.common.cdc_enabled {
+        get_db_user(name).await.ok()
+    } else {
+        None
+    };
     let key = format!("/user/{name}");
     match db::delete(&key, false, db::NEED_WATCH, None).await {
-        Ok(_) => {}
+        Ok(_) => cdc::record_user(before.as_ref(), None).await,
         Err(e) => {
             log::error!("Error deleting user: {}", e);
             return Err(anyhow::anyhow!("Error deleting user: {}", e));
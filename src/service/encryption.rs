@@ -0,0 +1,344 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use config::{
+    meta::stream::StreamType,
+    utils::{
+        base64,
+        json::{Map, Value},
+    },
+    RwHashMap, CONFIG,
+};
+use infra::{
+    dist_lock,
+    errors::{DbError, Error},
+    schema::STREAM_SETTINGS,
+};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+
+use crate::{
+    common::{
+        meta::user::UserRole,
+        utils::auth::{is_root_user, AuthExtractor},
+    },
+    service::{db, users},
+};
+
+/// Prefix of encrypted field values.
+pub const ENCRYPTED_VALUE_PREFIX: &str = "zoenc:";
+
+const ORG_KEY_PREFIX: &str = "/encryption/org_key";
+const NONCE_LEN: usize = 24;
+
+static ORG_KEYS: Lazy<RwHashMap<String, [u8; 32]>> = Lazy::new(Default::default);
+
+/// The key wrapping the org keys in the meta store, `ZO_ENCRYPTION_MASTER_KEY`
+static MASTER_KEY: Lazy<Option<[u8; 32]>> = Lazy::new(|| {
+    if CONFIG.auth.encryption_master_key.is_empty() {
+        return None;
+    }
+    base64::decode_raw(&CONFIG.auth.encryption_master_key)
+        .ok()
+        .and_then(|v| v.try_into().ok())
+});
+
+/// Field encryption needs a master key to wrap the org keys
+pub fn is_enabled() -> bool {
+    MASTER_KEY.is_some()
+}
+
+fn master_key() -> Result<&'static [u8; 32], Error> {
+    MASTER_KEY.as_ref().ok_or_else(|| {
+        Error::Message("field encryption needs ZO_ENCRYPTION_MASTER_KEY to be set".to_string())
+    })
+}
+
+/// Returns the fields configured to be encrypted for the stream.
+pub async fn get_encrypt_fields(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Vec<String> {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STREAM_SETTINGS
+        .read()
+        .await
+        .get(&key)
+        .map(|s| s.encrypt_fields.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the encryption key of the org, `None` if it was never created.
+pub async fn get_key(org_id: &str) -> Result<Option<[u8; 32]>, Error> {
+    if let Some(key) = ORG_KEYS.get(org_id) {
+        return Ok(Some(*key));
+    }
+    let db_key = format!("{ORG_KEY_PREFIX}/{org_id}");
+    match db::get(&db_key).await {
+        Ok(v) => {
+            let key = unwrap_key(master_key()?, org_id, &v).ok_or_else(|| {
+                Error::Message(format!("invalid encryption key for org {org_id}"))
+            })?;
+            ORG_KEYS.insert(org_id.to_string(), key);
+            Ok(Some(key))
+        }
+        Err(Error::DbError(DbError::KeyNotExists(_))) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the encryption key of the org, creating it on first use.
+pub async fn get_or_create_key(org_id: &str) -> Result<[u8; 32], Error> {
+    if let Some(key) = get_key(org_id).await? {
+        return Ok(key);
+    }
+    let master_key = master_key()?;
+    let db_key = format!("{ORG_KEY_PREFIX}/{org_id}");
+    let locker = dist_lock::lock(&db_key, 0).await?;
+    // another node may have created it while we were waiting for the lock
+    let ret = match get_key(org_id).await {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            let wrapped = wrap_key(master_key, org_id, &key);
+            match db::put(&db_key, wrapped.into(), db::NO_NEED_WATCH, None).await {
+                Ok(_) => {
+                    ORG_KEYS.insert(org_id.to_string(), key);
+                    Ok(key)
+                }
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+/// Seals `data` with XChaCha20-Poly1305, the output is `nonce || ciphertext`
+fn seal(key: &[u8; 32], aad: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let sealed = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: data, aad })
+        .expect("encrypt in memory");
+    let mut buf = nonce.to_vec();
+    buf.extend(sealed);
+    buf
+}
+
+fn open(key: &[u8; 32], aad: &[u8], buf: &[u8]) -> Option<Vec<u8>> {
+    if buf.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, sealed) = buf.split_at(NONCE_LEN);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: sealed, aad })
+        .ok()
+}
+
+/// The org key is stored sealed by the master key, bound to its org
+fn wrap_key(master_key: &[u8; 32], org_id: &str, key: &[u8; 32]) -> Vec<u8> {
+    seal(master_key, org_id.as_bytes(), key)
+}
+
+fn unwrap_key(master_key: &[u8; 32], org_id: &str, wrapped: &[u8]) -> Option<[u8; 32]> {
+    open(master_key, org_id.as_bytes(), wrapped)?
+        .try_into()
+        .ok()
+}
+
+/// Encrypts a value with the org key, the output is `zoenc:` followed by the base64 of
+/// `nonce || ciphertext || tag`.
+pub fn encrypt_value(key: &[u8; 32], value: &str) -> String {
+    let buf = seal(key, ENCRYPTED_VALUE_PREFIX.as_bytes(), value.as_bytes());
+    format!("{ENCRYPTED_VALUE_PREFIX}{}", base64::encode_raw(&buf))
+}
+
+/// Decrypts a value produced by [`encrypt_value`], returns `None` if the value was not
+/// encrypted with this key or was tampered with.
+pub fn decrypt_value(key: &[u8; 32], value: &str) -> Option<String> {
+    let value = value.strip_prefix(ENCRYPTED_VALUE_PREFIX)?;
+    let buf = base64::decode_raw(value).ok()?;
+    let plain = open(key, ENCRYPTED_VALUE_PREFIX.as_bytes(), &buf)?;
+    String::from_utf8(plain).ok()
+}
+
+/// Encrypts the configured fields of the record, non string values are encrypted as
+/// their json representation.
+pub async fn encrypt_fields(
+    org_id: &str,
+    record: &mut Map<String, Value>,
+    fields: &[String],
+) -> Result<(), Error> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let mut org_key = None;
+    for field in fields {
+        if field == &CONFIG.common.column_timestamp {
+            continue;
+        }
+        let Some(value) = record.get_mut(field) else {
+            continue;
+        };
+        let plain = match value {
+            Value::Null => continue,
+            Value::String(v) => {
+                if v.starts_with(ENCRYPTED_VALUE_PREFIX) {
+                    continue;
+                }
+                v.clone()
+            }
+            v => v.to_string(),
+        };
+        let key = match org_key {
+            Some(key) => key,
+            None => *org_key.insert(get_or_create_key(org_id).await?),
+        };
+        *value = Value::String(encrypt_value(&key, &plain));
+    }
+    Ok(())
+}
+
+/// The permission, granted per stream as `AllowDecrypt`, to read the encrypted
+/// fields of a stream back in plain text.
+const DECRYPT_PERMISSION: &str = "DECRYPT";
+
+/// Checks whether the user is allowed to decrypt the encrypted fields of the
+/// stream. With OpenFGA this needs the decrypt permission on the stream, which
+/// being allowed to update the stream does not imply. Without OpenFGA only the
+/// admins may decrypt.
+pub async fn can_decrypt(
+    org_id: &str,
+    user_id: Option<&str>,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> bool {
+    let Some(user_id) = user_id else {
+        return false;
+    };
+    if is_root_user(user_id) {
+        return true;
+    }
+    let Some(user) = users::get_user(Some(org_id), user_id).await else {
+        return false;
+    };
+
+    #[cfg(feature = "enterprise")]
+    {
+        use o2_enterprise::enterprise::common::infra::config::O2_CONFIG;
+
+        if O2_CONFIG.openfga.enabled {
+            return crate::handler::http::auth::validator::check_permissions(
+                user_id,
+                decrypt_request(org_id, stream_type, stream_name),
+                Some(user.role),
+            )
+            .await;
+        }
+    }
+    #[cfg(not(feature = "enterprise"))]
+    let _ = (stream_type, stream_name);
+
+    role_can_decrypt(&user.role)
+}
+
+#[cfg_attr(not(feature = "enterprise"), allow(dead_code))]
+fn decrypt_request(org_id: &str, stream_type: StreamType, stream_name: &str) -> AuthExtractor {
+    AuthExtractor {
+        auth: "".to_string(),
+        method: DECRYPT_PERMISSION.to_string(),
+        o2_type: format!("{stream_type}:{stream_name}"),
+        org_id: org_id.to_string(),
+        bypass_check: false,
+        parent_id: "".to_string(),
+    }
+}
+
+fn role_can_decrypt(role: &UserRole) -> bool {
+    matches!(role, UserRole::Admin | UserRole::Root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_does_not_allow_decrypt() {
+        // members may update the streams of the organization
+        assert!(!role_can_decrypt(&UserRole::Member));
+        #[cfg(feature = "enterprise")]
+        {
+            assert!(!role_can_decrypt(&UserRole::Editor));
+            assert!(!role_can_decrypt(&UserRole::Viewer));
+        }
+        assert!(role_can_decrypt(&UserRole::Admin));
+        assert!(role_can_decrypt(&UserRole::Root));
+
+        let req = decrypt_request("default", StreamType::Logs, "payments");
+        assert_eq!(req.method, DECRYPT_PERMISSION);
+        assert_ne!(req.method, "PUT");
+        assert_eq!(req.o2_type, "logs:payments");
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_value() {
+        let key = [7u8; 32];
+        let encrypted = encrypt_value(&key, "4111 1111 1111 1111");
+        assert!(encrypted.starts_with(ENCRYPTED_VALUE_PREFIX));
+        assert!(!encrypted.contains("4111"));
+        assert_eq!(
+            decrypt_value(&key, &encrypted).as_deref(),
+            Some("4111 1111 1111 1111")
+        );
+        // random nonce, same input gives different output
+        assert_ne!(encrypted, encrypt_value(&key, "4111 1111 1111 1111"));
+        // wrong key
+        assert_eq!(decrypt_value(&[8u8; 32], &encrypted), None);
+        // not encrypted
+        assert_eq!(decrypt_value(&key, "plain"), None);
+    }
+
+    #[test]
+    fn test_wrap_key() {
+        let master_key = [1u8; 32];
+        let key = [7u8; 32];
+        let wrapped = wrap_key(&master_key, "default", &key);
+        assert!(!wrapped.windows(32).any(|w| w == key));
+        assert_eq!(unwrap_key(&master_key, "default", &wrapped), Some(key));
+        // bound to the org and the master key
+        assert_eq!(unwrap_key(&master_key, "other", &wrapped), None);
+        assert_eq!(unwrap_key(&[2u8; 32], "default", &wrapped), None);
+    }
+
+    #[test]
+    fn test_decrypt_tampered_value() {
+        let key = [7u8; 32];
+        let encrypted = encrypt_value(&key, "secret");
+        let mut buf = base64::decode_raw(&encrypted[ENCRYPTED_VALUE_PREFIX.len()..]).unwrap();
+        buf[NONCE_LEN] ^= 1;
+        let tampered = format!("{ENCRYPTED_VALUE_PREFIX}{}", base64::encode_raw(&buf));
+        assert_eq!(decrypt_value(&key, &tampered), None);
+    }
+}
@@ -16,10 +16,7 @@
 use std::{collections::HashMap, io::Error, sync::Arc};
 
 use actix_multipart::Multipart;
-use actix_web::{
-    http::{self, StatusCode},
-    HttpResponse,
-};
+use actix_web::{http::StatusCode, HttpResponse};
 use bytes::Bytes;
 use chrono::Utc;
 use config::{
@@ -38,10 +35,12 @@ use infra::{
 };
 
 use crate::{
-    common::meta::{self, http::HttpResponse as MetaHttpResponse, stream::SchemaRecords},
+    common::meta::{http::HttpResponse as MetaHttpResponse, stream::SchemaRecords},
     service::{
         compact::retention,
-        db, format_stream_name,
+        db,
+        error::ServiceError,
+        format_stream_name,
         ingestion::write_file,
         schema::{check_for_schema, stream_schema_exists, SchemaCache},
         usage::report_request_usage_stats,
@@ -49,6 +48,7 @@ use crate::{
 };
 
 pub mod geoip;
+pub mod source;
 
 pub async fn save_enrichment_data(
     org_id: &str,
@@ -57,18 +57,47 @@ pub async fn save_enrichment_data(
     thread_id: usize,
     append_data: bool,
 ) -> Result<HttpResponse, Error> {
+    let mut files = Vec::new();
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let content_disposition = field.content_disposition();
+        let filename = content_disposition.get_filename();
+        let mut data = bytes::Bytes::new();
+
+        if filename.is_some() {
+            while let Some(chunk) = field.next().await {
+                let chunked_data = chunk.unwrap();
+                // Reconstruct entire CSV data bytes here to prevent fragmentation of values.
+                data = Bytes::from([data.as_ref(), chunked_data.as_ref()].concat());
+            }
+            files.push(data);
+        }
+    }
+
+    match save_enrichment_csv(org_id, table_name, &files, thread_id, append_data).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(MetaHttpResponse::error(
+            StatusCode::OK.into(),
+            "Saved enrichment table".to_string(),
+        ))),
+        Err(e) => Ok(e.into()),
+    }
+}
+
+/// Writes the records of the CSV files into the enrichment table, replacing
+/// its data unless `append_data`. Returns the number of records written.
+pub async fn save_enrichment_csv(
+    org_id: &str,
+    table_name: &str,
+    files: &[Bytes],
+    thread_id: usize,
+    append_data: bool,
+) -> Result<usize, ServiceError> {
     let start = std::time::Instant::now();
     let mut hour_key = String::new();
     let mut buf: HashMap<String, SchemaRecords> = HashMap::new();
     let stream_name = &format_stream_name(table_name);
 
     if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                "not an ingester".to_string(),
-            )),
-        );
+        return Err(ServiceError::internal("not an ingester"));
     }
 
     // check if we are allowed to ingest
@@ -78,12 +107,9 @@ pub async fn save_enrichment_data(
         stream_name,
         None,
     ) {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                format!("enrichment table [{stream_name}] is being deleted"),
-            )),
-        );
+        return Err(ServiceError::internal(format!(
+            "enrichment table [{stream_name}] is being deleted"
+        )));
     }
 
     let mut schema_evolved = false;
@@ -96,6 +122,32 @@ pub async fn save_enrichment_data(
     )
     .await;
 
+    // parse the files before the current data is deleted, so an invalid file
+    // leaves the table as it is
+    let mut rows = vec![];
+    for data in files.iter() {
+        let mut rdr = csv::Reader::from_reader(data.as_ref());
+        let headers = rdr.headers().map_err(ServiceError::bad_request)?.clone();
+
+        for result in rdr.records() {
+            // The iterator yields Result<StringRecord, Error>, so we check the
+            // error here.
+            let record = result.map_err(ServiceError::bad_request)?;
+            // Transform the record to a JSON value
+            let mut json_record = json::Map::new();
+
+            for (header, field) in headers.iter().zip(record.iter()) {
+                json_record.insert(header.into(), json::Value::String(field.into()));
+            }
+            rows.push(json_record);
+        }
+    }
+    if rows.is_empty() {
+        return Err(ServiceError::bad_request(
+            "No records to ingest for look up table",
+        ));
+    }
+
     if stream_schema.has_fields && !append_data {
         delete_enrichment_table(org_id, stream_name, StreamType::EnrichmentTables).await;
     }
@@ -114,78 +166,46 @@ pub async fn save_enrichment_data(
             .parse::<i64>()
             .unwrap()
     };
-    while let Ok(Some(mut field)) = payload.try_next().await {
-        let content_disposition = field.content_disposition();
-        let filename = content_disposition.get_filename();
-        let mut data = bytes::Bytes::new();
-
-        if filename.is_some() {
-            while let Some(chunk) = field.next().await {
-                let chunked_data = chunk.unwrap();
-                // Reconstruct entire CSV data bytes here to prevent fragmentation of values.
-                data = Bytes::from([data.as_ref(), chunked_data.as_ref()].concat());
-            }
-            let mut rdr = csv::Reader::from_reader(data.as_ref());
-            let headers = rdr.headers()?.clone();
-
-            for result in rdr.records() {
-                // The iterator yields Result<StringRecord, Error>, so we check the
-                // error here.
-                let record = result?;
-                // Transform the record to a JSON value
-                let mut json_record = json::Map::new();
-
-                for (header, field) in headers.iter().zip(record.iter()) {
-                    json_record.insert(header.into(), json::Value::String(field.into()));
-                }
-                json_record.insert(
-                    CONFIG.common.column_timestamp.clone(),
-                    json::Value::Number(timestamp.into()),
-                );
+    for mut json_record in rows {
+        json_record.insert(
+            CONFIG.common.column_timestamp.clone(),
+            json::Value::Number(timestamp.into()),
+        );
 
-                // check for schema evolution
-                if !schema_evolved
-                    && check_for_schema(
-                        org_id,
-                        stream_name,
-                        StreamType::EnrichmentTables,
-                        &mut stream_schema_map,
-                        vec![&json_record],
-                        timestamp,
-                    )
-                    .await
-                    .is_ok()
-                {
-                    schema_evolved = true;
-                }
+        // check for schema evolution
+        if !schema_evolved
+            && check_for_schema(
+                org_id,
+                stream_name,
+                StreamType::EnrichmentTables,
+                &mut stream_schema_map,
+                vec![&json_record],
+                timestamp,
+            )
+            .await
+            .is_ok()
+        {
+            schema_evolved = true;
+        }
 
-                if records.is_empty() {
-                    let schema = stream_schema_map.get(stream_name).unwrap();
-                    let schema_key = schema.hash_key();
-                    hour_key = super::ingestion::get_wal_time_key(
-                        timestamp,
-                        &vec![],
-                        PartitionTimeLevel::Unset,
-                        &json_record,
-                        Some(schema_key),
-                    );
-                }
-                let record = json::Value::Object(json_record);
-                let record_size = json::estimate_json_bytes(&record);
-                records.push(Arc::new(record));
-                records_size += record_size;
-            }
+        if records.is_empty() {
+            let schema = stream_schema_map.get(stream_name).unwrap();
+            let schema_key = schema.hash_key();
+            hour_key = super::ingestion::get_wal_time_key(
+                timestamp,
+                &vec![],
+                PartitionTimeLevel::Unset,
+                &json_record,
+                Some(schema_key),
+            );
         }
+        let record = json::Value::Object(json_record);
+        let record_size = json::estimate_json_bytes(&record);
+        records.push(Arc::new(record));
+        records_size += record_size;
     }
 
-    if records.is_empty() {
-        return Ok(
-            HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                "No records to ingest for look up table".to_string(),
-            )),
-        );
-    }
+    let records_num = records.len();
 
     let schema = stream_schema_map
         .get(stream_name)
@@ -224,10 +244,7 @@ pub async fn save_enrichment_data(
     )
     .await;
 
-    Ok(HttpResponse::Ok().json(MetaHttpResponse::error(
-        StatusCode::OK.into(),
-        "Saved enrichment table".to_string(),
-    )))
+    Ok(records_num)
 }
 
 async fn delete_enrichment_table(org_id: &str, stream_name: &str, stream_type: StreamType) {
@@ -0,0 +1,281 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Enrichment tables refreshed from a url. An ingester claims each due source
+//! under a lock so only one node downloads it, the file is verified and only
+//! replaces the table when its checksum changed. Saving the new checksum
+//! notifies every node, which loads the new data before swapping it in.
+
+use bytes::Bytes;
+use chrono::Utc;
+use config::{cluster, meta::stream::StreamType, RwHashMap, CONFIG};
+use infra::dist_lock;
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{infra::config::ENRICHMENT_TABLES, meta::enrichment_table::EnrichmentTableSource},
+    service::{db, enrichment::StreamTable, error::ServiceError, format_stream_name},
+};
+
+/// Checksum of the data each table was last swapped to on this node.
+static LOADED_CHECKSUMS: Lazy<RwHashMap<String, String>> = Lazy::new(Default::default);
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut source: EnrichmentTableSource,
+) -> Result<(), ServiceError> {
+    source.name = format_stream_name(name);
+    source.url = source.url.trim().to_string();
+    source.sha256_url = source.sha256_url.trim().to_string();
+    for url in [&source.url, &source.sha256_url] {
+        // the checksum url is optional
+        if url.is_empty() && !source.url.is_empty() {
+            continue;
+        }
+        match url::Url::parse(url) {
+            Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {}
+            _ => return Err(ServiceError::bad_request(format!("url [{url}] is invalid"))),
+        }
+    }
+    if source.interval < 60 {
+        return Err(ServiceError::bad_request(
+            "interval must be at least 60 seconds",
+        ));
+    }
+    // keep the state of the refreshes, a changed url is fetched right away
+    match db::enrichment_table::get_source(org_id, &source.name).await {
+        Ok(old) if old.url == source.url => {
+            source.checksum = old.checksum;
+            source.refreshed_at = old.refreshed_at;
+            source.last_error = old.last_error;
+        }
+        _ => {
+            source.checksum = String::new();
+            source.refreshed_at = 0;
+            source.last_error = String::new();
+        }
+    }
+    db::enrichment_table::set_source(org_id, &source)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<EnrichmentTableSource, ServiceError> {
+    db::enrichment_table::get_source(org_id, &format_stream_name(name))
+        .await
+        .map_err(|_| ServiceError::not_found("Enrichment table source not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<EnrichmentTableSource>, ServiceError> {
+    db::enrichment_table::list_sources(org_id)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// Stops refreshing the table, its data is kept.
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    let source = get(org_id, name).await?;
+    db::enrichment_table::delete_source(org_id, &source.name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// Refreshes the sources which are due, returns how many this node refreshed.
+pub async fn refresh_due() -> Result<usize, anyhow::Error> {
+    if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(0);
+    }
+    let now = Utc::now().timestamp_micros();
+    let mut refreshed = 0;
+    for (key, source) in db::enrichment_table::list_all_sources().await? {
+        if !source.is_due(now) {
+            continue;
+        }
+        let Some((org_id, _)) = key.split_once('/') else {
+            continue;
+        };
+        let Some(source) = claim(org_id, &source.name).await? else {
+            continue;
+        };
+        refresh(org_id, source).await;
+        refreshed += 1;
+    }
+    Ok(refreshed)
+}
+
+/// Marks the source as refreshed now so no other node picks it, returns it
+/// unless another node already claimed it.
+async fn claim(org_id: &str, name: &str) -> Result<Option<EnrichmentTableSource>, anyhow::Error> {
+    let lock_key = format!("/enrichment_table_sources/lock/{org_id}/{name}");
+    let locker = dist_lock::lock(&lock_key, 0).await?;
+    let now = Utc::now().timestamp_micros();
+    let ret = match db::enrichment_table::get_source(org_id, name).await {
+        Ok(mut source) if source.is_due(now) => {
+            source.refreshed_at = now;
+            db::enrichment_table::set_source(org_id, &source)
+                .await
+                .map(|_| Some(source))
+        }
+        _ => Ok(None),
+    };
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+async fn refresh(org_id: &str, mut source: EnrichmentTableSource) {
+    match download(&source).await {
+        Ok((_, checksum)) if checksum == source.checksum => {
+            log::debug!("[ENRICHMENT] source of {org_id}/{} unchanged", source.name);
+            source.last_error = String::new();
+        }
+        Ok((data, checksum)) => {
+            match super::save_enrichment_csv(org_id, &source.name, &[data], 0, false).await {
+                Ok(records) => {
+                    log::info!(
+                        "[ENRICHMENT] refreshed {org_id}/{} with {records} records",
+                        source.name
+                    );
+                    source.checksum = checksum;
+                    source.last_error = String::new();
+                }
+                Err(e) => source.last_error = e.to_string(),
+            }
+        }
+        Err(e) => source.last_error = e.to_string(),
+    }
+    if !source.last_error.is_empty() {
+        log::error!(
+            "[ENRICHMENT] refresh {org_id}/{} error: {}",
+            source.name,
+            source.last_error
+        );
+    }
+    // saving the checksum makes every node swap to the new data
+    if let Err(e) = db::enrichment_table::set_source(org_id, &source).await {
+        log::error!(
+            "[ENRICHMENT] save source of {org_id}/{} error: {e}",
+            source.name
+        );
+    }
+}
+
+/// Downloads the file of the source, returns it with its sha256.
+async fn download(source: &EnrichmentTableSource) -> Result<(Bytes, String), anyhow::Error> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(
+            CONFIG.limit.enrichment_source_timeout,
+        ))
+        .build()?;
+    let data = client
+        .get(&source.url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let max_size = CONFIG.limit.enrichment_table_limit * 1024 * 1024;
+    if data.len() > max_size {
+        return Err(anyhow::anyhow!(
+            "file exceeds allowed limit of {} mb",
+            CONFIG.limit.enrichment_table_limit
+        ));
+    }
+    let checksum = sha256::digest(data.as_ref());
+    if !source.sha256_url.is_empty() {
+        let expected = client
+            .get(&source.sha256_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let expected = parse_checksum(&expected);
+        if !expected.eq_ignore_ascii_case(&checksum) {
+            return Err(anyhow::anyhow!(
+                "checksum mismatch, expected {expected} got {checksum}"
+            ));
+        }
+    }
+    Ok((data, checksum))
+}
+
+/// The checksum of a sha256 file, which may be followed by the file name.
+fn parse_checksum(content: &str) -> &str {
+    content.split_whitespace().next().unwrap_or_default()
+}
+
+/// Loads the data the source was refreshed to and swaps it in, the old data
+/// is served until the new one is loaded.
+pub async fn swap_table(org_id: &str, source: &EnrichmentTableSource) {
+    if source.checksum.is_empty() {
+        return;
+    }
+    let key = format!("{org_id}/{}/{}", StreamType::EnrichmentTables, source.name);
+    if LOADED_CHECKSUMS
+        .get(&key)
+        .is_some_and(|v| *v == source.checksum)
+    {
+        return;
+    }
+    let data = match db::enrichment_table::get(org_id, &source.name).await {
+        Ok(data) if !data.is_empty() => data,
+        Ok(_) => {
+            log::warn!("[ENRICHMENT] no data loaded for {key}, keeping the current one");
+            return;
+        }
+        Err(e) => {
+            log::error!("[ENRICHMENT] load {key} error: {e}");
+            return;
+        }
+    };
+    ENRICHMENT_TABLES.insert(
+        key.clone(),
+        StreamTable {
+            org_id: org_id.to_string(),
+            stream_name: source.name.clone(),
+            data,
+        },
+    );
+    LOADED_CHECKSUMS.insert(key, source.checksum.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_due_and_checksum() {
+        let mut source = EnrichmentTableSource {
+            name: "ips".to_string(),
+            url: "https://example.com/ips.csv".to_string(),
+            sha256_url: "".to_string(),
+            interval: 3600,
+            checksum: "".to_string(),
+            refreshed_at: 0,
+            last_error: "".to_string(),
+        };
+        let now = 10_000_000_000;
+        assert!(source.is_due(now));
+        source.refreshed_at = now - 3599 * 1_000_000;
+        assert!(!source.is_due(now));
+        source.refreshed_at = now - 3600 * 1_000_000;
+        assert!(source.is_due(now));
+
+        assert_eq!(parse_checksum("abc123  ips.csv\n"), "abc123");
+        assert_eq!(parse_checksum("abc123"), "abc123");
+        assert_eq!(parse_checksum(""), "");
+    }
+}
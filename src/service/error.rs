@@ -0,0 +1,159 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use actix_web::{http::StatusCode, HttpResponse};
+use infra::errors::ErrorCodes;
+use thiserror::Error as ThisError;
+
+use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+
+pub type Result<T, E = ServiceError> = std::result::Result<T, E>;
+
+/// The error returned by the services, the HTTP handlers and gRPC services
+/// map it to their status codes.
+#[derive(ThisError, Debug)]
+pub enum ServiceError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Unavailable(String),
+    /// A search error, the clients get its error code
+    #[error("{}", .0.get_message())]
+    Search(ErrorCodes),
+    #[error("{0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl ServiceError {
+    pub fn bad_request(msg: impl ToString) -> Self {
+        Self::BadRequest(msg.to_string())
+    }
+
+    pub fn not_found(msg: impl ToString) -> Self {
+        Self::NotFound(msg.to_string())
+    }
+
+    pub fn internal(msg: impl ToString) -> Self {
+        Self::Internal(anyhow::anyhow!(msg.to_string()))
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Search(code) => match code {
+                ErrorCodes::SearchCancelQuery(_) => StatusCode::TOO_MANY_REQUESTS,
+                ErrorCodes::SearchStreamNotFound(_) => StatusCode::NOT_FOUND,
+                ErrorCodes::SearchSQLNotValid(_)
+                | ErrorCodes::FullTextSearchFieldNotFound
+                | ErrorCodes::SearchFieldNotFound(_)
+                | ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The HTTP response of the error, the ones of searches hold the trace id
+    /// of the search.
+    pub fn into_http_response(self, trace_id: Option<String>) -> HttpResponse {
+        let code = self.status_code();
+        match self {
+            Self::Search(e) => HttpResponse::build(code)
+                .json(MetaHttpResponse::error_code_with_trace_id(e, trace_id)),
+            e => {
+                HttpResponse::build(code).json(MetaHttpResponse::error(code.into(), e.to_string()))
+            }
+        }
+    }
+}
+
+impl From<infra::errors::Error> for ServiceError {
+    fn from(e: infra::errors::Error) -> Self {
+        use infra::errors::{DbError, Error};
+        match e {
+            Error::DbError(DbError::KeyNotExists(key)) => {
+                Self::NotFound(format!("{key} not found"))
+            }
+            Error::DbError(e @ DbError::RevisionConflict(..)) => Self::Conflict(e.to_string()),
+            Error::ErrorCode(e) => Self::Search(e),
+            e => Self::Internal(e.into()),
+        }
+    }
+}
+
+impl From<ServiceError> for HttpResponse {
+    fn from(e: ServiceError) -> Self {
+        e.into_http_response(None)
+    }
+}
+
+impl From<ServiceError> for tonic::Status {
+    fn from(e: ServiceError) -> Self {
+        let msg = e.to_string();
+        match e {
+            ServiceError::BadRequest(_) => tonic::Status::invalid_argument(msg),
+            ServiceError::Forbidden(_) => tonic::Status::permission_denied(msg),
+            ServiceError::NotFound(_) => tonic::Status::not_found(msg),
+            ServiceError::Conflict(_) => tonic::Status::aborted(msg),
+            ServiceError::Unavailable(_) => tonic::Status::unavailable(msg),
+            // the leader decodes the error code from the message
+            ServiceError::Search(code) => tonic::Status::internal(code.to_json()),
+            ServiceError::Internal(_) => tonic::Status::internal(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_error_mapping() {
+        let e = ServiceError::not_found("stream not found");
+        assert_eq!(e.status_code(), StatusCode::NOT_FOUND);
+        let status: tonic::Status = e.into();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert_eq!(status.message(), "stream not found");
+
+        let e: ServiceError = infra::errors::Error::DbError(
+            infra::errors::DbError::RevisionConflict("/schema/default/logs/k8s".to_string(), 1, 2),
+        )
+        .into();
+        assert_eq!(e.status_code(), StatusCode::CONFLICT);
+
+        let resp: HttpResponse = ServiceError::internal("boom").into();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let e: ServiceError =
+            infra::errors::Error::ErrorCode(ErrorCodes::SearchCancelQuery("q".to_string())).into();
+        assert_eq!(e.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        let status: tonic::Status = e.into();
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert!(matches!(
+            ErrorCodes::from_json(status.message()),
+            Ok(ErrorCodes::SearchCancelQuery(_))
+        ));
+    }
+}
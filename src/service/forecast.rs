@@ -0,0 +1,428 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use config::{
+    meta::{search, stream::StreamType},
+    utils::{
+        json::{self, Map, Value},
+        time::parse_timestamp_micro_from_value,
+    },
+};
+
+use crate::{
+    common::meta::forecast::{
+        Forecast, ForecastFill, ForecastMethod, ForecastPoint, ForecastRequest, ForecastResponse,
+        ForecastSeries,
+    },
+    service::{
+        error::{Result, ServiceError},
+        search as SearchService,
+    },
+};
+
+/// Most time buckets read by a forecast, and kept in a series once the empty
+/// buckets are filled
+pub const MAX_ROWS: usize = 10_000;
+
+/// Most buckets forecast after the last bucket of a series
+const MAX_STEPS: i64 = 10_000;
+
+/// Smoothing factors of the level, the trend and the season
+const ALPHA: f64 = 0.5;
+const BETA: f64 = 0.1;
+const GAMMA: f64 = 0.1;
+
+pub fn validate(forecast: &Forecast) -> Result<()> {
+    if forecast.timestamp_column.is_empty() || forecast.value_column.is_empty() {
+        return Err(ServiceError::bad_request(
+            "forecast needs a timestamp_column and a value_column",
+        ));
+    }
+    if forecast.horizon <= 0 {
+        return Err(ServiceError::bad_request(
+            "forecast horizon should be positive",
+        ));
+    }
+    if forecast.season == 1 {
+        return Err(ServiceError::bad_request(
+            "forecast season should have at least 2 buckets",
+        ));
+    }
+    Ok(())
+}
+
+/// Runs the query and forecasts the series of buckets it returns
+pub async fn search(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    req: &ForecastRequest,
+) -> Result<ForecastResponse> {
+    validate(&req.forecast)?;
+    let search_req = search::Request {
+        query: search::Query {
+            sql: req.sql.clone(),
+            size: MAX_ROWS + 1,
+            start_time: req.start_time,
+            end_time: req.end_time,
+            sql_mode: "full".to_string(),
+            ..Default::default()
+        },
+        aggs: Default::default(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search("", org_id, stream_type, user_id, &search_req)
+        .await
+        .map_err(|e| ServiceError::bad_request(format!("search error: {e}")))?;
+    check_rows(res.hits.len())?;
+    let rows = res
+        .hits
+        .into_iter()
+        .filter_map(|v| match v {
+            Value::Object(v) => Some(v),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    Ok(ForecastResponse {
+        series: forecast_rows(&req.forecast, &rows, req.end_time).await,
+    })
+}
+
+/// The forecast would miss the latest buckets of a query returning more rows
+/// than it reads
+pub fn check_rows(rows: usize) -> Result<()> {
+    if rows > MAX_ROWS {
+        return Err(ServiceError::bad_request(format!(
+            "forecast query returns more than {MAX_ROWS} buckets, use larger buckets or a shorter time range"
+        )));
+    }
+    Ok(())
+}
+
+/// Groups the rows into series by their other columns and forecasts each
+/// series with two complete buckets at least. The bucket still filling up at
+/// `end_time`, the end of the query, is left out.
+pub async fn forecast_rows(
+    forecast: &Forecast,
+    rows: &[Map<String, Value>],
+    end_time: i64,
+) -> Vec<ForecastSeries> {
+    let mut groups: BTreeMap<String, ForecastSeries> = BTreeMap::new();
+    for row in rows {
+        let Some(timestamp) = row
+            .get(&forecast.timestamp_column)
+            .and_then(|v| parse_timestamp_micro_from_value(v).ok())
+        else {
+            continue;
+        };
+        let Some(value) = row.get(&forecast.value_column).and_then(|v| v.as_f64()) else {
+            continue;
+        };
+        let mut labels = row.clone();
+        labels.remove(&forecast.timestamp_column);
+        labels.remove(&forecast.value_column);
+        let key = json::to_string(&labels).unwrap_or_default();
+        groups
+            .entry(key)
+            .or_insert_with(|| ForecastSeries {
+                labels,
+                ..Default::default()
+            })
+            .points
+            .push(ForecastPoint { timestamp, value });
+    }
+
+    let mut series = Vec::with_capacity(groups.len());
+    for mut s in groups.into_values() {
+        s.points.sort_by_key(|p| p.timestamp);
+        let Some(step) = bucket_interval(&s.points) else {
+            continue;
+        };
+        if s.points.last().unwrap().timestamp + step > end_time {
+            s.points.pop();
+        }
+        s.points = fill_buckets(&s.points, step, forecast.fill);
+        if s.points.len() < 2 {
+            continue;
+        }
+        let steps = ((forecast.horizon * 60 * 1_000_000 + step - 1) / step).min(MAX_STEPS);
+        let last = s.points.last().unwrap().timestamp;
+        let timestamps = (1..=steps).map(|i| last + i * step).collect::<Vec<_>>();
+        let predicted = match forecast.method {
+            ForecastMethod::Linear => linear(&s.points, &timestamps),
+            ForecastMethod::HoltWinters => {
+                let values = s.points.iter().map(|p| p.value).collect::<Vec<_>>();
+                holt_winters(&values, forecast.season, timestamps.len())
+            }
+        };
+        s.forecast = timestamps
+            .into_iter()
+            .zip(predicted)
+            .map(|(timestamp, value)| ForecastPoint { timestamp, value })
+            .collect();
+        if let Some(condition) = forecast.condition.as_ref() {
+            for point in s.forecast.iter() {
+                let mut row = s.labels.clone();
+                row.insert(forecast.value_column.clone(), point.value.into());
+                if condition.evaluate(&row).await {
+                    s.crosses_at = Some(*point);
+                    break;
+                }
+            }
+        }
+        series.push(s);
+    }
+    series
+}
+
+/// The rows an alert fires with, one per series crossing the condition of
+/// the forecast
+pub fn crossing_rows(forecast: &Forecast, series: &[ForecastSeries]) -> Vec<Map<String, Value>> {
+    series
+        .iter()
+        .filter_map(|s| {
+            let point = s.crosses_at?;
+            let mut row = s.labels.clone();
+            row.insert(forecast.timestamp_column.clone(), point.timestamp.into());
+            row.insert(forecast.value_column.clone(), point.value.into());
+            Some(row)
+        })
+        .collect()
+}
+
+/// The median interval between the buckets
+fn bucket_interval(points: &[ForecastPoint]) -> Option<i64> {
+    let mut steps = points
+        .windows(2)
+        .map(|w| w[1].timestamp - w[0].timestamp)
+        .filter(|step| *step > 0)
+        .collect::<Vec<_>>();
+    if steps.is_empty() {
+        return None;
+    }
+    steps.sort_unstable();
+    Some(steps[steps.len() / 2])
+}
+
+/// Adds the buckets missing between the points, `MAX_ROWS` buckets at most,
+/// so that the series has a bucket every `step`
+fn fill_buckets(points: &[ForecastPoint], step: i64, fill: ForecastFill) -> Vec<ForecastPoint> {
+    let mut filled: Vec<ForecastPoint> = Vec::with_capacity(points.len());
+    for point in points {
+        if let Some(prev) = filled.last().copied() {
+            let missing = ((point.timestamp - prev.timestamp) / step - 1).clamp(0, MAX_ROWS as i64);
+            for i in 1..=missing {
+                let timestamp = prev.timestamp + i * step;
+                let value = match fill {
+                    ForecastFill::Zero => 0.0,
+                    ForecastFill::Linear => {
+                        prev.value
+                            + (point.value - prev.value) * (timestamp - prev.timestamp) as f64
+                                / (point.timestamp - prev.timestamp) as f64
+                    }
+                };
+                filled.push(ForecastPoint { timestamp, value });
+            }
+        }
+        filled.push(*point);
+    }
+    // the latest buckets tell the most about the next ones
+    if filled.len() > MAX_ROWS {
+        filled.drain(..filled.len() - MAX_ROWS);
+    }
+    filled
+}
+
+/// Least squares line through the points by their timestamps, evaluated at
+/// `timestamps`
+pub fn linear(points: &[ForecastPoint], timestamps: &[i64]) -> Vec<f64> {
+    let Some(first) = points.first() else {
+        return vec![];
+    };
+    // relative to the first point, the squares of microseconds lose precision
+    let x = |timestamp: i64| (timestamp - first.timestamp) as f64;
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| x(p.timestamp)).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.value).sum::<f64>() / n;
+    let (mut sxy, mut sxx) = (0.0, 0.0);
+    for p in points {
+        let dx = x(p.timestamp) - mean_x;
+        sxy += dx * (p.value - mean_y);
+        sxx += dx * dx;
+    }
+    let slope = if sxx == 0.0 { 0.0 } else { sxy / sxx };
+    let intercept = mean_y - slope * mean_x;
+    timestamps
+        .iter()
+        .map(|ts| intercept + slope * x(*ts))
+        .collect()
+}
+
+/// Additive Holt-Winters with a season of `season` values, Holt's linear
+/// trend without a season or with less than two seasons of values. The
+/// values are a bucket apart.
+pub fn holt_winters(values: &[f64], season: usize, steps: usize) -> Vec<f64> {
+    if values.len() < 2 {
+        return values.last().map(|v| vec![*v; steps]).unwrap_or_default();
+    }
+    if season < 2 || values.len() < 2 * season {
+        let mut level = values[0];
+        let mut trend = values[1] - values[0];
+        for y in values[1..].iter() {
+            let last_level = level;
+            level = ALPHA * y + (1.0 - ALPHA) * (level + trend);
+            trend = BETA * (level - last_level) + (1.0 - BETA) * trend;
+        }
+        return (1..=steps).map(|h| level + h as f64 * trend).collect();
+    }
+
+    // the first two seasons give the initial level, trend and season
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let first = mean(&values[..season]);
+    let second = mean(&values[season..2 * season]);
+    let mut level = first;
+    let mut trend = (second - first) / season as f64;
+    let mut seasonals = values[..season]
+        .iter()
+        .map(|y| y - first)
+        .collect::<Vec<_>>();
+    for (i, y) in values.iter().enumerate() {
+        let seasonal = seasonals[i % season];
+        let last_level = level;
+        level = ALPHA * (y - seasonal) + (1.0 - ALPHA) * (level + trend);
+        trend = BETA * (level - last_level) + (1.0 - BETA) * trend;
+        seasonals[i % season] = GAMMA * (y - level) + (1.0 - GAMMA) * seasonal;
+    }
+    let n = values.len();
+    (1..=steps)
+        .map(|h| level + h as f64 * trend + seasonals[(n + h - 1) % season])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::alerts::{Condition, Operator};
+
+    fn points(values: &[(i64, f64)]) -> Vec<ForecastPoint> {
+        values
+            .iter()
+            .map(|(timestamp, value)| ForecastPoint {
+                timestamp: *timestamp,
+                value: *value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_linear() {
+        let start = 1_700_000_000_000_000i64;
+        let values = (0..10)
+            .map(|i| (start + i * 1000, 2.0 * i as f64 + 1.0))
+            .collect::<Vec<_>>();
+        let predicted = linear(&points(&values), &[start + 10_000, start + 12_000]);
+        assert!((predicted[0] - 21.0).abs() < 1e-9);
+        assert!((predicted[1] - 25.0).abs() < 1e-9);
+        // a gap doesn't bend the line
+        let values = [(0, 0.0), (1, 1.0), (5, 5.0), (6, 6.0)];
+        assert!((linear(&points(&values), &[8])[0] - 8.0).abs() < 1e-9);
+        assert_eq!(linear(&points(&[(0, 5.0)]), &[1, 2]), vec![5.0, 5.0]);
+        assert!(linear(&[], &[1]).is_empty());
+    }
+
+    #[test]
+    fn test_fill_buckets() {
+        let values = points(&[(0, 2.0), (10, 4.0), (40, 10.0)]);
+        assert_eq!(
+            fill_buckets(&values, 10, ForecastFill::Zero),
+            points(&[(0, 2.0), (10, 4.0), (20, 0.0), (30, 0.0), (40, 10.0)])
+        );
+        assert_eq!(
+            fill_buckets(&values, 10, ForecastFill::Linear),
+            points(&[(0, 2.0), (10, 4.0), (20, 6.0), (30, 8.0), (40, 10.0)])
+        );
+    }
+
+    #[test]
+    fn test_holt_winters() {
+        // a trend of 1 per bucket and a season of 4 buckets
+        let pattern = [0.0, 5.0, 0.0, -5.0];
+        let values = (0..40)
+            .map(|i| i as f64 + pattern[i % 4])
+            .collect::<Vec<_>>();
+        let predicted = holt_winters(&values, 4, 4);
+        for (h, v) in predicted.iter().enumerate() {
+            let expected = (40 + h) as f64 + pattern[(40 + h) % 4];
+            assert!((v - expected).abs() < 1.0, "{v} != {expected}");
+        }
+        // no season, the trend goes on
+        let values = (0..20).map(|i| i as f64).collect::<Vec<_>>();
+        let predicted = holt_winters(&values, 0, 2);
+        assert!((predicted[1] - 21.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_forecast_rows() {
+        let forecast = Forecast {
+            timestamp_column: "zo_sql_key".to_string(),
+            value_column: "used".to_string(),
+            horizon: 5 * 60,
+            method: ForecastMethod::Linear,
+            season: 0,
+            fill: ForecastFill::Zero,
+            condition: Some(Condition {
+                column: "used".to_string(),
+                operator: Operator::GreaterThanEquals,
+                value: json::json!(100),
+                ignore_case: false,
+            }),
+        };
+        let start = 1_700_000_000_000_000i64;
+        let hour = 3600 * 1_000_000i64;
+        let mut rows = vec![];
+        for (host, growth) in [("a", 10), ("b", 0)] {
+            for i in 0..5i64 {
+                let mut row = Map::new();
+                row.insert("zo_sql_key".to_string(), (start + i * hour).into());
+                row.insert("used".to_string(), (50 + i * growth).into());
+                row.insert("host".to_string(), host.into());
+                rows.push(row);
+            }
+        }
+        let series = forecast_rows(&forecast, &rows, start + 5 * hour).await;
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].forecast.len(), 5);
+        // host a reaches 100 at the 6th hour
+        assert_eq!(
+            series[0].crosses_at,
+            Some(ForecastPoint {
+                timestamp: start + 5 * hour,
+                value: 100.0
+            })
+        );
+        assert_eq!(series[1].crosses_at, None);
+        let crossing = crossing_rows(&forecast, &series);
+        assert_eq!(crossing.len(), 1);
+        assert_eq!(crossing[0].get("host").unwrap(), "a");
+
+        // the last hour is still filling up
+        let series = forecast_rows(&forecast, &rows, start + 4 * hour + 1).await;
+        assert_eq!(series[0].points.len(), 4);
+    }
+}
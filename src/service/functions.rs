@@ -13,13 +13,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::io::Error;
+use std::{collections::HashMap, io::Error};
 
 use actix_web::{
     http::{self, StatusCode},
     HttpResponse,
 };
-use config::meta::stream::StreamType;
+use config::{
+    meta::stream::StreamType,
+    utils::{flatten, json},
+    CONFIG,
+};
+use vector_enrichment::TableRegistry;
 
 use crate::{
     common::{
@@ -27,13 +32,19 @@ use crate::{
         meta::{
             authz::Authz,
             functions::{
-                FunctionList, StreamFunctionsList, StreamOrder, StreamTransform, Transform,
+                FunctionList, FunctionTestResponse, FunctionTestResult, StreamFunctionsList,
+                StreamOrder, StreamTransform, Transform, VRLResultResolver, GLOBAL_FUNCTIONS_ORG,
             },
             http::HttpResponse as MetaHttpResponse,
+            revisions::{ObjectType, RevisionAction},
         },
-        utils::auth::{remove_ownership, set_ownership},
+        utils::auth::{is_root_user, remove_ownership, set_ownership},
+    },
+    service::{
+        db,
+        ingestion::{compile_vrl_function, init_functions_runtime, try_apply_vrl_fn},
+        revisions,
     },
-    service::{db, ingestion::compile_vrl_function},
 };
 
 const FN_SUCCESS: &str = "Function saved successfully";
@@ -44,15 +55,34 @@ const FN_DELETED: &str = "Function deleted";
 const FN_ALREADY_EXIST: &str = "Function already exist";
 const FN_IN_USE: &str =
     "Function is associated with streams, please remove association from streams before deleting:";
+const FN_GLOBAL_ROOT_ONLY: &str = "Only root users can change global functions";
+const FN_TEST_VRL_ONLY: &str = "Only VRL functions can be tested";
+const FN_TEST_MAX_RECORDS: usize = 1000;
 
 #[tracing::instrument(skip(func))]
-pub async fn save_function(org_id: String, mut func: Transform) -> Result<HttpResponse, Error> {
-    if let Some(_existing_fn) = check_existing_fn(&org_id, &func.name).await {
+pub async fn save_function(
+    org_id: String,
+    mut func: Transform,
+    user_id: &str,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_global_access(&org_id, user_id) {
+        return Ok(resp);
+    }
+    let existing_fn = check_existing_fn(&org_id, &func.name).await;
+    if existing_fn
+        .as_ref()
+        .is_some_and(|existing_fn| !existing_fn.global)
+    {
         Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
             StatusCode::BAD_REQUEST.into(),
             FN_ALREADY_EXIST.to_string(),
         )))
     } else {
+        // overriding a global function keeps the streams it is applied to
+        if let Some(existing_fn) = existing_fn {
+            func.streams = existing_fn.streams;
+        }
+        func.global = false;
         if !func.function.ends_with('.') {
             func.function = format!("{} \n .", func.function);
         }
@@ -74,6 +104,15 @@ pub async fn save_function(org_id: String, mut func: Transform) -> Result<HttpRe
             );
         } else {
             set_ownership(&org_id, "functions", Authz::new(&func.name)).await;
+            revisions::record(
+                &org_id,
+                ObjectType::Function,
+                &func.name,
+                user_id,
+                RevisionAction::Saved,
+                &func,
+            )
+            .await;
 
             Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
                 http::StatusCode::OK.into(),
@@ -88,7 +127,11 @@ pub async fn update_function(
     org_id: &str,
     fn_name: &str,
     mut func: Transform,
+    user_id: &str,
 ) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_global_access(org_id, user_id) {
+        return Ok(resp);
+    }
     let existing_fn = match check_existing_fn(org_id, fn_name).await {
         Some(function) => function,
         None => {
@@ -105,6 +148,7 @@ pub async fn update_function(
     // UI mostly like in 1st version won't send streams, so we need to add them back
     // from existing function
     func.streams = existing_fn.streams;
+    func.global = false;
 
     if !func.function.ends_with('.') {
         func.function = format!("{} \n .", func.function);
@@ -126,6 +170,15 @@ pub async fn update_function(
             )),
         );
     }
+    revisions::record(
+        org_id,
+        ObjectType::Function,
+        &func.name,
+        user_id,
+        RevisionAction::Saved,
+        &func,
+    )
+    .await;
     Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
         http::StatusCode::OK.into(),
         FN_SUCCESS.to_string(),
@@ -138,9 +191,18 @@ pub async fn list_functions(
     permitted: Option<Vec<String>>,
 ) -> Result<HttpResponse, Error> {
     if let Ok(functions) = db::functions::list(&org_id).await {
+        let functions = if org_id == GLOBAL_FUNCTIONS_ORG {
+            functions
+        } else {
+            let global = db::functions::list(GLOBAL_FUNCTIONS_ORG)
+                .await
+                .unwrap_or_default();
+            merge_global_functions(functions, global)
+        };
         let mut result = Vec::new();
         for function in functions {
-            if permitted.is_none()
+            if function.global
+                || permitted.is_none()
                 || permitted
                     .as_ref()
                     .unwrap()
@@ -161,7 +223,14 @@ pub async fn list_functions(
 }
 
 #[tracing::instrument]
-pub async fn delete_function(org_id: String, fn_name: String) -> Result<HttpResponse, Error> {
+pub async fn delete_function(
+    org_id: String,
+    fn_name: String,
+    user_id: &str,
+) -> Result<HttpResponse, Error> {
+    if let Some(resp) = check_global_access(&org_id, user_id) {
+        return Ok(resp);
+    }
     let existing_fn = match check_existing_fn(&org_id, &fn_name).await {
         Some(function) => function,
         None => {
@@ -171,7 +240,7 @@ pub async fn delete_function(org_id: String, fn_name: String) -> Result<HttpResp
             )));
         }
     };
-    if let Some(val) = existing_fn.streams {
+    if let Some(val) = &existing_fn.streams {
         if !val.is_empty() {
             let names = val
                 .iter()
@@ -192,10 +261,28 @@ pub async fn delete_function(org_id: String, fn_name: String) -> Result<HttpResp
             }
         }
     }
+    if org_id == GLOBAL_FUNCTIONS_ORG {
+        let names = global_fn_streams(&fn_name).join(", ");
+        if !names.is_empty() {
+            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                StatusCode::BAD_REQUEST.into(),
+                format!("{} {}", FN_IN_USE, names),
+            )));
+        }
+    }
     let result = db::functions::delete(&org_id, &fn_name).await;
     match result {
         Ok(_) => {
             remove_ownership(&org_id, "functions", Authz::new(&fn_name)).await;
+            revisions::record(
+                &org_id,
+                ObjectType::Function,
+                &fn_name,
+                user_id,
+                RevisionAction::Deleted,
+                &existing_fn,
+            )
+            .await;
 
             Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
                 http::StatusCode::OK.into(),
@@ -281,7 +368,11 @@ pub async fn add_function_to_stream(
     fn_name: &str,
     mut stream_order: StreamOrder,
 ) -> Result<HttpResponse, Error> {
-    let mut existing_fn = match check_existing_fn(org_id, fn_name).await {
+    let existing_fn = match check_existing_fn(org_id, fn_name).await {
+        Some(function) => Some(function),
+        None => global_fn_reference(org_id, fn_name).await,
+    };
+    let mut existing_fn = match existing_fn {
         Some(function) => function,
         None => {
             return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
@@ -321,6 +412,92 @@ pub async fn add_function_to_stream(
     }
 }
 
+/// Runs the function against sample records the way ingestion would, without
+/// storing anything.
+#[tracing::instrument(skip(records))]
+pub async fn test_function(
+    org_id: &str,
+    fn_name: &str,
+    records: Vec<json::Value>,
+) -> Result<HttpResponse, Error> {
+    if records.len() > FN_TEST_MAX_RECORDS {
+        return Ok(MetaHttpResponse::bad_request(format!(
+            "Too many records, at most {FN_TEST_MAX_RECORDS} can be tested at once"
+        )));
+    }
+    let func = match check_existing_fn(org_id, fn_name).await {
+        Some(function) if !function.global => Some(function),
+        _ if org_id != GLOBAL_FUNCTIONS_ORG => {
+            db::functions::get(GLOBAL_FUNCTIONS_ORG, fn_name).await.ok()
+        }
+        _ => None,
+    };
+    let Some(func) = func else {
+        return Ok(MetaHttpResponse::not_found(FN_NOT_FOUND));
+    };
+    if func.trans_type != Some(0) {
+        return Ok(MetaHttpResponse::bad_request(FN_TEST_VRL_ONLY));
+    }
+    // compiling and running the function is CPU bound, keep it off the workers
+    // serving the requests
+    let org_id = org_id.to_string();
+    let results = tokio::task::spawn_blocking(move || test_records(&org_id, &func, records)).await;
+    match results {
+        Ok(Ok(results)) => Ok(MetaHttpResponse::json(FunctionTestResponse { results })),
+        Ok(Err(e)) => Ok(MetaHttpResponse::bad_request(e)),
+        Err(e) => Ok(MetaHttpResponse::internal_error(e)),
+    }
+}
+
+fn test_records(
+    org_id: &str,
+    func: &Transform,
+    records: Vec<json::Value>,
+) -> Result<Vec<FunctionTestResult>, Error> {
+    let vrl_runtime_config = compile_vrl_function(&func.function, org_id)?;
+    let registry = vrl_runtime_config
+        .config
+        .get_custom::<TableRegistry>()
+        .unwrap();
+    registry.finish_load();
+    let resolver = VRLResultResolver {
+        program: vrl_runtime_config.program,
+        fields: vrl_runtime_config.fields,
+    };
+
+    let mut runtime = init_functions_runtime();
+    Ok(records
+        .into_iter()
+        .map(|record| test_record(&mut runtime, &resolver, record))
+        .collect())
+}
+
+fn test_record(
+    runtime: &mut vrl::compiler::runtime::Runtime,
+    resolver: &VRLResultResolver,
+    record: json::Value,
+) -> FunctionTestResult {
+    let start = std::time::Instant::now();
+    let level = CONFIG.limit.ingest_flatten_level;
+    let result = flatten::flatten_with_level(record, level)
+        .map_err(|e| e.to_string())
+        .and_then(|value| try_apply_vrl_fn(runtime, resolver, &value))
+        .and_then(|value| flatten::flatten_with_level(value, level).map_err(|e| e.to_string()));
+    let took = start.elapsed().as_micros() as u64;
+    match result {
+        Ok(output) => FunctionTestResult {
+            output: Some(output),
+            error: None,
+            took,
+        },
+        Err(error) => FunctionTestResult {
+            output: None,
+            error: Some(error),
+            took,
+        },
+    }
+}
+
 fn extract_num_args(func: &mut Transform) {
     if func.trans_type.unwrap() == 1 {
         let src: String = func.function.to_owned();
@@ -345,6 +522,73 @@ async fn check_existing_fn(org_id: &str, fn_name: &str) -> Option<Transform> {
     }
 }
 
+/// An org entry referring to the global function `fn_name`, which is
+/// resolved on use so that changes to the global function apply everywhere.
+async fn global_fn_reference(org_id: &str, fn_name: &str) -> Option<Transform> {
+    if org_id == GLOBAL_FUNCTIONS_ORG {
+        return None;
+    }
+    let global = db::functions::get(GLOBAL_FUNCTIONS_ORG, fn_name)
+        .await
+        .ok()?;
+    Some(Transform {
+        function: String::new(),
+        streams: None,
+        global: true,
+        ..global
+    })
+}
+
+/// The functions of an org followed by the global functions it doesn't
+/// override. References to global functions are replaced by the global
+/// definitions, keeping the streams they are applied to in the org.
+fn merge_global_functions(functions: Vec<Transform>, global: Vec<Transform>) -> Vec<Transform> {
+    let mut references = HashMap::new();
+    let mut result = Vec::with_capacity(functions.len() + global.len());
+    for function in functions {
+        if function.global {
+            references.insert(function.name.clone(), function.streams);
+        } else {
+            result.push(function);
+        }
+    }
+    for mut function in global {
+        if result.iter().any(|f| f.name == function.name) {
+            continue;
+        }
+        function.global = true;
+        function.streams = references.remove(&function.name).flatten();
+        result.push(function);
+    }
+    result
+}
+
+/// The streams of the orgs the global function `fn_name` is applied to, as
+/// `org/stream_type/stream`.
+fn global_fn_streams(fn_name: &str) -> Vec<String> {
+    let mut names = STREAM_FUNCTIONS
+        .iter()
+        .filter(|entry| !entry.key().starts_with(&format!("{GLOBAL_FUNCTIONS_ORG}/")))
+        .filter(|entry| {
+            entry
+                .value()
+                .list
+                .iter()
+                .any(|f| f.transform.global && f.transform.name == fn_name && !f.is_removed)
+        })
+        .map(|entry| entry.key().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn check_global_access(org_id: &str, user_id: &str) -> Option<HttpResponse> {
+    if org_id == GLOBAL_FUNCTIONS_ORG && !is_root_user(user_id) {
+        return Some(MetaHttpResponse::forbidden(FN_GLOBAL_ROOT_ONLY));
+    }
+    None
+}
+
 fn _remove_stream_fn_from_cache(key: &str, fn_name: &str) {
     if let Some(val) = STREAM_FUNCTIONS.clone().get(key) {
         if val.list.len() > 1 {
@@ -375,6 +619,7 @@ mod tests {
             streams: None,
             num_args: 0,
             trans_type: Some(1),
+            global: false,
         };
 
         let mut vrl_trans = Transform {
@@ -389,6 +634,7 @@ mod tests {
                 order: 0,
                 is_removed: false,
             }]),
+            global: false,
         };
 
         extract_num_args(&mut trans);
@@ -398,16 +644,117 @@ mod tests {
 
         assert_eq!(trans.num_args, 1);
 
-        let res = save_function("nexus".to_owned(), trans).await;
+        let res = save_function("nexus".to_owned(), trans, "root@example.com").await;
         assert!(res.is_ok());
 
         let list_resp = list_functions("nexus".to_string(), None).await;
         assert!(list_resp.is_ok());
 
         assert!(
-            delete_function("nexus".to_string(), "dummyfn".to_owned())
-                .await
-                .is_ok()
+            delete_function(
+                "nexus".to_string(),
+                "dummyfn".to_owned(),
+                "root@example.com"
+            )
+            .await
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_test_record() {
+        let vrl = compile_vrl_function(".total = to_int!(.count) + 1 \n .", "nexus").unwrap();
+        let resolver = VRLResultResolver {
+            program: vrl.program,
+            fields: vrl.fields,
+        };
+        let mut runtime = init_functions_runtime();
+
+        let result = test_record(
+            &mut runtime,
+            &resolver,
+            json::json!({"count": 2, "labels": {"app": "web"}}),
+        );
+        let output = result.output.unwrap();
+        assert_eq!(output["total"], 3);
+        assert_eq!(output["labels_app"], "web");
+        assert!(result.error.is_none());
+
+        let result = test_record(&mut runtime, &resolver, json::json!({"count": "two"}));
+        assert!(result.output.is_none());
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn test_merge_global_functions() {
+        let function = |name: &str, body: &str, global: bool| Transform {
+            function: body.to_owned(),
+            name: name.to_owned(),
+            params: "row".to_owned(),
+            num_args: 1,
+            trans_type: Some(0),
+            streams: None,
+            global,
+        };
+        let mut reference = function("parse_nginx", "", true);
+        reference.streams = Some(vec![StreamOrder {
+            stream: "nginx".to_owned(),
+            stream_type: StreamType::Logs,
+            order: 1,
+            is_removed: false,
+        }]);
+        let org = vec![function("parse_json", ".org = 1", false), reference];
+        let global = vec![
+            function("parse_json", ".global = 1", false),
+            function("parse_nginx", ".nginx = 1", false),
+            function("parse_syslog", ".syslog = 1", false),
+        ];
+
+        let merged = merge_global_functions(org, global);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].function, ".org = 1");
+        assert!(!merged[0].global);
+        assert_eq!(merged[1].name, "parse_nginx");
+        assert_eq!(merged[1].function, ".nginx = 1");
+        assert!(merged[1].global);
+        assert_eq!(merged[1].streams.as_ref().unwrap()[0].stream, "nginx");
+        assert_eq!(merged[2].name, "parse_syslog");
+        assert!(merged[2].streams.is_none());
+    }
+
+    #[test]
+    fn test_global_fn_streams() {
+        let stream_fn = |name: &str, global: bool| StreamTransform {
+            transform: Transform {
+                function: String::new(),
+                name: name.to_string(),
+                params: "row".to_string(),
+                num_args: 0,
+                trans_type: Some(0),
+                streams: None,
+                global,
+            },
+            stream: "nginx".to_string(),
+            order: 0,
+            stream_type: StreamType::Logs,
+            is_removed: false,
+        };
+        STREAM_FUNCTIONS.insert(
+            "test_global_fn_org/logs/nginx".to_string(),
+            StreamFunctionsList {
+                list: vec![stream_fn("test_global_fn", true)],
+            },
+        );
+        STREAM_FUNCTIONS.insert(
+            "test_global_fn_other/logs/nginx".to_string(),
+            StreamFunctionsList {
+                list: vec![stream_fn("test_global_fn", false)],
+            },
+        );
+        assert_eq!(
+            global_fn_streams("test_global_fn"),
+            vec!["test_global_fn_org/logs/nginx".to_string()]
         );
+        assert!(global_fn_streams("test_global_fn_unused").is_empty());
     }
 }
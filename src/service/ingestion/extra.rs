@@ -0,0 +1,135 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::{
+    meta::stream::StreamType,
+    utils::json::{self, Map, Value},
+};
+use infra::schema::STREAM_SETTINGS;
+
+use super::reserved::SYSTEM_COLUMNS;
+
+/// Column holding, as JSON, the keys which didn't fit in the field limit of the
+/// stream.
+pub const EXTRA_COLUMN: &str = "_extra";
+
+/// Field limit of the stream and the keys kept as columns, the promoted and the
+/// encrypted ones, `None` when the stream has no limit.
+pub async fn get_field_limit(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Option<(usize, Vec<String>)> {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STREAM_SETTINGS
+        .read()
+        .await
+        .get(&key)
+        .filter(|s| s.max_fields > 0)
+        .map(|s| {
+            // the encrypted fields stay columns, the decrypt function reads them
+            let promoted = s
+                .promoted_fields
+                .iter()
+                .chain(s.encrypt_fields.iter())
+                .cloned()
+                .collect();
+            (s.max_fields, promoted)
+        })
+}
+
+/// Moves the new keys of the record which would grow the schema past
+/// `max_fields` into the extra column, the keys already in the schema, the
+/// promoted ones and the system columns stay columns. Returns the moved keys.
+pub fn route(
+    record: &mut Map<String, Value>,
+    schema_fields: &HashMap<String, usize>,
+    max_fields: usize,
+    promoted_fields: &[String],
+) -> Vec<String> {
+    let new_keys: Vec<String> = record
+        .keys()
+        .filter(|k| {
+            !schema_fields.contains_key(*k)
+                && k.as_str() != EXTRA_COLUMN
+                && !SYSTEM_COLUMNS.contains(k)
+                && !promoted_fields.contains(k)
+        })
+        .cloned()
+        .collect();
+    let mut free = max_fields.saturating_sub(schema_fields.len());
+    if new_keys.len() <= free {
+        return vec![];
+    }
+    // the extra column takes a field of its own
+    if !schema_fields.contains_key(EXTRA_COLUMN) {
+        free = free.saturating_sub(1);
+    }
+
+    let mut extra = match record.remove(EXTRA_COLUMN) {
+        Some(Value::String(v)) => json::from_str::<Map<String, Value>>(&v).unwrap_or_default(),
+        _ => Map::new(),
+    };
+    let moved = new_keys[free..].to_vec();
+    for key in moved.iter() {
+        if let Some(value) = record.remove(key) {
+            extra.insert(key.to_string(), value);
+        }
+    }
+    record.insert(
+        EXTRA_COLUMN.to_string(),
+        Value::String(Value::Object(extra).to_string()),
+    );
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let schema_fields: HashMap<String, usize> = ["_timestamp", "message"]
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.to_string(), i))
+            .collect();
+        let mut record = json::json!({
+            "_timestamp": 1,
+            "message": "hello",
+            "a": 1,
+            "b": 2,
+            "c": 3,
+            "kept": true,
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        // room for the record, nothing moves
+        assert!(route(&mut record.clone(), &schema_fields, 10, &[]).is_empty());
+
+        // 2 free fields, one of them for the extra column
+        let moved = route(&mut record, &schema_fields, 4, &["kept".to_string()]);
+        assert_eq!(moved, vec!["b", "c"]);
+        assert_eq!(record.get("a"), Some(&json::json!(1)));
+        assert_eq!(record.get("kept"), Some(&json::json!(true)));
+        assert!(!record.contains_key("b"));
+        let extra: Value = json::from_str(record[EXTRA_COLUMN].as_str().unwrap()).unwrap();
+        assert_eq!(extra, json::json!({"b": 2, "c": 3}));
+    }
+}
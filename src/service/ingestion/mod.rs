@@ -23,15 +23,20 @@ use chrono::{TimeZone, Utc};
 use config::{
     cluster,
     meta::{
-        stream::{PartitionTimeLevel, PartitioningDetails, Routing, StreamPartition, StreamType},
+        stream::{
+            DropRule, OtlpAttributes, PartitionTimeLevel, PartitioningDetails, Routing,
+            StreamPartition, StreamType,
+        },
         usage::RequestStats,
     },
+    metrics,
     utils::{
         flatten,
         json::{self, Map, Value},
     },
     CONFIG, SIZE_IN_MB,
 };
+use regex::Regex;
 use vector_enrichment::TableRegistry;
 use vrl::{
     compiler::{runtime::Runtime, CompilationResult, TargetValueRef},
@@ -44,6 +49,7 @@ use crate::{
         meta::{
             alerts::Alert,
             functions::{StreamTransform, VRLResultResolver, VRLRuntimeConfig},
+            organization::Feature,
             stream::{SchemaRecords, StreamParams},
         },
         utils::functions::get_vrl_compiler_config,
@@ -51,7 +57,10 @@ use crate::{
     service::{db, format_partition_key},
 };
 
+pub mod extra;
 pub mod grpc;
+pub mod quota;
+pub mod reserved;
 
 pub type TriggerAlertData = Vec<(Alert, Vec<Map<String, Value>>)>;
 
@@ -88,6 +97,22 @@ pub fn compile_vrl_function(func: &str, org_id: &str) -> Result<VRLRuntimeConfig
 }
 
 pub fn apply_vrl_fn(runtime: &mut Runtime, vrl_runtime: &VRLResultResolver, row: &Value) -> Value {
+    match try_apply_vrl_fn(runtime, vrl_runtime, row) {
+        Ok(val) => val,
+        Err(err) => {
+            log::error!("Returning original row , got error from vrl {}", err);
+            row.clone()
+        }
+    }
+}
+
+/// Runs the VRL program on the row, returning the error instead of the
+/// original row when the program fails.
+pub fn try_apply_vrl_fn(
+    runtime: &mut Runtime,
+    vrl_runtime: &VRLResultResolver,
+    row: &Value,
+) -> Result<Value, String> {
     let mut metadata = vrl::value::Value::from(BTreeMap::new());
     let mut target = TargetValueRef {
         value: &mut vrl::value::Value::from(row),
@@ -101,17 +126,8 @@ pub fn apply_vrl_fn(runtime: &mut Runtime, vrl_runtime: &VRLResultResolver, row:
         }
     };
     match result {
-        Ok(res) => match res.try_into() {
-            Ok(val) => val,
-            Err(err) => {
-                log::error!("Returning original row , got error from vrl {:?}", err);
-                row.clone()
-            }
-        },
-        Err(err) => {
-            log::error!("Returning original row , got error from vrl {:?}", err);
-            row.clone()
-        }
+        Ok(res) => res.try_into().map_err(|err| format!("{:?}", err)),
+        Err(err) => Err(err.to_string()),
     }
 }
 
@@ -152,6 +168,141 @@ pub async fn get_stream_partition_keys(
     }
 }
 
+/// The compiled drop rules of a stream, evaluated before a record is written
+/// so dropped records never count toward storage.
+#[derive(Default)]
+pub struct StreamDropRules {
+    org_id: String,
+    stream_type: StreamType,
+    stream_name: String,
+    rules: Vec<(String, Vec<(String, Regex)>)>,
+}
+
+impl StreamDropRules {
+    pub fn new(
+        org_id: &str,
+        stream_type: StreamType,
+        stream_name: &str,
+        rules: &[DropRule],
+    ) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                let conditions = rule
+                    .conditions
+                    .iter()
+                    .map(|c| Regex::new(&c.regex).map(|re| (c.column.clone(), re)))
+                    .collect::<Result<Vec<_>, _>>();
+                match conditions {
+                    Ok(conditions) if !conditions.is_empty() => {
+                        Some((rule.name.clone(), conditions))
+                    }
+                    Ok(_) => None,
+                    Err(e) => {
+                        log::error!(
+                            "[DROP RULES] {org_id}/{stream_type}/{stream_name} rule {} is invalid: {e}",
+                            rule.name
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self {
+            org_id: org_id.to_string(),
+            stream_type,
+            stream_name: stream_name.to_string(),
+            rules,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the name of the first rule whose conditions all match the record
+    pub fn matched(&self, record: &Map<String, Value>) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(_, conditions)| {
+                conditions
+                    .iter()
+                    .all(|(column, re)| match record.get(column) {
+                        None | Some(Value::Null) => false,
+                        Some(val) => re.is_match(&get_string_value(val)),
+                    })
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Checks the record against the rules and counts it when it is dropped
+    pub fn should_drop(&self, record: &Map<String, Value>) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let Some(rule) = self.matched(record) else {
+            return false;
+        };
+        metrics::INGEST_DROPPED_RECORDS
+            .with_label_values(&[
+                &self.org_id,
+                &self.stream_name,
+                self.stream_type.to_string().as_str(),
+                rule,
+            ])
+            .inc();
+        true
+    }
+}
+
+pub async fn get_stream_drop_rules(
+    org_id: &str,
+    stream_type: &StreamType,
+    stream_name: &str,
+) -> StreamDropRules {
+    let stream_settings = infra::schema::get_settings(org_id, stream_name, *stream_type)
+        .await
+        .unwrap_or_default();
+    StreamDropRules::new(
+        org_id,
+        *stream_type,
+        stream_name,
+        &stream_settings.drop_rules,
+    )
+}
+
+pub async fn get_stream_flatten_options(
+    org_id: &str,
+    stream_type: &StreamType,
+    stream_name: &str,
+) -> flatten::FlattenOptions {
+    infra::schema::get_settings(org_id, stream_name, *stream_type)
+        .await
+        .unwrap_or_default()
+        .flatten_options()
+}
+
+pub async fn get_stream_otlp_attributes(org_id: &str, stream_name: &str) -> OtlpAttributes {
+    infra::schema::get_settings(org_id, stream_name, StreamType::Logs)
+        .await
+        .unwrap_or_default()
+        .otlp_attributes
+}
+
+/// Value of the nested column holding the OTLP attributes which aren't
+/// promoted to columns, `None` when all of them are. It is stored as a JSON
+/// string so the flattening doesn't turn it back into columns.
+pub fn nested_otlp_attributes(
+    resource: Map<String, Value>,
+    scope: Map<String, Value>,
+) -> Option<Value> {
+    if resource.is_empty() && scope.is_empty() {
+        return None;
+    }
+    let value = json::json!({"resource": resource, "scope": scope});
+    Some(Value::String(value.to_string()))
+}
+
 pub async fn get_stream_alerts(
     streams: &[StreamParams],
     stream_alerts_map: &mut HashMap<String, Vec<Alert>>,
@@ -239,6 +390,9 @@ pub fn register_stream_functions(
 ) -> (Vec<StreamTransform>, HashMap<String, VRLResultResolver>) {
     let mut local_trans = vec![];
     let mut stream_vrl_map: HashMap<String, VRLResultResolver> = HashMap::new();
+    if !crate::service::organization::is_feature_enabled(org_id, Feature::StreamFunctions) {
+        return (local_trans, stream_vrl_map);
+    }
     let key = format!("{}/{}/{}", org_id, stream_type, stream_name);
 
     if let Some(transforms) = STREAM_FUNCTIONS.get(&key) {
@@ -246,8 +400,12 @@ pub fn register_stream_functions(
         local_trans.sort_by(|a, b| a.order.cmp(&b.order));
         for trans in &local_trans {
             let func_key = format!("{}/{}", &stream_name, trans.transform.name);
-            if let Ok(vrl_runtime_config) = compile_vrl_function(&trans.transform.function, org_id)
-            {
+            let Some(function) =
+                crate::common::utils::functions::resolve_function(&trans.transform)
+            else {
+                continue;
+            };
+            if let Ok(vrl_runtime_config) = compile_vrl_function(&function, org_id) {
                 let registry = vrl_runtime_config
                     .config
                     .get_custom::<TableRegistry>()
@@ -326,6 +484,7 @@ pub fn check_ingestion_allowed(org_id: &str, stream_name: Option<&str>) -> Resul
         return Err(anyhow!("not an ingester"));
     }
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Err(anyhow!("Quota exceeded for this organization [{}]", org_id));
     }
 
@@ -334,6 +493,9 @@ pub fn check_ingestion_allowed(org_id: &str, stream_name: Option<&str>) -> Resul
         if db::compact::retention::is_deleting_stream(org_id, StreamType::Logs, stream_name, None) {
             return Err(anyhow!("stream [{stream_name}] is being deleted"));
         }
+        if db::compact::rename::is_renaming(org_id, StreamType::Logs, stream_name) {
+            return Err(anyhow!("stream [{stream_name}] is being renamed"));
+        }
     };
 
     Ok(())
@@ -484,6 +646,16 @@ pub async fn get_stream_routing(
     stream_routing_map.insert(stream_params.stream_name.to_string(), res);
 }
 
+/// Whether `target_org` accepts records routed from the streams of `source_org`.
+pub async fn is_cross_org_routing_allowed(source_org: &str, target_org: &str) -> bool {
+    source_org != target_org
+        && crate::service::organization::get_setting(target_org)
+            .await
+            .routing_sources
+            .iter()
+            .any(|org| org == source_org)
+}
+
 pub async fn get_user_defined_schema(
     streams: &[StreamParams],
     user_defined_schema_map: &mut HashMap<String, Vec<String>>,
@@ -558,6 +730,56 @@ mod tests {
             "1970/01/01/00/default"
         );
     }
+    #[test]
+    fn test_stream_drop_rules() {
+        use config::meta::stream::DropCondition;
+
+        let rules = StreamDropRules::new(
+            "default",
+            StreamType::Logs,
+            "access",
+            &[DropRule {
+                name: "health_checks".to_string(),
+                conditions: vec![
+                    DropCondition {
+                        column: "path".to_string(),
+                        regex: "^/health".to_string(),
+                    },
+                    DropCondition {
+                        column: "status".to_string(),
+                        regex: "^2".to_string(),
+                    },
+                ],
+            }],
+        );
+        let record = |path: &str, status: i64| {
+            json::json!({"path": path, "status": status})
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(
+            rules.matched(&record("/healthz", 200)),
+            Some("health_checks")
+        );
+        assert_eq!(rules.matched(&record("/healthz", 500)), None);
+        assert_eq!(rules.matched(&record("/api/users", 200)), None);
+        assert_eq!(
+            rules.matched(
+                &json::json!({"path": "/health"})
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            ),
+            None
+        );
+        assert!(
+            StreamDropRules::default()
+                .matched(&record("/healthz", 200))
+                .is_none()
+        );
+    }
+
     #[tokio::test]
     async fn test_get_stream_partition_keys() {
         let mut meta = HashMap::new();
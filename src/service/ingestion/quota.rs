@@ -0,0 +1,321 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::stream::{StreamQuotaUsage, StreamType},
+    SIZE_IN_MB,
+};
+use hashbrown::HashMap;
+use infra::{cache::stats, schema::STREAM_SETTINGS};
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+
+use crate::{
+    common::{infra::cluster::get_cached_online_ingester_nodes, meta::stream::SchemaRecords},
+    service::db,
+};
+
+/// The usage last persisted by this ingester, to skip the unchanged streams.
+static SYNCED: Lazy<Mutex<HashMap<String, StreamQuotaUsage>>> = Lazy::new(Default::default);
+
+/// Counts the records buffered for the stream against its quotas before they
+/// are written, so the records dropped by the rules or routed to another org
+/// are not charged. The whole batch is rejected when a quota is exceeded.
+pub async fn check_pending<'a>(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    buf: impl IntoIterator<Item = &'a SchemaRecords>,
+) -> Result<()> {
+    let (records, size) = pending(buf);
+    check(org_id, stream_type, stream_name, records, size).await
+}
+
+fn pending<'a>(buf: impl IntoIterator<Item = &'a SchemaRecords>) -> (usize, usize) {
+    buf.into_iter().fold((0, 0), |(records, size), v| {
+        (records + v.records.len(), size + v.records_size)
+    })
+}
+
+/// Counts `records` events of `size` bytes against the quotas of the stream,
+/// the whole batch is rejected when one of them is exceeded. The events per second are split
+/// between the online ingesters and the megabytes per day add up the usage the other ingesters last
+/// synced.
+pub async fn check(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    records: usize,
+    size: usize,
+) -> Result<()> {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    let (max_events_per_sec, max_mb_per_day) = match STREAM_SETTINGS.read().await.get(&key) {
+        Some(settings) if settings.has_quota() => {
+            (settings.max_events_per_sec, settings.max_mb_per_day)
+        }
+        _ => return Ok(()),
+    };
+    if records == 0 {
+        return Ok(());
+    }
+
+    // pick up the usage persisted before a restart
+    if stats::get_stream_quota_usage(org_id, stream_name, stream_type).is_none() {
+        let usage = db::stream_quota::get(org_id, stream_type, stream_name, &LOCAL_NODE_UUID)
+            .await
+            .unwrap_or_default()
+            .unwrap_or_default();
+        stats::init_stream_quota_usage(org_id, stream_name, stream_type, usage);
+    }
+
+    let ingesters = get_cached_online_ingester_nodes()
+        .await
+        .map(|nodes| nodes.len())
+        .unwrap_or_default()
+        .max(1);
+    let now = Utc::now().timestamp_micros();
+    stats::update_stream_quota_usage(org_id, stream_name, stream_type, |usage| {
+        acquire(
+            usage,
+            max_events_per_sec,
+            max_mb_per_day,
+            ingesters,
+            records as f64,
+            size as f64 / SIZE_IN_MB,
+            now,
+        )
+    })
+    .map_err(|e| anyhow!("Quota exceeded for stream [{stream_name}]: {e}"))
+}
+
+/// The usage of the stream summed over the ingesters, as of their last sync.
+pub async fn get_usage(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<StreamQuotaUsage> {
+    let day_start = start_of_day(Utc::now().timestamp_micros());
+    let mut total = StreamQuotaUsage {
+        day_start,
+        ..Default::default()
+    };
+    for (_, usage) in db::stream_quota::list(org_id, stream_type, stream_name).await? {
+        if usage.day_start != day_start {
+            continue;
+        }
+        total.tokens += usage.tokens;
+        total.refilled_at = total.refilled_at.max(usage.refilled_at);
+        total.day_size += usage.day_size;
+        total.rejected += usage.rejected;
+    }
+    Ok(total)
+}
+
+/// Persists the usage of this ingester, drops the usage of previous days and
+/// refreshes the megabytes the other ingesters took today.
+pub async fn sync() -> Result<()> {
+    let local = stats::list_stream_quota_usage();
+    let mut synced = SYNCED.lock().await;
+    for (key, usage) in local.iter() {
+        if synced.get(key) == Some(usage) {
+            continue;
+        }
+        let columns = key.splitn(3, '/').collect::<Vec<_>>();
+        let (org_id, stream_type, stream_name) = (columns[0], columns[1], columns[2]);
+        db::stream_quota::set(
+            org_id,
+            StreamType::from(stream_type),
+            stream_name,
+            &LOCAL_NODE_UUID,
+            usage,
+        )
+        .await?;
+        synced.insert(key.clone(), *usage);
+    }
+    synced.retain(|key, _| local.iter().any(|(k, _)| k == key));
+    drop(synced);
+
+    let day_start = start_of_day(Utc::now().timestamp_micros());
+    let mut cluster_day_size: HashMap<String, f64> = HashMap::new();
+    for (key, usage) in db::stream_quota::list_all().await? {
+        let Some((stream_key, node)) = key.rsplit_once('/') else {
+            continue;
+        };
+        if usage.day_start < day_start {
+            if let Err(e) = db::stream_quota::delete_node(&key).await {
+                log::error!("[QUOTA] failed to delete the quota usage {key}: {e}");
+            }
+            continue;
+        }
+        if node != LOCAL_NODE_UUID.as_str() {
+            *cluster_day_size.entry(stream_key.to_string()).or_default() += usage.day_size;
+        }
+    }
+    for (key, _) in local {
+        let columns = key.splitn(3, '/').collect::<Vec<_>>();
+        let size = cluster_day_size.get(&key).copied().unwrap_or_default();
+        stats::update_stream_quota_usage(
+            columns[0],
+            columns[2],
+            StreamType::from(columns[1]),
+            |usage| {
+                if usage.day_start == day_start {
+                    usage.cluster_day_size = size;
+                }
+            },
+        );
+    }
+    Ok(())
+}
+
+fn start_of_day(now: i64) -> i64 {
+    now - now % Duration::try_days(1).unwrap().num_microseconds().unwrap()
+}
+
+/// Takes `records` events from the token bucket, refilled at this ingester's
+/// share of `max_events_per_sec` and holding one second of events, and adds
+/// `size` megabytes to the usage of the day. A batch larger than the bucket is
+/// accepted on a full bucket and paid back before the next one.
+fn acquire(
+    usage: &mut StreamQuotaUsage,
+    max_events_per_sec: u64,
+    max_mb_per_day: u64,
+    ingesters: usize,
+    records: f64,
+    size: f64,
+    now: i64,
+) -> Result<(), String> {
+    let day_start = start_of_day(now);
+    if usage.day_start != day_start {
+        usage.day_start = day_start;
+        usage.day_size = 0.0;
+        usage.cluster_day_size = 0.0;
+        usage.rejected = 0;
+    }
+    if max_mb_per_day > 0 && usage.day_size + usage.cluster_day_size + size > max_mb_per_day as f64
+    {
+        usage.rejected += records as i64;
+        return Err(format!("{max_mb_per_day} MB per day"));
+    }
+
+    if max_events_per_sec > 0 {
+        let capacity = max_events_per_sec as f64 / ingesters as f64;
+        usage.tokens = if usage.refilled_at == 0 {
+            capacity
+        } else {
+            let elapsed = (now - usage.refilled_at).max(0) as f64 / 1_000_000.0;
+            (usage.tokens + elapsed * capacity).min(capacity)
+        };
+        usage.refilled_at = now;
+        if usage.tokens < records && usage.tokens < capacity {
+            usage.rejected += records as i64;
+            return Err(format!("{max_events_per_sec} events per second"));
+        }
+        usage.tokens -= records;
+    }
+
+    usage.day_size += size;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::Schema;
+    use config::utils::json;
+
+    use super::*;
+
+    fn schema_records(records: usize, records_size: usize) -> SchemaRecords {
+        SchemaRecords {
+            schema_key: String::new(),
+            schema: Arc::new(Schema::empty()),
+            records: vec![Arc::new(json::json!({})); records],
+            records_size,
+        }
+    }
+
+    #[test]
+    fn test_pending_skips_dropped_records() {
+        // a batch of 10 records of which the drop rules kept 3
+        let buf = [schema_records(2, 200), schema_records(1, 100)];
+        let (records, size) = pending(&buf);
+        assert_eq!((records, size), (3, 300));
+
+        // the dropped records leave room in the bucket for the next batches
+        let mut usage = StreamQuotaUsage::default();
+        let now = 1_700_000_000_000_000;
+        for _ in 0..3 {
+            let size = size as f64 / SIZE_IN_MB;
+            assert!(acquire(&mut usage, 10, 0, 1, records as f64, size, now).is_ok());
+        }
+        assert_eq!(usage.tokens, 1.0);
+        assert!(acquire(&mut usage, 10, 0, 1, records as f64, 0.0, now).is_err());
+    }
+
+    #[test]
+    fn test_acquire_events_per_sec() {
+        let mut usage = StreamQuotaUsage::default();
+        let now = 1_700_000_000_000_000;
+        assert!(acquire(&mut usage, 100, 0, 1, 60.0, 0.0, now).is_ok());
+        assert!(acquire(&mut usage, 100, 0, 1, 60.0, 0.0, now).is_err());
+        assert_eq!(usage.rejected, 60);
+        // half a second refills 50 events
+        assert!(acquire(&mut usage, 100, 0, 1, 60.0, 0.0, now + 500_000).is_ok());
+        assert_eq!(usage.tokens, 30.0);
+
+        // a batch larger than the bucket passes once the bucket is full
+        let mut usage = StreamQuotaUsage::default();
+        assert!(acquire(&mut usage, 100, 0, 1, 250.0, 0.0, now).is_ok());
+        assert!(acquire(&mut usage, 100, 0, 1, 1.0, 0.0, now + 1_000_000).is_err());
+        assert!(acquire(&mut usage, 100, 0, 1, 1.0, 0.0, now + 3_000_000).is_ok());
+    }
+
+    #[test]
+    fn test_acquire_mb_per_day() {
+        let mut usage = StreamQuotaUsage::default();
+        let day = Duration::try_days(1).unwrap().num_microseconds().unwrap();
+        let now = 19_000 * day + 1_000;
+        assert!(acquire(&mut usage, 0, 10, 1, 1.0, 6.0, now).is_ok());
+        assert!(acquire(&mut usage, 0, 10, 1, 1.0, 6.0, now + 1_000).is_err());
+        assert_eq!(usage.day_size, 6.0);
+        // the usage starts over on the next day
+        assert!(acquire(&mut usage, 0, 10, 1, 1.0, 6.0, now + day).is_ok());
+        assert_eq!(usage.day_start, 19_001 * day);
+    }
+
+    #[test]
+    fn test_acquire_cluster() {
+        let mut usage = StreamQuotaUsage::default();
+        let day = Duration::try_days(1).unwrap().num_microseconds().unwrap();
+        let now = 19_000 * day + 1_000;
+        // four ingesters share the events per second
+        assert!(acquire(&mut usage, 100, 0, 4, 25.0, 0.0, now).is_ok());
+        assert!(acquire(&mut usage, 100, 0, 4, 1.0, 0.0, now).is_err());
+
+        // and the megabytes of the day
+        let mut usage = StreamQuotaUsage::default();
+        assert!(acquire(&mut usage, 0, 10, 4, 1.0, 2.0, now).is_ok());
+        usage.cluster_day_size = 7.0;
+        assert!(acquire(&mut usage, 0, 10, 4, 1.0, 2.0, now + 1_000).is_err());
+        assert_eq!(usage.rejected, 1);
+        assert!(acquire(&mut usage, 0, 10, 4, 1.0, 2.0, now + day).is_ok());
+        assert_eq!(usage.cluster_day_size, 0.0);
+        assert_eq!(usage.rejected, 0);
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::{
+    utils::{json, time::parse_timestamp_micro_from_value},
+    CONFIG,
+};
+use once_cell::sync::Lazy;
+
+use super::extra::EXTRA_COLUMN;
+use crate::service::{
+    lineage::LINEAGE_COLUMN,
+    original::{ORIGINAL_COLUMN, ORIGINAL_ID_COLUMN},
+    record_id::RECORD_ID_COLUMN,
+};
+
+/// Columns written by the system, ingested fields can't take their place.
+pub static SYSTEM_COLUMNS: Lazy<Vec<String>> = Lazy::new(|| {
+    vec![
+        CONFIG.common.column_timestamp.clone(),
+        "_id".to_string(),
+        RECORD_ID_COLUMN.to_string(),
+        "_org".to_string(),
+        LINEAGE_COLUMN.to_string(),
+        EXTRA_COLUMN.to_string(),
+        ORIGINAL_COLUMN.to_string(),
+        ORIGINAL_ID_COLUMN.to_string(),
+    ]
+});
+
+/// Moves the ingested fields named like a system column to the reserved field
+/// prefix, so `_id` is kept as `user_id`. The timestamp column stays where it
+/// is when it holds a valid timestamp. A warning per moved field is added to
+/// `warnings`.
+pub fn protect(record: &mut json::Map<String, json::Value>, warnings: &mut Vec<String>) {
+    for column in SYSTEM_COLUMNS.iter() {
+        let Some(value) = record.get(column) else {
+            continue;
+        };
+        if *column == CONFIG.common.column_timestamp
+            && parse_timestamp_micro_from_value(value).is_ok()
+        {
+            continue;
+        }
+        let value = record.remove(column).unwrap();
+        let key = free_key(record, &prefixed(column));
+        let warning = format!("field [{column}] is a system column, moved to [{key}]");
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+        record.insert(key, value);
+    }
+}
+
+fn prefixed(column: &str) -> String {
+    let prefix = &CONFIG.common.reserved_field_prefix;
+    if column.starts_with('_') {
+        format!("{prefix}{column}")
+    } else {
+        format!("{prefix}_{column}")
+    }
+}
+
+/// Appends a counter when the key is already used by another field.
+fn free_key(record: &json::Map<String, json::Value>, key: &str) -> String {
+    if !record.contains_key(key) {
+        return key.to_string();
+    }
+    (1..)
+        .map(|i| format!("{key}_{i}"))
+        .find(|k| !record.contains_key(k))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protect() {
+        let mut record = json::json!({
+            "_id": "abc",
+            "_org": "acme",
+            "user_org": "taken",
+            "_timestamp": "not a time",
+            "message": "hello",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        let mut warnings = vec![];
+        protect(&mut record, &mut warnings);
+        assert!(!record.contains_key("_id"));
+        assert!(!record.contains_key("_org"));
+        assert!(!record.contains_key(&CONFIG.common.column_timestamp));
+        assert_eq!(record["user_id"], "abc");
+        assert_eq!(record["user_org"], "taken");
+        assert_eq!(record["user_org_1"], "acme");
+        assert_eq!(record["user_timestamp"], "not a time");
+        assert_eq!(warnings.len(), 3);
+
+        // valid timestamps are kept
+        let mut record = json::json!({"_timestamp": 1700000000000000i64})
+            .as_object()
+            .unwrap()
+            .clone();
+        protect(&mut record, &mut warnings);
+        assert_eq!(record["_timestamp"], 1700000000000000i64);
+        assert_eq!(warnings.len(), 3);
+    }
+}
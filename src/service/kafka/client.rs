@@ -0,0 +1,643 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The part of the Kafka protocol the consumer needs: metadata, list offsets
+//! and fetch requests over plain TCP, and the v2 record batches returned by
+//! the fetches.
+
+use std::{collections::HashMap, io::Read, sync::Mutex, time::Duration};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const CLIENT_ID: &str = "openobserve";
+// one request at a time per connection, the id only checks the framing
+const CORRELATION_ID: i32 = 1;
+
+const API_FETCH: i16 = 1;
+const API_LIST_OFFSETS: i16 = 2;
+const API_METADATA: i16 = 3;
+
+const OFFSET_OUT_OF_RANGE: i16 = 1;
+const UNKNOWN_TOPIC_OR_PARTITION: i16 = 3;
+
+/// Largest response or decompressed batch accepted
+const MAX_RESPONSE_BYTES: usize = 256 * 1024 * 1024;
+
+const COMPRESSION_MASK: i16 = 0x07;
+const CONTROL_BATCH: i16 = 0x20;
+const XERIAL_SNAPPY_MAGIC: &[u8] = b"\x82SNAPPY\x00";
+
+#[derive(Debug, thiserror::Error)]
+pub enum KafkaError {
+    #[error("kafka connection error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("kafka request timed out")]
+    Timeout,
+    #[error("kafka error code {code} for {context}")]
+    Server { code: i16, context: String },
+    #[error("kafka invalid response: {0}")]
+    Decode(String),
+}
+
+impl KafkaError {
+    pub fn is_offset_out_of_range(&self) -> bool {
+        matches!(self, KafkaError::Server { code, .. } if *code == OFFSET_OUT_OF_RANGE)
+    }
+}
+
+fn decode_error(msg: impl Into<String>) -> KafkaError {
+    KafkaError::Decode(msg.into())
+}
+
+type Result<T> = std::result::Result<T, KafkaError>;
+
+#[derive(Clone, Copy, Debug)]
+pub enum OffsetAt {
+    Earliest,
+    Latest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topic {
+    pub name: String,
+    pub partitions: Vec<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub offset: i64,
+    pub value: Option<Vec<u8>>,
+}
+
+/// Sends the requests of a partition to its leader, found with the metadata
+/// of the bootstrap brokers.
+pub struct Client {
+    bootstrap: Vec<String>,
+    timeout: Duration,
+    // broker id -> address
+    brokers: Mutex<HashMap<i32, String>>,
+    // (topic, partition) -> leader broker id
+    leaders: Mutex<HashMap<(String, i32), i32>>,
+    // address -> connections not in use
+    idle: Mutex<HashMap<String, Vec<TcpStream>>>,
+}
+
+impl Client {
+    pub fn new(bootstrap: Vec<String>, timeout: Duration) -> Self {
+        Self {
+            bootstrap,
+            timeout,
+            brokers: Mutex::new(HashMap::new()),
+            leaders: Mutex::new(HashMap::new()),
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The topics among `names` which exist, topics are never created.
+    pub async fn topics(&self, names: &[String]) -> Result<Vec<Topic>> {
+        let mut body = BytesMut::new();
+        body.put_i32(names.len() as i32);
+        for name in names {
+            put_string(&mut body, name);
+        }
+        body.put_i8(0); // allow_auto_topic_creation
+        let mut last_error = decode_error("no brokers");
+        for addr in self.bootstrap.iter() {
+            match self.request(addr, API_METADATA, 4, &body).await {
+                Ok(resp) => return self.parse_metadata(resp),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn parse_metadata(&self, resp: Bytes) -> Result<Vec<Topic>> {
+        let mut d = Decoder(resp);
+        d.i32()?; // throttle time
+        let mut brokers = HashMap::new();
+        for _ in 0..d.array_len()? {
+            let id = d.i32()?;
+            let host = d.string()?;
+            let port = d.i32()?;
+            d.string()?; // rack
+            brokers.insert(id, format!("{host}:{port}"));
+        }
+        d.string()?; // cluster id
+        d.i32()?; // controller id
+        let mut topics = vec![];
+        let mut leaders = HashMap::new();
+        for _ in 0..d.array_len()? {
+            let code = d.i16()?;
+            let name = d.string()?;
+            d.i8()?; // is internal
+            let mut partitions = vec![];
+            for _ in 0..d.array_len()? {
+                d.i16()?; // error code
+                let id = d.i32()?;
+                let leader = d.i32()?;
+                // replicas and in sync replicas
+                for _ in 0..2 {
+                    let n = d.array_len()?;
+                    d.bytes(n * 4)?;
+                }
+                partitions.push(id);
+                leaders.insert((name.clone(), id), leader);
+            }
+            if code == 0 {
+                partitions.sort();
+                topics.push(Topic { name, partitions });
+            }
+        }
+        self.brokers.lock().unwrap().extend(brokers);
+        self.leaders.lock().unwrap().extend(leaders);
+        Ok(topics)
+    }
+
+    pub async fn offset(&self, topic: &str, partition: i32, at: OffsetAt) -> Result<i64> {
+        let mut body = BytesMut::new();
+        body.put_i32(-1); // replica id
+        body.put_i32(1);
+        put_string(&mut body, topic);
+        body.put_i32(1);
+        body.put_i32(partition);
+        body.put_i64(match at {
+            OffsetAt::Earliest => -2,
+            OffsetAt::Latest => -1,
+        });
+        let addr = self.leader(topic, partition).await?;
+        let mut d = Decoder(self.request(&addr, API_LIST_OFFSETS, 1, &body).await?);
+        for _ in 0..d.array_len()? {
+            let name = d.string()?;
+            for _ in 0..d.array_len()? {
+                let id = d.i32()?;
+                let code = d.i16()?;
+                d.i64()?; // timestamp
+                let offset = d.i64()?;
+                if name == topic && id == partition {
+                    check(code, topic, partition)?;
+                    return Ok(offset);
+                }
+            }
+        }
+        Err(decode_error(format!(
+            "{topic}/{partition} missing from the response"
+        )))
+    }
+
+    /// The records from `offset` on and the high watermark of the partition.
+    pub async fn fetch(
+        &self,
+        topic: &str,
+        partition: i32,
+        offset: i64,
+        max_bytes: i32,
+        max_wait_ms: i32,
+    ) -> Result<(Vec<Record>, i64)> {
+        let mut body = BytesMut::new();
+        body.put_i32(-1); // replica id
+        body.put_i32(max_wait_ms);
+        body.put_i32(1); // min bytes
+        body.put_i32(max_bytes);
+        body.put_i8(0); // read uncommitted
+        body.put_i32(1);
+        put_string(&mut body, topic);
+        body.put_i32(1);
+        body.put_i32(partition);
+        body.put_i64(offset);
+        body.put_i32(max_bytes);
+        let addr = self.leader(topic, partition).await?;
+        let mut d = Decoder(self.request(&addr, API_FETCH, 4, &body).await?);
+        d.i32()?; // throttle time
+        for _ in 0..d.array_len()? {
+            let name = d.string()?;
+            for _ in 0..d.array_len()? {
+                let id = d.i32()?;
+                let code = d.i16()?;
+                let high_watermark = d.i64()?;
+                d.i64()?; // last stable offset
+                // aborted transactions, producer id and first offset
+                let aborted = d.i32()?;
+                if aborted > 0 {
+                    d.bytes(aborted as usize * 16)?;
+                }
+                let len = d.i32()?;
+                let data = if len > 0 {
+                    d.bytes(len as usize)?
+                } else {
+                    Bytes::new()
+                };
+                if name == topic && id == partition {
+                    check(code, topic, partition)?;
+                    return Ok((decode_batches(data, offset)?, high_watermark));
+                }
+            }
+        }
+        Err(decode_error(format!(
+            "{topic}/{partition} missing from the response"
+        )))
+    }
+
+    async fn leader(&self, topic: &str, partition: i32) -> Result<String> {
+        if let Some(addr) = self.leader_addr(topic, partition) {
+            return Ok(addr);
+        }
+        self.topics(&[topic.to_string()]).await?;
+        self.leader_addr(topic, partition)
+            .ok_or_else(|| KafkaError::Server {
+                code: UNKNOWN_TOPIC_OR_PARTITION,
+                context: format!("{topic}/{partition}"),
+            })
+    }
+
+    fn leader_addr(&self, topic: &str, partition: i32) -> Option<String> {
+        let leader = *self
+            .leaders
+            .lock()
+            .unwrap()
+            .get(&(topic.to_string(), partition))?;
+        self.brokers.lock().unwrap().get(&leader).cloned()
+    }
+
+    /// Sends a request on an idle connection to the broker or a new one, a
+    /// connection which failed is dropped.
+    async fn request(
+        &self,
+        addr: &str,
+        api_key: i16,
+        api_version: i16,
+        body: &[u8],
+    ) -> Result<Bytes> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap()
+            .get_mut(addr)
+            .and_then(|v| v.pop());
+        let fut = async {
+            let mut conn = match idle {
+                Some(conn) => conn,
+                None => TcpStream::connect(addr).await?,
+            };
+            let resp = exchange(&mut conn, api_key, api_version, body).await?;
+            Ok::<_, KafkaError>((conn, resp))
+        };
+        let (conn, resp) = tokio::time::timeout(self.timeout, fut)
+            .await
+            .map_err(|_| KafkaError::Timeout)??;
+        self.idle
+            .lock()
+            .unwrap()
+            .entry(addr.to_string())
+            .or_default()
+            .push(conn);
+        Ok(resp)
+    }
+}
+
+async fn exchange(
+    conn: &mut TcpStream,
+    api_key: i16,
+    api_version: i16,
+    body: &[u8],
+) -> Result<Bytes> {
+    let mut header = BytesMut::new();
+    header.put_i16(api_key);
+    header.put_i16(api_version);
+    header.put_i32(CORRELATION_ID);
+    put_string(&mut header, CLIENT_ID);
+    let mut req = BytesMut::with_capacity(4 + header.len() + body.len());
+    req.put_i32((header.len() + body.len()) as i32);
+    req.extend_from_slice(&header);
+    req.extend_from_slice(body);
+    conn.write_all(&req).await?;
+
+    let size = conn.read_i32().await?;
+    if size < 4 || size as usize > MAX_RESPONSE_BYTES {
+        return Err(decode_error(format!("response size {size}")));
+    }
+    let mut resp = vec![0; size as usize];
+    conn.read_exact(&mut resp).await?;
+    let mut resp = Bytes::from(resp);
+    if resp.get_i32() != CORRELATION_ID {
+        return Err(decode_error("unexpected correlation id"));
+    }
+    Ok(resp)
+}
+
+fn check(code: i16, topic: &str, partition: i32) -> Result<()> {
+    if code == 0 {
+        return Ok(());
+    }
+    Err(KafkaError::Server {
+        code,
+        context: format!("{topic}/{partition}"),
+    })
+}
+
+fn put_string(buf: &mut BytesMut, v: &str) {
+    buf.put_i16(v.len() as i16);
+    buf.extend_from_slice(v.as_bytes());
+}
+
+/// The records of the v2 record batches from `min_offset` on. The last batch
+/// can be cut short by the max bytes of the fetch, it is left for the next one.
+fn decode_batches(data: Bytes, min_offset: i64) -> Result<Vec<Record>> {
+    let mut d = Decoder(data);
+    let mut records = vec![];
+    while d.0.remaining() >= 12 {
+        let base_offset = d.i64()?;
+        let len = d.i32()?;
+        if len < 0 || d.0.remaining() < len as usize {
+            break;
+        }
+        let mut batch = Decoder(d.bytes(len as usize)?);
+        batch.i32()?; // partition leader epoch
+        let magic = batch.i8()?;
+        if magic != 2 {
+            return Err(decode_error(format!("unsupported message format v{magic}")));
+        }
+        batch.i32()?; // crc, the connection is checksummed already
+        let attributes = batch.i16()?;
+        // last offset delta, timestamps, producer id and epoch, base sequence
+        batch.bytes(4 + 8 + 8 + 8 + 2 + 4)?;
+        let count = batch.i32()?;
+        if attributes & CONTROL_BATCH != 0 {
+            // commit and abort markers of transactions
+            continue;
+        }
+        let mut payload = Decoder(decompress(attributes & COMPRESSION_MASK, batch.0)?);
+        for _ in 0..count.max(0) {
+            let len = payload.varint()?;
+            let mut record = Decoder(payload.bytes(to_len(len)?)?);
+            record.i8()?; // attributes
+            record.varint()?; // timestamp delta
+            let offset = base_offset + record.varint()?;
+            record.varbytes()?; // key
+            let value = record.varbytes()?;
+            if offset >= min_offset {
+                records.push(Record {
+                    offset,
+                    value: value.map(|v| v.to_vec()),
+                });
+            }
+        }
+    }
+    Ok(records)
+}
+
+fn decompress(codec: i16, data: Bytes) -> Result<Bytes> {
+    let limit = MAX_RESPONSE_BYTES as u64;
+    let read_all = |mut r: Box<dyn Read + '_>| {
+        let mut out = vec![];
+        r.read_to_end(&mut out)?;
+        Ok::<_, KafkaError>(Bytes::from(out))
+    };
+    match codec {
+        0 => Ok(data),
+        1 => read_all(Box::new(
+            flate2::read::GzDecoder::new(&data[..]).take(limit),
+        )),
+        2 => snappy(&data).map(Bytes::from),
+        4 => read_all(Box::new(
+            zstd::stream::read::Decoder::new(&data[..])?.take(limit),
+        )),
+        _ => Err(decode_error(format!(
+            "unsupported compression codec {codec}"
+        ))),
+    }
+}
+
+/// Kafka clients write snappy in the xerial framing, blocks of raw snappy
+/// after a magic header, some write raw snappy.
+fn snappy(data: &[u8]) -> Result<Vec<u8>> {
+    let raw = |block: &[u8]| {
+        snap::raw::Decoder::new()
+            .decompress_vec(block)
+            .map_err(|e| decode_error(e.to_string()))
+    };
+    let Some(rest) = data.strip_prefix(XERIAL_SNAPPY_MAGIC) else {
+        return raw(data);
+    };
+    // version and compatible version
+    let mut rest = rest.get(8..).ok_or_else(|| decode_error("snappy header"))?;
+    let mut out = vec![];
+    while !rest.is_empty() {
+        let len = rest
+            .get(..4)
+            .map(|v| i32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .ok_or_else(|| decode_error("snappy block"))?;
+        let block = rest
+            .get(4..4 + to_len(len as i64)?)
+            .ok_or_else(|| decode_error("snappy block"))?;
+        out.extend(raw(block)?);
+        if out.len() > MAX_RESPONSE_BYTES {
+            return Err(decode_error("snappy batch too large"));
+        }
+        rest = &rest[4 + block.len()..];
+    }
+    Ok(out)
+}
+
+fn to_len(len: i64) -> Result<usize> {
+    usize::try_from(len).map_err(|_| decode_error(format!("length {len}")))
+}
+
+/// Reads the big endian fields of a response, a short response is an error.
+struct Decoder(Bytes);
+
+impl Decoder {
+    fn need(&self, n: usize) -> Result<()> {
+        if self.0.remaining() < n {
+            return Err(decode_error("unexpected end of data"));
+        }
+        Ok(())
+    }
+
+    fn i8(&mut self) -> Result<i8> {
+        self.need(1)?;
+        Ok(self.0.get_i8())
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        self.need(2)?;
+        Ok(self.0.get_i16())
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        self.need(4)?;
+        Ok(self.0.get_i32())
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        self.need(8)?;
+        Ok(self.0.get_i64())
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<Bytes> {
+        self.need(n)?;
+        Ok(self.0.split_to(n))
+    }
+
+    fn array_len(&mut self) -> Result<usize> {
+        Ok(self.i32()?.max(0) as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.i16()?;
+        if len < 0 {
+            return Ok(String::new());
+        }
+        let v = self.bytes(len as usize)?;
+        String::from_utf8(v.to_vec()).map_err(|e| decode_error(e.to_string()))
+    }
+
+    /// A zigzag encoded varint of the record batches
+    fn varint(&mut self) -> Result<i64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.i8()? as u8;
+            value |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 63 {
+                return Err(decode_error("varint too long"));
+            }
+        }
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn varbytes(&mut self) -> Result<Option<Bytes>> {
+        let len = self.varint()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.bytes(to_len(len)?)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn put_varint(buf: &mut Vec<u8>, v: i64) {
+        let mut v = ((v << 1) ^ (v >> 63)) as u64;
+        while v >= 0x80 {
+            buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        buf.push(v as u8);
+    }
+
+    fn batch(base_offset: i64, attributes: i16, values: &[Option<&[u8]>]) -> Vec<u8> {
+        let mut records = vec![];
+        for (i, value) in values.iter().enumerate() {
+            let mut record = vec![0u8];
+            put_varint(&mut record, 0); // timestamp delta
+            put_varint(&mut record, i as i64);
+            put_varint(&mut record, -1); // no key
+            match value {
+                Some(v) => {
+                    put_varint(&mut record, v.len() as i64);
+                    record.extend_from_slice(v);
+                }
+                None => put_varint(&mut record, -1),
+            }
+            put_varint(&mut record, 0); // headers
+            put_varint(&mut records, record.len() as i64);
+            records.extend(record);
+        }
+        if attributes & COMPRESSION_MASK == 1 {
+            let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(&records).unwrap();
+            records = encoder.finish().unwrap();
+        }
+        let mut body = BytesMut::new();
+        body.put_i32(0); // partition leader epoch
+        body.put_i8(2);
+        body.put_i32(0); // crc
+        body.put_i16(attributes);
+        body.put_bytes(0, 4 + 8 + 8 + 8 + 2 + 4);
+        body.put_i32(values.len() as i32);
+        body.extend_from_slice(&records);
+        let mut buf = BytesMut::new();
+        buf.put_i64(base_offset);
+        buf.put_i32(body.len() as i32);
+        buf.extend_from_slice(&body);
+        buf.to_vec()
+    }
+
+    #[test]
+    fn test_varint() {
+        for v in [0, 1, -1, 63, -64, 300, i32::MAX as i64, i64::MIN] {
+            let mut buf = vec![];
+            put_varint(&mut buf, v);
+            assert_eq!(Decoder(Bytes::from(buf)).varint().unwrap(), v);
+        }
+        assert!(Decoder(Bytes::from_static(&[0x80])).varint().is_err());
+    }
+
+    #[test]
+    fn test_decode_batches() {
+        let mut data = batch(10, 0, &[Some(b"a"), None, Some(b"c")]);
+        data.extend(batch(13, 1, &[Some(b"d")]));
+        // a transaction marker is skipped
+        data.extend(batch(14, CONTROL_BATCH, &[Some(b"commit")]));
+        data.extend(batch(15, 0, &[Some(b"f")]));
+        // the last batch cut short by the max bytes
+        let last = batch(16, 0, &[Some(b"g")]);
+        data.extend(&last[..last.len() - 3]);
+
+        let records = decode_batches(Bytes::from(data), 11).unwrap();
+        let offsets = records.iter().map(|r| r.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, vec![11, 12, 13, 15]);
+        assert_eq!(records[0].value, None);
+        assert_eq!(records[1].value.as_deref(), Some(&b"c"[..]));
+        assert_eq!(records[2].value.as_deref(), Some(&b"d"[..]));
+    }
+
+    #[test]
+    fn test_decode_batches_unsupported() {
+        let mut data = batch(0, 3, &[Some(b"a")]);
+        assert!(decode_batches(Bytes::from(data.clone()), 0).is_err());
+        // magic byte of the old message format
+        data[16] = 1;
+        assert!(decode_batches(Bytes::from(data), 0).is_err());
+    }
+
+    #[test]
+    fn test_snappy() {
+        let raw = snap::raw::Encoder::new().compress_vec(b"hello").unwrap();
+        assert_eq!(snappy(&raw).unwrap(), b"hello");
+
+        let mut xerial = XERIAL_SNAPPY_MAGIC.to_vec();
+        xerial.extend([0, 0, 0, 1, 0, 0, 0, 1]);
+        for block in [&b"hel"[..], &b"lo"[..]] {
+            let block = snap::raw::Encoder::new().compress_vec(block).unwrap();
+            xerial.extend((block.len() as i32).to_be_bytes());
+            xerial.extend(block);
+        }
+        assert_eq!(snappy(&xerial).unwrap(), b"hello");
+        assert!(snappy(&xerial[..xerial.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Kafka topics consumed into log streams. The ingesters poll every partition
+//! of the sources, a partition is consumed under a lock so only one node reads
+//! it at a time. The offset after the last ingested record is stored in the
+//! meta db once the batch is written, so records are ingested at least once.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use config::{cluster, utils::json, RwHashMap, CONFIG};
+use futures::StreamExt;
+use infra::dist_lock;
+use once_cell::sync::Lazy;
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use prost::Message;
+
+use self::client::{Client, KafkaError, OffsetAt, Record};
+use crate::{
+    common::meta::{
+        ingestion::IngestionRequest,
+        kafka::{KafkaFormat, KafkaOffset, KafkaSource, KafkaStartOffset},
+    },
+    service::{db, error::ServiceError, format_stream_name, logs},
+};
+
+/// Clients by the brokers they were built for.
+mod client;
+
+static CLIENTS: Lazy<RwHashMap<String, Arc<Client>>> = Lazy::new(Default::default);
+
+pub async fn save(org_id: &str, name: &str, mut source: KafkaSource) -> Result<(), ServiceError> {
+    source.name = format_stream_name(name);
+    source.stream_name = format_stream_name(source.stream_name.trim());
+    source.brokers = source
+        .brokers
+        .iter()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    source.topics = source
+        .topics
+        .iter()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if source.brokers.is_empty() {
+        return Err(ServiceError::bad_request("brokers are required"));
+    }
+    if source.topics.is_empty() {
+        return Err(ServiceError::bad_request("topics are required"));
+    }
+    if let Some(topic) = source.topics.iter().find(|t| !is_valid_name(t)) {
+        return Err(ServiceError::bad_request(format!(
+            "topic [{topic}] is invalid"
+        )));
+    }
+    if source.stream_name.is_empty() {
+        return Err(ServiceError::bad_request("stream_name is required"));
+    }
+    if source.consumer_group.is_empty() {
+        source.consumer_group = source.name.clone();
+    }
+    if !is_valid_name(&source.consumer_group) {
+        return Err(ServiceError::bad_request(
+            "consumer_group can only contain letters, digits, '.', '_' and '-'",
+        ));
+    }
+    source.last_error = String::new();
+    source.offsets = vec![];
+    db::kafka::set(org_id, &source)
+        .await
+        .map_err(ServiceError::from)?;
+    db::kafka::set_status(org_id, &source.name, "")
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<KafkaSource, ServiceError> {
+    let mut source = db::kafka::get(org_id, &format_stream_name(name))
+        .await
+        .map_err(|_| ServiceError::not_found("Kafka source not found"))?;
+    source.last_error = db::kafka::get_status(org_id, &source.name)
+        .await
+        .map_err(ServiceError::from)?;
+    source.offsets = db::kafka::list_offsets(org_id, &source.consumer_group)
+        .await
+        .map_err(ServiceError::from)?
+        .into_iter()
+        .filter(|o| source.topics.contains(&o.topic))
+        .collect();
+    Ok(source)
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<KafkaSource>, ServiceError> {
+    let mut sources = db::kafka::list(org_id).await.map_err(ServiceError::from)?;
+    for source in sources.iter_mut() {
+        source.last_error = db::kafka::get_status(org_id, &source.name)
+            .await
+            .map_err(ServiceError::from)?;
+    }
+    Ok(sources)
+}
+
+/// Stops consuming the topics, the offsets of the consumer group are kept.
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    let source = get(org_id, name).await?;
+    db::kafka::delete(org_id, &source.name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// Kafka topic names and consumer groups are limited to `[a-zA-Z0-9._-]`.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+}
+
+/// Consumes every partition of the sources up to its end, returns how many
+/// records this node ingested.
+pub async fn poll_all() -> Result<usize, anyhow::Error> {
+    if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(0);
+    }
+    let sources = db::kafka::list_all().await?;
+    let ingested = futures::stream::iter(sources.into_iter().filter(|(_, s)| !s.paused))
+        .map(|(key, source)| async move {
+            let Some((org_id, _)) = key.split_once('/') else {
+                return 0;
+            };
+            let (ingested, last_error) = match poll_source(org_id, &source).await {
+                Ok(n) => (n, String::new()),
+                Err(e) => {
+                    log::error!("[KAFKA] poll source {org_id}/{} error: {e}", source.name);
+                    if e.downcast_ref::<KafkaError>().is_some() {
+                        // the brokers may have moved, connect again on the next poll
+                        CLIENTS.remove(&source.brokers.join(","));
+                    }
+                    (0, e.to_string())
+                }
+            };
+            let stored = db::kafka::get_status(org_id, &source.name)
+                .await
+                .unwrap_or_default();
+            if stored != last_error {
+                if let Err(e) = db::kafka::set_status(org_id, &source.name, &last_error).await {
+                    log::error!("[KAFKA] save status {org_id}/{} error: {e}", source.name);
+                }
+            }
+            ingested
+        })
+        .buffer_unordered(CONFIG.kafka.poll_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .sum();
+    Ok(ingested)
+}
+
+/// Consumes the partitions of a source concurrently, so a partition waiting
+/// for its lock doesn't hold back the others.
+async fn poll_source(org_id: &str, source: &KafkaSource) -> Result<usize, anyhow::Error> {
+    let client = get_client(&source.brokers).await?;
+    let topics = client.topics(&source.topics).await?;
+    let mut partitions = vec![];
+    for name in source.topics.iter() {
+        let Some(topic) = topics.iter().find(|t| &t.name == name) else {
+            return Err(anyhow::anyhow!("topic [{name}] not found"));
+        };
+        partitions.extend(topic.partitions.iter().map(|p| (name.as_str(), *p)));
+    }
+    let client = &client;
+    let results = futures::stream::iter(partitions)
+        .map(|(name, partition)| async move {
+            let lock_key = format!(
+                "/kafka_offsets/lock/{org_id}/{}/{name}/{partition}",
+                source.consumer_group
+            );
+            let locker = dist_lock::lock(&lock_key, 0).await?;
+            let ret = poll_partition(org_id, source, client, name, partition).await;
+            dist_lock::unlock(&locker).await?;
+            ret
+        })
+        .buffer_unordered(CONFIG.kafka.poll_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    let mut ingested = 0;
+    let mut errors = vec![];
+    for ret in results {
+        match ret {
+            Ok(n) => ingested += n,
+            Err(e) => errors.push(e),
+        }
+    }
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(ingested),
+    }
+}
+
+/// Consumes a partition until its high watermark or until
+/// `ZO_KAFKA_MAX_POLL_SECS`, storing the offset after every batch.
+async fn poll_partition(
+    org_id: &str,
+    source: &KafkaSource,
+    client: &Client,
+    topic: &str,
+    partition: i32,
+) -> Result<usize, anyhow::Error> {
+    // read the offset under the lock, another node may just have moved it
+    let (mut offset, mut failures) =
+        match db::kafka::get_offset(org_id, &source.consumer_group, topic, partition).await? {
+            Some(v) => (v.offset, v.failures),
+            None => {
+                let at = match source.start_from {
+                    KafkaStartOffset::Earliest => OffsetAt::Earliest,
+                    KafkaStartOffset::Latest => OffsetAt::Latest,
+                };
+                let offset = client.offset(topic, partition, at).await?;
+                (offset, 0)
+            }
+        };
+    let fetch = |offset| {
+        client.fetch(
+            topic,
+            partition,
+            offset,
+            CONFIG.kafka.max_fetch_bytes,
+            CONFIG.kafka.max_wait_ms,
+        )
+    };
+    let deadline = Instant::now() + Duration::from_secs(CONFIG.kafka.max_poll_secs);
+    let mut ingested = 0;
+    loop {
+        let (records, high_watermark) = match fetch(offset).await {
+            Ok(v) => v,
+            Err(e) if e.is_offset_out_of_range() => {
+                // the stored offset was removed by the retention of the topic
+                let earliest = client.offset(topic, partition, OffsetAt::Earliest).await?;
+                log::warn!(
+                    "[KAFKA] offset {offset} of {topic}/{partition} is out of range, restart from {earliest}"
+                );
+                offset = earliest;
+                failures = 0;
+                fetch(earliest).await?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let Some(next_offset) = records.iter().map(|r| r.offset + 1).max() else {
+            return Ok(ingested);
+        };
+
+        match ingest(org_id, source, records).await {
+            Ok(n) => {
+                ingested += n;
+                offset = next_offset;
+                failures = 0;
+            }
+            Err(e) if e.downcast_ref::<Rejected>().is_some() => {
+                failures += 1;
+                if !should_skip(failures, CONFIG.kafka.max_attempts) {
+                    store_offset(org_id, source, topic, partition, offset, failures).await?;
+                    return Err(e);
+                }
+                log::error!(
+                    "[KAFKA] skip offsets {offset}..{next_offset} of {topic}/{partition} of source {org_id}/{} after {failures} attempts: {e}",
+                    source.name
+                );
+                offset = next_offset;
+                failures = 0;
+            }
+            Err(e) => return Err(e),
+        }
+        store_offset(org_id, source, topic, partition, offset, failures).await?;
+        if offset >= high_watermark || Instant::now() >= deadline {
+            return Ok(ingested);
+        }
+    }
+}
+
+async fn store_offset(
+    org_id: &str,
+    source: &KafkaSource,
+    topic: &str,
+    partition: i32,
+    offset: i64,
+    failures: u32,
+) -> Result<(), anyhow::Error> {
+    db::kafka::set_offset(
+        org_id,
+        &source.consumer_group,
+        &KafkaOffset {
+            topic: topic.to_string(),
+            partition,
+            offset,
+            updated_at: Utc::now().timestamp_micros(),
+            failures,
+        },
+    )
+    .await
+}
+
+/// Whether a batch rejected `failures` times is given up, `max_attempts` 0
+/// retries it forever.
+fn should_skip(failures: u32, max_attempts: u32) -> bool {
+    max_attempts > 0 && failures >= max_attempts
+}
+
+/// The ingestion answered with an error, retrying the same batch won't help
+/// for ever.
+#[derive(Debug, thiserror::Error)]
+#[error("ingestion failed {0}")]
+struct Rejected(String);
+
+async fn ingest(
+    org_id: &str,
+    source: &KafkaSource,
+    records: Vec<Record>,
+) -> Result<usize, anyhow::Error> {
+    let values = records
+        .into_iter()
+        .filter_map(|r| r.value)
+        .filter(|v| !v.is_empty());
+    match source.format {
+        KafkaFormat::Json => {
+            let items = json_items(values, |e| {
+                log::warn!(
+                    "[KAFKA] skip invalid json message of source {org_id}/{}: {e}",
+                    source.name
+                )
+            });
+            if items.is_empty() {
+                return Ok(0);
+            }
+            let count = items.len();
+            let body = actix_web::web::Bytes::from(json::to_vec(&items)?);
+            let resp = logs::ingest::ingest(
+                org_id,
+                &source.stream_name,
+                IngestionRequest::JSON(&body),
+                0,
+                "",
+            )
+            .await?;
+            if resp.code != actix_web::http::StatusCode::OK.as_u16() {
+                return Err(Rejected(format!(
+                    "with code {}: {}",
+                    resp.code,
+                    resp.error.unwrap_or_default()
+                ))
+                .into());
+            }
+            Ok(count)
+        }
+        KafkaFormat::OtlpProto => {
+            let mut request = ExportLogsServiceRequest::default();
+            for value in values {
+                match ExportLogsServiceRequest::decode(value.as_slice()) {
+                    Ok(v) => request.resource_logs.extend(v.resource_logs),
+                    Err(e) => log::warn!(
+                        "[KAFKA] skip invalid protobuf message of source {org_id}/{}: {e}",
+                        source.name
+                    ),
+                }
+            }
+            let count = request
+                .resource_logs
+                .iter()
+                .flat_map(|r| r.scope_logs.iter())
+                .map(|s| s.log_records.len())
+                .sum();
+            if count == 0 {
+                return Ok(0);
+            }
+            let resp = logs::otlp_grpc::handle_grpc_request(
+                org_id,
+                0,
+                request,
+                false,
+                Some(&source.stream_name),
+                "",
+            )
+            .await?;
+            if !resp.status().is_success() {
+                return Err(Rejected(format!("with status {}", resp.status())).into());
+            }
+            Ok(count)
+        }
+    }
+}
+
+/// The json values of the messages, an array message gives one value per
+/// element.
+fn json_items(
+    values: impl Iterator<Item = Vec<u8>>,
+    on_error: impl Fn(json::Error),
+) -> Vec<json::Value> {
+    let mut items = vec![];
+    for value in values {
+        match json::from_slice::<json::Value>(&value) {
+            Ok(json::Value::Array(v)) => items.extend(v),
+            Ok(v) => items.push(v),
+            Err(e) => on_error(e),
+        }
+    }
+    items
+}
+
+async fn get_client(brokers: &[String]) -> Result<Arc<Client>, anyhow::Error> {
+    let key = brokers.join(",");
+    if let Some(client) = CLIENTS.get(&key) {
+        return Ok(client.clone());
+    }
+    // a fetch waits up to the max wait on the broker before answering
+    let timeout =
+        Duration::from_millis(CONFIG.kafka.max_wait_ms.max(0) as u64) + Duration::from_secs(30);
+    let client = Arc::new(Client::new(brokers.to_vec(), timeout));
+    CLIENTS.insert(key, client.clone());
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("app.logs-v2_eu"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("app/logs"));
+        assert!(!is_valid_name("app logs"));
+    }
+
+    #[test]
+    fn test_should_skip() {
+        assert!(!should_skip(1, 5));
+        assert!(!should_skip(4, 5));
+        assert!(should_skip(5, 5));
+        assert!(!should_skip(100, 0));
+    }
+
+    #[test]
+    fn test_json_items() {
+        let values = vec![
+            br#"{"a":1}"#.to_vec(),
+            br#"[{"a":2},{"a":3}]"#.to_vec(),
+            b"not json".to_vec(),
+        ];
+        let errors = std::cell::Cell::new(0);
+        let items = json_items(values.into_iter(), |_| errors.set(errors.get() + 1));
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2]["a"], 3);
+        assert_eq!(errors.get(), 1);
+    }
+
+    #[test]
+    fn test_source_status_not_stored() {
+        let source: KafkaSource = json::from_str(
+            r#"{"brokers":["b:9092"],"topics":["t"],"stream_name":"s","last_error":"boom"}"#,
+        )
+        .unwrap();
+        assert!(source.last_error.is_empty());
+        let stored = json::to_string(&source).unwrap();
+        assert!(!stored.contains("last_error"));
+    }
+
+    #[test]
+    fn test_offset_failures_default() {
+        let offset: KafkaOffset =
+            json::from_str(r#"{"topic":"t","partition":0,"offset":7,"updated_at":0}"#).unwrap();
+        assert_eq!(offset.failures, 0);
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! The keys changed by the normalization at ingestion are reported per
+//! stream, so the producers can see which of their fields were renamed or
+//! dropped, e.g. `Field-Name` and `field_name` both stored in `field_name`.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    meta::stream::StreamType,
+    utils::{
+        flatten::{self, FlattenOptions},
+        json::Value,
+    },
+    RwHashSet,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    common::meta::stream::KeyMapping,
+    service::{db, error::Result},
+};
+
+// key: org_id/stream_type/stream_name/original/key, the mappings already stored
+static STORED: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+/// Mappings remembered at most, the set starts over once full and the
+/// mappings seen again are found in the meta store
+const MAX_STORED: usize = 100_000;
+
+/// The new mappings, stored together a second after the first of them
+static PENDING: Lazy<Mutex<Vec<PendingMapping>>> = Lazy::new(Default::default);
+
+struct PendingMapping {
+    stored_key: String,
+    org_id: String,
+    stream_type: StreamType,
+    stream_name: String,
+    mapping: KeyMapping,
+}
+
+/// Flattens a record with the options of its stream. When the stream reports
+/// its key mappings, the keys the normalization changed are stored the first
+/// time they are seen.
+pub fn flatten(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    value: Value,
+    options: &FlattenOptions,
+) -> Result<Value, anyhow::Error> {
+    if !options.keys.report {
+        return flatten::flatten_with_options(value, options);
+    }
+    let (value, renamed) = flatten::flatten_with_renames(value, options)?;
+    let mut new_mappings = Vec::new();
+    for (original, key) in renamed {
+        let stored_key = format!("{org_id}/{stream_type}/{stream_name}/{original}/{key}");
+        if STORED.contains(&stored_key) {
+            continue;
+        }
+        if STORED.len() >= MAX_STORED {
+            STORED.clear();
+        }
+        STORED.insert(stored_key.clone());
+        new_mappings.push(PendingMapping {
+            stored_key,
+            org_id: org_id.to_string(),
+            stream_type,
+            stream_name: stream_name.to_string(),
+            mapping: KeyMapping {
+                original,
+                key,
+                first_seen: Utc::now().timestamp_micros(),
+            },
+        });
+    }
+    if !new_mappings.is_empty() {
+        let mut pending = PENDING.lock();
+        if pending.is_empty() {
+            tokio::task::spawn(async {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                store_pending().await;
+            });
+        }
+        pending.extend(new_mappings);
+    }
+    Ok(value)
+}
+
+async fn store_pending() {
+    let pending = std::mem::take(&mut *PENDING.lock());
+    let mut streams: HashMap<(String, StreamType, String), Vec<PendingMapping>> = HashMap::new();
+    for item in pending {
+        let stream = (
+            item.org_id.clone(),
+            item.stream_type,
+            item.stream_name.clone(),
+        );
+        streams.entry(stream).or_default().push(item);
+    }
+    for ((org_id, stream_type, stream_name), items) in streams {
+        let mappings = items.iter().map(|v| v.mapping.clone()).collect::<Vec<_>>();
+        if let Err(e) =
+            db::key_mappings::set_if_absent(&org_id, stream_type, &stream_name, &mappings).await
+        {
+            log::error!("[KEY_MAPPING] {org_id}/{stream_type}/{stream_name} store error: {e}");
+            for item in items {
+                STORED.remove(&item.stored_key);
+            }
+        }
+    }
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<KeyMapping>> {
+    Ok(db::key_mappings::list(org_id, stream_type, stream_name).await?)
+}
+
+/// Forgets the mappings of a deleted stream.
+pub async fn delete(org_id: &str, stream_type: StreamType, stream_name: &str) {
+    let prefix = format!("{org_id}/{stream_type}/{stream_name}/");
+    STORED.retain(|k| !k.starts_with(&prefix));
+    if let Err(e) = db::key_mappings::delete(org_id, stream_type, stream_name).await {
+        log::error!("[KEY_MAPPING] {org_id}/{stream_type}/{stream_name} delete error: {e}");
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::{TimeZone, Utc};
+use config::{
+    meta::stream::StreamType,
+    utils::json::{Map, Value},
+    CONFIG,
+};
+use infra::{schema::STREAM_SETTINGS, storage};
+
+use crate::common::meta::organization::Feature;
+
+/// Marker appended to the preview of an offloaded value, followed by the reference of the
+/// object, `{stream_type}/{stream_name}/{YYYY/MM/DD}/{hash}`.
+pub const LARGE_FIELD_MARKER: &str = "zo_large_field:";
+
+const DAY_MICROS: i64 = 86_400_000_000;
+
+/// The objects are stored by stream and day, so that they are deleted with the files of
+/// the stream.
+fn object_key(org_id: &str, reference: &str) -> String {
+    format!("large_fields/{org_id}/{reference}")
+}
+
+fn day_key(timestamp: i64) -> String {
+    Utc.timestamp_nanos(timestamp * 1000)
+        .format("%Y/%m/%d")
+        .to_string()
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_valid_reference(reference: &str) -> bool {
+    let parts = reference.split('/').collect::<Vec<_>>();
+    parts.len() == 6
+        && parts[..2]
+            .iter()
+            .all(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        && parts[2..5]
+            .iter()
+            .all(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit()))
+        && is_hash(parts[5])
+}
+
+/// Returns the max field size configured for the stream, 0 means no limit.
+pub async fn get_max_field_size(org_id: &str, stream_type: StreamType, stream_name: &str) -> usize {
+    if !crate::service::organization::is_feature_enabled(org_id, Feature::LargeFields) {
+        return 0;
+    }
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STREAM_SETTINGS
+        .read()
+        .await
+        .get(&key)
+        .map(|s| s.max_field_size)
+        .unwrap_or_default()
+}
+
+/// Move string values larger than `max_size` to a content addressed object of the day of
+/// the record and replace them by a truncated preview with a reference to the object. The
+/// objects of a record are uploaded together.
+pub async fn offload(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    timestamp: i64,
+    record: &mut Map<String, Value>,
+    max_size: usize,
+) -> Result<(), anyhow::Error> {
+    if max_size == 0 {
+        return Ok(());
+    }
+    let day = day_key(timestamp);
+    let mut objects = Vec::new();
+    for (key, value) in record.iter_mut() {
+        if key == &CONFIG.common.column_timestamp {
+            continue;
+        }
+        let Value::String(v) = value else {
+            continue;
+        };
+        if v.len() <= max_size {
+            continue;
+        }
+        let hash = blake3::hash(v.as_bytes()).to_hex().to_string();
+        let preview = preview(v, CONFIG.limit.large_field_preview_size.min(max_size)).to_string();
+        let data = std::mem::take(v);
+        let reference = format!("{stream_type}/{stream_name}/{day}/{hash}");
+        *v = format!("{preview}...[{LARGE_FIELD_MARKER}{reference}]");
+        objects.push((object_key(org_id, &reference), data));
+    }
+
+    let uploads = objects
+        .into_iter()
+        .map(|(key, data)| async move { storage::put(&key, bytes::Bytes::from(data)).await });
+    futures::future::try_join_all(uploads).await?;
+    Ok(())
+}
+
+/// Returns the reference of an offloaded value from its preview, `None` if the value
+/// was not offloaded.
+pub fn parse_reference(value: &str) -> Option<&str> {
+    let (_, reference) = value.strip_suffix(']')?.rsplit_once(LARGE_FIELD_MARKER)?;
+    is_valid_reference(reference).then_some(reference)
+}
+
+/// Fetch the full value of an offloaded field.
+pub async fn get(org_id: &str, reference: &str) -> Result<bytes::Bytes, anyhow::Error> {
+    if !is_valid_reference(reference) {
+        return Err(anyhow::anyhow!(
+            "invalid large field reference: {reference}"
+        ));
+    }
+    storage::get(&object_key(org_id, reference)).await
+}
+
+/// Deletes the offloaded values of the stream, those of the days of the time range when
+/// given, along with the files of the stream.
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    time_range: Option<(i64, i64)>,
+) -> Result<(), anyhow::Error> {
+    let prefix = format!("large_fields/{org_id}/{stream_type}/{stream_name}/");
+    let prefixes = match time_range {
+        None => vec![prefix],
+        Some((start_time, end_time)) => {
+            let mut prefixes = vec![];
+            let mut day = start_time - start_time.rem_euclid(DAY_MICROS);
+            while day < end_time {
+                prefixes.push(format!("{prefix}{}/", day_key(day)));
+                day += DAY_MICROS;
+            }
+            prefixes
+        }
+    };
+    for prefix in prefixes {
+        loop {
+            let files = storage::list(&prefix).await?;
+            if files.is_empty() {
+                break;
+            }
+            storage::del(&files.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await?;
+        }
+    }
+    Ok(())
+}
+
+fn preview(value: &str, size: usize) -> &str {
+    if value.len() <= size {
+        return value;
+    }
+    let mut end = size;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("hello world", 5), "hello");
+        assert_eq!(preview("hello", 10), "hello");
+        // never split a multi-byte char
+        assert_eq!(preview("héllo", 2), "h");
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        let hash = blake3::hash(b"stack").to_hex().to_string();
+        let reference = format!("logs/app/2024/05/01/{hash}");
+        let value = format!("at main...[{LARGE_FIELD_MARKER}{reference}]");
+        assert_eq!(parse_reference(&value), Some(reference.as_str()));
+        assert_eq!(parse_reference("at main"), None);
+        assert_eq!(parse_reference(&format!("[{LARGE_FIELD_MARKER}abc]")), None);
+        // a bare hash or a path out of the stream is not a reference
+        assert_eq!(
+            parse_reference(&format!("[{LARGE_FIELD_MARKER}{hash}]")),
+            None
+        );
+        assert_eq!(
+            parse_reference(&format!("[{LARGE_FIELD_MARKER}logs/../2024/05/01/{hash}]")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_object_key() {
+        let hash = blake3::hash(b"stack").to_hex().to_string();
+        let reference = format!("logs/app/{}/{hash}", day_key(1_714_521_600_000_000));
+        assert_eq!(
+            object_key("default", &reference),
+            format!("large_fields/default/logs/app/2024/05/01/{hash}")
+        );
+    }
+}
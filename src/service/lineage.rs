@@ -0,0 +1,220 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Records ingested into a stream tracking lineage are stamped with the id of
+//! the pipeline and the function chain that ran, so the records affected by a
+//! faulty function or pipeline setting can be found with
+//! `WHERE _lineage = <id>` and backfilled alone.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    meta::stream::StreamType,
+    utils::{
+        hash::{fnv, Sum64},
+        json::{Map, Value},
+    },
+    RwHashSet,
+};
+use infra::schema::{get_settings_revision, STREAM_SETTINGS};
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{
+        meta::functions::{
+            Lineage, LineageFunction, LineageStage, StreamTransform, VRLResultResolver,
+        },
+        utils::functions::resolve_function,
+    },
+    service::{
+        db,
+        error::{Result, ServiceError},
+    },
+};
+
+/// Column holding the id of the function chain which transformed the record.
+pub const LINEAGE_COLUMN: &str = "_lineage";
+
+// key: org_id/stream_type/stream_name/id, the lineages already stored
+static STORED: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+/// Returns whether the stream stamps its records with their lineage.
+pub async fn is_enabled(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STREAM_SETTINGS
+        .read()
+        .await
+        .get(&key)
+        .map(|s| s.lineage)
+        .unwrap_or_default()
+}
+
+/// Id of the pipeline and function chain, changing the settings of a stream of
+/// the pipeline, a function or the order gives a new id.
+pub fn lineage_id(pipeline: &[LineageStage], functions: &[LineageFunction]) -> i64 {
+    if pipeline.is_empty() && functions.is_empty() {
+        return 0;
+    }
+    let chain = pipeline
+        .iter()
+        .map(|s| format!("{}@{}\n", s.stream_name, s.settings_revision))
+        .chain(
+            functions
+                .iter()
+                .map(|f| format!("{}\n{}\n", f.name, f.function)),
+        )
+        .collect::<String>();
+    // keep the ids positive
+    (fnv::new().sum64(&chain) >> 1) as i64
+}
+
+/// Returns the lineage id the records of the stream are stamped with, `None`
+/// when the stream doesn't track lineage. `routed_from` is the stream whose
+/// routing sent the records. The first time a lineage is seen it is stored for
+/// the lookups.
+pub async fn register(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    routed_from: Option<&str>,
+    local_trans: &[StreamTransform],
+    stream_vrl_map: &HashMap<String, VRLResultResolver>,
+) -> Option<i64> {
+    if !is_enabled(org_id, stream_type, stream_name).await {
+        return None;
+    }
+    let mut pipeline = Vec::with_capacity(2);
+    for name in routed_from
+        .filter(|name| *name != stream_name)
+        .into_iter()
+        .chain([stream_name])
+    {
+        let settings_revision = match infra::schema::get(org_id, name, stream_type).await {
+            Ok(schema) => get_settings_revision(&schema),
+            Err(e) => {
+                log::error!("[LINEAGE] {org_id}/{stream_type}/{name} get schema error: {e}");
+                return None;
+            }
+        };
+        pipeline.push(LineageStage {
+            stream_name: name.to_string(),
+            settings_revision,
+        });
+    }
+    // only the functions which compiled run on the records
+    let functions = local_trans
+        .iter()
+        .filter(|t| stream_vrl_map.contains_key(&format!("{stream_name}/{}", t.transform.name)))
+        .filter_map(|t| {
+            resolve_function(&t.transform).map(|function| LineageFunction {
+                name: t.transform.name.clone(),
+                function,
+            })
+        })
+        .collect::<Vec<_>>();
+    let id = lineage_id(&pipeline, &functions);
+
+    let key = format!("{org_id}/{stream_type}/{stream_name}/{id}");
+    if !STORED.contains(&key) {
+        let lineage = Lineage {
+            id,
+            stream_type,
+            stream_name: stream_name.to_string(),
+            pipeline,
+            functions,
+            first_seen: Utc::now().timestamp_micros(),
+            superseded: vec![],
+        };
+        match db::lineage::set_if_absent(org_id, &lineage).await {
+            Ok(_) => {
+                STORED.insert(key);
+            }
+            Err(e) => {
+                log::error!("[LINEAGE] {org_id}/{stream_type}/{stream_name} store error: {e}")
+            }
+        }
+    }
+    Some(id)
+}
+
+pub fn attach(record: &mut Map<String, Value>, id: i64) {
+    record.insert(LINEAGE_COLUMN.to_string(), Value::from(id));
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    id: i64,
+) -> Result<Lineage> {
+    db::lineage::get(org_id, stream_type, stream_name, id)
+        .await
+        .map_err(|_| ServiceError::not_found(format!("lineage {id} not found")))
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<Lineage>> {
+    Ok(db::lineage::list(org_id, stream_type, stream_name).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, function: &str) -> LineageFunction {
+        LineageFunction {
+            name: name.to_string(),
+            function: function.to_string(),
+        }
+    }
+
+    fn stage(stream_name: &str, settings_revision: u64) -> LineageStage {
+        LineageStage {
+            stream_name: stream_name.to_string(),
+            settings_revision,
+        }
+    }
+
+    #[test]
+    fn test_lineage_id() {
+        assert_eq!(lineage_id(&[], &[]), 0);
+        let pipeline = [stage("app", 3)];
+        let parse = function("parse", ".a = parse_json!(.message)");
+        let drop = function("drop", "del(.b)");
+        let id = lineage_id(&pipeline, &[parse.clone(), drop.clone()]);
+        assert!(id > 0);
+        assert_eq!(id, lineage_id(&pipeline, &[parse.clone(), drop.clone()]));
+        // the order and the body of the functions are part of the chain
+        assert_ne!(id, lineage_id(&pipeline, &[drop.clone(), parse.clone()]));
+        assert_ne!(
+            id,
+            lineage_id(
+                &pipeline,
+                &[function("parse", ".a = parse_json(.message)"), drop.clone()]
+            )
+        );
+        // so are the settings and the routing of the pipeline
+        let functions = [parse, drop];
+        assert_ne!(id, lineage_id(&[stage("app", 4)], &functions));
+        assert_ne!(
+            id,
+            lineage_id(&[stage("default", 1), stage("app", 3)], &functions)
+        );
+        assert!(lineage_id(&pipeline, &[]) > 0);
+    }
+}
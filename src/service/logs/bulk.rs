@@ -29,12 +29,17 @@ use config::{
         usage::UsageType,
     },
     metrics,
-    utils::{flatten, json, schema_ext::SchemaExt, time::parse_timestamp_micro_from_value},
+    utils::{
+        flatten::{self, FlattenOptions},
+        json,
+        schema_ext::SchemaExt,
+        time::parse_timestamp_micro_from_value,
+    },
     BLOCKED_STREAMS, CONFIG, DISTINCT_FIELDS,
 };
 use infra::schema::unwrap_partition_time_level;
 
-use super::{add_record, cast_to_schema_v1, StreamMeta};
+use super::{add_record, cast_to_schema_v1, routing::CrossOrgRecords, StreamMeta};
 use crate::{
     common::meta::{
         alerts::Alert,
@@ -47,7 +52,7 @@ use crate::{
     },
     service::{
         db,
-        ingestion::{evaluate_trigger, write_file, TriggerAlertData},
+        ingestion::{evaluate_trigger, write_file, StreamDropRules, TriggerAlertData},
         metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
         schema::{
             get_invalid_schema_start_dt, get_upto_discard_error, stream_schema_exists, SchemaCache,
@@ -72,6 +77,7 @@ pub async fn ingest(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -86,6 +92,7 @@ pub async fn ingest(
         took: 0,
         errors: false,
         items: vec![],
+        warnings: vec![],
     };
 
     let min_ts = (Utc::now() - Duration::try_hours(CONFIG.limit.ingest_allowed_upto).unwrap())
@@ -114,6 +121,19 @@ pub async fn ingest(
 
     let mut user_defined_schema_map: HashMap<String, Vec<String>> = HashMap::new();
 
+    let mut stream_drop_rules_map: HashMap<String, StreamDropRules> = HashMap::new();
+
+    let mut stream_flatten_map: HashMap<String, FlattenOptions> = HashMap::new();
+
+    // whether the stream keeps the original of the records, and encrypts it
+    let mut stream_original_map: HashMap<String, (bool, bool)> = HashMap::new();
+    let mut retain_original = false;
+    // key: (source stream, stream), the routing is part of the lineage
+    let mut stream_lineage_map: HashMap<(String, String), Option<i64>> = HashMap::new();
+
+    // records routed to a stream of another org, by target org and stream
+    let mut cross_org_records = CrossOrgRecords::default();
+
     let mut next_line_is_data = false;
     let reader = BufReader::new(body.as_ref());
     for line in reader.lines() {
@@ -162,6 +182,10 @@ pub async fn ingest(
 
             if let Some(routes) = stream_routing_map.get(&stream_name) {
                 for route in routes {
+                    // other orgs ingest their records with their own settings
+                    if route.cross_org_destination().is_some() {
+                        continue;
+                    }
                     streams.push(StreamParams {
                         org_id: org_id.to_owned().into(),
                         stream_type: StreamType::Logs,
@@ -172,6 +196,17 @@ pub async fn ingest(
 
             // End get stream keys
 
+            // the records are flattened with the settings of the stream they were sent to
+            if !stream_flatten_map.contains_key(&stream_name) {
+                let options = crate::service::ingestion::get_stream_flatten_options(
+                    org_id,
+                    &StreamType::Logs,
+                    &stream_name,
+                )
+                .await;
+                stream_flatten_map.insert(stream_name.clone(), options);
+            }
+
             crate::service::ingestion::get_user_defined_schema(
                 &streams,
                 &mut user_defined_schema_map,
@@ -193,6 +228,8 @@ pub async fn ingest(
             crate::service::ingestion::get_stream_alerts(&streams, &mut stream_alerts_map).await;
             // End get stream alert
 
+            // the records may be routed to a stream keeping the originals
+            retain_original = false;
             for stream in streams {
                 let local_stream_name = stream.stream_name.to_string();
                 if let std::collections::hash_map::Entry::Vacant(e) =
@@ -213,6 +250,36 @@ pub async fn ingest(
                     .await;
                     e.insert((stream_schema, partition_det));
                 }
+                if !stream_drop_rules_map.contains_key(&local_stream_name) {
+                    let drop_rules = crate::service::ingestion::get_stream_drop_rules(
+                        org_id,
+                        &StreamType::Logs,
+                        &local_stream_name,
+                    )
+                    .await;
+                    stream_drop_rules_map.insert(local_stream_name.clone(), drop_rules);
+                }
+                let enabled = match stream_original_map.get(&local_stream_name) {
+                    Some((enabled, _)) => *enabled,
+                    None => {
+                        let enabled = crate::service::original::is_enabled(
+                            org_id,
+                            StreamType::Logs,
+                            &local_stream_name,
+                        )
+                        .await;
+                        let encrypted = enabled
+                            && crate::service::original::is_encrypted(
+                                org_id,
+                                StreamType::Logs,
+                                &local_stream_name,
+                            )
+                            .await;
+                        stream_original_map.insert(local_stream_name, (enabled, encrypted));
+                        enabled
+                    }
+                };
+                retain_original |= enabled;
             }
 
             stream_data_map
@@ -223,88 +290,98 @@ pub async fn ingest(
         } else {
             next_line_is_data = false;
 
-            // JSON Flattening
-            let mut value = flatten::flatten_with_level(value, CONFIG.limit.ingest_flatten_level)?;
-
-            if let Some(routing) = stream_routing_map.get(&stream_name) {
-                if !routing.is_empty() {
-                    for route in routing {
-                        let mut is_routed = true;
-                        let val = &route.routing;
-                        for q_condition in val.iter() {
-                            is_routed =
-                                is_routed && q_condition.evaluate(value.as_object().unwrap()).await;
-                        }
-                        if is_routed && !val.is_empty() {
-                            stream_name = route.destination.clone();
-                            if !stream_data_map.contains_key(&stream_name) {
-                                stream_data_map.insert(
-                                    stream_name.clone(),
-                                    BulkStreamData {
-                                        data: HashMap::new(),
-                                    },
-                                );
+            // keep the record as received, before flattening and the functions
+            let original = retain_original.then(|| value.clone());
+
+            // a record can be exploded into one row per element of its arrays, the rows
+            // are routed on their own and the record gets a single status
+            let source_stream = stream_name.clone();
+            let rows = flatten::explode(value, &stream_flatten_map[&source_stream]);
+            let row_size = line.len() / rows.len();
+            let statuses = bulk_res.items.len();
+            for value in rows {
+                let mut stream_name = source_stream.clone();
+                let original = original.clone();
+
+                // JSON Flattening
+                let mut value = crate::service::key_mappings::flatten(
+                    org_id,
+                    StreamType::Logs,
+                    &stream_name,
+                    value,
+                    &stream_flatten_map[&stream_name],
+                )?;
+
+                let mut cross_org_route = None;
+                if let Some(routing) = stream_routing_map.get(&stream_name) {
+                    if !routing.is_empty() {
+                        for route in routing {
+                            let mut is_routed = true;
+                            let val = &route.routing;
+                            for q_condition in val.iter() {
+                                is_routed = is_routed
+                                    && q_condition.evaluate(value.as_object().unwrap()).await;
+                            }
+                            if is_routed && !val.is_empty() {
+                                if let Some((target_org, target_stream)) =
+                                    route.cross_org_destination()
+                                {
+                                    if !cross_org_records
+                                        .is_allowed(org_id, &stream_name, target_org)
+                                        .await
+                                    {
+                                        continue;
+                                    }
+                                    cross_org_route =
+                                        Some((target_org.to_string(), target_stream.to_string()));
+                                    break;
+                                }
+                                stream_name = route.destination.clone();
+                                if !stream_data_map.contains_key(&stream_name) {
+                                    stream_data_map.insert(
+                                        stream_name.clone(),
+                                        BulkStreamData {
+                                            data: HashMap::new(),
+                                        },
+                                    );
+                                }
+                                break;
                             }
-                            break;
                         }
                     }
                 }
-            }
-
-            let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
-            let buf = &mut stream_data.data;
 
-            let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
-
-            // Start row based transform
-            if let Some(transforms) = stream_functions_map.get(&key) {
-                let mut ret_value = value.clone();
-                ret_value = crate::service::ingestion::apply_stream_functions(
-                    transforms,
-                    ret_value,
-                    &stream_vrl_map,
-                    &stream_name,
-                    &mut runtime,
-                )?;
-
-                if ret_value.is_null() || !ret_value.is_object() {
-                    bulk_res.errors = true;
+                if let Some((target_org, target_stream)) = cross_org_route {
                     add_record_status(
-                        stream_name.clone(),
+                        format!("{target_org}/{target_stream}"),
                         doc_id.clone(),
                         action.clone(),
-                        Some(value),
+                        None,
                         &mut bulk_res,
-                        Some(TRANSFORM_FAILED.to_owned()),
-                        Some(TRANSFORM_FAILED.to_owned()),
+                        None,
+                        None,
                     );
+                    cross_org_records.push(&target_org, &target_stream, value);
                     continue;
-                } else {
-                    value = ret_value;
                 }
-            }
-            // End row based transform
 
-            // get json object
-            let mut local_val = match value.take() {
-                json::Value::Object(v) => v,
-                _ => unreachable!(),
-            };
+                let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
+                let buf = &mut stream_data.data;
 
-            if let Some(fields) = user_defined_schema_map.get(&stream_name) {
-                crate::service::logs::refactor_map(&mut local_val, fields);
-            }
+                let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
 
-            // set _id
-            if !doc_id.is_empty() {
-                local_val.insert("_id".to_string(), json::Value::String(doc_id.clone()));
-            }
+                // Start row based transform
+                if let Some(transforms) = stream_functions_map.get(&key) {
+                    let mut ret_value = value.clone();
+                    ret_value = crate::service::ingestion::apply_stream_functions(
+                        transforms,
+                        ret_value,
+                        &stream_vrl_map,
+                        &stream_name,
+                        &mut runtime,
+                    )?;
 
-            // handle timestamp
-            let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
-                Some(v) => match parse_timestamp_micro_from_value(v) {
-                    Ok(t) => t,
-                    Err(_e) => {
+                    if ret_value.is_null() || !ret_value.is_object() {
                         bulk_res.errors = true;
                         add_record_status(
                             stream_name.clone(),
@@ -312,80 +389,67 @@ pub async fn ingest(
                             action.clone(),
                             Some(value),
                             &mut bulk_res,
-                            Some(TS_PARSE_FAILED.to_string()),
-                            Some(TS_PARSE_FAILED.to_string()),
+                            Some(TRANSFORM_FAILED.to_owned()),
+                            Some(TRANSFORM_FAILED.to_owned()),
                         );
                         continue;
+                    } else {
+                        value = ret_value;
                     }
-                },
-                None => Utc::now().timestamp_micros(),
-            };
-            // check ingestion time
-            if timestamp < min_ts {
-                bulk_res.errors = true;
-                let failure_reason = Some(get_upto_discard_error().to_string());
-                add_record_status(
-                    stream_name.clone(),
-                    doc_id.clone(),
-                    action.clone(),
-                    Some(value),
-                    &mut bulk_res,
-                    Some(TS_PARSE_FAILED.to_string()),
-                    failure_reason,
-                );
-                continue;
-            }
-            local_val.insert(
-                CONFIG.common.column_timestamp.clone(),
-                json::Value::Number(timestamp.into()),
-            );
-            let (partition_keys, partition_time_level) =
-                match stream_partition_keys_map.get(&stream_name) {
-                    Some((_, partition_det)) => (
-                        partition_det.partition_keys.clone(),
-                        partition_det.partition_time_level,
-                    ),
-                    None => (vec![], None),
+                }
+                // End row based transform
+
+                // get json object
+                let mut local_val = match value.take() {
+                    json::Value::Object(v) => v,
+                    _ => unreachable!(),
                 };
 
-            // only for bulk insert
-            let mut status = RecordStatus::default();
-            let need_trigger = !stream_trigger_map.contains_key(&stream_name);
-
-            let mut to_add_distinct_values = vec![];
-            // get distinct_value items
-            for field in DISTINCT_FIELDS.iter() {
-                if let Some(val) = local_val.get(field) {
-                    if !val.is_null() {
-                        to_add_distinct_values.push(MetadataItem::DistinctValues(DvItem {
-                            stream_type: StreamType::Logs,
-                            stream_name: stream_name.clone(),
-                            field_name: field.to_string(),
-                            field_value: val.as_str().unwrap().to_string(),
-                            filter_name: "".to_string(),
-                            filter_value: "".to_string(),
-                        }));
-                    }
+                if stream_drop_rules_map
+                    .get(&stream_name)
+                    .is_some_and(|rules| rules.should_drop(&local_val))
+                {
+                    continue;
+                }
+
+                if let Some(fields) = user_defined_schema_map.get(&stream_name) {
+                    crate::service::logs::refactor_map(&mut local_val, fields);
                 }
-            }
 
-            // this is for schema inference at stream level , which avoids locks in case schema
-            // changes are frequent within request
-            if CONFIG.common.infer_schema_per_request {
-                if let Err(e) = add_record(
-                    &StreamMeta {
-                        org_id: org_id.to_string(),
-                        stream_name: stream_name.clone(),
-                        partition_keys: &partition_keys,
-                        partition_time_level: &partition_time_level,
-                        stream_alerts_map: &stream_alerts_map,
+                crate::service::ingestion::reserved::protect(
+                    &mut local_val,
+                    &mut bulk_res.warnings,
+                );
+
+                // set _id
+                if !doc_id.is_empty() {
+                    local_val.insert("_id".to_string(), json::Value::String(doc_id.clone()));
+                }
+
+                // handle timestamp
+                let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
+                    Some(v) => match parse_timestamp_micro_from_value(v) {
+                        Ok(t) => t,
+                        Err(_e) => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TS_PARSE_FAILED.to_string()),
+                                Some(TS_PARSE_FAILED.to_string()),
+                            );
+                            continue;
+                        }
                     },
-                    buf,
-                    local_val,
-                )
-                .await
-                {
+                    None => Utc::now().timestamp_micros(),
+                };
+                // check ingestion time
+                if timestamp < min_ts {
                     bulk_res.errors = true;
+                    let failure_reason = Some(get_upto_discard_error().to_string());
                     add_record_status(
                         stream_name.clone(),
                         doc_id.clone(),
@@ -393,29 +457,139 @@ pub async fn ingest(
                         Some(value),
                         &mut bulk_res,
                         Some(TS_PARSE_FAILED.to_string()),
-                        Some(e.to_string()),
+                        failure_reason,
                     );
                     continue;
                 }
-            } else {
-                let local_trigger = match super::add_valid_record(
-                    &StreamMeta {
-                        org_id: org_id.to_string(),
-                        stream_name: stream_name.clone(),
-                        partition_keys: &partition_keys,
-                        partition_time_level: &partition_time_level,
-                        stream_alerts_map: &stream_alerts_map,
-                    },
-                    &mut stream_schema_map,
-                    &mut status,
-                    buf,
-                    local_val,
-                    need_trigger,
+                local_val.insert(
+                    CONFIG.common.column_timestamp.clone(),
+                    json::Value::Number(timestamp.into()),
+                );
+                crate::service::record_id::attach(&mut local_val, timestamp);
+                let lineage_key = (source_stream.clone(), stream_name.clone());
+                let lineage = match stream_lineage_map.get(&lineage_key) {
+                    Some(lineage) => *lineage,
+                    None => {
+                        let lineage = crate::service::lineage::register(
+                            org_id,
+                            StreamType::Logs,
+                            &stream_name,
+                            Some(&source_stream),
+                            stream_functions_map
+                                .get(&key)
+                                .map(|v| v.as_slice())
+                                .unwrap_or_default(),
+                            &stream_vrl_map,
+                        )
+                        .await;
+                        stream_lineage_map.insert(lineage_key, lineage);
+                        lineage
+                    }
+                };
+                if let Some(lineage) = lineage {
+                    crate::service::lineage::attach(&mut local_val, lineage);
+                }
+                let (keep_original, encrypt_original) = stream_original_map
+                    .get(&stream_name)
+                    .copied()
+                    .unwrap_or_default();
+                if let Some(original) = original.filter(|_| keep_original) {
+                    match crate::service::original::capture(org_id, &original, encrypt_original)
+                        .await
+                    {
+                        Ok(v) => crate::service::original::attach(&mut local_val, v),
+                        Err(e) => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TRANSFORM_FAILED.to_owned()),
+                                Some(e.to_string()),
+                            );
+                            continue;
+                        }
+                    }
+                }
+                // only the records written to the stream count against its quotas
+                if let Err(e) = crate::service::ingestion::quota::check(
+                    org_id,
+                    StreamType::Logs,
+                    &stream_name,
+                    1,
+                    row_size,
                 )
                 .await
                 {
-                    Ok(v) => v,
-                    Err(e) => {
+                    bulk_res.errors = true;
+                    add_record_status(
+                        stream_name.clone(),
+                        doc_id.clone(),
+                        action.clone(),
+                        None,
+                        &mut bulk_res,
+                        Some("quota_exceeded".to_string()),
+                        Some(e.to_string()),
+                    );
+                    if let Some(item) = bulk_res
+                        .items
+                        .last_mut()
+                        .and_then(|v| v.values_mut().next())
+                    {
+                        item.status = 429;
+                    }
+                    continue;
+                }
+
+                let (partition_keys, partition_time_level) =
+                    match stream_partition_keys_map.get(&stream_name) {
+                        Some((_, partition_det)) => (
+                            partition_det.partition_keys.clone(),
+                            partition_det.partition_time_level,
+                        ),
+                        None => (vec![], None),
+                    };
+
+                // only for bulk insert
+                let mut status = RecordStatus::default();
+                let need_trigger = !stream_trigger_map.contains_key(&stream_name);
+
+                let mut to_add_distinct_values = vec![];
+                // get distinct_value items
+                for field in DISTINCT_FIELDS.iter() {
+                    if let Some(val) = local_val.get(field) {
+                        if !val.is_null() {
+                            to_add_distinct_values.push(MetadataItem::DistinctValues(DvItem {
+                                stream_type: StreamType::Logs,
+                                stream_name: stream_name.clone(),
+                                field_name: field.to_string(),
+                                field_value: val.as_str().unwrap().to_string(),
+                                filter_name: "".to_string(),
+                                filter_value: "".to_string(),
+                            }));
+                        }
+                    }
+                }
+
+                // this is for schema inference at stream level , which avoids locks in case schema
+                // changes are frequent within request
+                if CONFIG.common.infer_schema_per_request {
+                    if let Err(e) = add_record(
+                        &StreamMeta {
+                            org_id: org_id.to_string(),
+                            stream_name: stream_name.clone(),
+                            partition_keys: &partition_keys,
+                            partition_time_level: &partition_time_level,
+                            stream_alerts_map: &stream_alerts_map,
+                            reprocessed: false,
+                        },
+                        buf,
+                        local_val,
+                    )
+                    .await
+                    {
                         bulk_res.errors = true;
                         add_record_status(
                             stream_name.clone(),
@@ -428,37 +602,71 @@ pub async fn ingest(
                         );
                         continue;
                     }
-                };
-                if local_trigger.is_some() {
-                    stream_trigger_map.insert(stream_name.clone(), local_trigger);
-                }
+                } else {
+                    let local_trigger = match super::add_valid_record(
+                        &StreamMeta {
+                            org_id: org_id.to_string(),
+                            stream_name: stream_name.clone(),
+                            partition_keys: &partition_keys,
+                            partition_time_level: &partition_time_level,
+                            stream_alerts_map: &stream_alerts_map,
+                            reprocessed: false,
+                        },
+                        &mut stream_schema_map,
+                        &mut status,
+                        buf,
+                        local_val,
+                        need_trigger,
+                    )
+                    .await
+                    {
+                        Ok(v) => v,
+                        Err(e) => {
+                            bulk_res.errors = true;
+                            add_record_status(
+                                stream_name.clone(),
+                                doc_id.clone(),
+                                action.clone(),
+                                Some(value),
+                                &mut bulk_res,
+                                Some(TS_PARSE_FAILED.to_string()),
+                                Some(e.to_string()),
+                            );
+                            continue;
+                        }
+                    };
+                    if local_trigger.is_some() {
+                        stream_trigger_map.insert(stream_name.clone(), local_trigger);
+                    }
 
-                // get distinct_value item
-                distinct_values.extend(to_add_distinct_values);
+                    // get distinct_value item
+                    distinct_values.extend(to_add_distinct_values);
 
-                if status.failed > 0 {
-                    bulk_res.errors = true;
-                    add_record_status(
-                        stream_name.clone(),
-                        doc_id.clone(),
-                        action.clone(),
-                        Some(value),
-                        &mut bulk_res,
-                        Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
-                        Some(status.error),
-                    );
-                } else {
-                    add_record_status(
-                        stream_name.clone(),
-                        doc_id.clone(),
-                        action.clone(),
-                        None,
-                        &mut bulk_res,
-                        None,
-                        None,
-                    );
+                    if status.failed > 0 {
+                        bulk_res.errors = true;
+                        add_record_status(
+                            stream_name.clone(),
+                            doc_id.clone(),
+                            action.clone(),
+                            Some(value),
+                            &mut bulk_res,
+                            Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
+                            Some(status.error),
+                        );
+                    } else {
+                        add_record_status(
+                            stream_name.clone(),
+                            doc_id.clone(),
+                            action.clone(),
+                            None,
+                            &mut bulk_res,
+                            None,
+                            None,
+                        );
+                    }
                 }
             }
+            merge_row_statuses(&mut bulk_res.items, statuses);
         }
     }
 
@@ -472,6 +680,21 @@ pub async fn ingest(
             log::warn!("stream [{stream_name}] is being deleted");
             continue;
         }
+        if db::compact::rename::is_renaming(org_id, StreamType::Logs, &stream_name) {
+            bulk_res.errors = true;
+            for item in bulk_res.items.iter_mut().flat_map(|v| v.values_mut()) {
+                if item._index == stream_name && item.error.is_none() {
+                    item.status = 503;
+                    item.error = Some(BulkResponseError::new(
+                        "stream_renaming".to_string(),
+                        stream_name.clone(),
+                        format!("stream [{stream_name}] is being renamed"),
+                        "0".to_owned(),
+                    ));
+                }
+            }
+            continue;
+        }
 
         // new flow for schema inference at stream level
         stream_data.data = if CONFIG.common.infer_schema_per_request {
@@ -525,6 +748,11 @@ pub async fn ingest(
         }
     }
 
+    // records routed to other orgs are ingested as a request of the target org
+    cross_org_records
+        .ingest(org_id, thread_id, user_email)
+        .await;
+
     metrics::HTTP_RESPONSE_TIME
         .with_label_values(&[
             "/api/org/ingest/logs/_bulk",
@@ -756,6 +984,20 @@ fn add_record_status(
     bulk_res.items.push(item);
 }
 
+/// Keeps a single status for the rows a record was exploded into, appended
+/// after `from`: the first failure, or the first status when all succeeded.
+fn merge_row_statuses(items: &mut Vec<HashMap<String, BulkResponseItem>>, from: usize) {
+    if items.len() <= from + 1 {
+        return;
+    }
+    let rows = items.split_off(from);
+    let status = rows
+        .iter()
+        .position(|item| item.values().any(|v| v.error.is_some()))
+        .unwrap_or(0);
+    items.extend(rows.into_iter().nth(status));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -766,6 +1008,7 @@ mod tests {
             took: 0,
             errors: false,
             items: vec![],
+            warnings: vec![],
         };
         add_record_status(
             "olympics".to_string(),
@@ -778,4 +1021,32 @@ mod tests {
         );
         assert!(bulk_res.items.len() == 1);
     }
+
+    #[test]
+    fn test_merge_row_statuses() {
+        let mut bulk_res = BulkResponse {
+            took: 0,
+            errors: false,
+            items: vec![],
+            warnings: vec![],
+        };
+        let mut add = |failure: Option<&str>| {
+            add_record_status(
+                "olympics".to_string(),
+                "1".to_string(),
+                "create".to_string(),
+                None,
+                &mut bulk_res,
+                failure.map(|f| f.to_string()),
+                failure.map(|f| f.to_string()),
+            )
+        };
+        add(None);
+        add(None);
+        add(Some(TRANSFORM_FAILED));
+        add(None);
+        merge_row_statuses(&mut bulk_res.items, 1);
+        assert_eq!(bulk_res.items.len(), 2);
+        assert!(bulk_res.items[1]["create"].error.is_some());
+    }
 }
@@ -24,7 +24,11 @@ use chrono::{Duration, Utc};
 use config::{
     meta::{stream::StreamType, usage::UsageType},
     metrics,
-    utils::{flatten, json, time::parse_timestamp_micro_from_value},
+    utils::{
+        flatten::{self, FlattenOptions},
+        json,
+        time::parse_timestamp_micro_from_value,
+    },
     CONFIG, DISTINCT_FIELDS,
 };
 use flate2::read::GzDecoder;
@@ -44,7 +48,7 @@ use crate::{
     service::{
         get_formatted_stream_name,
         ingestion::{check_ingestion_allowed, evaluate_trigger, write_file, TriggerAlertData},
-        logs::StreamMeta,
+        logs::{routing::CrossOrgRecords, StreamMeta},
         metadata::{distinct_values::DvItem, write, MetadataItem, MetadataType},
         schema::{get_upto_discard_error, SchemaCache},
         usage::report_request_usage_stats,
@@ -57,6 +61,29 @@ pub async fn ingest(
     in_req: IngestionRequest<'_>,
     thread_id: usize,
     user_email: &str,
+) -> Result<IngestionResponse> {
+    ingest_records(org_id, in_stream_name, in_req, thread_id, user_email, true).await
+}
+
+/// Ingests the records another org routed to the stream, they are not routed
+/// again.
+pub(crate) async fn ingest_routed(
+    org_id: &str,
+    in_stream_name: &str,
+    in_req: IngestionRequest<'_>,
+    thread_id: usize,
+    user_email: &str,
+) -> Result<IngestionResponse> {
+    ingest_records(org_id, in_stream_name, in_req, thread_id, user_email, false).await
+}
+
+async fn ingest_records(
+    org_id: &str,
+    in_stream_name: &str,
+    in_req: IngestionRequest<'_>,
+    thread_id: usize,
+    user_email: &str,
+    cross_org_routing: bool,
 ) -> Result<IngestionResponse> {
     let start = std::time::Instant::now();
     // check stream
@@ -111,6 +138,35 @@ pub async fn ingest(
     .await;
     let partition_keys = partition_det.partition_keys;
     let partition_time_level = partition_det.partition_time_level;
+    let drop_rules =
+        crate::service::ingestion::get_stream_drop_rules(org_id, &StreamType::Logs, stream_name)
+            .await;
+    let flatten_options = crate::service::ingestion::get_stream_flatten_options(
+        org_id,
+        &StreamType::Logs,
+        stream_name,
+    )
+    .await;
+    let retain_original =
+        crate::service::original::is_enabled(org_id, StreamType::Logs, stream_name).await;
+    let encrypt_original = retain_original
+        && crate::service::original::is_encrypted(org_id, StreamType::Logs, stream_name).await;
+    let lineage = crate::service::lineage::register(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
+
+    let cross_org_routes = if cross_org_routing {
+        super::routing::cross_org_routes(org_id, stream_name).await
+    } else {
+        vec![]
+    };
+    let mut cross_org_records = CrossOrgRecords::default();
 
     let mut write_buf: HashMap<String, SchemaRecords> = HashMap::new();
 
@@ -131,7 +187,16 @@ pub async fn ingest(
         ),
     };
 
-    for ret in data.iter() {
+    // a record can be exploded into one row per element of its arrays
+    let rows = data.iter().flat_map(|ret| match ret {
+        Ok(item) => flatten::explode(item, &flatten_options)
+            .into_iter()
+            .map(Ok)
+            .collect::<Vec<_>>(),
+        Err(e) => vec![Err(e)],
+    });
+
+    for ret in rows {
         let item = match ret {
             Ok(item) => item,
             Err(e) => {
@@ -140,8 +205,35 @@ pub async fn ingest(
             }
         };
 
+        // records routed to another org are ingested with the settings of the target stream
+        if !cross_org_routes.is_empty() {
+            let value = flatten::flatten_with_options(item.clone(), &flatten_options)?;
+            if cross_org_records
+                .route(org_id, stream_name, &cross_org_routes, &value)
+                .await
+            {
+                continue;
+            }
+        }
+
+        // keep the record as received, before the functions change it
+        let original = if retain_original {
+            match crate::service::original::capture(org_id, &item, encrypt_original).await {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    stream_status.status.failed += 1;
+                    stream_status.status.error = e.to_string();
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
         let mut res = match apply_functions(
+            org_id,
             item,
+            &flatten_options,
             &local_trans,
             &stream_vrl_map,
             stream_name,
@@ -159,11 +251,24 @@ pub async fn ingest(
             json::Value::Object(val) => val,
             _ => unreachable!(),
         };
+        if drop_rules.should_drop(&local_val) {
+            continue;
+        }
+        crate::service::ingestion::reserved::protect(
+            &mut local_val,
+            &mut stream_status.status.warnings,
+        );
         if let Err(e) = handle_timestamp(&mut local_val, min_ts) {
             stream_status.status.failed += 1;
             stream_status.status.error = e.to_string();
             continue;
         }
+        if let Some(original) = original {
+            crate::service::original::attach(&mut local_val, original);
+        }
+        if let Some(lineage) = lineage {
+            crate::service::lineage::attach(&mut local_val, lineage);
+        }
 
         let mut to_add_distinct_values = vec![];
         // get distinct_value item
@@ -189,6 +294,7 @@ pub async fn ingest(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                reprocessed: false,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -213,6 +319,22 @@ pub async fn ingest(
         distinct_values.extend(to_add_distinct_values);
     }
 
+    // only the records written to the stream count against its quotas
+    if let Err(e) = crate::service::ingestion::quota::check_pending(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        write_buf.values(),
+    )
+    .await
+    {
+        return Ok(IngestionResponse {
+            code: http::StatusCode::TOO_MANY_REQUESTS.into(),
+            status: vec![],
+            error: Some(e.to_string()),
+        });
+    }
+
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
     let mut req_stats = write_file(&writer, stream_name, write_buf).await;
@@ -271,20 +393,31 @@ pub async fn ingest(
     drop(stream_params);
     drop(stream_alerts_map);
 
-    Ok(IngestionResponse::new(
-        http::StatusCode::OK.into(),
-        vec![stream_status],
-    ))
+    let mut status = vec![stream_status];
+    status.extend(
+        cross_org_records
+            .ingest(org_id, thread_id, user_email)
+            .await,
+    );
+    Ok(IngestionResponse::new(http::StatusCode::OK.into(), status))
 }
 
 pub fn apply_functions<'a>(
+    org_id: &str,
     item: json::Value,
+    flatten_options: &FlattenOptions,
     local_trans: &[StreamTransform],
     stream_vrl_map: &'a HashMap<String, VRLResultResolver>,
     stream_name: &'a str,
     runtime: &mut Runtime,
 ) -> Result<json::Value> {
-    let mut value = flatten::flatten_with_level(item, CONFIG.limit.ingest_flatten_level)?;
+    let mut value = crate::service::key_mappings::flatten(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        item,
+        flatten_options,
+    )?;
 
     if !local_trans.is_empty() {
         value = crate::service::ingestion::apply_stream_functions(
@@ -0,0 +1,477 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Loki push API compatibility, the streams of a push request are written into
+//! log streams with the Loki labels as fields.
+
+use std::collections::{BTreeSet, HashMap};
+
+use actix_web::web;
+use anyhow::Result;
+use config::{
+    meta::stream::{StreamPartition, StreamType},
+    utils::{flatten::format_key, json},
+    RwHashSet, CONFIG,
+};
+use once_cell::sync::Lazy;
+use prost::Message;
+use proto::loki_rpc;
+
+use crate::{
+    common::meta::ingestion::{IngestionRequest, IngestionResponse, StreamStatus},
+    service::format_stream_name,
+};
+
+/// Streams whose partition keys were already checked against the Loki labels.
+static PARTITIONED_STREAMS: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+/// One Loki stream, the labels and the entries sharing them.
+#[derive(Debug, Default, PartialEq)]
+pub struct LokiStream {
+    pub labels: Vec<(String, String)>,
+    /// (unix timestamp in nanoseconds, line, structured metadata)
+    pub entries: Vec<(i64, String, Vec<(String, String)>)>,
+}
+
+/// Decodes a snappy compressed protobuf `PushRequest`.
+pub fn decode_protobuf(body: &[u8]) -> Result<Vec<LokiStream>> {
+    let decoded = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .map_err(|e| anyhow::anyhow!("Invalid snappy compressed data: {}", e))?;
+    let request = loki_rpc::PushRequest::decode(bytes::Bytes::from(decoded))
+        .map_err(|e| anyhow::anyhow!("Invalid protobuf: {}", e))?;
+    request
+        .streams
+        .into_iter()
+        .map(|stream| {
+            let labels = parse_labels(&stream.labels)?;
+            let entries = stream
+                .entries
+                .into_iter()
+                .map(|entry| {
+                    let ts = entry
+                        .timestamp
+                        .map(|t| t.seconds * 1_000_000_000 + t.nanos as i64)
+                        .unwrap_or_default();
+                    let metadata = entry
+                        .structured_metadata
+                        .into_iter()
+                        .map(|p| (p.name, p.value))
+                        .collect();
+                    (ts, entry.line, metadata)
+                })
+                .collect();
+            Ok(LokiStream { labels, entries })
+        })
+        .collect()
+}
+
+/// Decodes a json push request:
+/// `{"streams":[{"stream":{"job":"app"},"values":[["<unix ns>","line",{"trace_id":"abc"}]]}]}`
+pub fn decode_json(body: &[u8]) -> Result<Vec<LokiStream>> {
+    let request: json::Value = json::from_slice(body)?;
+    let Some(streams) = request.get("streams").and_then(|v| v.as_array()) else {
+        return Err(anyhow::anyhow!("streams is required"));
+    };
+    let mut result = Vec::with_capacity(streams.len());
+    for stream in streams {
+        let labels = match stream.get("stream") {
+            Some(json::Value::Object(map)) => map
+                .iter()
+                .map(|(k, v)| (k.to_string(), json_str(v)))
+                .collect(),
+            Some(json::Value::Null) | None => vec![],
+            Some(_) => return Err(anyhow::anyhow!("stream must be an object of labels")),
+        };
+        let mut entries = vec![];
+        let values = stream.get("values").and_then(|v| v.as_array());
+        for value in values.into_iter().flatten() {
+            let Some(value) = value.as_array().filter(|v| v.len() >= 2) else {
+                return Err(anyhow::anyhow!("values must be [timestamp, line] pairs"));
+            };
+            let ts = match &value[0] {
+                json::Value::String(s) => s.parse::<i64>().ok(),
+                json::Value::Number(n) => n.as_i64(),
+                _ => None,
+            }
+            .ok_or_else(|| anyhow::anyhow!("invalid timestamp: {}", value[0]))?;
+            let metadata = match value.get(2) {
+                Some(json::Value::Object(map)) => map
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), json_str(v)))
+                    .collect(),
+                _ => vec![],
+            };
+            entries.push((ts, json_str(&value[1]), metadata));
+        }
+        result.push(LokiStream { labels, entries });
+    }
+    Ok(result)
+}
+
+fn json_str(v: &json::Value) -> String {
+    match v {
+        json::Value::String(s) => s.to_string(),
+        _ => v.to_string(),
+    }
+}
+
+/// Parses labels in the prometheus selector format: `{job="app", env="prod"}`.
+pub fn parse_labels(input: &str) -> Result<Vec<(String, String)>> {
+    let input = input.trim();
+    let inner = input
+        .strip_prefix('{')
+        .and_then(|v| v.strip_suffix('}'))
+        .ok_or_else(|| anyhow::anyhow!("invalid labels: {input}"))?;
+    let mut labels = vec![];
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+            name.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('=') || name.is_empty() {
+            return Err(anyhow::anyhow!("invalid labels: {input}"));
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('"') {
+            return Err(anyhow::anyhow!("invalid labels: {input}"));
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => value.push(c),
+                    None => return Err(anyhow::anyhow!("invalid labels: {input}")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(anyhow::anyhow!("invalid labels: {input}")),
+            }
+        }
+        labels.push((name, value));
+    }
+    Ok(labels)
+}
+
+/// Groups the entries into records per target log stream. The stream name is
+/// taken from the configured stream label, the labels become record fields.
+pub fn to_records(
+    streams: Vec<LokiStream>,
+) -> HashMap<String, (Vec<json::Value>, BTreeSet<String>)> {
+    let mut result: HashMap<String, (Vec<json::Value>, BTreeSet<String>)> = HashMap::new();
+    for stream in streams {
+        let stream_name = stream
+            .labels
+            .iter()
+            .find(|(k, _)| *k == CONFIG.loki.stream_label)
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(&CONFIG.loki.default_stream);
+        let stream_name = format_stream_name(stream_name);
+        let labels = stream
+            .labels
+            .into_iter()
+            .map(|(mut k, v)| {
+                format_key(&mut k);
+                (k, v)
+            })
+            .collect::<Vec<_>>();
+        let (records, label_names) = result.entry(stream_name).or_default();
+        label_names.extend(labels.iter().map(|(k, _)| k.to_string()));
+        for (ts, line, metadata) in stream.entries {
+            let mut record = json::Map::with_capacity(labels.len() + metadata.len() + 2);
+            for (k, v) in metadata {
+                record.insert(k, json::Value::String(v));
+            }
+            for (k, v) in labels.iter() {
+                record.insert(k.to_string(), json::Value::String(v.to_string()));
+            }
+            record.insert(
+                CONFIG.common.column_timestamp.clone(),
+                json::Value::Number((ts / 1000).into()),
+            );
+            record.insert(CONFIG.loki.message_field.clone(), json::Value::String(line));
+            records.push(json::Value::Object(record));
+        }
+    }
+    result
+}
+
+/// Writes the streams of a push request into log streams. The agents retry a
+/// push failing with a retryable status as a whole, so such a failure stops the
+/// push and its status is returned: the streams not written yet are retried,
+/// the written ones are written again. The other failures don't stop the push,
+/// the rest of the streams are written.
+pub async fn push(
+    org_id: &str,
+    streams: Vec<LokiStream>,
+    thread_id: usize,
+    user_email: &str,
+) -> Result<IngestionResponse> {
+    // the memtable is checked for all the streams before any is written
+    if let Err(e) = ingester::check_memtable_size() {
+        return Ok(IngestionResponse {
+            code: actix_web::http::StatusCode::SERVICE_UNAVAILABLE.into(),
+            status: vec![],
+            error: Some(e.to_string()),
+        });
+    }
+
+    let mut status = vec![];
+    let mut failed = vec![];
+    for (stream_name, (records, label_names)) in to_records(streams) {
+        if records.is_empty() {
+            continue;
+        }
+        let body = web::Bytes::from(json::to_vec(&records)?);
+        let resp = super::ingest::ingest(
+            org_id,
+            &stream_name,
+            IngestionRequest::JSON(&body),
+            thread_id,
+            user_email,
+        )
+        .await;
+        let (code, error) = match resp {
+            Ok(resp) if resp.code == actix_web::http::StatusCode::OK.as_u16() => {
+                status.extend(resp.status);
+                set_partition_keys(org_id, &stream_name, &label_names).await;
+                continue;
+            }
+            Ok(resp) => (resp.code, resp.error.unwrap_or_default()),
+            Err(e) => (
+                actix_web::http::StatusCode::INTERNAL_SERVER_ERROR.into(),
+                e.to_string(),
+            ),
+        };
+        log::error!("[LOKI] push to stream {stream_name} error: {error}");
+        failed.push((stream_name, code, error));
+        if is_retryable(code) {
+            break;
+        }
+    }
+    Ok(push_response(status, failed))
+}
+
+/// The statuses the agents retry a push on.
+fn is_retryable(code: u16) -> bool {
+    code == actix_web::http::StatusCode::TOO_MANY_REQUESTS.as_u16() || code >= 500
+}
+
+/// The response of a push, with the status of a retryable failure if there is
+/// one, else the one of the first failure.
+fn push_response(
+    status: Vec<StreamStatus>,
+    failed: Vec<(String, u16, String)>,
+) -> IngestionResponse {
+    let Some(code) = failed
+        .iter()
+        .map(|(_, code, _)| *code)
+        .find(|code| is_retryable(*code))
+        .or_else(|| failed.first().map(|(_, code, _)| *code))
+    else {
+        return IngestionResponse::new(actix_web::http::StatusCode::OK.into(), status);
+    };
+    let failed = failed
+        .iter()
+        .map(|(name, _, error)| format!("{name}: {error}"))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let error = if status.is_empty() {
+        format!("failed streams: {failed}")
+    } else {
+        format!("the other streams were written, failed streams: {failed}")
+    };
+    IngestionResponse {
+        code,
+        status,
+        error: Some(error),
+    }
+}
+
+/// Uses the configured partition labels as partition keys of the streams
+/// which don't have any partition keys yet.
+async fn set_partition_keys(org_id: &str, stream_name: &str, label_names: &BTreeSet<String>) {
+    if CONFIG.loki.partition_labels.is_empty() {
+        return;
+    }
+    let key = format!("{org_id}/{stream_name}");
+    if !PARTITIONED_STREAMS.insert(key.clone()) {
+        return;
+    }
+    let Some(mut settings) =
+        infra::schema::get_settings(org_id, stream_name, StreamType::Logs).await
+    else {
+        // the schema isn't there yet, check again on the next push
+        PARTITIONED_STREAMS.remove(&key);
+        return;
+    };
+    if !settings.partition_keys.is_empty() {
+        return;
+    }
+    settings.partition_keys = CONFIG
+        .loki
+        .partition_labels
+        .split(',')
+        .map(|v| {
+            let mut v = v.trim().to_string();
+            format_key(&mut v);
+            v
+        })
+        .filter(|v| label_names.contains(v))
+        .map(|v| StreamPartition::new(&v))
+        .collect();
+    if settings.partition_keys.is_empty() {
+        // none of the labels were seen yet
+        PARTITIONED_STREAMS.remove(&key);
+        return;
+    }
+    if let Err(e) = crate::service::stream::save_stream_settings(
+        org_id,
+        stream_name,
+        StreamType::Logs,
+        settings,
+        None,
+    )
+    .await
+    {
+        log::error!("[LOKI] set partition keys for stream {stream_name} error: {e}");
+        PARTITIONED_STREAMS.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(
+            parse_labels(r#"{job="app", env = "prod",msg="a \"b\", c"}"#).unwrap(),
+            vec![
+                ("job".to_string(), "app".to_string()),
+                ("env".to_string(), "prod".to_string()),
+                ("msg".to_string(), r#"a "b", c"#.to_string()),
+            ]
+        );
+        assert!(parse_labels("{}").unwrap().is_empty());
+        assert!(parse_labels(r#"job="app""#).is_err());
+        assert!(parse_labels(r#"{job="app}"#).is_err());
+    }
+
+    #[test]
+    fn test_decode_json() {
+        let body = r#"{"streams":[{"stream":{"job":"app"},"values":[["1700000000000000000","hello",{"trace_id":"abc"}],["1700000000000001000","world"]]}]}"#;
+        let streams = decode_json(body.as_bytes()).unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(
+            streams[0].labels,
+            vec![("job".to_string(), "app".to_string())]
+        );
+        assert_eq!(
+            streams[0].entries[0],
+            (
+                1700000000000000000,
+                "hello".to_string(),
+                vec![("trace_id".to_string(), "abc".to_string())]
+            )
+        );
+        assert!(decode_json(br#"{"streams":[{"values":[["x","a"]]}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_decode_protobuf() {
+        let request = loki_rpc::PushRequest {
+            streams: vec![loki_rpc::StreamAdapter {
+                labels: r#"{job="app"}"#.to_string(),
+                entries: vec![loki_rpc::EntryAdapter {
+                    timestamp: Some(loki_rpc::Timestamp {
+                        seconds: 1700000000,
+                        nanos: 5000,
+                    }),
+                    line: "hello".to_string(),
+                    structured_metadata: vec![],
+                }],
+                hash: 0,
+            }],
+        };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+        let streams = decode_protobuf(&body).unwrap();
+        assert_eq!(streams[0].entries[0].0, 1700000000000005000);
+        assert_eq!(streams[0].entries[0].1, "hello");
+    }
+
+    #[test]
+    fn test_to_records() {
+        let streams = vec![
+            LokiStream {
+                labels: vec![("job".to_string(), "app".to_string())],
+                entries: vec![(1700000000000000000, "hello".to_string(), vec![])],
+            },
+            LokiStream {
+                labels: vec![("env".to_string(), "prod".to_string())],
+                entries: vec![(1700000000000000000, "world".to_string(), vec![])],
+            },
+        ];
+        let records = to_records(streams);
+        let (app, labels) = records.get("app").unwrap();
+        assert!(labels.contains("job"));
+        assert_eq!(app[0]["job"], "app");
+        assert_eq!(app[0][&CONFIG.loki.message_field], "hello");
+        assert_eq!(app[0][&CONFIG.common.column_timestamp], 1700000000000000i64);
+        assert!(records.contains_key(&CONFIG.loki.default_stream));
+    }
+
+    #[test]
+    fn test_push_response_mixed_success() {
+        let written = || vec![StreamStatus::new("app")];
+        let failure = |code: u16| ("db".to_string(), code, "error".to_string());
+
+        let resp = push_response(written(), vec![]);
+        assert_eq!(resp.code, 200);
+        assert!(resp.error.is_none());
+
+        // the agents retry the push, the written streams are written again
+        let resp = push_response(written(), vec![failure(429)]);
+        assert_eq!(resp.code, 429);
+        assert_eq!(resp.status.len(), 1);
+        assert!(resp.error.unwrap().contains("db: error"));
+        let resp = push_response(written(), vec![failure(400), failure(503)]);
+        assert_eq!(resp.code, 503);
+
+        // not retried, the agents drop the push
+        let resp = push_response(written(), vec![failure(400)]);
+        assert_eq!(resp.code, 400);
+        assert!(
+            resp.error
+                .unwrap()
+                .starts_with("the other streams were written")
+        );
+
+        assert!(is_retryable(500));
+        assert!(is_retryable(429));
+        assert!(!is_retryable(400));
+    }
+}
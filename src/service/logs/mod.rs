@@ -28,8 +28,11 @@ use config::{
 use infra::schema::unwrap_partition_time_level;
 
 use super::{
-    ingestion::{get_string_value, TriggerAlertData},
+    correlation, encryption,
+    ingestion::{extra, get_string_value, TriggerAlertData},
+    large_fields,
     schema::get_invalid_schema_start_dt,
+    threat_intel,
 };
 use crate::{
     common::meta::{alerts::Alert, ingestion::RecordStatus, stream::SchemaRecords},
@@ -41,9 +44,15 @@ use crate::{
 
 pub mod bulk;
 pub mod ingest;
+pub mod loki;
 pub mod multi;
+pub mod netflow;
 pub mod otlp_grpc;
 pub mod otlp_http;
+pub mod reprocess;
+pub mod routing;
+pub mod simulate;
+pub mod snmp;
 pub mod syslog;
 
 static BULK_OPERATORS: [&str; 3] = ["create", "index", "update"];
@@ -268,6 +277,62 @@ async fn add_valid_record(
         .as_i64()
         .unwrap();
 
+    // give the record an id for point lookups
+    crate::service::record_id::attach(&mut record_val, timestamp);
+
+    // tag the records hitting a threat intel indicator, before the values are encrypted
+    if !stream_meta.reprocessed {
+        threat_intel::tag(
+            &stream_meta.org_id,
+            &stream_meta.stream_name,
+            &mut record_val,
+        );
+    }
+
+    // encrypt sensitive values before they reach the schema and the WAL, and before
+    // the fields past the limit are moved into the extra column
+    let encrypt_fields = encryption::get_encrypt_fields(
+        &stream_meta.org_id,
+        StreamType::Logs,
+        &stream_meta.stream_name,
+    )
+    .await;
+    if let Err(e) =
+        encryption::encrypt_fields(&stream_meta.org_id, &mut record_val, &encrypt_fields).await
+    {
+        status.failed += 1;
+        status.error = e.to_string();
+        return Ok(None);
+    }
+
+    // keep the new keys past the field limit of the stream out of the schema
+    if let Some((max_fields, promoted_fields)) = extra::get_field_limit(
+        &stream_meta.org_id,
+        StreamType::Logs,
+        &stream_meta.stream_name,
+    )
+    .await
+    {
+        if let Some(schema) = stream_schema_map.get(&stream_meta.stream_name) {
+            let moved = extra::route(
+                &mut record_val,
+                schema.fields_map(),
+                max_fields,
+                &promoted_fields,
+            );
+            if !moved.is_empty() {
+                let warning = format!(
+                    "stream reached its limit of {max_fields} fields, [{}] kept in [{}]",
+                    moved.join(", "),
+                    extra::EXTRA_COLUMN
+                );
+                if !status.warnings.contains(&warning) {
+                    status.warnings.push(warning);
+                }
+            }
+        }
+    }
+
     // check schema
     let (schema_evolution, _) = match check_for_schema(
         &stream_meta.org_id,
@@ -362,6 +427,32 @@ async fn add_valid_record(
         // End check for alert trigger
     }
 
+    if !stream_meta.reprocessed {
+        correlation::observe(&stream_meta.org_id, &stream_meta.stream_name, &record_val).await;
+    }
+
+    // move oversized values out of the row
+    let max_field_size = large_fields::get_max_field_size(
+        &stream_meta.org_id,
+        StreamType::Logs,
+        &stream_meta.stream_name,
+    )
+    .await;
+    if let Err(e) = large_fields::offload(
+        &stream_meta.org_id,
+        StreamType::Logs,
+        &stream_meta.stream_name,
+        timestamp,
+        &mut record_val,
+        max_field_size,
+    )
+    .await
+    {
+        status.failed += 1;
+        status.error = e.to_string();
+        return Ok(None);
+    }
+
     let hour_buf = write_buf.entry(hour_key).or_insert_with(|| {
         let schema = Arc::new(rec_schema.schema().clone().with_metadata(HashMap::new()));
         let schema_key = schema.hash_key();
@@ -445,6 +536,9 @@ struct StreamMeta<'a> {
     partition_keys: &'a Vec<StreamPartition>,
     partition_time_level: &'a Option<PartitionTimeLevel>,
     stream_alerts_map: &'a HashMap<String, Vec<Alert>>,
+    /// The records were ingested before, the threat intel and the correlation
+    /// rules already saw them
+    reprocessed: bool,
 }
 
 pub fn refactor_map(original_map: &mut Map<String, Value>, defined_schema_keys: &[String]) {
@@ -93,6 +93,15 @@ async fn ingest_inner(
         stream_name,
     );
     // End Register Transforms for stream
+    let lineage = crate::service::lineage::register(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
 
     let partition_det = crate::service::ingestion::get_stream_partition_keys(
         org_id,
@@ -102,6 +111,15 @@ async fn ingest_inner(
     .await;
     let partition_keys = partition_det.partition_keys;
     let partition_time_level = partition_det.partition_time_level;
+    let drop_rules =
+        crate::service::ingestion::get_stream_drop_rules(org_id, &StreamType::Logs, stream_name)
+            .await;
+    let flatten_options = crate::service::ingestion::get_stream_flatten_options(
+        org_id,
+        &StreamType::Logs,
+        stream_name,
+    )
+    .await;
 
     // Start get stream alerts
     crate::service::ingestion::get_stream_alerts(
@@ -117,20 +135,35 @@ async fn ingest_inner(
 
     let mut buf: HashMap<String, SchemaRecords> = HashMap::new();
     let reader = BufReader::new(body.as_ref());
-    for line in reader.lines() {
-        let line = line?;
-        if line.is_empty() {
-            continue;
-        }
-
-        let mut value: json::Value = json::from_slice(line.as_bytes())?;
-
+    // a line can be exploded into one row per element of its arrays
+    let rows = reader.lines().flat_map(|line| -> Vec<Result<json::Value>> {
+        let line = match line {
+            Ok(line) if line.is_empty() => return vec![],
+            Ok(line) => line,
+            Err(e) => return vec![Err(e.into())],
+        };
+        let mut value: json::Value = match json::from_slice(line.as_bytes()) {
+            Ok(value) => value,
+            Err(e) => return vec![Err(e.into())],
+        };
         for (key, val) in extend_json.iter() {
             value[key] = val.clone();
         }
+        flatten::explode(value, &flatten_options)
+            .into_iter()
+            .map(Ok)
+            .collect()
+    });
 
+    for value in rows {
         // JSON Flattening
-        value = flatten::flatten_with_level(value, CONFIG.limit.ingest_flatten_level)?;
+        let mut value = crate::service::key_mappings::flatten(
+            org_id,
+            StreamType::Logs,
+            stream_name,
+            value?,
+            &flatten_options,
+        )?;
         // Start row based transform
 
         if !local_trans.is_empty() {
@@ -154,6 +187,14 @@ async fn ingest_inner(
             json::Value::Object(v) => v,
             _ => unreachable!(),
         };
+        if drop_rules.should_drop(&local_val) {
+            continue;
+        }
+
+        crate::service::ingestion::reserved::protect(
+            &mut local_val,
+            &mut stream_status.status.warnings,
+        );
 
         // handle timestamp
         let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
@@ -177,6 +218,9 @@ async fn ingest_inner(
             CONFIG.common.column_timestamp.clone(),
             json::Value::Number(timestamp.into()),
         );
+        if let Some(lineage) = lineage {
+            crate::service::lineage::attach(&mut local_val, lineage);
+        }
 
         let mut to_add_distinct_values = vec![];
         // get distinct_value item
@@ -203,6 +247,7 @@ async fn ingest_inner(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                reprocessed: false,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -227,6 +272,22 @@ async fn ingest_inner(
         distinct_values.extend(to_add_distinct_values);
     }
 
+    // only the records written to the stream count against its quotas
+    if let Err(e) = crate::service::ingestion::quota::check_pending(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        buf.values(),
+    )
+    .await
+    {
+        return Ok(IngestionResponse {
+            code: http::StatusCode::TOO_MANY_REQUESTS.into(),
+            status: vec![],
+            error: Some(e.to_string()),
+        });
+    }
+
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
     let mut req_stats = write_file(&writer, stream_name, buf).await;
@@ -0,0 +1,574 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use actix_web::web;
+use anyhow::Result;
+use chrono::Utc;
+use config::{utils::json, RwHashMap, CONFIG};
+use once_cell::sync::Lazy;
+
+use crate::common::meta::ingestion::{IngestionRequest, IngestionResponse};
+
+const NETFLOW_V5: u16 = 5;
+const NETFLOW_V9: u16 = 9;
+const IPFIX: u16 = 10;
+
+const V5_HEADER_LEN: usize = 24;
+const V5_RECORD_LEN: usize = 48;
+const V9_HEADER_LEN: usize = 20;
+const IPFIX_HEADER_LEN: usize = 16;
+
+const V9_TEMPLATE_SET: u16 = 0;
+const V9_OPTIONS_TEMPLATE_SET: u16 = 1;
+const IPFIX_TEMPLATE_SET: u16 = 2;
+const IPFIX_OPTIONS_TEMPLATE_SET: u16 = 3;
+const MIN_DATA_SET_ID: u16 = 256;
+
+const IPFIX_VARIABLE_LENGTH: u16 = 65535;
+const IPFIX_ENTERPRISE_BIT: u16 = 0x8000;
+
+/// Templates announced by exporters, keyed by `{exporter}/{domain}/{template_id}`.
+static TEMPLATES: Lazy<RwHashMap<String, Template>> = Lazy::new(Default::default);
+
+#[derive(Clone, Debug, PartialEq)]
+struct TemplateField {
+    id: u16,
+    length: u16,
+    enterprise: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+struct Template {
+    fields: Vec<TemplateField>,
+    updated_at: i64,
+}
+
+/// Decodes one UDP datagram and ingests the expanded flow records into the
+/// configured netflow stream.
+pub async fn ingest(packet: &[u8], addr: SocketAddr) -> Result<IngestionResponse> {
+    let records = decode(packet, addr.ip())?;
+    if records.is_empty() {
+        // template-only packets don't carry any flows
+        return Ok(IngestionResponse::new(
+            actix_web::http::StatusCode::OK.into(),
+            vec![],
+        ));
+    }
+    let body = web::Bytes::from(json::to_vec(&records)?);
+    super::ingest::ingest(
+        &CONFIG.netflow.org_id,
+        &CONFIG.netflow.stream_name,
+        IngestionRequest::JSON(&body),
+        0,
+        "",
+    )
+    .await
+}
+
+/// Decodes a NetFlow v5, v9 or IPFIX packet into flat json records.
+pub fn decode(packet: &[u8], exporter: IpAddr) -> Result<Vec<json::Value>> {
+    let version = read_u16(packet, 0).ok_or_else(|| anyhow::anyhow!("packet too short"))?;
+    match version {
+        NETFLOW_V5 => decode_v5(packet, exporter),
+        NETFLOW_V9 => decode_v9(packet, exporter),
+        IPFIX => decode_ipfix(packet, exporter),
+        _ => Err(anyhow::anyhow!("unsupported netflow version: {version}")),
+    }
+}
+
+fn decode_v5(packet: &[u8], exporter: IpAddr) -> Result<Vec<json::Value>> {
+    if packet.len() < V5_HEADER_LEN {
+        return Err(anyhow::anyhow!("netflow v5 header too short"));
+    }
+    let count = read_u16(packet, 2).unwrap() as usize;
+    let sys_uptime = read_u32(packet, 4).unwrap() as i64;
+    let unix_secs = read_u32(packet, 8).unwrap() as i64;
+    let unix_nsecs = read_u32(packet, 12).unwrap() as i64;
+    let engine_type = packet[20];
+    let engine_id = packet[21];
+    let sampling_interval = read_u16(packet, 22).unwrap() & 0x3fff;
+    if packet.len() < V5_HEADER_LEN + count * V5_RECORD_LEN {
+        return Err(anyhow::anyhow!(
+            "netflow v5 packet truncated, expected {count} records"
+        ));
+    }
+
+    // boot time of the exporter in milliseconds, used to convert the uptime based
+    // first/last switched values into wall clock time
+    let boot_ms = unix_secs * 1000 + unix_nsecs / 1_000_000 - sys_uptime;
+    let mut records = Vec::with_capacity(count);
+    for i in 0..count {
+        let rec = &packet[V5_HEADER_LEN + i * V5_RECORD_LEN..][..V5_RECORD_LEN];
+        let first = boot_ms + read_u32(rec, 24).unwrap() as i64;
+        let last = boot_ms + read_u32(rec, 28).unwrap() as i64;
+        let mut val = json::Map::new();
+        val.insert("flow_version".to_string(), NETFLOW_V5.into());
+        val.insert("exporter".to_string(), exporter.to_string().into());
+        val.insert("engine_type".to_string(), engine_type.into());
+        val.insert("engine_id".to_string(), engine_id.into());
+        val.insert("sampling_interval".to_string(), sampling_interval.into());
+        val.insert("src_addr".to_string(), ipv4(&rec[0..4]).into());
+        val.insert("dst_addr".to_string(), ipv4(&rec[4..8]).into());
+        val.insert("next_hop".to_string(), ipv4(&rec[8..12]).into());
+        val.insert("input_snmp".to_string(), read_u16(rec, 12).unwrap().into());
+        val.insert("output_snmp".to_string(), read_u16(rec, 14).unwrap().into());
+        val.insert("packets".to_string(), read_u32(rec, 16).unwrap().into());
+        val.insert("bytes".to_string(), read_u32(rec, 20).unwrap().into());
+        val.insert("first_switched".to_string(), (first * 1000).into());
+        val.insert("last_switched".to_string(), (last * 1000).into());
+        val.insert("src_port".to_string(), read_u16(rec, 32).unwrap().into());
+        val.insert("dst_port".to_string(), read_u16(rec, 34).unwrap().into());
+        val.insert("tcp_flags".to_string(), rec[37].into());
+        val.insert("protocol".to_string(), rec[38].into());
+        val.insert("tos".to_string(), rec[39].into());
+        val.insert("src_as".to_string(), read_u16(rec, 40).unwrap().into());
+        val.insert("dst_as".to_string(), read_u16(rec, 42).unwrap().into());
+        val.insert("src_mask".to_string(), rec[44].into());
+        val.insert("dst_mask".to_string(), rec[45].into());
+        val.insert(CONFIG.common.column_timestamp.clone(), (last * 1000).into());
+        records.push(json::Value::Object(val));
+    }
+    Ok(records)
+}
+
+fn decode_v9(packet: &[u8], exporter: IpAddr) -> Result<Vec<json::Value>> {
+    if packet.len() < V9_HEADER_LEN {
+        return Err(anyhow::anyhow!("netflow v9 header too short"));
+    }
+    let unix_secs = read_u32(packet, 8).unwrap() as i64;
+    let source_id = read_u32(packet, 16).unwrap();
+    decode_sets(
+        &packet[V9_HEADER_LEN..],
+        NETFLOW_V9,
+        exporter,
+        source_id,
+        unix_secs,
+    )
+}
+
+fn decode_ipfix(packet: &[u8], exporter: IpAddr) -> Result<Vec<json::Value>> {
+    if packet.len() < IPFIX_HEADER_LEN {
+        return Err(anyhow::anyhow!("ipfix header too short"));
+    }
+    let length = (read_u16(packet, 2).unwrap() as usize).clamp(IPFIX_HEADER_LEN, packet.len());
+    let export_time = read_u32(packet, 4).unwrap() as i64;
+    let domain_id = read_u32(packet, 12).unwrap();
+    decode_sets(
+        &packet[IPFIX_HEADER_LEN..length],
+        IPFIX,
+        exporter,
+        domain_id,
+        export_time,
+    )
+}
+
+/// Walks the flowsets (v9) or sets (IPFIX) of a packet, caching templates and
+/// expanding data records with the templates seen so far.
+fn decode_sets(
+    mut buf: &[u8],
+    version: u16,
+    exporter: IpAddr,
+    domain_id: u32,
+    export_secs: i64,
+) -> Result<Vec<json::Value>> {
+    let (template_set, options_set) = if version == IPFIX {
+        (IPFIX_TEMPLATE_SET, IPFIX_OPTIONS_TEMPLATE_SET)
+    } else {
+        (V9_TEMPLATE_SET, V9_OPTIONS_TEMPLATE_SET)
+    };
+    let mut records = Vec::new();
+    while buf.len() >= 4 {
+        let set_id = read_u16(buf, 0).unwrap();
+        let set_len = read_u16(buf, 2).unwrap() as usize;
+        if set_len < 4 || set_len > buf.len() {
+            return Err(anyhow::anyhow!("invalid flowset length: {set_len}"));
+        }
+        let body = &buf[4..set_len];
+        if set_id == template_set {
+            parse_templates(body, version, exporter, domain_id)?;
+        } else if set_id == options_set {
+            // options templates describe exporter metadata, not flows
+        } else if set_id >= MIN_DATA_SET_ID {
+            let key = template_key(exporter, domain_id, set_id);
+            match TEMPLATES.get(&key) {
+                Some(template) => {
+                    decode_data_set(
+                        body,
+                        &template.fields,
+                        version,
+                        exporter,
+                        export_secs,
+                        &mut records,
+                    );
+                }
+                None => {
+                    log::debug!("[NETFLOW] no template {key} for data set, dropping it");
+                }
+            }
+        }
+        buf = &buf[set_len..];
+    }
+    Ok(records)
+}
+
+fn parse_templates(mut buf: &[u8], version: u16, exporter: IpAddr, domain_id: u32) -> Result<()> {
+    let now = Utc::now().timestamp();
+    while buf.len() >= 4 {
+        let template_id = read_u16(buf, 0).unwrap();
+        let field_count = read_u16(buf, 2).unwrap() as usize;
+        if template_id < MIN_DATA_SET_ID {
+            // padding at the end of the set
+            break;
+        }
+        let mut offset = 4;
+        let mut fields = Vec::with_capacity(field_count);
+        for _ in 0..field_count {
+            let (Some(id), Some(length)) = (read_u16(buf, offset), read_u16(buf, offset + 2))
+            else {
+                return Err(anyhow::anyhow!("template {template_id} truncated"));
+            };
+            offset += 4;
+            let mut field = TemplateField {
+                id,
+                length,
+                enterprise: None,
+            };
+            if version == IPFIX && id & IPFIX_ENTERPRISE_BIT != 0 {
+                let Some(pen) = read_u32(buf, offset) else {
+                    return Err(anyhow::anyhow!("template {template_id} truncated"));
+                };
+                offset += 4;
+                field.id = id & !IPFIX_ENTERPRISE_BIT;
+                field.enterprise = Some(pen);
+            }
+            fields.push(field);
+        }
+        let key = template_key(exporter, domain_id, template_id);
+        let template = Template {
+            fields,
+            updated_at: now,
+        };
+        if !insert_template(key.clone(), template, CONFIG.netflow.max_templates) {
+            log::warn!("[NETFLOW] template cache is full, dropping template {key}");
+        }
+        buf = &buf[offset..];
+    }
+    Ok(())
+}
+
+fn decode_data_set(
+    mut buf: &[u8],
+    fields: &[TemplateField],
+    version: u16,
+    exporter: IpAddr,
+    export_secs: i64,
+    records: &mut Vec<json::Value>,
+) {
+    let min_len: usize = fields
+        .iter()
+        .map(|f| {
+            if f.length == IPFIX_VARIABLE_LENGTH {
+                1
+            } else {
+                f.length as usize
+            }
+        })
+        .sum();
+    if min_len == 0 {
+        return;
+    }
+    // anything shorter than one record is padding
+    while buf.len() >= min_len {
+        let mut val = json::Map::new();
+        val.insert("flow_version".to_string(), version.into());
+        val.insert("exporter".to_string(), exporter.to_string().into());
+        let mut offset = 0;
+        for field in fields {
+            let mut length = field.length as usize;
+            if field.length == IPFIX_VARIABLE_LENGTH {
+                let Some(&short_len) = buf.get(offset) else {
+                    return;
+                };
+                offset += 1;
+                length = short_len as usize;
+                if short_len == 255 {
+                    let Some(long_len) = read_u16(buf, offset) else {
+                        return;
+                    };
+                    offset += 2;
+                    length = long_len as usize;
+                }
+            }
+            let Some(data) = buf.get(offset..offset + length) else {
+                return;
+            };
+            offset += length;
+            val.insert(field_name(field), field_value(field, data));
+        }
+        val.insert(
+            CONFIG.common.column_timestamp.clone(),
+            flow_timestamp(&val, export_secs).into(),
+        );
+        records.push(json::Value::Object(val));
+        buf = &buf[offset..];
+    }
+}
+
+/// Evicts templates that haven't been refreshed by their exporter within the
+/// configured ttl.
+pub fn clean_templates() {
+    let expired = Utc::now().timestamp() - CONFIG.netflow.template_ttl;
+    TEMPLATES.retain(|_, t| t.updated_at >= expired);
+}
+
+/// Caches a template unless the cache already holds `max` others, in which
+/// case the expired ones are evicted first. Refreshing a cached template is
+/// always accepted.
+fn insert_template(key: String, template: Template, max: usize) -> bool {
+    if !TEMPLATES.contains_key(&key) && TEMPLATES.len() >= max {
+        clean_templates();
+        if TEMPLATES.len() >= max {
+            return false;
+        }
+    }
+    TEMPLATES.insert(key, template);
+    true
+}
+
+fn template_key(exporter: IpAddr, domain_id: u32, template_id: u16) -> String {
+    format!("{exporter}/{domain_id}/{template_id}")
+}
+
+/// Picks the end of the flow as the record timestamp when the exporter sends
+/// absolute times, otherwise falls back to the packet export time.
+fn flow_timestamp(val: &json::Map<String, json::Value>, export_secs: i64) -> i64 {
+    if let Some(v) = val.get("flow_end_ms").and_then(|v| v.as_i64()) {
+        return v * 1000;
+    }
+    if let Some(v) = val.get("flow_end_seconds").and_then(|v| v.as_i64()) {
+        return v * 1_000_000;
+    }
+    if export_secs > 0 {
+        export_secs * 1_000_000
+    } else {
+        Utc::now().timestamp_micros()
+    }
+}
+
+fn field_name(field: &TemplateField) -> String {
+    if let Some(pen) = field.enterprise {
+        return format!("field_{pen}_{}", field.id);
+    }
+    let name = match field.id {
+        1 => "bytes",
+        2 => "packets",
+        3 => "flows",
+        4 => "protocol",
+        5 => "tos",
+        6 => "tcp_flags",
+        7 => "src_port",
+        8 => "src_addr",
+        9 => "src_mask",
+        10 => "input_snmp",
+        11 => "dst_port",
+        12 => "dst_addr",
+        13 => "dst_mask",
+        14 => "output_snmp",
+        15 => "next_hop",
+        16 => "src_as",
+        17 => "dst_as",
+        18 => "bgp_next_hop",
+        21 => "last_switched",
+        22 => "first_switched",
+        27 => "src_addr_v6",
+        28 => "dst_addr_v6",
+        29 => "src_mask_v6",
+        30 => "dst_mask_v6",
+        31 => "flow_label_v6",
+        32 => "icmp_type",
+        56 => "src_mac",
+        57 => "dst_mac",
+        58 => "src_vlan",
+        59 => "dst_vlan",
+        60 => "ip_version",
+        61 => "direction",
+        62 => "next_hop_v6",
+        136 => "flow_end_reason",
+        150 => "flow_start_seconds",
+        151 => "flow_end_seconds",
+        152 => "flow_start_ms",
+        153 => "flow_end_ms",
+        _ => return format!("field_{}", field.id),
+    };
+    name.to_string()
+}
+
+fn field_value(field: &TemplateField, data: &[u8]) -> json::Value {
+    if field.enterprise.is_none() {
+        match (field.id, data.len()) {
+            (8 | 12 | 15 | 18, 4) => return ipv4(data).into(),
+            (27 | 28 | 62, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                return Ipv6Addr::from(octets).to_string().into();
+            }
+            (56 | 57, 6) => {
+                return data
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<Vec<_>>()
+                    .join(":")
+                    .into();
+            }
+            _ => {}
+        }
+    }
+    if !data.is_empty() && data.len() <= 8 {
+        let v = data.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        v.into()
+    } else {
+        hex::encode(data).into()
+    }
+}
+
+fn ipv4(data: &[u8]) -> String {
+    Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string()
+}
+
+#[inline]
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    buf.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+#[inline]
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exporter() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))
+    }
+
+    #[test]
+    fn test_decode_v5() {
+        let mut packet = vec![0u8; V5_HEADER_LEN + V5_RECORD_LEN];
+        packet[0..2].copy_from_slice(&5u16.to_be_bytes());
+        packet[2..4].copy_from_slice(&1u16.to_be_bytes());
+        packet[4..8].copy_from_slice(&10_000u32.to_be_bytes()); // uptime ms
+        packet[8..12].copy_from_slice(&1_700_000_000u32.to_be_bytes());
+        let rec = &mut packet[V5_HEADER_LEN..];
+        rec[0..4].copy_from_slice(&[192, 168, 1, 10]);
+        rec[4..8].copy_from_slice(&[192, 168, 1, 20]);
+        rec[16..20].copy_from_slice(&3u32.to_be_bytes());
+        rec[20..24].copy_from_slice(&1500u32.to_be_bytes());
+        rec[24..28].copy_from_slice(&9_000u32.to_be_bytes());
+        rec[28..32].copy_from_slice(&9_500u32.to_be_bytes());
+        rec[32..34].copy_from_slice(&443u16.to_be_bytes());
+        rec[34..36].copy_from_slice(&51234u16.to_be_bytes());
+        rec[38] = 6;
+
+        let records = decode(&packet, exporter()).unwrap();
+        assert_eq!(records.len(), 1);
+        let rec = records[0].as_object().unwrap();
+        assert_eq!(rec["src_addr"], "192.168.1.10");
+        assert_eq!(rec["dst_addr"], "192.168.1.20");
+        assert_eq!(rec["bytes"], 1500);
+        assert_eq!(rec["src_port"], 443);
+        assert_eq!(rec["protocol"], 6);
+        // boot = 1_700_000_000_000 - 10_000, last = boot + 9_500
+        assert_eq!(rec["last_switched"], (1_700_000_000_000i64 - 500) * 1000);
+    }
+
+    #[test]
+    fn test_decode_v9_with_template() {
+        let exporter = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 9));
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&9u16.to_be_bytes());
+        packet.extend_from_slice(&2u16.to_be_bytes());
+        packet.extend_from_slice(&0u32.to_be_bytes());
+        packet.extend_from_slice(&1_700_000_000u32.to_be_bytes());
+        packet.extend_from_slice(&1u32.to_be_bytes());
+        packet.extend_from_slice(&7u32.to_be_bytes());
+        // template flowset: id 300 with src_addr(8,4), dst_port(11,2), bytes(1,4)
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&20u16.to_be_bytes());
+        packet.extend_from_slice(&300u16.to_be_bytes());
+        packet.extend_from_slice(&3u16.to_be_bytes());
+        for (id, len) in [(8u16, 4u16), (11, 2), (1, 4)] {
+            packet.extend_from_slice(&id.to_be_bytes());
+            packet.extend_from_slice(&len.to_be_bytes());
+        }
+        // data flowset with two records and 4 bytes of padding
+        packet.extend_from_slice(&300u16.to_be_bytes());
+        packet.extend_from_slice(&(4u16 + 20 + 4).to_be_bytes());
+        for (ip, port, bytes) in [([10, 1, 1, 1], 53u16, 80u32), ([10, 1, 1, 2], 80, 1200)] {
+            packet.extend_from_slice(&ip);
+            packet.extend_from_slice(&port.to_be_bytes());
+            packet.extend_from_slice(&bytes.to_be_bytes());
+        }
+        packet.extend_from_slice(&[0u8; 4]);
+
+        let records = decode(&packet, exporter).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["src_addr"], "10.1.1.1");
+        assert_eq!(records[0]["dst_port"], 53);
+        assert_eq!(records[1]["bytes"], 1200);
+        assert_eq!(records[1]["flow_version"], 9);
+        assert_eq!(records[1]["_timestamp"], 1_700_000_000_000_000i64);
+    }
+
+    #[test]
+    fn test_decode_ipfix_data_without_template() {
+        let exporter = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 10));
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&10u16.to_be_bytes());
+        packet.extend_from_slice(&(16u16 + 8).to_be_bytes());
+        packet.extend_from_slice(&1_700_000_000u32.to_be_bytes());
+        packet.extend_from_slice(&1u32.to_be_bytes());
+        packet.extend_from_slice(&1u32.to_be_bytes());
+        packet.extend_from_slice(&400u16.to_be_bytes());
+        packet.extend_from_slice(&8u16.to_be_bytes());
+        packet.extend_from_slice(&[1, 2, 3, 4]);
+        assert!(decode(&packet, exporter).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_unsupported_version() {
+        assert!(decode(&[0, 1, 0, 0], exporter()).is_err());
+        assert!(decode(&[0], exporter()).is_err());
+    }
+
+    #[test]
+    fn test_insert_template_when_full() {
+        let template = Template {
+            fields: vec![],
+            updated_at: Utc::now().timestamp(),
+        };
+        let key = template_key(exporter(), 7, 400);
+        assert!(!insert_template(key.clone(), template.clone(), 0));
+        assert!(!TEMPLATES.contains_key(&key));
+        assert!(insert_template(key.clone(), template.clone(), usize::MAX));
+        // a refresh of a cached template is accepted even when full
+        assert!(insert_template(key.clone(), template, 0));
+    }
+}
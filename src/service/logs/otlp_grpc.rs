@@ -32,7 +32,7 @@ use opentelemetry_proto::tonic::collector::logs::v1::{
 };
 use prost::Message;
 
-use super::StreamMeta;
+use super::{routing::CrossOrgRecords, StreamMeta};
 use crate::{
     common::meta::{
         alerts::Alert,
@@ -72,6 +72,7 @@ pub async fn usage_ingest(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -127,6 +128,11 @@ pub async fn usage_ingest(
             }
         };
 
+        crate::service::ingestion::reserved::protect(
+            &mut local_val,
+            &mut stream_status.status.warnings,
+        );
+
         // handle timestamp
         let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
             Some(v) => match parse_timestamp_micro_from_value(v) {
@@ -174,6 +180,7 @@ pub async fn usage_ingest(
                 partition_keys: &partition_keys,
                 partition_time_level: &partition_time_level,
                 stream_alerts_map: &stream_alerts_map,
+                reprocessed: false,
             },
             &mut stream_schema_map,
             &mut stream_status.status,
@@ -259,6 +266,7 @@ pub async fn handle_grpc_request(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -294,8 +302,18 @@ pub async fn handle_grpc_request(
     };
 
     let stream_name = &stream_name;
+    if db::compact::rename::is_renaming(org_id, StreamType::Logs, stream_name) {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                format!("stream [{stream_name}] is being renamed"),
+            )),
+        );
+    }
 
     let mut runtime = crate::service::ingestion::init_functions_runtime();
+    let cross_org_routes = super::routing::cross_org_routes(org_id, stream_name).await;
+    let mut cross_org_records = CrossOrgRecords::default();
     let mut stream_alerts_map: HashMap<String, Vec<Alert>> = HashMap::new();
     let mut stream_status = StreamStatus::new(stream_name);
     let mut distinct_values = Vec::with_capacity(16);
@@ -308,6 +326,11 @@ pub async fn handle_grpc_request(
     .await;
     let partition_keys = partition_det.partition_keys;
     let partition_time_level = partition_det.partition_time_level;
+    let drop_rules =
+        crate::service::ingestion::get_stream_drop_rules(org_id, &StreamType::Logs, stream_name)
+            .await;
+    let otlp_attributes =
+        crate::service::ingestion::get_stream_otlp_attributes(org_id, stream_name).await;
 
     // Start get stream alerts
     crate::service::ingestion::get_stream_alerts(
@@ -328,6 +351,15 @@ pub async fn handle_grpc_request(
         stream_name,
     );
     // End Register Transforms for stream
+    let lineage = crate::service::lineage::register(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
 
     let mut trigger: Option<TriggerAlertData> = None;
 
@@ -337,18 +369,35 @@ pub async fn handle_grpc_request(
         for instrumentation_logs in &resource_log.scope_logs {
             for log_record in &instrumentation_logs.log_records {
                 let mut rec = json::json!({});
+                let mut nested_resource = json::Map::new();
+                let mut nested_scope = json::Map::new();
 
                 match &resource_log.resource {
                     Some(res) => {
                         for item in &res.attributes {
-                            rec[item.key.as_str()] =
-                                get_val_with_type_retained(&item.value.as_ref());
+                            let value = get_val_with_type_retained(&item.value.as_ref());
+                            if otlp_attributes.is_promoted(&item.key) {
+                                rec[item.key.as_str()] = value;
+                            } else {
+                                nested_resource.insert(item.key.clone(), value);
+                            }
                         }
                     }
                     None => {}
                 }
                 match &instrumentation_logs.scope {
                     Some(lib) => {
+                        // the scope attributes are only kept when the stream nests the attributes
+                        if !otlp_attributes.nested_column.is_empty() {
+                            for item in &lib.attributes {
+                                let value = get_val_with_type_retained(&item.value.as_ref());
+                                if otlp_attributes.is_promoted(&item.key) {
+                                    rec[item.key.as_str()] = value;
+                                } else {
+                                    nested_scope.insert(item.key.clone(), value);
+                                }
+                            }
+                        }
                         let library_name = lib.name.to_owned();
                         if !library_name.is_empty() {
                             rec["instrumentation_library_name"] =
@@ -387,6 +436,11 @@ pub async fn handle_grpc_request(
                     rec[item.key.as_str()] = get_val_with_type_retained(&item.value.as_ref());
                 }
                 rec["dropped_attributes_count"] = log_record.dropped_attributes_count.into();
+                if let Some(nested) =
+                    crate::service::ingestion::nested_otlp_attributes(nested_resource, nested_scope)
+                {
+                    rec[otlp_attributes.nested_column.as_str()] = nested;
+                }
                 match TraceId::from_bytes(
                     log_record
                         .trace_id
@@ -418,6 +472,16 @@ pub async fn handle_grpc_request(
                 // flattening
                 rec = flatten::flatten_with_level(rec, CONFIG.limit.ingest_flatten_level)?;
 
+                // records routed to another org are ingested with the settings of the target
+                // stream
+                if !cross_org_routes.is_empty()
+                    && cross_org_records
+                        .route(org_id, stream_name, &cross_org_routes, &rec)
+                        .await
+                {
+                    continue;
+                }
+
                 if !local_trans.is_empty() {
                     rec = crate::service::ingestion::apply_stream_functions(
                         &local_trans,
@@ -429,10 +493,16 @@ pub async fn handle_grpc_request(
                 }
 
                 // get json object
-                let local_val = match rec.take() {
+                let mut local_val = match rec.take() {
                     json::Value::Object(v) => v,
                     _ => unreachable!(),
                 };
+                if drop_rules.should_drop(&local_val) {
+                    continue;
+                }
+                if let Some(lineage) = lineage {
+                    crate::service::lineage::attach(&mut local_val, lineage);
+                }
 
                 let mut to_add_distinct_values = vec![];
                 // get distinct_value item
@@ -458,6 +528,7 @@ pub async fn handle_grpc_request(
                         partition_keys: &partition_keys,
                         partition_time_level: &partition_time_level,
                         stream_alerts_map: &stream_alerts_map,
+                        reprocessed: false,
                     },
                     &mut stream_schema_map,
                     &mut stream_status.status,
@@ -482,6 +553,23 @@ pub async fn handle_grpc_request(
         }
     }
 
+    // only the records written to the stream count against its quotas
+    if let Err(e) = crate::service::ingestion::quota::check_pending(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        data_buf.values(),
+    )
+    .await
+    {
+        return Ok(
+            HttpResponse::TooManyRequests().json(MetaHttpResponse::error(
+                http::StatusCode::TOO_MANY_REQUESTS.into(),
+                e.to_string(),
+            )),
+        );
+    }
+
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
     let mut req_stats = write_file(&writer, stream_name, data_buf).await;
@@ -537,6 +625,12 @@ pub async fn handle_grpc_request(
         0,
     )
     .await;
+
+    // records routed to other orgs are ingested as a request of the target org
+    cross_org_records
+        .ingest(org_id, thread_id, user_email)
+        .await;
+
     let res = ExportLogsServiceResponse {
         partial_success: None,
     };
@@ -31,7 +31,7 @@ use opentelemetry_proto::tonic::collector::logs::v1::{
 };
 use prost::Message;
 
-use super::StreamMeta;
+use super::{routing::CrossOrgRecords, StreamMeta};
 use crate::{
     common::meta::{
         alerts::Alert,
@@ -104,6 +104,7 @@ pub async fn logs_json_handler(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -139,7 +140,17 @@ pub async fn logs_json_handler(
     };
 
     let stream_name = &stream_name;
+    if db::compact::rename::is_renaming(org_id, StreamType::Logs, stream_name) {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                format!("stream [{stream_name}] is being renamed"),
+            )),
+        );
+    }
     let mut runtime = crate::service::ingestion::init_functions_runtime();
+    let cross_org_routes = super::routing::cross_org_routes(org_id, stream_name).await;
+    let mut cross_org_records = CrossOrgRecords::default();
 
     let mut stream_alerts_map: HashMap<String, Vec<Alert>> = HashMap::new();
     let mut distinct_values = Vec::with_capacity(16);
@@ -157,6 +168,11 @@ pub async fn logs_json_handler(
     .await;
     let partition_keys = partition_det.partition_keys;
     let partition_time_level = partition_det.partition_time_level;
+    let drop_rules =
+        crate::service::ingestion::get_stream_drop_rules(org_id, &StreamType::Logs, stream_name)
+            .await;
+    let otlp_attributes =
+        crate::service::ingestion::get_stream_otlp_attributes(org_id, stream_name).await;
 
     // Start get stream alerts
     crate::service::ingestion::get_stream_alerts(
@@ -177,6 +193,15 @@ pub async fn logs_json_handler(
         stream_name,
     );
     // End Register Transforms for stream
+    let lineage = crate::service::lineage::register(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
 
     let mut buf: HashMap<String, SchemaRecords> = HashMap::new();
 
@@ -221,13 +246,20 @@ pub async fn logs_json_handler(
 
     for res_log in logs.iter() {
         let mut service_att_map: json::Map<String, json::Value> = json::Map::new();
+        let mut nested_resource = json::Map::new();
         if res_log.get("resource").is_some() {
             let resource = res_log.get("resource").unwrap().as_object().unwrap();
             if resource.get("attributes").is_some() {
                 let attributes = resource.get("attributes").unwrap().as_array().unwrap();
                 for res_attr in attributes {
                     let local_attr = res_attr.as_object().unwrap();
-                    if local_attr
+                    let key = local_attr.get("key").unwrap().as_str().unwrap();
+                    if !otlp_attributes.is_promoted(key) {
+                        nested_resource.insert(
+                            key.to_string(),
+                            get_val_for_attr(local_attr.get("value").unwrap()),
+                        );
+                    } else if local_attr
                         .get("key")
                         .unwrap()
                         .as_str()
@@ -259,6 +291,38 @@ pub async fn logs_json_handler(
             res_log.get("scope_logs").unwrap().as_array().unwrap()
         };
         for inst_log in inst_resources {
+            // the scope attributes are only kept when the stream nests the attributes
+            let mut scope_att_map = json::Map::new();
+            let mut nested_scope = json::Map::new();
+            if !otlp_attributes.nested_column.is_empty() {
+                let attributes = inst_log
+                    .get("scope")
+                    .and_then(|v| v.get("attributes"))
+                    .and_then(|v| v.as_array());
+                for scope_attr in attributes.into_iter().flatten() {
+                    let Some(local_attr) = scope_attr.as_object() else {
+                        continue;
+                    };
+                    let (Some(key), Some(value)) = (
+                        local_attr.get("key").and_then(|v| v.as_str()),
+                        local_attr.get("value"),
+                    ) else {
+                        continue;
+                    };
+                    if otlp_attributes.is_promoted(key) {
+                        let mut key = key.to_string();
+                        flatten::format_key(&mut key);
+                        scope_att_map.insert(key, get_val_for_attr(value));
+                    } else {
+                        nested_scope.insert(key.to_string(), get_val_for_attr(value));
+                    }
+                }
+            }
+            let nested = crate::service::ingestion::nested_otlp_attributes(
+                nested_resource.clone(),
+                nested_scope,
+            );
+
             let log_records = if inst_log.get("logRecords").is_some() {
                 inst_log.get("logRecords").unwrap().as_array().unwrap()
             } else {
@@ -344,12 +408,20 @@ pub async fn logs_json_handler(
                     continue;
                 }
 
+                crate::service::ingestion::reserved::protect(
+                    local_val,
+                    &mut stream_status.status.warnings,
+                );
                 local_val.insert(
                     CONFIG.common.column_timestamp.clone(),
                     json::Value::Number(timestamp.into()),
                 );
 
                 local_val.append(&mut service_att_map.clone());
+                local_val.append(&mut scope_att_map.clone());
+                if let Some(nested) = &nested {
+                    local_val.insert(otlp_attributes.nested_column.clone(), nested.clone());
+                }
 
                 value = json::to_value(local_val)?;
 
@@ -357,6 +429,16 @@ pub async fn logs_json_handler(
                 value =
                     flatten::flatten_with_level(value, CONFIG.limit.ingest_flatten_level).unwrap();
 
+                // records routed to another org are ingested with the settings of the target
+                // stream
+                if !cross_org_routes.is_empty()
+                    && cross_org_records
+                        .route(org_id, stream_name, &cross_org_routes, &value)
+                        .await
+                {
+                    continue;
+                }
+
                 if !local_trans.is_empty() {
                     value = crate::service::ingestion::apply_stream_functions(
                         &local_trans,
@@ -369,10 +451,16 @@ pub async fn logs_json_handler(
                 }
 
                 // get json object
-                let local_val = match value.take() {
+                let mut local_val = match value.take() {
                     json::Value::Object(v) => v,
                     _ => unreachable!(),
                 };
+                if drop_rules.should_drop(&local_val) {
+                    continue;
+                }
+                if let Some(lineage) = lineage {
+                    crate::service::lineage::attach(&mut local_val, lineage);
+                }
 
                 let mut to_add_distinct_values = vec![];
                 // get distinct_value item
@@ -398,6 +486,7 @@ pub async fn logs_json_handler(
                         partition_keys: &partition_keys,
                         partition_time_level: &partition_time_level,
                         stream_alerts_map: &stream_alerts_map,
+                        reprocessed: false,
                     },
                     &mut stream_schema_map,
                     &mut stream_status.status,
@@ -424,6 +513,23 @@ pub async fn logs_json_handler(
         }
     }
 
+    // only the records written to the stream count against its quotas
+    if let Err(e) = crate::service::ingestion::quota::check_pending(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        buf.values(),
+    )
+    .await
+    {
+        return Ok(
+            HttpResponse::TooManyRequests().json(MetaHttpResponse::error(
+                http::StatusCode::TOO_MANY_REQUESTS.into(),
+                e.to_string(),
+            )),
+        );
+    }
+
     // write data to wal
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Logs.to_string()).await;
     let mut req_stats = write_file(&writer, stream_name, buf).await;
@@ -474,6 +580,14 @@ pub async fn logs_json_handler(
     )
     .await;
 
+    // records routed to other orgs are ingested as a request of the target org
+    for routed in cross_org_records
+        .ingest(org_id, thread_id, user_email)
+        .await
+    {
+        stream_status.status.failed += routed.status.failed;
+    }
+
     let res = ExportLogsServiceResponse {
         partial_success: Some(ExportLogsPartialSuccess {
             rejected_log_records: stream_status.status.failed as i64,
@@ -0,0 +1,491 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Fix forward of the records written by a faulty function: the log records
+//! stamped with a lineage are run again through the current functions, into
+//! the same or another stream, and the old records are marked superseded.
+//! Streams keeping the originals are reprocessed from them, the others from
+//! the stored records. The jobs run on the ingesters, one window of time at a
+//! time, so another ingester can resume a job whose node is gone.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::{search, stream::StreamType},
+    utils::json::Value,
+    RwHashSet, CONFIG,
+};
+use infra::dist_lock;
+use once_cell::sync::Lazy;
+
+use super::{ingest::apply_functions, StreamMeta};
+use crate::{
+    common::{
+        infra::cluster::get_node_by_uuid,
+        meta::{
+            functions::{LineageSuperseded, ReprocessJob, ReprocessStatus},
+            ingestion::RecordStatus,
+            stream::{StreamParams, Tombstone},
+        },
+    },
+    service::{
+        compact::tombstones,
+        db,
+        encryption::{self, ENCRYPTED_VALUE_PREFIX},
+        error::{Result, ServiceError},
+        format_stream_name, get_formatted_stream_name,
+        ingestion::{
+            get_stream_drop_rules, get_stream_flatten_options, get_stream_partition_keys,
+            init_functions_runtime, register_stream_functions, write_file,
+        },
+        lineage::{self, LINEAGE_COLUMN},
+        original::{self, ORIGINAL_COLUMN, ORIGINAL_ID_COLUMN},
+        record_id::RECORD_ID_COLUMN,
+        schema::SchemaCache,
+        search as SearchService,
+    },
+};
+
+/// Time range searched by one pass of a job, one hour in microseconds
+const WINDOW: i64 = 3_600_000_000;
+
+// jobs running on this node
+static RUNNING: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+/// Queues a reprocess job, it is picked up by an ingester.
+pub async fn create(org_id: &str, mut job: ReprocessJob, user_email: &str) -> Result<ReprocessJob> {
+    let now = Utc::now().timestamp_micros();
+    if job.end_time == 0 || job.end_time > now {
+        job.end_time = now;
+    }
+    if job.start_time >= job.end_time {
+        return Err(ServiceError::bad_request(
+            "start_time should be less than end_time",
+        ));
+    }
+    let schema = infra::schema::get(org_id, &job.stream_name, StreamType::Logs).await?;
+    if schema.fields().is_empty() {
+        return Err(ServiceError::not_found(format!(
+            "stream {} not found",
+            job.stream_name
+        )));
+    }
+    lineage::get(org_id, StreamType::Logs, &job.stream_name, job.lineage).await?;
+    job.target_stream = if job.target_stream.is_empty() {
+        job.stream_name.clone()
+    } else {
+        format_stream_name(&job.target_stream)
+    };
+
+    job.id = config::ider::generate();
+    job.status = ReprocessStatus::Pending;
+    job.node = String::new();
+    job.done_until = job.start_time;
+    job.done_offset = 0;
+    job.records = 0;
+    job.failed = 0;
+    job.error = String::new();
+    job.created_by = user_email.to_string();
+    job.created_at = now;
+    db::reprocess::set(org_id, &job).await?;
+    Ok(job)
+}
+
+pub async fn get(org_id: &str, id: &str) -> Result<ReprocessJob> {
+    db::reprocess::get(org_id, id)
+        .await
+        .map_err(|_| ServiceError::not_found(format!("reprocess job {id} not found")))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<ReprocessJob>> {
+    Ok(db::reprocess::list(org_id).await?)
+}
+
+/// Claims the pending jobs, and the ones whose node is gone, and runs them in
+/// the background.
+pub async fn run_pending() -> Result<(), anyhow::Error> {
+    for (org_id, job) in db::reprocess::list_all().await? {
+        if !is_claimable(&job).await {
+            continue;
+        }
+        let lock_key = format!("/reprocess/{org_id}/{}", job.id);
+        let locker = dist_lock::lock(&lock_key, 0).await?;
+        // another ingester may have claimed it meanwhile
+        let mut job = match db::reprocess::get(&org_id, &job.id).await {
+            Ok(job) if is_claimable(&job).await => job,
+            _ => {
+                dist_lock::unlock(&locker).await?;
+                continue;
+            }
+        };
+        job.node = LOCAL_NODE_UUID.clone();
+        job.status = ReprocessStatus::Running;
+        let ret = db::reprocess::set(&org_id, &job).await;
+        dist_lock::unlock(&locker).await?;
+        drop(locker);
+        ret?;
+
+        RUNNING.insert(job.id.clone());
+        tokio::task::spawn(async move { execute(org_id, job).await });
+    }
+    Ok(())
+}
+
+async fn is_claimable(job: &ReprocessJob) -> bool {
+    match job.status {
+        ReprocessStatus::Done | ReprocessStatus::Failed => false,
+        _ if RUNNING.contains(&job.id) => false,
+        _ => {
+            job.node.is_empty()
+                || LOCAL_NODE_UUID.eq(&job.node)
+                || get_node_by_uuid(&job.node).await.is_none()
+        }
+    }
+}
+
+async fn execute(org_id: String, mut job: ReprocessJob) {
+    log::info!(
+        "[REPROCESS] start job {}: {org_id}/{} lineage {} into {}",
+        job.id,
+        job.stream_name,
+        job.lineage,
+        job.target_stream
+    );
+    match run(&org_id, &mut job).await {
+        Ok(_) => {
+            job.status = ReprocessStatus::Done;
+            if let Err(e) = supersede(&org_id, &job).await {
+                job.error = format!("superseded records not marked: {e}");
+            }
+        }
+        Err(e) => {
+            job.status = ReprocessStatus::Failed;
+            job.error = e.to_string();
+        }
+    }
+    if let Err(e) = db::reprocess::set(&org_id, &job).await {
+        log::error!("[REPROCESS] job {} save error: {e}", job.id);
+    }
+    log::info!(
+        "[REPROCESS] job {} {:?}, records: {}, failed: {}",
+        job.id,
+        job.status,
+        job.records,
+        job.failed
+    );
+    RUNNING.remove(&job.id);
+}
+
+async fn run(org_id: &str, job: &mut ReprocessJob) -> Result<(), anyhow::Error> {
+    let mut stream_schema_map: HashMap<String, SchemaCache> = HashMap::new();
+    let mut stream_params = StreamParams::new(org_id, &job.target_stream, StreamType::Logs);
+    let stream_name = get_formatted_stream_name(&mut stream_params, &mut stream_schema_map).await;
+
+    let mut runtime = init_functions_runtime();
+    let (local_trans, stream_vrl_map) =
+        register_stream_functions(org_id, &StreamType::Logs, &stream_name);
+    let new_lineage = lineage::register(
+        org_id,
+        StreamType::Logs,
+        &stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
+    // the same functions would give the same records, found again by the search
+    if stream_name == job.stream_name && new_lineage == Some(job.lineage) {
+        return Err(anyhow::anyhow!(
+            "the pipeline and functions of stream [{stream_name}] didn't change since lineage {}",
+            job.lineage
+        ));
+    }
+    let partition_det = get_stream_partition_keys(org_id, &StreamType::Logs, &stream_name).await;
+    let drop_rules = get_stream_drop_rules(org_id, &StreamType::Logs, &stream_name).await;
+    let flatten_options = get_stream_flatten_options(org_id, &StreamType::Logs, &stream_name).await;
+    let retain_original = original::is_enabled(org_id, StreamType::Logs, &stream_name).await;
+    let encrypt_original =
+        retain_original && original::is_encrypted(org_id, StreamType::Logs, &stream_name).await;
+    // the records already went through the real time alerts
+    let stream_alerts_map = HashMap::new();
+    let stream_meta = StreamMeta {
+        org_id: org_id.to_string(),
+        stream_name: stream_name.clone(),
+        partition_keys: &partition_det.partition_keys,
+        partition_time_level: &partition_det.partition_time_level,
+        stream_alerts_map: &stream_alerts_map,
+        reprocessed: true,
+    };
+    let writer = ingester::get_writer(0, org_id, &StreamType::Logs.to_string()).await;
+
+    let batch_size = CONFIG.limit.reprocess_batch_size.max(1) as usize;
+    // `select *` leaves the original out, it is asked for when the records kept it
+    let with_original = infra::schema::get(org_id, &job.stream_name, StreamType::Logs)
+        .await
+        .map(|schema| schema.field_with_name(ORIGINAL_COLUMN).is_ok())
+        .unwrap_or_default();
+    // only the originals kept by the source stream are restored, not the ones
+    // ingested before the column was reserved
+    let trust_original =
+        with_original && original::is_enabled(org_id, StreamType::Logs, &job.stream_name).await;
+    let mut start = job.done_until.max(job.start_time);
+    while start < job.end_time {
+        let end = (start + WINDOW).min(job.end_time);
+        // the batches written before a restart are skipped
+        let mut from = job.done_offset;
+        loop {
+            let hits = fetch(org_id, job, (start, end), from, batch_size, with_original).await?;
+            let fetched = hits.len();
+            let mut status = RecordStatus::default();
+            let mut write_buf = HashMap::new();
+            for hit in hits {
+                let Some((input, timestamp)) = split_record(org_id, hit, trust_original).await
+                else {
+                    status.failed += 1;
+                    continue;
+                };
+                let mut local_val = match apply_functions(
+                    org_id,
+                    input.clone(),
+                    &flatten_options,
+                    &local_trans,
+                    &stream_vrl_map,
+                    &stream_name,
+                    &mut runtime,
+                ) {
+                    Ok(Value::Object(val)) => val,
+                    Ok(_) => unreachable!(),
+                    Err(e) => {
+                        status.failed += 1;
+                        status.error = e.to_string();
+                        continue;
+                    }
+                };
+                if drop_rules.should_drop(&local_val) {
+                    continue;
+                }
+                local_val.insert(
+                    CONFIG.common.column_timestamp.clone(),
+                    Value::Number(timestamp.into()),
+                );
+                if let Some(id) = new_lineage {
+                    lineage::attach(&mut local_val, id);
+                }
+                if retain_original {
+                    match original::capture(org_id, &input, encrypt_original).await {
+                        Ok(v) => original::attach(&mut local_val, v),
+                        Err(e) => {
+                            status.failed += 1;
+                            status.error = e.to_string();
+                            continue;
+                        }
+                    }
+                }
+                if let Err(e) = super::add_valid_record(
+                    &stream_meta,
+                    &mut stream_schema_map,
+                    &mut status,
+                    &mut write_buf,
+                    local_val,
+                    false,
+                )
+                .await
+                {
+                    status.failed += 1;
+                    status.error = e.to_string();
+                }
+            }
+            write_file(&writer, &stream_name, write_buf).await;
+            writer.sync().await?;
+
+            // the progress is saved per batch, a restart doesn't write them again
+            from += fetched;
+            job.records += status.successful as i64;
+            job.failed += status.failed as i64;
+            if !status.error.is_empty() {
+                job.error = status.error;
+            }
+            job.done_offset = from;
+            db::reprocess::set(org_id, job).await?;
+            if fetched < batch_size {
+                break;
+            }
+        }
+
+        job.done_until = end;
+        job.done_offset = 0;
+        db::reprocess::set(org_id, job).await?;
+        start = end;
+    }
+    Ok(())
+}
+
+async fn fetch(
+    org_id: &str,
+    job: &ReprocessJob,
+    (start_time, end_time): (i64, i64),
+    from: usize,
+    size: usize,
+    with_original: bool,
+) -> Result<Vec<Value>, anyhow::Error> {
+    let columns = if with_original {
+        format!("*, \"{ORIGINAL_COLUMN}\"")
+    } else {
+        "*".to_string()
+    };
+    let query = search::Query {
+        sql: format!(
+            "SELECT {columns} FROM \"{}\" WHERE \"{LINEAGE_COLUMN}\" = {} ORDER BY {} ASC",
+            job.stream_name, job.lineage, CONFIG.common.column_timestamp
+        ),
+        from,
+        size,
+        start_time,
+        end_time,
+        sql_mode: "full".to_owned(),
+        ..Default::default()
+    };
+    let req = search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    // the records are read as the user who asked for the job
+    let res = SearchService::search(
+        &job.id,
+        org_id,
+        StreamType::Logs,
+        Some(job.created_by.clone()),
+        &req,
+    )
+    .await?;
+    Ok(res.hits)
+}
+
+/// Returns the input of the functions and the timestamp of a stored record,
+/// the original when the record kept it and `trust_original` is set. The encrypted values of a
+/// stored record are decrypted, the functions saw them in clear at the ingestion and
+/// they are encrypted again once.
+async fn split_record(org_id: &str, hit: Value, trust_original: bool) -> Option<(Value, i64)> {
+    let Value::Object(mut record) = hit else {
+        return None;
+    };
+    let timestamp = record
+        .remove(&CONFIG.common.column_timestamp)
+        .and_then(|v| v.as_i64())?;
+    if let Some(original) = record
+        .get(ORIGINAL_COLUMN)
+        .and_then(|v| v.as_str())
+        .filter(|_| trust_original)
+    {
+        return original::restore(org_id, original)
+            .await
+            .ok()
+            .map(|v| (v, timestamp));
+    }
+    record.retain(|k, v| !is_internal_column(k) && !v.is_null());
+    let encrypted = record
+        .values_mut()
+        .filter_map(|v| match v {
+            Value::String(s) if s.starts_with(ENCRYPTED_VALUE_PREFIX) => Some(s),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !encrypted.is_empty() {
+        let key = encryption::get_key(org_id).await.ok()??;
+        for value in encrypted {
+            *value = encryption::decrypt_value(&key, value)?;
+        }
+    }
+    Some((Value::Object(record), timestamp))
+}
+
+fn is_internal_column(name: &str) -> bool {
+    name == LINEAGE_COLUMN
+        || name == ORIGINAL_COLUMN
+        || name == ORIGINAL_ID_COLUMN
+        || name == RECORD_ID_COLUMN
+}
+
+/// Marks the records of the job's lineage and time range superseded, and
+/// deletes them if asked.
+async fn supersede(org_id: &str, job: &ReprocessJob) -> Result<(), anyhow::Error> {
+    let mut lineage =
+        db::lineage::get(org_id, StreamType::Logs, &job.stream_name, job.lineage).await?;
+    lineage.superseded.push(LineageSuperseded {
+        job_id: job.id.clone(),
+        start_time: job.start_time,
+        end_time: job.end_time,
+    });
+    db::lineage::set(org_id, &lineage).await?;
+    if job.delete_superseded {
+        tombstones::create(
+            org_id,
+            StreamType::Logs,
+            &job.stream_name,
+            Tombstone {
+                start_time: job.start_time,
+                end_time: job.end_time,
+                filter: format!("{LINEAGE_COLUMN} = {}", job.lineage),
+                ..Default::default()
+            },
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_split_record() {
+        let ts = CONFIG.common.column_timestamp.clone();
+        let record = json::json!({
+            ts.clone(): 1700000000000000i64,
+            "message": "login failed",
+            "level": null,
+            LINEAGE_COLUMN: 42,
+        });
+        let (input, timestamp) = split_record("default", record, true).await.unwrap();
+        assert_eq!(timestamp, 1700000000000000);
+        assert_eq!(input, json::json!({"message": "login failed"}));
+
+        let original = json::json!({"message": "{\"user\":\"root\"}"});
+        let record = json::json!({
+            ts.clone(): 1700000000000001i64,
+            "user": "root",
+            ORIGINAL_COLUMN: original::encode(&original).unwrap(),
+            ORIGINAL_ID_COLUMN: "1700000000000001-abc",
+        });
+        let (input, _) = split_record("default", record.clone(), true).await.unwrap();
+        assert_eq!(input, original);
+        // an original the stream didn't keep is dropped with the internal columns
+        let (input, _) = split_record("default", record, false).await.unwrap();
+        assert_eq!(input, json::json!({"user": "root"}));
+
+        assert!(
+            split_record("default", json::json!({"message": "no timestamp"}), true)
+                .await
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Routing of the records to the streams of other orgs. The records are
+//! ingested as a request of the target org, with the settings and functions of
+//! the target stream, and they are not routed again.
+
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use actix_web::web;
+use config::{
+    meta::stream::{Routing, StreamType},
+    utils::json,
+};
+
+use crate::{
+    common::meta::{
+        ingestion::{IngestionRequest, IngestionResponse, StreamStatus},
+        stream::StreamParams,
+    },
+    service::ingestion::{get_stream_routing, is_cross_org_routing_allowed},
+};
+
+/// The routes of the stream to the streams of other orgs.
+pub async fn cross_org_routes(org_id: &str, stream_name: &str) -> Vec<Routing> {
+    let mut routing_map = HashMap::new();
+    get_stream_routing(
+        StreamParams::new(org_id, stream_name, StreamType::Logs),
+        &mut routing_map,
+    )
+    .await;
+    routing_map
+        .remove(stream_name)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|route| route.cross_org_destination().is_some())
+        .collect()
+}
+
+/// The records of a request routed to the streams of other orgs, by target org
+/// and stream.
+#[derive(Default)]
+pub struct CrossOrgRecords {
+    allowed: HashMap<String, bool>,
+    records: HashMap<(String, String), Vec<json::Value>>,
+}
+
+impl CrossOrgRecords {
+    /// Whether `target_org` accepts the records of `org_id`, checked once per
+    /// request as the target org can revoke the permission at any time.
+    pub async fn is_allowed(&mut self, org_id: &str, stream_name: &str, target_org: &str) -> bool {
+        if let Some(allowed) = self.allowed.get(target_org) {
+            return *allowed;
+        }
+        let allowed = is_cross_org_routing_allowed(org_id, target_org).await;
+        if !allowed {
+            log::warn!(
+                "stream [{stream_name}] can't route records to organization [{target_org}], it doesn't accept [{org_id}] as a routing source"
+            );
+        }
+        self.allowed.insert(target_org.to_string(), allowed);
+        allowed
+    }
+
+    pub fn push(&mut self, target_org: &str, target_stream: &str, record: json::Value) {
+        self.records
+            .entry((target_org.to_string(), target_stream.to_string()))
+            .or_default()
+            .push(record);
+    }
+
+    /// Takes the flattened record when it matches a route to another org that
+    /// accepts it, returns whether it was taken.
+    pub async fn route(
+        &mut self,
+        org_id: &str,
+        stream_name: &str,
+        routes: &[Routing],
+        record: &json::Value,
+    ) -> bool {
+        let Some(fields) = record.as_object() else {
+            return false;
+        };
+        for route in routes {
+            let Some((target_org, target_stream)) = route.cross_org_destination() else {
+                continue;
+            };
+            if route.routing.is_empty() {
+                continue;
+            }
+            let mut is_routed = true;
+            for condition in route.routing.iter() {
+                is_routed = is_routed && condition.evaluate(fields).await;
+            }
+            if is_routed && self.is_allowed(org_id, stream_name, target_org).await {
+                self.push(target_org, target_stream, record.clone());
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Ingests the records as requests of their target org, returns the status
+    /// of each target stream named `org/stream`.
+    pub async fn ingest(
+        self,
+        org_id: &str,
+        thread_id: usize,
+        user_email: &str,
+    ) -> Vec<StreamStatus> {
+        let mut statuses = Vec::with_capacity(self.records.len());
+        for ((target_org, target_stream), records) in self.records {
+            let mut status = StreamStatus::new(&format!("{target_org}/{target_stream}"));
+            let body = match json::to_vec(&records) {
+                Ok(body) => web::Bytes::from(body),
+                Err(e) => {
+                    status.status.failed = records.len() as u32;
+                    status.status.error = e.to_string();
+                    statuses.push(status);
+                    continue;
+                }
+            };
+            // boxed, the ingestion of the target org is the one routing the records
+            let fut: Pin<Box<dyn Future<Output = anyhow::Result<IngestionResponse>> + Send + '_>> =
+                Box::pin(super::ingest::ingest_routed(
+                    &target_org,
+                    &target_stream,
+                    IngestionRequest::JSON(&body),
+                    thread_id,
+                    user_email,
+                ));
+            match fut.await {
+                Ok(resp) => {
+                    if let Some(e) = resp.error {
+                        log::error!(
+                            "routing records from [{org_id}] to [{target_org}/{target_stream}] failed: {e}"
+                        );
+                        status.status.failed = records.len() as u32;
+                        status.status.error = e;
+                    } else if let Some(target) = resp.status.into_iter().next() {
+                        status.status = target.status;
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "routing records from [{org_id}] to [{target_org}/{target_stream}] failed: {e}"
+                    );
+                    status.status.failed = records.len() as u32;
+                    status.status.error = e.to_string();
+                }
+            }
+            statuses.push(status);
+        }
+        statuses
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use config::{
+    meta::stream::{Routing, StreamType},
+    utils::{
+        flatten,
+        json::{self, Map, Value},
+    },
+    CONFIG,
+};
+use vrl::compiler::runtime::Runtime;
+
+use super::{bulk::TRANSFORM_FAILED, ingest::handle_timestamp, refactor_map};
+use crate::{
+    common::meta::{
+        functions::{StreamTransform, VRLResultResolver},
+        ingestion::{SimulatedRecord, SimulationResponse},
+        stream::StreamParams,
+    },
+    service::{
+        encryption::{self, ENCRYPTED_VALUE_PREFIX},
+        get_formatted_stream_name,
+        ingestion::{
+            apply_stream_functions, get_stream_drop_rules, get_stream_routing,
+            get_user_defined_schema, init_functions_runtime, is_cross_org_routing_allowed,
+            register_stream_functions, StreamDropRules,
+        },
+        schema::preview_schema_changes,
+    },
+};
+
+/// The ingestion settings of a stream used by the simulation.
+struct StreamPipeline {
+    routing: Vec<Routing>,
+    transforms: Vec<StreamTransform>,
+    vrl_map: HashMap<String, VRLResultResolver>,
+    drop_rules: StreamDropRules,
+    defined_fields: Option<Vec<String>>,
+    encrypt_fields: Vec<String>,
+}
+
+impl StreamPipeline {
+    async fn new(org_id: &str, stream_name: &str) -> Self {
+        let mut routing_map = HashMap::new();
+        get_stream_routing(
+            StreamParams::new(org_id, stream_name, StreamType::Logs),
+            &mut routing_map,
+        )
+        .await;
+        let mut defined_schema_map = HashMap::new();
+        get_user_defined_schema(
+            &[StreamParams::new(org_id, stream_name, StreamType::Logs)],
+            &mut defined_schema_map,
+        )
+        .await;
+        let (transforms, vrl_map) =
+            register_stream_functions(org_id, &StreamType::Logs, stream_name);
+        Self {
+            routing: routing_map.remove(stream_name).unwrap_or_default(),
+            transforms,
+            vrl_map,
+            drop_rules: get_stream_drop_rules(org_id, &StreamType::Logs, stream_name).await,
+            defined_fields: defined_schema_map.remove(stream_name),
+            encrypt_fields: encryption::get_encrypt_fields(org_id, StreamType::Logs, stream_name)
+                .await,
+        }
+    }
+}
+
+/// Runs the records through the logs ingestion of the stream without writing
+/// anything. Routing, functions, drop rules, the user defined schema, the
+/// timestamp checks and field encryption are applied the way `_bulk` does, and
+/// the schema changes the records would make are reported per stream.
+pub async fn simulate(
+    org_id: &str,
+    in_stream_name: &str,
+    records: Vec<json::Value>,
+) -> Result<SimulationResponse> {
+    let mut stream_schema_map = HashMap::new();
+    let mut stream_params = StreamParams::new(org_id, in_stream_name, StreamType::Logs);
+    let stream_name = get_formatted_stream_name(&mut stream_params, &mut stream_schema_map).await;
+    let min_ts = (Utc::now() - Duration::try_hours(CONFIG.limit.ingest_allowed_upto).unwrap())
+        .timestamp_micros();
+
+    let mut runtime = init_functions_runtime();
+    let mut pipelines = HashMap::new();
+    pipelines.insert(
+        stream_name.clone(),
+        StreamPipeline::new(org_id, &stream_name).await,
+    );
+
+    let mut response = SimulationResponse::default();
+    let mut stream_records: HashMap<String, Vec<Map<String, Value>>> = HashMap::new();
+    for record in records {
+        let mut simulated = SimulatedRecord {
+            stream: stream_name.clone(),
+            ..Default::default()
+        };
+        match simulate_record(
+            org_id,
+            &stream_name,
+            &mut pipelines,
+            &mut runtime,
+            min_ts,
+            record,
+            &mut simulated,
+        )
+        .await
+        {
+            Ok(Some(local_val)) => {
+                stream_records
+                    .entry(simulated.stream.clone())
+                    .or_default()
+                    .push(local_val.clone());
+                simulated.record = Some(Value::Object(local_val));
+            }
+            Ok(None) => {}
+            Err(e) => simulated.error = Some(e.to_string()),
+        }
+        response.records.push(simulated);
+    }
+
+    for (stream, records) in stream_records {
+        let (stream_org, name) = stream_location(org_id, &stream);
+        let changes = preview_schema_changes(stream_org, name, StreamType::Logs, &records).await?;
+        if !changes.is_empty() {
+            response.schema_changes.insert(stream, changes);
+        }
+    }
+    Ok(response)
+}
+
+/// Returns the record as it would be written, or `None` when it is dropped.
+async fn simulate_record(
+    org_id: &str,
+    stream_name: &str,
+    pipelines: &mut HashMap<String, StreamPipeline>,
+    runtime: &mut Runtime,
+    min_ts: i64,
+    record: json::Value,
+    simulated: &mut SimulatedRecord,
+) -> Result<Option<Map<String, Value>>> {
+    let value = flatten::flatten_with_level(record, CONFIG.limit.ingest_flatten_level)?;
+    let Some(local_val) = value.as_object() else {
+        return Err(anyhow::anyhow!("Records must be json objects"));
+    };
+
+    for route in pipelines[stream_name].routing.iter() {
+        if route.routing.is_empty() {
+            continue;
+        }
+        let mut is_routed = true;
+        for condition in route.routing.iter() {
+            is_routed = is_routed && condition.evaluate(local_val).await;
+        }
+        if is_routed {
+            // the records are only routed to the orgs accepting this one
+            if let Some((target_org, _)) = route.cross_org_destination() {
+                if !is_cross_org_routing_allowed(org_id, target_org).await {
+                    continue;
+                }
+            }
+            simulated.stream = route.destination.clone();
+            break;
+        }
+    }
+    if !pipelines.contains_key(&simulated.stream) {
+        let (stream_org, name) = stream_location(org_id, &simulated.stream);
+        let pipeline = StreamPipeline::new(stream_org, name).await;
+        pipelines.insert(simulated.stream.clone(), pipeline);
+    }
+    let pipeline = &pipelines[&simulated.stream];
+
+    let value = if pipeline.transforms.is_empty() {
+        value
+    } else {
+        apply_stream_functions(
+            &pipeline.transforms,
+            value,
+            &pipeline.vrl_map,
+            &simulated.stream,
+            runtime,
+        )?
+    };
+    let mut local_val = match value {
+        Value::Object(v) => v,
+        _ => return Err(anyhow::anyhow!(TRANSFORM_FAILED)),
+    };
+
+    if let Some(rule) = pipeline.drop_rules.matched(&local_val) {
+        simulated.dropped_by = Some(rule.to_string());
+        return Ok(None);
+    }
+    if let Some(fields) = &pipeline.defined_fields {
+        simulated.undefined_fields = local_val
+            .keys()
+            .filter(|key| !fields.contains(key))
+            .cloned()
+            .collect();
+        refactor_map(&mut local_val, fields);
+    }
+    crate::service::ingestion::reserved::protect(&mut local_val, &mut simulated.warnings);
+    handle_timestamp(&mut local_val, min_ts)?;
+    simulated.redacted_fields = redact_fields(&mut local_val, &pipeline.encrypt_fields);
+
+    Ok(Some(local_val))
+}
+
+/// The org and name of a stream the records are routed to, written as
+/// `org/stream` when it belongs to another org.
+fn stream_location<'a>(org_id: &'a str, stream: &'a str) -> (&'a str, &'a str) {
+    stream.split_once('/').unwrap_or((org_id, stream))
+}
+
+/// Masks the values field encryption would replace, without creating the org
+/// key the way a real ingestion would.
+fn redact_fields(record: &mut Map<String, Value>, fields: &[String]) -> Vec<String> {
+    let mut redacted = vec![];
+    for field in fields {
+        if field == &CONFIG.common.column_timestamp {
+            continue;
+        }
+        match record.get_mut(field) {
+            None | Some(Value::Null) => continue,
+            Some(Value::String(v)) if v.starts_with(ENCRYPTED_VALUE_PREFIX) => continue,
+            Some(value) => *value = Value::String(format!("{ENCRYPTED_VALUE_PREFIX}***")),
+        }
+        redacted.push(field.clone());
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_location() {
+        assert_eq!(stream_location("default", "k8s"), ("default", "k8s"));
+        assert_eq!(
+            stream_location("default", "soc/security_events"),
+            ("soc", "security_events")
+        );
+    }
+
+    #[test]
+    fn test_redact_fields() {
+        let mut record = json::json!({
+            "user": "alice",
+            "card": 4111111111111111u64,
+            "token": format!("{ENCRYPTED_VALUE_PREFIX}abc"),
+            "empty": null,
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        let fields = ["user", "card", "token", "empty", "missing"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+
+        let redacted = redact_fields(&mut record, &fields);
+        assert_eq!(redacted, vec!["user".to_string(), "card".to_string()]);
+        assert!(
+            record["user"]
+                .as_str()
+                .unwrap()
+                .starts_with(ENCRYPTED_VALUE_PREFIX)
+        );
+        assert!(record["card"].is_string());
+        assert_eq!(record["token"], format!("{ENCRYPTED_VALUE_PREFIX}abc"));
+        assert!(record["empty"].is_null());
+    }
+}
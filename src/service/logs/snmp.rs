@@ -0,0 +1,688 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    ops::Range,
+};
+
+use actix_web::web;
+use anyhow::{bail, Result};
+use config::{utils::json, RwHashMap};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use once_cell::sync::Lazy;
+use sha2::Digest;
+
+use crate::{
+    common::{
+        infra::config::SNMP_TRAP_ROUTES,
+        meta::{
+            ingestion::{IngestionRequest, IngestionResponse},
+            snmp::{SnmpAuthProtocol, SnmpTrapRoute, SnmpUser},
+        },
+    },
+    service::snmp::mib,
+};
+
+const SNMP_V1: i64 = 0;
+const SNMP_V2C: i64 = 1;
+const SNMP_V3: i64 = 3;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+const PDU_TRAP_V1: u8 = 0xa4;
+const PDU_TRAP_V2: u8 = 0xa7;
+
+const SNMPV3_AUTH_FLAG: u8 = 0x01;
+const SNMPV3_PRIV_FLAG: u8 = 0x02;
+/// The password is repeated over a megabyte to derive the key (RFC 3414 A.2)
+const PASSWORD_TO_KEY_LEN: usize = 1024 * 1024;
+
+const OID_SYS_UPTIME: &str = "1.3.6.1.2.1.1.3.0";
+const OID_SNMP_TRAP_OID: &str = "1.3.6.1.6.3.1.1.4.1.0";
+const OID_SNMP_TRAPS: &str = "1.3.6.1.6.3.1.1.5";
+
+/// Localized keys of the SNMPv3 users, by protocol, engine id and password.
+static USM_KEYS: Lazy<RwHashMap<(SnmpAuthProtocol, Vec<u8>, String), Vec<u8>>> =
+    Lazy::new(Default::default);
+
+/// A decoded trap with the identity used to pick its route.
+#[derive(Debug)]
+pub struct Trap {
+    pub community: Option<String>,
+    pub engine_id: Option<String>,
+    pub usm: Option<Usm>,
+    pub record: json::Map<String, json::Value>,
+}
+
+/// The user based security parameters of an SNMPv3 trap.
+#[derive(Debug)]
+pub struct Usm {
+    pub engine_id: Vec<u8>,
+    pub user: String,
+    /// Where the digest is in the packet
+    pub auth_params: Range<usize>,
+}
+
+pub async fn ingest(packet: &[u8], addr: SocketAddr) -> Result<IngestionResponse> {
+    let trap = decode(packet, addr.ip())?;
+    let Some(route) = find_route(&trap) else {
+        bail!("no snmp trap route matches the trap from {}", addr.ip());
+    };
+    if let Some(usm) = &trap.usm {
+        authenticate(packet, usm, &route)?;
+    }
+    let body = web::Bytes::from(json::to_vec(&vec![trap.record])?);
+    super::ingest::ingest(
+        &route.org_id,
+        &route.stream_name,
+        IngestionRequest::JSON(&body),
+        0,
+        "",
+    )
+    .await
+}
+
+fn find_route(trap: &Trap) -> Option<SnmpTrapRoute> {
+    SNMP_TRAP_ROUTES.iter().find_map(|route| {
+        let matched = match (&trap.community, &trap.engine_id) {
+            (Some(community), _) => !route.community.is_empty() && route.community == *community,
+            (_, Some(engine_id)) => {
+                !route.engine_id.is_empty() && route.engine_id.eq_ignore_ascii_case(engine_id)
+            }
+            _ => false,
+        };
+        matched.then(|| route.value().clone())
+    })
+}
+
+/// Checks the digest of an SNMPv3 trap, the HMAC of the whole message with
+/// the digest zeroed keyed by the user's key localized to the engine id.
+fn authenticate(packet: &[u8], usm: &Usm, route: &SnmpTrapRoute) -> Result<()> {
+    let Some(user) = route.users.iter().find(|u| u.name == usm.user) else {
+        bail!("unknown snmpv3 user [{}]", usm.user);
+    };
+    let digest = &packet[usm.auth_params.clone()];
+    if digest.len() != digest_len(user.auth_protocol) {
+        bail!("invalid snmpv3 digest length for user [{}]", usm.user);
+    }
+    let key = localized_key(user, &usm.engine_id);
+    let mut message = packet.to_vec();
+    message[usm.auth_params.clone()].fill(0);
+    let verified = match user.auth_protocol {
+        SnmpAuthProtocol::Md5 => verify::<Hmac<md5::Md5>>(&key, &message, digest),
+        SnmpAuthProtocol::Sha => verify::<Hmac<sha1::Sha1>>(&key, &message, digest),
+        SnmpAuthProtocol::Sha224 => verify::<Hmac<sha2::Sha224>>(&key, &message, digest),
+        SnmpAuthProtocol::Sha256 => verify::<Hmac<sha2::Sha256>>(&key, &message, digest),
+        SnmpAuthProtocol::Sha384 => verify::<Hmac<sha2::Sha384>>(&key, &message, digest),
+        SnmpAuthProtocol::Sha512 => verify::<Hmac<sha2::Sha512>>(&key, &message, digest),
+    };
+    if !verified {
+        bail!("snmpv3 digest mismatch for user [{}]", usm.user);
+    }
+    Ok(())
+}
+
+/// The digest is the HMAC truncated to these many bytes (RFC 3414, RFC 7860).
+fn digest_len(protocol: SnmpAuthProtocol) -> usize {
+    match protocol {
+        SnmpAuthProtocol::Md5 | SnmpAuthProtocol::Sha => 12,
+        SnmpAuthProtocol::Sha224 => 16,
+        SnmpAuthProtocol::Sha256 => 24,
+        SnmpAuthProtocol::Sha384 => 32,
+        SnmpAuthProtocol::Sha512 => 48,
+    }
+}
+
+fn verify<M: Mac + KeyInit>(key: &[u8], message: &[u8], digest: &[u8]) -> bool {
+    let Ok(mut mac) = <M as KeyInit>::new_from_slice(key) else {
+        return false;
+    };
+    mac.update(message);
+    mac.verify_truncated_left(digest).is_ok()
+}
+
+fn localized_key(user: &SnmpUser, engine_id: &[u8]) -> Vec<u8> {
+    let cache_key = (
+        user.auth_protocol,
+        engine_id.to_vec(),
+        user.auth_password.clone(),
+    );
+    if let Some(key) = USM_KEYS.get(&cache_key) {
+        return key.clone();
+    }
+    let password = user.auth_password.as_bytes();
+    let key = match user.auth_protocol {
+        SnmpAuthProtocol::Md5 => localize::<md5::Md5>(password, engine_id),
+        SnmpAuthProtocol::Sha => localize::<sha1::Sha1>(password, engine_id),
+        SnmpAuthProtocol::Sha224 => localize::<sha2::Sha224>(password, engine_id),
+        SnmpAuthProtocol::Sha256 => localize::<sha2::Sha256>(password, engine_id),
+        SnmpAuthProtocol::Sha384 => localize::<sha2::Sha384>(password, engine_id),
+        SnmpAuthProtocol::Sha512 => localize::<sha2::Sha512>(password, engine_id),
+    };
+    USM_KEYS.insert(cache_key, key.clone());
+    key
+}
+
+/// The password to key algorithm of RFC 3414 A.2, then the key localized to
+/// the engine id.
+fn localize<D: Digest>(password: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    if !password.is_empty() {
+        let mut chunk = [0u8; 64];
+        for start in (0..PASSWORD_TO_KEY_LEN).step_by(chunk.len()) {
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = password[(start + i) % password.len()];
+            }
+            hasher.update(chunk);
+        }
+    }
+    let key = hasher.finalize();
+    let mut hasher = D::new();
+    hasher.update(&key);
+    hasher.update(engine_id);
+    hasher.update(&key);
+    hasher.finalize().to_vec()
+}
+
+/// Decodes an SNMP v1/v2c trap or an authenticated, unencrypted SNMPv3 trap.
+/// The v3 digest is checked by [`authenticate`] with the users of the route.
+pub fn decode(packet: &[u8], source: IpAddr) -> Result<Trap> {
+    let mut message = Reader::new(Reader::new(packet).expect(TAG_SEQUENCE)?);
+    let version = decode_int(message.expect(TAG_INTEGER)?);
+    let mut record = json::Map::new();
+    record.insert("source".to_string(), source.to_string().into());
+
+    let (community, engine_id, usm, pdu) = match version {
+        SNMP_V1 | SNMP_V2C => {
+            let community = String::from_utf8_lossy(message.expect(TAG_OCTET_STRING)?);
+            let version = if version == SNMP_V1 { "v1" } else { "v2c" };
+            record.insert("version".to_string(), version.into());
+            (Some(community.to_string()), None, None, message.next()?)
+        }
+        SNMP_V3 => {
+            let mut global = Reader::new(message.expect(TAG_SEQUENCE)?);
+            global.expect(TAG_INTEGER)?; // msgID
+            global.expect(TAG_INTEGER)?; // msgMaxSize
+            let flags = global.expect(TAG_OCTET_STRING)?;
+            if flags.first().is_some_and(|f| f & SNMPV3_PRIV_FLAG != 0) {
+                bail!("encrypted snmpv3 traps are not supported");
+            }
+            if !flags.first().is_some_and(|f| f & SNMPV3_AUTH_FLAG != 0) {
+                bail!("unauthenticated snmpv3 traps are not accepted");
+            }
+            let mut usm = Reader::new(message.expect(TAG_OCTET_STRING)?);
+            let mut usm = Reader::new(usm.expect(TAG_SEQUENCE)?);
+            let raw_engine_id = usm.expect(TAG_OCTET_STRING)?;
+            let engine_id = hex::encode(raw_engine_id);
+            usm.expect(TAG_INTEGER)?; // engine boots
+            usm.expect(TAG_INTEGER)?; // engine time
+            let user = String::from_utf8_lossy(usm.expect(TAG_OCTET_STRING)?);
+            // the values are slices of the packet
+            let auth_params = usm.expect(TAG_OCTET_STRING)?;
+            let start = auth_params.as_ptr() as usize - packet.as_ptr() as usize;
+            let usm = Usm {
+                engine_id: raw_engine_id.to_vec(),
+                user: user.to_string(),
+                auth_params: start..start + auth_params.len(),
+            };
+            let mut scoped = Reader::new(message.expect(TAG_SEQUENCE)?);
+            scoped.expect(TAG_OCTET_STRING)?; // contextEngineID
+            let context = String::from_utf8_lossy(scoped.expect(TAG_OCTET_STRING)?);
+            record.insert("version".to_string(), "v3".into());
+            record.insert("engine_id".to_string(), engine_id.clone().into());
+            record.insert("user".to_string(), user.into());
+            if !context.is_empty() {
+                record.insert("context".to_string(), context.into());
+            }
+            (None, Some(engine_id), Some(usm), scoped.next()?)
+        }
+        _ => bail!("unsupported snmp version: {version}"),
+    };
+
+    match pdu {
+        (PDU_TRAP_V1, body) => decode_trap_v1(body, &mut record)?,
+        (PDU_TRAP_V2, body) => decode_trap_v2(body, &mut record)?,
+        (tag, _) => bail!("unsupported snmp pdu: {tag:#x}"),
+    }
+    Ok(Trap {
+        community,
+        engine_id,
+        usm,
+        record,
+    })
+}
+
+fn decode_trap_v1(body: &[u8], record: &mut json::Map<String, json::Value>) -> Result<()> {
+    let mut pdu = Reader::new(body);
+    let enterprise = decode_oid(pdu.expect(TAG_OID)?);
+    let agent = pdu.expect(TAG_IP_ADDRESS)?;
+    let generic = decode_int(pdu.expect(TAG_INTEGER)?);
+    let specific = decode_int(pdu.expect(TAG_INTEGER)?);
+    let uptime = decode_uint(pdu.expect(TAG_TIMETICKS)?);
+    // RFC 3584 mapping of v1 traps onto snmpTrapOID
+    let trap_oid = if generic == 6 {
+        format!("{enterprise}.0.{specific}")
+    } else {
+        format!("{OID_SNMP_TRAPS}.{}", generic + 1)
+    };
+    if agent.len() == 4 {
+        let agent = Ipv4Addr::new(agent[0], agent[1], agent[2], agent[3]);
+        record.insert("agent_address".to_string(), agent.to_string().into());
+    }
+    record.insert("enterprise".to_string(), enterprise.into());
+    record.insert("uptime".to_string(), uptime.into());
+    insert_trap_oid(record, trap_oid);
+    decode_varbinds(pdu.expect(TAG_SEQUENCE)?, record)
+}
+
+fn decode_trap_v2(body: &[u8], record: &mut json::Map<String, json::Value>) -> Result<()> {
+    let mut pdu = Reader::new(body);
+    pdu.expect(TAG_INTEGER)?; // request-id
+    pdu.expect(TAG_INTEGER)?; // error-status
+    pdu.expect(TAG_INTEGER)?; // error-index
+    decode_varbinds(pdu.expect(TAG_SEQUENCE)?, record)
+}
+
+fn decode_varbinds(body: &[u8], record: &mut json::Map<String, json::Value>) -> Result<()> {
+    let mut varbinds = Reader::new(body);
+    while !varbinds.is_empty() {
+        let mut varbind = Reader::new(varbinds.expect(TAG_SEQUENCE)?);
+        let oid = decode_oid(varbind.expect(TAG_OID)?);
+        let (tag, value) = varbind.next()?;
+        match oid.as_str() {
+            OID_SYS_UPTIME => {
+                record.insert("uptime".to_string(), decode_uint(value).into());
+            }
+            OID_SNMP_TRAP_OID if tag == TAG_OID => insert_trap_oid(record, decode_oid(value)),
+            _ => {
+                let name = mib::resolve(&oid).unwrap_or(oid);
+                record.insert(name, decode_value(tag, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn insert_trap_oid(record: &mut json::Map<String, json::Value>, trap_oid: String) {
+    if let Some(name) = mib::resolve(&trap_oid) {
+        record.insert("trap_name".to_string(), name.into());
+    }
+    record.insert("trap_oid".to_string(), trap_oid.into());
+}
+
+fn decode_value(tag: u8, value: &[u8]) -> json::Value {
+    match tag {
+        TAG_INTEGER => decode_int(value).into(),
+        TAG_OCTET_STRING => match std::str::from_utf8(value) {
+            Ok(s) if s.chars().all(|c| !c.is_control() || c.is_whitespace()) => s.into(),
+            _ => hex::encode(value).into(),
+        },
+        TAG_OID => {
+            let oid = decode_oid(value);
+            mib::resolve(&oid).unwrap_or(oid).into()
+        }
+        TAG_IP_ADDRESS if value.len() == 4 => Ipv4Addr::new(value[0], value[1], value[2], value[3])
+            .to_string()
+            .into(),
+        TAG_COUNTER32 | TAG_GAUGE32 | TAG_TIMETICKS | TAG_COUNTER64 => decode_uint(value).into(),
+        TAG_NULL | TAG_NO_SUCH_OBJECT | TAG_NO_SUCH_INSTANCE | TAG_END_OF_MIB_VIEW => {
+            json::Value::Null
+        }
+        _ => hex::encode(value).into(),
+    }
+}
+
+fn decode_int(value: &[u8]) -> i64 {
+    let init = if value.first().is_some_and(|b| b & 0x80 != 0) {
+        -1
+    } else {
+        0
+    };
+    value
+        .iter()
+        .take(8)
+        .fold(init, |acc, b| (acc << 8) | *b as i64)
+}
+
+fn decode_uint(value: &[u8]) -> u64 {
+    value
+        .iter()
+        .take(9)
+        .fold(0, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn decode_oid(value: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut sub: u64 = 0;
+    for b in value {
+        sub = (sub << 7) | (b & 0x7f) as u64;
+        if b & 0x80 != 0 {
+            continue;
+        }
+        if parts.is_empty() {
+            // the first sub-identifier packs the first two arcs
+            let first = (sub / 40).min(2);
+            parts.push(first);
+            parts.push(sub - first * 40);
+        } else {
+            parts.push(sub);
+        }
+        sub = 0;
+    }
+    parts
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Minimal BER reader over definite-length TLVs.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn next(&mut self) -> Result<(u8, &'a [u8])> {
+        let (&tag, rest) = self
+            .buf
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of packet"))?;
+        let (&first, mut rest) = rest
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of packet"))?;
+        let len = if first & 0x80 == 0 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                bail!("invalid ber length");
+            }
+            let len = rest[..n].iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+            rest = &rest[n..];
+            len
+        };
+        if rest.len() < len {
+            bail!("truncated ber value");
+        }
+        let (value, rest) = rest.split_at(len);
+        self.buf = rest;
+        Ok((tag, value))
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (got, value) = self.next()?;
+        if got != tag {
+            bail!("unexpected ber tag {got:#x}, expected {tag:#x}");
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if value.len() < 0x80 {
+            out.push(value.len() as u8);
+        } else {
+            out.push(0x82);
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+        out.extend_from_slice(value);
+        out
+    }
+
+    fn oid(arcs: &[u64]) -> Vec<u8> {
+        let mut out = vec![(arcs[0] * 40 + arcs[1]) as u8];
+        for arc in &arcs[2..] {
+            let mut bytes = vec![(arc & 0x7f) as u8];
+            let mut v = arc >> 7;
+            while v > 0 {
+                bytes.push((v & 0x7f) as u8 | 0x80);
+                v >>= 7;
+            }
+            bytes.reverse();
+            out.extend(bytes);
+        }
+        tlv(TAG_OID, &out)
+    }
+
+    fn varbind(name: &[u64], value: Vec<u8>) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &[oid(name), value].concat())
+    }
+
+    fn v2_pdu() -> Vec<u8> {
+        let varbinds = [
+            varbind(
+                &[1, 3, 6, 1, 2, 1, 1, 3, 0],
+                tlv(TAG_TIMETICKS, &[0x01, 0x00]),
+            ),
+            varbind(
+                &[1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0],
+                oid(&[1, 3, 6, 1, 6, 3, 1, 1, 5, 3]),
+            ),
+            varbind(
+                &[1, 3, 6, 1, 4, 1, 99999, 1, 2, 0],
+                tlv(TAG_INTEGER, &[0xfe]),
+            ),
+            varbind(
+                &[1, 3, 6, 1, 4, 1, 99999, 1, 3, 0],
+                tlv(TAG_OCTET_STRING, b"fan 1"),
+            ),
+        ]
+        .concat();
+        tlv(
+            PDU_TRAP_V2,
+            &[
+                tlv(TAG_INTEGER, &[1]),
+                tlv(TAG_INTEGER, &[0]),
+                tlv(TAG_INTEGER, &[0]),
+                tlv(TAG_SEQUENCE, &varbinds),
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn test_decode_v2c() {
+        let packet = tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_INTEGER, &[1]),
+                tlv(TAG_OCTET_STRING, b"public"),
+                v2_pdu(),
+            ]
+            .concat(),
+        );
+        let trap = decode(&packet, "10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(trap.community.as_deref(), Some("public"));
+        assert_eq!(trap.record["version"], "v2c");
+        assert_eq!(trap.record["uptime"], 256);
+        assert_eq!(trap.record["trap_oid"], "1.3.6.1.6.3.1.1.5.3");
+        assert_eq!(trap.record["trap_name"], "linkDown");
+        assert_eq!(trap.record["enterprises.99999.1.2.0"], -2);
+        assert_eq!(trap.record["enterprises.99999.1.3.0"], "fan 1");
+    }
+
+    #[test]
+    fn test_decode_v1() {
+        let pdu = tlv(
+            PDU_TRAP_V1,
+            &[
+                oid(&[1, 3, 6, 1, 4, 1, 99999]),
+                tlv(TAG_IP_ADDRESS, &[192, 168, 1, 5]),
+                tlv(TAG_INTEGER, &[6]),
+                tlv(TAG_INTEGER, &[3]),
+                tlv(TAG_TIMETICKS, &[0x10]),
+                tlv(TAG_SEQUENCE, &[]),
+            ]
+            .concat(),
+        );
+        let packet = tlv(
+            TAG_SEQUENCE,
+            &[tlv(TAG_INTEGER, &[0]), tlv(TAG_OCTET_STRING, b"dc1"), pdu].concat(),
+        );
+        let trap = decode(&packet, "10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(trap.record["version"], "v1");
+        assert_eq!(trap.record["agent_address"], "192.168.1.5");
+        assert_eq!(trap.record["trap_oid"], "1.3.6.1.4.1.99999.0.3");
+        assert_eq!(trap.record["uptime"], 16);
+    }
+
+    fn v3_packet(flags: u8) -> Vec<u8> {
+        let usm = tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_OCTET_STRING, &[0x80, 0x00, 0x1f, 0x88]),
+                tlv(TAG_INTEGER, &[1]),
+                tlv(TAG_INTEGER, &[2]),
+                tlv(TAG_OCTET_STRING, b"monitor"),
+                tlv(TAG_OCTET_STRING, &[0; 12]),
+                tlv(TAG_OCTET_STRING, &[]),
+            ]
+            .concat(),
+        );
+        let global = |flags: u8| {
+            tlv(
+                TAG_SEQUENCE,
+                &[
+                    tlv(TAG_INTEGER, &[7]),
+                    tlv(TAG_INTEGER, &[0x05, 0xdc]),
+                    tlv(TAG_OCTET_STRING, &[flags]),
+                    tlv(TAG_INTEGER, &[3]),
+                ]
+                .concat(),
+            )
+        };
+        let scoped = tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_OCTET_STRING, &[]),
+                tlv(TAG_OCTET_STRING, &[]),
+                v2_pdu(),
+            ]
+            .concat(),
+        );
+        tlv(
+            TAG_SEQUENCE,
+            &[
+                tlv(TAG_INTEGER, &[3]),
+                global(flags),
+                tlv(TAG_OCTET_STRING, &usm),
+                scoped,
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn test_decode_v3() {
+        let packet = v3_packet(SNMPV3_AUTH_FLAG);
+        let trap = decode(&packet, "10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(trap.engine_id.as_deref(), Some("80001f88"));
+        assert_eq!(trap.record["user"], "monitor");
+        assert_eq!(trap.record["trap_name"], "linkDown");
+        let usm = trap.usm.unwrap();
+        assert_eq!(usm.user, "monitor");
+        assert_eq!(&packet[usm.auth_params], &[0; 12]);
+
+        let source = "10.0.0.1".parse().unwrap();
+        assert!(decode(&v3_packet(0), source).is_err());
+        assert!(decode(&v3_packet(SNMPV3_AUTH_FLAG | SNMPV3_PRIV_FLAG), source).is_err());
+    }
+
+    #[test]
+    fn test_localize() {
+        // RFC 3414 A.3
+        let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        assert_eq!(
+            hex::encode(localize::<md5::Md5>(b"maplesyrup", &engine_id)),
+            "526f5eed9fcce26f8964c2930787d82b"
+        );
+        assert_eq!(
+            hex::encode(localize::<sha1::Sha1>(b"maplesyrup", &engine_id)),
+            "6695febc9288e36282235fc7151f128497b38f3f"
+        );
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let mut route = SnmpTrapRoute {
+            org_id: "default".to_string(),
+            stream_name: "snmp".to_string(),
+            community: String::new(),
+            engine_id: "80001f88".to_string(),
+            users: vec![SnmpUser {
+                name: "monitor".to_string(),
+                auth_protocol: SnmpAuthProtocol::Sha,
+                auth_password: "maplesyrup".to_string(),
+            }],
+            id: String::new(),
+        };
+        let mut packet = v3_packet(SNMPV3_AUTH_FLAG);
+        let usm = decode(&packet, "10.0.0.1".parse().unwrap())
+            .unwrap()
+            .usm
+            .unwrap();
+        let key = localized_key(&route.users[0], &usm.engine_id);
+        let mut mac = <Hmac<sha1::Sha1> as KeyInit>::new_from_slice(&key).unwrap();
+        mac.update(&packet);
+        let digest = mac.finalize().into_bytes();
+        packet[usm.auth_params.clone()].copy_from_slice(&digest[..12]);
+        assert!(authenticate(&packet, &usm, &route).is_ok());
+
+        // a tampered digest
+        let mut tampered = packet.clone();
+        tampered[usm.auth_params.start] ^= 0x01;
+        assert!(authenticate(&tampered, &usm, &route).is_err());
+        // a tampered message
+        let mut tampered = packet.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert!(authenticate(&tampered, &usm, &route).is_err());
+
+        // the key of another password or an unknown user
+        route.users[0].auth_password = "maplesyrup2".to_string();
+        assert!(authenticate(&packet, &usm, &route).is_err());
+        route.users[0].name = "admin".to_string();
+        assert!(authenticate(&packet, &usm, &route).is_err());
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        assert!(decode(&[0x30, 0x10, 0x02, 0x01], "10.0.0.1".parse().unwrap()).is_err());
+    }
+}
@@ -27,7 +27,7 @@ use config::{
 };
 use syslog_loose::{Message, ProcId, Protocol};
 
-use super::StreamMeta;
+use super::{routing::CrossOrgRecords, StreamMeta};
 use crate::{
     common::{
         infra::config::SYSLOG_ROUTES,
@@ -49,8 +49,8 @@ use crate::{
 
 pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     let start = std::time::Instant::now();
-    let ip = addr.ip();
-    let matching_route = get_org_for_ip(ip).await;
+    let parsed_msg = syslog_loose::parse_message(msg);
+    let matching_route = get_route(addr.ip(), parsed_msg.hostname);
 
     let route = match matching_route {
         Some(matching_route) => matching_route,
@@ -90,6 +90,14 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
             )),
         );
     }
+    if db::compact::rename::is_renaming(org_id, StreamType::Logs, stream_name) {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                format!("stream [{stream_name}] is being renamed"),
+            )),
+        );
+    }
 
     let mut stream_alerts_map: HashMap<String, Vec<Alert>> = HashMap::new();
     let mut stream_status = StreamStatus::new(stream_name);
@@ -105,6 +113,9 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     .await;
     let partition_keys = partition_det.partition_keys;
     let partition_time_level = partition_det.partition_time_level;
+    let drop_rules =
+        crate::service::ingestion::get_stream_drop_rules(org_id, &StreamType::Logs, stream_name)
+            .await;
 
     // Start get stream alerts
     crate::service::ingestion::get_stream_alerts(
@@ -125,13 +136,35 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
         stream_name,
     );
     // End Register Transforms for stream
+    let lineage = crate::service::lineage::register(
+        org_id,
+        StreamType::Logs,
+        stream_name,
+        None,
+        &local_trans,
+        &stream_vrl_map,
+    )
+    .await;
 
     let mut buf: HashMap<String, SchemaRecords> = HashMap::new();
 
-    let parsed_msg = syslog_loose::parse_message(msg);
     let mut value = message_to_value(parsed_msg);
     value = flatten::flatten_with_level(value, CONFIG.limit.ingest_flatten_level).unwrap();
 
+    // a message routed to another org is ingested with the settings of the target stream
+    let cross_org_routes = super::routing::cross_org_routes(org_id, stream_name).await;
+    let mut cross_org_records = CrossOrgRecords::default();
+    if !cross_org_routes.is_empty()
+        && cross_org_records
+            .route(org_id, stream_name, &cross_org_routes, &value)
+            .await
+    {
+        return Ok(HttpResponse::Ok().json(IngestionResponse::new(
+            http::StatusCode::OK.into(),
+            cross_org_records.ingest(org_id, thread_id, "").await,
+        )));
+    }
+
     if !local_trans.is_empty() {
         value = crate::service::ingestion::apply_stream_functions(
             &local_trans,
@@ -156,6 +189,17 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
         json::Value::Object(v) => v,
         _ => unreachable!(),
     };
+    if drop_rules.should_drop(&local_val) {
+        return Ok(HttpResponse::Ok().json(IngestionResponse::new(
+            http::StatusCode::OK.into(),
+            vec![stream_status],
+        )));
+    }
+
+    crate::service::ingestion::reserved::protect(
+        &mut local_val,
+        &mut stream_status.status.warnings,
+    );
 
     // handle timestamp
     let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
@@ -176,6 +220,9 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
         CONFIG.common.column_timestamp.clone(),
         json::Value::Number(timestamp.into()),
     );
+    if let Some(lineage) = lineage {
+        crate::service::lineage::attach(&mut local_val, lineage);
+    }
 
     let mut to_add_distinct_values = vec![];
     // get distinct_value item
@@ -201,6 +248,7 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
             partition_keys: &partition_keys,
             partition_time_level: &partition_time_level,
             stream_alerts_map: &stream_alerts_map,
+            reprocessed: false,
         },
         &mut stream_schema_map,
         &mut stream_status.status,
@@ -267,17 +315,28 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse> {
     )))
 }
 
-async fn get_org_for_ip(ip: std::net::IpAddr) -> Option<SyslogRoute> {
-    let mut matching_route = None;
-    for (_, route) in SYSLOG_ROUTES.clone() {
-        for subnet in &route.subnets {
-            if subnet.contains(ip) {
-                matching_route = Some(route);
-                break;
-            }
+/// Finds the route of a message. The hostname is set by the sender, so it only
+/// picks between the routes whose subnets contain the source ip, the ones
+/// matching the hostname win.
+fn get_route(ip: std::net::IpAddr, hostname: Option<&str>) -> Option<SyslogRoute> {
+    let routes = SYSLOG_ROUTES
+        .iter()
+        .filter(|route| route.subnets.iter().any(|subnet| subnet.contains(ip)))
+        .map(|route| route.value().clone())
+        .collect::<Vec<_>>();
+    select_route(routes, hostname)
+}
+
+fn select_route(mut routes: Vec<SyslogRoute>, hostname: Option<&str>) -> Option<SyslogRoute> {
+    if let Some(hostname) = hostname {
+        if let Some(pos) = routes
+            .iter()
+            .position(|route| route.matches_hostname(hostname))
+        {
+            return Some(routes.swap_remove(pos));
         }
     }
-    matching_route
+    routes.pop()
 }
 
 /// Create a `Value::Map` from the fields of the given syslog message.
@@ -328,7 +387,17 @@ fn message_to_value(message: Message<&str>) -> json::Value {
     for element in message.structured_data {
         let mut sdata = json::Map::new();
         for (name, value) in element.params() {
-            sdata.insert(name.to_string(), value.into());
+            match sdata.get_mut(name) {
+                // a param can repeat inside an element, keep all the values
+                Some(json::Value::Array(values)) => values.push(value.into()),
+                Some(existing) => {
+                    let first = existing.take();
+                    *existing = json::Value::Array(vec![first, value.into()]);
+                }
+                None => {
+                    sdata.insert(name.to_string(), value.into());
+                }
+            }
         }
         result.insert(element.id.to_string(), sdata.into());
     }
@@ -348,4 +417,36 @@ mod tests {
         let raw = r#"<190>2019-02-13T21:53:30.605850+00:00 74794bfb6795 liblogging-stdlog: [origin software="rsyslogd" swVersion="8.24.0" x-pid="9043" x-info="http://www.rsyslog.com"] This is a test message"#;
         ingest(raw, addr).await.unwrap();
     }
+
+    #[test]
+    fn test_structured_data_columns() {
+        let raw = r#"<165>1 2003-10-11T22:14:15.003Z fw01.corp evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application" eventSource="Audit"][origin ip="192.0.2.1"] An application event"#;
+        let value = message_to_value(syslog_loose::parse_message(raw));
+        assert_eq!(value["exampleSDID@32473"]["iut"], "3");
+        assert_eq!(
+            value["exampleSDID@32473"]["eventSource"],
+            json::json!(["Application", "Audit"])
+        );
+        assert_eq!(value["origin"]["ip"], "192.0.2.1");
+        assert_eq!(value["hostname"], "fw01.corp");
+        assert_eq!(value["message"], "An application event");
+    }
+
+    #[test]
+    fn test_select_route() {
+        let route = |stream_name: &str, hostnames: &[&str]| SyslogRoute {
+            org_id: "default".to_string(),
+            stream_name: stream_name.to_string(),
+            subnets: vec!["10.0.0.0/8".parse().unwrap()],
+            hostnames: hostnames.iter().map(|h| h.to_string()).collect(),
+            id: "".to_string(),
+        };
+        let routes = vec![route("firewall", &["fw-*"]), route("default", &[])];
+        let selected = select_route(routes.clone(), Some("fw-01")).unwrap();
+        assert_eq!(selected.stream_name, "firewall");
+        let selected = select_route(routes, Some("web-01")).unwrap();
+        assert_eq!(selected.stream_name, "default");
+        // a hostname never routes a message from outside the subnets
+        assert!(select_route(vec![], Some("fw-01")).is_none());
+    }
 }
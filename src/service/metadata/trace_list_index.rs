@@ -187,10 +187,37 @@ impl TraceListIndex {
                 routing: None,
                 flatten_level: None,
                 defined_schema_fields: None,
+                virtual_fields: vec![],
+                default_display_fields: vec![],
+                field_display: Default::default(),
+                nested_fields: vec![],
+                max_field_size: 0,
+                binary_fields: vec![],
+                encrypt_fields: vec![],
+                hot_data_days: 0,
+                drop_rules: vec![],
+                labels: Default::default(),
+                owner: None,
+                retain_original: false,
+                retention_lock_days: 0,
+                retention_lock_until: 0,
+                archive: false,
+                lineage: false,
+                field_metadata: Default::default(),
+                max_events_per_sec: 0,
+                max_mb_per_day: 0,
+                flatten_arrays: Default::default(),
+                flatten_separator: "".to_string(),
+                key_normalization: Default::default(),
+                otlp_attributes: Default::default(),
+                max_fields: 0,
+                promoted_fields: vec![],
+                parquet: Default::default(),
             };
 
-            stream::save_stream_settings(org_id, STREAM_NAME, StreamType::Metadata, settings)
-                .await?;
+            stream::save_stream_settings(org_id, STREAM_NAME, StreamType::Metadata, settings, None)
+                .await
+                .map_err(|e| infra::errors::Error::Message(e.to_string()))?;
         }
 
         self.db_schema_init.store(true, Ordering::Release);
@@ -55,6 +55,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -294,6 +295,19 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
         stream_status.status.successful += 1;
     }
 
+    // the records of a stream being renamed are rejected as a whole, for the
+    // client to send them again
+    if let Some(stream_name) = stream_data_buf
+        .keys()
+        .find(|name| db::compact::rename::is_renaming(org_id, StreamType::Metrics, name))
+    {
+        return Ok(IngestionResponse {
+            code: http::StatusCode::SERVICE_UNAVAILABLE.into(),
+            status: vec![],
+            error: Some(format!("stream [{stream_name}] is being renamed")),
+        });
+    }
+
     // write data to wal
     let time = start.elapsed().as_secs_f64();
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Metrics.to_string()).await;
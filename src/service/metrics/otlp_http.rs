@@ -94,6 +94,7 @@ pub async fn metrics_json_handler(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -472,6 +473,20 @@ pub async fn metrics_json_handler(
         }
     }
 
+    // the records of a stream being renamed are rejected as a whole, for the
+    // client to send them again
+    if let Some(stream_name) = metric_data_map
+        .keys()
+        .find(|name| db::compact::rename::is_renaming(org_id, StreamType::Metrics, name))
+    {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                format!("stream [{stream_name}] is being renamed"),
+            )),
+        );
+    }
+
     // write data to wal
     let time = start.elapsed().as_secs_f64();
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Metrics.to_string()).await;
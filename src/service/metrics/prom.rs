@@ -68,6 +68,7 @@ pub async fn remote_write(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Err(anyhow::anyhow!(
             "Quota exceeded for this organization [{}]",
             org_id
@@ -120,8 +121,16 @@ pub async fn remote_write(
             .unwrap();
     }
 
+    // native histograms are stored as the classic count, sum and bucket series
+    let mut timeseries = request.timeseries;
+    let histogram_series: Vec<_> = timeseries
+        .iter()
+        .flat_map(native_histogram_series)
+        .collect();
+    timeseries.extend(histogram_series);
+
     // maybe empty, we can return immediately
-    if request.timeseries.is_empty() {
+    if timeseries.is_empty() {
         let time = start.elapsed().as_secs_f64();
         metrics::HTTP_RESPONSE_TIME
             .with_label_values(&[
@@ -146,7 +155,7 @@ pub async fn remote_write(
 
     // parse timeseries
     let mut first_line = true;
-    for mut event in request.timeseries {
+    for mut event in timeseries {
         // get labels
         let mut replica_label = String::new();
 
@@ -393,6 +402,15 @@ pub async fn remote_write(
         }
     }
 
+    // the records of a stream being renamed are rejected as a whole, for the
+    // client to send them again
+    if let Some(stream_name) = metric_data_map
+        .keys()
+        .find(|name| db::compact::rename::is_renaming(org_id, StreamType::Metrics, name))
+    {
+        return Err(anyhow::anyhow!("stream [{stream_name}] is being renamed"));
+    }
+
     // write data to wal
     let time = start.elapsed().as_secs_f64();
     let writer = ingester::get_writer(thread_id, org_id, &StreamType::Metrics.to_string()).await;
@@ -459,6 +477,131 @@ pub async fn remote_write(
     Ok(())
 }
 
+/// Decomposes the native histograms of a time series into the `_count`,
+/// `_sum` and cumulative `_bucket` series of a classic histogram, one sample
+/// per histogram.
+fn native_histogram_series(event: &prometheus_rpc::TimeSeries) -> Vec<prometheus_rpc::TimeSeries> {
+    use prometheus_rpc::histogram::{Count, ZeroCount};
+
+    let Some(name) = event
+        .labels
+        .iter()
+        .find(|label| label.name == NAME_LABEL)
+        .map(|label| label.value.as_str())
+    else {
+        return vec![];
+    };
+    let series = |suffix: &str, le: Option<f64>, value: f64, timestamp: i64| {
+        let mut labels: Vec<_> = event
+            .labels
+            .iter()
+            .map(|label| prometheus_rpc::Label {
+                name: label.name.clone(),
+                value: if label.name == NAME_LABEL {
+                    format!("{name}_{suffix}")
+                } else {
+                    label.value.clone()
+                },
+            })
+            .collect();
+        if let Some(le) = le {
+            labels.push(prometheus_rpc::Label {
+                name: LE_LABEL.to_string(),
+                value: if le == f64::INFINITY {
+                    "+Inf".to_string()
+                } else {
+                    le.to_string()
+                },
+            });
+        }
+        prometheus_rpc::TimeSeries {
+            labels,
+            samples: vec![prometheus_rpc::Sample { value, timestamp }],
+            ..Default::default()
+        }
+    };
+
+    let mut ret = Vec::new();
+    for hist in event.histograms.iter() {
+        let count = match hist.count {
+            Some(Count::CountInt(v)) => v as f64,
+            Some(Count::CountFloat(v)) => v,
+            None => 0.0,
+        };
+        let zero_count = match hist.zero_count {
+            Some(ZeroCount::ZeroCountInt(v)) => v as f64,
+            Some(ZeroCount::ZeroCountFloat(v)) => v,
+            None => 0.0,
+        };
+        ret.push(series("count", None, count, hist.timestamp));
+        ret.push(series("sum", None, hist.sum, hist.timestamp));
+
+        // bucket i holds the values in (base^(i-1), base^i], the negative
+        // ones are mirrored, and the zero bucket the values up to the threshold
+        let base = 2_f64.powf(2_f64.powi(-hist.schema));
+        let mut buckets = Vec::new();
+        let negative = native_histogram_buckets(
+            &hist.negative_spans,
+            &hist.negative_deltas,
+            &hist.negative_counts,
+        );
+        for (index, value) in negative.into_iter().rev() {
+            buckets.push((-base.powi(index - 1), value));
+        }
+        buckets.push((hist.zero_threshold, zero_count));
+        let positive = native_histogram_buckets(
+            &hist.positive_spans,
+            &hist.positive_deltas,
+            &hist.positive_counts,
+        );
+        for (index, value) in positive {
+            buckets.push((base.powi(index), value));
+        }
+
+        let mut cumulative = 0.0;
+        for (le, value) in buckets {
+            cumulative += value;
+            ret.push(series("bucket", Some(le), cumulative, hist.timestamp));
+        }
+        ret.push(series("bucket", Some(f64::INFINITY), count, hist.timestamp));
+    }
+    ret
+}
+
+/// The index and count of the buckets of a native histogram, the counts are
+/// either absolute, for a float histogram, or deltas to the previous bucket.
+fn native_histogram_buckets(
+    spans: &[prometheus_rpc::BucketSpan],
+    deltas: &[i64],
+    counts: &[f64],
+) -> Vec<(i32, f64)> {
+    let counts: Vec<f64> = if counts.is_empty() {
+        deltas
+            .iter()
+            .scan(0, |count, delta| {
+                *count += delta;
+                Some(*count as f64)
+            })
+            .collect()
+    } else {
+        counts.to_vec()
+    };
+    let mut buckets = Vec::with_capacity(counts.len());
+    let mut index = 0;
+    let mut counts = counts.into_iter();
+    for span in spans {
+        index += span.offset;
+        for _ in 0..span.length {
+            let Some(count) = counts.next() else {
+                return buckets;
+            };
+            buckets.push((index, count));
+            index += 1;
+        }
+    }
+    buckets
+}
+
 pub(crate) async fn get_metadata(org_id: &str, req: RequestMetadata) -> Result<ResponseMetadata> {
     if req.limit == Some(0) {
         return Ok(hashbrown::HashMap::new());
@@ -869,3 +1012,72 @@ async fn prom_ha_handler(
 
     _accept_record
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_histogram_series() {
+        let event = prometheus_rpc::TimeSeries {
+            labels: vec![
+                prometheus_rpc::Label {
+                    name: NAME_LABEL.to_string(),
+                    value: "request_duration".to_string(),
+                },
+                prometheus_rpc::Label {
+                    name: "job".to_string(),
+                    value: "api".to_string(),
+                },
+            ],
+            histograms: vec![prometheus_rpc::Histogram {
+                count: Some(prometheus_rpc::histogram::Count::CountInt(7)),
+                sum: 2.5,
+                schema: 0,
+                zero_threshold: 0.001,
+                zero_count: Some(prometheus_rpc::histogram::ZeroCount::ZeroCountInt(1)),
+                negative_spans: vec![prometheus_rpc::BucketSpan {
+                    offset: 1,
+                    length: 1,
+                }],
+                negative_deltas: vec![3],
+                positive_spans: vec![prometheus_rpc::BucketSpan {
+                    offset: 0,
+                    length: 2,
+                }],
+                positive_deltas: vec![1, 1],
+                timestamp: 1_700_000_000_000,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let series = native_histogram_series(&event);
+        let values: Vec<_> = series
+            .iter()
+            .map(|ts| {
+                let name = &ts.labels[0].value;
+                let le = ts
+                    .labels
+                    .iter()
+                    .find(|l| l.name == LE_LABEL)
+                    .map(|l| l.value.as_str())
+                    .unwrap_or_default();
+                assert_eq!(ts.labels[1].value, "api");
+                assert_eq!(ts.samples[0].timestamp, 1_700_000_000_000);
+                (name.as_str(), le, ts.samples[0].value)
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("request_duration_count", "", 7.0),
+                ("request_duration_sum", "", 2.5),
+                ("request_duration_bucket", "-1", 3.0),
+                ("request_duration_bucket", "0.001", 4.0),
+                ("request_duration_bucket", "1", 5.0),
+                ("request_duration_bucket", "2", 7.0),
+                ("request_duration_bucket", "+Inf", 7.0),
+            ]
+        );
+    }
+}
@@ -21,27 +21,51 @@ use regex::Regex;
 use crate::common::meta::stream::StreamParams;
 
 pub mod alerts;
+pub mod autoscaling;
+pub mod cdc;
 pub mod compact;
+pub mod continuous_queries;
+pub mod correlation;
 pub mod dashboards;
 pub mod db;
+pub mod encryption;
 pub mod enrichment;
 pub mod enrichment_table;
+pub mod error;
 pub mod file_list;
+pub mod forecast;
 pub mod functions;
 pub mod ingestion;
+pub mod kafka;
+pub mod key_mappings;
 pub mod kv;
+pub mod large_fields;
+pub mod lineage;
 pub mod logs;
 pub mod metadata;
 pub mod metrics;
+pub mod monitors;
 pub mod organization;
+pub mod original;
 pub mod promql;
+pub mod quality_monitors;
+pub mod record_id;
+pub mod revisions;
+pub mod sample_data;
 pub mod schema;
 pub mod search;
+pub mod search_templates;
+pub mod short_url;
+pub mod sigma;
+pub mod slo;
+pub mod snmp;
 pub mod stream;
 pub mod syslogs_route;
+pub mod threat_intel;
 pub mod traces;
 pub mod usage;
 pub mod users;
+pub mod webhooks;
 
 const MAX_KEY_LENGTH: usize = 100;
 
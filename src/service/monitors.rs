@@ -0,0 +1,270 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use actix_web::{http, web};
+use config::{utils::json, CONFIG};
+use tokio::{net::TcpStream, process::Command, time};
+
+use crate::{
+    common::meta::{
+        ingestion::IngestionRequest,
+        monitors::{CheckType, Monitor, MONITORS_STREAM},
+    },
+    service::{db, logs},
+};
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut monitor: Monitor,
+    create: bool,
+) -> Result<(), (http::StatusCode, anyhow::Error)> {
+    if !name.is_empty() {
+        monitor.name = name.to_string();
+    }
+    if let Err(e) = validate(&monitor) {
+        return Err((http::StatusCode::BAD_REQUEST, e));
+    }
+
+    match db::monitors::get(org_id, &monitor.name).await {
+        Ok(_) => {
+            if create {
+                return Err((
+                    http::StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Monitor already exists"),
+                ));
+            }
+        }
+        Err(_) => {
+            if !create {
+                return Err((
+                    http::StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Monitor not found"),
+                ));
+            }
+        }
+    }
+
+    db::monitors::set(org_id, &monitor)
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<Monitor, anyhow::Error> {
+    db::monitors::get(org_id, name)
+        .await
+        .map_err(|_| anyhow::anyhow!("Monitor not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<Monitor>, anyhow::Error> {
+    db::monitors::list(org_id).await
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), (http::StatusCode, anyhow::Error)> {
+    if db::monitors::get(org_id, name).await.is_err() {
+        return Err((
+            http::StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Monitor not found {}", name),
+        ));
+    }
+    db::monitors::delete(org_id, name)
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+fn validate(monitor: &Monitor) -> Result<(), anyhow::Error> {
+    if monitor.name.is_empty() {
+        return Err(anyhow::anyhow!("Monitor name is required"));
+    }
+    if monitor.name.contains('/') {
+        return Err(anyhow::anyhow!("Monitor name cannot contain '/'"));
+    }
+    if monitor.target.trim().is_empty() {
+        return Err(anyhow::anyhow!("Monitor target is required"));
+    }
+    if monitor.interval < CONFIG.monitors.min_interval {
+        return Err(anyhow::anyhow!(
+            "Monitor interval should be at least {} seconds",
+            CONFIG.monitors.min_interval
+        ));
+    }
+    if monitor.timeout <= 0 || monitor.timeout > monitor.interval {
+        return Err(anyhow::anyhow!(
+            "Monitor timeout should be between 1 second and the interval"
+        ));
+    }
+    match monitor.check_type {
+        CheckType::Http => {
+            if !monitor.target.starts_with("http://") && !monitor.target.starts_with("https://") {
+                return Err(anyhow::anyhow!("Http monitor target should be a url"));
+            }
+        }
+        CheckType::Tcp => {
+            if monitor
+                .target
+                .rsplit_once(':')
+                .and_then(|(_, port)| port.parse::<u16>().ok())
+                .is_none()
+            {
+                return Err(anyhow::anyhow!("Tcp monitor target should be host:port"));
+            }
+        }
+        CheckType::Icmp => {
+            // the target is handed to `ping`, don't let it look like an option
+            if monitor.target.starts_with('-') || monitor.target.contains(char::is_whitespace) {
+                return Err(anyhow::anyhow!("Icmp monitor target should be a host"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs a check once and writes the result into the `_monitors` stream, so
+/// failures can be alerted on like any other log record.
+pub async fn run_check(org_id: &str, monitor: &Monitor) -> Result<(), anyhow::Error> {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(monitor.timeout as u64);
+    let (status_code, error) = match check(monitor, timeout).await {
+        Ok(code) => (code, None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let took = start.elapsed().as_millis() as u64;
+
+    let mut record = json::Map::new();
+    record.insert("monitor".to_string(), monitor.name.to_string().into());
+    record.insert("type".to_string(), monitor.check_type.to_string().into());
+    record.insert("target".to_string(), monitor.target.to_string().into());
+    record.insert(
+        "region".to_string(),
+        CONFIG.monitors.region.to_string().into(),
+    );
+    record.insert(
+        "node".to_string(),
+        CONFIG.common.instance_name.to_string().into(),
+    );
+    let status = if error.is_none() { "up" } else { "down" };
+    record.insert("status".to_string(), status.into());
+    record.insert("latency_ms".to_string(), took.into());
+    if let Some(code) = status_code {
+        record.insert("status_code".to_string(), code.into());
+    }
+    if let Some(error) = error {
+        record.insert("error".to_string(), error.into());
+    }
+
+    let body = web::Bytes::from(json::to_vec(&vec![record])?);
+    let resp = logs::ingest::ingest(
+        org_id,
+        MONITORS_STREAM,
+        IngestionRequest::JSON(&body),
+        0,
+        "",
+    )
+    .await?;
+    if let Some(e) = resp.error {
+        return Err(anyhow::anyhow!(e));
+    }
+    Ok(())
+}
+
+/// Returns the http status code for `http` checks, `None` otherwise.
+async fn check(monitor: &Monitor, timeout: Duration) -> Result<Option<u16>, anyhow::Error> {
+    match monitor.check_type {
+        CheckType::Http => {
+            let client = reqwest::Client::builder().timeout(timeout).build()?;
+            let code = client.get(&monitor.target).send().await?.status().as_u16();
+            let ok = match monitor.expected_status {
+                Some(expected) => code == expected,
+                None => (200..400).contains(&code),
+            };
+            if !ok {
+                return Err(anyhow::anyhow!("unexpected status code {code}"));
+            }
+            Ok(Some(code))
+        }
+        CheckType::Tcp => {
+            time::timeout(timeout, TcpStream::connect(&monitor.target))
+                .await
+                .map_err(|_| anyhow::anyhow!("connection timed out"))??;
+            Ok(None)
+        }
+        CheckType::Icmp => {
+            // raw sockets need extra privileges, the system ping already has them
+            let output = Command::new("ping")
+                .args([
+                    "-c",
+                    "1",
+                    "-W",
+                    &monitor.timeout.to_string(),
+                    &monitor.target,
+                ])
+                .kill_on_drop(true)
+                .output();
+            let output = time::timeout(timeout + Duration::from_secs(1), output)
+                .await
+                .map_err(|_| anyhow::anyhow!("ping timed out"))??;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!("host unreachable"));
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(check_type: CheckType, target: &str) -> Monitor {
+        Monitor {
+            name: "api".to_string(),
+            check_type,
+            target: target.to_string(),
+            interval: 60,
+            timeout: 10,
+            regions: vec![],
+            expected_status: None,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&monitor(CheckType::Http, "https://example.com/health")).is_ok());
+        assert!(validate(&monitor(CheckType::Http, "example.com")).is_err());
+        assert!(validate(&monitor(CheckType::Tcp, "db.local:5432")).is_ok());
+        assert!(validate(&monitor(CheckType::Tcp, "db.local")).is_err());
+        assert!(validate(&monitor(CheckType::Icmp, "10.0.0.1")).is_ok());
+        assert!(validate(&monitor(CheckType::Icmp, "-f 10.0.0.1")).is_err());
+
+        let mut m = monitor(CheckType::Http, "https://example.com");
+        m.interval = 1;
+        assert!(validate(&m).is_err());
+        m.interval = 60;
+        m.name = "a/b".to_string();
+        assert!(validate(&m).is_err());
+    }
+
+    #[test]
+    fn test_runs_in() {
+        let mut m = monitor(CheckType::Http, "https://example.com");
+        assert!(m.runs_in("us-east"));
+        m.regions = vec!["eu-west".to_string()];
+        assert!(!m.runs_in("us-east"));
+        assert!(m.runs_in("eu-west"));
+    }
+}
@@ -15,26 +15,53 @@
 
 use std::io::{Error, ErrorKind};
 
-use config::{meta::stream::StreamType, utils::rand::generate_random_string};
+use config::{
+    meta::stream::StreamType,
+    utils::{json, rand::generate_random_string},
+};
 
 use crate::{
     common::{
         infra::config::USERS_RUM_TOKEN,
         meta::{
             organization::{
-                IngestionPasscode, IngestionTokensContainer, OrgSummary, Organization,
-                RumIngestionToken,
+                Feature, IngestionPasscode, IngestionTokensContainer, OrgSummary, Organization,
+                OrganizationSetting, RumIngestionToken,
             },
             user::UserOrg,
         },
         utils::auth::is_root_user,
     },
-    service::{db, stream::get_streams},
+    service::{db, error::ServiceError, stream::get_streams},
 };
 
+/// Returns the org settings, or the defaults when they were never saved.
+pub async fn get_setting(org_id: &str) -> OrganizationSetting {
+    match db::organization::get_org_setting(org_id).await {
+        Ok(v) => json::from_slice(&v).unwrap_or_default(),
+        Err(_) => OrganizationSetting::default(),
+    }
+}
+
+/// Whether a feature which is rolled out per org is enabled for the org.
+pub fn is_feature_enabled(org_id: &str, feature: Feature) -> bool {
+    db::organization::get_features(org_id).is_enabled(feature)
+}
+
+/// Fails with `Forbidden` when the feature isn't enabled for the org.
+pub fn check_feature(org_id: &str, feature: Feature) -> Result<(), ServiceError> {
+    if is_feature_enabled(org_id, feature) {
+        Ok(())
+    } else {
+        Err(ServiceError::Forbidden(format!(
+            "feature [{feature}] is not enabled for the organization"
+        )))
+    }
+}
+
 #[tracing::instrument]
 pub async fn get_summary(org_id: &str) -> OrgSummary {
-    let streams = get_streams(org_id, None, false, None).await;
+    let streams = get_streams(org_id, None, false, None, true).await;
     let functions = db::functions::list(org_id).await.unwrap();
     let alerts = db::alerts::list(org_id, None, None).await.unwrap();
     let mut num_streams = 0;
@@ -264,4 +291,27 @@ mod tests {
         let resp = update_passcode(Some(org_id), user_id).await.unwrap();
         assert_ne!(resp.passcode, passcode);
     }
+
+    #[tokio::test]
+    async fn test_feature_flags() {
+        let org_id = "test_feature_flags";
+        assert!(is_feature_enabled(org_id, Feature::DeleteRecords));
+        assert!(check_feature(org_id, Feature::DeleteRecords).is_ok());
+
+        infra_db::create_table().await.unwrap();
+        let mut flags = crate::common::meta::organization::OrgFeatureFlags::default();
+        flags.features.insert(Feature::DeleteRecords, false);
+        db::organization::set_features(org_id, &flags)
+            .await
+            .unwrap();
+        assert!(!is_feature_enabled(org_id, Feature::DeleteRecords));
+        assert!(is_feature_enabled(org_id, Feature::StreamFunctions));
+        assert!(is_feature_enabled(org_id, Feature::Tail));
+        assert!(matches!(
+            check_feature(org_id, Feature::DeleteRecords),
+            Err(ServiceError::Forbidden(_))
+        ));
+        // other orgs keep the defaults
+        assert!(is_feature_enabled("default", Feature::DeleteRecords));
+    }
 }
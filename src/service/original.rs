@@ -0,0 +1,231 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use config::{
+    ider,
+    meta::{search, stream::StreamType},
+    utils::{
+        base64, json,
+        json::{Map, Value},
+    },
+    CONFIG,
+};
+use infra::schema::STREAM_SETTINGS;
+
+use crate::service::{
+    encryption::{self, ENCRYPTED_VALUE_PREFIX},
+    error::{Result, ServiceError},
+    format_stream_name, large_fields, search as SearchService,
+};
+
+/// Column holding the compressed record as it was received, before the functions ran.
+pub const ORIGINAL_COLUMN: &str = "_original";
+/// Column holding the id used to fetch the original record back.
+pub const ORIGINAL_ID_COLUMN: &str = "_original_id";
+
+/// Returns whether the stream keeps the original of the ingested records.
+pub async fn is_enabled(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STREAM_SETTINGS
+        .read()
+        .await
+        .get(&key)
+        .map(|s| s.retain_original)
+        .unwrap_or_default()
+}
+
+/// Returns whether the original of the stream records is encrypted, which is the case
+/// when the stream has encrypted fields.
+pub async fn is_encrypted(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    !encryption::get_encrypt_fields(org_id, stream_type, stream_name)
+        .await
+        .is_empty()
+}
+
+/// Compresses the received record into the value stored in the original column,
+/// encrypted as a whole when the stream encrypts some of its fields.
+pub async fn capture(org_id: &str, value: &Value, encrypt: bool) -> Result<String> {
+    let encoded = encode(value)?;
+    if !encrypt {
+        return Ok(encoded);
+    }
+    let key = encryption::get_or_create_key(org_id).await?;
+    Ok(encryption::encrypt_value(&key, &encoded))
+}
+
+/// Restores the received record from the value stored in the original column, the
+/// value can be offloaded as a large field and encrypted.
+pub async fn restore(org_id: &str, value: &str) -> Result<Value> {
+    let stored = load(org_id, value).await?;
+    unseal(org_id, &stored).await
+}
+
+/// Fetches the full stored value when it was offloaded as a large field.
+async fn load(org_id: &str, value: &str) -> Result<String> {
+    let Some(reference) = large_fields::parse_reference(value) else {
+        return Ok(value.to_string());
+    };
+    let data = large_fields::get(org_id, reference).await?;
+    String::from_utf8(data.to_vec()).map_err(ServiceError::internal)
+}
+
+async fn unseal(org_id: &str, stored: &str) -> Result<Value> {
+    if !stored.starts_with(ENCRYPTED_VALUE_PREFIX) {
+        return Ok(decode(stored)?);
+    }
+    let Some(key) = encryption::get_key(org_id).await? else {
+        return Err(ServiceError::internal(format!(
+            "encryption key of org {org_id} not found"
+        )));
+    };
+    let Some(encoded) = encryption::decrypt_value(&key, stored) else {
+        return Err(ServiceError::internal("invalid encrypted original record"));
+    };
+    Ok(decode(&encoded)?)
+}
+
+/// Compresses the received record into the value stored in the original column.
+pub fn encode(value: &Value) -> Result<String, anyhow::Error> {
+    let data = json::to_vec(value)?;
+    let buf = zstd::encode_all(data.as_slice(), 3)?;
+    Ok(base64::encode_raw(&buf))
+}
+
+/// Restores the received record from the value stored in the original column.
+pub fn decode(value: &str) -> Result<Value, anyhow::Error> {
+    let buf = base64::decode_raw(value)?;
+    let data = zstd::decode_all(buf.as_slice())?;
+    Ok(json::from_slice(&data)?)
+}
+
+/// Stores the encoded original in the record with an id starting with the record
+/// timestamp, so fetching it back only scans the files of that time.
+pub fn attach(record: &mut Map<String, Value>, original: String) {
+    let timestamp = record
+        .get(&CONFIG.common.column_timestamp)
+        .and_then(|v| v.as_i64())
+        .unwrap_or_default();
+    record.insert(
+        ORIGINAL_ID_COLUMN.to_string(),
+        Value::String(format!("{timestamp}-{}", ider::generate())),
+    );
+    record.insert(ORIGINAL_COLUMN.to_string(), Value::String(original));
+}
+
+fn parse_id(id: &str) -> Option<i64> {
+    let (timestamp, rest) = id.split_once('-')?;
+    if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    timestamp.parse().ok()
+}
+
+/// Fetches the original of the record with the given id as the user, `None` if there
+/// is no such record. An encrypted original is only returned to the users allowed to
+/// decrypt the fields of the stream.
+pub async fn get(
+    org_id: &str,
+    user_id: &str,
+    stream_name: &str,
+    id: &str,
+) -> Result<Option<Value>> {
+    let Some(timestamp) = parse_id(id) else {
+        return Err(ServiceError::bad_request(format!(
+            "invalid original record id: {id}"
+        )));
+    };
+    if format_stream_name(stream_name) != stream_name {
+        return Err(ServiceError::bad_request(format!(
+            "invalid stream name: {stream_name}"
+        )));
+    }
+    let query = search::Query {
+        sql: format!(
+            "SELECT \"{ORIGINAL_COLUMN}\" FROM \"{stream_name}\" WHERE \"{ORIGINAL_ID_COLUMN}\" = '{id}'"
+        ),
+        start_time: timestamp,
+        end_time: timestamp + 1,
+        size: 1,
+        sql_mode: "full".to_owned(),
+        ..Default::default()
+    };
+    let req = search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search(
+        "",
+        org_id,
+        StreamType::Logs,
+        Some(user_id.to_string()),
+        &req,
+    )
+    .await?;
+    let Some(original) = res
+        .hits
+        .first()
+        .and_then(|hit| hit.get(ORIGINAL_COLUMN))
+        .and_then(|v| v.as_str())
+    else {
+        return Ok(None);
+    };
+    let stored = load(org_id, original).await?;
+    if stored.starts_with(ENCRYPTED_VALUE_PREFIX)
+        && !encryption::can_decrypt(org_id, Some(user_id), StreamType::Logs, stream_name).await
+    {
+        return Err(ServiceError::Forbidden(
+            "the original record has encrypted fields".to_string(),
+        ));
+    }
+    unseal(org_id, &stored).await.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let value = json::json!({"message": "login failed", "user": {"name": "root"}});
+        let encoded = encode(&value).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), value);
+    }
+
+    #[tokio::test]
+    async fn test_restore() {
+        let value = json::json!({"message": "login failed"});
+        let encoded = capture("default", &value, false).await.unwrap();
+        assert_eq!(restore("default", &encoded).await.unwrap(), value);
+    }
+
+    #[test]
+    fn test_parse_id() {
+        let mut record = Map::new();
+        record.insert(
+            CONFIG.common.column_timestamp.clone(),
+            Value::Number(1700000000000000i64.into()),
+        );
+        attach(&mut record, "abc".to_string());
+        let id = record.get(ORIGINAL_ID_COLUMN).unwrap().as_str().unwrap();
+        assert_eq!(parse_id(id), Some(1700000000000000));
+        assert_eq!(parse_id("1700000000000000-x' OR 1=1"), None);
+        assert_eq!(parse_id("abc"), None);
+    }
+}
@@ -0,0 +1,538 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use actix_web::{http, web};
+use chrono::Utc;
+use config::{
+    meta::{search, stream::StreamType},
+    utils::{
+        json,
+        json::{Map, Value},
+    },
+    CONFIG,
+};
+
+use crate::{
+    common::meta::{
+        alerts::Alert,
+        ingestion::IngestionRequest,
+        quality_monitors::{Heartbeat, QualityCheck, QualityMonitor, HEARTBEATS_STREAM},
+    },
+    service::{alerts::destinations, db, logs, search as SearchService},
+};
+
+/// Shippers which sent heartbeats in the last day are expected to keep sending them
+const KNOWN_SHIPPERS_MINUTES: i64 = 24 * 60;
+
+const MAX_SHIPPERS: usize = 10_000;
+
+pub async fn save(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+    mut monitor: QualityMonitor,
+    create: bool,
+) -> Result<(), (http::StatusCode, anyhow::Error)> {
+    monitor.stream_name = stream_name.to_string();
+    if !name.is_empty() {
+        monitor.name = name.to_string();
+    }
+    if let Err(e) = validate(&monitor) {
+        return Err((http::StatusCode::BAD_REQUEST, e));
+    }
+    for dest in monitor.destinations.iter() {
+        if destinations::get(org_id, dest).await.is_err() {
+            return Err((
+                http::StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Alert destination {dest} not found"),
+            ));
+        }
+    }
+
+    match db::quality_monitors::get(org_id, monitor.stream_type, stream_name, &monitor.name).await {
+        Ok(old) => {
+            if create {
+                return Err((
+                    http::StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Quality monitor already exists"),
+                ));
+            }
+            // keep the state of the check unless the check itself changed
+            if old.check == monitor.check {
+                monitor.failing = old.failing;
+                monitor.known_fields = old.known_fields;
+                monitor.last_checked_at = old.last_checked_at;
+            }
+        }
+        Err(_) => {
+            if !create {
+                return Err((
+                    http::StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Quality monitor not found"),
+                ));
+            }
+        }
+    }
+
+    db::quality_monitors::set(org_id, &monitor, create)
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn get(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<QualityMonitor, anyhow::Error> {
+    db::quality_monitors::get(org_id, stream_type, stream_name, name)
+        .await
+        .map_err(|_| anyhow::anyhow!("Quality monitor not found"))
+}
+
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<QualityMonitor>, anyhow::Error> {
+    db::quality_monitors::list(org_id, stream_type, stream_name).await
+}
+
+pub async fn delete(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    name: &str,
+) -> Result<(), (http::StatusCode, anyhow::Error)> {
+    if db::quality_monitors::get(org_id, stream_type, stream_name, name)
+        .await
+        .is_err()
+    {
+        return Err((
+            http::StatusCode::NOT_FOUND,
+            anyhow::anyhow!("Quality monitor not found {}", name),
+        ));
+    }
+    db::quality_monitors::delete(org_id, stream_type, stream_name, name)
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+fn validate(monitor: &QualityMonitor) -> Result<(), anyhow::Error> {
+    if monitor.name.is_empty() {
+        return Err(anyhow::anyhow!("Quality monitor name is required"));
+    }
+    if monitor.name.contains('/') {
+        return Err(anyhow::anyhow!("Quality monitor name cannot contain '/'"));
+    }
+    if monitor.interval < 60 {
+        return Err(anyhow::anyhow!(
+            "Quality monitor interval should be at least 60 seconds"
+        ));
+    }
+    if monitor.destinations.is_empty() {
+        return Err(anyhow::anyhow!("Quality monitor destinations are required"));
+    }
+    match &monitor.check {
+        QualityCheck::IngestionStopped { minutes } => {
+            if *minutes <= 0 {
+                return Err(anyhow::anyhow!("minutes should be greater than 0"));
+            }
+        }
+        QualityCheck::NullRatio {
+            field,
+            max_percent,
+            minutes,
+        } => {
+            if field.is_empty() || field.contains('"') {
+                return Err(anyhow::anyhow!("field is required"));
+            }
+            if !(0.0..100.0).contains(max_percent) {
+                return Err(anyhow::anyhow!("max_percent should be between 0 and 100"));
+            }
+            if *minutes <= 0 {
+                return Err(anyhow::anyhow!("minutes should be greater than 0"));
+            }
+        }
+        QualityCheck::ShipperStopped { minutes } => {
+            if *minutes <= 0 {
+                return Err(anyhow::anyhow!("minutes should be greater than 0"));
+            }
+        }
+        QualityCheck::SchemaChanged => {}
+    }
+    Ok(())
+}
+
+/// Writes the heartbeat of a shipper of the stream into the `_heartbeats`
+/// stream, where `shipper_stopped` checks look for it.
+pub async fn heartbeat(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    heartbeat: Heartbeat,
+) -> Result<(), anyhow::Error> {
+    let record = heartbeat_record(stream_type, stream_name, heartbeat)?;
+    let body = web::Bytes::from(json::to_vec(&vec![record])?);
+    let resp = logs::ingest::ingest(
+        org_id,
+        HEARTBEATS_STREAM,
+        IngestionRequest::JSON(&body),
+        0,
+        "",
+    )
+    .await?;
+    if let Some(e) = resp.error {
+        return Err(anyhow::anyhow!(e));
+    }
+    Ok(())
+}
+
+fn heartbeat_record(
+    stream_type: StreamType,
+    stream_name: &str,
+    heartbeat: Heartbeat,
+) -> Result<Map<String, Value>, anyhow::Error> {
+    if heartbeat.shipper.trim().is_empty() {
+        return Err(anyhow::anyhow!("shipper is required"));
+    }
+    let mut record = heartbeat.attributes;
+    record.insert("shipper".to_string(), heartbeat.shipper.into());
+    record.insert("stream".to_string(), stream_name.into());
+    record.insert("stream_type".to_string(), stream_type.to_string().into());
+    Ok(record)
+}
+
+/// Runs the check of the monitor, notifies its destinations when the check
+/// starts failing and saves the new state of the monitor.
+pub async fn run(org_id: &str, monitor: &mut QualityMonitor) -> Result<(), anyhow::Error> {
+    let failure = check(org_id, monitor).await?;
+    if let Some(message) = &failure {
+        if !monitor.failing {
+            notify(org_id, monitor, message).await?;
+        }
+    }
+    monitor.failing = failure.is_some();
+    monitor.last_checked_at = Some(Utc::now().timestamp_micros());
+    db::quality_monitors::set_without_updating_trigger(org_id, monitor).await
+}
+
+/// Returns the reason the check failed, if it did
+async fn check(
+    org_id: &str,
+    monitor: &mut QualityMonitor,
+) -> Result<Option<String>, anyhow::Error> {
+    let stream_name = monitor.stream_name.as_str();
+    let schema = infra::schema::get(org_id, stream_name, monitor.stream_type).await?;
+    match &monitor.check {
+        QualityCheck::IngestionStopped { minutes } => {
+            let sql = format!("SELECT COUNT(*) AS total FROM \"{stream_name}\"");
+            let hit = query(org_id, monitor.stream_type, sql, *minutes, 1)
+                .await?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            let total = hit.get("total").and_then(|v| v.as_i64()).unwrap_or(0);
+            Ok((total == 0)
+                .then(|| format!("no records were ingested in the last {minutes} minutes")))
+        }
+        QualityCheck::NullRatio {
+            field,
+            max_percent,
+            minutes,
+        } => {
+            // a field missing from the schema is null in every record
+            let sql = if schema.field_with_name(field).is_ok() {
+                format!(
+                    "SELECT COUNT(*) AS total, COUNT(\"{field}\") AS present FROM \"{stream_name}\""
+                )
+            } else {
+                format!("SELECT COUNT(*) AS total FROM \"{stream_name}\"")
+            };
+            let hit = query(org_id, monitor.stream_type, sql, *minutes, 1)
+                .await?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            let total = hit.get("total").and_then(|v| v.as_i64()).unwrap_or(0);
+            let present = hit.get("present").and_then(|v| v.as_i64()).unwrap_or(0);
+            if total == 0 {
+                return Ok(None);
+            }
+            let percent = (total - present) as f64 * 100.0 / total as f64;
+            Ok((percent > *max_percent).then(|| {
+                format!(
+                    "{field} is null in {percent:.1}% of the records of the last {minutes} minutes, above {max_percent}%"
+                )
+            }))
+        }
+        QualityCheck::ShipperStopped { minutes } => {
+            let heartbeats =
+                infra::schema::get(org_id, HEARTBEATS_STREAM, StreamType::Logs).await?;
+            if heartbeats.fields().is_empty() {
+                return Ok(None);
+            }
+            let sql = format!(
+                "SELECT shipper, MAX(\"{}\") AS last_seen FROM \"{HEARTBEATS_STREAM}\" WHERE stream = '{}' AND stream_type = '{}' GROUP BY shipper",
+                CONFIG.common.column_timestamp,
+                stream_name.replace('\'', "''"),
+                monitor.stream_type
+            );
+            let rows = query(
+                org_id,
+                StreamType::Logs,
+                sql,
+                known_shippers_minutes(*minutes),
+                MAX_SHIPPERS,
+            )
+            .await?;
+            let stopped = stopped_shippers(&rows, Utc::now().timestamp_micros(), *minutes);
+            Ok((!stopped.is_empty()).then(|| {
+                format!(
+                    "shippers stopped sending heartbeats for {minutes} minutes: {}",
+                    stopped.join(", ")
+                )
+            }))
+        }
+        QualityCheck::SchemaChanged => {
+            let mut fields = schema
+                .fields()
+                .iter()
+                .map(|f| format!("{}:{}", f.name(), f.data_type()))
+                .collect::<Vec<_>>();
+            fields.sort();
+            let known_fields = std::mem::replace(&mut monitor.known_fields, fields);
+            // the first check only records the schema
+            if known_fields.is_empty() {
+                return Ok(None);
+            }
+            Ok(schema_changes(&known_fields, &monitor.known_fields))
+        }
+    }
+}
+
+/// Describes the fields added and removed between two sorted field lists
+fn schema_changes(old: &[String], new: &[String]) -> Option<String> {
+    let added = new.iter().filter(|f| !old.contains(f)).collect::<Vec<_>>();
+    let removed = old.iter().filter(|f| !new.contains(f)).collect::<Vec<_>>();
+    let mut changes = Vec::new();
+    if !added.is_empty() {
+        changes.push(format!(
+            "added: {}",
+            added
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if !removed.is_empty() {
+        changes.push(format!(
+            "removed: {}",
+            removed
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    (!changes.is_empty()).then(|| format!("schema changed, {}", changes.join("; ")))
+}
+
+/// The shippers whose heartbeats are looked for: the ones which sent one in
+/// the last day, or in twice the check window when it is longer. A shipper
+/// silent for longer is forgotten and no longer reported.
+fn known_shippers_minutes(minutes: i64) -> i64 {
+    KNOWN_SHIPPERS_MINUTES.max(minutes * 2)
+}
+
+/// The shippers whose last heartbeat, per `shipper` and `last_seen` of the
+/// rows, is older than `minutes` before `now`, sorted.
+fn stopped_shippers(rows: &[Map<String, Value>], now: i64, minutes: i64) -> Vec<&str> {
+    let deadline = now - minutes * 60 * 1_000_000;
+    let mut stopped = rows
+        .iter()
+        .filter(|row| {
+            row.get("last_seen")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default()
+                < deadline
+        })
+        .filter_map(|row| row.get("shipper").and_then(|v| v.as_str()))
+        .collect::<Vec<_>>();
+    stopped.sort();
+    stopped
+}
+
+/// Runs an aggregation over the last minutes and returns its rows
+async fn query(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: String,
+    minutes: i64,
+    size: usize,
+) -> Result<Vec<Map<String, Value>>, anyhow::Error> {
+    let end_time = Utc::now().timestamp_micros();
+    let req = search::Request {
+        query: search::Query {
+            sql,
+            size,
+            start_time: end_time - minutes * 60 * 1_000_000,
+            end_time,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search("", org_id, stream_type, None, &req)
+        .await
+        .map_err(|e| anyhow::anyhow!("search error: {e}"))?;
+    Ok(res
+        .hits
+        .into_iter()
+        .filter_map(|v| match v {
+            Value::Object(v) => Some(v),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Sends the failure through the alert destinations of the monitor
+async fn notify(
+    org_id: &str,
+    monitor: &QualityMonitor,
+    message: &str,
+) -> Result<(), anyhow::Error> {
+    let alert = Alert {
+        name: monitor.name.clone(),
+        org_id: org_id.to_string(),
+        stream_type: monitor.stream_type,
+        stream_name: monitor.stream_name.clone(),
+        destinations: monitor.destinations.clone(),
+        description: message.to_string(),
+        enabled: true,
+        ..Default::default()
+    };
+    let mut row = Map::new();
+    row.insert("monitor".to_string(), monitor.name.clone().into());
+    row.insert("check".to_string(), monitor.check.to_string().into());
+    row.insert("stream".to_string(), monitor.stream_name.clone().into());
+    row.insert("message".to_string(), message.into());
+    alert.send_notification(&[row]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(check: QualityCheck) -> QualityMonitor {
+        QualityMonitor {
+            name: "orders".to_string(),
+            stream_type: StreamType::Logs,
+            stream_name: "default".to_string(),
+            check,
+            interval: 300,
+            destinations: vec!["slack".to_string()],
+            enabled: true,
+            failing: false,
+            known_fields: vec![],
+            last_checked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&monitor(QualityCheck::IngestionStopped { minutes: 10 })).is_ok());
+        assert!(validate(&monitor(QualityCheck::IngestionStopped { minutes: 0 })).is_err());
+        assert!(
+            validate(&monitor(QualityCheck::NullRatio {
+                field: "user_id".to_string(),
+                max_percent: 5.0,
+                minutes: 60,
+            }))
+            .is_ok()
+        );
+        assert!(
+            validate(&monitor(QualityCheck::NullRatio {
+                field: "user_id".to_string(),
+                max_percent: 150.0,
+                minutes: 60,
+            }))
+            .is_err()
+        );
+        let mut m = monitor(QualityCheck::SchemaChanged);
+        assert!(validate(&m).is_ok());
+        m.destinations.clear();
+        assert!(validate(&m).is_err());
+    }
+
+    #[test]
+    fn test_stopped_shippers() {
+        let now = 10_000 * 60 * 1_000_000;
+        let minute = 60 * 1_000_000;
+        let row = |shipper: &str, last_seen: i64| {
+            let mut row = Map::new();
+            row.insert("shipper".to_string(), shipper.into());
+            row.insert("last_seen".to_string(), last_seen.into());
+            row
+        };
+        let rows = vec![
+            row("web-2", now - 11 * minute),
+            row("web-1", now - 10 * minute - 1),
+            // a heartbeat right at the deadline is still alive
+            row("db-1", now - 10 * minute),
+            row("db-2", now - minute),
+        ];
+        assert_eq!(stopped_shippers(&rows, now, 10), vec!["web-1", "web-2"]);
+        assert_eq!(stopped_shippers(&rows, now, 15), Vec::<&str>::new());
+        assert!(stopped_shippers(&[], now, 10).is_empty());
+
+        // the shippers gone for longer than the lookback are forgotten
+        assert_eq!(known_shippers_minutes(10), KNOWN_SHIPPERS_MINUTES);
+        assert_eq!(known_shippers_minutes(24 * 60), 2 * 24 * 60);
+    }
+
+    #[test]
+    fn test_heartbeat_record() {
+        let heartbeat = |shipper: &str| Heartbeat {
+            shipper: shipper.to_string(),
+            attributes: json::json!({"version": "1.2"})
+                .as_object()
+                .cloned()
+                .unwrap(),
+        };
+        let record = heartbeat_record(StreamType::Logs, "app", heartbeat("host-1/vector")).unwrap();
+        assert_eq!(record["shipper"], "host-1/vector");
+        assert_eq!(record["stream"], "app");
+        assert_eq!(record["stream_type"], "logs");
+        assert_eq!(record["version"], "1.2");
+        assert!(heartbeat_record(StreamType::Logs, "app", heartbeat(" ")).is_err());
+    }
+
+    #[test]
+    fn test_schema_changes() {
+        let old = vec!["a:Utf8".to_string(), "b:Int64".to_string()];
+        assert_eq!(schema_changes(&old, &old), None);
+        let new = vec!["a:Utf8".to_string(), "c:Utf8".to_string()];
+        assert_eq!(
+            schema_changes(&old, &new).unwrap(),
+            "schema changed, added: c:Utf8; removed: b:Int64"
+        );
+    }
+}
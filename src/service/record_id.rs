@@ -0,0 +1,156 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use config::{
+    cluster,
+    meta::{search, stream::StreamType},
+    utils::json::{Map, Value},
+};
+use once_cell::sync::Lazy;
+
+use crate::service::{error::ServiceError, format_stream_name, search as SearchService};
+
+/// Column holding the unique id of the record. It is apart from `_id`, which
+/// keeps the document id of a `_bulk` request.
+pub const RECORD_ID_COLUMN: &str = "_record_id";
+
+const ALPHABET: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+const SEQUENCE_BITS: u32 = 54;
+
+/// Ids handed out by this process, it starts at the start time in
+/// microseconds so a restarted node doesn't give the ids it gave before.
+static SEQUENCE: Lazy<AtomicU64> =
+    Lazy::new(|| AtomicU64::new(Utc::now().timestamp_micros().max(0) as u64));
+
+/// Generates an id with the record timestamp in the high bits, so looking a
+/// record up by its id only scans the files of that time. The low bits are the
+/// node id and a sequence of the process, which keeps the ids unique across
+/// nodes without a lock per record.
+pub fn generate(timestamp: i64) -> String {
+    let node_id = unsafe { cluster::LOCAL_NODE_ID } as u64 & 0x3ff;
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed) & ((1 << SEQUENCE_BITS) - 1);
+    let low = node_id << SEQUENCE_BITS | sequence;
+    let id = (timestamp.max(0) as u128) << 64 | low as u128;
+    let mut buf = Vec::with_capacity(25);
+    let mut v = id;
+    loop {
+        buf.push(ALPHABET[(v % 36) as usize]);
+        v /= 36;
+        if v == 0 {
+            break;
+        }
+    }
+    buf.reverse();
+    String::from_utf8(buf).unwrap()
+}
+
+/// Returns the record timestamp an id was generated for.
+pub fn parse(id: &str) -> Option<i64> {
+    if id.is_empty() || id.len() > 25 {
+        return None;
+    }
+    let mut v: u128 = 0;
+    for c in id.bytes() {
+        let digit = ALPHABET.iter().position(|a| *a == c)? as u128;
+        v = v.checked_mul(36)?.checked_add(digit)?;
+    }
+    i64::try_from(v >> 64).ok()
+}
+
+/// Gives the record an id, a value ingested in the column was already moved
+/// away as it is a system column.
+pub fn attach(record: &mut Map<String, Value>, timestamp: i64) {
+    record.insert(
+        RECORD_ID_COLUMN.to_string(),
+        Value::String(generate(timestamp)),
+    );
+}
+
+/// Fetches the log record with the given id as the user, `None` if there is no
+/// such record.
+pub async fn get(
+    org_id: &str,
+    user_id: &str,
+    stream_name: &str,
+    id: &str,
+) -> Result<Option<Value>, ServiceError> {
+    let Some(timestamp) = parse(id) else {
+        return Err(ServiceError::bad_request(format!(
+            "invalid record id: {id}"
+        )));
+    };
+    if format_stream_name(stream_name) != stream_name {
+        return Err(ServiceError::bad_request(format!(
+            "invalid stream name: {stream_name}"
+        )));
+    }
+    let query = search::Query {
+        sql: format!("SELECT * FROM \"{stream_name}\" WHERE \"{RECORD_ID_COLUMN}\" = '{id}'"),
+        start_time: timestamp,
+        end_time: timestamp + 1,
+        size: 1,
+        sql_mode: "full".to_owned(),
+        ..Default::default()
+    };
+    let req = search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search(
+        "",
+        org_id,
+        StreamType::Logs,
+        Some(user_id.to_string()),
+        &req,
+    )
+    .await?;
+    Ok(res.hits.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_parse() {
+        let id = generate(1700000000000000);
+        assert!(id.len() <= 25);
+        assert_eq!(parse(&id), Some(1700000000000000));
+        assert_ne!(generate(1700000000000000), id);
+        assert_eq!(parse(&generate(0)), Some(0));
+        assert_eq!(parse("abc' OR 1=1"), None);
+        assert_eq!(parse("ABC"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_attach_replaces() {
+        let mut record = Map::new();
+        record.insert(RECORD_ID_COLUMN.to_string(), Value::String("x".to_string()));
+        attach(&mut record, 1700000000000000);
+        let id = record[RECORD_ID_COLUMN].as_str().unwrap();
+        assert_eq!(parse(id), Some(1700000000000000));
+    }
+}
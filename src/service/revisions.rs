@@ -0,0 +1,335 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! History of the dashboards, alerts and functions. Every change stores the
+//! state of the object after it, so accidental overwrites and deletes can be
+//! looked at and undone.
+
+use std::collections::{BTreeSet, HashSet};
+
+use config::{
+    meta::stream::StreamType,
+    utils::json::{self, Value},
+    CONFIG,
+};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        meta::{
+            alerts::Alert,
+            authz::Authz,
+            dashboards::Dashboard,
+            functions::Transform,
+            revisions::{
+                ObjectType, Revision, RevisionAction, RevisionChange, RevisionDiff, RevisionInfo,
+            },
+        },
+        utils::auth::set_ownership,
+    },
+    service::{alerts, db, error::ServiceError},
+};
+
+pub fn dashboard_id(folder: &str, dashboard_id: &str) -> String {
+    format!("{folder}/{dashboard_id}")
+}
+
+pub fn alert_id(stream_type: StreamType, stream_name: &str, name: &str) -> String {
+    format!("{stream_type}/{stream_name}/{name}")
+}
+
+/// The stored form of a dashboard, the one of its version.
+pub fn dashboard_data(dashboard: &Dashboard) -> Value {
+    let ret = if let Some(v) = &dashboard.v3 {
+        json::to_value(v)
+    } else if let Some(v) = &dashboard.v2 {
+        json::to_value(v)
+    } else {
+        json::to_value(&dashboard.v1)
+    };
+    ret.unwrap_or_default()
+}
+
+/// Stores a revision of the object and drops the oldest ones over the limit.
+/// Errors are only logged, they must not fail the change itself.
+pub async fn record(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    author: &str,
+    action: RevisionAction,
+    data: &impl Serialize,
+) {
+    if CONFIG.limit.revisions_max == 0 {
+        return;
+    }
+    let data = match json::to_value(data) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("[REVISIONS] serialize {object_type} {org_id}/{object_id} error: {e}");
+            return;
+        }
+    };
+    let revision = Revision {
+        revision: chrono::Utc::now().timestamp_micros(),
+        object_type,
+        object_id: object_id.to_string(),
+        author: author.to_string(),
+        action,
+        data,
+    };
+    if let Err(e) = db::revisions::put(org_id, &revision).await {
+        log::error!("[REVISIONS] save {object_type} {org_id}/{object_id} error: {e}");
+        return;
+    }
+
+    let revisions = match db::revisions::list(org_id, object_type, object_id).await {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("[REVISIONS] list {object_type} {org_id}/{object_id} error: {e}");
+            return;
+        }
+    };
+    for old in revisions.iter().skip(CONFIG.limit.revisions_max) {
+        if let Err(e) = db::revisions::delete(org_id, object_type, object_id, old.revision).await {
+            log::error!("[REVISIONS] delete {object_type} {org_id}/{object_id} error: {e}");
+        }
+    }
+}
+
+pub async fn list(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+) -> Result<Vec<RevisionInfo>, ServiceError> {
+    let revisions = db::revisions::list(org_id, object_type, object_id).await?;
+    Ok(revisions.iter().map(RevisionInfo::from).collect())
+}
+
+/// The objects of the type whose last change is a delete, newest first.
+pub async fn list_deleted(
+    org_id: &str,
+    object_type: ObjectType,
+) -> Result<Vec<RevisionInfo>, ServiceError> {
+    let revisions = db::revisions::list_by_type(org_id, object_type).await?;
+    let mut seen = HashSet::new();
+    Ok(revisions
+        .iter()
+        .filter(|r| seen.insert(r.object_id.clone()) && r.action == RevisionAction::Deleted)
+        .map(RevisionInfo::from)
+        .collect())
+}
+
+pub async fn get(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    revision: i64,
+) -> Result<Revision, ServiceError> {
+    db::revisions::get(org_id, object_type, object_id, revision)
+        .await
+        .map_err(|e| match ServiceError::from(e) {
+            ServiceError::NotFound(_) => ServiceError::not_found("Revision not found"),
+            e => e,
+        })
+}
+
+/// The changes from the `from` revision to the `to` one, the latest if none.
+pub async fn diff(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    from: i64,
+    to: Option<i64>,
+) -> Result<RevisionDiff, ServiceError> {
+    let before = get(org_id, object_type, object_id, from).await?;
+    let after = match to {
+        Some(to) => get(org_id, object_type, object_id, to).await?,
+        None => db::revisions::list(org_id, object_type, object_id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ServiceError::not_found("Revision not found"))?,
+    };
+    let mut changes = vec![];
+    diff_values("", Some(&before.data), Some(&after.data), &mut changes);
+    Ok(RevisionDiff {
+        from: before.revision,
+        to: after.revision,
+        changes,
+    })
+}
+
+fn diff_values(
+    path: &str,
+    before: Option<&Value>,
+    after: Option<&Value>,
+    changes: &mut Vec<RevisionChange>,
+) {
+    match (before, after) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let keys = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let key_path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                diff_values(&key_path, a.get(key), b.get(key), changes);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b.iter()).enumerate() {
+                diff_values(&format!("{path}/{i}"), Some(a), Some(b), changes);
+            }
+        }
+        (a, b) if a != b => changes.push(RevisionChange {
+            path: path.to_string(),
+            before: a.cloned(),
+            after: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Puts the object back to the state of the revision, recreating it if it
+/// was deleted.
+pub async fn restore(
+    org_id: &str,
+    object_type: ObjectType,
+    object_id: &str,
+    revision: i64,
+    author: &str,
+) -> Result<(), ServiceError> {
+    let revision = get(org_id, object_type, object_id, revision).await?;
+    match object_type {
+        ObjectType::Dashboard => restore_dashboard(org_id, object_id, &revision.data).await?,
+        ObjectType::Alert => restore_alert(org_id, &revision.data).await?,
+        ObjectType::Function => restore_function(org_id, &revision.data).await?,
+    }
+    record(
+        org_id,
+        object_type,
+        object_id,
+        author,
+        RevisionAction::Restored,
+        &revision.data,
+    )
+    .await;
+    Ok(())
+}
+
+async fn restore_dashboard(
+    org_id: &str,
+    object_id: &str,
+    data: &Value,
+) -> Result<(), ServiceError> {
+    let Some((folder, dashboard_id)) = object_id.split_once('/') else {
+        return Err(ServiceError::bad_request("Invalid dashboard id"));
+    };
+    if db::dashboards::folders::get(org_id, folder).await.is_err() {
+        return Err(ServiceError::bad_request(format!(
+            "Folder {folder} not found"
+        )));
+    }
+    let exists = db::dashboards::get(org_id, dashboard_id, folder)
+        .await
+        .is_ok();
+    db::dashboards::put(
+        org_id,
+        dashboard_id,
+        folder,
+        json::to_vec(data).map_err(ServiceError::internal)?.into(),
+    )
+    .await?;
+    if !exists {
+        set_ownership(
+            org_id,
+            "dashboards",
+            Authz {
+                obj_id: dashboard_id.to_string(),
+                parent_type: "folders".to_owned(),
+                parent: folder.to_string(),
+            },
+        )
+        .await;
+    }
+    Ok(())
+}
+
+async fn restore_alert(org_id: &str, data: &Value) -> Result<(), ServiceError> {
+    let mut alert: Alert = json::from_value(data.clone()).map_err(ServiceError::internal)?;
+    // the destinations, the stream or the query of the revision may be gone
+    if let Err(e) = alerts::validate(org_id, &mut alert).await {
+        return Err(ServiceError::bad_request(e.to_string()));
+    }
+    let exists = matches!(
+        db::alerts::get(org_id, alert.stream_type, &alert.stream_name, &alert.name).await,
+        Ok(Some(_))
+    );
+    db::alerts::set(
+        org_id,
+        alert.stream_type,
+        &alert.stream_name,
+        &alert,
+        !exists,
+    )
+    .await?;
+    if !exists {
+        set_ownership(org_id, "alerts", Authz::new(&alert.name)).await;
+    }
+    Ok(())
+}
+
+async fn restore_function(org_id: &str, data: &Value) -> Result<(), ServiceError> {
+    let mut func: Transform = json::from_value(data.clone()).map_err(ServiceError::internal)?;
+    // the streams using the function are not part of its history
+    let existing = db::functions::get(org_id, &func.name).await.ok();
+    let exists = existing.is_some();
+    func.streams = existing.and_then(|f| f.streams);
+    db::functions::set(org_id, &func.name, &func).await?;
+    if !exists {
+        set_ownership(org_id, "functions", Authz::new(&func.name)).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_values() {
+        let before = json::json!({
+            "title": "web",
+            "tabs": [{"name": "a"}, {"name": "b"}],
+            "a/b": 1,
+            "removed": true,
+        });
+        let after = json::json!({
+            "title": "web traffic",
+            "tabs": [{"name": "a"}, {"name": "c"}],
+            "a/b": 1,
+            "added": [1],
+        });
+        let mut changes = vec![];
+        diff_values("", Some(&before), Some(&after), &mut changes);
+        let paths = changes.iter().map(|c| c.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/added", "/removed", "/tabs/1/name", "/title"]);
+        assert_eq!(changes[0].before, None);
+        assert_eq!(changes[1].after, None);
+        assert_eq!(changes[3].after, Some(json::json!("web traffic")));
+
+        let mut changes = vec![];
+        diff_values("", Some(&before), Some(&before), &mut changes);
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sample streams filled with generated data, so a new deployment has
+//! something to search, chart and alert on.
+
+use actix_web::web;
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::stream::StreamType,
+    utils::json::{self, Value},
+    CONFIG,
+};
+use infra::dist_lock;
+use rand::Rng;
+
+use crate::{
+    common::{
+        infra::cluster::get_node_by_uuid,
+        meta::{
+            alerts::{
+                destinations::{Destination, DestinationType, HTTPType},
+                templates::Template,
+                Alert, Condition, Operator, QueryCondition, TriggerCondition,
+            },
+            dashboards::DEFAULT_FOLDER,
+            ingestion::{IngestionRequest, IngestionResponse},
+        },
+        utils::synthetic,
+    },
+    service::{alerts, dashboards, db, logs, metrics, traces},
+};
+
+pub const WEB_LOGS_STREAM: &str = "sample_web_logs";
+pub const K8S_LOGS_STREAM: &str = "sample_k8s_logs";
+const SAMPLE_NAME: &str = "sample_data";
+
+// the node generating the data
+const GENERATOR_KEY: &str = "/sample_data/generator";
+// set once the dashboards and alerts of an org are created
+const ASSETS_KEY_PREFIX: &str = "/sample_data/assets/";
+
+/// Makes this node the one generating the sample data, unless another node
+/// which is online already is. Returns whether this node generates.
+pub async fn claim_generator() -> Result<bool, anyhow::Error> {
+    let owner = get_generator().await;
+    if owner == *LOCAL_NODE_UUID {
+        return Ok(true);
+    }
+    if !owner.is_empty() && get_node_by_uuid(&owner).await.is_some() {
+        return Ok(false);
+    }
+
+    let locker = dist_lock::lock(GENERATOR_KEY, 0).await?;
+    // check again, maybe another node claimed it first
+    let owner = get_generator().await;
+    let ret = if owner.is_empty()
+        || owner == *LOCAL_NODE_UUID
+        || get_node_by_uuid(&owner).await.is_none()
+    {
+        db::put(
+            GENERATOR_KEY,
+            LOCAL_NODE_UUID.clone().into(),
+            db::NO_NEED_WATCH,
+            None,
+        )
+        .await
+        .map(|_| true)
+    } else {
+        Ok(false)
+    };
+    dist_lock::unlock(&locker).await?;
+    Ok(ret?)
+}
+
+async fn get_generator() -> String {
+    db::get(GENERATOR_KEY)
+        .await
+        .map(|v| String::from_utf8_lossy(&v).to_string())
+        .unwrap_or_default()
+}
+
+/// Ingests a batch of sample logs, metrics and traces spread over the last
+/// `interval` seconds.
+pub async fn generate(org_id: &str, records: usize, interval: u64) -> Result<(), anyhow::Error> {
+    let now = chrono::Utc::now().timestamp_micros();
+    let span = (interval as i64 * 1_000_000).max(1);
+    // ThreadRng is not Send, keep it out of the awaits below
+    let (web_logs, k8s_logs, metric_records, trace_request) = {
+        let mut rng = rand::thread_rng();
+        let web_logs = (0..records)
+            .map(|_| {
+                let ts = now - rng.gen_range(0..span);
+                synthetic::web_log(&mut rng, ts)
+            })
+            .collect::<Vec<_>>();
+        let k8s_logs = (0..records)
+            .map(|_| {
+                let ts = now - rng.gen_range(0..span);
+                synthetic::k8s_log(&mut rng, ts)
+            })
+            .collect::<Vec<_>>();
+        (
+            web_logs,
+            k8s_logs,
+            synthetic::metrics(&mut rng, now),
+            synthetic::traces(&mut rng, now, (records / 10).max(1)),
+        )
+    };
+
+    for (stream_name, records) in [(WEB_LOGS_STREAM, web_logs), (K8S_LOGS_STREAM, k8s_logs)] {
+        let body = web::Bytes::from(json::to_vec(&records)?);
+        let resp = logs::ingest::ingest(
+            org_id,
+            stream_name,
+            IngestionRequest::JSON(&body),
+            0,
+            &CONFIG.auth.root_user_email,
+        )
+        .await?;
+        check_response(stream_name, &resp)?;
+    }
+
+    let body = web::Bytes::from(json::to_vec(&metric_records)?);
+    let resp = metrics::json::ingest(org_id, body, 0).await?;
+    check_response("metrics", &resp)?;
+
+    let body = web::Bytes::from(json::to_vec(&trace_request)?);
+    let resp = traces::otlp_http::traces_json(org_id, 0, body, None).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "ingest sample traces failed: {}",
+            resp.status()
+        ));
+    }
+    Ok(())
+}
+
+fn check_response(name: &str, resp: &IngestionResponse) -> Result<(), anyhow::Error> {
+    match &resp.error {
+        Some(e) => Err(anyhow::anyhow!("ingest sample {name} failed: {e}")),
+        None => Ok(()),
+    }
+}
+
+/// Creates the sample dashboard and alert of the org once. The alert and
+/// its destination are overwritten if a previous attempt failed midway, the
+/// dashboard is created last as it can't be overwritten.
+pub async fn create_assets(org_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("{ASSETS_KEY_PREFIX}{org_id}");
+    if db::get(&key).await.is_ok() {
+        return Ok(());
+    }
+
+    // the alert is disabled, its destination only shows how to set one up
+    let template = Template {
+        name: SAMPLE_NAME.to_string(),
+        body: r#"{"text": "{alert_name} fired on {stream_name}: {alert_count} server errors, {alert_url}"}"#.to_string(),
+        is_default: Some(false),
+        template_type: DestinationType::Http,
+    };
+    let create = alerts::templates::get(org_id, SAMPLE_NAME).await.is_err();
+    alerts::templates::save(org_id, "", template, create).await?;
+    let destination = Destination {
+        name: SAMPLE_NAME.to_string(),
+        url: "http://localhost:8080/webhook".to_string(),
+        method: HTTPType::POST,
+        skip_tls_verify: false,
+        headers: None,
+        template: SAMPLE_NAME.to_string(),
+        emails: vec![],
+        slack_bot_token: "".to_string(),
+        slack_channel: "".to_string(),
+        slack_signing_secret: "".to_string(),
+        destination_type: DestinationType::Http,
+    };
+    let create = alerts::destinations::get(org_id, SAMPLE_NAME)
+        .await
+        .is_err();
+    alerts::destinations::save(org_id, "", destination, create)
+        .await
+        .map_err(|(_, e)| e)?;
+    let alert = Alert {
+        name: "sample_server_errors".to_string(),
+        stream_type: StreamType::Logs,
+        query_condition: QueryCondition {
+            conditions: Some(vec![Condition {
+                column: "status".to_string(),
+                operator: Operator::GreaterThanEquals,
+                value: json::json!(500),
+                ignore_case: false,
+            }]),
+            ..Default::default()
+        },
+        trigger_condition: TriggerCondition {
+            period: 5,
+            operator: Operator::GreaterThanEquals,
+            threshold: 10,
+            frequency: 60,
+            ..Default::default()
+        },
+        destinations: vec![SAMPLE_NAME.to_string()],
+        description: "More than 10 server errors in 5 minutes".to_string(),
+        enabled: false,
+        ..Default::default()
+    };
+    let create = alerts::get(org_id, StreamType::Logs, WEB_LOGS_STREAM, &alert.name)
+        .await?
+        .is_none();
+    alerts::save(
+        org_id,
+        WEB_LOGS_STREAM,
+        "",
+        alert,
+        create,
+        &CONFIG.auth.root_user_email,
+    )
+    .await?;
+
+    let resp = dashboards::create_dashboard(
+        org_id,
+        DEFAULT_FOLDER,
+        json::to_vec(&sample_dashboard())?.into(),
+        &CONFIG.auth.root_user_email,
+    )
+    .await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "create sample dashboard failed: {}",
+            resp.status()
+        ));
+    }
+
+    db::put(&key, "true".into(), db::NO_NEED_WATCH, None).await?;
+    log::info!("[SAMPLE_DATA] created the sample dashboard and alert of {org_id}");
+    Ok(())
+}
+
+fn sample_dashboard() -> Value {
+    let panel = |id: &str, typ: &str, title: &str, sql: &str, x: Value, y: Value, i: i64| {
+        json::json!({
+            "id": id,
+            "type": typ,
+            "title": title,
+            "description": "",
+            "config": {"show_legends": true, "legends_position": null, "base_map": null, "map_view": null},
+            "queryType": "sql",
+            "queries": [{
+                "query": sql,
+                "customQuery": false,
+                "fields": {
+                    "stream": WEB_LOGS_STREAM,
+                    "stream_type": "logs",
+                    "x": [x],
+                    "y": [y],
+                    "filter": [],
+                },
+                "config": {"promql_legend": ""},
+            }],
+            "layout": {"x": (i % 2) * 96, "y": (i / 2) * 18, "w": 96, "h": 18, "i": i + 1},
+        })
+    };
+    let histogram = json::json!({
+        "label": "Timestamp",
+        "alias": "x_axis_1",
+        "column": "_timestamp",
+        "color": null,
+        "aggregationFunction": "histogram",
+    });
+    json::json!({
+        "version": 3,
+        "title": "Sample web traffic",
+        "description": "Requests of the sample web servers",
+        "tabs": [{
+            "tabId": "default",
+            "name": "Default",
+            "panels": [
+                panel(
+                    "panel_requests",
+                    "line",
+                    "Requests",
+                    &format!("SELECT histogram(_timestamp) AS \"x_axis_1\", count(_timestamp) AS \"y_axis_1\" FROM \"{WEB_LOGS_STREAM}\" GROUP BY x_axis_1 ORDER BY x_axis_1"),
+                    histogram.clone(),
+                    json::json!({"label": "Requests", "alias": "y_axis_1", "column": "_timestamp", "color": "#5960b2", "aggregationFunction": "count"}),
+                    0,
+                ),
+                panel(
+                    "panel_errors",
+                    "bar",
+                    "Server errors by host",
+                    &format!("SELECT host AS \"x_axis_1\", count(_timestamp) AS \"y_axis_1\" FROM \"{WEB_LOGS_STREAM}\" WHERE status >= 500 GROUP BY x_axis_1"),
+                    json::json!({"label": "Host", "alias": "x_axis_1", "column": "host", "color": null}),
+                    json::json!({"label": "Errors", "alias": "y_axis_1", "column": "_timestamp", "color": "#e8484b", "aggregationFunction": "count"}),
+                    1,
+                ),
+                panel(
+                    "panel_latency",
+                    "line",
+                    "Average latency (ms)",
+                    &format!("SELECT histogram(_timestamp) AS \"x_axis_1\", avg(took_ms) AS \"y_axis_1\" FROM \"{WEB_LOGS_STREAM}\" GROUP BY x_axis_1 ORDER BY x_axis_1"),
+                    histogram,
+                    json::json!({"label": "Latency", "alias": "y_axis_1", "column": "took_ms", "color": "#5960b2", "aggregationFunction": "avg"}),
+                    2,
+                ),
+            ],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_dashboard() {
+        let dashboard: crate::common::meta::dashboards::v3::Dashboard =
+            json::from_value(sample_dashboard()).unwrap();
+        assert_eq!(dashboard.tabs[0].panels.len(), 3);
+    }
+}
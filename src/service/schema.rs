@@ -24,7 +24,7 @@ use anyhow::Result;
 use config::{
     meta::stream::{StreamPartition, StreamSettings, StreamType},
     utils::{
-        json,
+        base64, json,
         schema::{infer_json_schema, infer_json_schema_from_map},
         schema_ext::SchemaExt,
     },
@@ -34,14 +34,19 @@ use datafusion::arrow::{
     datatypes::{DataType, Field, Schema},
     error::ArrowError,
 };
+use infra::schema::unwrap_stream_settings;
 use itertools::Itertools;
 use serde_json::{Map, Value};
 
 use crate::{
     common::meta::{
-        authz::Authz, ingestion::StreamSchemaChk, prom::METADATA_LABEL, stream::SchemaEvolution,
+        authz::Authz,
+        ingestion::{SchemaChangeType, SchemaFieldChange, StreamSchemaChk},
+        prom::METADATA_LABEL,
+        stream::SchemaEvolution,
+        webhooks::{StreamEvent, StreamEventType},
     },
-    service::db,
+    service::{cdc, db, webhooks},
 };
 
 pub(crate) fn get_upto_discard_error() -> anyhow::Error {
@@ -243,15 +248,21 @@ pub struct SchemaCache {
     schema: Schema,
     fields_map: HashMap<String, usize>,
     hash_key: String,
+    binary_fields: Vec<String>,
 }
 
 impl SchemaCache {
     pub fn new(schema: Schema, fields_map: HashMap<String, usize>) -> Self {
         let hash_key = schema.hash_key();
+        // read once per cached schema, not once per ingested record
+        let binary_fields = unwrap_stream_settings(&schema)
+            .map(|s| s.binary_fields)
+            .unwrap_or_default();
         Self {
             schema,
             fields_map,
             hash_key,
+            binary_fields,
         }
     }
 
@@ -304,8 +315,14 @@ pub async fn check_for_schema(
     }
 
     // get infer schema
-    let value_iter = record_val.into_iter();
+    let value_iter = record_val.iter().copied();
     let inferred_schema = infer_json_schema_from_map(value_iter, stream_type).unwrap();
+    let inferred_schema = apply_binary_fields(
+        &schema.binary_fields,
+        schema.schema(),
+        inferred_schema,
+        &record_val,
+    )?;
 
     // fast path
     if schema.schema().fields.eq(&inferred_schema.fields) {
@@ -383,6 +400,101 @@ pub async fn check_for_schema(
     Ok((ret, Some(inferred_schema)))
 }
 
+/// The changes ingesting the records would make to the stream schema, the schema
+/// itself is left untouched.
+pub async fn preview_schema_changes(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    records: &[Map<String, Value>],
+) -> Result<Vec<SchemaFieldChange>> {
+    if records.is_empty() {
+        return Ok(vec![]);
+    }
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    let inferred_schema = infer_json_schema_from_map(records.iter(), stream_type)?;
+    let records = records.iter().collect::<Vec<_>>();
+    let binary_fields = unwrap_stream_settings(&schema)
+        .map(|s| s.binary_fields)
+        .unwrap_or_default();
+    let inferred_schema = apply_binary_fields(&binary_fields, &schema, inferred_schema, &records)?;
+    Ok(diff_schema_fields(&schema, &inferred_schema))
+}
+
+fn diff_schema_fields(schema: &Schema, inferred_schema: &Schema) -> Vec<SchemaFieldChange> {
+    let mut changes = vec![];
+    for field in inferred_schema.fields().iter() {
+        let (change, current_type) = match schema.field_with_name(field.name()) {
+            Err(_) => (SchemaChangeType::Added, None),
+            Ok(existing) if existing.data_type() == field.data_type() => continue,
+            Ok(existing) => {
+                let change = if CONFIG.common.widening_schema_evolution
+                    && infra::schema::is_widening_conversion(
+                        existing.data_type(),
+                        field.data_type(),
+                    ) {
+                    SchemaChangeType::Widened
+                } else {
+                    SchemaChangeType::Cast
+                };
+                (change, Some(existing.data_type().to_string()))
+            }
+        };
+        changes.push(SchemaFieldChange {
+            field: field.name().to_string(),
+            change,
+            data_type: field.data_type().to_string(),
+            current_type,
+        });
+    }
+    changes
+}
+
+/// Declared binary fields accept base64 strings and are stored as Binary, fields that
+/// already exist as strings keep their type.
+fn apply_binary_fields(
+    binary_fields: &[String],
+    stream_schema: &Schema,
+    inferred_schema: Schema,
+    records: &[&Map<String, Value>],
+) -> Result<Schema> {
+    if binary_fields.is_empty() {
+        return Ok(inferred_schema);
+    }
+    for record in records.iter() {
+        for field in binary_fields.iter() {
+            let valid = match record.get(field) {
+                None | Some(Value::Null) => true,
+                Some(Value::String(v)) => base64::decode_raw(v).is_ok(),
+                Some(_) => false,
+            };
+            if !valid {
+                return Err(anyhow::anyhow!(
+                    "field [{field}] expects a base64 encoded value"
+                ));
+            }
+        }
+    }
+    let fields = inferred_schema
+        .fields()
+        .iter()
+        .map(|f| {
+            let is_string = stream_schema
+                .field_with_name(f.name())
+                .is_ok_and(|v| v.data_type() == &DataType::Utf8);
+            if binary_fields.contains(f.name()) && !is_string {
+                Arc::new(f.as_ref().clone().with_data_type(DataType::Binary))
+            } else {
+                f.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(Schema::new_with_metadata(
+        fields,
+        inferred_schema.metadata().clone(),
+    ))
+}
+
 pub async fn get_merged_schema(
     org_id: &str,
     stream_name: &str,
@@ -438,6 +550,15 @@ async fn handle_diff_schema(
         );
     }
 
+    // a new stream starts with the settings of the stream template of the org,
+    // they are ignored when another thread created the stream first
+    let template_schema = if is_new {
+        with_stream_template(org_id, stream_type, inferred_schema).await
+    } else {
+        None
+    };
+    let merge_schema = template_schema.as_ref().unwrap_or(inferred_schema);
+
     let mut retries = 0;
     let mut err: Option<anyhow::Error> = None;
     let mut ret: Option<_> = None;
@@ -447,7 +568,7 @@ async fn handle_diff_schema(
             org_id,
             stream_name,
             stream_type,
-            inferred_schema,
+            merge_schema,
             Some(record_ts),
         )
         .await
@@ -487,17 +608,55 @@ async fn handle_diff_schema(
         );
         return Err(e);
     }
-    let Some((final_schema, field_datatype_delta)) = ret else {
+    let Some((final_schema, field_datatype_delta, stored_schema)) = ret else {
         return Ok(None);
     };
 
-    if is_new {
-        crate::common::utils::auth::set_ownership(
-            org_id,
-            &stream_type.to_string(),
-            Authz::new(stream_name),
-        )
-        .await;
+    // the events are sent by the thread whose merge changed the stored schema
+    match stored_schema {
+        None => {
+            crate::common::utils::auth::set_ownership(
+                org_id,
+                &stream_type.to_string(),
+                Authz::new(stream_name),
+            )
+            .await;
+            webhooks::notify(StreamEvent::new(
+                StreamEventType::StreamCreated,
+                org_id,
+                stream_type,
+                stream_name,
+                json::json!({ "fields": final_schema.fields().len() }),
+            ));
+            cdc::record_schema(
+                org_id,
+                &format!("{stream_type}/{stream_name}"),
+                stream_name,
+                None,
+                Some(&final_schema),
+            )
+            .await;
+        }
+        Some(stored_schema) => {
+            let changes = diff_schema_fields(&stored_schema, &final_schema);
+            if !changes.is_empty() {
+                cdc::record_schema(
+                    org_id,
+                    &format!("{stream_type}/{stream_name}"),
+                    stream_name,
+                    Some(&stored_schema),
+                    Some(&final_schema),
+                )
+                .await;
+                webhooks::notify(StreamEvent::new(
+                    StreamEventType::SchemaChanged,
+                    org_id,
+                    stream_type,
+                    stream_name,
+                    json::json!({ "changes": changes }),
+                ));
+            }
+        }
     }
 
     let fields_map = final_schema
@@ -519,6 +678,25 @@ async fn handle_diff_schema(
     }))
 }
 
+/// The schema carrying the settings of the stream template of the org, `None`
+/// when the org has no template for the stream type.
+async fn with_stream_template(
+    org_id: &str,
+    stream_type: StreamType,
+    schema: &Schema,
+) -> Option<Schema> {
+    let template = crate::service::organization::get_setting(org_id)
+        .await
+        .stream_template
+        .filter(|t| t.applies_to(stream_type))?;
+    let mut metadata = schema.metadata().clone();
+    metadata.insert(
+        "settings".to_string(),
+        json::to_string(&template.settings()).unwrap(),
+    );
+    Some(schema.clone().with_metadata(metadata))
+}
+
 fn get_schema_changes(schema: &SchemaCache, inferred_schema: &Schema) -> (bool, Vec<Field>) {
     let mut is_schema_changed = false;
     let mut field_datatype_delta: Vec<Field> = vec![];
@@ -631,6 +809,32 @@ pub async fn add_stream_schema(
             routing: None,
             flatten_level: None,
             defined_schema_fields: None,
+            virtual_fields: vec![],
+            default_display_fields: vec![],
+            field_display: Default::default(),
+            nested_fields: vec![],
+            max_field_size: 0,
+            binary_fields: vec![],
+            encrypt_fields: vec![],
+            hot_data_days: 0,
+            drop_rules: vec![],
+            labels: Default::default(),
+            owner: None,
+            retain_original: false,
+            retention_lock_days: 0,
+            retention_lock_until: 0,
+            archive: false,
+            lineage: false,
+            field_metadata: Default::default(),
+            max_events_per_sec: 0,
+            max_mb_per_day: 0,
+            flatten_arrays: Default::default(),
+            flatten_separator: "".to_string(),
+            key_normalization: Default::default(),
+            otlp_attributes: Default::default(),
+            max_fields: 0,
+            promoted_fields: vec![],
+            parquet: Default::default(),
         };
         metadata.insert(
             "settings".to_string(),
@@ -698,6 +902,27 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_diff_schema_fields() {
+        let schema = Schema::new(vec![
+            Field::new("c1", DataType::Int64, false),
+            Field::new("c2", DataType::Utf8, false),
+        ]);
+        let inferred = Schema::new(vec![
+            Field::new("c1", DataType::Utf8, false),
+            Field::new("c2", DataType::Utf8, false),
+            Field::new("c3", DataType::Boolean, false),
+        ]);
+        let changes = diff_schema_fields(&schema, &inferred);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "c1");
+        assert_eq!(changes[0].current_type.as_deref(), Some("Int64"));
+        assert_ne!(changes[0].change, SchemaChangeType::Added);
+        assert_eq!(changes[1].field, "c3");
+        assert_eq!(changes[1].change, SchemaChangeType::Added);
+        assert!(changes[1].current_type.is_none());
+    }
+
     #[test]
     fn test_try_merge() {
         let merged = try_merge(vec![
@@ -779,4 +1004,35 @@ mod tests {
         let value_iter = record_val.into_iter();
         infer_json_schema_from_map(value_iter, stream_type).unwrap();
     }
+
+    #[test]
+    fn test_apply_binary_fields() {
+        let settings = StreamSettings {
+            binary_fields: vec!["payload".to_string()],
+            ..Default::default()
+        };
+        let schema = Schema::new(vec![]).with_metadata(HashMap::from([(
+            "settings".to_string(),
+            json::to_string(&settings).unwrap(),
+        )]));
+        let cache = SchemaCache::new(schema.clone(), HashMap::new());
+        assert_eq!(cache.binary_fields, vec!["payload"]);
+
+        let record = json::json!({"payload": "aGVsbG8="});
+        let records = vec![record.as_object().unwrap()];
+        let inferred =
+            infer_json_schema_from_map(records.iter().copied(), StreamType::Logs).unwrap();
+        let inferred =
+            apply_binary_fields(&cache.binary_fields, &schema, inferred, &records).unwrap();
+        assert_eq!(
+            inferred.field_with_name("payload").unwrap().data_type(),
+            &DataType::Binary
+        );
+
+        let record = json::json!({"payload": "not base64!"});
+        let records = vec![record.as_object().unwrap()];
+        let inferred =
+            infer_json_schema_from_map(records.iter().copied(), StreamType::Logs).unwrap();
+        assert!(apply_binary_fields(&cache.binary_fields, &schema, inferred, &records).is_err());
+    }
 }
@@ -0,0 +1,398 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use config::{
+    meta::{search, sql::Sql as MetaSql, stream::StreamType},
+    utils::{json, time::parse_timestamp_micro_from_value},
+    CONFIG,
+};
+use infra::errors::Error;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::RwLock;
+
+/// Origin of the histogram buckets, 2001-01-01T00:00:00Z as in the
+/// histogram rewrite of the search SQL
+const HISTOGRAM_ORIGIN: i64 = 978_307_200_000_000;
+
+static RE_HISTOGRAM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)histogram\(\s*"?(\w+)"?\s*,\s*'(\d+)\s*(second|minute|hour|day|week)s?'\s*\)\s+as\s+"?(\w+)"?"#).unwrap()
+});
+
+static RE_WHITESPACE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s+").unwrap());
+
+static RESULT_CACHE: Lazy<RwLock<HashMap<String, Entry>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// The complete buckets of a query
+#[derive(Debug)]
+struct Entry {
+    org_id: String,
+    stream_type: StreamType,
+    stream_name: String,
+    /// Microseconds
+    interval: i64,
+    /// Time range of the buckets, aligned on the interval
+    start_time: i64,
+    end_time: i64,
+    /// Hits with the start of their bucket
+    hits: Vec<(i64, json::Value)>,
+    last_used: i64,
+}
+
+/// How the results of a query are cached
+#[derive(Debug, PartialEq)]
+pub(crate) struct Plan {
+    key: String,
+    stream_name: String,
+    /// Column holding the bucket of the hits
+    column: String,
+    /// Microseconds
+    interval: i64,
+    descending: bool,
+}
+
+/// Only the aggregations over fixed histogram buckets of the timestamp,
+/// ordered by their bucket, are cached. The other queries are searched as
+/// usual.
+pub(crate) fn plan(
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+    can_decrypt: bool,
+) -> Option<Plan> {
+    if req.query.from > 0
+        || req.query.start_time == 0
+        || req.query.end_time == 0
+        || req.query.query_fn.is_some()
+        || req.query.sample > 0.0
+        || req.query.partial
+        || req.query.track_total_hits
+        || !req.aggs.is_empty()
+    {
+        return None;
+    }
+    let caps = RE_HISTOGRAM.captures(&req.query.sql)?;
+    if caps[1] != CONFIG.common.column_timestamp {
+        return None;
+    }
+    let unit = match caps[3].to_lowercase().as_str() {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        _ => 7 * 86400,
+    };
+    let interval = caps[2].parse::<i64>().ok()? * unit * 1_000_000;
+    let column = caps[4].to_string();
+    let meta = MetaSql::new(&req.query.sql).ok()?;
+    if interval == 0 || meta.limit > 0 || !meta.group_by.contains(&column) {
+        return None;
+    }
+    let descending = match meta.order_by.as_slice() {
+        [] => false,
+        [(field, desc)] if field == &column => *desc,
+        _ => return None,
+    };
+    let sql = RE_WHITESPACE.replace_all(req.query.sql.trim(), " ");
+    Some(Plan {
+        key: format!(
+            "{org_id}/{stream_type}/{}/{}/{}/{can_decrypt}/{}/{sql}",
+            req.query.sql_mode,
+            req.query.size,
+            req.query.uses_zo_fn,
+            req.clusters.join(",")
+        ),
+        stream_name: meta.source,
+        column,
+        interval,
+        descending,
+    })
+}
+
+fn align_down(t: i64, interval: i64) -> i64 {
+    HISTOGRAM_ORIGIN + (t - HISTOGRAM_ORIGIN).div_euclid(interval) * interval
+}
+
+fn align_up(t: i64, interval: i64) -> i64 {
+    let aligned = align_down(t, interval);
+    if aligned == t { t } else { aligned + interval }
+}
+
+/// Searches the buckets which are not cached and caches the complete ones,
+/// the buckets whose data may still be ingested are searched every time.
+pub(crate) async fn search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+    plan: &Plan,
+    can_decrypt: bool,
+) -> Result<search::Response, Error> {
+    let (start_time, end_time) = (req.query.start_time, req.query.end_time);
+    let now = Utc::now().timestamp_micros();
+    let delay = CONFIG.limit.query_result_cache_delay * 60 * 1_000_000;
+    let first = align_up(start_time, plan.interval);
+    let last = align_down(end_time.min(now - delay), plan.interval);
+    if first >= last {
+        return super::cluster_search(trace_id, org_id, stream_type, req, can_decrypt).await;
+    }
+
+    // the cached buckets from the first complete bucket on
+    let (cached_end, mut hits) = {
+        let mut cache = RESULT_CACHE.write().await;
+        match cache.get_mut(&plan.key) {
+            Some(entry) if entry.start_time <= first && entry.end_time > first => {
+                entry.last_used = now;
+                let cached_end = entry.end_time.min(last);
+                let hits = entry
+                    .hits
+                    .iter()
+                    .filter(|(bucket, _)| *bucket >= first && *bucket < cached_end)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                (cached_end, hits)
+            }
+            _ => (first, vec![]),
+        }
+    };
+
+    let mut gaps = vec![];
+    if cached_end > first {
+        if start_time < first {
+            gaps.push((start_time, first));
+        }
+        if cached_end < end_time {
+            gaps.push((cached_end, end_time));
+        }
+    } else {
+        gaps.push((start_time, end_time));
+    }
+    let mut res = search::Response::new(0, req.query.size);
+    res.trace_id = trace_id.to_string();
+    let mut complete = true;
+    for (gap_start, gap_end) in gaps {
+        let mut gap_req = req.clone();
+        gap_req.query.start_time = gap_start;
+        gap_req.query.end_time = gap_end;
+        let gap_res =
+            super::cluster_search(trace_id, org_id, stream_type, &gap_req, can_decrypt).await?;
+        // the buckets of a truncated result can't be merged
+        if gap_res.hits.len() >= req.query.size {
+            return super::cluster_search(trace_id, org_id, stream_type, req, can_decrypt).await;
+        }
+        for hit in gap_res.hits.iter() {
+            let Some(bucket) = hit
+                .get(&plan.column)
+                .and_then(|v| parse_timestamp_micro_from_value(v).ok())
+            else {
+                return super::cluster_search(trace_id, org_id, stream_type, req, can_decrypt)
+                    .await;
+            };
+            hits.push((bucket, hit.clone()));
+        }
+        res.took += gap_res.took;
+        res.file_count += gap_res.file_count;
+        res.scan_size += gap_res.scan_size;
+        res.scan_records += gap_res.scan_records;
+        res.columns = gap_res.columns;
+        res.function_error = gap_res.function_error;
+        // a part of the data was not searched, the buckets are not cached
+        if !gap_res.warning.is_empty() || gap_res.completeness.is_some() {
+            complete = false;
+            if !gap_res.warning.is_empty() {
+                res.warning = gap_res.warning;
+            }
+            if gap_res.completeness.is_some() {
+                res.completeness = gap_res.completeness;
+            }
+        }
+    }
+    hits.sort_by_key(|(bucket, _)| *bucket);
+    if plan.descending {
+        hits.reverse();
+    }
+
+    // cache the complete buckets of the merged result
+    if complete && hits.len() < req.query.size {
+        let entry = Entry {
+            org_id: org_id.to_string(),
+            stream_type,
+            stream_name: plan.stream_name.clone(),
+            interval: plan.interval,
+            start_time: first,
+            end_time: last,
+            hits: hits
+                .iter()
+                .filter(|(bucket, _)| *bucket >= first && *bucket < last)
+                .cloned()
+                .collect(),
+            last_used: now,
+        };
+        let mut cache = RESULT_CACHE.write().await;
+        if !cache.contains_key(&plan.key)
+            && cache.len() >= CONFIG.limit.query_result_cache_max_entries
+        {
+            if let Some(key) = cache
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(key, _)| key.clone())
+            {
+                cache.remove(&key);
+            }
+        }
+        cache.insert(plan.key.clone(), entry);
+    }
+
+    res.cache_ratio = Some(((cached_end - first) * 100) as f64 / (end_time - start_time) as f64);
+    res.hits = hits.into_iter().map(|(_, hit)| hit).collect();
+    res.hits.truncate(req.query.size);
+    res.total = res.hits.len();
+    Ok(res)
+}
+
+/// Drops the cached buckets of the stream from the start of the time range
+/// on, the files or the records holding them changed.
+pub(crate) async fn invalidate(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    start_time: i64,
+    end_time: i64,
+) {
+    let mut cache = RESULT_CACHE.write().await;
+    cache.retain(|_, entry| {
+        if entry.org_id != org_id
+            || entry.stream_type != stream_type
+            || entry.stream_name != stream_name
+            || entry.start_time >= end_time
+            || entry.end_time <= start_time
+        {
+            return true;
+        }
+        // buckets are kept up to the one holding the start of the range
+        let end = align_down(start_time, entry.interval).max(entry.start_time);
+        entry.end_time = end;
+        entry.hits.retain(|(bucket, _)| *bucket < end);
+        entry.end_time > entry.start_time
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(sql: &str) -> search::Request {
+        json::from_value(json::json!({
+            "query": {
+                "sql": sql,
+                "size": 100,
+                "start_time": HISTOGRAM_ORIGIN,
+                "end_time": HISTOGRAM_ORIGIN + 3600 * 1_000_000,
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan() {
+        let req = request(
+            "SELECT histogram(_timestamp, '5 minutes') AS zo_sql_key, count(*) AS zo_sql_num FROM t GROUP BY zo_sql_key ORDER BY zo_sql_key DESC",
+        );
+        let plan = plan("org", StreamType::Logs, &req, false).unwrap();
+        assert_eq!(plan.stream_name, "t");
+        assert_eq!(plan.column, "zo_sql_key");
+        assert_eq!(plan.interval, 300 * 1_000_000);
+        assert!(plan.descending);
+
+        // the interval picked from the time range changes with it
+        let req = request(
+            "SELECT histogram(_timestamp) AS zo_sql_key, count(*) AS zo_sql_num FROM t GROUP BY zo_sql_key",
+        );
+        assert_eq!(super::plan("org", StreamType::Logs, &req, false), None);
+        // ordered by the counts
+        let req = request(
+            "SELECT histogram(_timestamp, '1 hour') AS k, count(*) AS n FROM t GROUP BY k ORDER BY n DESC",
+        );
+        assert_eq!(super::plan("org", StreamType::Logs, &req, false), None);
+        let req = request("SELECT * FROM t");
+        assert_eq!(super::plan("org", StreamType::Logs, &req, false), None);
+
+        // the clusters searched are part of the key
+        let mut req =
+            request("SELECT histogram(_timestamp, '1 hour') AS k, count(*) AS n FROM t GROUP BY k");
+        let all = super::plan("org", StreamType::Logs, &req, false).unwrap();
+        req.clusters = vec!["local".to_string()];
+        let local = super::plan("org", StreamType::Logs, &req, false).unwrap();
+        assert_ne!(all.key, local.key);
+    }
+
+    #[test]
+    fn test_align() {
+        let minute = 60 * 1_000_000;
+        assert_eq!(
+            align_down(HISTOGRAM_ORIGIN + 90 * 1_000_000, minute),
+            HISTOGRAM_ORIGIN + minute
+        );
+        assert_eq!(
+            align_up(HISTOGRAM_ORIGIN + 90 * 1_000_000, minute),
+            HISTOGRAM_ORIGIN + 2 * minute
+        );
+        assert_eq!(
+            align_up(HISTOGRAM_ORIGIN + minute, minute),
+            HISTOGRAM_ORIGIN + minute
+        );
+        assert_eq!(
+            align_down(HISTOGRAM_ORIGIN - 1, minute),
+            HISTOGRAM_ORIGIN - minute
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalidate() {
+        let minute = 60 * 1_000_000;
+        let hits = (0..10)
+            .map(|i| (HISTOGRAM_ORIGIN + i * minute, json::json!({ "n": i })))
+            .collect::<Vec<_>>();
+        RESULT_CACHE.write().await.insert(
+            "test_invalidate".to_string(),
+            Entry {
+                org_id: "test_invalidate".to_string(),
+                stream_type: StreamType::Logs,
+                stream_name: "t".to_string(),
+                interval: minute,
+                start_time: HISTOGRAM_ORIGIN,
+                end_time: HISTOGRAM_ORIGIN + 10 * minute,
+                hits,
+                last_used: 0,
+            },
+        );
+        invalidate(
+            "test_invalidate",
+            StreamType::Logs,
+            "t",
+            HISTOGRAM_ORIGIN + 4 * minute + 1,
+            HISTOGRAM_ORIGIN + 20 * minute,
+        )
+        .await;
+        let cache = RESULT_CACHE.read().await;
+        let entry = cache.get("test_invalidate").unwrap();
+        assert_eq!(entry.end_time, HISTOGRAM_ORIGIN + 4 * minute);
+        assert_eq!(entry.hits.len(), 4);
+    }
+}
@@ -0,0 +1,483 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// answer trivial aggregations from the file_list statistics and the histogram sketches of
+// the files without reading any data
+
+use std::sync::Arc;
+
+use config::{
+    meta::{
+        search,
+        stream::{FileKey, StreamType},
+    },
+    utils::{json, parquet::read_sketch_from_footer, sketch::HistogramSketch},
+    RwHashMap, CONFIG,
+};
+use futures::{StreamExt, TryStreamExt};
+use infra::{
+    schema::{unwrap_partition_time_level, unwrap_stream_settings},
+    storage,
+};
+use once_cell::sync::Lazy;
+use parquet::file::{footer::decode_footer, FOOTER_SIZE};
+use proto::cluster_rpc;
+use regex::Regex;
+use sqlparser::{
+    ast::{
+        Expr, FunctionArg, FunctionArgExpr, GroupByExpr, SelectItem, SetExpr, Statement,
+        TableFactor,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
+
+use crate::{
+    common::infra::cluster as infra_cluster,
+    service::{
+        compact::tombstones,
+        file_list,
+        search::sql::{generate_histogram_interval, Sql},
+    },
+};
+
+/// The histogram aggregation of the logs page
+static RE_HISTOGRAM_AGG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)^\s*select\s+histogram\s*\(\s*"?(\w+)"?\s*(?:,\s*'([^']*)'\s*)?\)\s+as\s+"?(\w+)"?\s*,\s*count\s*\(\s*\*\s*\)\s+as\s+"?(\w+)"?\s+from\s+"?query"?\s+group\s+by\s+"?(\w+)"?\s+order\s+by\s+"?(\w+)"?(?:\s+(asc|desc))?\s*;?\s*$"#).unwrap()
+});
+
+/// Origin of the histogram bins, 2001-01-01T00:00:00
+const HISTOGRAM_ORIGIN: i64 = 978_307_200_000_000;
+
+/// Parquet footer bytes read at once, enough for the metadata of most files.
+const FOOTER_READ_SIZE: usize = 64 * 1024;
+
+const SKETCH_CACHE_MAX: usize = 100_000;
+
+// files are immutable, so are their sketches
+static SKETCHES: Lazy<RwHashMap<String, Option<Arc<HistogramSketch>>>> =
+    Lazy::new(Default::default);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StatsColumn {
+    Count,
+    MinTimestamp,
+    MaxTimestamp,
+}
+
+/// Returns the aliased columns of a `select count(*), min(_timestamp), max(_timestamp) from
+/// stream` query, `None` if the query needs to read data.
+fn parse_stats_columns(sql: &str) -> Option<Vec<(String, StatsColumn)>> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql).ok()?;
+    if statements.len() != 1 {
+        return None;
+    }
+    let Statement::Query(query) = statements.remove(0) else {
+        return None;
+    };
+    if query.with.is_some() || query.offset.is_some() || query.fetch.is_some() {
+        return None;
+    }
+    let SetExpr::Select(select) = *query.body else {
+        return None;
+    };
+    if select.distinct.is_some()
+        || select.selection.is_some()
+        || select.having.is_some()
+        || !matches!(&select.group_by, GroupByExpr::Expressions(v) if v.is_empty())
+        || select.from.len() != 1
+        || !select.from[0].joins.is_empty()
+        || !matches!(select.from[0].relation, TableFactor::Table { .. })
+    {
+        return None;
+    }
+
+    let mut columns = Vec::with_capacity(select.projection.len());
+    for item in select.projection.iter() {
+        // without an alias the column name depends on the query engine
+        let SelectItem::ExprWithAlias { expr, alias } = item else {
+            return None;
+        };
+        let Expr::Function(f) = expr else {
+            return None;
+        };
+        if f.distinct || f.over.is_some() || f.filter.is_some() || f.args.len() != 1 {
+            return None;
+        }
+        let FunctionArg::Unnamed(arg) = &f.args[0] else {
+            return None;
+        };
+        let is_timestamp = matches!(arg, FunctionArgExpr::Expr(Expr::Identifier(id)) if id.value == CONFIG.common.column_timestamp);
+        let column = match f.name.to_string().to_lowercase().as_str() {
+            "count" if matches!(arg, FunctionArgExpr::Wildcard) || is_timestamp => {
+                StatsColumn::Count
+            }
+            "min" if is_timestamp => StatsColumn::MinTimestamp,
+            "max" if is_timestamp => StatsColumn::MaxTimestamp,
+            _ => return None,
+        };
+        columns.push((alias.value.clone(), column));
+    }
+    Some(columns)
+}
+
+/// Computes the columns from the files, `None` if a file is only partly inside the time range.
+fn compute_stats(
+    files: &[FileKey],
+    columns: &[(String, StatsColumn)],
+    time_min: i64,
+    time_max: i64,
+) -> Option<json::Map<String, json::Value>> {
+    let mut count = 0;
+    let mut min_ts = None::<i64>;
+    let mut max_ts = None::<i64>;
+    for file in files {
+        if file.meta.max_ts < time_min || file.meta.min_ts >= time_max {
+            continue;
+        }
+        if file.meta.min_ts < time_min || file.meta.max_ts >= time_max {
+            return None;
+        }
+        count += file.meta.records;
+        min_ts = Some(min_ts.map_or(file.meta.min_ts, |v| v.min(file.meta.min_ts)));
+        max_ts = Some(max_ts.map_or(file.meta.max_ts, |v| v.max(file.meta.max_ts)));
+    }
+    Some(
+        columns
+            .iter()
+            .map(|(alias, column)| {
+                let value = match column {
+                    StatsColumn::Count => json::Value::from(count),
+                    StatsColumn::MinTimestamp => json::Value::from(min_ts),
+                    StatsColumn::MaxTimestamp => json::Value::from(max_ts),
+                };
+                (alias.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+// recent data may still be in the WAL of the ingesters
+fn is_settled(time_max: i64) -> bool {
+    let settled = chrono::Utc::now().timestamp_micros()
+        - ((CONFIG.limit.max_file_retention_time + CONFIG.limit.file_push_interval * 2) * 1_000_000)
+            as i64;
+    time_max > 0 && time_max <= settled
+}
+
+async fn query_files(
+    trace_id: &str,
+    sql: &Sql,
+    req: &cluster_rpc::SearchRequest,
+    time_min: i64,
+    time_max: i64,
+) -> Option<Vec<FileKey>> {
+    let stream_type = StreamType::from(req.stream_type.as_str());
+    let stream_settings = unwrap_stream_settings(&sql.schema).unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+    let is_local = CONFIG.common.meta_store_external
+        || infra_cluster::get_cached_online_querier_nodes()
+            .await
+            .unwrap_or_default()
+            .len()
+            <= 1;
+    match file_list::query(
+        &sql.org_id,
+        &sql.stream_name,
+        stream_type,
+        partition_time_level,
+        time_min,
+        time_max,
+        is_local,
+    )
+    .await
+    {
+        Ok(files) => Some(files),
+        Err(e) => {
+            log::error!("[trace_id {trace_id}] search->file_stats: get file_list error: {e}");
+            None
+        }
+    }
+}
+
+/// The file metadata still counts the records of pending deletes until the compactor
+/// rewrites the files
+fn has_tombstones(sql: &Sql, time_min: i64, time_max: i64) -> bool {
+    tombstones::search_condition(
+        &sql.org_id,
+        sql.stream_type,
+        &sql.stream_name,
+        Some((time_min, time_max)),
+    )
+    .is_some()
+}
+
+/// Answers `count(*)` and `min/max(_timestamp)` queries from the file_list, `None` means
+/// the query has to run normally.
+pub async fn search(
+    trace_id: &str,
+    sql: &Sql,
+    req: &cluster_rpc::SearchRequest,
+) -> Option<search::Response> {
+    let start = std::time::Instant::now();
+    let query = req.query.as_ref()?;
+    if !req.aggs.is_empty() || !query.query_fn.is_empty() || sql.uses_zo_fn || sql.meta.offset > 0 {
+        return None;
+    }
+    // the rewritten sql carries the time range filter, check the one of the user
+    let columns = parse_stats_columns(&query.sql)?;
+    let (time_min, time_max) = sql.meta.time_range?;
+    if !is_settled(time_max) || has_tombstones(sql, time_min, time_max) {
+        return None;
+    }
+    let files = query_files(trace_id, sql, req, time_min, time_max).await?;
+    let hit = compute_stats(&files, &columns, time_min, time_max)?;
+
+    let mut result = search::Response::new(sql.meta.offset, sql.meta.limit);
+    result.add_hit(&json::Value::Object(hit));
+    result.set_total(1);
+    result.set_cluster_took(start.elapsed().as_millis() as usize, 0);
+    log::info!(
+        "[trace_id {trace_id}] search->file_stats: answered from {} files, took: {}",
+        files.len(),
+        result.took,
+    );
+    Some(result)
+}
+
+struct HistogramAgg {
+    interval: Option<String>,
+    key: String,
+    num: String,
+    desc: bool,
+}
+
+fn parse_histogram_agg(sql: &str) -> Option<HistogramAgg> {
+    let caps = RE_HISTOGRAM_AGG.captures(sql)?;
+    let key = caps[3].to_string();
+    if caps[1] != CONFIG.common.column_timestamp || caps[5] != key || caps[6] != key {
+        return None;
+    }
+    Some(HistogramAgg {
+        interval: caps.get(2).map(|v| v.as_str().to_string()),
+        key,
+        num: caps[4].to_string(),
+        desc: caps
+            .get(7)
+            .is_some_and(|v| v.as_str().eq_ignore_ascii_case("desc")),
+    })
+}
+
+/// Parses an interval like `5 minute` to microseconds.
+fn parse_interval(interval: &str) -> Option<i64> {
+    let (num, unit) = interval.trim().split_once(' ')?;
+    let num = num.trim().parse::<i64>().ok()?;
+    let unit = match unit.trim().trim_end_matches('s').to_lowercase().as_str() {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        _ => return None,
+    };
+    Some(num * unit * 1_000_000)
+}
+
+async fn get_sketch(file: &FileKey) -> Result<Option<Arc<HistogramSketch>>, anyhow::Error> {
+    if let Some(sketch) = SKETCHES.get(&file.key) {
+        return Ok(sketch.clone());
+    }
+    let size = file.meta.compressed_size as usize;
+    if size < FOOTER_SIZE {
+        return Ok(None);
+    }
+    let mut footer = storage::get_range(&file.key, size - size.min(FOOTER_READ_SIZE)..size).await?;
+    let metadata_len = decode_footer(footer[footer.len() - FOOTER_SIZE..].try_into()?)?;
+    if metadata_len + FOOTER_SIZE > footer.len() && metadata_len + FOOTER_SIZE <= size {
+        footer = storage::get_range(&file.key, size - metadata_len - FOOTER_SIZE..size).await?;
+    }
+    let sketch = read_sketch_from_footer(&footer)?.map(Arc::new);
+    if SKETCHES.len() >= SKETCH_CACHE_MAX {
+        SKETCHES.clear();
+    }
+    SKETCHES.insert(file.key.clone(), sketch.clone());
+    Ok(sketch)
+}
+
+/// Answers the histogram aggregation of an unfiltered logs query from the sketches of the
+/// files. On success the aggregation is removed from the request and its hits returned.
+pub async fn search_histogram(
+    trace_id: &str,
+    sql: &Sql,
+    req: &mut cluster_rpc::SearchRequest,
+) -> Option<(String, Vec<json::Value>)> {
+    let start = std::time::Instant::now();
+    if !CONFIG.common.histogram_sketch_enabled
+        || sql.stream_type != StreamType::Logs
+        || sql.meta.selection.is_some()
+        || !sql.fulltext.is_empty()
+    {
+        return None;
+    }
+    let (name, agg) = req
+        .aggs
+        .iter()
+        .find_map(|agg| parse_histogram_agg(&agg.sql).map(|v| (agg.name.clone(), v)))?;
+    let (time_min, time_max) = sql.meta.time_range?;
+    if time_min <= 0 || !is_settled(time_max) || has_tombstones(sql, time_min, time_max) {
+        return None;
+    }
+    let interval = match agg.interval.as_deref().map(|v| (v, v.parse::<u16>())) {
+        Some((_, Ok(num))) => generate_histogram_interval(sql.meta.time_range, num),
+        Some((v, Err(_))) => v.to_string(),
+        None => generate_histogram_interval(sql.meta.time_range, 0),
+    };
+    let interval = parse_interval(&interval)?;
+
+    let files = query_files(trace_id, sql, req, time_min, time_max).await?;
+    let files = files
+        .iter()
+        .filter(|f| f.meta.max_ts >= time_min && f.meta.min_ts < time_max)
+        .collect::<Vec<_>>();
+    let sketches = futures::stream::iter(files.iter().map(|f| get_sketch(f)))
+        .buffer_unordered(CONFIG.limit.cpu_num.max(1))
+        .try_collect::<Vec<_>>()
+        .await;
+    let sketches = match sketches {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("[trace_id {trace_id}] search->file_stats: get sketch error: {e}");
+            return None;
+        }
+    };
+    let mut sketch = HistogramSketch::default();
+    for v in sketches {
+        // files written before sketches existed need a scan
+        sketch.merge(v.as_deref()?);
+    }
+    let bins = sketch.histogram(time_min, time_max, interval, HISTOGRAM_ORIGIN)?;
+
+    let mut hits = Vec::with_capacity(bins.len());
+    for (bin, count) in bins {
+        let key = chrono::DateTime::from_timestamp_micros(bin)?
+            .naive_utc()
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string();
+        hits.push(json::json!({ &agg.key: key, &agg.num: count }));
+    }
+    if agg.desc {
+        hits.reverse();
+    }
+    req.aggs.retain(|v| v.name != name);
+    log::info!(
+        "[trace_id {trace_id}] search->file_stats: histogram answered from {} sketches, took: {}",
+        files.len(),
+        start.elapsed().as_millis(),
+    );
+    Some((name, hits))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    fn file(min_ts: i64, max_ts: i64, records: i64) -> FileKey {
+        FileKey {
+            key: format!("{min_ts}.parquet"),
+            meta: FileMeta {
+                min_ts,
+                max_ts,
+                records,
+                ..Default::default()
+            },
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_stats_columns() {
+        assert_eq!(
+            parse_stats_columns(
+                "select count(*) as num, min(_timestamp) as first, MAX(_timestamp) AS last from t"
+            ),
+            Some(vec![
+                ("num".to_string(), StatsColumn::Count),
+                ("first".to_string(), StatsColumn::MinTimestamp),
+                ("last".to_string(), StatsColumn::MaxTimestamp),
+            ])
+        );
+        assert_eq!(parse_stats_columns("select count(*) from t"), None);
+        assert_eq!(
+            parse_stats_columns("select count(*) as num from t where a = 1"),
+            None
+        );
+        assert_eq!(
+            parse_stats_columns("select count(*) as num from t group by a"),
+            None
+        );
+        assert_eq!(parse_stats_columns("select min(a) as v from t"), None);
+        assert_eq!(
+            parse_stats_columns("select count(distinct a) as v from t"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_histogram_agg() {
+        let agg = parse_histogram_agg(
+            "select histogram(_timestamp, '5 minute') AS zo_sql_key, count(*) AS zo_sql_num from query GROUP BY zo_sql_key ORDER BY zo_sql_key",
+        )
+        .unwrap();
+        assert_eq!(agg.interval.as_deref(), Some("5 minute"));
+        assert_eq!(agg.key, "zo_sql_key");
+        assert_eq!(agg.num, "zo_sql_num");
+        assert!(!agg.desc);
+        assert!(parse_histogram_agg(
+            "select histogram(_timestamp) AS k, count(*) AS n from query GROUP BY k ORDER BY k DESC"
+        )
+        .is_some_and(|v| v.desc && v.interval.is_none()));
+        // a filtered or grouped histogram needs a scan
+        assert!(parse_histogram_agg(
+            "select histogram(_timestamp) AS k, count(*) AS n from query where a=1 GROUP BY k ORDER BY k"
+        )
+        .is_none());
+        assert!(parse_histogram_agg(
+            "select histogram(_timestamp) AS k, count(*) AS n from query GROUP BY k, a ORDER BY k"
+        )
+        .is_none());
+
+        assert_eq!(parse_interval("5 minute"), Some(300_000_000));
+        assert_eq!(parse_interval("1 hours"), Some(3_600_000_000));
+        assert_eq!(parse_interval("1 week"), None);
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let columns = vec![
+            ("num".to_string(), StatsColumn::Count),
+            ("first".to_string(), StatsColumn::MinTimestamp),
+            ("last".to_string(), StatsColumn::MaxTimestamp),
+        ];
+        let files = vec![file(10, 20, 5), file(30, 40, 7), file(100, 200, 1)];
+        let hit = compute_stats(&files, &columns, 10, 50).unwrap();
+        assert_eq!(hit.get("num").unwrap(), 12);
+        assert_eq!(hit.get("first").unwrap(), 10);
+        assert_eq!(hit.get("last").unwrap(), 40);
+        // a file crossing the time range needs a scan
+        assert!(compute_stats(&files, &columns, 15, 50).is_none());
+    }
+}
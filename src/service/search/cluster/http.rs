@@ -17,14 +17,19 @@ use std::sync::Arc;
 
 use ::datafusion::arrow::{json as arrow_json, record_batch::RecordBatch};
 use config::{
-    meta::search,
-    utils::{flatten, json},
+    meta::search::{self, SampleInfo},
+    utils::{flatten, json, record_batch_ext::encode_binary_columns},
+};
+use infra::{
+    errors::{Error, ErrorCodes, Result},
+    schema::unwrap_stream_settings,
 };
-use infra::errors::{Error, ErrorCodes, Result};
 use proto::cluster_rpc;
 use vector_enrichment::TableRegistry;
 
-use crate::common::meta::functions::VRLResultResolver;
+use crate::{
+    common::meta::functions::VRLResultResolver, service::search::nested::promote_nested_fields,
+};
 
 #[tracing::instrument(
     name = "service:search:cluster",
@@ -35,6 +40,7 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
     let start = std::time::Instant::now();
     let trace_id = req.job.as_ref().unwrap().trace_id.clone();
     let query_type = req.query.as_ref().unwrap().query_type.to_lowercase();
+    let nested = req.query.as_ref().unwrap().nested;
 
     // handle request time range
     let meta = super::super::sql::Sql::new(&req).await?;
@@ -43,6 +49,16 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
     }
     let sql = Arc::new(meta);
 
+    // the streams people query are compacted first
+    crate::service::compact::priority::record_query(&sql.org_id, sql.stream_type, &sql.stream_name);
+
+    // count(*) and min/max(_timestamp) can be answered from the file_list
+    if let Some(result) = super::file_stats::search(&trace_id, &sql, &req).await {
+        return Ok(result);
+    }
+    // the histogram of the logs page can be answered from the file sketches
+    let sketch_histogram = super::file_stats::search_histogram(&trace_id, &sql, &mut req).await;
+
     // set this value to null & use it later on results ,
     // this being to avoid performance impact of query fn being applied during query
     // execution
@@ -63,6 +79,33 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
         None => &empty_vec,
     };
 
+    // promote the nested fields of the stream to structs
+    let nested_fields = if nested
+        && query_fn.is_empty()
+        && !sql.uses_zo_fn
+        && query_type != "table"
+        && query_type != "metrics"
+    {
+        unwrap_stream_settings(&sql.schema)
+            .map(|s| s.nested_fields)
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+    // binary columns are returned as base64 strings
+    let batches_query = batches_query
+        .iter()
+        .map(|batch| {
+            let batch = encode_binary_columns(batch)?;
+            if nested_fields.is_empty() {
+                Ok(batch)
+            } else {
+                promote_nested_fields(&batch, &nested_fields)
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::ErrorCode(ErrorCodes::ServerInternalError(e.to_string())))?;
+
     if !batches_query.is_empty() {
         let schema = batches_query[0].schema();
         let batches_query_ref: Vec<&RecordBatch> = batches_query.iter().collect();
@@ -159,6 +202,12 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
         }
     }
 
+    if let Some((name, hits)) = sketch_histogram {
+        for hit in hits {
+            result.add_agg(&name, &hit);
+        }
+    }
+
     // total
     let total = match result.aggs.get("_count") {
         Some(v) => v.first().unwrap().get("num").unwrap().as_u64().unwrap() as usize,
@@ -189,6 +238,23 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
     result.set_file_count(scan_stats.files as usize);
     result.set_scan_size(scan_stats.original_size as usize);
     result.set_scan_records(scan_stats.records as usize);
+    if scan_stats.sample_records > 0 {
+        result.sample = Some(SampleInfo {
+            rate: 0.0,
+            fraction: scan_stats.records as f64 / scan_stats.sample_records as f64,
+            files: scan_stats.files as usize,
+        });
+    }
+    if let Some(completeness) = completeness(scan_stats.files, scan_stats.skipped_files) {
+        result.completeness = Some(completeness);
+        if result.warning.is_empty() {
+            result.warning = format!(
+                "Query timed out after searching {} of {} files, results are partial",
+                (scan_stats.files - scan_stats.skipped_files).max(0),
+                scan_stats.files
+            );
+        }
+    }
 
     if query_type == "table" {
         result.response_type = "table".to_string();
@@ -205,3 +271,27 @@ pub async fn search(mut req: cluster_rpc::SearchRequest) -> Result<search::Respo
 
     Ok(result)
 }
+
+/// Percent of the files searched by a partial query which timed out. The files
+/// count the WAL files, which are always searched in full.
+fn completeness(files: i64, skipped_files: i64) -> Option<f64> {
+    if skipped_files <= 0 || files <= 0 {
+        return None;
+    }
+    let searched = (files - skipped_files).max(0);
+    Some(searched as f64 * 100.0 / files as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completeness() {
+        assert_eq!(completeness(10, 0), None);
+        assert_eq!(completeness(0, 0), None);
+        assert_eq!(completeness(10, 4), Some(60.0));
+        assert_eq!(completeness(10, 10), Some(0.0));
+        assert_eq!(completeness(4, 8), Some(0.0));
+    }
+}
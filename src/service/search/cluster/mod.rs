@@ -26,6 +26,7 @@ use config::{
             FileKey, PartitionTimeLevel, QueryPartitionStrategy, StreamPartition, StreamType,
         },
     },
+    metrics,
     utils::json,
     CONFIG, DEFAULT_INDEX_TRIM_CHARS, INDEX_MIN_CHAR_LEN,
 };
@@ -43,6 +44,7 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{common::infra::cluster as infra_cluster, service::file_list};
 
+pub mod file_stats;
 pub mod grpc;
 pub mod http;
 #[cfg(feature = "enterprise")]
@@ -254,6 +256,18 @@ pub async fn search(
         )
     };
 
+    // sampled queries scan a subset of the files and skip the WAL, whose
+    // records aren't in the file list
+    let sample = req.query.as_ref().map(|q| q.sample).unwrap_or_default();
+    let mut sample_records = 0;
+    let file_list = if super::sample::is_sampled(sample) {
+        req.query.as_mut().unwrap().skip_wal = true;
+        sample_records = file_list.iter().map(|f| f.meta.records).sum();
+        super::sample::files(file_list, sample)
+    } else {
+        file_list
+    };
+
     let file_list_took = start.elapsed().as_millis() as usize;
     log::info!(
         "[trace_id {trace_id}] search: get file_list time_range: {:?}, num: {}, took: {}",
@@ -276,6 +290,7 @@ pub async fn search(
     };
 
     let locker_key = "/search/cluster_queue/".to_string() + work_group_str.as_str();
+    let mut queue_guard = QueryQueueGuard::new();
     // get a cluster search queue lock
     let locker = if CONFIG.common.local_mode || !CONFIG.common.feature_query_queue_enabled {
         None
@@ -305,6 +320,7 @@ pub async fn search(
     }
     #[cfg(feature = "enterprise")]
     dist_lock::unlock(&locker).await?;
+    queue_guard.start();
     let took_wait = start.elapsed().as_millis() as usize - file_list_took;
     log::info!(
         "[trace_id {trace_id}] search: wait in queue took: {}",
@@ -564,7 +580,7 @@ pub async fn search(
         }
     }
 
-    let (merge_batches, scan_stats) =
+    let (merge_batches, mut scan_stats) =
         match merge_grpc_result(trace_id, meta.clone(), results, is_final_phase).await {
             Ok(v) => v,
             Err(e) => {
@@ -594,6 +610,7 @@ pub async fn search(
         .await
         .map_err(|e| Error::Message(e.to_string()))?;
 
+    scan_stats.sample_records = sample_records;
     Ok((merge_batches, scan_stats, inverted_index_count, took_wait))
 }
 
@@ -684,6 +701,7 @@ async fn merge_grpc_result(
         tokio::select! {
             res = super::datafusion::exec::merge(
                 &sql.org_id,
+                sql.can_decrypt,
                 sql.meta.offset,
                 sql.meta.limit,
                 &merge_sql,
@@ -809,6 +827,37 @@ pub(crate) async fn partition_file_by_hash<'a>(
     partitions
 }
 
+/// Counts a search as pending in the query queue until it starts and as
+/// running until it finishes, the autoscaler reads the gauges.
+struct QueryQueueGuard {
+    running: bool,
+}
+
+impl QueryQueueGuard {
+    fn new() -> Self {
+        metrics::QUERY_PENDING_NUMS.with_label_values(&[]).inc();
+        Self { running: false }
+    }
+
+    fn start(&mut self) {
+        if !self.running {
+            metrics::QUERY_PENDING_NUMS.with_label_values(&[]).dec();
+            metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).inc();
+            self.running = true;
+        }
+    }
+}
+
+impl Drop for QueryQueueGuard {
+    fn drop(&mut self) {
+        if self.running {
+            metrics::QUERY_RUNNING_NUMS.with_label_values(&[]).dec();
+        } else {
+            metrics::QUERY_PENDING_NUMS.with_label_values(&[]).dec();
+        }
+    }
+}
+
 fn handle_table_response(
     schema: Arc<Schema>,
     sources: Vec<json::Value>,
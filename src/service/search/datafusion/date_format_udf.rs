@@ -15,7 +15,6 @@
 
 use std::{iter::zip, sync::Arc};
 
-use chrono::{FixedOffset, TimeZone, Utc};
 use config::utils::time;
 use datafusion::{
     arrow::{
@@ -31,6 +30,8 @@ use datafusion::{
 use datafusion_expr::ColumnarValue;
 use once_cell::sync::Lazy;
 
+use super::timezone_udf::{cached_zone, Zone};
+
 /// The name of the date_format UDF given to DataFusion.
 pub const DATE_FORMAT_UDF_NAME: &str = "date_format";
 
@@ -65,7 +66,8 @@ pub fn date_format_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Resul
     let format = as_string_array(&args[1]).expect("cast failed");
     let timezone = as_string_array(&args[2]).expect("cast failed");
 
-    // 2. perform the computation
+    // 2. perform the computation, the zone is an offset like +08:00 or an IANA name
+    let mut last_zone: Option<(&str, Zone)> = None;
     let array = zip(timestamp.iter(), zip(format.iter(), timezone.iter()))
         .map(|(timestamp, val)| {
             match (timestamp, val) {
@@ -73,20 +75,13 @@ pub fn date_format_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Resul
                 // Here we decide to make our UDF to return null when either argument is null.
                 (Some(timestamp), (Some(format), Some(timezone))) => {
                     let timestamp = time::parse_i64_to_timestamp_micros(timestamp);
-                    let t = Utc.timestamp_nanos(timestamp * 1000);
-                    let offset = time::parse_timezone_to_offset(timezone) as i32;
-                    let result = if offset == 0 {
-                        t.format(format).to_string()
-                    } else {
-                        let t = t.with_timezone(&FixedOffset::east_opt(offset).unwrap());
-                        t.format(format).to_string()
-                    };
-                    Some(result)
+                    let zone = cached_zone(&mut last_zone, timezone)?;
+                    Ok(Some(zone.to_local(timestamp).format(format).to_string()))
                 }
-                _ => None,
+                _ => Ok(None),
             }
         })
-        .collect::<StringArray>();
+        .collect::<datafusion::error::Result<StringArray>>()?;
 
     // `Ok` because no error occurred during the calculation
     // `Arc` because arrays are immutable, thread-safe, trait objects.
@@ -162,6 +157,16 @@ mod tests {
                     "+---------------------+",
                 ],
             ),
+            (
+                "select date_format(time, '%Y-%m-%dT%H:%M:%S', 'America/Los_Angeles') as ret from t",
+                vec![
+                    "+---------------------+",
+                    "| ret                 |",
+                    "+---------------------+",
+                    "| 2020-12-31T16:00:00 |",
+                    "+---------------------+",
+                ],
+            ),
         ];
 
         // define a schema.
@@ -0,0 +1,71 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray},
+        datatypes::DataType,
+    },
+    common::cast::as_string_array,
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+
+use crate::service::encryption;
+
+/// The name of the decrypt UDF given to DataFusion.
+pub const DECRYPT_UDF_NAME: &str = "decrypt";
+
+/// Builds the decrypt UDF bound to the encryption key of an org
+pub(crate) fn decrypt_udf(key: [u8; 32]) -> ScalarUDF {
+    create_udf(
+        DECRYPT_UDF_NAME,
+        // expects an encrypted string field
+        vec![DataType::Utf8],
+        // returns string
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        Arc::new(move |args: &[ColumnarValue]| decrypt_expr_impl(&key, args)),
+    )
+}
+
+/// decrypt function for datafusion, values which can't be decrypted are returned as is
+pub fn decrypt_expr_impl(
+    key: &[u8; 32],
+    args: &[ColumnarValue],
+) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError("UDF params should be: decrypt(field)".to_string()),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+
+    let array = values
+        .iter()
+        .map(|value| {
+            value.map(|v| encryption::decrypt_value(key, v).unwrap_or_else(|| v.to_string()))
+        })
+        .collect::<StringArray>();
+
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
@@ -20,9 +20,12 @@ use config::{
     meta::{
         search::{SearchType, Session as SearchSession, StorageType},
         sql,
-        stream::{FileKey, FileMeta, StreamType},
+        stream::{FileKey, FileMeta, ParquetOptions, StreamType},
+    },
+    utils::{
+        flatten, json, parquet::new_parquet_writer_with_options,
+        schema::infer_json_schema_from_values, sketch::HistogramSketch,
     },
-    utils::{flatten, json, parquet::new_parquet_writer, schema::infer_json_schema_from_values},
     CONFIG, PARQUET_BATCH_SIZE,
 };
 use datafusion::{
@@ -54,13 +57,20 @@ use once_cell::sync::Lazy;
 use parquet::arrow::ArrowWriter;
 use regex::Regex;
 
-use super::{storage::file_list, transform_udf::get_all_transform};
+use super::{
+    percentile_udf::{
+        PERCENTILE_TDIGEST_FINAL_UDF_NAME, PERCENTILE_TDIGEST_MERGE_UDF_NAME,
+        PERCENTILE_TDIGEST_UDF_NAME,
+    },
+    storage::file_list,
+    transform_udf::get_all_transform,
+};
 use crate::{
     common::meta::functions::VRLResultResolver,
     service::search::{datafusion::rewrite, sql::Sql, RE_SELECT_WILDCARD},
 };
 
-const AGGREGATE_UDF_LIST: [&str; 7] = [
+const AGGREGATE_UDF_LIST: [&str; 8] = [
     "min",
     "max",
     "count",
@@ -68,6 +78,7 @@ const AGGREGATE_UDF_LIST: [&str; 7] = [
     "sum",
     "array_agg",
     "approx_percentile_cont",
+    PERCENTILE_TDIGEST_UDF_NAME,
 ];
 
 static RE_WHERE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i) where (.*)").unwrap());
@@ -148,9 +159,9 @@ pub async fn sql(
     };
 
     // register UDF
-    register_udf(&mut ctx, &sql.org_id).await;
+    register_udf(&mut ctx, &sql.org_id, sql.can_decrypt).await;
     if let Some(ctx_aggs) = &mut ctx_aggs {
-        register_udf(ctx_aggs, &sql.org_id).await;
+        register_udf(ctx_aggs, &sql.org_id, sql.can_decrypt).await;
     }
 
     let mut result: HashMap<String, Vec<RecordBatch>> = HashMap::new();
@@ -517,7 +528,7 @@ async fn get_fast_mode_ctx(
     .await?;
 
     // register UDF
-    register_udf(&mut ctx, &sql.org_id).await;
+    register_udf(&mut ctx, &sql.org_id, sql.can_decrypt).await;
 
     Ok((ctx, schema))
 }
@@ -539,8 +550,10 @@ fn replace_in_query(replace_pat: &str, where_query: &mut String, is_alias: bool)
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn merge(
     org_id: &str,
+    can_decrypt: bool,
     offset: usize,
     limit: usize,
     sql: &str,
@@ -635,7 +648,7 @@ pub async fn merge(
     ctx.register_table("tbl", Arc::new(table))?;
 
     // register UDF
-    register_udf(&mut ctx, org_id).await;
+    register_udf(&mut ctx, org_id, can_decrypt).await;
 
     // Debug SQL
     if CONFIG.common.print_key_sql {
@@ -896,6 +909,27 @@ fn merge_rewrite_sql(sql: &str, schema: Arc<Schema>, is_final_phase: bool) -> Re
                 "{fn_name}(\"{}\", {}) {}",
                 schema_field, percentile, over_as
             );
+        } else if fn_name == PERCENTILE_TDIGEST_UDF_NAME {
+            // nodes return digests, only the final phase turns them into a value
+            fields[i] = if is_final_phase {
+                let percentile = cap
+                    .get(2)
+                    .unwrap()
+                    .as_str()
+                    .splitn(2, ',')
+                    .last()
+                    .unwrap()
+                    .trim();
+                format!(
+                    "{PERCENTILE_TDIGEST_FINAL_UDF_NAME}(\"{}\", {}) {}",
+                    schema_field, percentile, over_as
+                )
+            } else {
+                format!(
+                    "{PERCENTILE_TDIGEST_MERGE_UDF_NAME}(\"{}\") {}",
+                    schema_field, over_as
+                )
+            };
         } else {
             fields[i] = format!("{fn_name}(\"{}\") {}", schema_field, over_as);
         }
@@ -913,12 +947,14 @@ fn merge_rewrite_sql(sql: &str, schema: Arc<Schema>, is_final_phase: bool) -> Re
     Ok(sql)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn convert_parquet_file(
     trace_id: &str,
     buf: &mut Vec<u8>,
     schema: Arc<Schema>,
     bloom_filter_fields: &[String],
     full_text_search_fields: &[String],
+    parquet_options: &ParquetOptions,
     rules: HashMap<String, DataType>,
     file_type: FileType,
 ) -> Result<()> {
@@ -1011,12 +1047,14 @@ pub async fn convert_parquet_file(
     let schema = Arc::new(schema);
     let batches = df.collect().await?;
     let file_meta = FileMeta::default();
-    let mut writer = new_parquet_writer(
+    let mut writer = new_parquet_writer_with_options(
         buf,
         &schema,
         bloom_filter_fields,
         full_text_search_fields,
         &file_meta,
+        None,
+        parquet_options,
     );
     for batch in batches {
         writer.write(&batch).await?;
@@ -1042,9 +1080,17 @@ pub async fn merge_parquet_files(
     schema: Arc<Schema>,
     bloom_filter_fields: &[String],
     full_text_search_fields: &[String],
+    parquet_options: &ParquetOptions,
     original_size: i64,
     fts_buf: &mut Vec<RecordBatch>,
+    delete_filter: Option<&str>,
 ) -> Result<(FileMeta, Arc<Schema>)> {
+    // drop the records deleted by a tombstone
+    let where_sql = match delete_filter {
+        Some(filter) => format!(" WHERE NOT ({filter})"),
+        None => "".to_string(),
+    };
+
     // query data
     let runtime_env = create_runtime_env(None)?;
     let session_config = create_session_config(&SearchType::Normal)?;
@@ -1065,8 +1111,8 @@ pub async fn merge_parquet_files(
 
     // get meta data
     let meta_sql = format!(
-        "SELECT MIN({}) as min_ts, MAX({}) as max_ts, COUNT(1) as num_records FROM tbl",
-        CONFIG.common.column_timestamp, CONFIG.common.column_timestamp
+        "SELECT MIN({}) as min_ts, MAX({}) as max_ts, COUNT(1) as num_records FROM tbl{}",
+        CONFIG.common.column_timestamp, CONFIG.common.column_timestamp, where_sql
     );
     let df = ctx.sql(&meta_sql).await?;
     let batches = df.collect().await?;
@@ -1085,6 +1131,10 @@ pub async fn merge_parquet_files(
                     original_size,
                     compressed_size: 0,
                 },
+                None if delete_filter.is_some() => {
+                    // all the records are deleted
+                    return Ok((FileMeta::default(), schema));
+                }
                 None => {
                     return Err(DataFusionError::Execution(
                         "merge_parquet_files: Invalid file meta data".to_string(),
@@ -1118,8 +1168,8 @@ pub async fn merge_parquet_files(
         )
     } else {
         format!(
-            "SELECT * FROM tbl ORDER BY {} DESC",
-            CONFIG.common.column_timestamp
+            "SELECT * FROM tbl{} ORDER BY {} DESC",
+            where_sql, CONFIG.common.column_timestamp
         )
     };
 
@@ -1136,12 +1186,20 @@ pub async fn merge_parquet_files(
     let schema = Arc::new(schema);
     let batches = df.collect().await?;
 
-    let mut writer = new_parquet_writer(
+    // pre-aggregate the histogram of logs, so it can be answered without a scan
+    let sketch = if stream_type == StreamType::Logs && CONFIG.common.histogram_sketch_enabled {
+        HistogramSketch::from_batches(&batches)
+    } else {
+        None
+    };
+    let mut writer = new_parquet_writer_with_options(
         buf,
         &schema,
         bloom_filter_fields,
         full_text_search_fields,
         &file_meta,
+        sketch.as_ref(),
+        parquet_options,
     );
     for batch in batches {
         if stream_type == StreamType::Logs {
@@ -1280,7 +1338,7 @@ pub fn prepare_datafusion_context(
     }
 }
 
-async fn register_udf(ctx: &mut SessionContext, _org_id: &str) {
+async fn register_udf(ctx: &mut SessionContext, _org_id: &str, can_decrypt: bool) {
     ctx.register_udf(super::match_udf::MATCH_UDF.clone());
     ctx.register_udf(super::match_udf::MATCH_IGNORE_CASE_UDF.clone());
     ctx.register_udf(super::regexp_udf::REGEX_MATCH_UDF.clone());
@@ -1288,7 +1346,25 @@ async fn register_udf(ctx: &mut SessionContext, _org_id: &str) {
     ctx.register_udf(super::regexp_udf::REGEXP_MATCH_TO_FIELDS_UDF.clone());
     ctx.register_udf(super::time_range_udf::TIME_RANGE_UDF.clone());
     ctx.register_udf(super::date_format_udf::DATE_FORMAT_UDF.clone());
+    ctx.register_udf(super::timezone_udf::DATE_TRUNC_TZ_UDF.clone());
+    ctx.register_udf(super::timezone_udf::TO_TIMEZONE_UDF.clone());
+    ctx.register_udf(super::units_udf::PARSE_DURATION_UDF.clone());
+    ctx.register_udf(super::units_udf::PARSE_BYTES_UDF.clone());
     ctx.register_udf(super::string_to_array_v2_udf::STRING_TO_ARRAY_V2_UDF.clone());
+    ctx.register_udf(super::hex_preview_udf::HEX_PREVIEW_UDF.clone());
+    ctx.register_udf(super::http_lookup_udf::HTTP_LOOKUP_UDF.clone());
+    ctx.register_udaf(super::percentile_udf::PERCENTILE_TDIGEST_UDF.clone());
+    ctx.register_udaf(super::percentile_udf::PERCENTILE_TDIGEST_MERGE_UDF.clone());
+    ctx.register_udaf(super::percentile_udf::PERCENTILE_TDIGEST_FINAL_UDF.clone());
+    // the key only exists once the org ingested encrypted fields, the function
+    // is unknown to the users who can't decrypt them
+    if can_decrypt {
+        match crate::service::encryption::get_key(_org_id).await {
+            Ok(Some(key)) => ctx.register_udf(super::decrypt_udf::decrypt_udf(key)),
+            Ok(None) => {}
+            Err(e) => log::error!("get encryption key for org {_org_id} error: {e}"),
+        }
+    }
 
     {
         let udf_list = get_all_transform(_org_id).await;
@@ -1536,4 +1612,21 @@ mod tests {
 
         assert!(!res.is_empty())
     }
+
+    #[test]
+    fn test_merge_rewrite_sql_percentile_tdigest() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k8s_namespace_name", DataType::Utf8, true),
+            Field::new("p99", DataType::Binary, true),
+        ]));
+        let sql = "SELECT k8s_namespace_name, percentile_tdigest(took, 0.99) AS p99 FROM tbl GROUP BY k8s_namespace_name";
+        assert_eq!(
+            merge_rewrite_sql(sql, schema.clone(), false).unwrap(),
+            "SELECT k8s_namespace_name, percentile_tdigest_merge(\"p99\") AS \"p99\" FROM tbl GROUP BY k8s_namespace_name"
+        );
+        assert_eq!(
+            merge_rewrite_sql(sql, schema, true).unwrap(),
+            "SELECT k8s_namespace_name, percentile_tdigest_final(\"p99\", 0.99) AS \"p99\" FROM tbl GROUP BY k8s_namespace_name"
+        );
+    }
 }
@@ -0,0 +1,130 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{iter::zip, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray},
+        datatypes::DataType,
+    },
+    common::cast::{as_binary_array, as_int64_array},
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use once_cell::sync::Lazy;
+
+/// The name of the hex_preview UDF given to DataFusion.
+pub const HEX_PREVIEW_UDF_NAME: &str = "hex_preview";
+
+/// Implementation of hex_preview
+pub(crate) static HEX_PREVIEW_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        HEX_PREVIEW_UDF_NAME,
+        // expects a binary field and the number of bytes to show
+        vec![DataType::Binary, DataType::Int64],
+        // returns string
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        Arc::new(hex_preview_expr_impl),
+    )
+});
+
+/// hex_preview function for datafusion, renders the first bytes of a binary value as hex
+pub fn hex_preview_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 2 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError("UDF params should be: hex_preview(field, bytes)".to_string()),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_binary_array(&args[0]).expect("cast failed");
+    let sizes = as_int64_array(&args[1]).expect("cast failed");
+
+    let array = zip(values.iter(), sizes.iter())
+        .map(|(value, size)| match (value, size) {
+            (Some(value), Some(size)) => {
+                let size = size.max(0) as usize;
+                if value.len() > size {
+                    Some(format!("{}...", hex::encode(&value[..size])))
+                } else {
+                    Some(hex::encode(value))
+                }
+            }
+            _ => None,
+        })
+        .collect::<StringArray>();
+
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::BinaryArray,
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hex_preview_udf() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "payload",
+            DataType::Binary,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(BinaryArray::from(vec![
+                b"\x01\x02\x03\x04".as_ref(),
+                b"\xff".as_ref(),
+            ]))],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(HEX_PREVIEW_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        let df = ctx
+            .sql("select hex_preview(payload, 2) as ret from t")
+            .await
+            .unwrap();
+        let data = df.collect().await.unwrap();
+        assert_batches_eq!(
+            vec![
+                "+---------+",
+                "| ret     |",
+                "+---------+",
+                "| 0102... |",
+                "| ff      |",
+                "+---------+",
+            ],
+            &data
+        );
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, iter::zip, sync::Arc, time::Duration};
+
+use config::CONFIG;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray},
+        datatypes::DataType,
+    },
+    common::cast::as_string_array,
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use url::Url;
+
+/// The name of the http_lookup UDF given to DataFusion.
+pub const HTTP_LOOKUP_UDF_NAME: &str = "http_lookup";
+
+/// The placeholder replaced by the key in the url template
+const KEY_PLACEHOLDER: &str = "{key}";
+
+const CACHE_MAX_ENTRIES: usize = 10_000;
+
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+const MAX_REDIRECTS: usize = 3;
+
+/// Longest response body kept, a larger one is a failed lookup
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Responses by url, with the time they expire at. Failed lookups are cached as
+/// None so that a down service isn't called for every row.
+static CACHE: Lazy<RwLock<HashMap<String, (i64, Option<String>)>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+static ALLOWED_HOSTS: Lazy<Vec<String>> = Lazy::new(|| {
+    CONFIG
+        .common
+        .query_http_lookup_hosts
+        .split(',')
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect()
+});
+
+/// Follows a redirect only to an allowed host, so a redirect can't reach a
+/// host the allowlist keeps the function away from.
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    let policy = reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.stop()
+        } else if is_allowed(attempt.url(), &ALLOWED_HOSTS) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    let timeout = Duration::from_secs(CONFIG.limit.query_http_lookup_timeout);
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .redirect(policy)
+        .build()
+        .expect("http_lookup client")
+});
+
+/// The requests run on a runtime of their own, the function is called
+/// synchronously from inside the async runtime of the query.
+static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("http_lookup")
+        .enable_all()
+        .build()
+        .expect("http_lookup runtime")
+});
+
+/// Implementation of http_lookup
+pub(crate) static HTTP_LOOKUP_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        HTTP_LOOKUP_UDF_NAME,
+        // expects an url template and the key put into it
+        vec![DataType::Utf8, DataType::Utf8],
+        // returns the response body
+        Arc::new(DataType::Utf8),
+        Volatility::Volatile,
+        Arc::new(http_lookup_expr_impl),
+    )
+});
+
+/// Builds the url of a key, the key is percent encoded into the `{key}` of the
+/// template. Only http urls on the allowed hosts are returned.
+fn build_url(template: &str, key: &str, allowed_hosts: &[String]) -> Result<String, String> {
+    let key = url::form_urlencoded::byte_serialize(key.as_bytes()).collect::<String>();
+    let url = template.replace(KEY_PLACEHOLDER, &key);
+    let parsed = Url::parse(&url).map_err(|e| format!("invalid url {url}: {e}"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("url scheme of {url} is not http"));
+    }
+    if !is_allowed(&parsed, allowed_hosts) {
+        return Err(format!(
+            "host of {url} is not allowed, see ZO_QUERY_HTTP_LOOKUP_HOSTS"
+        ));
+    }
+    Ok(url)
+}
+
+fn is_allowed(url: &Url, allowed_hosts: &[String]) -> bool {
+    matches!(url.scheme(), "http" | "https")
+        && url
+            .host_str()
+            .is_some_and(|host| allowed_hosts.iter().any(|v| v == &host.to_lowercase()))
+}
+
+/// http_lookup function for datafusion, returns the body of the response to
+/// the url, or null when the request failed
+pub fn http_lookup_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 2 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError(
+                "UDF params should be: http_lookup(url_template, key)".to_string(),
+            ),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let templates = as_string_array(&args[0]).expect("cast failed");
+    let keys = as_string_array(&args[1]).expect("cast failed");
+
+    let urls = zip(templates.iter(), keys.iter())
+        .map(|(template, key)| match (template, key) {
+            (Some(template), Some(key)) => build_url(template, key, &ALLOWED_HOSTS)
+                .map(Some)
+                .map_err(DataFusionError::Execution),
+            _ => Ok(None),
+        })
+        .collect::<datafusion::error::Result<Vec<_>>>()?;
+
+    let now = chrono::Utc::now().timestamp_micros();
+    let mut responses = HashMap::new();
+    let mut missing = Vec::new();
+    {
+        let cache = CACHE.read();
+        for url in urls.iter().flatten() {
+            if responses.contains_key(url) || missing.contains(url) {
+                continue;
+            }
+            match cache.get(url) {
+                Some((expires_at, body)) if *expires_at > now => {
+                    responses.insert(url.to_string(), body.clone());
+                }
+                _ => missing.push(url.to_string()),
+            }
+        }
+    }
+    if !missing.is_empty() {
+        let fetched = fetch(missing);
+        let expires_at = now + CONFIG.limit.query_http_lookup_cache_ttl * 1_000_000;
+        let mut cache = CACHE.write();
+        if cache.len() + fetched.len() > CACHE_MAX_ENTRIES {
+            cache.retain(|_, (v, _)| *v > now);
+            if cache.len() + fetched.len() > CACHE_MAX_ENTRIES {
+                cache.clear();
+            }
+        }
+        for (url, body) in fetched {
+            cache.insert(url.clone(), (expires_at, body.clone()));
+            responses.insert(url, body);
+        }
+    }
+
+    let array = urls
+        .iter()
+        .map(|url| {
+            url.as_ref()
+                .and_then(|v| responses.get(v).cloned().flatten())
+        })
+        .collect::<StringArray>();
+
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+/// Requests the urls, the lookups not done within the timeout are left out
+/// so they are tried again by the next query.
+fn fetch(urls: Vec<String>) -> Vec<(String, Option<String>)> {
+    let handle = RUNTIME.spawn(async move {
+        let deadline =
+            tokio::time::sleep(Duration::from_secs(CONFIG.limit.query_http_lookup_timeout));
+        futures::stream::iter(urls)
+            .map(|url| async move {
+                let body = match CLIENT.get(&url).send().await {
+                    Ok(resp) if resp.status().is_success() => read_body(&url, resp).await,
+                    Ok(resp) => {
+                        log::warn!("http_lookup {url} status: {}", resp.status());
+                        None
+                    }
+                    Err(e) => {
+                        log::warn!("http_lookup {url} error: {e}");
+                        None
+                    }
+                };
+                (url, body)
+            })
+            .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+            .take_until(deadline)
+            .collect::<Vec<_>>()
+            .await
+    });
+    let wait = || futures::executor::block_on(handle).unwrap_or_default();
+    match tokio::runtime::Handle::try_current() {
+        Ok(rt) if rt.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(wait)
+        }
+        _ => wait(),
+    }
+}
+
+/// Reads the body of the response, giving up as soon as it is known to be
+/// larger than [MAX_BODY_BYTES] so a large response is never held in memory.
+async fn read_body(url: &str, mut resp: reqwest::Response) -> Option<String> {
+    if let Some(len) = resp.content_length() {
+        if len > MAX_BODY_BYTES as u64 {
+            log::warn!("http_lookup {url} body of {len} bytes");
+            return None;
+        }
+    }
+    let mut body = Vec::new();
+    loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                if body.len() + chunk.len() > MAX_BODY_BYTES {
+                    log::warn!("http_lookup {url} body over {MAX_BODY_BYTES} bytes");
+                    return None;
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok(None) => return Some(String::from_utf8_lossy(&body).to_string()),
+            Err(e) => {
+                log::warn!("http_lookup {url} error: {e}");
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_url() {
+        let hosts = vec!["cmdb.internal".to_string()];
+        assert_eq!(
+            build_url("http://cmdb.internal/hosts/{key}", "web 1/a", &hosts).unwrap(),
+            "http://cmdb.internal/hosts/web+1%2Fa"
+        );
+        assert!(build_url("http://other.internal/hosts/{key}", "web", &hosts).is_err());
+        assert!(build_url("file:///etc/{key}", "passwd", &hosts).is_err());
+        assert!(build_url("http://cmdb.internal/{key}", "web", &[]).is_err());
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let hosts = vec!["cmdb.internal".to_string()];
+        let url = |v: &str| Url::parse(v).unwrap();
+        assert!(is_allowed(&url("https://CMDB.internal/a"), &hosts));
+        assert!(!is_allowed(&url("http://169.254.169.254/latest"), &hosts));
+        assert!(!is_allowed(&url("ftp://cmdb.internal/a"), &hosts));
+    }
+}
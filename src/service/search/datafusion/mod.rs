@@ -18,14 +18,20 @@ use std::str::FromStr;
 use crate::common::meta::functions::ZoFunction;
 
 mod date_format_udf;
+mod decrypt_udf;
 pub mod exec;
+mod hex_preview_udf;
+mod http_lookup_udf;
 pub mod match_udf;
+mod percentile_udf;
 pub mod regexp_udf;
 mod rewrite;
 pub mod storage;
 pub mod string_to_array_v2_udf;
 mod time_range_udf;
+mod timezone_udf;
 mod transform_udf;
+mod units_udf;
 
 #[derive(PartialEq, Debug)]
 pub enum MemoryPoolType {
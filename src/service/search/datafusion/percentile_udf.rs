@@ -0,0 +1,467 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Mergeable approximate percentiles backed by a t-digest.
+//!
+//! Exact percentiles need every value of a group in memory, which does not
+//! work for latency fields over large time ranges. `percentile_tdigest` keeps
+//! a bounded digest per group instead. The nodes return the serialized digest,
+//! intermediate merges combine digests with `percentile_tdigest_merge`, and
+//! only the final merge estimates the quantile with `percentile_tdigest_final`.
+//! The rewrite from the user function to the merge functions happens in
+//! `exec::merge_rewrite_sql`.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{array::ArrayRef, datatypes::DataType},
+    common::cast::{as_binary_array, as_float64_array},
+    error::{DataFusionError, Result},
+    logical_expr::{create_udaf, Accumulator, AggregateUDF, Volatility},
+    scalar::ScalarValue,
+};
+use once_cell::sync::Lazy;
+
+/// The name of the percentile_tdigest UDAF given to DataFusion.
+pub const PERCENTILE_TDIGEST_UDF_NAME: &str = "percentile_tdigest";
+/// The name of the UDAF merging digests in the intermediate merge phases.
+pub const PERCENTILE_TDIGEST_MERGE_UDF_NAME: &str = "percentile_tdigest_merge";
+/// The name of the UDAF estimating the percentile in the final merge phase.
+pub const PERCENTILE_TDIGEST_FINAL_UDF_NAME: &str = "percentile_tdigest_final";
+
+/// Upper bound of centroids kept by a compressed digest.
+const DIGEST_MAX_SIZE: usize = 100;
+/// Number of buffered points that triggers a compression.
+const DIGEST_BUFFER_SIZE: usize = DIGEST_MAX_SIZE * 5;
+/// count, min and max are written in front of the centroids.
+const DIGEST_HEADER_LEN: usize = 24;
+
+/// Implementation of percentile_tdigest(field, percentile), returns a digest
+pub(crate) static PERCENTILE_TDIGEST_UDF: Lazy<AggregateUDF> = Lazy::new(|| {
+    create_udaf(
+        PERCENTILE_TDIGEST_UDF_NAME,
+        // expects a numeric field and the percentile between 0 and 1
+        vec![DataType::Float64, DataType::Float64],
+        // returns the serialized digest
+        Arc::new(DataType::Binary),
+        Volatility::Immutable,
+        Arc::new(|_| Ok(Box::new(TDigestAccumulator::new(Phase::Partial)))),
+        Arc::new(vec![DataType::Binary]),
+    )
+});
+
+/// Implementation of percentile_tdigest_merge(digest), returns a digest
+pub(crate) static PERCENTILE_TDIGEST_MERGE_UDF: Lazy<AggregateUDF> = Lazy::new(|| {
+    create_udaf(
+        PERCENTILE_TDIGEST_MERGE_UDF_NAME,
+        // expects the digest column
+        vec![DataType::Binary],
+        // returns the merged digest
+        Arc::new(DataType::Binary),
+        Volatility::Immutable,
+        Arc::new(|_| Ok(Box::new(TDigestAccumulator::new(Phase::Merge)))),
+        Arc::new(vec![DataType::Binary]),
+    )
+});
+
+/// Implementation of percentile_tdigest_final(digest, percentile), returns the
+/// estimated value
+pub(crate) static PERCENTILE_TDIGEST_FINAL_UDF: Lazy<AggregateUDF> = Lazy::new(|| {
+    create_udaf(
+        PERCENTILE_TDIGEST_FINAL_UDF_NAME,
+        // expects the digest column and the percentile between 0 and 1
+        vec![DataType::Binary, DataType::Float64],
+        // returns the estimated value
+        Arc::new(DataType::Float64),
+        Volatility::Immutable,
+        Arc::new(|_| Ok(Box::new(TDigestAccumulator::new(Phase::Final)))),
+        Arc::new(vec![DataType::Binary, DataType::Float64]),
+    )
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// A merging t-digest, memory use is bounded by `DIGEST_MAX_SIZE` centroids
+/// plus `DIGEST_BUFFER_SIZE` buffered points regardless of the input size.
+#[derive(Debug, Clone)]
+pub(crate) struct TDigest {
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self {
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl TDigest {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.count == 0.0
+    }
+
+    pub(crate) fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub(crate) fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        for c in other.centroids.iter().chain(other.buffer.iter()) {
+            self.push(*c);
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    fn push(&mut self, centroid: Centroid) {
+        self.count += centroid.weight;
+        self.buffer.push(centroid);
+        if self.buffer.len() >= DIGEST_BUFFER_SIZE {
+            self.compress();
+        }
+    }
+
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut points = std::mem::take(&mut self.centroids);
+        points.append(&mut self.buffer);
+        points.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let mut centroids = Vec::with_capacity(DIGEST_MAX_SIZE);
+        let mut iter = points.into_iter();
+        let mut current = iter.next().unwrap();
+        let mut weight_so_far = current.weight;
+        let mut k = 1.0;
+        let mut limit = k_to_q(k) * self.count;
+        for c in iter {
+            if weight_so_far + c.weight <= limit {
+                weight_so_far += c.weight;
+                let weight = current.weight + c.weight;
+                current.mean += (c.mean - current.mean) * c.weight / weight;
+                current.weight = weight;
+            } else {
+                centroids.push(current);
+                weight_so_far += c.weight;
+                k += 1.0;
+                limit = k_to_q(k) * self.count;
+                current = c;
+            }
+        }
+        centroids.push(current);
+        self.centroids = centroids;
+    }
+
+    /// Estimates the value at percentile `q`, `None` for an empty digest.
+    pub(crate) fn quantile(&mut self, q: f64) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        self.compress();
+        let centroids = &self.centroids;
+        if centroids.len() == 1 {
+            return Some(centroids[0].mean);
+        }
+
+        let rank = q * self.count;
+        let mut cum = 0.0;
+        let mut prev: Option<(f64, f64)> = None; // (center, mean)
+        for c in centroids.iter() {
+            let center = cum + c.weight / 2.0;
+            if rank < center {
+                let value = match prev {
+                    Some((prev_center, prev_mean)) => {
+                        prev_mean
+                            + (c.mean - prev_mean) * (rank - prev_center) / (center - prev_center)
+                    }
+                    None => self.min + (c.mean - self.min) * rank / center,
+                };
+                return Some(value.clamp(self.min, self.max));
+            }
+            prev = Some((center, c.mean));
+            cum += c.weight;
+        }
+        let (last_center, last_mean) = prev.unwrap();
+        let value = if self.count > last_center {
+            last_mean + (self.max - last_mean) * (rank - last_center) / (self.count - last_center)
+        } else {
+            last_mean
+        };
+        Some(value.clamp(self.min, self.max))
+    }
+
+    pub(crate) fn to_bytes(&mut self) -> Vec<u8> {
+        self.compress();
+        let mut buf = Vec::with_capacity(DIGEST_HEADER_LEN + self.centroids.len() * 16);
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        buf.extend_from_slice(&self.min.to_le_bytes());
+        buf.extend_from_slice(&self.max.to_le_bytes());
+        for c in self.centroids.iter() {
+            buf.extend_from_slice(&c.mean.to_le_bytes());
+            buf.extend_from_slice(&c.weight.to_le_bytes());
+        }
+        buf
+    }
+
+    pub(crate) fn from_bytes(buf: &[u8]) -> Result<Self> {
+        if buf.len() < DIGEST_HEADER_LEN || (buf.len() - DIGEST_HEADER_LEN) % 16 != 0 {
+            return Err(DataFusionError::Execution(
+                "percentile_tdigest: invalid digest".to_string(),
+            ));
+        }
+        let mut values = buf
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()));
+        let count = values.next().unwrap();
+        let min = values.next().unwrap();
+        let max = values.next().unwrap();
+        let mut centroids = Vec::with_capacity((buf.len() - DIGEST_HEADER_LEN) / 16);
+        while let (Some(mean), Some(weight)) = (values.next(), values.next()) {
+            centroids.push(Centroid { mean, weight });
+        }
+        Ok(Self {
+            centroids,
+            buffer: Vec::new(),
+            count,
+            min,
+            max,
+        })
+    }
+
+    fn size(&self) -> usize {
+        (self.centroids.capacity() + self.buffer.capacity()) * std::mem::size_of::<Centroid>()
+    }
+}
+
+/// Scale function of the digest, centroids near the tails stay small so the
+/// high percentiles which matter for latencies keep their accuracy.
+fn k_to_q(k: f64) -> f64 {
+    let k_div_d = k / DIGEST_MAX_SIZE as f64;
+    if k_div_d >= 0.5 {
+        let base = 1.0 - k_div_d;
+        1.0 - 2.0 * base * base
+    } else {
+        2.0 * k_div_d * k_div_d
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    /// raw values to digest
+    Partial,
+    /// digests to digest
+    Merge,
+    /// digests to the estimated value
+    Final,
+}
+
+#[derive(Debug)]
+struct TDigestAccumulator {
+    digest: TDigest,
+    phase: Phase,
+    percentile: Option<f64>,
+}
+
+impl TDigestAccumulator {
+    fn new(phase: Phase) -> Self {
+        Self {
+            digest: TDigest::default(),
+            phase,
+            percentile: None,
+        }
+    }
+
+    fn merge_digests(&mut self, digests: &ArrayRef) -> Result<()> {
+        for buf in as_binary_array(digests)?.iter().flatten() {
+            self.digest.merge(&TDigest::from_bytes(buf)?);
+        }
+        Ok(())
+    }
+
+    fn set_percentile(&mut self, percentiles: &ArrayRef) -> Result<()> {
+        if self.percentile.is_some() {
+            return Ok(());
+        }
+        if let Some(p) = as_float64_array(percentiles)?.iter().flatten().next() {
+            if !(0.0..=1.0).contains(&p) {
+                return Err(DataFusionError::Execution(format!(
+                    "percentile_tdigest: percentile should be between 0 and 1, got {p}"
+                )));
+            }
+            self.percentile = Some(p);
+        }
+        Ok(())
+    }
+}
+
+impl Accumulator for TDigestAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        match self.phase {
+            Phase::Partial => {
+                for v in as_float64_array(&values[0])?.iter().flatten() {
+                    self.digest.add(v);
+                }
+            }
+            Phase::Merge | Phase::Final => self.merge_digests(&values[0])?,
+        }
+        if self.phase != Phase::Merge {
+            self.set_percentile(&values[1])?;
+        }
+        Ok(())
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        self.merge_digests(&states[0])?;
+        if self.phase == Phase::Final {
+            self.set_percentile(&states[1])?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let mut state = vec![ScalarValue::Binary(Some(self.digest.to_bytes()))];
+        if self.phase == Phase::Final {
+            state.push(ScalarValue::Float64(self.percentile));
+        }
+        Ok(state)
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        if self.phase == Phase::Final {
+            let value = match self.percentile {
+                Some(p) => self.digest.quantile(p),
+                None => None,
+            };
+            return Ok(ScalarValue::Float64(value));
+        }
+        if self.digest.is_empty() {
+            return Ok(ScalarValue::Binary(None));
+        }
+        Ok(ScalarValue::Binary(Some(self.digest.to_bytes())))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.digest.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::Float64Array,
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_tdigest_merge() {
+        let mut full = TDigest::default();
+        let mut parts = vec![TDigest::default(); 4];
+        for i in 1..=100_000 {
+            full.add(i as f64);
+            parts[i % 4].add(i as f64);
+        }
+        let mut merged = TDigest::default();
+        for part in parts.iter_mut() {
+            let digest = TDigest::from_bytes(&part.to_bytes()).unwrap();
+            merged.merge(&digest);
+        }
+        for (q, expected) in [(0.5, 50_000.0), (0.9, 90_000.0), (0.99, 99_000.0)] {
+            let full = full.quantile(q).unwrap();
+            let merged = merged.quantile(q).unwrap();
+            assert!((full - expected).abs() / expected < 0.01, "{q}: {full}");
+            assert!((merged - expected).abs() / expected < 0.01, "{q}: {merged}");
+        }
+        assert_eq!(merged.quantile(0.0), Some(1.0));
+        assert_eq!(merged.quantile(1.0), Some(100_000.0));
+        assert!(merged.centroids.len() <= DIGEST_MAX_SIZE);
+        assert!(TDigest::default().quantile(0.5).is_none());
+        assert!(TDigest::from_bytes(&[0u8; 10]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_percentile_tdigest_udf() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "took",
+            DataType::Float64,
+            true,
+        )]));
+        let batches = (0..4)
+            .map(|n| {
+                let values = (1..=250).map(|i| (n * 250 + i) as f64).collect::<Vec<_>>();
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Float64Array::from(values))])
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let ctx = SessionContext::new();
+        ctx.register_udaf(PERCENTILE_TDIGEST_UDF.clone());
+        ctx.register_udaf(PERCENTILE_TDIGEST_MERGE_UDF.clone());
+        ctx.register_udaf(PERCENTILE_TDIGEST_FINAL_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![batches]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        // the partial phase returns a digest per node
+        let partial = ctx
+            .sql("select percentile_tdigest(took, 0.9) as p90 from t")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let provider = MemTable::try_new(partial[0].schema(), vec![partial]).unwrap();
+        ctx.register_table("tbl", Arc::new(provider)).unwrap();
+
+        let data = ctx
+            .sql("select percentile_tdigest_final(p90, 0.9) as p90 from tbl")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let value = as_float64_array(data[0].column(0)).unwrap().value(0);
+        assert!((value - 900.0).abs() < 10.0, "{value}");
+    }
+}
@@ -27,6 +27,7 @@ use object_store::{
 use tokio::io::AsyncWrite;
 
 use super::GetRangeExt;
+use crate::service::search::scan_costs;
 
 /// File system with memory cache
 #[derive(Debug, Default)]
@@ -143,7 +144,8 @@ impl ObjectStore for FS {
 
     async fn get_range(&self, location: &Path, range: Range<usize>) -> Result<Bytes> {
         let location = &self.format_location(location);
-        match self.get_cache(location, Some(range.clone())).await {
+        let start = std::time::Instant::now();
+        let ret = match self.get_cache(location, Some(range.clone())).await {
             Some(data) => {
                 if range.start > range.end {
                     return Err(super::Error::BadRange(location.to_string()).into());
@@ -160,7 +162,11 @@ impl ObjectStore for FS {
                 Ok(data) => Ok(data),
                 Err(_) => storage::DEFAULT.get_range(location, range).await,
             },
+        };
+        if let Ok(data) = &ret {
+            scan_costs::record_read(location.as_ref(), start.elapsed(), data.len());
         }
+        ret
     }
 
     async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> Result<Vec<Bytes>> {
@@ -168,7 +174,8 @@ impl ObjectStore for FS {
             return Ok(vec![]);
         }
         let location = &self.format_location(location);
-        match self.get_cache(location, None).await {
+        let start = std::time::Instant::now();
+        let ret: Result<Vec<Bytes>> = match self.get_cache(location, None).await {
             Some(data) => ranges
                 .iter()
                 .map(|range| {
@@ -185,7 +192,12 @@ impl ObjectStore for FS {
                 Ok(data) => Ok(data),
                 Err(_) => storage::DEFAULT.get_ranges(location, ranges).await,
             },
+        };
+        if let Ok(data) = &ret {
+            let bytes = data.iter().map(|d| d.len()).sum();
+            scan_costs::record_read(location.as_ref(), start.elapsed(), bytes);
         }
+        ret
     }
 
     async fn head(&self, location: &Path) -> Result<ObjectMeta> {
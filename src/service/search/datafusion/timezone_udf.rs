@@ -0,0 +1,402 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{iter::zip, sync::Arc};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use config::utils::time;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, Int64Array},
+        datatypes::DataType,
+    },
+    common::cast::{as_int64_array, as_string_array},
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use once_cell::sync::Lazy;
+
+/// The name of the date_trunc_tz UDF given to DataFusion.
+pub const DATE_TRUNC_TZ_UDF_NAME: &str = "date_trunc_tz";
+/// The name of the to_timezone UDF given to DataFusion.
+pub const TO_TIMEZONE_UDF_NAME: &str = "to_timezone";
+
+/// Implementation of date_trunc_tz
+pub(crate) static DATE_TRUNC_TZ_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        DATE_TRUNC_TZ_UDF_NAME,
+        // expects the unit, a timestamp field and the zone
+        vec![DataType::Utf8, DataType::Int64, DataType::Utf8],
+        // returns the start of the local period in microseconds
+        Arc::new(DataType::Int64),
+        Volatility::Immutable,
+        Arc::new(date_trunc_tz_expr_impl),
+    )
+});
+
+/// Implementation of to_timezone
+pub(crate) static TO_TIMEZONE_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        TO_TIMEZONE_UDF_NAME,
+        // expects a timestamp field and the zone
+        vec![DataType::Int64, DataType::Utf8],
+        // returns the local wall clock time in microseconds
+        Arc::new(DataType::Int64),
+        Volatility::Immutable,
+        Arc::new(to_timezone_expr_impl),
+    )
+});
+
+/// A time zone given to the search functions, either a fixed offset like
+/// `+08:00` or an IANA name like `Europe/Berlin` which follows daylight saving.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Zone {
+    pub(crate) fn parse(zone: &str) -> Result<Self, DataFusionError> {
+        let zone = zone.trim();
+        // keep the offsets accepted by date_format before IANA names existed
+        match zone.to_uppercase().as_str() {
+            "" | "UTC" => return Ok(Zone::Fixed(Utc.fix())),
+            "CST" => return Ok(Zone::Fixed(FixedOffset::east_opt(8 * 3600).unwrap())),
+            _ => {}
+        }
+        if zone.starts_with('+') || zone.starts_with('-') {
+            return parse_offset(zone).map(Zone::Fixed).ok_or_else(|| {
+                DataFusionError::Execution(format!("invalid time zone offset: {zone}"))
+            });
+        }
+        zone.parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| DataFusionError::Execution(format!("unknown time zone: {zone}")))
+    }
+
+    /// Converts microseconds since the epoch to the local wall clock time.
+    pub(crate) fn to_local(&self, micros: i64) -> DateTime<FixedOffset> {
+        let t = Utc.timestamp_nanos(micros * 1000);
+        match self {
+            Zone::Fixed(offset) => t.with_timezone(offset),
+            Zone::Named(tz) => t.with_timezone(tz).fixed_offset(),
+        }
+    }
+
+    /// Converts a local wall clock time back to microseconds since the epoch,
+    /// times skipped by a daylight saving change move forward to the change.
+    pub(crate) fn from_local(&self, local: &NaiveDateTime) -> i64 {
+        match self {
+            Zone::Fixed(offset) => {
+                local.and_utc().timestamp_micros() - offset.local_minus_utc() as i64 * 1_000_000
+            }
+            Zone::Named(tz) => {
+                let mut local = *local;
+                for _ in 0..4 {
+                    if let Some(t) = tz.from_local_datetime(&local).earliest() {
+                        return t.timestamp_micros();
+                    }
+                    local += chrono::Duration::try_minutes(30).unwrap();
+                }
+                local.and_utc().timestamp_micros()
+            }
+        }
+    }
+}
+
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, time) = match offset.split_at(1) {
+        ("+", time) => (1, time),
+        ("-", time) => (-1, time),
+        _ => return None,
+    };
+    let mut parts = time.split(':');
+    let hours = parts.next()?.parse::<i32>().ok()?;
+    let minutes = match parts.next() {
+        Some(v) => v.parse::<i32>().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+/// Parses `day`, `1 day` or `30 minutes` into the step and its unit.
+fn parse_interval(interval: &str) -> Option<(i64, TimeUnit)> {
+    let interval = interval.trim().to_lowercase();
+    let mut parts = interval.split_whitespace();
+    let first = parts.next()?;
+    let (num, unit) = match parts.next() {
+        Some(unit) => (first.parse::<i64>().ok()?, unit),
+        None => (1, first),
+    };
+    if num <= 0 || parts.next().is_some() {
+        return None;
+    }
+    let unit = match unit.trim_end_matches('s') {
+        "second" => TimeUnit::Second,
+        "minute" => TimeUnit::Minute,
+        "hour" => TimeUnit::Hour,
+        "day" => TimeUnit::Day,
+        "week" => TimeUnit::Week,
+        "month" => TimeUnit::Month,
+        "quarter" => TimeUnit::Quarter,
+        "year" => TimeUnit::Year,
+        _ => return None,
+    };
+    Some((num, unit))
+}
+
+/// Truncates a local wall clock time to the start of its `num` `unit` period,
+/// weeks start on Monday.
+fn truncate(local: &NaiveDateTime, num: i64, unit: TimeUnit) -> Option<NaiveDateTime> {
+    let (step, origin) = match unit {
+        TimeUnit::Second => (num, 0),
+        TimeUnit::Minute => (num * 60, 0),
+        TimeUnit::Hour => (num * 3600, 0),
+        TimeUnit::Day => (num * 86400, 0),
+        // 1970-01-01 is a Thursday, the Monday before is three days earlier
+        TimeUnit::Week => (num * 7 * 86400, -3 * 86400),
+        TimeUnit::Month | TimeUnit::Quarter | TimeUnit::Year => {
+            let months = match unit {
+                TimeUnit::Month => num,
+                TimeUnit::Quarter => num * 3,
+                _ => num * 12,
+            };
+            let total = local.year() as i64 * 12 + local.month0() as i64;
+            let bucket = total.div_euclid(months) * months;
+            return NaiveDate::from_ymd_opt(
+                bucket.div_euclid(12) as i32,
+                bucket.rem_euclid(12) as u32 + 1,
+                1,
+            )?
+            .and_hms_opt(0, 0, 0);
+        }
+    };
+    let secs = local.and_utc().timestamp();
+    let bucket = (secs - origin).div_euclid(step) * step + origin;
+    DateTime::from_timestamp(bucket, 0).map(|t| t.naive_utc())
+}
+
+/// date_trunc_tz function for datafusion, truncates to local period boundaries
+pub fn date_trunc_tz_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 3 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError(
+                "UDF params should be: date_trunc_tz(unit, field, zone)".to_string(),
+            ),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let unit = as_string_array(&args[0]).expect("cast failed");
+    let timestamp = as_int64_array(&args[1]).expect("cast failed");
+    let timezone = as_string_array(&args[2]).expect("cast failed");
+
+    // the unit and zone are almost always literals, parse them once
+    let mut last_unit: Option<(&str, (i64, TimeUnit))> = None;
+    let mut last_zone: Option<(&str, Zone)> = None;
+    let mut array = Int64Array::builder(timestamp.len());
+    for (unit, (timestamp, timezone)) in zip(unit.iter(), zip(timestamp.iter(), timezone.iter())) {
+        let (Some(unit), Some(timestamp), Some(timezone)) = (unit, timestamp, timezone) else {
+            array.append_null();
+            continue;
+        };
+        let (num, unit) = match last_unit {
+            Some((name, parsed)) if name == unit => parsed,
+            _ => {
+                let parsed = parse_interval(unit).ok_or_else(|| {
+                    DataFusionError::Execution(format!("invalid date_trunc_tz unit: {unit}"))
+                })?;
+                last_unit = Some((unit, parsed));
+                parsed
+            }
+        };
+        let zone = cached_zone(&mut last_zone, timezone)?;
+        let local = zone
+            .to_local(time::parse_i64_to_timestamp_micros(timestamp))
+            .naive_local();
+        match truncate(&local, num, unit) {
+            Some(start) => array.append_value(zone.from_local(&start)),
+            None => array.append_null(),
+        }
+    }
+
+    Ok(ColumnarValue::from(Arc::new(array.finish()) as ArrayRef))
+}
+
+/// to_timezone function for datafusion, shifts a timestamp to the wall clock
+/// time of the zone so the UTC based functions show local values
+pub fn to_timezone_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 2 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError("UDF params should be: to_timezone(field, zone)".to_string()),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let timestamp = as_int64_array(&args[0]).expect("cast failed");
+    let timezone = as_string_array(&args[1]).expect("cast failed");
+
+    let mut last_zone: Option<(&str, Zone)> = None;
+    let mut array = Int64Array::builder(timestamp.len());
+    for (timestamp, timezone) in zip(timestamp.iter(), timezone.iter()) {
+        let (Some(timestamp), Some(timezone)) = (timestamp, timezone) else {
+            array.append_null();
+            continue;
+        };
+        let zone = cached_zone(&mut last_zone, timezone)?;
+        let local = zone.to_local(time::parse_i64_to_timestamp_micros(timestamp));
+        array.append_value(local.naive_local().and_utc().timestamp_micros());
+    }
+
+    Ok(ColumnarValue::from(Arc::new(array.finish()) as ArrayRef))
+}
+
+pub(crate) fn cached_zone<'a>(
+    last: &mut Option<(&'a str, Zone)>,
+    timezone: &'a str,
+) -> datafusion::error::Result<Zone> {
+    match last {
+        Some((name, zone)) if *name == timezone => Ok(*zone),
+        _ => {
+            let zone = Zone::parse(timezone)?;
+            *last = Some((timezone, zone));
+            Ok(zone)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::StringArray,
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("day"), Some((1, TimeUnit::Day)));
+        assert_eq!(parse_interval("30 minutes"), Some((30, TimeUnit::Minute)));
+        assert_eq!(parse_interval(" 1 Quarter "), Some((1, TimeUnit::Quarter)));
+        assert_eq!(parse_interval("0 day"), None);
+        assert_eq!(parse_interval("1 fortnight"), None);
+    }
+
+    #[tokio::test]
+    async fn test_timezone_udf() {
+        // 2024-03-31T00:30:00Z, the night Europe/Berlin switches to summer time
+        let data_time = time::parse_str_to_timestamp_micros("2024-03-31T00:30:00Z").unwrap();
+        let sqls = [
+            (
+                "select date_format(date_trunc_tz('day', time, 'Europe/Berlin'), '%Y-%m-%dT%H:%M:%S', '') as ret from t",
+                vec![
+                    "+---------------------+",
+                    "| ret                 |",
+                    "+---------------------+",
+                    "| 2024-03-30T23:00:00 |",
+                    "+---------------------+",
+                ],
+            ),
+            (
+                "select date_format(date_trunc_tz('month', time, 'America/New_York'), '%Y-%m-%dT%H:%M:%S', '') as ret from t",
+                vec![
+                    "+---------------------+",
+                    "| ret                 |",
+                    "+---------------------+",
+                    "| 2024-03-01T05:00:00 |",
+                    "+---------------------+",
+                ],
+            ),
+            (
+                "select date_format(date_trunc_tz('week', time, '+08:00'), '%Y-%m-%dT%H:%M:%S', '') as ret from t",
+                vec![
+                    "+---------------------+",
+                    "| ret                 |",
+                    "+---------------------+",
+                    "| 2024-03-24T16:00:00 |",
+                    "+---------------------+",
+                ],
+            ),
+            (
+                "select date_format(to_timezone(time, 'Asia/Kolkata'), '%Y-%m-%dT%H:%M:%S', '') as ret from t",
+                vec![
+                    "+---------------------+",
+                    "| ret                 |",
+                    "+---------------------+",
+                    "| 2024-03-31T06:00:00 |",
+                    "+---------------------+",
+                ],
+            ),
+        ];
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("log", DataType::Utf8, false),
+            Field::new("time", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a"])),
+                Arc::new(Int64Array::from(vec![data_time])),
+            ],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(DATE_TRUNC_TZ_UDF.clone());
+        ctx.register_udf(TO_TIMEZONE_UDF.clone());
+        ctx.register_udf(super::super::date_format_udf::DATE_FORMAT_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        for item in sqls {
+            let df = ctx.sql(item.0).await.unwrap();
+            let data = df.collect().await.unwrap();
+            assert_batches_eq!(item.1, &data);
+        }
+
+        let df = ctx
+            .sql("select to_timezone(time, 'Mars/Olympus') as ret from t")
+            .await
+            .unwrap();
+        assert!(df.collect().await.is_err());
+    }
+}
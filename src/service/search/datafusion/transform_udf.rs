@@ -30,7 +30,7 @@ use vector_enrichment::TableRegistry;
 use vrl::compiler::{runtime::Runtime, CompilationResult, Program, TargetValueRef, VrlRuntime};
 
 use crate::{
-    common::infra::config::QUERY_FUNCTIONS,
+    common::utils::functions::get_all_transforms,
     service::ingestion::{compile_vrl_function, get_string_value},
 };
 
@@ -79,20 +79,16 @@ fn create_user_df(
 pub async fn get_all_transform(org_id: &str) -> Vec<datafusion::logical_expr::ScalarUDF> {
     let mut udf;
     let mut udf_list = Vec::new();
-    for transform in QUERY_FUNCTIONS.clone().iter() {
-        let key = transform.key();
-        // do not register ingest_time transforms
-        if key.contains(org_id) {
-            udf = get_udf_vrl(
-                transform.name.to_owned(),
-                transform.function.to_owned().as_str(),
-                &transform.params,
-                transform.num_args,
-                org_id,
-            );
+    for transform in get_all_transforms(org_id) {
+        udf = get_udf_vrl(
+            transform.name.to_owned(),
+            transform.function.to_owned().as_str(),
+            &transform.params,
+            transform.num_args,
+            org_id,
+        );
 
-            udf_list.push(udf);
-        }
+        udf_list.push(udf);
     }
     udf_list
 }
@@ -0,0 +1,253 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, Int64Array},
+        datatypes::DataType,
+    },
+    common::cast::as_string_array,
+    error::DataFusionError,
+    logical_expr::{ScalarUDF, Volatility},
+    prelude::create_udf,
+    sql::sqlparser::parser::ParserError,
+};
+use datafusion_expr::ColumnarValue;
+use once_cell::sync::Lazy;
+
+/// The name of the parse_duration UDF given to DataFusion.
+pub const PARSE_DURATION_UDF_NAME: &str = "parse_duration";
+/// The name of the parse_bytes UDF given to DataFusion.
+pub const PARSE_BYTES_UDF_NAME: &str = "parse_bytes";
+
+/// Implementation of parse_duration
+pub(crate) static PARSE_DURATION_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        PARSE_DURATION_UDF_NAME,
+        // expects a string field like 10ms or 1h30m
+        vec![DataType::Utf8],
+        // returns nanoseconds
+        Arc::new(DataType::Int64),
+        Volatility::Immutable,
+        Arc::new(parse_duration_expr_impl),
+    )
+});
+
+/// Implementation of parse_bytes
+pub(crate) static PARSE_BYTES_UDF: Lazy<ScalarUDF> = Lazy::new(|| {
+    create_udf(
+        PARSE_BYTES_UDF_NAME,
+        // expects a string field like 2.5GB or 512KiB
+        vec![DataType::Utf8],
+        // returns bytes
+        Arc::new(DataType::Int64),
+        Volatility::Immutable,
+        Arc::new(parse_bytes_expr_impl),
+    )
+});
+
+/// parse_duration function for datafusion, values which can not be parsed
+/// become null so one malformed line does not fail the query
+pub fn parse_duration_expr_impl(
+    args: &[ColumnarValue],
+) -> datafusion::error::Result<ColumnarValue> {
+    parse_expr_impl(args, PARSE_DURATION_UDF_NAME, parse_duration)
+}
+
+/// parse_bytes function for datafusion, values which can not be parsed
+/// become null so one malformed line does not fail the query
+pub fn parse_bytes_expr_impl(args: &[ColumnarValue]) -> datafusion::error::Result<ColumnarValue> {
+    parse_expr_impl(args, PARSE_BYTES_UDF_NAME, parse_bytes)
+}
+
+fn parse_expr_impl(
+    args: &[ColumnarValue],
+    name: &str,
+    parse: fn(&str) -> Option<i64>,
+) -> datafusion::error::Result<ColumnarValue> {
+    if args.len() != 1 {
+        return Err(DataFusionError::SQL(
+            ParserError::ParserError(format!("UDF params should be: {name}(field)")),
+            None,
+        ));
+    }
+    let args = ColumnarValue::values_to_arrays(args)?;
+    let values = as_string_array(&args[0]).expect("cast failed");
+    let array = values
+        .iter()
+        .map(|v| v.and_then(parse))
+        .collect::<Int64Array>();
+
+    Ok(ColumnarValue::from(Arc::new(array) as ArrayRef))
+}
+
+/// Parses durations like `250ms`, `1.5s` or `1h30m` into nanoseconds, a bare
+/// `0` is accepted but other numbers need a unit.
+pub(crate) fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (negative, mut rest) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    if rest == "0" {
+        return Some(0);
+    }
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let (num, after) = split_number(rest)?;
+        let unit_len = after
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(after.len());
+        let factor = match after[..unit_len].trim() {
+            "ns" => 1.0,
+            "us" | "µs" | "μs" => 1e3,
+            "ms" => 1e6,
+            "s" | "sec" | "secs" => 1e9,
+            "m" | "min" | "mins" => 60e9,
+            "h" | "hr" | "hrs" => 3600e9,
+            "d" => 86400e9,
+            _ => return None,
+        };
+        total += num * factor;
+        rest = &after[unit_len..];
+    }
+    to_i64(if negative { -total } else { total })
+}
+
+/// Parses sizes like `512`, `2.5GB` or `10 MiB` into bytes, `KB`/`MB`/`GB` are
+/// powers of 1000 and `KiB`/`MiB`/`GiB` powers of 1024.
+pub(crate) fn parse_bytes(value: &str) -> Option<i64> {
+    let (num, unit) = split_number(value.trim())?;
+    let factor = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        "t" | "tb" => 1e12,
+        "p" | "pb" => 1e15,
+        "ki" | "kib" => 1024.0,
+        "mi" | "mib" => 1024.0 * 1024.0,
+        "gi" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "ti" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        "pi" | "pib" => 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    to_i64(num * factor)
+}
+
+/// Splits the leading decimal number from the rest of the string.
+fn split_number(value: &str) -> Option<(f64, &str)> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let num = value[..end].parse::<f64>().ok()?;
+    Some((num, &value[end..]))
+}
+
+fn to_i64(value: f64) -> Option<i64> {
+    if value.is_finite() && value.abs() < i64::MAX as f64 {
+        Some(value.round() as i64)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::StringArray,
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        assert_batches_eq,
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("10ms"), Some(10_000_000));
+        assert_eq!(parse_duration("1.5s"), Some(1_500_000_000));
+        assert_eq!(parse_duration("1h30m"), Some(5_400_000_000_000));
+        assert_eq!(parse_duration("250 us"), Some(250_000));
+        assert_eq!(parse_duration("-2m"), Some(-120_000_000_000));
+        assert_eq!(parse_duration("0"), Some(0));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("fast"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("2.5GB"), Some(2_500_000_000));
+        assert_eq!(parse_bytes("512"), Some(512));
+        assert_eq!(parse_bytes("10 MiB"), Some(10_485_760));
+        assert_eq!(parse_bytes("1kb"), Some(1000));
+        assert_eq!(parse_bytes("3 bananas"), None);
+        assert_eq!(parse_bytes(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_units_udf() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("took", DataType::Utf8, true),
+            Field::new("size", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("10ms"), Some("oops"), None])),
+                Arc::new(StringArray::from(vec![
+                    Some("1KiB"),
+                    Some("2.5GB"),
+                    Some("7"),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(PARSE_DURATION_UDF.clone());
+        ctx.register_udf(PARSE_BYTES_UDF.clone());
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+
+        let df = ctx
+            .sql("select parse_duration(took) as took, parse_bytes(size) as size from t")
+            .await
+            .unwrap();
+        let data = df.collect().await.unwrap();
+        assert_batches_eq!(
+            vec![
+                "+----------+------------+",
+                "| took     | size       |",
+                "+----------+------------+",
+                "| 10000000 | 1024       |",
+                "|          | 2500000000 |",
+                "|          | 7          |",
+                "+----------+------------+",
+            ],
+            &data
+        );
+    }
+}
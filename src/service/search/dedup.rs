@@ -0,0 +1,123 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// dedup(fields...) collapses the hits sharing the values of the fields
+
+use config::{utils::json, CONFIG};
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static RE_DEDUP: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bdedup(_first)?\s*\(([^()]*)\)").unwrap());
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dedup {
+    pub fields: Vec<String>,
+    /// Keeps the oldest hit of every group instead of the latest one
+    pub keep_first: bool,
+}
+
+/// Takes the dedup operator out of the sql, the operator is replaced by `true`
+/// so that the conditions around it are kept as they are.
+pub fn parse(sql: &str) -> Option<(String, Dedup)> {
+    let cap = RE_DEDUP.captures(sql)?;
+    let fields = cap[2]
+        .split(',')
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return None;
+    }
+    let dedup = Dedup {
+        fields,
+        keep_first: cap.get(1).is_some(),
+    };
+    let sql = sql.replacen(&cap[0], "true", 1);
+    Some((sql, dedup))
+}
+
+/// Collapses the hits sharing the values of the dedup fields into the latest
+/// one, or the oldest one for `dedup_first`. Every group stays at the position
+/// of its first hit.
+pub fn apply(dedup: &Dedup, hits: Vec<json::Value>) -> Vec<json::Value> {
+    let ts_field = CONFIG.common.column_timestamp.as_str();
+    let timestamp = |hit: &json::Value| hit.get(ts_field).and_then(|v| v.as_i64()).unwrap_or(0);
+    let mut groups: HashMap<String, usize> = HashMap::with_capacity(hits.len());
+    let mut ret: Vec<json::Value> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        let key = dedup
+            .fields
+            .iter()
+            .map(|field| hit.get(field).cloned().unwrap_or(json::Value::Null))
+            .collect::<Vec<_>>();
+        let key = json::to_string(&key).unwrap_or_default();
+        match groups.get(&key) {
+            Some(&idx) => {
+                let (ts, kept_ts) = (timestamp(&hit), timestamp(&ret[idx]));
+                if (dedup.keep_first && ts < kept_ts) || (!dedup.keep_first && ts > kept_ts) {
+                    ret[idx] = hit;
+                }
+            }
+            None => {
+                groups.insert(key, ret.len());
+                ret.push(hit);
+            }
+        }
+    }
+    ret
+}
+
+/// The hits of the requested page, taken after collapsing all of them.
+pub fn page(hits: Vec<json::Value>, from: usize, size: usize) -> Vec<json::Value> {
+    hits.into_iter().skip(from).take(size).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup() {
+        let (sql, dedup) =
+            parse("SELECT * FROM t WHERE dedup(host, \"pod\") AND code = 500").unwrap();
+        assert_eq!(sql, "SELECT * FROM t WHERE true AND code = 500");
+        assert_eq!(dedup.fields, vec!["host", "pod"]);
+        assert!(!dedup.keep_first);
+        assert!(parse("SELECT * FROM t WHERE dedup() AND code = 500").is_none());
+
+        let ts = CONFIG.common.column_timestamp.as_str();
+        let hits = vec![
+            json::json!({ts: 3, "host": "a", "pod": "1", "v": "a3"}),
+            json::json!({ts: 5, "host": "a", "pod": "1", "v": "a5"}),
+            json::json!({ts: 4, "host": "b", "pod": "1", "v": "b4"}),
+            json::json!({ts: 1, "host": "a", "pod": "1", "v": "a1"}),
+        ];
+        let latest = apply(&dedup, hits.clone());
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0]["v"], "a5");
+        assert_eq!(latest[1]["v"], "b4");
+
+        let (_, dedup) = parse("SELECT * FROM t WHERE dedup_first(host)").unwrap();
+        let first = apply(&dedup, hits);
+        assert_eq!(first[0]["v"], "a1");
+        assert_eq!(first[1]["v"], "b4");
+
+        let paged = page(first, 1, 10);
+        assert_eq!(paged.len(), 1);
+        assert_eq!(paged[0]["v"], "b4");
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// estimate the total hits of a limited query by counting the matches in a few
+// sampled hours and scaling them by the records of the whole time range
+
+use config::{
+    meta::{
+        search,
+        sql::Sql as MetaSql,
+        stream::{FileKey, StreamType},
+    },
+    CONFIG,
+};
+use infra::schema::{unwrap_partition_time_level, unwrap_stream_settings};
+use proto::cluster_rpc;
+
+use crate::service::file_list;
+
+const PARTITION_LEN: i64 = 3600 * 1_000_000;
+
+/// Matches needed for the relative error of the estimate to be about 10%
+const CONFIDENT_MATCHES: i64 = 100;
+
+/// Returns true if the response of the request may miss hits, so its total is
+/// worth estimating.
+pub fn need_estimate(req: &search::Request, res: &search::Response) -> bool {
+    req.query.estimate_total
+        && CONFIG.limit.query_estimate_partitions > 0
+        && !req.query.track_total_hits
+        && req.query.size > 0
+        && req.query.start_time > 0
+        && res.hits.len() >= req.query.size
+}
+
+/// Splits the time range into hours and returns `count` of them evenly spread
+fn sample_partitions(start_time: i64, end_time: i64, count: usize) -> Vec<(i64, i64)> {
+    let first = start_time - start_time % PARTITION_LEN;
+    let num = ((end_time - first + PARTITION_LEN - 1) / PARTITION_LEN).max(1) as usize;
+    let mut indexes = if count >= num {
+        (0..num).collect::<Vec<_>>()
+    } else if count == 1 {
+        vec![num / 2]
+    } else {
+        (0..count).map(|i| i * (num - 1) / (count - 1)).collect()
+    };
+    indexes.dedup();
+    indexes
+        .into_iter()
+        .map(|i| {
+            let start = first + i as i64 * PARTITION_LEN;
+            (start.max(start_time), (start + PARTITION_LEN).min(end_time))
+        })
+        .collect()
+}
+
+fn count_records(files: &[FileKey], start_time: i64, end_time: i64) -> i64 {
+    files
+        .iter()
+        .filter(|f| f.meta.max_ts >= start_time && f.meta.min_ts < end_time)
+        .map(|f| f.meta.records)
+        .sum()
+}
+
+/// Estimates the total hits of the query, `None` if the query can't be
+/// estimated.
+pub async fn estimate_total(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+) -> Option<search::TotalEstimate> {
+    let meta = MetaSql::new(&req.query.sql).ok()?;
+    if !meta.group_by.is_empty() {
+        return None;
+    }
+    let start_time = req.query.start_time;
+    let end_time = if req.query.end_time > 0 {
+        req.query.end_time
+    } else {
+        chrono::Utc::now().timestamp_micros()
+    };
+
+    let schema = infra::schema::get(org_id, &meta.source, stream_type)
+        .await
+        .ok()?;
+    let stream_settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let partition_time_level =
+        unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+    let files = file_list::query(
+        org_id,
+        &meta.source,
+        stream_type,
+        partition_time_level,
+        start_time,
+        end_time,
+        CONFIG.common.meta_store_external,
+    )
+    .await
+    .ok()?;
+    let total_records = count_records(&files, start_time, end_time);
+    if total_records == 0 {
+        return None;
+    }
+    let Some(selection) = meta.selection.as_ref() else {
+        // every record matches
+        return Some(search::TotalEstimate {
+            value: total_records as usize,
+            confident: true,
+        });
+    };
+
+    let partitions =
+        sample_partitions(start_time, end_time, CONFIG.limit.query_estimate_partitions);
+    let mut sampled_records = 0;
+    let mut matches = 0;
+    for (i, (start, end)) in partitions.iter().enumerate() {
+        let records = count_records(&files, *start, *end);
+        if records == 0 {
+            continue;
+        }
+        let mut count_req = req.clone();
+        count_req.query.sql = format!(
+            "SELECT COUNT(*) AS zo_sql_num FROM \"{}\" WHERE {selection}",
+            meta.source
+        );
+        count_req.query.sql_mode = "full".to_string();
+        count_req.query.start_time = *start;
+        count_req.query.end_time = *end;
+        count_req.query.from = 0;
+        count_req.query.size = 1;
+        count_req.query.track_total_hits = false;
+        count_req.aggs.clear();
+        let mut rpc_req: cluster_rpc::SearchRequest = count_req.into();
+        rpc_req.job.as_mut().unwrap().trace_id = format!("{trace_id}-estimate-{i}");
+        rpc_req.org_id = org_id.to_string();
+        rpc_req.stype = cluster_rpc::SearchType::Cluster as _;
+        rpc_req.stream_type = stream_type.to_string();
+        let res = match super::cluster::http::search(rpc_req).await {
+            Ok(res) => res,
+            Err(e) => {
+                log::error!("[trace_id {trace_id}] search->estimate: count error: {e}");
+                return None;
+            }
+        };
+        matches += res
+            .hits
+            .first()
+            .and_then(|hit| hit.get("zo_sql_num"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default();
+        sampled_records += records;
+    }
+    if sampled_records == 0 {
+        return None;
+    }
+    let value = (matches as f64 / sampled_records as f64 * total_records as f64).round() as usize;
+    Some(search::TotalEstimate {
+        value,
+        confident: sampled_records == total_records || matches >= CONFIDENT_MATCHES,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_partitions() {
+        let hour = PARTITION_LEN;
+        // 10 hours starting in the middle of an hour
+        let start = 100 * hour + hour / 2;
+        let end = start + 10 * hour;
+        let partitions = sample_partitions(start, end, 3);
+        assert_eq!(
+            partitions,
+            vec![
+                (start, 101 * hour),
+                (105 * hour, 106 * hour),
+                (110 * hour, end),
+            ]
+        );
+        assert_eq!(sample_partitions(start, start + hour / 4, 3).len(), 1);
+        assert_eq!(sample_partitions(start, end, 20).len(), 11);
+    }
+
+    #[test]
+    fn test_need_estimate_opt_in() {
+        let mut req = search::Request {
+            query: search::Query {
+                sql: "SELECT * FROM t WHERE a = 1".to_string(),
+                size: 2,
+                start_time: 1,
+                ..Default::default()
+            },
+            aggs: Default::default(),
+            encoding: search::RequestEncoding::Empty,
+            clusters: vec![],
+            timeout: 0,
+        };
+        let mut res = search::Response::new(0, 2);
+        res.hits = vec![Default::default(), Default::default()];
+        assert!(!need_estimate(&req, &res));
+        req.query.estimate_total = true;
+        assert_eq!(
+            need_estimate(&req, &res),
+            CONFIG.limit.query_estimate_partitions > 0
+        );
+    }
+}
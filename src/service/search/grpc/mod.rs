@@ -123,6 +123,7 @@ pub async fn search(
     let trace_id3 = trace_id.clone();
     let sql3 = sql.clone();
     let file_list: Vec<FileKey> = req.file_list.iter().map(FileKey::from).collect();
+    let partial = req.query.as_ref().unwrap().partial;
     let storage_span = info_span!(
         "service:search:grpc:in_storage",
         trace_id = trace_id3.as_ref().clone(),
@@ -142,6 +143,7 @@ pub async fn search(
                     stream_type,
                     &work_group3,
                     timeout,
+                    partial,
                 )
                 .await
             }
@@ -214,6 +216,7 @@ pub async fn search(
         tokio::select! {
             result = super::datafusion::exec::merge(
                 &sql.org_id,
+                sql.can_decrypt,
                 offset,
                 limit,
                 &merge_sql,
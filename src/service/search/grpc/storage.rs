@@ -24,7 +24,13 @@ use config::{
     utils::schema_ext::SchemaExt,
     CONFIG,
 };
-use datafusion::{arrow::record_batch::RecordBatch, common::FileType};
+use datafusion::{
+    arrow::{
+        datatypes::{DataType, Schema},
+        record_batch::RecordBatch,
+    },
+    common::FileType,
+};
 use futures::future::try_join_all;
 use hashbrown::HashMap;
 use infra::{
@@ -32,7 +38,10 @@ use infra::{
     errors::{Error, ErrorCodes},
     schema::{unwrap_partition_time_level, unwrap_stream_settings},
 };
-use tokio::{sync::Semaphore, time::Duration};
+use tokio::{
+    sync::Semaphore,
+    time::{Duration, Instant},
+};
 use tracing::{info_span, Instrument};
 
 use crate::service::{
@@ -40,6 +49,7 @@ use crate::service::{
     search::{
         datafusion::exec,
         grpc::{generate_search_schema, generate_select_start_search_schema},
+        scan_costs,
         sql::Sql,
         RE_SELECT_WILDCARD,
     },
@@ -54,6 +64,7 @@ pub async fn search(
     stream_type: StreamType,
     work_group: &str,
     timeout: u64,
+    partial: bool,
 ) -> super::SearchResult {
     // fetch all schema versions, group files by version
     let schema_versions =
@@ -124,11 +135,7 @@ pub async fn search(
             scan_stats.original_size += file.meta.original_size;
             scan_stats.compressed_size += file.meta.compressed_size;
             // check schema version
-            let schema_ver_id = match db::schema::filter_schema_version_id(
-                &schema_versions,
-                file.meta.min_ts,
-                file.meta.max_ts,
-            ) {
+            let schema_ver_id = match db::schema::file_schema_version_id(&schema_versions, file) {
                 Some(id) => id,
                 None => {
                     log::error!(
@@ -183,6 +190,12 @@ pub async fn search(
         schema_latest_map.insert(field.name(), field);
     }
     let select_wildcard = RE_SELECT_WILDCARD.is_match(sql.origin_sql.as_str());
+    // partial queries stop at the deadline instead of failing
+    let deadline = partial.then(|| Instant::now() + Duration::from_secs(timeout));
+
+    for files in files_group.values() {
+        scan_costs::record_scan(files);
+    }
 
     let mut tasks = Vec::new();
     for (ver, files) in files_group {
@@ -241,14 +254,13 @@ pub async fn search(
         let task = tokio::task::spawn(
             async move {
                 tokio::select! {
-                    ret = exec::sql(
+                    ret = search_files(
                         &session,
                         schema.clone(),
                         &diff_fields,
                         &sql,
                         &files,
-                        None,
-                        FileType::PARQUET,
+                        deadline,
                     ) => {
                         match ret {
                             Ok(ret) => Ok(ret),
@@ -266,7 +278,13 @@ pub async fn search(
                             }
                         }
                     },
-                    _ = tokio::time::sleep(Duration::from_secs(timeout)) => {
+                    _ = async {
+                        if deadline.is_some() {
+                            futures::future::pending::<()>().await;
+                        } else {
+                            tokio::time::sleep(Duration::from_secs(timeout)).await;
+                        }
+                    } => {
                         log::error!("[trace_id {}] search->storage: search timeout", session.id);
                         Err(datafusion::error::DataFusionError::Execution(format!(
                             "[trace_id {}] search->storage: task timeout", session.id
@@ -297,7 +315,8 @@ pub async fn search(
         .map_err(|e| Error::ErrorCode(ErrorCodes::ServerInternalError(e.to_string())))?;
     for ret in task_results {
         match ret {
-            Ok(ret) => {
+            Ok((ret, skipped)) => {
+                scan_stats.skipped_files += skipped as i64;
                 for (k, v) in ret {
                     let v = v
                         .into_iter()
@@ -318,6 +337,91 @@ pub async fn search(
     Ok((results, scan_stats))
 }
 
+/// Searches the files, with a deadline they are searched in chunks and the
+/// results of the finished chunks are returned once it passes, along with the
+/// number of files left unsearched. Only the files in storage are searched in
+/// chunks, the WAL is searched in full by the partial queries too.
+async fn search_files(
+    session: &config::meta::search::Session,
+    schema: Arc<Schema>,
+    diff_fields: &HashMap<String, DataType>,
+    sql: &Arc<Sql>,
+    files: &[FileKey],
+    deadline: Option<Instant>,
+) -> datafusion::error::Result<(HashMap<String, Vec<RecordBatch>>, usize)> {
+    let Some(deadline) = deadline else {
+        let ret = exec::sql(
+            session,
+            schema,
+            diff_fields,
+            sql,
+            files,
+            None,
+            FileType::PARQUET,
+        )
+        .await?;
+        return Ok((ret, 0));
+    };
+
+    let chunk_size = std::cmp::max(1, CONFIG.limit.query_partial_chunk_files);
+    let (results, skipped) = search_chunks(files, chunk_size, deadline, |i, chunk| {
+        // each chunk registers its own file list
+        let chunk_session = config::meta::search::Session {
+            id: format!("{}-{i}", session.id),
+            ..session.clone()
+        };
+        let schema = schema.clone();
+        async move {
+            exec::sql(
+                &chunk_session,
+                schema,
+                diff_fields,
+                sql,
+                chunk,
+                None,
+                FileType::PARQUET,
+            )
+            .await
+        }
+    })
+    .await?;
+    if skipped > 0 {
+        log::warn!(
+            "[trace_id {}] search->storage: search timeout, return partial result, skipped {skipped} of {} files",
+            session.id,
+            files.len()
+        );
+    }
+    Ok((results, skipped))
+}
+
+/// Searches the files chunk by chunk until the deadline, the chunk running
+/// when it passes is dropped and its files are counted as skipped.
+async fn search_chunks<'a, F, Fut>(
+    files: &'a [FileKey],
+    chunk_size: usize,
+    deadline: Instant,
+    mut search: F,
+) -> datafusion::error::Result<(HashMap<String, Vec<RecordBatch>>, usize)>
+where
+    F: FnMut(usize, &'a [FileKey]) -> Fut,
+    Fut: std::future::Future<Output = datafusion::error::Result<HashMap<String, Vec<RecordBatch>>>>,
+{
+    let mut results: HashMap<String, Vec<RecordBatch>> = HashMap::new();
+    for (i, chunk) in files.chunks(chunk_size).enumerate() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match tokio::time::timeout(remaining, search(i, chunk)).await {
+            Ok(ret) => {
+                for (k, v) in ret? {
+                    results.entry(k).or_default().extend(v);
+                }
+            }
+            Err(_) => return Ok((results, files.len() - i * chunk_size)),
+        }
+    }
+    Ok((results, 0))
+}
+
 #[tracing::instrument(name = "service:search:grpc:storage:get_file_list", skip_all, fields(trace_id, org_id = sql.org_id, stream_name = sql.stream_name))]
 async fn get_file_list(
     trace_id: &str,
@@ -400,6 +504,7 @@ async fn cache_parquet_files(
         let file_name = file.key.clone();
         let permit = semaphore.clone().acquire_owned().await.unwrap();
         let task: tokio::task::JoinHandle<Option<String>> = tokio::task::spawn(async move {
+            let start = std::time::Instant::now();
             let ret = match cache_type {
                 file_data::CacheType::Memory => {
                     if !file_data::memory::exist(&file_name).await
@@ -422,6 +527,7 @@ async fn cache_parquet_files(
                 }
                 _ => None,
             };
+            scan_costs::record_load(&file_name, start.elapsed());
             let ret = if let Some(e) = ret {
                 if e.to_string().to_lowercase().contains("not found")
                     || e.to_string().to_lowercase().contains("data size is zero")
@@ -470,3 +576,59 @@ async fn cache_parquet_files(
 
     Ok((cache_type, delete_files))
 }
+
+#[cfg(test)]
+mod tests {
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    fn files(n: usize) -> Vec<FileKey> {
+        (0..n)
+            .map(|i| {
+                FileKey::new(
+                    &format!("files/default/logs/app/{i}.parquet"),
+                    FileMeta::default(),
+                    false,
+                )
+            })
+            .collect()
+    }
+
+    async fn search_chunk(
+        i: usize,
+        slow_from: usize,
+    ) -> datafusion::error::Result<HashMap<String, Vec<RecordBatch>>> {
+        let delay = if i < slow_from { 50 } else { 10_000 };
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        Ok(HashMap::from([(format!("chunk{i}"), vec![])]))
+    }
+
+    #[tokio::test]
+    async fn test_search_chunks_deadline() {
+        let files = files(5);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        // the third chunk is still running at the deadline
+        let (results, skipped) = search_chunks(&files, 2, deadline, |i, _| search_chunk(i, 2))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.contains_key("chunk0") && results.contains_key("chunk1"));
+        assert_eq!(skipped, 1);
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let (results, skipped) = search_chunks(&files, 2, deadline, |i, _| search_chunk(i, 3))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(skipped, 0);
+
+        // past the deadline nothing is searched
+        let (results, skipped) =
+            search_chunks(&files, 2, Instant::now(), |i, _| search_chunk(i, 3))
+                .await
+                .unwrap();
+        assert!(results.is_empty());
+        assert_eq!(skipped, 5);
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// locate the terms a query searched for in its hits, so that they can be emphasized
+
+use std::collections::HashMap;
+
+use config::{
+    meta::{search, stream::StreamType},
+    utils::json,
+    SQL_FULL_TEXT_SEARCH_FIELDS,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::service::stream::get_stream_setting_fts_fields;
+
+static RE_MATCH_ALL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bmatch_all(?:_indexed)?(_ignore_case)?\(\s*'([^']*)'\s*\)").unwrap()
+});
+static RE_STR_MATCH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?i)\b(?:str_match|match)(_ignore_case)?\(\s*"?([^,"\s]+)"?\s*,\s*'([^']*)'\s*\)"#,
+    )
+    .unwrap()
+});
+static RE_REGEX_MATCH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)\bre_match\(\s*"?([^,"\s]+)"?\s*,\s*'([^']*)'\s*\)"#).unwrap());
+
+#[derive(Debug)]
+enum Target {
+    /// The full text search fields of the stream
+    FullText,
+    Field(String),
+}
+
+/// Returns the patterns searched by the sql with the fields they apply to
+fn parse_patterns(sql: &str) -> Vec<(Target, Regex)> {
+    let mut patterns = Vec::new();
+    let text_pattern = |value: &str, ignore_case: bool| {
+        let prefix = if ignore_case { "(?i)" } else { "" };
+        Regex::new(&format!("{prefix}{}", regex::escape(value))).ok()
+    };
+    for cap in RE_MATCH_ALL.captures_iter(sql) {
+        if cap[2].is_empty() {
+            continue;
+        }
+        if let Some(re) = text_pattern(&cap[2], cap.get(1).is_some()) {
+            patterns.push((Target::FullText, re));
+        }
+    }
+    for cap in RE_STR_MATCH.captures_iter(sql) {
+        if cap[3].is_empty() {
+            continue;
+        }
+        if let Some(re) = text_pattern(&cap[3], cap.get(1).is_some()) {
+            patterns.push((Target::Field(cap[2].to_string()), re));
+        }
+    }
+    for cap in RE_REGEX_MATCH.captures_iter(sql) {
+        if let Ok(re) = Regex::new(&cap[2]) {
+            patterns.push((Target::Field(cap[1].to_string()), re));
+        }
+    }
+    patterns
+}
+
+/// Returns the `[start, end)` character offsets of the matches in the value,
+/// overlapping matches are merged.
+fn match_offsets(value: &str, patterns: &[&Regex]) -> Vec<[usize; 2]> {
+    let mut ranges = patterns
+        .iter()
+        .flat_map(|re| re.find_iter(value))
+        .filter(|m| !m.is_empty())
+        .map(|m| [m.start(), m.end()])
+        .collect::<Vec<_>>();
+    ranges.sort();
+    let mut merged: Vec<[usize; 2]> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range[0] <= last[1] => last[1] = last[1].max(range[1]),
+            _ => merged.push(range),
+        }
+    }
+    // byte offsets to character offsets
+    merged
+        .into_iter()
+        .map(|[start, end]| {
+            let start_chars = value[..start].chars().count();
+            [start_chars, start_chars + value[start..end].chars().count()]
+        })
+        .collect()
+}
+
+fn highlight_hit(
+    hit: &json::Value,
+    patterns: &[(Target, Regex)],
+    fts_fields: &[String],
+) -> HashMap<String, Vec<[usize; 2]>> {
+    let mut ret = HashMap::new();
+    let Some(hit) = hit.as_object() else {
+        return ret;
+    };
+    for (key, value) in hit {
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+        let is_fts = fts_fields.contains(&key.to_lowercase());
+        let field_patterns = patterns
+            .iter()
+            .filter(|(target, _)| match target {
+                Target::FullText => is_fts,
+                Target::Field(field) => field == key,
+            })
+            .map(|(_, re)| re)
+            .collect::<Vec<_>>();
+        if field_patterns.is_empty() {
+            continue;
+        }
+        let offsets = match_offsets(value, &field_patterns);
+        if !offsets.is_empty() {
+            ret.insert(key.to_string(), offsets);
+        }
+    }
+    ret
+}
+
+/// Adds the offsets of the searched terms in every hit of the response
+pub async fn highlight(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    sql: &str,
+    res: &mut search::Response,
+) {
+    let patterns = parse_patterns(sql);
+    if patterns.is_empty() {
+        return;
+    }
+    let fts_fields = match infra::schema::get(org_id, stream_name, stream_type).await {
+        Ok(schema) => get_stream_setting_fts_fields(&schema).unwrap_or_default(),
+        Err(_) => vec![],
+    };
+    let fts_fields = if fts_fields.is_empty() {
+        SQL_FULL_TEXT_SEARCH_FIELDS
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+    } else {
+        fts_fields.iter().map(|v| v.to_lowercase()).collect()
+    };
+    res.highlights = res
+        .hits
+        .iter()
+        .map(|hit| highlight_hit(hit, &patterns, &fts_fields))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_hit() {
+        let sql = "SELECT * FROM default WHERE match_all_ignore_case('error') AND str_match(host, 'web') AND re_match(code, 'E[0-9]+')";
+        let patterns = parse_patterns(sql);
+        assert_eq!(patterns.len(), 3);
+        let hit = json::json!({
+            "log": "Error: disk error é error",
+            "host": "web-1 web",
+            "code": "E42 and E7",
+            "other": "error",
+        });
+        let ret = highlight_hit(&hit, &patterns, &["log".to_string()]);
+        assert_eq!(ret["log"], vec![[0, 5], [12, 17], [20, 25]]);
+        assert_eq!(ret["host"], vec![[0, 3], [6, 9]]);
+        assert_eq!(ret["code"], vec![[0, 3], [8, 10]]);
+        assert!(!ret.contains_key("other"));
+    }
+}
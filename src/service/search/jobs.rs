@@ -0,0 +1,576 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Background searches: a job is queued in the meta store and picked up by a
+//! querier, which searches its time partitions one after the other and writes
+//! the hits of each partition to the storage in chunks. The progress is saved
+//! after every partition, so another querier resumes a job whose node is gone.
+
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use chrono::Utc;
+use config::{
+    cluster::LOCAL_NODE_UUID,
+    meta::{
+        cluster::Role,
+        search,
+        sql::Sql as MetaSql,
+        stream::StreamType,
+        usage::{RequestStats, UsageType},
+    },
+    utils::json,
+    RwHashSet, CONFIG,
+};
+use infra::{dist_lock, storage};
+use once_cell::sync::Lazy;
+
+use super::streaming::{plan, Plan};
+use crate::{
+    common::{
+        infra::cluster::{get_node_by_uuid, get_node_from_consistent_hash},
+        meta::{
+            search_jobs::{SearchJob, SearchJobResults, SearchJobState, SearchJobStatus},
+            user::UserRole,
+        },
+        utils::auth::is_root_user,
+    },
+    service::{
+        db,
+        error::{Result, ServiceError},
+        usage::report_request_usage_stats,
+        users,
+    },
+};
+
+/// Hits written to one object of the storage
+const CHUNK_HITS: usize = 1000;
+
+/// Microseconds between two passes deleting the expired jobs
+const EXPIRE_INTERVAL: i64 = 3_600_000_000;
+
+// jobs running on this node
+static RUNNING: Lazy<RwHashSet<String>> = Lazy::new(Default::default);
+
+static LAST_EXPIRED: AtomicI64 = AtomicI64::new(0);
+
+/// Queues a search job, it is picked up by a querier. `size` limits the hits
+/// kept, up to `search_job_max_hits` when zero, and up to
+/// `search_job_max_sorted_hits` when the hits can't be split by time.
+pub async fn create(
+    org_id: &str,
+    stream_type: StreamType,
+    mut req: search::Request,
+    user_id: &str,
+) -> Result<SearchJob> {
+    let now = Utc::now().timestamp_micros();
+    if req.query.end_time == 0 || req.query.end_time > now {
+        req.query.end_time = now;
+    }
+    if req.query.start_time >= req.query.end_time {
+        return Err(ServiceError::bad_request(
+            "start_time should be less than end_time",
+        ));
+    }
+    if let Err(e) = MetaSql::new(&req.query.sql) {
+        return Err(ServiceError::bad_request(format!("invalid sql: {e}")));
+    }
+    if req.query.from > 0 {
+        return Err(ServiceError::bad_request(
+            "from isn't supported, the hits are fetched page by page",
+        ));
+    }
+    let max_hits = match plan(&req.query.sql) {
+        Plan::Partitions { .. } => CONFIG.limit.search_job_max_hits,
+        Plan::Whole | Plan::Pages => CONFIG.limit.search_job_max_sorted_hits,
+    };
+    if req.query.size == 0 || req.query.size > max_hits {
+        req.query.size = max_hits;
+    }
+
+    let job = SearchJob {
+        id: config::ider::generate(),
+        stream_type,
+        request: req,
+        status: SearchJobStatus::Pending,
+        node: String::new(),
+        partitions: vec![],
+        partition_hits: vec![],
+        total: 0,
+        scan_size: 0,
+        scan_records: 0,
+        error: String::new(),
+        created_by: user_id.to_string(),
+        created_at: now,
+        updated_at: now,
+    };
+    db::search_jobs::set(org_id, &job).await?;
+    Ok(job)
+}
+
+/// Returns the job, to its creator and to the admins of the organization
+pub async fn get(org_id: &str, id: &str, user_id: &str) -> Result<SearchJob> {
+    let job = db::search_jobs::get(org_id, id)
+        .await
+        .map_err(|_| ServiceError::not_found(format!("search job {id} not found")))?;
+    if job.created_by != user_id && !is_admin(org_id, user_id).await {
+        return Err(ServiceError::Forbidden(format!(
+            "search job {id} was created by another user"
+        )));
+    }
+    Ok(job)
+}
+
+/// Returns the jobs of the user, all of them to the admins
+pub async fn list(org_id: &str, user_id: &str) -> Result<Vec<SearchJobState>> {
+    let admin = is_admin(org_id, user_id).await;
+    Ok(db::search_jobs::list(org_id)
+        .await?
+        .into_iter()
+        .filter(|job| admin || job.created_by == user_id)
+        .map(SearchJobState::from)
+        .collect())
+}
+
+async fn is_admin(org_id: &str, user_id: &str) -> bool {
+    if is_root_user(user_id) {
+        return true;
+    }
+    users::get_user(Some(org_id), user_id)
+        .await
+        .is_some_and(|user| user.role == UserRole::Admin)
+}
+
+fn lock_key(org_id: &str, id: &str) -> String {
+    format!("/search_jobs/{org_id}/{id}")
+}
+
+/// Stops the job after its current partition, the hits found so far are kept.
+pub async fn cancel(org_id: &str, id: &str, user_id: &str) -> Result<SearchJob> {
+    // the querier saves the progress of the job under the same lock
+    let locker = dist_lock::lock(&lock_key(org_id, id), 0).await?;
+    let ret = match get(org_id, id, user_id).await {
+        Ok(job) if job.status.is_finished() => Err(ServiceError::Conflict(format!(
+            "search job {id} is already {:?}",
+            job.status
+        ))),
+        Ok(mut job) => {
+            job.status = SearchJobStatus::Cancelled;
+            job.updated_at = Utc::now().timestamp_micros();
+            db::search_jobs::set(org_id, &job)
+                .await
+                .map(|_| job)
+                .map_err(ServiceError::from)
+        }
+        Err(e) => Err(e),
+    };
+    dist_lock::unlock(&locker).await?;
+    let job = ret?;
+    #[cfg(feature = "enterprise")]
+    if !job.node.is_empty() {
+        if let Err(e) = super::cancel_query(&job.id).await {
+            log::warn!("[SEARCH JOB] cancel query of job {id} error: {e}");
+        }
+    }
+    Ok(job)
+}
+
+/// Deletes the job and its hits, a running job stops after its current
+/// partition.
+pub async fn delete(org_id: &str, id: &str, user_id: &str) -> Result<()> {
+    let job = get(org_id, id, user_id).await?;
+    db::search_jobs::delete(org_id, id).await?;
+    delete_hits(org_id, &job).await?;
+    Ok(())
+}
+
+/// Returns a page of the hits found so far, in the order of the partitions.
+/// Only the chunks holding the page are fetched.
+pub async fn results(
+    org_id: &str,
+    job: &SearchJob,
+    from: usize,
+    size: usize,
+) -> Result<SearchJobResults> {
+    let mut hits = Vec::with_capacity(size.min(job.total));
+    for (partition, range) in pages(&job.partition_hits, from, size) {
+        for (chunk, range) in chunks(range) {
+            let data = storage::get(&hits_key(org_id, &job.id, partition, chunk)).await?;
+            let chunk_hits: Vec<json::Value> =
+                json::from_slice(&data).map_err(anyhow::Error::from)?;
+            hits.extend(chunk_hits.into_iter().skip(range.start).take(range.len()));
+        }
+    }
+    Ok(SearchJobResults {
+        id: job.id.clone(),
+        status: job.status,
+        progress: job.progress(),
+        from,
+        size,
+        total: job.total,
+        hits,
+    })
+}
+
+/// Returns the partitions holding the hits [from, from + size), with the
+/// range of the hits in each of them
+fn pages(partition_hits: &[usize], from: usize, size: usize) -> Vec<(usize, Range<usize>)> {
+    let end = from + size;
+    let mut pages = vec![];
+    let mut offset = 0;
+    for (partition, hits) in partition_hits.iter().enumerate() {
+        let (start, stop) = (from.max(offset), end.min(offset + hits));
+        if start < stop {
+            pages.push((partition, start - offset..stop - offset));
+        }
+        offset += hits;
+        if offset >= end {
+            break;
+        }
+    }
+    pages
+}
+
+/// Returns the chunks holding the hits of a partition, with the range of the
+/// hits in each of them
+fn chunks(range: Range<usize>) -> Vec<(usize, Range<usize>)> {
+    if range.is_empty() {
+        return vec![];
+    }
+    (range.start / CHUNK_HITS..range.end.div_ceil(CHUNK_HITS))
+        .map(|chunk| {
+            let offset = chunk * CHUNK_HITS;
+            let (start, stop) = (range.start.max(offset), range.end.min(offset + CHUNK_HITS));
+            (chunk, start - offset..stop - offset)
+        })
+        .collect()
+}
+
+fn hits_key(org_id: &str, id: &str, partition: usize, chunk: usize) -> String {
+    format!("search_jobs/{org_id}/{id}/{partition}/{chunk}.json")
+}
+
+async fn delete_hits(org_id: &str, job: &SearchJob) -> Result<(), anyhow::Error> {
+    let keys = job
+        .partition_hits
+        .iter()
+        .enumerate()
+        .flat_map(|(partition, hits)| {
+            (0..hits.div_ceil(CHUNK_HITS))
+                .map(move |chunk| hits_key(org_id, &job.id, partition, chunk))
+        })
+        .collect::<Vec<_>>();
+    storage::del(&keys.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await
+}
+
+/// Claims the queued jobs, and the ones whose node is gone, and runs them in
+/// the background, up to `search_job_max_concurrent` at a time.
+pub async fn run_pending() -> Result<(), anyhow::Error> {
+    if let Err(e) = expire().await {
+        log::error!("[SEARCH JOB] delete expired jobs error: {e}");
+    }
+    for (org_id, id) in db::search_jobs::list_queued().await? {
+        if RUNNING.len() >= CONFIG.limit.search_job_max_concurrent {
+            break;
+        }
+        let job = match db::search_jobs::get(&org_id, &id).await {
+            Ok(job) => job,
+            Err(e) => {
+                log::warn!("[SEARCH JOB] get queued job {id} error: {e}");
+                continue;
+            }
+        };
+        if !is_claimable(&job).await {
+            continue;
+        }
+        let locker = dist_lock::lock(&lock_key(&org_id, &id), 0).await?;
+        // another querier may have claimed it meanwhile
+        let mut job = match db::search_jobs::get(&org_id, &id).await {
+            Ok(job) if is_claimable(&job).await => job,
+            _ => {
+                dist_lock::unlock(&locker).await?;
+                continue;
+            }
+        };
+        job.node = LOCAL_NODE_UUID.clone();
+        job.status = SearchJobStatus::Running;
+        job.updated_at = Utc::now().timestamp_micros();
+        let ret = db::search_jobs::set(&org_id, &job).await;
+        dist_lock::unlock(&locker).await?;
+        drop(locker);
+        ret?;
+
+        RUNNING.insert(job.id.clone());
+        tokio::task::spawn(async move { execute(org_id, job).await });
+    }
+    Ok(())
+}
+
+/// Deletes the jobs finished more than `search_job_retention` ago, once an
+/// hour by one of the queriers
+async fn expire() -> Result<(), anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    if now - LAST_EXPIRED.load(Ordering::Relaxed) < EXPIRE_INTERVAL {
+        return Ok(());
+    }
+    LAST_EXPIRED.store(now, Ordering::Relaxed);
+    match get_node_from_consistent_hash("search_jobs", &Role::Querier).await {
+        Some(node) if LOCAL_NODE_UUID.eq(&node) => {}
+        _ => return Ok(()), // another querier expires them
+    }
+    let expired_at = now - CONFIG.limit.search_job_retention * 3_600_000_000;
+    for (org_id, job) in db::search_jobs::list_all().await? {
+        if !job.status.is_finished() || job.updated_at >= expired_at {
+            continue;
+        }
+        db::search_jobs::delete(&org_id, &job.id).await?;
+        if let Err(e) = delete_hits(&org_id, &job).await {
+            log::error!("[SEARCH JOB] delete hits of job {} error: {e}", job.id);
+        }
+    }
+    Ok(())
+}
+
+async fn is_claimable(job: &SearchJob) -> bool {
+    if job.status.is_finished() || RUNNING.contains(&job.id) {
+        return false;
+    }
+    job.node.is_empty()
+        || LOCAL_NODE_UUID.eq(&job.node)
+        || get_node_by_uuid(&job.node).await.is_none()
+}
+
+async fn execute(org_id: String, mut job: SearchJob) {
+    log::info!(
+        "[SEARCH JOB] start job {}: {org_id}/{} partitions done: {}",
+        job.id,
+        job.stream_type,
+        job.partition_hits.len()
+    );
+    let start = std::time::Instant::now();
+    match run(&org_id, &mut job).await {
+        Ok(true) => job.status = SearchJobStatus::Done,
+        // cancelled or deleted meanwhile
+        Ok(false) => {}
+        Err(e) => {
+            job.status = SearchJobStatus::Failed;
+            job.error = e.to_string();
+        }
+    }
+    if job.status.is_finished() {
+        if let Err(e) = finish(&org_id, &mut job).await {
+            log::error!("[SEARCH JOB] job {} save error: {e}", job.id);
+        }
+    }
+    let stream_name = MetaSql::new(&job.request.query.sql)
+        .map(|meta| meta.source)
+        .unwrap_or_default();
+    let req_stats = RequestStats {
+        records: job.total as i64,
+        response_time: start.elapsed().as_secs_f64(),
+        size: job.scan_size as f64,
+        request_body: Some(job.request.query.sql.clone()),
+        user_email: Some(job.created_by.clone()),
+        min_ts: Some(job.request.query.start_time),
+        max_ts: Some(job.request.query.end_time),
+        ..Default::default()
+    };
+    report_request_usage_stats(
+        req_stats,
+        &org_id,
+        &stream_name,
+        job.stream_type,
+        UsageType::Search,
+        0,
+    )
+    .await;
+    log::info!(
+        "[SEARCH JOB] job {} {:?}, hits: {}, took: {:.3}s",
+        job.id,
+        job.status,
+        job.total,
+        start.elapsed().as_secs_f64()
+    );
+    RUNNING.remove(&job.id);
+}
+
+/// Saves the final status of the job, a cancellation saved meanwhile wins
+async fn finish(org_id: &str, job: &mut SearchJob) -> Result<(), anyhow::Error> {
+    let locker = dist_lock::lock(&lock_key(org_id, &job.id), 0).await?;
+    let ret = match db::search_jobs::get(org_id, &job.id).await {
+        Ok(saved) => {
+            if saved.status == SearchJobStatus::Cancelled {
+                job.status = SearchJobStatus::Cancelled;
+                job.error.clear();
+            }
+            job.updated_at = Utc::now().timestamp_micros();
+            db::search_jobs::set(org_id, job).await
+        }
+        // deleted, with the hits written before
+        Err(_) => delete_hits(org_id, job).await,
+    };
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+/// Searches the partitions left, returns false when the job was cancelled or
+/// deleted meanwhile
+async fn run(org_id: &str, job: &mut SearchJob) -> Result<bool, anyhow::Error> {
+    let mut req = job.request.clone();
+    let limit = job.request.query.size;
+    let page_size = CONFIG.limit.query_stream_page_size.max(1);
+    let plan = plan(&req.query.sql);
+    if job.partitions.is_empty() {
+        job.partitions = match &plan {
+            Plan::Whole | Plan::Pages => {
+                vec![[req.query.start_time, req.query.end_time]]
+            }
+            Plan::Partitions { ascending } => {
+                let part_req = search::SearchPartitionRequest {
+                    sql: req.query.sql.clone(),
+                    sql_mode: req.query.sql_mode.clone(),
+                    start_time: req.query.start_time,
+                    end_time: req.query.end_time,
+                };
+                let mut partitions =
+                    super::search_partition(&job.id, org_id, job.stream_type, &part_req)
+                        .await?
+                        .partitions;
+                // the partitions are the newest first
+                if *ascending {
+                    partitions.reverse();
+                }
+                partitions
+            }
+        };
+        if !save_progress(org_id, job).await? {
+            return Ok(false);
+        }
+    }
+
+    // the hits ordered by another field than the timestamp are sorted once,
+    // their number is capped by `search_job_max_sorted_hits`
+    let at_once = !matches!(plan, Plan::Partitions { .. });
+    while job.partition_hits.len() < job.partitions.len() && job.total < limit {
+        let partition = job.partition_hits.len();
+        let [start_time, end_time] = job.partitions[partition];
+        req.query.start_time = start_time;
+        req.query.end_time = end_time;
+        req.query.from = 0;
+        // hits not written yet
+        let mut hits = Vec::new();
+        let (mut found, mut chunk) = (0, 0);
+        loop {
+            let size = if at_once {
+                limit - job.total
+            } else {
+                page_size.min(limit - job.total - found)
+            };
+            req.query.size = size;
+            let res = super::search(
+                &job.id,
+                org_id,
+                job.stream_type,
+                Some(job.created_by.clone()),
+                &req,
+            )
+            .await?;
+            job.scan_size += res.scan_size;
+            job.scan_records += res.scan_records;
+            let fetched = res.hits.len();
+            found += fetched;
+            hits.extend(res.hits);
+            let mut written = 0;
+            while hits.len() - written >= CHUNK_HITS {
+                let key = hits_key(org_id, &job.id, partition, chunk);
+                storage::put(
+                    &key,
+                    json::to_vec(&hits[written..written + CHUNK_HITS])?.into(),
+                )
+                .await?;
+                written += CHUNK_HITS;
+                chunk += 1;
+            }
+            hits.drain(..written);
+            if at_once || fetched < size || job.total + found >= limit {
+                break;
+            }
+            req.query.from += size;
+        }
+        if !hits.is_empty() {
+            let key = hits_key(org_id, &job.id, partition, chunk);
+            storage::put(&key, json::to_vec(&hits)?.into()).await?;
+        }
+        job.total += found;
+        job.partition_hits.push(found);
+        if !save_progress(org_id, job).await? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Saves the progress of the job unless it was cancelled or deleted
+async fn save_progress(org_id: &str, job: &mut SearchJob) -> Result<bool, anyhow::Error> {
+    let locker = dist_lock::lock(&lock_key(org_id, &job.id), 0).await?;
+    let ret = match db::search_jobs::get(org_id, &job.id).await {
+        Ok(saved) if saved.status == SearchJobStatus::Running => {
+            job.updated_at = Utc::now().timestamp_micros();
+            db::search_jobs::set(org_id, job).await.map(|_| true)
+        }
+        Ok(saved) => {
+            // keep the hits found before the cancellation
+            job.status = saved.status;
+            job.updated_at = Utc::now().timestamp_micros();
+            db::search_jobs::set(org_id, job).await.map(|_| false)
+        }
+        // deleted, with the hits written before
+        Err(_) => delete_hits(org_id, job).await.map(|_| false),
+    };
+    dist_lock::unlock(&locker).await?;
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages() {
+        let partition_hits = [3, 0, 5, 2];
+        assert_eq!(pages(&partition_hits, 0, 2), vec![(0, 0..2)]);
+        assert_eq!(pages(&partition_hits, 2, 4), vec![(0, 2..3), (2, 0..3)]);
+        assert_eq!(pages(&partition_hits, 3, 10), vec![(2, 0..5), (3, 0..2)]);
+        assert_eq!(pages(&partition_hits, 8, 1), vec![(3, 0..1)]);
+        assert!(pages(&partition_hits, 10, 5).is_empty());
+        assert!(pages(&[], 0, 5).is_empty());
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(chunks(0..10), vec![(0, 0..10)]);
+        assert_eq!(
+            chunks(CHUNK_HITS - 5..CHUNK_HITS + 5),
+            vec![(0, CHUNK_HITS - 5..CHUNK_HITS), (1, 0..5)]
+        );
+        assert_eq!(
+            chunks(CHUNK_HITS..3 * CHUNK_HITS),
+            vec![(1, 0..CHUNK_HITS), (2, 0..CHUNK_HITS)]
+        );
+        assert!(chunks(5..5).is_empty());
+    }
+}
@@ -13,14 +13,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{cmp::max, sync::Arc};
+use std::{cmp::max, collections::HashMap, sync::Arc};
 
 use chrono::Duration;
 use config::{
     ider,
     meta::{
         search,
-        stream::{FileKey, StreamType},
+        stream::{FieldMetadata, FileKey, StreamType},
     },
     utils::str::find,
     CONFIG,
@@ -50,10 +50,21 @@ use crate::{
     service::format_partition_key,
 };
 
+pub(crate) mod cache;
 pub(crate) mod cluster;
 pub(crate) mod datafusion;
+pub(crate) mod dedup;
+pub(crate) mod estimate;
 pub(crate) mod grpc;
+pub(crate) mod highlight;
+pub(crate) mod jobs;
+pub(crate) mod nested;
+pub(crate) mod policy;
+pub(crate) mod sample;
+pub(crate) mod scan_costs;
 pub(crate) mod sql;
+pub(crate) mod streaming;
+pub(crate) mod transaction;
 
 pub static SEARCH_SERVER: Lazy<Searcher> = Lazy::new(Searcher::new);
 
@@ -82,6 +93,54 @@ pub async fn search(
         trace_id.to_string()
     };
 
+    // the decrypt function is only registered for the users allowed to read the
+    // encrypted fields
+    let can_decrypt = match config::meta::sql::Sql::new(&req.query.sql) {
+        Ok(meta) => {
+            crate::service::encryption::can_decrypt(
+                org_id,
+                user_id.as_deref(),
+                stream_type,
+                &meta.source,
+            )
+            .await
+        }
+        Err(_) => false,
+    };
+
+    // check the org query policies
+    let policies = crate::service::db::organization::get_query_policies(org_id).await;
+    if let Err(e) = policy::check(
+        &policies,
+        &req.query.sql,
+        req.query.start_time,
+        req.query.end_time,
+    ) {
+        return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e)));
+    }
+    if let Err(e) = sample::validate(req.query.sample) {
+        return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e)));
+    }
+
+    // take the query operators out of the sql, they are applied around its search
+    let mut in_req = req.to_owned();
+    let dedup = dedup::parse(&req.query.sql).map(|(sql, dedup)| {
+        in_req.query.sql = sql;
+        // the groups span the pages, all the hits are collapsed before paging
+        in_req.query.from = 0;
+        in_req.query.size = CONFIG.limit.query_dedup_max_hits;
+        dedup
+    });
+    let transaction = transaction::parse(&in_req.query.sql).map(|(sql, transaction)| {
+        in_req.query.sql = sql;
+        transaction
+    });
+    if let Some(transaction) = &transaction {
+        in_req.query.sql =
+            transaction::rewrite_sql(org_id, stream_type, &in_req.query.sql, transaction).await?;
+    }
+    let in_req = &in_req;
+
     #[cfg(feature = "enterprise")]
     {
         let sql = Some(req.query.sql.clone());
@@ -105,29 +164,17 @@ pub async fn search(
             .await;
     }
 
-    #[cfg(feature = "enterprise")]
-    let req_clusters = req.clusters.clone();
-    #[cfg(feature = "enterprise")]
-    let local_cluster_search = !req_clusters.is_empty()
-        && (req_clusters == vec!["local"] || req_clusters == vec![config::get_cluster_name()]);
-
-    let mut req: cluster_rpc::SearchRequest = req.to_owned().into();
-    req.job.as_mut().unwrap().trace_id = trace_id.clone();
-    req.org_id = org_id.to_string();
-    req.stype = cluster_rpc::SearchType::Cluster as _;
-    req.stream_type = stream_type.to_string();
-
-    let res = {
-        #[cfg(feature = "enterprise")]
-        if O2_CONFIG.super_cluster.enabled && !local_cluster_search {
-            cluster::super_cluster::search(req, req_clusters).await
-        } else {
-            cluster::http::search(req).await
-        }
-        #[cfg(not(feature = "enterprise"))]
-        {
-            cluster::http::search(req).await
+    // dashboards search the complete buckets of their aggregations once
+    let plan = if CONFIG.limit.query_result_cache_enabled {
+        cache::plan(org_id, stream_type, in_req, can_decrypt)
+    } else {
+        None
+    };
+    let res = match plan {
+        Some(plan) => {
+            cache::search(&trace_id, org_id, stream_type, in_req, &plan, can_decrypt).await
         }
+        None => cluster_search(&trace_id, org_id, stream_type, in_req, can_decrypt).await,
     };
 
     // remove task because task if finished
@@ -136,11 +183,98 @@ pub async fn search(
 
     // do this because of clippy warning
     match res {
-        Ok(res) => Ok(res),
+        Ok(mut res) => {
+            if let Some(dedup) = dedup {
+                if res.hits.len() >= CONFIG.limit.query_dedup_max_hits && res.warning.is_empty() {
+                    res.warning = format!(
+                        "dedup only collapsed the first {} hits",
+                        CONFIG.limit.query_dedup_max_hits
+                    );
+                }
+                let hits = dedup::apply(&dedup, std::mem::take(&mut res.hits));
+                res.total = hits.len();
+                res.hits = dedup::page(hits, req.query.from, req.query.size);
+                res.from = req.query.from;
+                res.size = req.query.size;
+            }
+            if transaction.is_some() {
+                transaction::finish(&mut res.hits);
+            }
+            res.field_metadata = field_metadata(org_id, stream_type, &in_req.query.sql, &res).await;
+            if sample::is_sampled(in_req.query.sample) {
+                sample::scale(in_req, &mut res);
+            } else if estimate::need_estimate(in_req, &res) {
+                res.total_estimate =
+                    estimate::estimate_total(&trace_id, org_id, stream_type, in_req).await;
+            }
+            Ok(res)
+        }
         Err(e) => Err(e),
     }
 }
 
+/// The metadata of the fields returned by the search, from the settings of the
+/// searched stream.
+async fn field_metadata(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: &str,
+    res: &search::Response,
+) -> HashMap<String, FieldMetadata> {
+    let Ok(meta) = config::meta::sql::Sql::new(sql) else {
+        return HashMap::new();
+    };
+    match infra::schema::get_settings(org_id, &meta.source, stream_type).await {
+        Some(settings) if !settings.field_metadata.is_empty() => {
+            returned_field_metadata(settings.field_metadata, res)
+        }
+        _ => HashMap::new(),
+    }
+}
+
+fn returned_field_metadata(
+    mut metadata: HashMap<String, FieldMetadata>,
+    res: &search::Response,
+) -> HashMap<String, FieldMetadata> {
+    metadata.retain(|field, _| {
+        res.columns.contains(field) || res.hits.iter().any(|hit| hit.get(field).is_some())
+    });
+    metadata
+}
+
+/// Searches the clusters the request asks for
+async fn cluster_search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    in_req: &search::Request,
+    can_decrypt: bool,
+) -> Result<search::Response, Error> {
+    #[cfg(feature = "enterprise")]
+    let req_clusters = in_req.clusters.clone();
+    #[cfg(feature = "enterprise")]
+    let local_cluster_search = !req_clusters.is_empty()
+        && (req_clusters == vec!["local"] || req_clusters == vec![config::get_cluster_name()]);
+
+    let mut req: cluster_rpc::SearchRequest = in_req.to_owned().into();
+    req.job.as_mut().unwrap().trace_id = trace_id.to_string();
+    req.org_id = org_id.to_string();
+    req.stype = cluster_rpc::SearchType::Cluster as _;
+    req.stream_type = stream_type.to_string();
+    req.can_decrypt = can_decrypt;
+
+    #[cfg(feature = "enterprise")]
+    if O2_CONFIG.super_cluster.enabled && !local_cluster_search {
+        cluster::super_cluster::search(req, req_clusters).await
+    } else {
+        cluster::http::search(req).await
+    }
+    #[cfg(not(feature = "enterprise"))]
+    {
+        cluster::http::search(req).await
+    }
+}
+
 #[tracing::instrument(name = "service:search_partition:enter", skip(req))]
 pub async fn search_partition(
     trace_id: &str,
@@ -360,6 +494,8 @@ pub async fn query_status() -> Result<search::QueryStatusResponse, Error> {
                 records: scan_stats.records,
                 original_size: scan_stats.original_size / 1024 / 1024, // change to MB
                 compressed_size: scan_stats.compressed_size / 1024 / 1024, // change to MB
+                skipped_files: scan_stats.skipped_files,
+                ..Default::default()
             });
         let query_status = if result.is_queue {
             "waiting"
@@ -600,6 +736,32 @@ impl<'a> opentelemetry::propagation::Injector for MetadataMap<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_returned_field_metadata() {
+        let metadata = ["user_email", "duration", "code"]
+            .into_iter()
+            .map(|field| {
+                (
+                    field.to_string(),
+                    FieldMetadata {
+                        description: String::new(),
+                        tags: vec!["pii".to_string()],
+                    },
+                )
+            })
+            .collect();
+        let mut res = search::Response::new(0, 10);
+        res.columns = vec!["code".to_string()];
+        res.hits = vec![
+            config::utils::json::json!({"user_email": "a@b.c"}),
+            config::utils::json::json!({"host": "a"}),
+        ];
+        let returned = returned_field_metadata(metadata, &res);
+        let mut fields = returned.keys().collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, vec!["code", "user_email"]);
+    }
+
     #[test]
     fn test_matches_by_partition_key_with_str() {
         let path = "files/default/logs/gke-fluentbit/2023/04/14/08/kuberneteshost=gke-dev1/kubernetesnamespacename=ziox-dev/7052558621820981249.parquet";
@@ -0,0 +1,167 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use ::datafusion::arrow::{
+    array::{ArrayRef, StructArray},
+    datatypes::{DataType, Field, Fields, Schema},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+
+enum Node {
+    Leaf(usize),
+    Group(Vec<(String, Node)>),
+}
+
+/// Promote the flattened columns under the given dot paths to nested struct columns,
+/// e.g. with path `kubernetes.labels` the column `kubernetes_labels_app` becomes
+/// `kubernetes: {labels: {app}}`. Columns that don't match any path are kept as they are.
+pub(crate) fn promote_nested_fields(
+    batch: &RecordBatch,
+    paths: &[String],
+) -> Result<RecordBatch, ArrowError> {
+    let schema = batch.schema();
+    let mut paths = paths
+        .iter()
+        .map(|p| (p.replace('.', "_") + "_", p.split('.').collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    // prefer the longest path
+    paths.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+    let mut root = Vec::with_capacity(schema.fields().len());
+    for (i, field) in schema.fields().iter().enumerate() {
+        let name = field.name();
+        let promoted = paths
+            .iter()
+            .find(|(prefix, _)| name.len() > prefix.len() && name.starts_with(prefix.as_str()))
+            .is_some_and(|(prefix, segments)| {
+                insert_leaf(&mut root, segments, &name[prefix.len()..], i)
+            });
+        // keep the column flat when the nested name is taken
+        if !promoted {
+            root.push((name.to_string(), Node::Leaf(i)));
+        }
+    }
+
+    let mut fields = Vec::with_capacity(root.len());
+    let mut columns = Vec::with_capacity(root.len());
+    for (name, node) in root {
+        let (field, column) = build_column(batch, name, node)?;
+        fields.push(field);
+        columns.push(column);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+fn insert_leaf(nodes: &mut Vec<(String, Node)>, segments: &[&str], leaf: &str, i: usize) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        if nodes.iter().any(|(k, _)| k == leaf) {
+            return false;
+        }
+        nodes.push((leaf.to_string(), Node::Leaf(i)));
+        return true;
+    };
+    let (pos, created) = match nodes.iter().position(|(k, _)| k == segment) {
+        Some(pos) => (pos, false),
+        None => {
+            nodes.push((segment.to_string(), Node::Group(vec![])));
+            (nodes.len() - 1, true)
+        }
+    };
+    let inserted = match &mut nodes[pos].1 {
+        Node::Group(children) => insert_leaf(children, rest, leaf, i),
+        Node::Leaf(_) => false,
+    };
+    if !inserted && created {
+        nodes.pop();
+    }
+    inserted
+}
+
+fn build_column(
+    batch: &RecordBatch,
+    name: String,
+    node: Node,
+) -> Result<(Field, ArrayRef), ArrowError> {
+    match node {
+        Node::Leaf(i) => {
+            let field = batch.schema().field(i).clone().with_name(name);
+            Ok((field, batch.column(i).clone()))
+        }
+        Node::Group(children) => {
+            let mut fields = Vec::with_capacity(children.len());
+            let mut columns = Vec::with_capacity(children.len());
+            for (name, node) in children {
+                let (field, column) = build_column(batch, name, node)?;
+                fields.push(field);
+                columns.push(column);
+            }
+            let fields = Fields::from(fields);
+            let array = StructArray::try_new(fields.clone(), columns, None)?;
+            Ok((
+                Field::new(name, DataType::Struct(fields), true),
+                Arc::new(array),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::datafusion::arrow::{
+        array::{Array, Int64Array, StringArray},
+        json as arrow_json,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_promote_nested_fields() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_timestamp", DataType::Int64, false),
+            Field::new("kubernetes_labels_app", DataType::Utf8, true),
+            Field::new("kubernetes_pod_name", DataType::Utf8, true),
+            Field::new("message", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["web"])),
+                Arc::new(StringArray::from(vec!["web-0"])),
+                Arc::new(StringArray::from(vec!["hello"])),
+            ],
+        )
+        .unwrap();
+        let paths = vec!["kubernetes".to_string(), "kubernetes.labels".to_string()];
+        let batch = promote_nested_fields(&batch, &paths).unwrap();
+        let names = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["_timestamp", "kubernetes", "message"]);
+        assert_eq!(batch.column(1).len(), 1);
+
+        let rows = arrow_json::writer::record_batches_to_json_rows(&[&batch]).unwrap();
+        assert_eq!(
+            rows[0].get("kubernetes").unwrap().to_string(),
+            r#"{"labels":{"app":"web"},"pod_name":"web-0"}"#
+        );
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::meta::sql::Sql as MetaSql;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::sql::{sql_function_names, virtual_field_columns};
+use crate::common::meta::organization::QueryPolicy;
+
+static RE_SELECT_ALL: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^\s*select\s+\*").unwrap());
+
+/// Checks the policies are well formed before they are saved.
+pub(crate) fn validate(policies: &[QueryPolicy]) -> Result<(), String> {
+    for (i, policy) in policies.iter().enumerate() {
+        if policy.name.is_empty() {
+            return Err("query policy name can't be empty".to_string());
+        }
+        if policies[..i].iter().any(|p| p.name == policy.name) {
+            return Err(format!("query policy [{}] is duplicated", policy.name));
+        }
+        if policy.max_time_range < 0 {
+            return Err(format!(
+                "query policy [{}] max_time_range should be a positive value",
+                policy.name
+            ));
+        }
+        if policy.max_time_range == 0
+            && policy.required_filters.is_empty()
+            && policy.banned_functions.is_empty()
+        {
+            return Err(format!("query policy [{}] has no rule", policy.name));
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates a search against the org policies, returns the reason of the rejection.
+pub(crate) fn check(
+    policies: &[QueryPolicy],
+    sql: &str,
+    start_time: i64,
+    end_time: i64,
+) -> Result<(), String> {
+    if policies.is_empty() {
+        return Ok(());
+    }
+    // invalid queries are rejected later with a better message
+    let Ok(meta) = MetaSql::new(sql) else {
+        return Ok(());
+    };
+    let functions = sql_function_names(sql);
+    let filters = meta
+        .selection
+        .as_ref()
+        .map(|expr| virtual_field_columns(&expr.to_string()))
+        .unwrap_or_default();
+    let (start_time, end_time) = match meta.time_range {
+        // the time range in the query can only narrow the one of the request
+        Some((start, end)) => (
+            if start > 0 {
+                start.max(start_time)
+            } else {
+                start_time
+            },
+            if end > 0 { end.min(end_time) } else { end_time },
+        ),
+        None => (start_time, end_time),
+    };
+
+    for policy in policies {
+        if !policy.streams.is_empty() && !policy.streams.contains(&meta.source) {
+            continue;
+        }
+        if policy.max_time_range > 0
+            && (!policy.select_all_only || RE_SELECT_ALL.is_match(sql))
+            && end_time - start_time > policy.max_time_range * 3600 * 1_000_000
+        {
+            return Err(format!(
+                "query rejected by policy [{}]: time range can't be longer than {} hours{}",
+                policy.name,
+                policy.max_time_range,
+                if policy.select_all_only {
+                    " for select * queries"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if let Some(field) = policy
+            .required_filters
+            .iter()
+            .find(|f| !filters.contains(f))
+        {
+            return Err(format!(
+                "query rejected by policy [{}]: stream [{}] requires a filter on field [{field}]",
+                policy.name, meta.source
+            ));
+        }
+        if let Some(function) = policy
+            .banned_functions
+            .iter()
+            .find(|f| functions.contains(&f.to_lowercase()))
+        {
+            return Err(format!(
+                "query rejected by policy [{}]: function [{function}] is not allowed",
+                policy.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600 * 1_000_000;
+
+    fn policy() -> QueryPolicy {
+        QueryPolicy {
+            name: "hygiene".to_string(),
+            streams: vec!["k8s".to_string()],
+            max_time_range: 24 * 7,
+            select_all_only: true,
+            required_filters: vec!["namespace".to_string()],
+            banned_functions: vec!["re_match".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_check_query_policy() {
+        let policies = vec![policy()];
+        let end = 1_700_000_000_000_000;
+        let start = end - 30 * 24 * HOUR;
+
+        let ret = check(
+            &policies,
+            "select * from k8s where namespace = 'a'",
+            start,
+            end,
+        );
+        assert!(ret.unwrap_err().contains("168 hours"));
+        // only select * is limited
+        assert!(
+            check(
+                &policies,
+                "select count(*) from k8s where namespace = 'a'",
+                start,
+                end
+            )
+            .is_ok()
+        );
+        // a narrow time range in the query is fine
+        assert!(
+            check(
+                &policies,
+                "select * from k8s where namespace = 'a'",
+                end - HOUR,
+                end
+            )
+            .is_ok()
+        );
+
+        let ret = check(
+            &policies,
+            "select * from k8s where pod = 'a'",
+            end - HOUR,
+            end,
+        );
+        assert!(ret.unwrap_err().contains("[namespace]"));
+
+        let ret = check(
+            &policies,
+            "select * from k8s where namespace = 'a' and RE_MATCH(log, 'x')",
+            end - HOUR,
+            end,
+        );
+        assert!(ret.unwrap_err().contains("[re_match]"));
+
+        // other streams are not affected
+        assert!(check(&policies, "select * from default", start, end).is_ok());
+    }
+
+    #[test]
+    fn test_validate_query_policy() {
+        assert!(validate(&[policy()]).is_ok());
+        assert!(validate(&[policy(), policy()]).is_err());
+        assert!(
+            validate(&[QueryPolicy {
+                name: "empty".to_string(),
+                ..Default::default()
+            }])
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// sampled queries scan a deterministic subset of the files, picked by the
+// hash of their keys, and scale their counts and sums by the fraction of the
+// records scanned
+
+use config::{
+    meta::{
+        search::{self, SampleInfo},
+        sql::Sql as MetaSql,
+        stream::FileKey,
+    },
+    utils::{
+        hash::{murmur3, Sum64},
+        json::{Map, Value},
+    },
+};
+use sqlparser::{
+    ast::{Expr, SelectItem, SetExpr, Statement},
+    dialect::GenericDialect,
+    parser::Parser,
+};
+
+/// z-score of the 95% confidence margins
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregate {
+    Count,
+    Sum,
+}
+
+pub fn validate(rate: f64) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&rate) {
+        return Err("sample must be between 0 and 1".to_string());
+    }
+    Ok(())
+}
+
+pub fn is_sampled(rate: f64) -> bool {
+    rate > 0.0 && rate < 1.0
+}
+
+/// The files of the sample, every run of the query picks the same ones so
+/// its results can be compared.
+pub fn files(files: Vec<FileKey>, rate: f64) -> Vec<FileKey> {
+    let threshold = (rate * u64::MAX as f64) as u64;
+    let mut h = murmur3::new();
+    files
+        .into_iter()
+        .filter(|f| h.sum64(&f.key) <= threshold)
+        .collect()
+}
+
+/// Scales the counts and sums of the response of a sampled request and adds
+/// their margins of error. The files differ in size, so the results are
+/// scaled by the fraction of the records scanned rather than the rate of
+/// files picked.
+pub fn scale(req: &search::Request, res: &mut search::Response) {
+    let rate = req.query.sample;
+    let fraction = res
+        .sample
+        .as_ref()
+        .map(|s| s.fraction)
+        .filter(|f| *f > 0.0)
+        .unwrap_or(rate);
+    scale_hits(&req.query.sql, fraction, &mut res.hits);
+    for (name, hits) in res.aggs.iter_mut() {
+        if let Some(sql) = req.aggs.get(name) {
+            scale_hits(sql, fraction, hits);
+        }
+    }
+    // the total of an aggregation counts its groups, not the records
+    if !is_aggregate(&req.query.sql) {
+        res.total = (res.total as f64 / fraction).round() as usize;
+    }
+    res.sample = Some(SampleInfo {
+        rate,
+        fraction,
+        files: res.file_count,
+    });
+}
+
+fn is_aggregate(sql: &str) -> bool {
+    MetaSql::new(sql).map_or(true, |meta| !meta.group_by.is_empty())
+        || super::streaming::RE_AGGREGATE.is_match(sql)
+}
+
+fn scale_hits(sql: &str, rate: f64, hits: &mut [Value]) {
+    let columns = aggregate_columns(sql);
+    if columns.is_empty() {
+        return;
+    }
+    // the sums borrow the relative error of the count of their row
+    let count_column = columns
+        .iter()
+        .find(|(_, agg)| *agg == Aggregate::Count)
+        .map(|(name, _)| name.clone());
+    for hit in hits.iter_mut() {
+        let Some(hit) = hit.as_object_mut() else {
+            continue;
+        };
+        let count = count_column
+            .as_ref()
+            .and_then(|c| hit.get(c))
+            .and_then(|v| v.as_f64());
+        for (name, agg) in columns.iter() {
+            scale_value(hit, name, *agg, count, rate);
+        }
+    }
+}
+
+fn scale_value(
+    hit: &mut Map<String, Value>,
+    name: &str,
+    agg: Aggregate,
+    count: Option<f64>,
+    rate: f64,
+) {
+    let Some(raw) = hit.get(name).and_then(|v| v.as_f64()) else {
+        return;
+    };
+    let (value, error) = match agg {
+        Aggregate::Count => {
+            // the count of a sample is close to a poisson variable
+            let value = (raw / rate).round() as i64;
+            let error = (Z_95 * raw.sqrt() / rate).round() as i64;
+            (Value::from(value), Some(Value::from(error)))
+        }
+        Aggregate::Sum => {
+            let value = raw / rate;
+            let error = count
+                .filter(|c| *c > 0.0)
+                .map(|c| Value::from(value.abs() * Z_95 / c.sqrt()));
+            (Value::from(value), error)
+        }
+    };
+    hit.insert(name.to_string(), value);
+    if let Some(error) = error {
+        hit.insert(format!("{name}_error"), error);
+    }
+}
+
+/// The columns of the counts and sums selected by the query, distinct counts
+/// can't be scaled.
+fn aggregate_columns(sql: &str) -> Vec<(String, Aggregate)> {
+    let Ok(statements) = Parser::parse_sql(&GenericDialect {}, sql) else {
+        return vec![];
+    };
+    let Some(Statement::Query(query)) = statements.first() else {
+        return vec![];
+    };
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return vec![];
+    };
+    select
+        .projection
+        .iter()
+        .filter_map(|item| {
+            let (expr, name) = match item {
+                SelectItem::UnnamedExpr(expr) => (expr, expr.to_string()),
+                SelectItem::ExprWithAlias { expr, alias } => (expr, alias.value.clone()),
+                _ => return None,
+            };
+            let Expr::Function(f) = expr else {
+                return None;
+            };
+            if f.distinct || f.over.is_some() {
+                return None;
+            }
+            match f.name.to_string().to_lowercase().as_str() {
+                "count" => Some((name, Aggregate::Count)),
+                "sum" => Some((name, Aggregate::Sum)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{meta::stream::FileMeta, utils::json};
+
+    use super::*;
+
+    #[test]
+    fn test_sample_files() {
+        let keys = (0..1000)
+            .map(|i| FileKey {
+                key: format!("files/default/logs/app/2024/01/01/00/{i}.parquet"),
+                meta: FileMeta::default(),
+                deleted: false,
+            })
+            .collect::<Vec<_>>();
+        let sampled = files(keys.clone(), 0.1);
+        assert!(
+            sampled.len() > 50 && sampled.len() < 150,
+            "{}",
+            sampled.len()
+        );
+        // the same files every time
+        let again = files(keys, 0.1);
+        assert_eq!(
+            sampled.iter().map(|f| &f.key).collect::<Vec<_>>(),
+            again.iter().map(|f| &f.key).collect::<Vec<_>>()
+        );
+
+        assert!(validate(0.01).is_ok());
+        assert!(validate(1.5).is_err());
+        assert!(validate(f64::NAN).is_err());
+        assert!(!is_sampled(0.0) && !is_sampled(1.0) && is_sampled(0.5));
+    }
+
+    #[test]
+    fn test_scale_hits() {
+        let sql = "SELECT host, count(*) AS cnt, sum(bytes) AS total, avg(bytes) AS mean, count(distinct ip) AS ips FROM \"default\" GROUP BY host";
+        let mut hits =
+            vec![json::json!({"host": "a", "cnt": 100, "total": 2000.0, "mean": 20.0, "ips": 7})];
+        scale_hits(sql, 0.5, &mut hits);
+        let hit = hits[0].as_object().unwrap();
+        assert_eq!(hit["cnt"], json::json!(200));
+        assert_eq!(hit["cnt_error"], json::json!(39));
+        assert_eq!(hit["total"], json::json!(4000.0));
+        assert_eq!(hit["total_error"], json::json!(4000.0 * 1.96 / 10.0));
+        assert_eq!(hit["mean"], json::json!(20.0));
+        assert_eq!(hit["ips"], json::json!(7));
+        assert!(!hit.contains_key("ips_error"));
+    }
+
+    #[test]
+    fn test_scale() {
+        let req = search::Request {
+            query: search::Query {
+                sql: "SELECT * FROM \"default\"".to_string(),
+                sample: 0.1,
+                ..Default::default()
+            },
+            aggs: Default::default(),
+            encoding: search::RequestEncoding::Empty,
+            clusters: vec![],
+            timeout: 0,
+        };
+        // the files picked hold a quarter of the records
+        let mut res = search::Response {
+            total: 50,
+            sample: Some(SampleInfo {
+                rate: 0.0,
+                fraction: 0.25,
+                files: 3,
+            }),
+            ..Default::default()
+        };
+        scale(&req, &mut res);
+        assert_eq!(res.total, 200);
+        assert_eq!(res.sample.as_ref().unwrap().rate, 0.1);
+
+        let mut req = req;
+        req.query.sql = "SELECT host, count(*) AS cnt FROM \"default\" GROUP BY host".to_string();
+        let mut res = search::Response {
+            total: 2,
+            ..Default::default()
+        };
+        scale(&req, &mut res);
+        assert_eq!(res.total, 2);
+        assert_eq!(res.sample.as_ref().unwrap().fraction, 0.1);
+    }
+}
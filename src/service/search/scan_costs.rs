@@ -0,0 +1,316 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! Scan costs per file, to find the files and partitions worth recompacting.
+//! Each querier keeps the costs of its scans in memory until it restarts,
+//! the list adds up the costs of all the queriers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use config::{
+    meta::stream::{FileKey, StreamType},
+    RwHashMap, CONFIG,
+};
+use hashbrown::HashMap;
+use infra::errors::{Error, Result};
+use once_cell::sync::Lazy;
+use proto::cluster_rpc;
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request};
+
+use crate::common::{infra::cluster as infra_cluster, meta::stream::FileScanStats};
+
+/// Sharded so that the scans of different files don't wait for each other
+static FILE_STATS: Lazy<RwHashMap<String, FileStat>> = Lazy::new(Default::default);
+
+static EVICTING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Debug, Default)]
+struct FileStat {
+    searches: u64,
+    load_ms: u64,
+    read_ms: u64,
+    bytes_read: u64,
+    /// original_size / compressed_size
+    ratio: f64,
+}
+
+impl FileStat {
+    fn cost(&self) -> u64 {
+        self.load_ms + self.read_ms
+    }
+}
+
+#[inline]
+fn enabled() -> bool {
+    CONFIG.limit.query_file_stats_max_files > 0
+}
+
+/// Records the time spent downloading a file into the cache
+pub fn record_load(key: &str, took: std::time::Duration) {
+    if !enabled() {
+        return;
+    }
+    FILE_STATS.entry(key.to_string()).or_default().load_ms += took.as_millis() as u64;
+    evict();
+}
+
+/// Records a read of a file by a scan
+pub fn record_read(key: &str, took: std::time::Duration, bytes: usize) {
+    if !enabled() {
+        return;
+    }
+    {
+        let mut stat = FILE_STATS.entry(key.to_string()).or_default();
+        stat.read_ms += took.as_millis() as u64;
+        stat.bytes_read += bytes as u64;
+    }
+    evict();
+}
+
+/// Records the files searched by a query
+pub fn record_scan(files: &[FileKey]) {
+    if !enabled() {
+        return;
+    }
+    for file in files {
+        let mut stat = FILE_STATS.entry(file.key.clone()).or_default();
+        stat.searches += 1;
+        if file.meta.compressed_size > 0 {
+            stat.ratio = file.meta.original_size as f64 / file.meta.compressed_size as f64;
+        }
+    }
+    evict();
+}
+
+/// Drops the cheapest files once there are too many, keeping 80% of the
+/// limit. One scan evicts while the others go on recording.
+fn evict() {
+    let max = CONFIG.limit.query_file_stats_max_files;
+    if FILE_STATS.len() <= max || EVICTING.swap(true, Ordering::Acquire) {
+        return;
+    }
+    let mut costs = FILE_STATS
+        .iter()
+        .map(|v| (v.value().cost(), v.key().clone()))
+        .collect::<Vec<_>>();
+    let evicted = costs.len().saturating_sub(max * 4 / 5);
+    costs.sort_unstable();
+    for (_, key) in costs.into_iter().take(evicted) {
+        FILE_STATS.remove(&key);
+    }
+    EVICTING.store(false, Ordering::Release);
+}
+
+/// The costs of the files of a stream scanned by this querier, or of its
+/// partitions when `by_partition` is set
+pub fn list_local(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    by_partition: bool,
+) -> Vec<FileScanStats> {
+    let prefix = format!("files/{org_id}/{stream_type}/{stream_name}/");
+    let mut items: HashMap<String, FileScanStats> = HashMap::new();
+    for entry in FILE_STATS.iter() {
+        let (key, stat) = (entry.key(), entry.value());
+        let Some(path) = key.strip_prefix(&prefix) else {
+            continue;
+        };
+        let key = if by_partition {
+            match path.rsplit_once('/') {
+                Some((partition, _)) => partition.to_string(),
+                None => continue,
+            }
+        } else {
+            key.to_string()
+        };
+        let item = items.entry(key.clone()).or_insert_with(|| FileScanStats {
+            key,
+            ..Default::default()
+        });
+        item.files += 1;
+        item.searches += stat.searches;
+        item.load_ms += stat.load_ms;
+        item.read_ms += stat.read_ms;
+        item.bytes_read += stat.bytes_read;
+        item.decompressed_bytes += (stat.bytes_read as f64 * stat.ratio) as u64;
+    }
+    items.into_values().collect()
+}
+
+/// Lists the costliest files of a stream, or its partitions when
+/// `by_partition` is set, over all the queriers, the most time consuming
+/// first
+pub async fn list(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    by_partition: bool,
+    size: usize,
+) -> Result<Vec<FileScanStats>> {
+    let mut nodes = infra_cluster::get_cached_online_query_nodes()
+        .await
+        .unwrap_or_default();
+    nodes.dedup_by(|a, b| a.grpc_addr == b.grpc_addr);
+    let req = cluster_rpc::ScanCostsRequest {
+        org_id: org_id.to_string(),
+        stream_type: stream_type.to_string(),
+        stream_name: stream_name.to_string(),
+        by_partition,
+    };
+    let tasks = nodes
+        .into_iter()
+        .map(|node| {
+            let req = req.clone();
+            tokio::task::spawn(async move { list_node(&node.grpc_addr, req).await })
+        })
+        .collect::<Vec<_>>();
+    let mut items = Vec::new();
+    for task in tasks {
+        let node_items = task.await.map_err(|e| Error::Message(e.to_string()))??;
+        items.extend(node_items.into_iter().map(FileScanStats::from));
+    }
+    Ok(merge(items, size))
+}
+
+async fn list_node(
+    node_addr: &str,
+    req: cluster_rpc::ScanCostsRequest,
+) -> Result<Vec<cluster_rpc::ScanCost>> {
+    let token: MetadataValue<_> = infra_cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Error::Message("invalid token".to_string()))?;
+    let channel = Channel::from_shared(node_addr.to_string())
+        .map_err(|e| Error::Message(e.to_string()))?
+        .connect()
+        .await
+        .map_err(|e| {
+            log::error!("scan_costs->grpc: node: {node_addr}, connect err: {e:?}");
+            Error::Message("connect search node error".to_string())
+        })?;
+    let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            Ok(req)
+        },
+    );
+    client = client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .max_decoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024)
+        .max_encoding_message_size(CONFIG.grpc.max_message_size * 1024 * 1024);
+    match client.scan_costs(req).await {
+        Ok(res) => Ok(res.into_inner().items),
+        Err(e) => {
+            log::error!("scan_costs->grpc: node: {node_addr}, err: {e:?}");
+            Err(Error::Message(e.message().to_string()))
+        }
+    }
+}
+
+/// Adds up the costs of the same key from several queriers and keeps the
+/// `size` costliest
+fn merge(items: Vec<FileScanStats>, size: usize) -> Vec<FileScanStats> {
+    let mut merged: HashMap<String, FileScanStats> = HashMap::new();
+    for item in items {
+        match merged.get_mut(&item.key) {
+            None => {
+                merged.insert(item.key.clone(), item);
+            }
+            Some(v) => {
+                // a file scanned by several queriers is still one file
+                v.files = v.files.max(item.files);
+                v.searches += item.searches;
+                v.load_ms += item.load_ms;
+                v.read_ms += item.read_ms;
+                v.bytes_read += item.bytes_read;
+                v.decompressed_bytes += item.decompressed_bytes;
+            }
+        }
+    }
+    let mut items = merged.into_values().collect::<Vec<_>>();
+    items.sort_by(|a, b| {
+        (b.load_ms + b.read_ms)
+            .cmp(&(a.load_ms + a.read_ms))
+            .then(b.decompressed_bytes.cmp(&a.decompressed_bytes))
+            .then(a.key.cmp(&b.key))
+    });
+    items.truncate(size);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use config::meta::stream::FileMeta;
+
+    use super::*;
+
+    #[test]
+    fn test_list_by_partition() {
+        let prefix = "files/file_stats_org/logs/default/2024/01/01/00";
+        let slow = format!("{prefix}/host=a/1.parquet");
+        let fast = format!("{prefix}/host=b/2.parquet");
+        record_scan(&[
+            FileKey {
+                key: slow.clone(),
+                meta: FileMeta {
+                    original_size: 400,
+                    compressed_size: 100,
+                    ..Default::default()
+                },
+                deleted: false,
+            },
+            FileKey {
+                key: fast.clone(),
+                meta: FileMeta::default(),
+                deleted: false,
+            },
+        ]);
+        record_load(&slow, Duration::from_millis(30));
+        record_read(&slow, Duration::from_millis(20), 100);
+        record_read(&fast, Duration::from_millis(5), 10);
+
+        let files = merge(
+            list_local("file_stats_org", StreamType::Logs, "default", false),
+            10,
+        );
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].key, slow);
+        assert_eq!(files[0].searches, 1);
+        assert_eq!(files[0].load_ms, 30);
+        assert_eq!(files[0].decompressed_bytes, 400);
+
+        let partitions = merge(
+            list_local("file_stats_org", StreamType::Logs, "default", true),
+            1,
+        );
+        assert_eq!(partitions.len(), 1);
+        assert_eq!(partitions[0].key, "2024/01/01/00/host=a");
+        assert_eq!(partitions[0].files, 1);
+
+        assert!(list_local("file_stats_org", StreamType::Logs, "other", false).is_empty());
+
+        // the same partition on two queriers
+        let mut other = partitions.clone();
+        other[0].files = 2;
+        let merged = merge([partitions, other].concat(), 10);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].files, 2);
+        assert_eq!(merged[0].searches, 2);
+        assert_eq!(merged[0].load_ms, 60);
+    }
+}
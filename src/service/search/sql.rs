@@ -16,13 +16,14 @@
 use std::{
     collections::HashMap,
     fmt::{Display, Formatter},
+    ops::ControlFlow,
 };
 
 use chrono::Duration;
 use config::{
     meta::{
         sql::{Sql as MetaSql, SqlOperator},
-        stream::{FileKey, StreamPartition, StreamType},
+        stream::{FileKey, StreamPartition, StreamType, VirtualField},
     },
     CONFIG, QUICK_MODEL_FIELDS, SQL_FULL_TEXT_SEARCH_FIELDS,
 };
@@ -30,16 +31,24 @@ use datafusion::arrow::datatypes::{DataType, Schema};
 use hashbrown::HashSet;
 use infra::{
     errors::{Error, ErrorCodes},
-    schema::STREAM_SCHEMAS_FIELDS,
+    schema::{unwrap_stream_settings, STREAM_SCHEMAS_FIELDS},
 };
 use once_cell::sync::Lazy;
 use proto::cluster_rpc;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sqlparser::{
+    ast::{visit_expressions, Expr},
+    dialect::GenericDialect,
+    parser::Parser,
+    tokenizer::Token,
+};
 
 use crate::{
-    common::meta::stream::StreamParams,
-    service::{search::match_source, stream::get_stream_setting_fts_fields},
+    common::meta::{organization::Feature, stream::StreamParams},
+    service::{
+        original::ORIGINAL_COLUMN, search::match_source, stream::get_stream_setting_fts_fields,
+    },
 };
 
 const SQL_DELIMITERS: [u8; 12] = [
@@ -84,6 +93,9 @@ pub struct Sql {
     pub uses_zo_fn: bool,
     pub query_fn: Option<String>,
     pub fts_terms: Vec<String>,
+    /// The decrypt function is registered for the users allowed to read the
+    /// encrypted fields only
+    pub can_decrypt: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -287,48 +299,20 @@ impl Sql {
                 "".to_string()
             };
             if !time_range_sql.is_empty() && meta_time_range_is_empty {
-                match RE_WHERE.captures(origin_sql.as_str()) {
-                    Some(caps) => {
-                        let mut where_str = caps.get(1).unwrap().as_str().to_string();
-                        if !meta.group_by.is_empty() {
-                            where_str = where_str
-                                [0..where_str.to_lowercase().rfind(" group ").unwrap()]
-                                .to_string();
-                        } else if meta.having {
-                            where_str = where_str
-                                [0..where_str.to_lowercase().rfind(" having ").unwrap()]
-                                .to_string();
-                        } else if !meta.order_by.is_empty() {
-                            where_str = where_str
-                                [0..where_str.to_lowercase().rfind(" order ").unwrap()]
-                                .to_string();
-                        } else if meta.limit > 0 {
-                            where_str = where_str
-                                [0..where_str.to_lowercase().rfind(" limit ").unwrap()]
-                                .to_string();
-                        } else if meta.offset > 0 {
-                            where_str = where_str
-                                [0..where_str.to_lowercase().rfind(" offset ").unwrap()]
-                                .to_string();
-                        }
-                        let pos_start = origin_sql.find(where_str.as_str()).unwrap();
-                        let pos_end = pos_start + where_str.len();
-                        origin_sql = format!(
-                            "{}{} AND ({}){}",
-                            &origin_sql[0..pos_start],
-                            time_range_sql,
-                            where_str,
-                            &origin_sql[pos_end..]
-                        );
-                    }
-                    None => {
-                        origin_sql = origin_sql
-                            .replace(" FROM tbl", &format!(" FROM tbl WHERE {time_range_sql}"));
-                    }
-                };
+                origin_sql = add_where_condition(&origin_sql, &meta, &time_range_sql);
             }
         }
 
+        // Hide the records deleted by tombstones until the compactor rewrites them
+        if let Some(cond) = crate::service::compact::tombstones::search_condition(
+            &org_id,
+            stream_type,
+            &meta.source,
+            meta.time_range,
+        ) {
+            origin_sql = add_where_condition(&origin_sql, &meta, &cond);
+        }
+
         // Hack offset limit and sort by for sql
         if meta.limit == 0 {
             meta.offset = req_query.from as usize;
@@ -377,7 +361,7 @@ impl Sql {
         // fetch fts fields
         let mut fts_terms = HashSet::new();
         let fts_fields = get_stream_setting_fts_fields(&schema).unwrap();
-        let match_all_fields = if !fts_fields.is_empty() {
+        let mut match_all_fields = if !fts_fields.is_empty() {
             fts_fields.iter().map(|v| v.to_lowercase()).collect()
         } else {
             SQL_FULL_TEXT_SEARCH_FIELDS
@@ -385,6 +369,8 @@ impl Sql {
                 .map(|v| v.to_string())
                 .collect::<Vec<String>>()
         };
+        // the original of the records is only fetched by its id
+        match_all_fields.retain(|v| v != ORIGINAL_COLUMN);
 
         // Hack for quick_mode
         // replace `select *` to `select f1,f2,f3`
@@ -413,6 +399,60 @@ impl Sql {
                 .to_string();
             // reset meta fields
             meta.fields.extend(fields);
+        } else if schema.field_with_name(ORIGINAL_COLUMN).is_ok()
+            && RE_ONLY_SELECT.is_match(&origin_sql)
+        {
+            // Hack for the original of the records
+            // replace `select *` to the fields without the original, it is only
+            // fetched by its id
+            let fields = schema
+                .fields()
+                .iter()
+                .filter(|f| f.name() != ORIGINAL_COLUMN)
+                .map(|f| f.name().to_string())
+                .collect::<Vec<_>>();
+            let select_fields = "SELECT ".to_string()
+                + &fields
+                    .iter()
+                    .map(|f| format!("\"{f}\""))
+                    .collect::<Vec<_>>()
+                    .join(",");
+            origin_sql = RE_ONLY_SELECT
+                .replace(origin_sql.as_str(), &select_fields)
+                .to_string();
+            rewrite_sql = RE_ONLY_SELECT
+                .replace(rewrite_sql.as_str(), &select_fields)
+                .to_string();
+            meta.fields.extend(fields);
+        }
+
+        // Hack for dot path fields
+        let (sql, dot_fields) = rewrite_dot_path_fields(&origin_sql, &schema);
+        if !dot_fields.is_empty() {
+            origin_sql = sql;
+            meta.fields.extend(dot_fields);
+        }
+
+        // Hack for virtual fields
+        // expand the expressions defined in stream settings, real columns take precedence
+        let virtual_fields = unwrap_stream_settings(&schema)
+            .map(|s| s.virtual_fields)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|f| schema.field_with_name(&f.name).is_err())
+            .filter_map(|f| match parse_virtual_field(&f) {
+                Ok(f) => Some(f),
+                Err(e) => {
+                    log::warn!("skip the stream virtual field: {e}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if !virtual_fields.is_empty() {
+            origin_sql = rewrite_virtual_fields(&origin_sql, &virtual_fields);
+            for field in virtual_fields.iter() {
+                meta.fields.extend(virtual_field_columns(&field.expr));
+            }
         }
 
         // get sql where tokens
@@ -559,12 +599,16 @@ impl Sql {
                 },
                 None => generate_histogram_interval(meta.time_range, 0),
             };
-            origin_sql = origin_sql.replace(
-                cap.get(0).unwrap().as_str(),
-                &format!(
+            // buckets follow the local day boundaries when a zone is given
+            let bucket = match attrs.get(2).filter(|v| !v.is_empty()) {
+                Some(zone) => format!(
+                    "to_timestamp_micros(date_trunc_tz('{interval}', \"{field}\", '{zone}'))",
+                ),
+                None => format!(
                     "date_bin(interval '{interval}', to_timestamp_micros(\"{field}\"), to_timestamp('2001-01-01T00:00:00'))",
-                )
-            );
+                ),
+            };
+            origin_sql = origin_sql.replace(cap.get(0).unwrap().as_str(), &bucket);
         }
 
         // pickup where
@@ -603,6 +647,9 @@ impl Sql {
             if let Some(caps) = RE_ONLY_FROM.captures(&sql) {
                 sql = sql.replace(&caps[0].to_string(), " FROM tbl ");
             }
+            if !virtual_fields.is_empty() {
+                sql = rewrite_virtual_fields(&sql, &virtual_fields);
+            }
             if !where_str.is_empty() {
                 match RE_ONLY_WHERE.captures(&sql) {
                     Some(caps) => {
@@ -675,6 +722,17 @@ impl Sql {
                         "Please use alias for function used in query.".to_string(),
                     )));
                 }
+                if (!used_fns.is_empty() || !req_query.query_fn.is_empty())
+                    && !crate::service::organization::is_feature_enabled(
+                        &org_id,
+                        Feature::QueryFunctions,
+                    )
+                {
+                    return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+                        "feature [{}] is not enabled for the organization",
+                        Feature::QueryFunctions
+                    ))));
+                }
             }
             Err(e) => {
                 log::error!("parse sql error: {}, sql: {}", e, origin_sql);
@@ -704,6 +762,7 @@ impl Sql {
             uses_zo_fn: req_query.uses_zo_fn,
             query_fn,
             fts_terms: fts_terms.into_iter().collect(),
+            can_decrypt: req.can_decrypt,
         })
     }
 
@@ -741,6 +800,42 @@ impl Sql {
     }
 }
 
+/// Adds a condition to the where clause of the sql, creates the where clause
+/// if the sql has none.
+fn add_where_condition(origin_sql: &str, meta: &MetaSql, condition: &str) -> String {
+    match RE_WHERE.captures(origin_sql) {
+        Some(caps) => {
+            let mut where_str = caps.get(1).unwrap().as_str().to_string();
+            if !meta.group_by.is_empty() {
+                where_str =
+                    where_str[0..where_str.to_lowercase().rfind(" group ").unwrap()].to_string();
+            } else if meta.having {
+                where_str =
+                    where_str[0..where_str.to_lowercase().rfind(" having ").unwrap()].to_string();
+            } else if !meta.order_by.is_empty() {
+                where_str =
+                    where_str[0..where_str.to_lowercase().rfind(" order ").unwrap()].to_string();
+            } else if meta.limit > 0 {
+                where_str =
+                    where_str[0..where_str.to_lowercase().rfind(" limit ").unwrap()].to_string();
+            } else if meta.offset > 0 {
+                where_str =
+                    where_str[0..where_str.to_lowercase().rfind(" offset ").unwrap()].to_string();
+            }
+            let pos_start = origin_sql.find(where_str.as_str()).unwrap();
+            let pos_end = pos_start + where_str.len();
+            format!(
+                "{}{} AND ({}){}",
+                &origin_sql[0..pos_start],
+                condition,
+                where_str,
+                &origin_sql[pos_end..]
+            )
+        }
+        None => origin_sql.replace(" FROM tbl", &format!(" FROM tbl WHERE {condition}")),
+    }
+}
+
 pub fn generate_filter_from_quick_text(
     data: &[(String, String, SqlOperator)],
 ) -> Vec<(&str, Vec<String>)> {
@@ -775,6 +870,10 @@ pub(crate) fn generate_quick_mode_fields(
             .map(|f| f.name().to_string())
             .collect(),
     };
+    let schema_fields = schema_fields
+        .into_iter()
+        .filter(|f| f != ORIGINAL_COLUMN)
+        .collect::<Vec<_>>();
     let mut fields = match strategy.as_str() {
         "last" => {
             let skip = std::cmp::max(0, schema_fields.len() - CONFIG.limit.quick_mode_num_fields);
@@ -820,7 +919,7 @@ pub(crate) fn generate_quick_mode_fields(
     fields
 }
 
-fn generate_histogram_interval(time_range: Option<(i64, i64)>, num: u16) -> String {
+pub(crate) fn generate_histogram_interval(time_range: Option<(i64, i64)>, num: u16) -> String {
     if time_range.is_none() || time_range.unwrap().eq(&(0, 0)) {
         return "1 hour".to_string();
     }
@@ -976,6 +1075,264 @@ fn split_sql_token(text: &str) -> Vec<String> {
     tokens
 }
 
+const SQL_CLAUSES: [&str; 8] = [
+    "select", "from", "where", "group", "having", "order", "limit", "offset",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SqlTokenKind {
+    Word,
+    Quoted,
+    Literal,
+    Space,
+    Punct,
+}
+
+fn split_sql_words(text: &str) -> Vec<(SqlTokenKind, &str)> {
+    let mut tokens = Vec::new();
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let end_of = |i: usize| chars.get(i).map(|(p, _)| *p).unwrap_or(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let kind = if c == '\'' || c == '"' {
+            i += 1;
+            while i < chars.len() {
+                if chars[i].1 == c {
+                    // '' is an escaped quote inside a string literal
+                    if chars.get(i + 1).is_some_and(|(_, n)| *n == c) {
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+            if c == '"' {
+                SqlTokenKind::Quoted
+            } else {
+                SqlTokenKind::Literal
+            }
+        } else if c.is_whitespace() {
+            while i < chars.len() && chars[i].1.is_whitespace() {
+                i += 1;
+            }
+            SqlTokenKind::Space
+        } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '@' {
+            while i < chars.len()
+                && (chars[i].1.is_alphanumeric() || ['_', '.', '@'].contains(&chars[i].1))
+            {
+                i += 1;
+            }
+            SqlTokenKind::Word
+        } else {
+            i += 1;
+            SqlTokenKind::Punct
+        };
+        tokens.push((kind, &text[start..end_of(i)]));
+    }
+    tokens
+}
+
+fn sql_word_name<'a>(token: &(SqlTokenKind, &'a str)) -> Option<&'a str> {
+    match token.0 {
+        SqlTokenKind::Word => Some(token.1),
+        SqlTokenKind::Quoted => Some(token.1.trim_matches('"')),
+        _ => None,
+    }
+}
+
+/// Parses the expression of a virtual field as a single expression without
+/// subqueries. The queries get the expression as written back by the parser,
+/// so nothing else from the stored text ends up in them.
+pub(crate) fn parse_virtual_field(field: &VirtualField) -> Result<VirtualField, String> {
+    if field.name.is_empty()
+        || field.name.starts_with(|c: char| c.is_ascii_digit())
+        || !field
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("virtual field name [{}] is invalid", field.name));
+    }
+    let invalid = |e: String| format!("virtual field [{}] expression is invalid: {e}", field.name);
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect)
+        .try_with_sql(&field.expr)
+        .map_err(|e| invalid(e.to_string()))?;
+    let expr = parser.parse_expr().map_err(|e| invalid(e.to_string()))?;
+    if parser.peek_token().token != Token::EOF {
+        return Err(invalid("it should be a single expression".to_string()));
+    }
+    let subquery = visit_expressions(&expr, |expr| match expr {
+        Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } => ControlFlow::Break(()),
+        _ => ControlFlow::Continue(()),
+    });
+    if subquery.is_break() {
+        return Err(invalid("subqueries are not allowed".to_string()));
+    }
+    Ok(VirtualField {
+        name: field.name.clone(),
+        expr: expr.to_string(),
+    })
+}
+
+/// returns the columns referenced by a virtual field expression
+pub(crate) fn virtual_field_columns(expr: &str) -> Vec<String> {
+    let tokens = split_sql_words(expr);
+    let mut columns = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let Some(name) = sql_word_name(token) else {
+            continue;
+        };
+        let is_fn = tokens[i + 1..]
+            .iter()
+            .find(|t| t.0 != SqlTokenKind::Space)
+            .is_some_and(|t| t.1 == "(");
+        if !is_fn && !name.starts_with(|c: char| c.is_ascii_digit()) {
+            columns.push(name.to_string());
+        }
+    }
+    columns
+}
+
+/// returns the lowercased names of the functions called in a query
+pub(crate) fn sql_function_names(sql: &str) -> Vec<String> {
+    let tokens = split_sql_words(sql);
+    let mut names = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.0 != SqlTokenKind::Word {
+            continue;
+        }
+        let is_fn = tokens[i + 1..]
+            .iter()
+            .find(|t| t.0 != SqlTokenKind::Space)
+            .is_some_and(|t| t.1 == "(");
+        if is_fn {
+            names.push(token.1.to_lowercase());
+        }
+    }
+    names
+}
+
+/// Expand the stream virtual fields in a query:
+/// 1. `select *` gets every virtual field appended as a column
+/// 2. a virtual field selected as a column becomes `(expr) AS "name"`
+/// 3. in `group by` and `order by` a selected virtual field refers to the alias
+/// 4. any other reference is replaced by `(expr)`
+fn rewrite_virtual_fields(sql: &str, fields: &[VirtualField]) -> String {
+    let tokens = split_sql_words(sql);
+    let solid = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.0 != SqlTokenKind::Space)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let field = |name: &str| fields.iter().find(|f| f.name == name);
+    let keyword = |pos: usize, kw: &str| {
+        let t = &tokens[solid[pos]];
+        t.0 == SqlTokenKind::Word && t.1.eq_ignore_ascii_case(kw)
+    };
+
+    // find the top level clause of every token and the virtual fields selected as columns
+    let mut clauses = vec![""; solid.len()];
+    let mut depth = 0;
+    let mut clause = "";
+    for (pos, &i) in solid.iter().enumerate() {
+        match tokens[i].1 {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 && tokens[i].0 == SqlTokenKind::Word {
+            if let Some(kw) = SQL_CLAUSES
+                .iter()
+                .find(|kw| kw.eq_ignore_ascii_case(tokens[i].1))
+            {
+                clause = kw;
+            }
+        }
+        clauses[pos] = if depth == 0 { clause } else { "" };
+    }
+    let is_select_item = |pos: usize| {
+        clauses[pos] == "select"
+            && pos > 0
+            && (tokens[solid[pos - 1]].1 == ","
+                || keyword(pos - 1, "select")
+                || keyword(pos - 1, "distinct"))
+            && pos + 1 < solid.len()
+            && (tokens[solid[pos + 1]].1 == "," || keyword(pos + 1, "from"))
+    };
+    let mut selected = HashSet::new();
+    for pos in 0..solid.len() {
+        if let Some(name) = sql_word_name(&tokens[solid[pos]]) {
+            if is_select_item(pos) && field(name).is_some() {
+                selected.insert(name);
+            }
+        }
+    }
+
+    let mut result = String::with_capacity(sql.len());
+    let mut pos = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if solid.get(pos) != Some(&i) {
+            result.push_str(token.1);
+            continue;
+        }
+        let cur = pos;
+        pos += 1;
+        if token.1 == "*" && is_select_item(cur) {
+            result.push('*');
+            for f in fields
+                .iter()
+                .filter(|f| !selected.contains(f.name.as_str()))
+            {
+                result.push_str(&format!(", ({}) AS \"{}\"", f.expr, f.name));
+            }
+            continue;
+        }
+        let Some(f) = sql_word_name(token).and_then(field) else {
+            result.push_str(token.1);
+            continue;
+        };
+        let is_fn = solid.get(cur + 1).is_some_and(|&n| tokens[n].1 == "(");
+        let is_alias = cur > 0 && keyword(cur - 1, "as");
+        let is_alias_ref =
+            ["group", "order"].contains(&clauses[cur]) && selected.contains(f.name.as_str());
+        if is_fn || is_alias || is_alias_ref {
+            result.push_str(token.1);
+        } else if is_select_item(cur) {
+            result.push_str(&format!("({}) AS \"{}\"", f.expr, f.name));
+        } else {
+            result.push_str(&format!("({})", f.expr));
+        }
+    }
+    result
+}
+
+/// Map dot paths like `kubernetes.labels.app` or `"kubernetes.labels.app"` to the flattened
+/// column `kubernetes_labels_app`. Only paths whose flattened name is a stream field are
+/// rewritten, returns the rewritten sql and the columns it resolved to.
+fn rewrite_dot_path_fields(sql: &str, schema: &Schema) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(sql.len());
+    let mut columns = Vec::new();
+    for token in split_sql_words(sql) {
+        let column = sql_word_name(&token)
+            .filter(|name| name.contains('.') && !name.starts_with("tbl."))
+            .map(|name| name.replace('.', "_"))
+            .filter(|column| schema.field_with_name(column).is_ok());
+        match column {
+            Some(column) => {
+                result.push_str(&format!("\"{column}\""));
+                columns.push(column);
+            }
+            None => result.push_str(token.1),
+        }
+    }
+    (result, columns)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1000,6 +1357,10 @@ mod tests {
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
+            nested: false,
+            sample: 0.0,
+            partial: false,
+            estimate_total: false,
         };
 
         let req: config::meta::search::Request = config::meta::search::Request {
@@ -1110,6 +1471,10 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                nested: false,
+                sample: 0.0,
+                partial: false,
+                estimate_total: false,
             };
             let req = config::meta::search::Request {
                 query: query.clone(),
@@ -1201,6 +1566,12 @@ mod tests {
                 0,
                 (0, 0),
             ),
+            (
+                "select histogram(_timestamp, '1 day', 'Europe/Berlin') AS zo_sql_key, count(*) AS zo_sql_num from table1 GROUP BY zo_sql_key ORDER BY zo_sql_key",
+                true,
+                0,
+                (0, 0),
+            ),
             (
                 "select DISTINCT field1, field2, field3 FROM table1",
                 true,
@@ -1232,6 +1603,10 @@ mod tests {
                 uses_zo_fn: false,
                 query_fn: None,
                 skip_wal: false,
+                nested: false,
+                sample: 0.0,
+                partial: false,
+                estimate_total: false,
             };
             let req = config::meta::search::Request {
                 query: query.clone(),
@@ -1265,4 +1640,75 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_rewrite_virtual_fields() {
+        let fields = vec![
+            VirtualField {
+                name: "duration_ms".to_string(),
+                expr: "duration_ns / 1e6".to_string(),
+            },
+            VirtualField {
+                name: "env".to_string(),
+                expr: "coalesce(environment, 'prod')".to_string(),
+            },
+        ];
+        let cases = [
+            (
+                "SELECT * FROM tbl WHERE env = 'prod' LIMIT 10",
+                "SELECT *, (duration_ns / 1e6) AS \"duration_ms\", (coalesce(environment, 'prod')) AS \"env\" FROM tbl WHERE (coalesce(environment, 'prod')) = 'prod' LIMIT 10",
+            ),
+            (
+                "SELECT _timestamp, duration_ms FROM tbl ORDER BY duration_ms DESC",
+                "SELECT _timestamp, (duration_ns / 1e6) AS \"duration_ms\" FROM tbl ORDER BY duration_ms DESC",
+            ),
+            (
+                "SELECT env, avg(duration_ms) AS avg FROM tbl GROUP BY env",
+                "SELECT (coalesce(environment, 'prod')) AS \"env\", avg((duration_ns / 1e6)) AS avg FROM tbl GROUP BY env",
+            ),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(rewrite_virtual_fields(sql, &fields), expected);
+        }
+        assert_eq!(
+            virtual_field_columns("coalesce(environment, 'prod')"),
+            vec!["environment"]
+        );
+    }
+
+    #[test]
+    fn test_parse_virtual_field() {
+        let field = |name: &str, expr: &str| VirtualField {
+            name: name.to_string(),
+            expr: expr.to_string(),
+        };
+        assert_eq!(
+            parse_virtual_field(&field("env", "coalesce(environment,'prod') -- default"))
+                .unwrap()
+                .expr,
+            "coalesce(environment, 'prod')"
+        );
+        assert!(parse_virtual_field(&field("x", "1 FROM other_stream --")).is_err());
+        assert!(parse_virtual_field(&field("x", "(SELECT max(a) FROM other_stream)")).is_err());
+        assert!(parse_virtual_field(&field("x", "a IN (SELECT a FROM other_stream)")).is_err());
+        assert!(parse_virtual_field(&field("x\" FROM other_stream --", "1")).is_err());
+        assert!(parse_virtual_field(&field("x", "")).is_err());
+    }
+
+    #[test]
+    fn test_rewrite_dot_path_fields() {
+        let schema = Schema::new(vec![
+            datafusion::arrow::datatypes::Field::new("kubernetes_labels_app", DataType::Utf8, true),
+            datafusion::arrow::datatypes::Field::new("message", DataType::Utf8, true),
+        ]);
+        let (sql, columns) = rewrite_dot_path_fields(
+            "SELECT kubernetes.labels.app, message FROM tbl WHERE \"kubernetes.labels.app\" = 'a.b' AND tbl.message = 'x'",
+            &schema,
+        );
+        assert_eq!(
+            sql,
+            "SELECT \"kubernetes_labels_app\", message FROM tbl WHERE \"kubernetes_labels_app\" = 'a.b' AND tbl.message = 'x'"
+        );
+        assert_eq!(columns.len(), 2);
+    }
 }
@@ -0,0 +1,352 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use actix_web::web::Bytes;
+use config::{
+    meta::{
+        search,
+        sql::Sql as MetaSql,
+        stream::StreamType,
+        usage::{RequestStats, UsageType},
+    },
+    utils::json,
+    CONFIG,
+};
+use infra::errors::Error;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::{dedup, transaction};
+use crate::service::usage::report_request_usage_stats;
+
+/// Pages serialized ahead of the client, the search waits for a slow client
+/// instead of piling up its hits
+const CHANNEL_SIZE: usize = 2;
+
+pub(super) static RE_AGGREGATE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(\bselect\s+distinct\b|\b(count|sum|avg|min|max|median|array_agg|approx_\w+|percentile\w*|stddev\w*|var_\w+)\s*\()").unwrap()
+});
+
+/// How the hits of a query are searched
+#[derive(Debug, PartialEq)]
+pub(super) enum Plan {
+    /// All at once, the hits of aggregations and limited queries can't be
+    /// split
+    Whole,
+    /// At once over the whole time range and sent page by page, the hits
+    /// are ordered by another field than the timestamp
+    Pages,
+    /// Page by page over consecutive time ranges, each page starting at the
+    /// timestamp of the last hit sent
+    Partitions { ascending: bool },
+}
+
+pub(super) fn plan(sql: &str) -> Plan {
+    let Ok(meta) = MetaSql::new(sql) else {
+        return Plan::Whole;
+    };
+    if meta.limit > 0
+        || !meta.group_by.is_empty()
+        || meta.having
+        || RE_AGGREGATE.is_match(sql)
+        || dedup::parse(sql).is_some()
+        || transaction::parse(sql).is_some()
+    {
+        return Plan::Whole;
+    }
+    match meta.order_by.first() {
+        None => Plan::Partitions { ascending: false },
+        Some((field, desc)) if field == &CONFIG.common.column_timestamp => {
+            Plan::Partitions { ascending: !desc }
+        }
+        Some(_) => Plan::Pages,
+    }
+}
+
+/// Streams the hits of the search as NDJSON, one hit per line, so that the
+/// client reads them while the next ones are searched. The hits are searched
+/// `query_stream_page_size` at a time over the time partitions of the query
+/// and `size` limits the hits streamed, all of them when zero. Aggregations,
+/// queries with a LIMIT and queries ordered by another field are searched at
+/// once, up to `query_stream_page_size` hits when `size` is zero followed by
+/// an error line when there are more. An error ends the stream with a line
+/// holding it.
+pub fn search(
+    trace_id: String,
+    org_id: String,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    req: search::Request,
+) -> ReceiverStream<Bytes> {
+    let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+    tokio::task::spawn(async move {
+        let start = std::time::Instant::now();
+        let sql = req.query.sql.clone();
+        let (start_time, end_time) = (req.query.start_time, req.query.end_time);
+        match send_hits(&tx, &trace_id, &org_id, stream_type, user_id.clone(), req).await {
+            Ok((records, scan_size)) => {
+                let stream_name = MetaSql::new(&sql)
+                    .map(|meta| meta.source)
+                    .unwrap_or_default();
+                let req_stats = RequestStats {
+                    records: records as i64,
+                    response_time: start.elapsed().as_secs_f64(),
+                    size: scan_size as f64,
+                    request_body: Some(sql),
+                    user_email: user_id,
+                    min_ts: Some(start_time),
+                    max_ts: Some(end_time),
+                    ..Default::default()
+                };
+                report_request_usage_stats(
+                    req_stats,
+                    &org_id,
+                    &stream_name,
+                    stream_type,
+                    UsageType::Search,
+                    0,
+                )
+                .await;
+            }
+            Err(e) => {
+                log::error!("[trace_id {trace_id}] streaming search error: {e}");
+                let line = json::json!({ "trace_id": trace_id, "error": e.to_string() });
+                _ = tx.send(ndjson(&[line])).await;
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Returns the hits sent and the size scanned, it stops early when the
+/// client goes away
+async fn send_hits(
+    tx: &mpsc::Sender<Bytes>,
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    mut req: search::Request,
+) -> Result<(usize, usize), Error> {
+    let limit = req.query.size;
+    let page_size = CONFIG.limit.query_stream_page_size;
+    let ascending = match plan(&req.query.sql) {
+        Plan::Whole | Plan::Pages => {
+            // one more hit tells whether the hits are cut
+            let max = if limit > 0 { limit } else { page_size };
+            req.query.size = if limit > 0 { limit } else { page_size + 1 };
+            let res = super::search(trace_id, org_id, stream_type, user_id, &req).await?;
+            let hits = &res.hits[..res.hits.len().min(max)];
+            for page in hits.chunks(page_size) {
+                if tx.send(ndjson(page)).await.is_err() {
+                    // the client went away
+                    return Ok((hits.len(), res.scan_size));
+                }
+            }
+            if res.hits.len() > max {
+                let line = json::json!({
+                    "trace_id": trace_id,
+                    "error": format!("the hits are cut at {max}, set a size or a LIMIT to get more"),
+                });
+                _ = tx.send(ndjson(&[line])).await;
+            }
+            return Ok((hits.len(), res.scan_size));
+        }
+        Plan::Partitions { ascending } => ascending,
+    };
+    let part_req = search::SearchPartitionRequest {
+        sql: req.query.sql.clone(),
+        sql_mode: req.query.sql_mode.clone(),
+        start_time: req.query.start_time,
+        end_time: req.query.end_time,
+    };
+    let mut partitions = super::search_partition(trace_id, org_id, stream_type, &part_req)
+        .await?
+        .partitions;
+    // the partitions are the newest first
+    if ascending {
+        partitions.reverse();
+    }
+    // without an ORDER BY, only the searches in context mode sort the hits
+    let sorted = !req.query.sql_mode.eq_ignore_ascii_case("full")
+        || MetaSql::new(&req.query.sql).is_ok_and(|meta| !meta.order_by.is_empty());
+
+    let (mut sent, mut scan_size) = (0, 0);
+    for [start_time, end_time] in partitions {
+        req.query.start_time = start_time;
+        req.query.end_time = end_time;
+        req.query.from = 0;
+        loop {
+            let size = if limit > 0 {
+                page_size.min(limit - sent)
+            } else {
+                page_size
+            };
+            if size == 0 {
+                return Ok((sent, scan_size));
+            }
+            req.query.size = size;
+            let res = super::search(trace_id, org_id, stream_type, user_id.clone(), &req).await?;
+            scan_size += res.scan_size;
+            if !res.hits.is_empty() && tx.send(ndjson(&res.hits)).await.is_err() {
+                // the client went away
+                return Ok((sent, scan_size));
+            }
+            sent += res.hits.len();
+            if res.hits.len() < size {
+                break;
+            }
+            match next_page(&res.hits, ascending) {
+                Some(next) if sorted => next.apply(&mut req.query, ascending),
+                _ => req.query.from += size,
+            }
+        }
+    }
+    Ok((sent, scan_size))
+}
+
+/// Where the page following the hits starts: at the timestamp of the last
+/// hit, skipping the hits of that timestamp already sent. The pages don't
+/// shift when records arrive and each search skips only a few hits.
+#[derive(Debug, PartialEq)]
+struct NextPage {
+    timestamp: i64,
+    skip: usize,
+}
+
+fn next_page(hits: &[json::Value], ascending: bool) -> Option<NextPage> {
+    let ts = |hit: &json::Value| {
+        hit.get(&CONFIG.common.column_timestamp)
+            .and_then(|v| v.as_i64())
+    };
+    let first = ts(hits.first()?)?;
+    let timestamp = ts(hits.last()?)?;
+    // the hits must follow the order of the pages
+    if (ascending && first > timestamp) || (!ascending && first < timestamp) {
+        return None;
+    }
+    let skip = hits
+        .iter()
+        .rev()
+        .take_while(|hit| ts(hit) == Some(timestamp))
+        .count();
+    Some(NextPage { timestamp, skip })
+}
+
+impl NextPage {
+    fn apply(self, query: &mut search::Query, ascending: bool) {
+        // the end of the time range is exclusive
+        let (bound, value) = if ascending {
+            (&mut query.start_time, self.timestamp)
+        } else {
+            (&mut query.end_time, self.timestamp + 1)
+        };
+        if *bound == value {
+            // the whole page had the timestamp of the previous one
+            query.from += self.skip;
+        } else {
+            *bound = value;
+            query.from = self.skip;
+        }
+    }
+}
+
+fn ndjson(hits: &[json::Value]) -> Bytes {
+    let mut buf = Vec::new();
+    for hit in hits {
+        buf.extend(json::to_vec(hit).unwrap_or_default());
+        buf.push(b'\n');
+    }
+    Bytes::from(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        assert_eq!(
+            plan("SELECT * FROM t WHERE code = 500"),
+            Plan::Partitions { ascending: false }
+        );
+        assert_eq!(
+            plan("SELECT * FROM t ORDER BY _timestamp ASC"),
+            Plan::Partitions { ascending: true }
+        );
+        assert_eq!(plan("SELECT * FROM t ORDER BY code DESC"), Plan::Pages);
+        assert_eq!(plan("SELECT * FROM t LIMIT 10"), Plan::Whole);
+        assert_eq!(plan("SELECT COUNT(*) AS n FROM t"), Plan::Whole);
+        assert_eq!(plan("SELECT DISTINCT code FROM t"), Plan::Whole);
+        assert_eq!(
+            plan("SELECT code, COUNT(*) AS n FROM t GROUP BY code"),
+            Plan::Whole
+        );
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let hits = vec![json::json!({"a": 1}), json::json!({"b": "x"})];
+        assert_eq!(ndjson(&hits), Bytes::from("{\"a\":1}\n{\"b\":\"x\"}\n"));
+    }
+
+    #[test]
+    fn test_next_page() {
+        let col = &CONFIG.common.column_timestamp;
+        let hits = |ts: &[i64]| -> Vec<json::Value> {
+            ts.iter()
+                .map(|t| {
+                    let mut hit = json::Map::new();
+                    hit.insert(col.to_string(), json::json!(t));
+                    json::Value::Object(hit)
+                })
+                .collect()
+        };
+        assert_eq!(
+            next_page(&hits(&[50, 40, 30, 30]), false),
+            Some(NextPage {
+                timestamp: 30,
+                skip: 2
+            })
+        );
+        assert_eq!(
+            next_page(&hits(&[10, 20, 20]), true),
+            Some(NextPage {
+                timestamp: 20,
+                skip: 2
+            })
+        );
+        assert_eq!(next_page(&hits(&[10, 20]), false), None);
+        assert_eq!(next_page(&[json::json!({"a": 1})], false), None);
+
+        let mut query = search::Query {
+            start_time: 0,
+            end_time: 100,
+            from: 0,
+            ..Default::default()
+        };
+        next_page(&hits(&[90, 30, 30]), false)
+            .unwrap()
+            .apply(&mut query, false);
+        assert_eq!((query.end_time, query.from), (31, 2));
+        // a page of the same timestamp skips it further
+        next_page(&hits(&[30, 30, 30]), false)
+            .unwrap()
+            .apply(&mut query, false);
+        assert_eq!((query.end_time, query.from), (31, 5));
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// transaction(field) groups the events sharing a correlation field into one
+// row per group with its time span, event count and error events
+
+use config::{
+    meta::{sql::Sql as MetaSql, stream::StreamType},
+    utils::json,
+    CONFIG, SQL_FULL_TEXT_SEARCH_FIELDS,
+};
+use datafusion::arrow::datatypes::DataType;
+use infra::errors::{Error, ErrorCodes};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::service::stream::get_stream_setting_fts_fields;
+
+static RE_TRANSACTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\btransaction\s*\(\s*"?([\w.@-]+)"?\s*(?:,\s*'([^']*)'\s*)?\)"#).unwrap()
+});
+
+const DEFAULT_ERROR_MARKER: &str = "error";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub field: String,
+    /// Text marking the error events, searched case insensitively in the full
+    /// text search fields
+    pub error_marker: String,
+}
+
+/// Takes the transaction operator out of the sql, the operator is replaced by
+/// `true` so that the conditions around it are kept as they are.
+pub fn parse(sql: &str) -> Option<(String, Transaction)> {
+    let cap = RE_TRANSACTION.captures(sql)?;
+    let transaction = Transaction {
+        field: cap[1].to_string(),
+        error_marker: cap
+            .get(2)
+            .map(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_ERROR_MARKER)
+            .to_string(),
+    };
+    let sql = sql.replacen(&cap[0], "true", 1);
+    Some((sql, transaction))
+}
+
+/// Rewrites the sql into the aggregation of its events per transaction
+pub async fn rewrite_sql(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: &str,
+    transaction: &Transaction,
+) -> Result<String, Error> {
+    let meta = MetaSql::new(sql)
+        .map_err(|e| Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e.to_string())))?;
+    if !meta.group_by.is_empty() {
+        return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(
+            "transaction can't be used with group by".to_string(),
+        )));
+    }
+    let schema = infra::schema::get(org_id, &meta.source, stream_type)
+        .await
+        .map_err(|e| Error::Message(e.to_string()))?;
+    let fts_fields = get_stream_setting_fts_fields(&schema).unwrap_or_default();
+    let fts_fields = if fts_fields.is_empty() {
+        SQL_FULL_TEXT_SEARCH_FIELDS
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+    } else {
+        fts_fields.iter().map(|v| v.to_lowercase()).collect()
+    };
+    let marker_fields = schema
+        .fields()
+        .iter()
+        .filter(|f| {
+            f.data_type() == &DataType::Utf8 && fts_fields.contains(&f.name().to_lowercase())
+        })
+        .map(|f| f.name().to_string())
+        .collect::<Vec<_>>();
+    Ok(build_sql(
+        &meta.source,
+        meta.selection.as_ref().map(|v| v.to_string()),
+        transaction,
+        &marker_fields,
+    ))
+}
+
+fn build_sql(
+    stream_name: &str,
+    selection: Option<String>,
+    transaction: &Transaction,
+    marker_fields: &[String],
+) -> String {
+    let ts = &CONFIG.common.column_timestamp;
+    let marker = transaction.error_marker.replace('\'', "''");
+    let is_error = if marker_fields.is_empty() {
+        "false".to_string()
+    } else {
+        marker_fields
+            .iter()
+            .map(|f| format!("\"{f}\" ILIKE '%{marker}%'"))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    };
+    let selection = selection.map(|v| format!(" WHERE {v}")).unwrap_or_default();
+    format!(
+        "SELECT \"{field}\", MIN(\"{ts}\") AS first_time, MAX(\"{ts}\") AS last_time, COUNT(*) AS events, SUM(CASE WHEN {is_error} THEN 1 ELSE 0 END) AS errors FROM \"{stream_name}\"{selection} GROUP BY \"{field}\" ORDER BY first_time DESC",
+        field = transaction.field,
+    )
+}
+
+/// Adds the duration of the transactions and whether they had error events
+pub fn finish(hits: &mut [json::Value]) {
+    for hit in hits.iter_mut() {
+        let Some(hit) = hit.as_object_mut() else {
+            continue;
+        };
+        let first = hit.get("first_time").and_then(|v| v.as_i64()).unwrap_or(0);
+        let last = hit.get("last_time").and_then(|v| v.as_i64()).unwrap_or(0);
+        let errors = hit.get("errors").and_then(|v| v.as_i64()).unwrap_or(0);
+        hit.insert("duration".to_string(), json::Value::from(last - first));
+        hit.insert("has_error".to_string(), json::Value::from(errors > 0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction() {
+        let (sql, transaction) =
+            parse("SELECT * FROM \"default\" WHERE transaction(request_id) AND code >= 500")
+                .unwrap();
+        assert_eq!(sql, "SELECT * FROM \"default\" WHERE true AND code >= 500");
+        assert_eq!(transaction.field, "request_id");
+        assert_eq!(transaction.error_marker, "error");
+        let (_, transaction) =
+            parse("SELECT * FROM \"default\" WHERE transaction(\"trace_id\", 'fail')").unwrap();
+        assert_eq!(transaction.field, "trace_id");
+        assert_eq!(transaction.error_marker, "fail");
+
+        let sql = build_sql(
+            "default",
+            Some("code >= 500".to_string()),
+            &transaction,
+            &["log".to_string()],
+        );
+        assert!(sql.starts_with("SELECT \"trace_id\", MIN("));
+        assert!(sql.contains("SUM(CASE WHEN \"log\" ILIKE '%fail%' THEN 1 ELSE 0 END) AS errors"));
+        assert!(sql.ends_with(
+            "FROM \"default\" WHERE code >= 500 GROUP BY \"trace_id\" ORDER BY first_time DESC"
+        ));
+
+        let mut hits = vec![json::json!({"first_time": 10, "last_time": 25, "errors": 2})];
+        finish(&mut hits);
+        assert_eq!(hits[0]["duration"], 15);
+        assert_eq!(hits[0]["has_error"], true);
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Search templates are queries stored by name, applications invoke them
+//! with parameters instead of embedding the SQL, so the queries can be
+//! optimized later without changing the applications.
+
+use std::collections::{HashMap, HashSet};
+
+use config::{
+    meta::{search, stream::StreamType},
+    utils::json::Value,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    common::meta::search_templates::{
+        SearchTemplate, SearchTemplateRequest, TemplateParam, TemplateParamType,
+    },
+    service::{db, error::ServiceError},
+};
+
+static RE_PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").unwrap());
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut template: SearchTemplate,
+    create: bool,
+) -> Result<(), ServiceError> {
+    if !name.is_empty() {
+        template.name = name.to_string();
+    }
+    template.name = template.name.trim().to_string();
+    validate(&template).map_err(ServiceError::bad_request)?;
+
+    match db::search_templates::get(org_id, &template.name).await {
+        Ok(_) if create => {
+            return Err(ServiceError::bad_request("Search template already exists"));
+        }
+        Err(_) if !create => {
+            return Err(ServiceError::not_found("Search template not found"));
+        }
+        _ => {}
+    }
+    db::search_templates::set(org_id, &template)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<SearchTemplate, ServiceError> {
+    db::search_templates::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("Search template not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<SearchTemplate>, ServiceError> {
+    db::search_templates::list(org_id)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    get(org_id, name).await?;
+    db::search_templates::delete(org_id, name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// The search request of an invocation of the template, with the stream type
+/// it searches.
+pub async fn build_request(
+    org_id: &str,
+    name: &str,
+    req: SearchTemplateRequest,
+) -> Result<(StreamType, search::Request), ServiceError> {
+    let template = get(org_id, name).await?;
+    let sql = render(&template, &req.params).map_err(ServiceError::bad_request)?;
+    let end_time = if req.end_time > 0 {
+        req.end_time
+    } else {
+        chrono::Utc::now().timestamp_micros()
+    };
+    let mut query = search::Query {
+        sql,
+        from: req.from,
+        start_time: req.start_time,
+        end_time,
+        ..Default::default()
+    };
+    if let Some(size) = req.size {
+        query.size = size;
+    }
+    let req = search::Request {
+        query,
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    Ok((template.stream_type, req))
+}
+
+fn validate(template: &SearchTemplate) -> Result<(), String> {
+    if template.name.is_empty() {
+        return Err("Search template name is required".to_string());
+    }
+    if template.name.contains('/') {
+        return Err("Search template name cannot contain '/'".to_string());
+    }
+    if template.sql.trim().is_empty() {
+        return Err("Search template sql is required".to_string());
+    }
+    let mut names = HashSet::new();
+    for param in template.params.iter() {
+        if param.name.is_empty()
+            || !param
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!(
+                "parameter [{}] can only contain letters, digits and '_'",
+                param.name
+            ));
+        }
+        if !names.insert(param.name.as_str()) {
+            return Err(format!("parameter [{}] is duplicated", param.name));
+        }
+        if let Some(default) = &param.default {
+            quote(param, default)?;
+        }
+    }
+    for caps in RE_PLACEHOLDER.captures_iter(&template.sql) {
+        if !names.contains(&caps[1]) {
+            return Err(format!("parameter [{}] is not declared", &caps[1]));
+        }
+    }
+    Ok(())
+}
+
+/// The SQL literal of the value, strings are quoted so values can't change
+/// the query itself.
+fn quote(param: &TemplateParam, value: &Value) -> Result<String, String> {
+    match (param.param_type, value) {
+        (TemplateParamType::String, Value::String(v)) => Ok(format!("'{}'", v.replace('\'', "''"))),
+        (TemplateParamType::Number, Value::Number(v)) => Ok(v.to_string()),
+        (TemplateParamType::Boolean, Value::Bool(v)) => Ok(v.to_string()),
+        _ => Err(format!(
+            "parameter [{}] must be a {}",
+            param.name, param.param_type
+        )),
+    }
+}
+
+fn render(template: &SearchTemplate, params: &HashMap<String, Value>) -> Result<String, String> {
+    if let Some(name) = params
+        .keys()
+        .find(|k| !template.params.iter().any(|p| &p.name == *k))
+    {
+        return Err(format!("parameter [{name}] is not declared"));
+    }
+    let mut values = HashMap::with_capacity(template.params.len());
+    for param in template.params.iter() {
+        let value = match params.get(&param.name).or(param.default.as_ref()) {
+            Some(v) => quote(param, v)?,
+            None => return Err(format!("parameter [{}] is required", param.name)),
+        };
+        values.insert(param.name.as_str(), value);
+    }
+    let sql = RE_PLACEHOLDER.replace_all(&template.sql, |caps: &regex::Captures| {
+        values
+            .get(&caps[1])
+            .cloned()
+            .unwrap_or_else(|| caps[0].to_string())
+    });
+    Ok(sql.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use config::utils::json;
+
+    use super::*;
+
+    fn template() -> SearchTemplate {
+        SearchTemplate {
+            name: "errors_by_service".to_string(),
+            description: "".to_string(),
+            sql: "SELECT * FROM \"default\" WHERE service = {{service}} AND code >= {{ min_code }}"
+                .to_string(),
+            stream_type: StreamType::Logs,
+            params: vec![
+                TemplateParam {
+                    name: "service".to_string(),
+                    param_type: TemplateParamType::String,
+                    default: None,
+                    description: "".to_string(),
+                },
+                TemplateParam {
+                    name: "min_code".to_string(),
+                    param_type: TemplateParamType::Number,
+                    default: Some(json::json!(500)),
+                    description: "".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut t = template();
+        assert!(validate(&t).is_ok());
+        t.sql.push_str(" AND host = {{host}}");
+        assert!(validate(&t).is_err());
+        let mut t = template();
+        t.params[1].default = Some(json::json!("500"));
+        assert!(validate(&t).is_err());
+        let mut t = template();
+        t.params[1].name = "service".to_string();
+        assert!(validate(&t).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let t = template();
+        let params = HashMap::from([("service".to_string(), json::json!("o'brien"))]);
+        assert_eq!(
+            render(&t, &params).unwrap(),
+            "SELECT * FROM \"default\" WHERE service = 'o''brien' AND code >= 500"
+        );
+        assert!(render(&t, &HashMap::new()).is_err());
+        let params = HashMap::from([("service".to_string(), json::json!(1))]);
+        assert!(render(&t, &params).is_err());
+        let params = HashMap::from([
+            ("service".to_string(), json::json!("api")),
+            ("host".to_string(), json::json!("a")),
+        ]);
+        assert!(render(&t, &params).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::{Duration, Utc};
+use config::{cluster::LOCAL_NODE_UUID, meta::cluster::Role, RwHashMap, CONFIG};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+
+use crate::{
+    common::{infra::cluster::get_node_from_consistent_hash, meta::short_url::ShortUrl},
+    service::db,
+};
+
+const SIGNATURE_LEN: usize = 8;
+
+/// Signing keys by org, a key of its own so the links don't depend on the org
+/// encryption key.
+static KEYS: Lazy<RwHashMap<String, [u8; 32]>> = Lazy::new(Default::default);
+
+async fn get_or_create_key(org_id: &str) -> Result<[u8; 32], anyhow::Error> {
+    if let Some(key) = get_key(org_id).await? {
+        return Ok(key);
+    }
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    // another node may create it at the same time, the first one stored wins
+    let stored = db::short_url::create_key(org_id, &key).await?;
+    let key = to_key(org_id, stored)?;
+    KEYS.insert(org_id.to_string(), key);
+    Ok(key)
+}
+
+async fn get_key(org_id: &str) -> Result<Option<[u8; 32]>, anyhow::Error> {
+    if let Some(key) = KEYS.get(org_id) {
+        return Ok(Some(*key));
+    }
+    let Some(val) = db::short_url::get_key(org_id).await? else {
+        return Ok(None);
+    };
+    let key = to_key(org_id, val)?;
+    KEYS.insert(org_id.to_string(), key);
+    Ok(Some(key))
+}
+
+fn to_key(org_id: &str, val: Vec<u8>) -> Result<[u8; 32], anyhow::Error> {
+    val.try_into()
+        .map_err(|_| anyhow::anyhow!("invalid short url key of org {org_id}"))
+}
+
+/// Compares in constant time, so the signature can't be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn sign(key: &[u8; 32], org_id: &str, id: &str, url: &str) -> String {
+    let hash = blake3::Hasher::new_keyed(key)
+        .update(org_id.as_bytes())
+        .update(b"\0")
+        .update(id.as_bytes())
+        .update(b"\0")
+        .update(url.as_bytes())
+        .finalize();
+    hex::encode(&hash.as_bytes()[..SIGNATURE_LEN])
+}
+
+/// Splits a `{id}.{signature}` token.
+fn parse_token(token: &str) -> Option<(&str, &str)> {
+    let (id, signature) = token.split_once('.')?;
+    let is_hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex(id) || signature.len() != SIGNATURE_LEN * 2 || !is_hex(signature) {
+        return None;
+    }
+    Some((id, signature))
+}
+
+/// Stores a short link to the url and returns it.
+pub async fn create(org_id: &str, url: &str) -> Result<String, anyhow::Error> {
+    // signed so a link can't be guessed or pointed at another url
+    let key = get_or_create_key(org_id).await?;
+    let mut buf = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut buf);
+    let id = hex::encode(buf);
+    let now = Utc::now();
+    let short_url = ShortUrl {
+        url: url.to_string(),
+        created_at: now.timestamp_micros(),
+        expires_at: (now + Duration::days(CONFIG.common.short_url_retention_days))
+            .timestamp_micros(),
+    };
+    db::short_url::set(org_id, &id, &short_url).await?;
+    Ok(format!(
+        "{}{}/short/{org_id}/{id}.{}",
+        CONFIG.common.web_url,
+        CONFIG.common.base_uri,
+        sign(&key, org_id, &id, url)
+    ))
+}
+
+/// Returns the url behind a short link token, `None` if the token is invalid,
+/// tampered with or expired.
+pub async fn resolve(org_id: &str, token: &str) -> Result<Option<String>, anyhow::Error> {
+    let Some((id, signature)) = parse_token(token) else {
+        return Ok(None);
+    };
+    let Ok(short_url) = db::short_url::get(org_id, id).await else {
+        return Ok(None);
+    };
+    if short_url.expires_at < Utc::now().timestamp_micros() {
+        db::short_url::delete(org_id, id).await?;
+        return Ok(None);
+    }
+    let Some(key) = get_key(org_id).await? else {
+        return Ok(None);
+    };
+    let expected = sign(&key, org_id, id, &short_url.url);
+    if !constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+        return Ok(None);
+    }
+    Ok(Some(short_url.url))
+}
+
+/// Deletes the expired short urls, by one of the compactors.
+pub async fn delete_expired() -> Result<(), anyhow::Error> {
+    match get_node_from_consistent_hash("short_urls", &Role::Compactor).await {
+        Some(node) if LOCAL_NODE_UUID.eq(&node) => {}
+        _ => return Ok(()), // another compactor deletes them
+    }
+    let now = Utc::now().timestamp_micros();
+    for (key, short_url) in db::short_url::list_all().await? {
+        if short_url.expires_at >= now {
+            continue;
+        }
+        let Some((org_id, id)) = key.split_once('/') else {
+            continue;
+        };
+        db::short_url::delete(org_id, id).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_parse_token() {
+        let key = [7u8; 32];
+        let signature = sign(&key, "default", "0a1b2c3d", "http://localhost/web/logs");
+        assert_eq!(signature.len(), SIGNATURE_LEN * 2);
+        assert_ne!(
+            signature,
+            sign(&key, "default", "0a1b2c3d", "http://localhost/web/metrics")
+        );
+        assert_ne!(
+            signature,
+            sign(&key, "other", "0a1b2c3d", "http://localhost/web/logs")
+        );
+
+        let token = format!("0a1b2c3d.{signature}");
+        assert_eq!(parse_token(&token), Some(("0a1b2c3d", signature.as_str())));
+        assert_eq!(parse_token("0a1b2c3d"), None);
+        assert_eq!(parse_token("0a1b2c3d.abc"), None);
+        assert_eq!(parse_token("../x.0123456789abcdef"), None);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"0123456789abcdef", b"0123456789abcdef"));
+        assert!(!constant_time_eq(b"0123456789abcdef", b"0123456789abcdee"));
+        assert!(!constant_time_eq(b"0123", b"0123456789abcdef"));
+    }
+}
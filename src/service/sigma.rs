@@ -0,0 +1,1071 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Imports Sigma rules, the generic signature format of detection rules, so
+//! an existing rule library runs as alerts and correlation rules. The fields
+//! of each rule are renamed by the mapping profile of its log source.
+
+use std::collections::HashSet;
+
+use config::{
+    meta::stream::StreamType,
+    utils::{
+        flatten::format_key,
+        json::{Map, Value},
+    },
+};
+use ipnetwork::IpNetwork;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    common::meta::{
+        alerts::{Alert, Condition, Operator, QueryCondition, QueryType, TriggerCondition},
+        correlation::{CorrelationRule, CorrelationStep},
+        sigma::{
+            FailedRule, ImportedKind, ImportedRule, MappingProfile, SigmaImportRequest,
+            SigmaImportResponse,
+        },
+    },
+    service::{alerts, correlation, db, error::ServiceError},
+};
+
+#[derive(Debug, Default, Deserialize)]
+struct SigmaRule {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    level: String,
+    #[serde(default)]
+    logsource: LogSource,
+    #[serde(default)]
+    detection: Option<Map<String, Value>>,
+    #[serde(default)]
+    correlation: Option<SigmaCorrelation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LogSource {
+    #[serde(default)]
+    product: String,
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    service: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SigmaCorrelation {
+    #[serde(rename = "type")]
+    correlation_type: String,
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default, rename = "group-by")]
+    group_by: Vec<String>,
+    #[serde(default)]
+    timespan: String,
+    #[serde(default)]
+    condition: Map<String, Value>,
+    /// The referenced rules raise alerts on their own too.
+    #[serde(default)]
+    generate: bool,
+}
+
+impl SigmaRule {
+    fn is_named(&self, name: &str) -> bool {
+        (!self.name.is_empty() && self.name == name) || (!self.id.is_empty() && self.id == name)
+    }
+
+    /// Name of the alert or correlation rule created for the rule.
+    fn object_name(&self) -> Result<String, String> {
+        let mut name = if self.name.is_empty() {
+            self.title.clone()
+        } else {
+            self.name.clone()
+        };
+        format_key(&mut name);
+        let name = name.trim_matches('_').to_string();
+        if name.is_empty() {
+            return Err("rule has no title".to_string());
+        }
+        Ok(name)
+    }
+
+    fn object_description(&self) -> String {
+        let description = self.description.trim();
+        if self.id.is_empty() {
+            return description.to_string();
+        }
+        format!(
+            "{description} (Sigma rule {}, level {})",
+            self.id, self.level
+        )
+        .trim()
+        .to_string()
+    }
+}
+
+enum Converted {
+    Alert(Alert),
+    Correlation(CorrelationRule),
+}
+
+pub async fn save_profile(
+    org_id: &str,
+    name: &str,
+    mut profile: MappingProfile,
+    create: bool,
+) -> Result<(), ServiceError> {
+    if !name.is_empty() {
+        profile.name = name.to_string();
+    }
+    profile.name = profile.name.trim().to_string();
+    profile.stream_name = profile.stream_name.trim().to_string();
+    if profile.name.is_empty() {
+        return Err(ServiceError::bad_request(
+            "Mapping profile name is required",
+        ));
+    }
+    if profile.name.contains('/') {
+        return Err(ServiceError::bad_request(
+            "Mapping profile name cannot contain '/'",
+        ));
+    }
+    if profile.stream_name.is_empty() {
+        return Err(ServiceError::bad_request(
+            "Mapping profile stream_name is required",
+        ));
+    }
+
+    match db::sigma::get(org_id, &profile.name).await {
+        Ok(_) if create => {
+            return Err(ServiceError::bad_request("Mapping profile already exists"));
+        }
+        Err(_) if !create => {
+            return Err(ServiceError::not_found("Mapping profile not found"));
+        }
+        _ => {}
+    }
+    db::sigma::set(org_id, &profile)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get_profile(org_id: &str, name: &str) -> Result<MappingProfile, ServiceError> {
+    db::sigma::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("Mapping profile not found"))
+}
+
+pub async fn list_profiles(org_id: &str) -> Result<Vec<MappingProfile>, ServiceError> {
+    db::sigma::list(org_id).await.map_err(ServiceError::from)
+}
+
+pub async fn delete_profile(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    get_profile(org_id, name).await?;
+    db::sigma::delete(org_id, name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// Converts and saves the rules of the request, a rule which can't be
+/// converted or saved is reported without failing the others.
+pub async fn import(
+    org_id: &str,
+    user_id: &str,
+    req: SigmaImportRequest,
+) -> Result<SigmaImportResponse, ServiceError> {
+    if req.destinations.is_empty() {
+        return Err(ServiceError::bad_request("destinations are required"));
+    }
+    if req.period < 1 {
+        return Err(ServiceError::bad_request(
+            "period must be at least 1 minute",
+        ));
+    }
+    let rules = parse_rules(&req.rules).map_err(ServiceError::bad_request)?;
+    let profiles = list_profiles(org_id).await?;
+
+    // rules referenced by a correlation only raise alerts when it asks so
+    let referenced = rules
+        .iter()
+        .filter_map(|r| r.correlation.as_ref())
+        .filter(|c| !c.generate)
+        .flat_map(|c| c.rules.iter().map(|r| r.as_str()))
+        .collect::<HashSet<_>>();
+
+    let mut resp = SigmaImportResponse::default();
+    for rule in rules.iter() {
+        if !req.levels.is_empty()
+            && !req
+                .levels
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(&rule.level))
+        {
+            continue;
+        }
+        if rule.detection.is_some() && referenced.iter().any(|name| rule.is_named(name)) {
+            continue;
+        }
+        let ret = match convert(rule, &rules, &profiles, &req) {
+            Ok(converted) => save_converted(org_id, user_id, converted, req.overwrite).await,
+            Err(e) => Err(e),
+        };
+        match ret {
+            Ok((kind, name, stream_name)) => resp.imported.push(ImportedRule {
+                title: rule.title.clone(),
+                kind,
+                name,
+                stream_name,
+            }),
+            Err(error) => resp.failed.push(FailedRule {
+                title: rule.title.clone(),
+                error,
+            }),
+        }
+    }
+    Ok(resp)
+}
+
+async fn save_converted(
+    org_id: &str,
+    user_id: &str,
+    converted: Converted,
+    overwrite: bool,
+) -> Result<(ImportedKind, String, String), String> {
+    match converted {
+        Converted::Alert(alert) => {
+            let (name, stream_name) = (alert.name.clone(), alert.stream_name.clone());
+            let exists = matches!(
+                db::alerts::get(org_id, StreamType::Logs, &stream_name, &name).await,
+                Ok(Some(_))
+            );
+            if exists && !overwrite {
+                return Err(format!("Alert [{name}] already exists"));
+            }
+            alerts::save(org_id, &stream_name, "", alert, !exists, user_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok((ImportedKind::Alert, name, stream_name))
+        }
+        Converted::Correlation(rule) => {
+            let name = rule.name.clone();
+            let stream_name = rule
+                .steps
+                .iter()
+                .map(|s| s.stream.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+            let exists = correlation::get(org_id, &name).await.is_ok();
+            if exists && !overwrite {
+                return Err(format!("Correlation rule [{name}] already exists"));
+            }
+            correlation::save(org_id, "", rule, !exists)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok((ImportedKind::Correlation, name, stream_name))
+        }
+    }
+}
+
+fn parse_rules(body: &str) -> Result<Vec<SigmaRule>, String> {
+    let mut rules = Vec::new();
+    for doc in serde_yaml::Deserializer::from_str(body) {
+        let value = serde_yaml::Value::deserialize(doc).map_err(|e| e.to_string())?;
+        if value.is_null() {
+            continue;
+        }
+        rules.push(serde_yaml::from_value(value).map_err(|e| e.to_string())?);
+    }
+    if rules.is_empty() {
+        return Err("no Sigma rule found".to_string());
+    }
+    Ok(rules)
+}
+
+fn convert(
+    rule: &SigmaRule,
+    rules: &[SigmaRule],
+    profiles: &[MappingProfile],
+    req: &SigmaImportRequest,
+) -> Result<Converted, String> {
+    let name = rule.object_name()?;
+    if let Some(corr) = &rule.correlation {
+        let window = parse_timespan(&corr.timespan)?;
+        let referenced = corr
+            .rules
+            .iter()
+            .map(|name| {
+                rules
+                    .iter()
+                    .find(|r| r.detection.is_some() && r.is_named(name))
+                    .ok_or_else(|| format!("referenced rule [{name}] not found"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if referenced.is_empty() {
+            return Err("correlation references no rule".to_string());
+        }
+        return match corr.correlation_type.as_str() {
+            "event_count" => {
+                let mut stream_name = None;
+                let mut wheres = Vec::with_capacity(referenced.len());
+                for r in referenced.iter() {
+                    let profile = find_profile(r, profiles)?;
+                    if stream_name.is_some_and(|s| s != profile.stream_name) {
+                        return Err("referenced rules search different streams".to_string());
+                    }
+                    stream_name = Some(profile.stream_name.as_str());
+                    wheres.push(detection_sql(r.detection.as_ref().unwrap(), profile)?.0);
+                }
+                let profile = find_profile(referenced[0], profiles)?;
+                let group_by = corr
+                    .group_by
+                    .iter()
+                    .map(|f| format!("\"{}\"", field_name(profile, f)))
+                    .collect::<Vec<_>>();
+                let (op, threshold) = count_condition(&corr.condition)?;
+                let select = group_by
+                    .iter()
+                    .map(|f| format!("{f}, "))
+                    .collect::<String>();
+                let group = if group_by.is_empty() {
+                    String::new()
+                } else {
+                    format!(" GROUP BY {}", group_by.join(", "))
+                };
+                let sql = format!(
+                    "SELECT {select}count(*) AS events FROM \"{}\" WHERE {}{group} HAVING count(*) {op} {threshold}",
+                    profile.stream_name,
+                    join(wheres, " OR ")
+                );
+                Ok(Converted::Alert(build_alert(
+                    rule, name, profile, sql, window, req,
+                )))
+            }
+            "temporal_ordered" => {
+                if corr.group_by.len() != 1 {
+                    return Err("temporal_ordered correlations need one group-by field".to_string());
+                }
+                let mut steps = Vec::with_capacity(referenced.len());
+                for r in referenced.iter() {
+                    let profile = find_profile(r, profiles)?;
+                    steps.push(CorrelationStep {
+                        name: r.object_name()?,
+                        stream: profile.stream_name.clone(),
+                        conditions: detection_conditions(r.detection.as_ref().unwrap(), profile)?,
+                        count: 1,
+                    });
+                }
+                let profile = find_profile(referenced[0], profiles)?;
+                Ok(Converted::Correlation(CorrelationRule {
+                    name,
+                    description: rule.object_description(),
+                    entity_field: field_name(profile, &corr.group_by[0]),
+                    window,
+                    steps,
+                    destinations: req.destinations.clone(),
+                    enabled: req.enabled,
+                }))
+            }
+            other => Err(format!("correlation type [{other}] is not supported")),
+        };
+    }
+
+    let Some(detection) = &rule.detection else {
+        return Err("rule has no detection".to_string());
+    };
+    let profile = find_profile(rule, profiles)?;
+    let (where_sql, timeframe) = detection_sql(detection, profile)?;
+    let sql = format!(
+        "SELECT * FROM \"{}\" WHERE {where_sql}",
+        profile.stream_name
+    );
+    let window = timeframe.unwrap_or(req.period * 60);
+    Ok(Converted::Alert(build_alert(
+        rule, name, profile, sql, window, req,
+    )))
+}
+
+fn build_alert(
+    rule: &SigmaRule,
+    name: String,
+    profile: &MappingProfile,
+    sql: String,
+    window: i64,
+    req: &SigmaImportRequest,
+) -> Alert {
+    // the alert searches the window every window, at least a minute
+    let period = std::cmp::max(1, (window + 59) / 60);
+    Alert {
+        name,
+        stream_type: StreamType::Logs,
+        stream_name: profile.stream_name.clone(),
+        is_real_time: false,
+        query_condition: QueryCondition {
+            query_type: QueryType::SQL,
+            sql: Some(sql),
+            ..Default::default()
+        },
+        trigger_condition: TriggerCondition {
+            period,
+            operator: Operator::GreaterThanEquals,
+            threshold: 1,
+            frequency: period * 60,
+            ..Default::default()
+        },
+        destinations: req.destinations.clone(),
+        description: rule.object_description(),
+        enabled: req.enabled,
+        ..Default::default()
+    }
+}
+
+/// The profile matching the most attributes of the log source of the rule.
+fn find_profile<'a>(
+    rule: &SigmaRule,
+    profiles: &'a [MappingProfile],
+) -> Result<&'a MappingProfile, String> {
+    let src = &rule.logsource;
+    profiles
+        .iter()
+        .filter_map(|p| {
+            p.score(&src.product, &src.category, &src.service)
+                .map(|score| (score, p))
+        })
+        // the first profile by name wins a tie
+        .fold(None, |best: Option<(usize, &MappingProfile)>, (score, p)| match best {
+            Some((best_score, _)) if best_score >= score => best,
+            _ => Some((score, p)),
+        })
+        .map(|(_, p)| p)
+        .ok_or_else(|| {
+            format!(
+                "no mapping profile for logsource product [{}] category [{}] service [{}]",
+                src.product, src.category, src.service
+            )
+        })
+}
+
+fn field_name(profile: &MappingProfile, name: &str) -> String {
+    match profile.fields.get(name) {
+        Some(field) => field.to_string(),
+        None => {
+            let mut field = name.to_string();
+            format_key(&mut field);
+            field
+        }
+    }
+}
+
+/// Seconds of a Sigma timespan like `30s`, `5m`, `1h` or `2d`.
+fn parse_timespan(timespan: &str) -> Result<i64, String> {
+    let timespan = timespan.trim();
+    let err = || format!("timespan [{timespan}] is invalid");
+    if timespan.len() < 2 {
+        return Err(err());
+    }
+    let (num, unit) = timespan.split_at(timespan.len() - 1);
+    let num: i64 = num.parse().map_err(|_| err())?;
+    let secs = match unit {
+        "s" => num,
+        "m" => num * 60,
+        "h" => num * 3600,
+        "d" => num * 86400,
+        _ => return Err(err()),
+    };
+    if secs <= 0 {
+        return Err(err());
+    }
+    Ok(secs)
+}
+
+fn count_condition(condition: &Map<String, Value>) -> Result<(&'static str, i64), String> {
+    if condition.len() != 1 {
+        return Err("correlation condition needs one of gt, gte, lt, lte or eq".to_string());
+    }
+    let (op, value) = condition.iter().next().unwrap();
+    let op = match op.as_str() {
+        "gt" => ">",
+        "gte" => ">=",
+        "lt" => "<",
+        "lte" => "<=",
+        "eq" => "=",
+        _ => return Err(format!("correlation condition [{op}] is not supported")),
+    };
+    let threshold = value
+        .as_i64()
+        .ok_or_else(|| format!("correlation condition value [{value}] is not a number"))?;
+    Ok((op, threshold))
+}
+
+fn join(mut parts: Vec<String>, op: &str) -> String {
+    if parts.len() == 1 {
+        parts.pop().unwrap()
+    } else {
+        format!("({})", parts.join(op))
+    }
+}
+
+/// The SQL filter of the detection and its timeframe in seconds.
+fn detection_sql(
+    detection: &Map<String, Value>,
+    profile: &MappingProfile,
+) -> Result<(String, Option<i64>), String> {
+    let mut searches = Vec::with_capacity(detection.len());
+    let mut timeframe = None;
+    let mut condition = None;
+    for (key, value) in detection.iter() {
+        match key.as_str() {
+            "condition" => condition = Some(value),
+            "timeframe" => {
+                timeframe = Some(parse_timespan(value.as_str().unwrap_or_default())?);
+            }
+            _ => searches.push((key.to_string(), search_sql(value, profile)?)),
+        }
+    }
+    let conditions = match condition {
+        Some(Value::String(v)) => vec![v.as_str()],
+        Some(Value::Array(v)) => v.iter().filter_map(|v| v.as_str()).collect(),
+        _ => return Err("detection has no condition".to_string()),
+    };
+    if conditions.is_empty() {
+        return Err("detection has no condition".to_string());
+    }
+    let sql = conditions
+        .into_iter()
+        .map(|c| ConditionParser::new(c, &searches)?.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((join(sql, " OR "), timeframe))
+}
+
+fn search_sql(search: &Value, profile: &MappingProfile) -> Result<String, String> {
+    match search {
+        Value::Object(fields) => {
+            if fields.is_empty() {
+                return Err("search has no field".to_string());
+            }
+            let parts = fields
+                .iter()
+                .map(|(key, value)| field_sql(key, value, profile))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(join(parts, " AND "))
+        }
+        Value::Array(items) if !items.is_empty() => {
+            let parts = if items.iter().all(|v| v.is_object()) {
+                items
+                    .iter()
+                    .map(|v| search_sql(v, profile))
+                    .collect::<Result<Vec<_>, _>>()?
+            } else {
+                items
+                    .iter()
+                    .map(keyword_sql)
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            Ok(join(parts, " OR "))
+        }
+        Value::String(_) | Value::Number(_) => keyword_sql(search),
+        _ => Err(format!("search [{search}] is not supported")),
+    }
+}
+
+/// A value searched in every field.
+fn keyword_sql(keyword: &Value) -> Result<String, String> {
+    let keyword = match keyword {
+        Value::String(v) => v.trim_matches('*').to_string(),
+        Value::Number(v) => v.to_string(),
+        _ => return Err(format!("keyword [{keyword}] is not supported")),
+    };
+    if keyword.is_empty() || keyword.contains(['*', '?', '\'', '\\']) {
+        return Err(format!("keyword [{keyword}] is not supported"));
+    }
+    Ok(format!("match_all('{keyword}')"))
+}
+
+fn field_sql(key: &str, value: &Value, profile: &MappingProfile) -> Result<String, String> {
+    let mut parts = key.split('|');
+    let name = parts.next().unwrap_or_default();
+    let mut modifiers = parts.collect::<Vec<_>>();
+    if name.is_empty() {
+        return Err(format!("keywords with modifiers [{key}] are not supported"));
+    }
+    let all = modifiers.contains(&"all");
+    modifiers.retain(|m| *m != "all");
+    let column = format!("\"{}\"", field_name(profile, name));
+    let values = match value {
+        Value::Array(v) => v.iter().collect(),
+        v => vec![v],
+    };
+    if values.is_empty() {
+        return Err(format!("field [{key}] has no value"));
+    }
+    let parts = values
+        .into_iter()
+        .map(|v| value_sql(&column, &modifiers, v))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(join(parts, if all { " AND " } else { " OR " }))
+}
+
+fn value_sql(column: &str, modifiers: &[&str], value: &Value) -> Result<String, String> {
+    let modifier = modifiers.first().copied().unwrap_or_default();
+    let unsupported = || format!("modifiers [{}] are not supported", modifiers.join("|"));
+    if modifiers.len() > 1 && modifier != "re" {
+        return Err(unsupported());
+    }
+    match (modifier, value) {
+        ("", Value::Null) => Ok(format!("{column} IS NULL")),
+        ("exists", Value::Bool(v)) => {
+            Ok(format!("{column} IS {}NULL", if *v { "NOT " } else { "" }))
+        }
+        ("lt" | "lte" | "gt" | "gte", v) => {
+            let num = match v {
+                Value::Number(v) => v.to_string(),
+                Value::String(v) if v.trim().parse::<f64>().is_ok() => v.trim().to_string(),
+                _ => {
+                    return Err(format!(
+                        "value [{v}] of modifier [{modifier}] is not a number"
+                    ));
+                }
+            };
+            let op = match modifier {
+                "lt" => "<",
+                "lte" => "<=",
+                "gt" => ">",
+                _ => ">=",
+            };
+            Ok(format!("{column} {op} {num}"))
+        }
+        ("re", Value::String(v)) => {
+            let mut flags = String::new();
+            for flag in modifiers[1..].iter() {
+                match *flag {
+                    "i" | "m" | "s" => flags.push_str(flag),
+                    _ => return Err(unsupported()),
+                }
+            }
+            let pattern = if flags.is_empty() {
+                v.to_string()
+            } else {
+                format!("(?{flags}){v}")
+            };
+            Regex::new(&pattern).map_err(|e| format!("regex [{v}] is invalid: {e}"))?;
+            Ok(format!(
+                "re_match({column}, '{}')",
+                pattern.replace('\'', "''")
+            ))
+        }
+        ("cidr", Value::String(v)) => cidr_sql(column, v),
+        ("" | "contains" | "startswith" | "endswith", Value::String(v)) => {
+            let pattern = match modifier {
+                "contains" => format!("*{v}*"),
+                "startswith" => format!("{v}*"),
+                "endswith" => format!("*{v}"),
+                _ => v.to_string(),
+            };
+            // Sigma strings are case insensitive
+            Ok(format!("{column} ILIKE '{}'", like_pattern(&pattern)))
+        }
+        ("", Value::Number(v)) => Ok(format!("{column} = {v}")),
+        ("", Value::Bool(v)) => Ok(format!("{column} = {v}")),
+        _ => Err(unsupported()),
+    }
+}
+
+/// The LIKE pattern of a Sigma string, whose `*` and `?` are wildcards unless
+/// escaped by a backslash.
+fn like_pattern(value: &str) -> String {
+    fn push_literal(pattern: &mut String, c: char) {
+        match c {
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            '\'' => pattern.push_str("''"),
+            c => pattern.push(c),
+        }
+    }
+
+    let mut pattern = String::with_capacity(value.len() + 2);
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some('*' | '?' | '\\') => {
+                    let c = chars.next().unwrap();
+                    push_literal(&mut pattern, c);
+                }
+                _ => push_literal(&mut pattern, c),
+            },
+            '*' => pattern.push('%'),
+            '?' => pattern.push('_'),
+            c => push_literal(&mut pattern, c),
+        }
+    }
+    pattern
+}
+
+/// Addresses in the network, searched by their prefix so only IPv4 networks
+/// on octet boundaries are supported.
+fn cidr_sql(column: &str, network: &str) -> Result<String, String> {
+    let unsupported =
+        || format!("cidr [{network}] is not supported, only IPv4 networks on octet boundaries are");
+    let IpNetwork::V4(net) = network.parse::<IpNetwork>().map_err(|_| unsupported())? else {
+        return Err(unsupported());
+    };
+    if net.prefix() % 8 != 0 {
+        return Err(unsupported());
+    }
+    let octets = (net.prefix() / 8) as usize;
+    if octets == 0 {
+        return Ok(format!("{column} IS NOT NULL"));
+    }
+    if octets == 4 {
+        return Ok(format!("{column} = '{}'", net.ip()));
+    }
+    let prefix = net.network().octets()[..octets]
+        .iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+    Ok(format!("{column} LIKE '{prefix}.%'"))
+}
+
+/// The alert conditions of a detection, correlation steps only support a
+/// single search of fields equal to or containing a value.
+fn detection_conditions(
+    detection: &Map<String, Value>,
+    profile: &MappingProfile,
+) -> Result<Vec<Condition>, String> {
+    let unsupported =
+        || "only a single search of plain values can be a correlation step".to_string();
+    let searches = detection
+        .iter()
+        .filter(|(k, _)| *k != "condition" && *k != "timeframe")
+        .collect::<Vec<_>>();
+    let [(name, Value::Object(fields))] = searches.as_slice() else {
+        return Err(unsupported());
+    };
+    if detection
+        .get("condition")
+        .and_then(|v| v.as_str())
+        .map(|v| v.trim())
+        != Some(name.as_str())
+    {
+        return Err(unsupported());
+    }
+    let mut conditions = Vec::with_capacity(fields.len());
+    for (key, value) in fields.iter() {
+        let (field, modifier) = key.split_once('|').unwrap_or((key, ""));
+        let column = field_name(profile, field);
+        let condition = match (modifier, value) {
+            ("", Value::Number(_) | Value::Bool(_)) => Condition {
+                column,
+                operator: Operator::EqualTo,
+                value: value.clone(),
+                ignore_case: false,
+            },
+            ("" | "contains", Value::String(v)) => {
+                let inner = v.trim_matches('*');
+                if inner.is_empty() || inner.contains(['*', '?', '\\']) {
+                    return Err(unsupported());
+                }
+                let operator = if modifier == "contains" || (v.starts_with('*') && v.ends_with('*'))
+                {
+                    Operator::Contains
+                } else if inner.len() == v.len() {
+                    Operator::EqualTo
+                } else {
+                    return Err(unsupported());
+                };
+                Condition {
+                    column,
+                    operator,
+                    value: Value::String(inner.to_string()),
+                    ignore_case: true,
+                }
+            }
+            _ => return Err(unsupported()),
+        };
+        conditions.push(condition);
+    }
+    Ok(conditions)
+}
+
+/// Parses the condition of a detection into SQL, `not` binds tighter than
+/// `and` which binds tighter than `or`.
+struct ConditionParser<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    searches: &'a [(String, String)],
+}
+
+impl<'a> ConditionParser<'a> {
+    fn new(condition: &str, searches: &'a [(String, String)]) -> Result<Self, String> {
+        if condition.contains('|') {
+            return Err(
+                "aggregations in conditions are deprecated, use a correlation rule".to_string(),
+            );
+        }
+        let tokens = condition
+            .replace('(', " ( ")
+            .replace(')', " ) ")
+            .split_whitespace()
+            .map(|t| t.to_string())
+            .collect();
+        Ok(Self {
+            tokens,
+            pos: 0,
+            searches,
+        })
+    }
+
+    fn parse(mut self) -> Result<String, String> {
+        let sql = self.parse_or()?;
+        match self.tokens.get(self.pos) {
+            Some(token) => Err(format!("unexpected [{token}] in condition")),
+            None => Ok(sql),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, keyword: &str) -> bool {
+        if self
+            .tokens
+            .get(self.pos)
+            .is_some_and(|t| t.eq_ignore_ascii_case(keyword))
+        {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn parse_or(&mut self) -> Result<String, String> {
+        let mut parts = vec![self.parse_and()?];
+        while self.eat("or") {
+            parts.push(self.parse_and()?);
+        }
+        Ok(join(parts, " OR "))
+    }
+
+    fn parse_and(&mut self) -> Result<String, String> {
+        let mut parts = vec![self.parse_not()?];
+        while self.eat("and") {
+            parts.push(self.parse_not()?);
+        }
+        Ok(join(parts, " AND "))
+    }
+
+    fn parse_not(&mut self) -> Result<String, String> {
+        if self.eat("not") {
+            // a search on a missing field is null, which Sigma treats as false
+            return Ok(format!("NOT COALESCE({}, false)", self.parse_not()?));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<String, String> {
+        let Some(token) = self.next_token() else {
+            return Err("condition ends unexpectedly".to_string());
+        };
+        if token == "(" {
+            let sql = self.parse_or()?;
+            if !self.eat(")") {
+                return Err("condition misses a ')'".to_string());
+            }
+            return Ok(sql);
+        }
+        let quantifier = token.to_lowercase();
+        if matches!(quantifier.as_str(), "1" | "any" | "all") && self.eat("of") {
+            let Some(target) = self.next_token() else {
+                return Err(format!("condition ends after [{token} of]"));
+            };
+            let matched = if target == "them" {
+                self.searches
+                    .iter()
+                    .filter(|(name, _)| !name.starts_with('_'))
+                    .map(|(_, sql)| sql.clone())
+                    .collect::<Vec<_>>()
+            } else {
+                let re = Regex::new(&format!(
+                    "^{}$",
+                    regex::escape(&target).replace("\\*", ".*")
+                ))
+                .map_err(|e| e.to_string())?;
+                self.searches
+                    .iter()
+                    .filter(|(name, _)| re.is_match(name))
+                    .map(|(_, sql)| sql.clone())
+                    .collect::<Vec<_>>()
+            };
+            if matched.is_empty() {
+                return Err(format!("no search matches [{target}]"));
+            }
+            let op = if quantifier == "all" { " AND " } else { " OR " };
+            return Ok(join(matched, op));
+        }
+        self.searches
+            .iter()
+            .find(|(name, _)| *name == token)
+            .map(|(_, sql)| sql.clone())
+            .ok_or_else(|| format!("search [{token}] is not defined"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn profile() -> MappingProfile {
+        MappingProfile {
+            name: "windows".to_string(),
+            description: "".to_string(),
+            product: "windows".to_string(),
+            category: "".to_string(),
+            service: "".to_string(),
+            stream_name: "winlog".to_string(),
+            fields: HashMap::from([("EventID".to_string(), "event_id".to_string())]),
+        }
+    }
+
+    fn detection(yaml: &str) -> Map<String, Value> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_detection_sql() {
+        let (sql, timeframe) = detection_sql(
+            &detection(
+                r#"
+selection:
+  CommandLine|contains|all:
+    - ' -enc '
+    - powershell
+  EventID: 4688
+filter_admin:
+  User|startswith: 'ADMIN\*'
+condition: selection and not 1 of filter_*
+timeframe: 10m
+"#,
+            ),
+            &profile(),
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "(((\"commandline\" ILIKE '% -enc %' AND \"commandline\" ILIKE '%powershell%') AND \"event_id\" = 4688) AND NOT COALESCE(\"user\" ILIKE 'ADMIN*%', false))"
+        );
+        assert_eq!(timeframe, Some(600));
+
+        let (sql, _) = detection_sql(
+            &detection(
+                r#"
+keywords:
+  - mimikatz
+  - '*sekurlsa*'
+sel:
+  - Image|endswith: '\cmd.exe'
+  - SourceIp|cidr: 10.1.0.0/16
+condition: all of them
+"#,
+            ),
+            &profile(),
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "((match_all('mimikatz') OR match_all('sekurlsa')) AND (\"image\" ILIKE '%\\\\cmd.exe' OR \"sourceip\" LIKE '10.1.%'))"
+        );
+
+        for (yaml, err) in [
+            ("sel:\n  a: 1\ncondition: sel | count() > 5", "deprecated"),
+            ("sel:\n  a: 1\ncondition: other", "not defined"),
+            ("sel:\n  a|base64: x\ncondition: sel", "not supported"),
+            ("sel:\n  a: 1", "no condition"),
+        ] {
+            let e = detection_sql(&detection(yaml), &profile()).unwrap_err();
+            assert!(e.contains(err), "{e}");
+        }
+    }
+
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("a*b?c"), "a%b_c");
+        assert_eq!(like_pattern(r"100\*_%"), r"100*\_\%");
+        assert_eq!(like_pattern(r"C:\Windows\\x"), r"C:\\Windows\\x");
+        assert_eq!(like_pattern("it's"), "it''s");
+    }
+
+    #[test]
+    fn test_parse_rules_and_profiles() {
+        let rules = parse_rules(
+            r#"
+title: Failed logon
+name: failed_logon
+logsource:
+  product: windows
+  service: security
+detection:
+  selection:
+    EventID: 4625
+  condition: selection
+---
+title: Many failed logons
+correlation:
+  type: temporal_ordered
+  rules:
+    - failed_logon
+  group-by:
+    - TargetUserName
+  timespan: 5m
+"#,
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].is_named("failed_logon"));
+        assert_eq!(rules[1].object_name().unwrap(), "many_failed_logons");
+        assert_eq!(parse_timespan("5m"), Ok(300));
+        assert!(parse_timespan("5x").is_err());
+
+        let mut security = profile();
+        security.name = "windows_security".to_string();
+        security.service = "security".to_string();
+        security.stream_name = "security".to_string();
+        let profiles = vec![profile(), security];
+        assert_eq!(
+            find_profile(&rules[0], &profiles).unwrap().stream_name,
+            "security"
+        );
+
+        let conditions =
+            detection_conditions(rules[0].detection.as_ref().unwrap(), &profiles[1]).unwrap();
+        assert_eq!(conditions.len(), 1);
+        assert_eq!(conditions[0].column, "event_id");
+        assert_eq!(conditions[0].operator, Operator::EqualTo);
+    }
+}
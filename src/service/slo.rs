@@ -0,0 +1,771 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, ops::ControlFlow};
+
+use actix_web::web;
+use chrono::Utc;
+use config::{
+    meta::{search, sql::Sql, stream::StreamType},
+    utils::json::{self, Map, Value},
+    CONFIG,
+};
+use infra::schema::unwrap_stream_settings;
+use sqlparser::{
+    ast::{visit_expressions, Expr},
+    dialect::GenericDialect,
+    parser::Parser,
+    tokenizer::Token,
+};
+
+use crate::{
+    common::meta::{
+        alerts::Alert,
+        ingestion::IngestionRequest,
+        slo::{
+            BurnRateRule, BurnRateStatus, Slo, SloHistory, SloHistoryPoint, SloStatus,
+            SLO_HISTORY_STREAM,
+        },
+    },
+    service::{
+        alerts::destinations,
+        db,
+        error::{Result, ServiceError},
+        logs, search as SearchService,
+    },
+};
+
+/// Seconds between two evaluations of the burn rate rules
+pub const EVALUATION_INTERVAL: i64 = 60;
+
+const MINUTE: i64 = 60 * 1_000_000;
+
+const DAY: i64 = 24 * 60 * MINUTE;
+
+/// Longest time range an evaluation records, after the evaluations stopped
+/// for a while
+const MAX_RECORD_MINUTES: i64 = 24 * 60;
+
+/// Points of the history when its interval is not given
+const MAX_POINTS: i64 = 500;
+
+/// Most buckets of the history stream read for the points of the history
+const MAX_BUCKETS: usize = 10_000;
+
+/// The multi-window multi-burn-rate rules of the Google SRE workbook as
+/// `(name, severity, long window, short window, share of the error budget
+/// burnt over the long window)`, windows in minutes.
+const RULES: [(&str, &str, i64, i64, f64); 4] = [
+    ("page_1h", "page", 60, 5, 0.02),
+    ("page_6h", "page", 6 * 60, 30, 0.05),
+    ("ticket_1d", "ticket", 24 * 60, 2 * 60, 0.1),
+    ("ticket_3d", "ticket", 3 * 24 * 60, 6 * 60, 0.1),
+];
+
+pub async fn save(org_id: &str, name: &str, mut slo: Slo, create: bool) -> Result<()> {
+    if !name.is_empty() {
+        slo.name = name.to_string();
+    }
+    validate(&mut slo)?;
+    for dest in slo.destinations.iter() {
+        if destinations::get(org_id, dest).await.is_err() {
+            return Err(ServiceError::bad_request(format!(
+                "Alert destination {dest} not found"
+            )));
+        }
+    }
+    let existing = db::slo::get(org_id, &slo.name).await.ok();
+    if create && existing.is_some() {
+        return Err(ServiceError::Conflict(format!(
+            "slo [{}] already exists",
+            slo.name
+        )));
+    }
+    if !create && existing.is_none() {
+        return Err(ServiceError::not_found("slo not found"));
+    }
+    slo.rules = generate_rules(slo.window);
+    slo.firing = vec![];
+    // the history goes on from the last evaluation
+    slo.last_evaluated_at = existing.and_then(|s| s.last_evaluated_at);
+    db::slo::set(org_id, &slo, create).await?;
+    Ok(())
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<Slo> {
+    db::slo::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("slo not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<Slo>> {
+    Ok(db::slo::list(org_id).await?)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<()> {
+    get(org_id, name).await?;
+    db::slo::delete(org_id, name).await?;
+    Ok(())
+}
+
+/// Checks the SLO, its filters are written back by the parser.
+fn validate(slo: &mut Slo) -> Result<()> {
+    if slo.name.is_empty() || slo.name.contains('/') {
+        return Err(ServiceError::bad_request(
+            "slo name is required and cannot contain '/'",
+        ));
+    }
+    if slo.stream_name.is_empty() {
+        return Err(ServiceError::bad_request("stream_name is required"));
+    }
+    if !(slo.target > 0.0 && slo.target < 100.0) {
+        return Err(ServiceError::bad_request(
+            "target should be a percent between 0 and 100",
+        ));
+    }
+    if !(1..=90).contains(&slo.window) {
+        return Err(ServiceError::bad_request(
+            "window should be from 1 to 90 days",
+        ));
+    }
+    if slo.destinations.is_empty() {
+        return Err(ServiceError::bad_request("slo destinations are required"));
+    }
+    slo.error_filter = parse_filter("error_filter", &slo.error_filter)?;
+    if !slo.total_filter.is_empty() {
+        slo.total_filter = parse_filter("total_filter", &slo.total_filter)?;
+    }
+    Sql::new(&count_sql(slo)).map_err(|e| ServiceError::bad_request(e.to_string()))?;
+    Ok(())
+}
+
+/// Parses a filter as a single condition without subqueries. The condition is
+/// returned as written by the parser, the queries get nothing else from the
+/// filter.
+fn parse_filter(name: &str, filter: &str) -> Result<String> {
+    let invalid = |e: String| ServiceError::bad_request(format!("{name} is invalid: {e}"));
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect)
+        .try_with_sql(filter)
+        .map_err(|e| invalid(e.to_string()))?;
+    let expr = parser.parse_expr().map_err(|e| invalid(e.to_string()))?;
+    if parser.peek_token().token != Token::EOF {
+        return Err(invalid("it should be a single condition".to_string()));
+    }
+    let subquery = visit_expressions(&expr, |expr| match expr {
+        Expr::Subquery(_) | Expr::Exists { .. } | Expr::InSubquery { .. } => ControlFlow::Break(()),
+        _ => ControlFlow::Continue(()),
+    });
+    if subquery.is_break() {
+        return Err(invalid("subqueries are not allowed".to_string()));
+    }
+    Ok(expr.to_string())
+}
+
+/// The rules of an SLO over `window` days, a rule burning the budget of the
+/// whole window over its long window is left out.
+pub fn generate_rules(window: i64) -> Vec<BurnRateRule> {
+    let window = window * 24 * 60;
+    RULES
+        .iter()
+        .filter(|(_, _, long_window, ..)| *long_window < window)
+        .map(
+            |(name, severity, long_window, short_window, budget_share)| BurnRateRule {
+                name: name.to_string(),
+                severity: severity.to_string(),
+                long_window: *long_window,
+                short_window: *short_window,
+                burn_rate: (budget_share * window as f64 / *long_window as f64 * 100.0).round()
+                    / 100.0,
+            },
+        )
+        .collect()
+}
+
+fn count_sql(slo: &Slo) -> String {
+    let filter = if slo.total_filter.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE ({})", slo.total_filter)
+    };
+    format!(
+        "SELECT COUNT(*) AS total, SUM(CASE WHEN ({}) THEN 1 ELSE 0 END) AS errors FROM \"{}\"{filter}",
+        slo.error_filter, slo.stream_name
+    )
+}
+
+/// How many times faster than allowed the errors burn the budget
+fn burn_rate(slo: &Slo, (total, errors): (i64, i64)) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let budget = 1.0 - slo.target / 100.0;
+    errors as f64 / total as f64 / budget
+}
+
+async fn query(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: String,
+    start_time: i64,
+    end_time: i64,
+    size: usize,
+) -> Result<Vec<Map<String, Value>>> {
+    let req = search::Request {
+        query: search::Query {
+            sql,
+            size,
+            start_time,
+            end_time,
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+        clusters: vec![],
+        timeout: 0,
+    };
+    let res = SearchService::search("", org_id, stream_type, None, &req)
+        .await
+        .map_err(|e| ServiceError::internal(format!("search error: {e}")))?;
+    Ok(res
+        .hits
+        .into_iter()
+        .filter_map(|v| match v {
+            Value::Object(v) => Some(v),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The events and the bad events of the SLO between the two times
+async fn counts(org_id: &str, slo: &Slo, start_time: i64, end_time: i64) -> Result<(i64, i64)> {
+    let hits = query(
+        org_id,
+        slo.stream_type,
+        count_sql(slo),
+        start_time,
+        end_time,
+        1,
+    )
+    .await?;
+    let hit = hits.into_iter().next().unwrap_or_default();
+    let total = hit.get("total").and_then(|v| v.as_i64()).unwrap_or(0);
+    let errors = hit.get("errors").and_then(|v| v.as_i64()).unwrap_or(0);
+    Ok((total, errors))
+}
+
+/// The windows of the rules in minutes, each once
+fn rule_windows(slo: &Slo) -> Vec<i64> {
+    let mut windows = slo
+        .rules
+        .iter()
+        .flat_map(|rule| [rule.long_window, rule.short_window])
+        .collect::<Vec<_>>();
+    windows.sort_unstable();
+    windows.dedup();
+    windows
+}
+
+/// The events and the bad events of the SLO over each window ending at
+/// `end_time`, windows in minutes. They are summed from the history the
+/// evaluations recorded before `until`, with a single query.
+async fn history_counts(
+    org_id: &str,
+    slo: &Slo,
+    windows: &[i64],
+    end_time: i64,
+    until: i64,
+) -> Result<HashMap<i64, (i64, i64)>> {
+    let mut counts: HashMap<i64, (i64, i64)> = windows.iter().map(|w| (*w, (0, 0))).collect();
+    let Some(longest) = windows.iter().max() else {
+        return Ok(counts);
+    };
+    let start_time = end_time - longest * MINUTE;
+    if start_time >= until {
+        return Ok(counts);
+    }
+    let schema = infra::schema::get(org_id, SLO_HISTORY_STREAM, StreamType::Logs).await?;
+    if schema.fields().is_empty() {
+        return Ok(counts);
+    }
+    let ts = &CONFIG.common.column_timestamp;
+    let columns = windows
+        .iter()
+        .map(|minutes| {
+            let start = end_time - minutes * MINUTE;
+            format!(
+                "SUM(CASE WHEN \"{ts}\" >= {start} THEN total ELSE 0 END) AS total_{minutes}, SUM(CASE WHEN \"{ts}\" >= {start} THEN errors ELSE 0 END) AS errors_{minutes}"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT {columns} FROM \"{SLO_HISTORY_STREAM}\" WHERE slo = '{}'",
+        slo.name.replace('\'', "''"),
+    );
+    let hits = query(org_id, StreamType::Logs, sql, start_time, until, 1).await?;
+    if let Some(hit) = hits.first() {
+        for (minutes, counts) in counts.iter_mut() {
+            let get = |column: String| hit.get(&column).and_then(|v| v.as_i64()).unwrap_or(0);
+            *counts = (
+                get(format!("total_{minutes}")),
+                get(format!("errors_{minutes}")),
+            );
+        }
+    }
+    Ok(counts)
+}
+
+/// The burn rates of the rules from the counts of their windows
+fn rule_statuses(slo: &Slo, windows: &HashMap<i64, (i64, i64)>) -> Vec<BurnRateStatus> {
+    slo.rules
+        .iter()
+        .map(|rule| {
+            let counts = |minutes| windows.get(&minutes).copied().unwrap_or_default();
+            let long_burn_rate = burn_rate(slo, counts(rule.long_window));
+            let short_burn_rate = burn_rate(slo, counts(rule.short_window));
+            BurnRateStatus {
+                name: rule.name.clone(),
+                severity: rule.severity.clone(),
+                burn_rate: rule.burn_rate,
+                long_burn_rate,
+                short_burn_rate,
+                firing: long_burn_rate > rule.burn_rate && short_burn_rate > rule.burn_rate,
+            }
+        })
+        .collect()
+}
+
+/// The state of the SLO as of its last evaluation, read from the history
+/// instead of scanning the window of the SLO.
+pub async fn status(org_id: &str, name: &str) -> Result<SloStatus> {
+    let slo = get(org_id, name).await?;
+    let end_time = Utc::now().timestamp_micros();
+    let window = slo.window * 24 * 60;
+    let mut windows = rule_windows(&slo);
+    windows.push(window);
+    let counts = history_counts(org_id, &slo, &windows, end_time, end_time).await?;
+    let (total, errors) = counts.get(&window).copied().unwrap_or_default();
+    let (sli, error_budget_remaining) = if total == 0 {
+        (100.0, 1.0)
+    } else {
+        (
+            (total - errors) as f64 * 100.0 / total as f64,
+            1.0 - burn_rate(&slo, (total, errors)),
+        )
+    };
+    Ok(SloStatus {
+        name: slo.name.clone(),
+        target: slo.target,
+        window: slo.window,
+        total,
+        errors,
+        sli,
+        error_budget_remaining,
+        rules: rule_statuses(&slo, &counts),
+    })
+}
+
+/// Evaluates the burn rate rules of the SLO, notifies the rules which start
+/// firing, records the evaluation into the history and saves which rules
+/// fire. The firing rules are saved even when recording fails, so they
+/// aren't notified again on the next evaluation.
+///
+/// Only the events since the last evaluation are counted from the stream,
+/// the windows of the rules are summed from the history.
+pub async fn run(org_id: &str, slo: &mut Slo) -> Result<()> {
+    let end_time = Utc::now().timestamp_micros();
+    let start_time = slo
+        .last_evaluated_at
+        .unwrap_or(end_time - EVALUATION_INTERVAL * 1_000_000)
+        .max(end_time - MAX_RECORD_MINUTES * MINUTE);
+    let new_counts = counts(org_id, slo, start_time, end_time).await?;
+    let windows = rule_windows(slo);
+    let mut window_counts = history_counts(org_id, slo, &windows, end_time, start_time).await?;
+    for minutes in windows {
+        let counts = window_counts.entry(minutes).or_default();
+        if end_time - minutes * MINUTE <= start_time {
+            counts.0 += new_counts.0;
+            counts.1 += new_counts.1;
+        } else {
+            // the events since the last evaluation outlast the window, after
+            // the evaluations stopped for a while
+            *counts = self::counts(org_id, slo, end_time - minutes * MINUTE, end_time).await?;
+        }
+    }
+    let rules = rule_statuses(slo, &window_counts);
+    let mut firing = vec![];
+    for rule in rules.iter().filter(|r| r.firing) {
+        if !slo.firing.contains(&rule.name) {
+            notify(org_id, slo, rule).await?;
+        }
+        firing.push(rule.name.clone());
+    }
+    slo.firing = firing;
+    // the events are recorded again with the next evaluation, it starts from
+    // the last recorded one
+    if let Err(e) = record(org_id, slo, (start_time, end_time), new_counts, &rules).await {
+        log::error!("[SLO] record history of {org_id}/{} error: {e}", slo.name);
+    }
+    db::slo::set_without_updating_trigger(org_id, slo).await?;
+    Ok(())
+}
+
+/// Records the events between the two times and the burn rates of the rules
+/// into the history stream, the dashboards and the next evaluations read the
+/// history instead of scanning the window of the SLO again.
+async fn record(
+    org_id: &str,
+    slo: &mut Slo,
+    (start_time, end_time): (i64, i64),
+    (total, errors): (i64, i64),
+    rules: &[BurnRateStatus],
+) -> Result<()> {
+    let mut record = Map::new();
+    record.insert(CONFIG.common.column_timestamp.clone(), start_time.into());
+    record.insert("slo".to_string(), slo.name.clone().into());
+    record.insert("total".to_string(), total.into());
+    record.insert("errors".to_string(), errors.into());
+    for rule in rules.iter() {
+        record.insert(rule_column(&rule.name), rule.long_burn_rate.into());
+    }
+    let body = web::Bytes::from(json::to_vec(&vec![record]).map_err(ServiceError::internal)?);
+    let resp = logs::ingest::ingest(
+        org_id,
+        SLO_HISTORY_STREAM,
+        IngestionRequest::JSON(&body),
+        0,
+        "",
+    )
+    .await?;
+    if let Some(e) = resp.error {
+        return Err(ServiceError::internal(e));
+    }
+    slo.last_evaluated_at = Some(end_time);
+    Ok(())
+}
+
+fn rule_column(rule: &str) -> String {
+    format!("burn_rate_{rule}")
+}
+
+/// Events of an interval of the history
+#[derive(Debug, Default)]
+struct Bucket {
+    timestamp: i64,
+    total: i64,
+    errors: i64,
+    rules: HashMap<String, f64>,
+}
+
+/// The error budget and the burn rates of the SLO between the two times,
+/// read from the history the evaluations recorded. `interval` is in seconds,
+/// it is picked from the time range when zero.
+pub async fn history(
+    org_id: &str,
+    name: &str,
+    start_time: i64,
+    end_time: i64,
+    interval: i64,
+) -> Result<SloHistory> {
+    let slo = get(org_id, name).await?;
+    let window = slo.window * DAY;
+    let end_time = if end_time == 0 {
+        Utc::now().timestamp_micros()
+    } else {
+        end_time
+    };
+    let start_time = if start_time == 0 {
+        end_time - window
+    } else {
+        start_time
+    };
+    if start_time >= end_time {
+        return Err(ServiceError::bad_request(
+            "start_time should be before end_time",
+        ));
+    }
+    if interval < 0 {
+        return Err(ServiceError::bad_request("interval cannot be negative"));
+    }
+    let interval = history_interval(interval, end_time - start_time, window);
+    let start_time = start_time / interval * interval;
+
+    let mut buckets = vec![];
+    let schema = infra::schema::get(org_id, SLO_HISTORY_STREAM, StreamType::Logs).await?;
+    // the history stream is deleted by the retention like any stream, there is
+    // nothing to read before it
+    let retention_days = match unwrap_stream_settings(&schema).map(|s| s.data_retention) {
+        Some(days) if days > 0 => days,
+        _ => CONFIG.compact.data_retention_days,
+    };
+    let retention_start = if retention_days > 0 {
+        Utc::now().timestamp_micros() - retention_days * DAY
+    } else {
+        0
+    };
+    if !schema.fields().is_empty() {
+        let rules = slo
+            .rules
+            .iter()
+            .map(|rule| rule_column(&rule.name))
+            .filter(|column| schema.field_with_name(column).is_ok())
+            .collect::<Vec<_>>();
+        let sql = format!(
+            "SELECT \"{}\" / {interval} * {interval} AS bucket, SUM(total) AS total, SUM(errors) AS errors{} FROM \"{SLO_HISTORY_STREAM}\" WHERE slo = '{}' GROUP BY bucket ORDER BY bucket",
+            CONFIG.common.column_timestamp,
+            rules
+                .iter()
+                .map(|column| format!(", MAX(\"{column}\") AS \"{column}\""))
+                .collect::<String>(),
+            slo.name.replace('\'', "''"),
+        );
+        let hits = query(
+            org_id,
+            StreamType::Logs,
+            sql,
+            (start_time - window).max(retention_start),
+            end_time,
+            MAX_BUCKETS,
+        )
+        .await?;
+        buckets = hits
+            .iter()
+            .map(|hit| Bucket {
+                timestamp: hit.get("bucket").and_then(|v| v.as_i64()).unwrap_or(0),
+                total: hit.get("total").and_then(|v| v.as_i64()).unwrap_or(0),
+                errors: hit.get("errors").and_then(|v| v.as_i64()).unwrap_or(0),
+                rules: slo
+                    .rules
+                    .iter()
+                    .filter_map(|rule| {
+                        hit.get(&rule_column(&rule.name))
+                            .and_then(|v| v.as_f64())
+                            .map(|v| (rule.name.clone(), v))
+                    })
+                    .collect(),
+            })
+            .collect();
+    }
+
+    Ok(SloHistory {
+        points: points(&slo, &buckets, start_time, end_time, interval),
+        name: slo.name,
+        target: slo.target,
+        window: slo.window,
+        interval: interval / 1_000_000,
+    })
+}
+
+/// The interval of the history points in microseconds, whole minutes which
+/// keep the points and the buckets read under their limits
+fn history_interval(interval: i64, range: i64, window: i64) -> i64 {
+    let interval = if interval == 0 {
+        range / MAX_POINTS
+    } else {
+        interval * 1_000_000
+    };
+    let interval = interval.max((range + window) / MAX_BUCKETS as i64);
+    ((interval + MINUTE - 1) / MINUTE).max(1) * MINUTE
+}
+
+/// One point per interval, the error budget of each point is over the window
+/// of the SLO ending with the point so `buckets` start a window earlier.
+fn points(
+    slo: &Slo,
+    buckets: &[Bucket],
+    start_time: i64,
+    end_time: i64,
+    interval: i64,
+) -> Vec<SloHistoryPoint> {
+    let window = slo.window * DAY;
+    let (mut total, mut errors) = (0, 0);
+    let (mut added, mut removed) = (0, 0);
+    let mut points = vec![];
+    let mut timestamp = start_time;
+    while timestamp < end_time {
+        while added < buckets.len() && buckets[added].timestamp <= timestamp {
+            total += buckets[added].total;
+            errors += buckets[added].errors;
+            added += 1;
+        }
+        while removed < added && buckets[removed].timestamp <= timestamp - window {
+            total -= buckets[removed].total;
+            errors -= buckets[removed].errors;
+            removed += 1;
+        }
+        let bucket = buckets[..added].last().filter(|b| b.timestamp == timestamp);
+        let counts = bucket.map_or((0, 0), |b| (b.total, b.errors));
+        points.push(SloHistoryPoint {
+            timestamp,
+            total: counts.0,
+            errors: counts.1,
+            burn_rate: burn_rate(slo, counts),
+            error_budget_remaining: 1.0 - burn_rate(slo, (total, errors)),
+            rules: bucket.map(|b| b.rules.clone()).unwrap_or_default(),
+        });
+        timestamp += interval;
+    }
+    points
+}
+
+/// Sends the firing rule through the alert destinations of the SLO
+async fn notify(org_id: &str, slo: &Slo, rule: &BurnRateStatus) -> Result<()> {
+    let message = format!(
+        "SLO {} burns its error budget {:.1}x faster than allowed, above the {}x of rule {}",
+        slo.name, rule.long_burn_rate, rule.burn_rate, rule.name
+    );
+    let alert = Alert {
+        name: format!("{} {}", slo.name, rule.name),
+        org_id: org_id.to_string(),
+        stream_type: slo.stream_type,
+        stream_name: slo.stream_name.clone(),
+        destinations: slo.destinations.clone(),
+        description: message.clone(),
+        enabled: true,
+        ..Default::default()
+    };
+    let mut row = Map::new();
+    row.insert("slo".to_string(), slo.name.clone().into());
+    row.insert("rule".to_string(), rule.name.clone().into());
+    row.insert("severity".to_string(), rule.severity.clone().into());
+    row.insert("long_burn_rate".to_string(), rule.long_burn_rate.into());
+    row.insert("short_burn_rate".to_string(), rule.short_burn_rate.into());
+    row.insert("message".to_string(), Value::String(message));
+    Ok(alert.send_notification(&[row]).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slo() -> Slo {
+        Slo {
+            name: "checkout".to_string(),
+            stream_type: StreamType::Logs,
+            stream_name: "http".to_string(),
+            error_filter: "status >= 500".to_string(),
+            total_filter: String::new(),
+            target: 99.9,
+            window: 30,
+            destinations: vec!["oncall".to_string()],
+            enabled: true,
+            rules: vec![],
+            firing: vec![],
+            last_evaluated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_generate_rules() {
+        let rules = generate_rules(30);
+        let burn_rates = rules.iter().map(|r| r.burn_rate).collect::<Vec<_>>();
+        assert_eq!(burn_rates, vec![14.4, 6.0, 3.0, 1.0]);
+        // a 3 day window has no room for the 3 day rule
+        assert_eq!(generate_rules(3).len(), 3);
+    }
+
+    #[test]
+    fn test_burn_rate() {
+        let slo = slo();
+        assert_eq!(burn_rate(&slo, (0, 0)), 0.0);
+        assert!((burn_rate(&slo, (1000, 1)) - 1.0).abs() < 1e-9);
+        assert!((burn_rate(&slo, (1000, 20)) - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_history_interval() {
+        // a day over a 30 days window, limited by the buckets read
+        assert_eq!(history_interval(0, DAY, 30 * DAY), 5 * MINUTE);
+        assert_eq!(history_interval(3600, DAY, 30 * DAY), 60 * MINUTE);
+        assert_eq!(history_interval(1, MINUTE, MINUTE), MINUTE);
+    }
+
+    #[test]
+    fn test_points() {
+        let mut slo = slo();
+        slo.window = 1;
+        let bucket = |timestamp, total, errors| Bucket {
+            timestamp,
+            total,
+            errors,
+            ..Default::default()
+        };
+        // the first bucket is a window before the points
+        let buckets = vec![
+            bucket(0, 1000, 1),
+            bucket(DAY, 1000, 0),
+            bucket(DAY + 2 * MINUTE, 1000, 2),
+        ];
+        let points = points(&slo, &buckets, DAY, DAY + 3 * MINUTE, MINUTE);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].total, 1000);
+        assert_eq!(points[0].burn_rate, 0.0);
+        assert!((points[0].error_budget_remaining - 1.0).abs() < 1e-9);
+        assert_eq!(points[1].total, 0);
+        assert!((points[2].burn_rate - 2.0).abs() < 1e-9);
+        assert!((points[2].error_budget_remaining - 0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut slo = slo();
+        assert!(validate(&mut slo).is_ok());
+        slo.target = 100.0;
+        assert!(validate(&mut slo).is_err());
+        slo.target = 99.0;
+        slo.error_filter = "status >= 500; DROP".to_string();
+        assert!(validate(&mut slo).is_err());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("error_filter", "status >= 500 OR  code = 'x'").unwrap(),
+            "status >= 500 OR code = 'x'"
+        );
+        // a comment can't swallow the rest of the query
+        assert_eq!(
+            parse_filter("error_filter", "status >= 500 -- ").unwrap(),
+            "status >= 500"
+        );
+        assert!(parse_filter("error_filter", "").is_err());
+        assert!(parse_filter("error_filter", "status >= 500) OR (1 = 1").is_err());
+        assert!(parse_filter("error_filter", "status >= 500 UNION SELECT 1").is_err());
+        assert!(
+            parse_filter(
+                "error_filter",
+                "EXISTS (SELECT * FROM \"other\" WHERE secret = 'x')"
+            )
+            .is_err()
+        );
+        assert!(parse_filter("error_filter", "user IN (SELECT user FROM \"other\")").is_err());
+    }
+
+    #[test]
+    fn test_rule_statuses() {
+        let mut slo = slo();
+        slo.rules = generate_rules(slo.window);
+        let windows = HashMap::from([(60, (1000, 20)), (5, (100, 2))]);
+        let rules = rule_statuses(&slo, &windows);
+        assert_eq!(rules.len(), 4);
+        assert!(rules[0].firing);
+        assert!((rules[0].long_burn_rate - 20.0).abs() < 1e-9);
+        // no events over the windows of the other rules
+        assert!(!rules[1].firing);
+        assert_eq!(rules[1].long_burn_rate, 0.0);
+        assert_eq!(rule_windows(&slo), vec![5, 30, 60, 120, 360, 1440, 4320]);
+    }
+}
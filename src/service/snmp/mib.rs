@@ -0,0 +1,340 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A small SMI reader that is just good enough to map OIDs to object names.
+//! It understands `OBJECT IDENTIFIER` assignments, the SMIv2 macros
+//! (`OBJECT-TYPE`, `NOTIFICATION-TYPE`, `MODULE-IDENTITY`, ...) and SMIv1
+//! `TRAP-TYPE`, and ignores everything else.
+
+use hashbrown::HashMap;
+
+use crate::common::infra::config::{SNMP_MIBS, SNMP_OID_NAMES};
+
+const MACROS: [&str; 8] = [
+    "OBJECT-TYPE",
+    "NOTIFICATION-TYPE",
+    "MODULE-IDENTITY",
+    "OBJECT-IDENTITY",
+    "OBJECT-GROUP",
+    "NOTIFICATION-GROUP",
+    "MODULE-COMPLIANCE",
+    "AGENT-CAPABILITIES",
+];
+
+/// Registration tree roots and the objects every trap carries, so traps
+/// are readable even before any MIB is uploaded.
+const WELL_KNOWN: [(&str, &str); 26] = [
+    ("iso", "1"),
+    ("org", "1.3"),
+    ("dod", "1.3.6"),
+    ("internet", "1.3.6.1"),
+    ("directory", "1.3.6.1.1"),
+    ("mgmt", "1.3.6.1.2"),
+    ("mib-2", "1.3.6.1.2.1"),
+    ("system", "1.3.6.1.2.1.1"),
+    ("sysUpTime", "1.3.6.1.2.1.1.3"),
+    ("interfaces", "1.3.6.1.2.1.2"),
+    ("transmission", "1.3.6.1.2.1.10"),
+    ("experimental", "1.3.6.1.3"),
+    ("private", "1.3.6.1.4"),
+    ("enterprises", "1.3.6.1.4.1"),
+    ("security", "1.3.6.1.5"),
+    ("snmpV2", "1.3.6.1.6"),
+    ("snmpModules", "1.3.6.1.6.3"),
+    ("snmpMIBObjects", "1.3.6.1.6.3.1.1"),
+    ("snmpTrapOID", "1.3.6.1.6.3.1.1.4.1"),
+    ("snmpTrapEnterprise", "1.3.6.1.6.3.1.1.4.3"),
+    ("coldStart", "1.3.6.1.6.3.1.1.5.1"),
+    ("warmStart", "1.3.6.1.6.3.1.1.5.2"),
+    ("linkDown", "1.3.6.1.6.3.1.1.5.3"),
+    ("linkUp", "1.3.6.1.6.3.1.1.5.4"),
+    ("authenticationFailure", "1.3.6.1.6.3.1.1.5.5"),
+    ("zeroDotZero", "0.0"),
+];
+
+/// Object definition as written in the MIB: a name and its OID components,
+/// where the first component may be a symbolic parent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub components: Vec<String>,
+}
+
+/// Rebuilds the global OID name table from all cached MIBs.
+pub fn compile() {
+    let defs = SNMP_MIBS
+        .iter()
+        .flat_map(|mib| parse(&mib.content))
+        .collect::<Vec<_>>();
+    let names = compile_definitions(&defs);
+    *SNMP_OID_NAMES.write() = names;
+}
+
+/// Returns the object name for an OID, keeping the instance suffix, e.g.
+/// `1.3.6.1.2.1.2.2.1.1.3` becomes `ifIndex.3`.
+pub fn resolve(oid: &str) -> Option<String> {
+    let names = SNMP_OID_NAMES.read();
+    if names.is_empty() {
+        resolve_with(&well_known(), oid)
+    } else {
+        resolve_with(&names, oid)
+    }
+}
+
+pub fn resolve_with(names: &HashMap<String, String>, oid: &str) -> Option<String> {
+    let parts = oid.split('.').collect::<Vec<_>>();
+    for i in (1..=parts.len()).rev() {
+        let prefix = parts[..i].join(".");
+        if let Some(name) = names.get(&prefix) {
+            return Some(if i == parts.len() {
+                name.to_string()
+            } else {
+                format!("{name}.{}", parts[i..].join("."))
+            });
+        }
+    }
+    None
+}
+
+fn well_known() -> HashMap<String, String> {
+    WELL_KNOWN
+        .iter()
+        .map(|(name, oid)| (oid.to_string(), name.to_string()))
+        .collect()
+}
+
+/// Resolves symbolic parents across all definitions and returns an
+/// `oid -> name` map. Definitions whose parent is unknown are dropped.
+pub fn compile_definitions(defs: &[Definition]) -> HashMap<String, String> {
+    let mut oids: HashMap<String, String> = WELL_KNOWN
+        .iter()
+        .map(|(name, oid)| (name.to_string(), oid.to_string()))
+        .collect();
+    let mut pending = defs.iter().collect::<Vec<_>>();
+    // parents can be defined after their children or in another module
+    loop {
+        let before = pending.len();
+        pending.retain(|def| {
+            let Some(first) = def.components.first() else {
+                return false;
+            };
+            let base = if first.chars().all(|c| c.is_ascii_digit()) {
+                first.to_string()
+            } else if let Some(oid) = oids.get(first) {
+                oid.to_string()
+            } else {
+                return true;
+            };
+            let oid = std::iter::once(base)
+                .chain(def.components[1..].iter().cloned())
+                .collect::<Vec<_>>()
+                .join(".");
+            oids.insert(def.name.to_string(), oid);
+            false
+        });
+        if pending.is_empty() || pending.len() == before {
+            break;
+        }
+    }
+    oids.into_iter().map(|(name, oid)| (oid, name)).collect()
+}
+
+/// Extracts OID definitions from the text of a MIB module.
+pub fn parse(content: &str) -> Vec<Definition> {
+    let tokens = tokenize(content);
+    let mut defs = Vec::new();
+    let mut pending: Option<String> = None;
+    let mut enterprise: Option<String> = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i].as_str();
+        if MACROS.contains(&token) || token == "TRAP-TYPE" {
+            pending = i
+                .checked_sub(1)
+                .map(|p| tokens[p].as_str())
+                .filter(|name| is_identifier(name))
+                .map(|name| name.to_string());
+            enterprise = None;
+        } else if token == "ENTERPRISE" && i + 1 < tokens.len() {
+            enterprise = Some(tokens[i + 1].to_string());
+        } else if token == "::=" && i + 1 < tokens.len() {
+            if tokens[i + 1] == "{" {
+                let name = if i >= 3
+                    && tokens[i - 2] == "OBJECT"
+                    && tokens[i - 1] == "IDENTIFIER"
+                    && is_identifier(&tokens[i - 3])
+                {
+                    Some(tokens[i - 3].to_string())
+                } else {
+                    pending.take()
+                };
+                let end = tokens[i + 1..]
+                    .iter()
+                    .position(|t| t == "}")
+                    .map(|p| i + 1 + p)
+                    .unwrap_or(tokens.len());
+                if let Some(name) = name {
+                    let components = components(&tokens[i + 2..end]);
+                    if !components.is_empty() {
+                        defs.push(Definition { name, components });
+                    }
+                }
+                i = end;
+            } else if let (Some(name), Some(parent)) = (pending.take(), enterprise.take()) {
+                // SMIv1 traps are `enterprise.0.specific-trap`
+                if tokens[i + 1].chars().all(|c| c.is_ascii_digit()) {
+                    defs.push(Definition {
+                        name,
+                        components: vec![parent, "0".to_string(), tokens[i + 1].to_string()],
+                    });
+                }
+            }
+        }
+        i += 1;
+    }
+    defs
+}
+
+/// Turns `iso org(3) dod(6) 1` or `ifEntry 1` into OID components.
+fn components(tokens: &[String]) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] == "(" && i + 1 < tokens.len() {
+            if let Some(last) = parts.last_mut() {
+                *last = tokens[i + 1].to_string();
+            }
+            i += 3; // skip `n )`
+            continue;
+        }
+        parts.push(tokens[i].to_string());
+        i += 1;
+    }
+    parts
+}
+
+fn is_identifier(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_lowercase())
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn tokenize(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                // descriptions may contain anything, drop them
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                }
+                push_token(&mut tokens, &mut current);
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                push_token(&mut tokens, &mut current);
+            }
+            '{' | '}' | '(' | ')' | ',' | ';' => {
+                push_token(&mut tokens, &mut current);
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => push_token(&mut tokens, &mut current),
+            c => current.push(c),
+        }
+    }
+    push_token(&mut tokens, &mut current);
+    tokens
+}
+
+fn push_token(tokens: &mut Vec<String>, current: &mut String) {
+    if !current.is_empty() {
+        tokens.push(std::mem::take(current));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: &str = r#"
+ACME-MIB DEFINITIONS ::= BEGIN
+IMPORTS
+    MODULE-IDENTITY, OBJECT-TYPE, NOTIFICATION-TYPE, enterprises
+        FROM SNMPv2-SMI;
+
+acme MODULE-IDENTITY
+    LAST-UPDATED "202401010000Z"
+    DESCRIPTION "Acme ::= { bogus 1 } hardware"
+    ::= { enterprises 99999 }
+
+acmeObjects OBJECT IDENTIFIER ::= { acme 1 }
+
+-- fan state ::= { nothing }
+acmeFanState OBJECT-TYPE
+    SYNTAX INTEGER { ok(1), failed(2) }
+    MAX-ACCESS read-only
+    STATUS current
+    DESCRIPTION "fan state"
+    ::= { acmeObjects 2 }
+
+acmeFanFailed NOTIFICATION-TYPE
+    OBJECTS { acmeFanState }
+    STATUS current
+    ::= { acme 0 1 }
+
+acmePsuFailed TRAP-TYPE
+    ENTERPRISE acme
+    VARIABLES { acmeFanState }
+    ::= 3
+
+acmeAlt OBJECT IDENTIFIER ::= { iso org(3) dod(6) 1 4 1 99998 }
+END
+"#;
+
+    #[test]
+    fn test_parse_and_resolve() {
+        let defs = parse(MIB);
+        assert_eq!(defs.len(), 6);
+        let names = compile_definitions(&defs);
+        assert_eq!(
+            resolve_with(&names, "1.3.6.1.4.1.99999").as_deref(),
+            Some("acme")
+        );
+        assert_eq!(
+            resolve_with(&names, "1.3.6.1.4.1.99999.1.2.0").as_deref(),
+            Some("acmeFanState.0")
+        );
+        assert_eq!(
+            resolve_with(&names, "1.3.6.1.4.1.99999.0.1").as_deref(),
+            Some("acmeFanFailed")
+        );
+        assert_eq!(
+            resolve_with(&names, "1.3.6.1.4.1.99999.0.3").as_deref(),
+            Some("acmePsuFailed")
+        );
+        assert_eq!(
+            resolve_with(&names, "1.3.6.1.4.1.99998").as_deref(),
+            Some("acmeAlt")
+        );
+        assert_eq!(resolve_with(&names, "2.1"), None);
+    }
+}
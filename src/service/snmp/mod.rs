@@ -0,0 +1,220 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io;
+
+use actix_web::{http::StatusCode, HttpResponse};
+use config::ider;
+
+use crate::{
+    common::{
+        infra::config::{SNMP_MIBS, SNMP_TRAP_ROUTES},
+        meta::{
+            http::HttpResponse as MetaHttpResponse,
+            snmp::{SnmpMib, SnmpMibList, SnmpTrapRoute, SnmpTrapRoutes},
+        },
+    },
+    service::db::snmp,
+};
+
+pub mod mib;
+
+#[tracing::instrument(skip_all)]
+pub async fn create_route(mut route: SnmpTrapRoute) -> Result<HttpResponse, io::Error> {
+    if route.org_id.trim().is_empty()
+        || route.stream_name.trim().is_empty()
+        || (route.community.is_empty() && route.engine_id.is_empty())
+    {
+        return Ok(Response::BadRequest(
+            "Please provide stream name/org_id and community or engine_id for route".to_owned(),
+        )
+        .into());
+    }
+    if let Some(e) = validate_users(&route) {
+        return Ok(Response::BadRequest(e).into());
+    }
+    if let Some(existing) = find_conflict(&route) {
+        return Ok(Response::BadRequest(format!(
+            "Provided community/engine_id is already routed for organization {}",
+            existing.org_id
+        ))
+        .into());
+    }
+
+    route.id = ider::generate();
+    if let Err(e) = snmp::set(&route).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    tracing::info!(id = route.id, "SNMP trap route created");
+    Ok(HttpResponse::Created().json(route))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn update_route(id: &str, route: &mut SnmpTrapRoute) -> Result<HttpResponse, io::Error> {
+    route.id = id.to_owned();
+    let old_route = match snmp::get(id).await {
+        Ok(route) => route,
+        Err(error) => {
+            tracing::info!(%error, id, "SNMP trap route not found");
+            return Ok(Response::NotFound("SNMP trap route not found".to_owned()).into());
+        }
+    };
+    if route.org_id.is_empty() {
+        route.org_id = old_route.org_id.clone();
+    }
+    if route.stream_name.is_empty() {
+        route.stream_name = old_route.stream_name.clone();
+    }
+    if route.community.is_empty() && route.engine_id.is_empty() {
+        route.community = old_route.community.clone();
+        route.engine_id = old_route.engine_id.clone();
+    }
+    if route.users.is_empty() {
+        route.users = old_route.users.clone();
+    }
+    if let Some(e) = validate_users(route) {
+        return Ok(Response::BadRequest(e).into());
+    }
+
+    if route == &old_route {
+        return Ok(HttpResponse::Ok().json(route));
+    }
+    if let Some(existing) = find_conflict(route) {
+        return Ok(Response::BadRequest(format!(
+            "Provided community/engine_id is already routed for organization {}",
+            existing.org_id
+        ))
+        .into());
+    }
+
+    if let Err(error) = snmp::set(route).await {
+        tracing::error!(%error, id, "Failed to save the snmp trap route");
+        return Ok(Response::InternalServerError(error).into());
+    }
+    Ok(HttpResponse::Ok().json(route))
+}
+
+#[tracing::instrument]
+pub async fn list_routes() -> Result<HttpResponse, io::Error> {
+    Ok(HttpResponse::Ok().json(SnmpTrapRoutes {
+        routes: snmp::list().await.unwrap(),
+    }))
+}
+
+#[tracing::instrument]
+pub async fn delete_route(id: &str) -> Result<HttpResponse, io::Error> {
+    let resp = if snmp::delete(id).await.is_err() {
+        Response::NotFound("SNMP trap route not found".to_owned())
+    } else {
+        Response::OkMessage("SNMP trap route deleted".to_owned())
+    };
+    Ok(resp.into())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn save_mib(mib: SnmpMib) -> Result<HttpResponse, io::Error> {
+    if mib.name.trim().is_empty() {
+        return Ok(Response::BadRequest("Please provide a name for the MIB".to_owned()).into());
+    }
+    if mib::parse(&mib.content).is_empty() {
+        return Ok(Response::BadRequest(
+            "No object definitions found in the provided MIB".to_owned(),
+        )
+        .into());
+    }
+    if let Err(e) = snmp::set_mib(&mib).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    tracing::info!(name = mib.name, "SNMP MIB saved");
+    Ok(Response::OkMessage("SNMP MIB saved".to_owned()).into())
+}
+
+#[tracing::instrument]
+pub async fn list_mibs() -> Result<HttpResponse, io::Error> {
+    let mut list = SNMP_MIBS
+        .iter()
+        .map(|mib| mib.key().to_string())
+        .collect::<Vec<_>>();
+    list.sort();
+    Ok(HttpResponse::Ok().json(SnmpMibList { list }))
+}
+
+#[tracing::instrument]
+pub async fn delete_mib(name: &str) -> Result<HttpResponse, io::Error> {
+    if !SNMP_MIBS.contains_key(name) {
+        return Ok(Response::NotFound("SNMP MIB not found".to_owned()).into());
+    }
+    let resp = match snmp::delete_mib(name).await {
+        Ok(_) => Response::OkMessage("SNMP MIB deleted".to_owned()),
+        Err(e) => Response::InternalServerError(e),
+    };
+    Ok(resp.into())
+}
+
+/// The v3 traps are only accepted from the users of the route, with a
+/// password long enough to derive their key (RFC 3414).
+fn validate_users(route: &SnmpTrapRoute) -> Option<String> {
+    if !route.engine_id.is_empty() && route.users.is_empty() {
+        return Some("Please provide the SNMPv3 users of the engine_id".to_owned());
+    }
+    for user in route.users.iter() {
+        if user.name.is_empty() {
+            return Some("Please provide a name for the SNMPv3 user".to_owned());
+        }
+        if user.auth_password.len() < 8 {
+            return Some(format!(
+                "The password of SNMPv3 user {} must have at least 8 characters",
+                user.name
+            ));
+        }
+    }
+    None
+}
+
+fn find_conflict(route: &SnmpTrapRoute) -> Option<SnmpTrapRoute> {
+    SNMP_TRAP_ROUTES.iter().find_map(|existing| {
+        let conflict = existing.id != route.id
+            && ((!route.community.is_empty() && existing.community == route.community)
+                || (!route.engine_id.is_empty()
+                    && existing.engine_id.eq_ignore_ascii_case(&route.engine_id)));
+        conflict.then(|| existing.value().clone())
+    })
+}
+
+#[derive(Debug)]
+enum Response {
+    OkMessage(String),
+    NotFound(String),
+    InternalServerError(anyhow::Error),
+    BadRequest(String),
+}
+
+impl From<Response> for HttpResponse {
+    fn from(resp: Response) -> Self {
+        match resp {
+            Response::OkMessage(message) => {
+                Self::Ok().json(MetaHttpResponse::message(StatusCode::OK.into(), message))
+            }
+            Response::NotFound(err) => {
+                Self::NotFound().json(MetaHttpResponse::error(StatusCode::NOT_FOUND.into(), err))
+            }
+            Response::InternalServerError(err) => Self::InternalServerError().json(
+                MetaHttpResponse::error(StatusCode::INTERNAL_SERVER_ERROR.into(), err.to_string()),
+            ),
+            Response::BadRequest(err) => Self::BadRequest()
+                .json(MetaHttpResponse::error(StatusCode::BAD_REQUEST.into(), err)),
+        }
+    }
+}
@@ -13,34 +13,61 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, io::Error};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use actix_web::{http, http::StatusCode, HttpResponse};
+use chrono::{TimeZone, Utc};
 use config::{
+    cluster::LOCAL_NODE_UUID,
     is_local_disk_storage,
     meta::{
-        stream::{StreamSettings, StreamStats, StreamType},
-        usage::Stats,
+        cluster::Role,
+        stream::{
+            FileKey, FileMeta, ParquetOptions, PartitionTimeLevel, StreamOwner, StreamSettings,
+            StreamStats, StreamType,
+        },
+        usage::{Stats, STATS_STREAM, TRIGGERS_USAGE_STREAM, USAGE_STREAM},
     },
-    utils::json,
+    utils::{json, time},
     CONFIG, SIZE_IN_MB, SQL_FULL_TEXT_SEARCH_FIELDS,
 };
 use datafusion::arrow::datatypes::Schema;
 use infra::{
     cache::stats,
+    dist_lock, file_list as infra_file_list,
     schema::{
-        unwrap_partition_time_level, unwrap_stream_settings, STREAM_SCHEMAS, STREAM_SETTINGS,
+        get_settings_revision, unwrap_partition_time_level, unwrap_stream_settings, STREAM_SCHEMAS,
+        STREAM_SETTINGS,
     },
+    storage,
 };
 
 use crate::{
-    common::meta::{
-        authz::Authz,
-        http::HttpResponse as MetaHttpResponse,
-        prom,
-        stream::{Stream, StreamProperty},
+    common::{
+        infra::{
+            cluster::{get_node_by_uuid, get_node_from_consistent_hash},
+            config::{STREAM_FUNCTIONS, SYSLOG_ROUTES, USERS},
+        },
+        meta::{
+            authz::Authz,
+            cdc::CdcObjectType,
+            organization::StreamTemplate,
+            prom,
+            stream::{
+                RepartitionStatus, Stream, StreamGroup, StreamProperty, StreamRenameJob,
+                StreamSettingsBulk, StreamSettingsBulkResult, StreamStatsHistory, StreamStatsPoint,
+            },
+            webhooks::{StreamEvent, StreamEventType},
+        },
+        utils::auth::is_root_user,
+    },
+    service::{
+        cdc, compact, db,
+        error::{Result, ServiceError},
+        file_list,
+        ingestion::reserved::SYSTEM_COLUMNS,
+        metrics::get_prom_metadata_from_schema,
+        search as SearchService, webhooks,
     },
-    service::{db, metrics::get_prom_metadata_from_schema, search as SearchService},
 };
 
 const LOCAL: &str = "disk";
@@ -50,54 +77,109 @@ pub async fn get_stream(
     org_id: &str,
     stream_name: &str,
     stream_type: StreamType,
-) -> Result<HttpResponse, Error> {
-    let schema = infra::schema::get(org_id, stream_name, stream_type)
-        .await
-        .unwrap();
+) -> Result<(Stream, u64)> {
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema == Schema::empty() {
+        return Err(ServiceError::not_found("stream not found"));
+    }
 
     let mut stats = stats::get_stream_stats(org_id, stream_name, stream_type);
     transform_stats(&mut stats);
-    if schema != Schema::empty() {
-        let stream = stream_res(stream_name, stream_type, schema, Some(stats));
-        Ok(HttpResponse::Ok().json(stream))
-    } else {
-        Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
-            StatusCode::NOT_FOUND.into(),
-            "stream not found".to_string(),
-        )))
+    let revision = get_settings_revision(&schema);
+    let mut stream = stream_res(stream_name, stream_type, schema, Some(stats));
+    stream.owner_stale = is_stale_owner(org_id, stream.owner.as_ref());
+    if stream.settings.has_quota() {
+        stream.quota_usage = Some(
+            crate::service::ingestion::quota::get_usage(org_id, stream_type, stream_name)
+                .await
+                .unwrap_or_default(),
+        );
+    }
+    Ok((stream, revision))
+}
+
+pub async fn get_stream_stats_history(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    period: &str,
+) -> Result<StreamStatsHistory> {
+    let period = match time::parse_milliseconds(period) {
+        Ok(v) if v > 0 => v as i64 * 1000,
+        _ => {
+            return Err(ServiceError::bad_request(format!(
+                "invalid period: {period}"
+            )));
+        }
+    };
+    let end = Utc::now().timestamp_micros();
+    let stream_key = format!("{org_id}/{stream_type}/{stream_name}");
+    let history = infra_file_list::get_stream_stats_history(&stream_key, (end - period, end))
+        .await
+        .map_err(ServiceError::internal)?;
+
+    let current = stats::get_stream_stats(org_id, stream_name, stream_type);
+    Ok(StreamStatsHistory {
+        name: stream_name.to_string(),
+        stream_type,
+        points: history_points(history, (end, current)),
+    })
+}
+
+/// The points of the stats history in MB, ending with the current stats.
+fn history_points(
+    history: Vec<(i64, StreamStats)>,
+    current: (i64, StreamStats),
+) -> Vec<StreamStatsPoint> {
+    let mut points: Vec<StreamStatsPoint> = Vec::with_capacity(history.len() + 1);
+    for (ts, mut stats) in history.into_iter().chain(std::iter::once(current)) {
+        transform_stats(&mut stats);
+        let point = StreamStatsPoint {
+            timestamp: ts,
+            doc_num: stats.doc_num,
+            file_num: stats.file_num,
+            storage_size: stats.storage_size,
+            compressed_size: stats.compressed_size,
+        };
+        // a restarted node may snapshot the same point twice
+        match points.last_mut() {
+            Some(last) if last.timestamp == ts => *last = point,
+            _ => points.push(point),
+        }
     }
+    points
 }
 
+/// Lists the streams of the org, limited to the `permitted_streams` when the
+/// caller's permissions are known. Internal streams are only listed when
+/// `include_internal` is set.
 pub async fn get_streams(
     org_id: &str,
     stream_type: Option<StreamType>,
     fetch_schema: bool,
     permitted_streams: Option<Vec<String>>,
+    include_internal: bool,
 ) -> Vec<Stream> {
     let indices = db::schema::list(org_id, stream_type, fetch_schema)
         .await
         .unwrap_or_default();
 
-    let filtered_indices = if let Some(s_type) = stream_type {
-        match permitted_streams {
-            Some(permitted_streams) => {
-                if permitted_streams.contains(&format!("{}:_all_{}", s_type, org_id)) {
-                    indices
-                } else {
-                    indices
-                        .into_iter()
-                        .filter(|stream_loc| {
-                            permitted_streams
-                                .contains(&format!("{}:{}", s_type, stream_loc.stream_name))
-                        })
-                        .collect::<Vec<_>>()
-                }
-            }
-            None => indices,
-        }
-    } else {
-        indices
-    };
+    let filtered_indices = indices
+        .into_iter()
+        .filter(|stream_loc| {
+            include_internal
+                || !is_internal_stream(org_id, stream_loc.stream_type, &stream_loc.stream_name)
+        })
+        .filter(|stream_loc| match &permitted_streams {
+            Some(permitted_streams) => is_permitted_stream(
+                org_id,
+                stream_loc.stream_type,
+                &stream_loc.stream_name,
+                permitted_streams,
+            ),
+            None => true,
+        })
+        .collect::<Vec<_>>();
     let mut indices_res = Vec::with_capacity(filtered_indices.len());
     for stream_loc in filtered_indices {
         let mut stats = stats::get_stream_stats(
@@ -105,26 +187,143 @@ pub async fn get_streams(
             stream_loc.stream_name.as_str(),
             stream_loc.stream_type,
         );
-        if stats.eq(&StreamStats::default()) {
-            indices_res.push(stream_res(
+        let mut stream = if stats.eq(&StreamStats::default()) {
+            stream_res(
                 stream_loc.stream_name.as_str(),
                 stream_loc.stream_type,
                 stream_loc.schema,
                 None,
-            ));
+            )
         } else {
             transform_stats(&mut stats);
-            indices_res.push(stream_res(
+            stream_res(
                 stream_loc.stream_name.as_str(),
                 stream_loc.stream_type,
                 stream_loc.schema,
                 Some(stats),
-            ));
-        }
+            )
+        };
+        stream.owner_stale = is_stale_owner(org_id, stream.owner.as_ref());
+        indices_res.push(stream);
     }
     indices_res
 }
 
+/// Parses the label filters of the stream list, e.g. `team:payments,env:prod`.
+pub fn parse_label_filters(filters: &str) -> Result<Vec<(String, String)>> {
+    filters
+        .split(',')
+        .filter(|filter| !filter.trim().is_empty())
+        .map(|filter| match filter.split_once(':') {
+            Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+            None => Err(ServiceError::bad_request(format!(
+                "label filter [{filter}] must be written as key:value"
+            ))),
+        })
+        .collect()
+}
+
+/// Keeps the streams having all the labels.
+pub fn filter_by_labels(streams: Vec<Stream>, labels: &[(String, String)]) -> Vec<Stream> {
+    if labels.is_empty() {
+        return streams;
+    }
+    streams
+        .into_iter()
+        .filter(|stream| {
+            labels
+                .iter()
+                .all(|(key, value)| stream.settings.labels.get(key) == Some(value))
+        })
+        .collect()
+}
+
+/// Groups the streams by the value of their `label`, the streams without the
+/// label are grouped last under an empty value.
+pub fn group_by_label(streams: Vec<Stream>, label: &str) -> Vec<StreamGroup> {
+    let mut groups: Vec<StreamGroup> = Vec::new();
+    for stream in streams {
+        let value = stream
+            .settings
+            .labels
+            .get(label)
+            .cloned()
+            .unwrap_or_default();
+        match groups.iter_mut().find(|group| group.value == value) {
+            Some(group) => group.list.push(stream),
+            None => groups.push(StreamGroup {
+                value,
+                list: vec![stream],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| {
+        a.value
+            .is_empty()
+            .cmp(&b.value.is_empty())
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    groups
+}
+
+fn owner_exists(org_id: &str, user_id: &str) -> bool {
+    USERS.contains_key(&format!("{org_id}/{user_id}")) || is_root_user(user_id)
+}
+
+/// The owning user left the org, checked against the users known to every node
+/// so that all of them flag the stream the same way.
+fn is_stale_owner(org_id: &str, owner: Option<&StreamOwner>) -> bool {
+    owner.is_some_and(|owner| !owner.user.is_empty() && !owner_exists(org_id, &owner.user))
+}
+
+/// The streams whose owning user left the org, as `org/stream_type/stream`, so
+/// that someone else can be found before their retention or deletion is
+/// decided.
+pub async fn check_stale_owners() -> Vec<String> {
+    let mut stale = STREAM_SETTINGS
+        .read()
+        .await
+        .iter()
+        .filter(|(key, settings)| {
+            let org_id = key.split('/').next().unwrap_or_default();
+            is_stale_owner(org_id, settings.owner.as_ref())
+        })
+        .map(|(key, _)| key.to_string())
+        .collect::<Vec<_>>();
+    stale.sort();
+    stale
+}
+
+/// Streams the usage reporting writes into the usage org, the audit stream
+/// comes from the enterprise auditor.
+const USAGE_ORG_STREAMS: [&str; 4] = [USAGE_STREAM, STATS_STREAM, TRIGGERS_USAGE_STREAM, "audit"];
+
+/// Streams derived by OpenObserve itself and the usage streams of the usage
+/// org, the other streams of the usage org are regular streams.
+pub fn is_internal_stream(org_id: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    (org_id == CONFIG.common.usage_org
+        && stream_type == StreamType::Logs
+        && USAGE_ORG_STREAMS.contains(&stream_name))
+        || matches!(
+            stream_type,
+            StreamType::Metadata | StreamType::Index | StreamType::Filelist
+        )
+}
+
+fn is_permitted_stream(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    permitted_streams: &[String],
+) -> bool {
+    // enrichment tables and metadata streams are not covered by the permissions
+    matches!(
+        stream_type,
+        StreamType::EnrichmentTables | StreamType::Metadata
+    ) || permitted_streams.contains(&format!("{stream_type}:_all_{org_id}"))
+        || permitted_streams.contains(&format!("{stream_type}:{stream_name}"))
+}
+
 pub fn stream_res(
     stream_name: &str,
     stream_type: StreamType,
@@ -132,14 +331,8 @@ pub fn stream_res(
     stats: Option<StreamStats>,
 ) -> Stream {
     let storage_type = if is_local_disk_storage() { LOCAL } else { S3 };
-    let mappings = schema
-        .fields()
-        .iter()
-        .map(|field| StreamProperty {
-            prop_type: field.data_type().to_string(),
-            name: field.name().to_string(),
-        })
-        .collect::<Vec<_>>();
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let mappings = stream_properties(&schema, &settings);
 
     let mut stats = match stats {
         Some(v) => v,
@@ -166,7 +359,6 @@ pub fn stream_res(
         None
     };
 
-    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
     settings.partition_time_level = Some(unwrap_partition_time_level(
         settings.partition_time_level,
         stream_type,
@@ -178,45 +370,303 @@ pub fn stream_res(
         stream_type,
         schema: mappings,
         stats,
+        owner: settings.owner.clone(),
+        owner_stale: false,
+        quota_usage: None,
         settings,
         metrics_meta,
     }
 }
 
+/// The fields of the schema with the metadata and unit set in the settings.
+fn stream_properties(schema: &Schema, settings: &StreamSettings) -> Vec<StreamProperty> {
+    schema
+        .fields()
+        .iter()
+        .filter(|field| field.name() != super::original::ORIGINAL_COLUMN)
+        .map(|field| {
+            let metadata = settings.field_metadata.get(field.name());
+            StreamProperty {
+                prop_type: field.data_type().to_string(),
+                name: field.name().to_string(),
+                description: metadata.map(|m| m.description.clone()).unwrap_or_default(),
+                unit: settings
+                    .field_display
+                    .get(field.name())
+                    .map(|d| d.unit.clone())
+                    .unwrap_or_default(),
+                tags: metadata.map(|m| m.tags.clone()).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the fields of the stream, only the ones with the tag if one is
+/// given.
+pub async fn list_fields(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    tag: Option<&str>,
+) -> Result<Vec<StreamProperty>> {
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(ServiceError::not_found(format!(
+            "stream [{stream_name}] not found"
+        )));
+    }
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let mut fields = stream_properties(&schema, &settings);
+    if let Some(tag) = tag {
+        fields.retain(|f| f.tags.iter().any(|t| t == tag));
+    }
+    Ok(fields)
+}
+
+/// Checks the partition and full text search keys of the settings.
+fn validate_keys(settings: &StreamSettings) -> Result<()> {
+    for key in settings.partition_keys.iter() {
+        if key.field.is_empty()
+            || SQL_FULL_TEXT_SEARCH_FIELDS.contains(&key.field)
+            || SYSTEM_COLUMNS.contains(&key.field)
+        {
+            return Err(ServiceError::bad_request(format!(
+                "field [{}] can't be used for partition key",
+                key.field
+            )));
+        }
+    }
+    for key in settings.full_text_search_keys.iter() {
+        if key.is_empty() || SYSTEM_COLUMNS.contains(key) {
+            return Err(ServiceError::bad_request(format!(
+                "field [{key}] can't be used for full text search key"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the stream template of an org, the new streams start with its
+/// settings without going through [save_stream_settings].
+pub fn validate_stream_template(template: &StreamTemplate) -> Result<()> {
+    if template.data_retention < 0 {
+        return Err(ServiceError::bad_request(
+            "data_retention of the stream template can't be negative".to_string(),
+        ));
+    }
+    validate_keys(&template.settings())
+}
+
 #[tracing::instrument(skip(settings))]
 pub async fn save_stream_settings(
     org_id: &str,
     stream_name: &str,
     stream_type: StreamType,
     mut settings: StreamSettings,
-) -> Result<HttpResponse, Error> {
+    revision: Option<u64>,
+) -> Result<u64> {
     // check if we are allowed to ingest
     if db::compact::retention::is_deleting_stream(org_id, stream_type, stream_name, None) {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                http::StatusCode::INTERNAL_SERVER_ERROR.into(),
-                format!("stream [{stream_name}] is being deleted"),
-            )),
-        );
+        return Err(ServiceError::Conflict(format!(
+            "stream [{stream_name}] is being deleted"
+        )));
     }
 
-    for key in settings.partition_keys.iter() {
-        if SQL_FULL_TEXT_SEARCH_FIELDS.contains(&key.field) {
-            return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                format!("field [{}] can't be used for partition key", key.field),
+    validate_keys(&settings)?;
+
+    if !settings.encrypt_fields.is_empty() && !crate::service::encryption::is_enabled() {
+        return Err(ServiceError::bad_request(
+            "encrypt_fields needs ZO_ENCRYPTION_MASTER_KEY to be set",
+        ));
+    }
+    // encrypted values are randomized, they can't be used to partition or filter data
+    for field in settings.encrypt_fields.iter() {
+        if field == &CONFIG.common.column_timestamp
+            || settings.partition_keys.iter().any(|k| &k.field == field)
+            || settings.binary_fields.contains(field)
+        {
+            return Err(ServiceError::bad_request(format!(
+                "field [{field}] can't be encrypted"
+            )));
+        }
+    }
+
+    for (i, rule) in settings.drop_rules.iter().enumerate() {
+        let error = if rule.name.is_empty() {
+            Some("drop rule name is required".to_string())
+        } else if settings.drop_rules[..i].iter().any(|r| r.name == rule.name) {
+            Some(format!("drop rule [{}] is duplicated", rule.name))
+        } else if rule.conditions.is_empty() {
+            Some(format!("drop rule [{}] has no conditions", rule.name))
+        } else {
+            rule.conditions
+                .iter()
+                .find_map(|c| regex::Regex::new(&c.regex).err())
+                .map(|e| format!("drop rule [{}] has an invalid regex: {e}", rule.name))
+        };
+        if let Some(error) = error {
+            return Err(ServiceError::bad_request(error));
+        }
+    }
+
+    // the list filter is written as `key:value,key:value`
+    for (key, value) in settings.labels.iter() {
+        if key.is_empty() || key.contains([',', ':']) || value.contains(',') {
+            return Err(ServiceError::bad_request(format!(
+                "label [{key}] is invalid, keys can't be empty or contain ',' or ':' and values \
+                 can't contain ','"
+            )));
+        }
+    }
+
+    // routing to another org needs that org to accept this one as a source
+    if let Some(routing) = &settings.routing {
+        for destination in routing.keys() {
+            let Some((target_org, target_stream)) = destination.split_once('/') else {
+                continue;
+            };
+            if target_stream.is_empty()
+                || !crate::service::ingestion::is_cross_org_routing_allowed(org_id, target_org)
+                    .await
+            {
+                return Err(ServiceError::bad_request(format!(
+                    "routing to [{destination}] is not allowed, organization [{target_org}] has \
+                     to add [{org_id}] to its routing sources"
+                )));
+            }
+        }
+    }
+
+    if let Some(owner) = &settings.owner {
+        if owner.user.is_empty() && owner.team.is_empty() {
+            return Err(ServiceError::bad_request(
+                "owner needs a user or a team".to_string(),
+            ));
+        }
+        if !owner.user.is_empty() && !owner_exists(org_id, &owner.user) {
+            return Err(ServiceError::bad_request(format!(
+                "owner [{}] is not a member of the organization",
+                owner.user
             )));
         }
     }
 
+    if settings.hot_data_days < 0 {
+        return Err(ServiceError::bad_request(
+            "hot_data_days can't be negative".to_string(),
+        ));
+    }
+    if chrono::Duration::try_days(settings.hot_data_days)
+        .and_then(|d| Utc::now().checked_sub_signed(d))
+        .is_none()
+    {
+        return Err(ServiceError::bad_request(
+            "hot_data_days is too large".to_string(),
+        ));
+    }
+    if settings.data_retention < 0 {
+        return Err(ServiceError::bad_request(
+            "data_retention can't be negative".to_string(),
+        ));
+    }
+    if settings.retention_lock_days < 0 {
+        return Err(ServiceError::bad_request(
+            "retention_lock_days can't be negative".to_string(),
+        ));
+    }
+    validate_archive(&settings, infra::storage::archive::is_enabled())?;
+    if settings.flatten_level.is_some_and(|v| v < 0) {
+        return Err(ServiceError::bad_request(
+            "flatten_level can't be negative".to_string(),
+        ));
+    }
+    if !settings
+        .flatten_separator
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(ServiceError::bad_request(
+            "flatten_separator can only contain lowercase letters, digits and _".to_string(),
+        ));
+    }
+    if !settings
+        .key_normalization
+        .replacement
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(ServiceError::bad_request(
+            "key_normalization.replacement can only contain lowercase letters, digits and _"
+                .to_string(),
+        ));
+    }
+    if !settings
+        .otlp_attributes
+        .nested_column
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(ServiceError::bad_request(
+            "otlp_attributes.nested_column can only contain lowercase letters, digits and _"
+                .to_string(),
+        ));
+    }
+    if SYSTEM_COLUMNS.contains(&settings.otlp_attributes.nested_column) {
+        return Err(ServiceError::bad_request(format!(
+            "otlp_attributes.nested_column can't be the system column [{}]",
+            settings.otlp_attributes.nested_column
+        )));
+    }
+    if settings.otlp_attributes.nested_column.is_empty()
+        && !settings.otlp_attributes.promote.is_empty()
+    {
+        return Err(ServiceError::bad_request(
+            "otlp_attributes.promote requires a nested_column for the other attributes".to_string(),
+        ));
+    }
+    if let Err(e) = settings.parquet.validate() {
+        return Err(ServiceError::bad_request(format!("parquet: {e}")));
+    }
+
     // we need to keep the old partition information, because the hash bucket num can't be changed
     // get old settings and then update partition_keys
     let schema = infra::schema::get(org_id, stream_name, stream_type)
         .await
         .unwrap();
-    let mut old_partition_keys = unwrap_stream_settings(&schema)
-        .unwrap_or_default()
-        .partition_keys;
+    if let Err(e) = validate_virtual_fields(&schema, &settings)
+        .and_then(|_| validate_display_fields(&settings))
+        .and_then(|_| validate_nested_fields(&settings))
+        .and_then(|_| validate_field_metadata(&settings))
+    {
+        return Err(ServiceError::bad_request(e));
+    }
+
+    // no settings yet, the settings are created
+    let stored_settings = unwrap_stream_settings(&schema);
+    let old_settings = stored_settings.clone().unwrap_or_default();
+
+    // the retention lock keeps regulated data, it can't be weakened until it expires
+    let now = Utc::now().timestamp_micros();
+    if old_settings.is_retention_locked(now)
+        && settings.retention_lock_days < old_settings.retention_lock_days
+    {
+        return Err(ServiceError::Forbidden(format!(
+            "retention_lock_days can't be shortened, the stream is locked for {} days",
+            old_settings.retention_lock_days
+        )));
+    }
+    settings.set_retention_lock_until(&old_settings, now);
+    if settings.retention_lock_days > 0
+        && settings.data_retention > 0
+        && settings.data_retention < settings.retention_lock_days
+    {
+        return Err(ServiceError::Forbidden(format!(
+            "data_retention can't be shorter than the retention lock of {} days",
+            settings.retention_lock_days
+        )));
+    }
+
+    let mut old_partition_keys = old_settings.partition_keys.clone();
     // first disable all old partition keys
     for v in old_partition_keys.iter_mut() {
         v.disabled = true;
@@ -225,9 +675,9 @@ pub async fn save_stream_settings(
     for v in settings.partition_keys.iter() {
         if let Some(old_field) = old_partition_keys.iter_mut().find(|k| k.field == v.field) {
             if old_field.types != v.types {
-                return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    format!("field [{}] partition types can't be changed", v.field),
+                return Err(ServiceError::bad_request(format!(
+                    "field [{}] partition types can't be changed",
+                    v.field
                 )));
             }
             old_field.disabled = v.disabled;
@@ -245,112 +695,977 @@ pub async fn save_stream_settings(
             chrono::Utc::now().timestamp_micros().to_string(),
         );
     }
-    db::schema::update_setting(org_id, stream_name, stream_type, metadata)
-        .await
-        .unwrap();
-
-    Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
-        http::StatusCode::OK.into(),
-        "".to_string(),
-    )))
-}
-
-#[tracing::instrument]
-pub async fn delete_stream(
-    org_id: &str,
-    stream_name: &str,
-    stream_type: StreamType,
-) -> Result<HttpResponse, Error> {
-    let schema = infra::schema::get_versions(org_id, stream_name, stream_type)
-        .await
-        .unwrap();
-    if schema.is_empty() {
-        return Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
-            StatusCode::NOT_FOUND.into(),
-            "stream not found".to_string(),
-        )));
-    }
-
-    // create delete for compactor
     if let Err(e) =
-        db::compact::retention::delete_stream(org_id, stream_type, stream_name, None).await
+        db::schema::update_setting(org_id, stream_name, stream_type, metadata, revision).await
     {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR.into(),
-                format!("failed to delete stream: {e}"),
+        return Err(match e.downcast::<infra::errors::Error>() {
+            Ok(infra::errors::Error::DbError(infra::errors::DbError::RevisionConflict(
+                _,
+                _,
+                current,
+            ))) => ServiceError::Conflict(format!(
+                "stream [{stream_name}] settings changed, revision is {current}"
             )),
-        );
+            Ok(e) => e.into(),
+            Err(e) => e.into(),
+        });
     }
 
-    // delete stream schema
-    if let Err(e) = db::schema::delete(org_id, stream_name, Some(stream_type)).await {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR.into(),
-                format!("failed to delete stream: {e}"),
-            )),
-        );
-    }
+    cdc::record(
+        org_id,
+        CdcObjectType::StreamSettings,
+        &format!("{stream_type}/{stream_name}"),
+        stored_settings.and_then(|v| json::to_value(v).ok()),
+        json::to_value(&settings).ok(),
+    )
+    .await;
 
-    // delete stream schema cache
-    let key = format!("{org_id}/{stream_type}/{stream_name}");
-    let mut w = STREAM_SCHEMAS.write().await;
-    w.remove(&key);
-    drop(w);
+    // the historical files are moved to the new layout by the compactor
+    let enabled_keys = |s: &StreamSettings| {
+        s.partition_keys
+            .iter()
+            .filter(|k| !k.disabled)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let (keys, old_keys) = (enabled_keys(&settings), enabled_keys(&old_settings));
+    if keys != old_keys {
+        let changed_keys = keys
+            .iter()
+            .filter(|k| {
+                old_keys
+                    .iter()
+                    .any(|old| old.field == k.field && old.types != k.types)
+            })
+            .map(|k| k.field.clone())
+            .collect::<Vec<_>>();
+        if let Err(e) =
+            compact::rewrite::repartition(org_id, stream_type, stream_name, &changed_keys).await
+        {
+            log::error!(
+                "[STREAM] start repartition of {org_id}/{stream_type}/{stream_name} error: {e}"
+            );
+        }
+    }
 
-    // delete stream settings cache
-    let mut w = STREAM_SETTINGS.write().await;
-    w.remove(&key);
-    drop(w);
+    if settings.data_retention != old_settings.data_retention {
+        webhooks::notify(StreamEvent::new(
+            StreamEventType::RetentionChanged,
+            org_id,
+            stream_type,
+            stream_name,
+            json::json!({
+                "old_retention_days": old_settings.data_retention,
+                "retention_days": settings.data_retention,
+            }),
+        ));
+    }
 
-    // delete stream stats cache
-    stats::remove_stream_stats(org_id, stream_name, stream_type);
+    Ok(settings_revision(org_id, stream_name, stream_type).await)
+}
 
-    // delete stream compaction offset
-    if let Err(e) = db::compact::files::del_offset(org_id, stream_type, stream_name).await {
-        return Ok(
-            HttpResponse::InternalServerError().json(MetaHttpResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR.into(),
-                format!("failed to delete stream: {e}"),
-            )),
+/// Applies the settings patch to the listed streams and to the streams matching
+/// the pattern, each stream is saved on its own so one failure doesn't stop the
+/// others.
+pub async fn save_stream_settings_bulk(
+    org_id: &str,
+    stream_type: StreamType,
+    req: StreamSettingsBulk,
+) -> Result<Vec<StreamSettingsBulkResult>> {
+    let mut stream_names = req.streams;
+    if let Some(pattern) = req.pattern.as_deref() {
+        let re = glob_regex(pattern).map_err(ServiceError::bad_request)?;
+        stream_names.extend(
+            db::schema::list_streams_from_cache(org_id, stream_type)
+                .await
+                .into_iter()
+                .filter(|name| re.is_match(name)),
         );
-    };
-
-    crate::common::utils::auth::remove_ownership(
-        org_id,
-        &stream_type.to_string(),
-        Authz::new(stream_name),
-    )
-    .await;
+    }
+    stream_names.sort();
+    stream_names.dedup();
+    if stream_names.is_empty() {
+        return Err(ServiceError::bad_request(
+            "no stream matches the streams or the pattern".to_string(),
+        ));
+    }
 
-    Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
-        StatusCode::OK.into(),
-        "stream deleted".to_string(),
-    )))
+    let mut results = Vec::with_capacity(stream_names.len());
+    for stream_name in stream_names {
+        let ret = match infra::schema::get(org_id, &stream_name, stream_type).await {
+            Ok(schema) if schema == Schema::empty() => {
+                Err(ServiceError::not_found("stream not found"))
+            }
+            Ok(schema) => {
+                let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+                if let Some(keys) = req.settings.full_text_search_keys.as_ref() {
+                    settings.full_text_search_keys = keys.clone();
+                }
+                if let Some(days) = req.settings.data_retention {
+                    settings.data_retention = days;
+                }
+                if let Some(keys) = req.settings.partition_keys.as_ref() {
+                    settings.partition_keys = keys.clone();
+                }
+                save_stream_settings(org_id, &stream_name, stream_type, settings, None)
+                    .await
+                    .map(|_| ())
+            }
+            Err(e) => Err(e.into()),
+        };
+        results.push(StreamSettingsBulkResult {
+            stream_name,
+            success: ret.is_ok(),
+            error: ret.err().map(|e| e.to_string()),
+        });
+    }
+    Ok(results)
 }
 
-pub fn get_stream_setting_fts_fields(schema: &Schema) -> Result<Vec<String>, anyhow::Error> {
-    match unwrap_stream_settings(schema) {
-        Some(setting) => Ok(setting.full_text_search_keys),
-        None => Ok(vec![]),
+/// Translates a glob pattern, where `*` matches any characters and `?` a
+/// single one, to a regex matching whole stream names.
+fn glob_regex(pattern: &str) -> std::result::Result<regex::Regex, String> {
+    let mut re = String::with_capacity(pattern.len() + 8);
+    re.push('^');
+    let mut buf = [0; 4];
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut buf))),
+        }
     }
+    re.push('$');
+    regex::Regex::new(&re).map_err(|e| format!("pattern [{pattern}] is invalid: {e}"))
 }
 
-pub fn get_stream_setting_bloom_filter_fields(
-    schema: &Schema,
-) -> Result<Vec<String>, anyhow::Error> {
-    match unwrap_stream_settings(schema) {
-        Some(setting) => Ok(setting.bloom_filter_fields),
-        None => Ok(vec![]),
+/// The current partition keys of the stream and the progress of moving its
+/// historical files to them.
+pub async fn get_repartition_status(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Result<RepartitionStatus> {
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema == Schema::empty() {
+        return Err(ServiceError::not_found("stream not found"));
     }
+    let settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    let job = db::compact::rewrite::get(org_id, stream_type, stream_name)
+        .await?
+        .filter(|job| job.repartition || job.repartition_pending);
+    Ok(RepartitionStatus {
+        partition_keys: settings
+            .partition_keys
+            .iter()
+            .filter(|k| !k.disabled)
+            .map(|k| k.field.clone())
+            .collect(),
+        in_progress: job.is_some(),
+        job,
+    })
 }
 
-fn transform_stats(stats: &mut StreamStats) {
-    stats.storage_size /= SIZE_IN_MB;
-    stats.compressed_size /= SIZE_IN_MB;
-    stats.storage_size = (stats.storage_size * 100.0).round() / 100.0;
-    stats.compressed_size = (stats.compressed_size * 100.0).round() / 100.0;
+/// Rewrites the settings of a stream saved by an older version whose metadata
+/// is malformed, dropping the values which can't be parsed. Returns the
+/// errors which were repaired.
+pub async fn repair_stream_settings(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Result<Vec<String>, anyhow::Error> {
+    let schema = infra::schema::get_from_db(org_id, stream_name, stream_type).await?;
+    let Some(data) = schema.metadata().get("settings") else {
+        return Ok(vec![]);
+    };
+    let (settings, errors) = StreamSettings::parse(data);
+    if errors.is_empty() {
+        return Ok(errors);
+    }
+    let mut metadata = schema.metadata().clone();
+    metadata.insert("settings".to_string(), json::to_string(&settings)?);
+    db::schema::update_setting(
+        org_id,
+        stream_name,
+        stream_type,
+        metadata,
+        Some(get_settings_revision(&schema)),
+    )
+    .await?;
+    log::info!(
+        "[STREAM] repaired settings of {org_id}/{stream_type}/{stream_name}: {}",
+        errors.join(", ")
+    );
+    Ok(errors)
+}
+
+/// The current revision of the stream settings as stored in the db
+pub async fn settings_revision(org_id: &str, stream_name: &str, stream_type: StreamType) -> u64 {
+    infra::schema::get_from_db(org_id, stream_name, stream_type)
+        .await
+        .map(|schema| get_settings_revision(&schema))
+        .unwrap_or_default()
+}
+
+/// The ETag of the settings revision, sent back in `If-Match` to update the
+/// settings only if nobody else changed them meanwhile
+/// The expired files of a stream can only be archived into an archive bucket.
+fn validate_archive(settings: &StreamSettings, archive_enabled: bool) -> Result<()> {
+    if settings.archive && !archive_enabled {
+        return Err(ServiceError::bad_request(
+            "archive requires an archive bucket, set ZO_S3_ARCHIVE_BUCKET_NAME",
+        ));
+    }
+    Ok(())
+}
+
+pub fn settings_etag(revision: u64) -> String {
+    format!("\"{revision}\"")
+}
+
+fn validate_virtual_fields(schema: &Schema, settings: &StreamSettings) -> Result<(), String> {
+    let names = settings
+        .virtual_fields
+        .iter()
+        .map(|f| f.name.as_str())
+        .collect::<Vec<_>>();
+    for (i, field) in settings.virtual_fields.iter().enumerate() {
+        if names[..i].contains(&field.name.as_str()) {
+            return Err(format!("virtual field [{}] is duplicated", field.name));
+        }
+        if schema.field_with_name(&field.name).is_ok() {
+            return Err(format!(
+                "virtual field [{}] conflicts with an existing field",
+                field.name
+            ));
+        }
+        if field.expr.trim().is_empty() {
+            return Err(format!(
+                "virtual field [{}] expression is empty",
+                field.name
+            ));
+        }
+        let field = SearchService::sql::parse_virtual_field(field)?;
+        if let Some(name) = SearchService::sql::virtual_field_columns(&field.expr)
+            .into_iter()
+            .find(|c| names.contains(&c.as_str()))
+        {
+            return Err(format!(
+                "virtual field [{}] can't refer to virtual field [{name}]",
+                field.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_display_fields(settings: &StreamSettings) -> Result<(), String> {
+    for (i, field) in settings.default_display_fields.iter().enumerate() {
+        if field.is_empty() {
+            return Err("default display field can't be empty".to_string());
+        }
+        if settings.default_display_fields[..i].contains(field) {
+            return Err(format!("default display field [{field}] is duplicated"));
+        }
+    }
+    for (field, display) in settings.field_display.iter() {
+        if field.is_empty() {
+            return Err("field display name can't be empty".to_string());
+        }
+        // every placeholder in the link template must be closed
+        let mut open = false;
+        for c in display.link.chars() {
+            match c {
+                '{' if !open => open = true,
+                '}' if open => open = false,
+                '{' | '}' => {
+                    return Err(format!("field [{field}] link template is invalid"));
+                }
+                _ => {}
+            }
+        }
+        if open {
+            return Err(format!("field [{field}] link template is invalid"));
+        }
+    }
+    Ok(())
+}
+
+/// The nested fields are dot paths of non empty segments, each listed once.
+fn validate_nested_fields(settings: &StreamSettings) -> Result<(), String> {
+    for (i, path) in settings.nested_fields.iter().enumerate() {
+        if path.split('.').any(|v| v.is_empty()) {
+            return Err(format!("nested field [{path}] is invalid"));
+        }
+        if settings.nested_fields[..i].contains(path) {
+            return Err(format!("nested field [{path}] is duplicated"));
+        }
+    }
+    Ok(())
+}
+
+/// The tags of a field are lowercase words, each listed once.
+fn validate_field_metadata(settings: &StreamSettings) -> Result<(), String> {
+    for (field, metadata) in settings.field_metadata.iter() {
+        if field.is_empty() {
+            return Err("field metadata name can't be empty".to_string());
+        }
+        for (i, tag) in metadata.tags.iter().enumerate() {
+            if tag.is_empty()
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(format!(
+                    "field [{field}] tag [{tag}] is invalid, tags are lowercase letters, digits \
+                     and '_'"
+                ));
+            }
+            if metadata.tags[..i].contains(tag) {
+                return Err(format!("field [{field}] tag [{tag}] is duplicated"));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument]
+pub async fn delete_stream(org_id: &str, stream_name: &str, stream_type: StreamType) -> Result<()> {
+    let schema = infra::schema::get_versions(org_id, stream_name, stream_type).await?;
+    if schema.is_empty() {
+        return Err(ServiceError::not_found("stream not found"));
+    }
+
+    // the stream can only go once its retention lock expired
+    let settings = schema
+        .last()
+        .and_then(unwrap_stream_settings)
+        .unwrap_or_default();
+    if settings.is_retention_locked(Utc::now().timestamp_micros()) {
+        return Err(ServiceError::Forbidden(format!(
+            "stream [{stream_name}] is under a retention lock until {}",
+            Utc.timestamp_micros(settings.retention_lock_until)
+                .single()
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_default()
+        )));
+    }
+
+    if !db::compact::legal_holds::list_by_stream(org_id, stream_type, stream_name).is_empty() {
+        return Err(ServiceError::Forbidden(format!(
+            "stream [{stream_name}] is under a legal hold"
+        )));
+    }
+
+    if db::compact::rename::get(org_id, stream_type, stream_name)
+        .await?
+        .is_some()
+    {
+        return Err(ServiceError::Conflict(
+            "stream is being renamed".to_string(),
+        ));
+    }
+
+    // the compactor archives the files instead of deleting them
+    if settings.archive && infra::storage::archive::is_enabled() {
+        if let Err(e) = db::compact::archive::set_delete(org_id, stream_type, stream_name).await {
+            return Err(ServiceError::internal(format!(
+                "failed to delete stream: {e}"
+            )));
+        }
+    }
+
+    // create delete for compactor
+    if let Err(e) =
+        db::compact::retention::delete_stream(org_id, stream_type, stream_name, None).await
+    {
+        return Err(ServiceError::internal(format!(
+            "failed to delete stream: {e}"
+        )));
+    }
+
+    // delete stream schema
+    if let Err(e) = db::schema::delete(org_id, stream_name, Some(stream_type)).await {
+        return Err(ServiceError::internal(format!(
+            "failed to delete stream: {e}"
+        )));
+    }
+
+    // delete stream schema cache
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    let mut w = STREAM_SCHEMAS.write().await;
+    w.remove(&key);
+    drop(w);
+
+    // delete stream settings cache
+    let mut w = STREAM_SETTINGS.write().await;
+    w.remove(&key);
+    drop(w);
+
+    // delete stream stats cache
+    stats::remove_stream_stats(org_id, stream_name, stream_type);
+    stats::remove_stream_quota_usage(org_id, stream_name, stream_type);
+    if let Err(e) = db::stream_quota::delete(org_id, stream_type, stream_name).await {
+        log::error!("Error deleting the quota usage of stream {stream_name}: {e}");
+    }
+
+    // delete the report of the normalized keys
+    crate::service::key_mappings::delete(org_id, stream_type, stream_name).await;
+
+    // delete stream compaction offset
+    if let Err(e) = db::compact::files::del_offset(org_id, stream_type, stream_name).await {
+        return Err(ServiceError::internal(format!(
+            "failed to delete stream: {e}"
+        )));
+    };
+
+    crate::common::utils::auth::remove_ownership(
+        org_id,
+        &stream_type.to_string(),
+        Authz::new(stream_name),
+    )
+    .await;
+
+    cdc::record_schema(
+        org_id,
+        &format!("{stream_type}/{stream_name}"),
+        stream_name,
+        schema.last(),
+        None,
+    )
+    .await;
+    webhooks::notify(StreamEvent::new(
+        StreamEventType::StreamDeleted,
+        org_id,
+        stream_type,
+        stream_name,
+        json::Value::Null,
+    ));
+
+    Ok(())
+}
+
+/// Renames a stream, its schema history, files, stats and compaction offset
+/// move to the new name. The rename is queued and run by the compactor owning
+/// the stream, which stops merging it meanwhile. A stream used by alerts,
+/// functions, SLOs, continuous queries or syslog routes isn't renamed, those
+/// have to be moved to the new name first.
+/// Ingestion under the old name should be stopped first, records still in the
+/// WAL are uploaded to the old name.
+#[tracing::instrument]
+pub async fn rename_stream(
+    org_id: &str,
+    old_name: &str,
+    new_name: &str,
+    stream_type: StreamType,
+) -> Result<()> {
+    if new_name.is_empty() || super::format_stream_name(new_name) != new_name {
+        return Err(ServiceError::bad_request(format!(
+            "stream name [{new_name}] is invalid"
+        )));
+    }
+    if old_name == new_name {
+        return Err(ServiceError::bad_request(
+            "the new stream name is the same as the old one",
+        ));
+    }
+    if stream_type == StreamType::EnrichmentTables {
+        return Err(ServiceError::bad_request(
+            "enrichment tables can't be renamed",
+        ));
+    }
+    let schema = infra::schema::get_versions(org_id, old_name, stream_type).await?;
+    if schema.is_empty() {
+        return Err(ServiceError::not_found("stream not found"));
+    }
+    if !infra::schema::get_versions(org_id, new_name, stream_type)
+        .await?
+        .is_empty()
+    {
+        return Err(ServiceError::Conflict(format!(
+            "stream [{new_name}] already exists"
+        )));
+    }
+    if db::compact::retention::is_deleting_stream(org_id, stream_type, old_name, None) {
+        return Err(ServiceError::Conflict(
+            "stream is being deleted".to_string(),
+        ));
+    }
+    // the holds are placed on the stream name, the files can't leave them
+    if !db::compact::legal_holds::list_by_stream(org_id, stream_type, old_name).is_empty() {
+        return Err(ServiceError::Forbidden(format!(
+            "stream [{old_name}] is under a legal hold"
+        )));
+    }
+    if db::compact::rewrite::get(org_id, stream_type, old_name)
+        .await?
+        .is_some()
+    {
+        return Err(ServiceError::Conflict(
+            "stream is being rewritten".to_string(),
+        ));
+    }
+    for (job_org, job_type, job_stream, job) in db::compact::rename::list().await? {
+        if job_org != org_id || job_type != stream_type {
+            continue;
+        }
+        if job_stream == old_name {
+            return Err(ServiceError::Conflict(format!(
+                "stream is already being renamed to [{}]",
+                job.new_name
+            )));
+        }
+        if job.new_name == new_name || job.new_name == old_name {
+            return Err(ServiceError::Conflict(format!(
+                "stream [{job_stream}] is being renamed to [{}]",
+                job.new_name
+            )));
+        }
+    }
+    let used_by = stream_references(org_id, stream_type, old_name).await?;
+    if !used_by.is_empty() {
+        return Err(ServiceError::Conflict(format!(
+            "stream is used by {}, move them to the new name first",
+            used_by.join(", ")
+        )));
+    }
+
+    let job = StreamRenameJob {
+        new_name: new_name.to_string(),
+        created_at: Utc::now().timestamp_micros(),
+        moved_files: 0,
+        reserved: false,
+    };
+    db::compact::rename::set(org_id, stream_type, old_name, &job).await?;
+    Ok(())
+}
+
+/// The objects reading or writing the stream by its name.
+async fn stream_references(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<Vec<String>> {
+    let mut refs = Vec::new();
+    for alert in db::alerts::list(org_id, Some(stream_type), Some(stream_name)).await? {
+        refs.push(format!("alert [{}]", alert.name));
+    }
+    if STREAM_FUNCTIONS
+        .get(&format!("{org_id}/{stream_type}/{stream_name}"))
+        .is_some_and(|v| !v.list.is_empty())
+    {
+        refs.push("functions".to_string());
+    }
+    for slo in db::slo::list(org_id).await? {
+        if slo.stream_type == stream_type && slo.stream_name == stream_name {
+            refs.push(format!("SLO [{}]", slo.name));
+        }
+    }
+    for query in db::continuous_queries::list(org_id).await? {
+        let reads = query.stream_type == stream_type
+            && config::meta::sql::Sql::new(&query.sql).is_ok_and(|sql| sql.source == stream_name);
+        let writes = stream_type == StreamType::Logs && query.destination == stream_name;
+        if reads || writes {
+            refs.push(format!("continuous query [{}]", query.name));
+        }
+    }
+    if stream_type == StreamType::Logs
+        && SYSLOG_ROUTES
+            .iter()
+            .any(|r| r.org_id == org_id && r.stream_name == stream_name)
+    {
+        refs.push("syslog routes".to_string());
+    }
+    Ok(refs)
+}
+
+/// Runs the pending stream renames of the streams this compactor owns. The
+/// files move an hour at a time, a rename stopped half way resumes from the
+/// files left in the old stream.
+pub async fn run_rename_jobs() -> Result<(), anyhow::Error> {
+    for (org_id, stream_type, old_name, job) in db::compact::rename::list().await? {
+        let Some(node) = get_node_from_consistent_hash(&old_name, &Role::Compactor).await else {
+            continue; // no compactor node
+        };
+        if LOCAL_NODE_UUID.ne(&node) {
+            continue; // another compactor owns the stream
+        }
+        if let Err(e) = rename_by_job(&org_id, stream_type, &old_name, job).await {
+            log::error!(
+                "[STREAM] rename [{}/{}/{}] error: {}",
+                org_id,
+                stream_type,
+                old_name,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn rename_by_job(
+    org_id: &str,
+    stream_type: StreamType,
+    old_name: &str,
+    mut job: StreamRenameJob,
+) -> Result<(), anyhow::Error> {
+    let new_name = job.new_name.clone();
+    // hold the lock a compactor takes to claim the stream
+    let lock_key = format!("/compact/merge/{org_id}/{stream_type}/{old_name}");
+    let locker = dist_lock::lock(&lock_key, 0).await?;
+    let (offset, node) = db::compact::files::get_offset(org_id, stream_type, old_name).await;
+    let ret =
+        if !node.is_empty() && LOCAL_NODE_UUID.ne(&node) && get_node_by_uuid(&node).await.is_some()
+        {
+            // a compactor is merging the stream, retry on the next run
+            Ok(RenameStatus::Pending)
+        } else {
+            move_stream(org_id, old_name, stream_type, offset, &mut job).await
+        };
+    dist_lock::unlock(&locker).await?;
+    match ret? {
+        RenameStatus::Pending => return Ok(()),
+        RenameStatus::Failed(e) => {
+            // nothing was moved yet, the old stream takes ingestion again
+            db::compact::rename::delete(org_id, stream_type, old_name).await?;
+            return Err(anyhow::anyhow!("rename to [{new_name}] failed: {e}"));
+        }
+        RenameStatus::Done => {}
+    }
+    db::compact::rename::delete(org_id, stream_type, old_name).await?;
+    log::info!(
+        "[STREAM] renamed [{}/{}/{}] to [{}], {} files moved",
+        org_id,
+        stream_type,
+        old_name,
+        new_name,
+        job.moved_files
+    );
+
+    // move the caches of this node, others follow the schema watch
+    let old_key = format!("{org_id}/{stream_type}/{old_name}");
+    let mut w = STREAM_SCHEMAS.write().await;
+    w.remove(&old_key);
+    drop(w);
+    let mut w = STREAM_SETTINGS.write().await;
+    w.remove(&old_key);
+    drop(w);
+    let old_stats = stats::get_stream_stats(org_id, old_name, stream_type);
+    stats::set_stream_stats(org_id, &new_name, stream_type, old_stats);
+    stats::remove_stream_stats(org_id, old_name, stream_type);
+
+    crate::common::utils::auth::remove_ownership(
+        org_id,
+        &stream_type.to_string(),
+        Authz::new(old_name),
+    )
+    .await;
+    crate::common::utils::auth::set_ownership(
+        org_id,
+        &stream_type.to_string(),
+        Authz::new(&new_name),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Creates a stream with the schema and settings of another one, the data of
+/// the stream isn't copied.
+#[tracing::instrument]
+pub async fn clone_stream(
+    org_id: &str,
+    from: &str,
+    new_name: &str,
+    stream_type: StreamType,
+) -> Result<()> {
+    if new_name.is_empty() || super::format_stream_name(new_name) != new_name {
+        return Err(ServiceError::bad_request(format!(
+            "stream name [{new_name}] is invalid"
+        )));
+    }
+    if stream_type == StreamType::EnrichmentTables {
+        return Err(ServiceError::bad_request(
+            "enrichment tables can't be cloned",
+        ));
+    }
+    let schema = infra::schema::get(org_id, from, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(ServiceError::not_found("stream not found"));
+    }
+    if !infra::schema::get_versions(org_id, new_name, stream_type)
+        .await?
+        .is_empty()
+    {
+        return Err(ServiceError::Conflict(format!(
+            "stream [{new_name}] already exists"
+        )));
+    }
+
+    // the new stream starts its own schema history
+    let now = Utc::now().timestamp_micros();
+    let mut metadata = HashMap::new();
+    if let Some(settings) = schema.metadata().get("settings") {
+        metadata.insert("settings".to_string(), settings.clone());
+    }
+    metadata.insert("created_at".to_string(), now.to_string());
+    metadata.insert("start_dt".to_string(), now.to_string());
+    let new_schema = Schema::new(schema.fields().clone()).with_metadata(metadata);
+    db::schema::merge(org_id, new_name, stream_type, &new_schema, Some(now))
+        .await
+        .map_err(|e| ServiceError::internal(format!("failed to clone stream: {e}")))?;
+
+    crate::common::utils::auth::set_ownership(
+        org_id,
+        &stream_type.to_string(),
+        Authz::new(new_name),
+    )
+    .await;
+    webhooks::notify(StreamEvent::new(
+        StreamEventType::StreamCreated,
+        org_id,
+        stream_type,
+        new_name,
+        json::json!({ "fields": new_schema.fields().len(), "cloned_from": from }),
+    ));
+
+    Ok(())
+}
+
+/// What a run of a rename job got to.
+#[derive(Debug, PartialEq)]
+enum RenameStatus {
+    Pending,
+    Done,
+    Failed(String),
+}
+
+/// Takes the new name for the job unless a stream or another rename took it
+/// since the rename was asked, returns whether the job has to be saved. A
+/// reserved job keeps the name, the schema under it is the one it copied.
+fn reserve_new_name(job: &mut StreamRenameJob, taken: bool) -> Result<bool, String> {
+    if job.reserved {
+        return Ok(false);
+    }
+    if taken {
+        return Err(format!("stream [{}] already exists", job.new_name));
+    }
+    job.reserved = true;
+    Ok(true)
+}
+
+async fn is_new_name_taken(
+    org_id: &str,
+    stream_type: StreamType,
+    old_name: &str,
+    new_name: &str,
+) -> Result<bool, anyhow::Error> {
+    if !infra::schema::get_versions(org_id, new_name, stream_type)
+        .await?
+        .is_empty()
+    {
+        return Ok(true);
+    }
+    Ok(db::compact::rename::list().await?.into_iter().any(
+        |(job_org, job_type, job_stream, job)| {
+            job_org == org_id
+                && job_type == stream_type
+                && job_stream != old_name
+                && job.reserved
+                && job.new_name == new_name
+        },
+    ))
+}
+
+/// Moves the stream to the name of the job. Every step can run again, so a
+/// move stopped half way is resumed by running it again.
+async fn move_stream(
+    org_id: &str,
+    old_name: &str,
+    stream_type: StreamType,
+    offset: i64,
+    job: &mut StreamRenameJob,
+) -> Result<RenameStatus, anyhow::Error> {
+    let new_name = job.new_name.clone();
+    if infra::schema::get_versions(org_id, old_name, stream_type)
+        .await?
+        .is_empty()
+    {
+        // the schema goes last, only the old offset can be left
+        db::compact::files::del_offset(org_id, stream_type, old_name).await?;
+        return Ok(RenameStatus::Done);
+    }
+
+    // the copy overwrites the schema under the new name, check it is still
+    // free under a lock of the name before taking it
+    if !job.reserved {
+        let lock_key = format!("/stream_rename/{org_id}/{stream_type}/{new_name}");
+        let locker = dist_lock::lock(&lock_key, 0).await?;
+        let ret = async {
+            let taken = is_new_name_taken(org_id, stream_type, old_name, &new_name).await?;
+            match reserve_new_name(job, taken) {
+                Ok(_) => {
+                    db::compact::rename::set(org_id, stream_type, old_name, job).await?;
+                    Ok(None)
+                }
+                Err(e) => Ok::<_, anyhow::Error>(Some(e)),
+            }
+        }
+        .await;
+        dist_lock::unlock(&locker).await?;
+        if let Some(e) = ret? {
+            return Ok(RenameStatus::Failed(e));
+        }
+    }
+
+    // the new stream gets the schema first so the moved files are searchable
+    infra::schema::copy(org_id, stream_type, old_name, &new_name).await?;
+
+    let old_prefix = format!("files/{org_id}/{stream_type}/{old_name}/");
+    let new_prefix = format!("files/{org_id}/{stream_type}/{new_name}/");
+    let files = file_list::query(
+        org_id,
+        old_name,
+        stream_type,
+        PartitionTimeLevel::Unset,
+        1,
+        i64::MAX,
+        true,
+    )
+    .await?;
+    let mut hours: BTreeMap<String, Vec<FileKey>> = BTreeMap::new();
+    for file in files {
+        let Some(path) = file.key.strip_prefix(&old_prefix) else {
+            continue;
+        };
+        let hour_key = path.split('/').take(4).collect::<Vec<_>>().join("/");
+        hours.entry(hour_key).or_default().push(file);
+    }
+
+    // move the files an hour at a time, each file belongs to one of the
+    // streams at any point
+    for (hour_key, files) in hours {
+        let mut moved = StreamStats::default();
+        let mut events = Vec::with_capacity(files.len() * 2);
+        for file in files.iter() {
+            let new_key = format!("{new_prefix}{}", &file.key[old_prefix.len()..]);
+            let data = storage::get(&file.key).await?;
+            storage::put(&new_key, data).await?;
+            moved = moved - file.meta.clone();
+            events.push(FileKey {
+                key: new_key,
+                meta: file.meta.clone(),
+                deleted: false,
+            });
+            events.push(FileKey {
+                key: file.key.clone(),
+                meta: FileMeta::default(),
+                deleted: true,
+            });
+        }
+        let day_key = hour_key.split('/').take(3).collect::<Vec<_>>().join("-");
+        compact::retention::write_file_list(
+            HashSet::from([day_key]),
+            HashMap::from([(hour_key, events)]),
+        )
+        .await?;
+        // the stats job adds the new files to the new stream
+        infra_file_list::set_stream_stats(
+            org_id,
+            &[(format!("{org_id}/{stream_type}/{old_name}"), moved)],
+        )
+        .await?;
+        let old_keys = files.iter().map(|f| f.key.as_str()).collect::<Vec<_>>();
+        if let Err(e) = storage::del(&old_keys).await {
+            log::error!("[STREAM] rename {old_name} to {new_name}, delete old files error: {e}");
+        }
+        job.moved_files += files.len();
+        db::compact::rename::set(org_id, stream_type, old_name, job).await?;
+    }
+
+    db::compact::files::set_offset(org_id, stream_type, &new_name, offset, None).await?;
+    db::schema::delete(org_id, old_name, Some(stream_type)).await?;
+    db::compact::files::del_offset(org_id, stream_type, old_name).await?;
+    Ok(RenameStatus::Done)
+}
+
+/// Returns the start of the hot tier of the stream in microseconds, the data
+/// before it is only searched when a query asks for all the tiers. `None`
+/// if the stream has no cold tier.
+pub async fn hot_tier_start(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+) -> Result<Option<i64>> {
+    let days = match infra::schema::get_settings(org_id, stream_name, stream_type).await {
+        Some(settings) if settings.hot_data_days > 0 => settings.hot_data_days,
+        _ => CONFIG.limit.query_hot_data_days,
+    };
+    hot_start_of(days, Utc::now())
+}
+
+fn hot_start_of(days: i64, now: chrono::DateTime<Utc>) -> Result<Option<i64>> {
+    if days <= 0 {
+        return Ok(None);
+    }
+    chrono::Duration::try_days(days)
+        .and_then(|d| now.checked_sub_signed(d))
+        .map(|t| Some(t.timestamp_micros()))
+        .ok_or_else(|| ServiceError::internal(format!("hot_data_days {days} is out of range")))
+}
+
+/// Where the time range of a query falls against the start of the hot tier.
+#[derive(Debug, PartialEq)]
+pub enum HotTierCut {
+    /// The whole range is in the hot tier
+    Hot,
+    /// The range starts in the cold tier, only the part from the start of the
+    /// hot tier is searched
+    Partial(i64),
+    /// The whole range is in the cold tier
+    Cold,
+}
+
+/// Cuts the time range of a query, `end_time` being exclusive and 0 when
+/// unset, to the hot tier starting at `hot_start`.
+pub fn cut_to_hot_tier(start_time: i64, end_time: i64, hot_start: i64) -> HotTierCut {
+    if start_time >= hot_start {
+        HotTierCut::Hot
+    } else if end_time > 0 && end_time <= hot_start {
+        HotTierCut::Cold
+    } else {
+        HotTierCut::Partial(hot_start)
+    }
+}
+
+pub fn get_stream_setting_fts_fields(schema: &Schema) -> Result<Vec<String>, anyhow::Error> {
+    match unwrap_stream_settings(schema) {
+        Some(setting) => Ok(setting.full_text_search_keys),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn get_stream_setting_bloom_filter_fields(
+    schema: &Schema,
+) -> Result<Vec<String>, anyhow::Error> {
+    match unwrap_stream_settings(schema) {
+        Some(setting) => Ok(setting.bloom_filter_fields),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn get_stream_setting_parquet_options(schema: &Schema) -> ParquetOptions {
+    match unwrap_stream_settings(schema) {
+        Some(setting) => setting.parquet,
+        None => ParquetOptions::default(),
+    }
+}
+
+fn transform_stats(stats: &mut StreamStats) {
+    stats.storage_size /= SIZE_IN_MB;
+    stats.compressed_size /= SIZE_IN_MB;
+    stats.storage_size = (stats.storage_size * 100.0).round() / 100.0;
+    stats.compressed_size = (stats.compressed_size * 100.0).round() / 100.0;
 }
 
 pub fn stream_created(schema: &Schema) -> Option<i64> {
@@ -384,6 +1699,33 @@ pub async fn delete_fields(
     Ok(())
 }
 
+/// Promotes keys of the `_extra` column to columns of their own, the records
+/// ingested from now on store them as columns even past the field limit of the
+/// stream. Returns the new settings revision.
+pub async fn promote_fields(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    fields: &[String],
+) -> Result<u64> {
+    if fields.iter().any(|f| f.is_empty()) {
+        return Err(ServiceError::bad_request("field name can't be empty"));
+    }
+    let schema = infra::schema::get(org_id, stream_name, stream_type).await?;
+    if schema.fields().is_empty() {
+        return Err(ServiceError::not_found(format!(
+            "stream [{stream_name}] not found"
+        )));
+    }
+    let mut settings = unwrap_stream_settings(&schema).unwrap_or_default();
+    for field in fields {
+        if !settings.promoted_fields.contains(field) {
+            settings.promoted_fields.push(field.to_string());
+        }
+    }
+    save_stream_settings(org_id, stream_name, stream_type, settings, None).await
+}
+
 /// get stream stats from usage report
 async fn _get_stream_stats(
     org_id: &str,
@@ -446,10 +1788,297 @@ async fn _get_stream_stats(
 
 #[cfg(test)]
 mod tests {
+    use config::meta::stream::StreamPartition;
     use datafusion::arrow::datatypes::{DataType, Field};
 
     use super::*;
 
+    #[test]
+    fn test_validate_stream_template() {
+        let mut template = StreamTemplate {
+            full_text_search_keys: vec!["message".to_string()],
+            partition_keys: vec![StreamPartition::new("kubernetes_namespace")],
+            ..Default::default()
+        };
+        assert!(validate_stream_template(&template).is_ok());
+        template.partition_keys = vec![StreamPartition::new(&CONFIG.common.column_timestamp)];
+        assert!(validate_stream_template(&template).is_err());
+        template.partition_keys = vec![];
+        template.full_text_search_keys = vec!["".to_string()];
+        assert!(validate_stream_template(&template).is_err());
+        template.full_text_search_keys = vec![];
+        template.data_retention = -1;
+        assert!(validate_stream_template(&template).is_err());
+    }
+
+    #[test]
+    fn test_glob_regex() {
+        let re = glob_regex("k8s-*").unwrap();
+        assert!(re.is_match("k8s-default"));
+        assert!(re.is_match("k8s-"));
+        assert!(!re.is_match("prod-k8s-default"));
+        let re = glob_regex("app_?.logs").unwrap();
+        assert!(re.is_match("app_1.logs"));
+        assert!(!re.is_match("app_12.logs"));
+        assert!(!re.is_match("app_1xlogs"));
+    }
+
+    #[test]
+    fn test_field_metadata() {
+        let schema = Schema::new(vec![
+            Field::new("user_email", DataType::Utf8, true),
+            Field::new("took", DataType::Int64, true),
+        ]);
+        let mut settings = StreamSettings::default();
+        settings.field_metadata.insert(
+            "user_email".to_string(),
+            config::meta::stream::FieldMetadata {
+                description: "Email of the signed-in user".to_string(),
+                tags: vec!["pii".to_string()],
+            },
+        );
+        settings.field_display.insert(
+            "took".to_string(),
+            config::meta::stream::FieldDisplay {
+                unit: "ms".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(validate_field_metadata(&settings).is_ok());
+        let fields = stream_properties(&schema, &settings);
+        assert_eq!(fields[0].tags, vec!["pii"]);
+        assert_eq!(fields[0].description, "Email of the signed-in user");
+        assert_eq!(fields[1].unit, "ms");
+        assert!(fields[1].tags.is_empty());
+
+        settings
+            .field_metadata
+            .get_mut("user_email")
+            .unwrap()
+            .tags
+            .push("Duration ms".to_string());
+        assert!(validate_field_metadata(&settings).is_err());
+    }
+
+    #[test]
+    fn test_validate_nested_fields() {
+        let mut settings = StreamSettings {
+            nested_fields: vec!["kubernetes.labels".to_string()],
+            ..Default::default()
+        };
+        assert!(validate_nested_fields(&settings).is_ok());
+        settings
+            .nested_fields
+            .push("kubernetes..labels".to_string());
+        assert!(validate_nested_fields(&settings).is_err());
+        settings.nested_fields[1] = "kubernetes.labels".to_string();
+        assert!(validate_nested_fields(&settings).is_err());
+    }
+
+    #[test]
+    fn test_history_points() {
+        let stats = |doc_num: i64| StreamStats {
+            doc_num,
+            file_num: 1,
+            storage_size: 2.0 * SIZE_IN_MB,
+            ..Default::default()
+        };
+        let history = vec![(1000, stats(10)), (2000, stats(20)), (2000, stats(21))];
+        let points = history_points(history, (2500, stats(30)));
+        // the point snapshotted twice is kept once, with the later stats
+        assert_eq!(
+            points
+                .iter()
+                .map(|p| (p.timestamp, p.doc_num))
+                .collect::<Vec<_>>(),
+            vec![(1000, 10), (2000, 21), (2500, 30)]
+        );
+        assert_eq!(points[0].storage_size, 2.0);
+
+        let points = history_points(vec![], (2500, stats(30)));
+        assert_eq!(points.len(), 1);
+    }
+
+    #[test]
+    fn test_hot_tier_cutoff() {
+        let now = Utc::now();
+        let day = 86_400_000_000;
+        let hot_start = hot_start_of(1, now).unwrap().unwrap();
+        assert_eq!(hot_start, now.timestamp_micros() - day);
+        assert_eq!(hot_start_of(0, now).unwrap(), None);
+        assert!(hot_start_of(i64::MAX, now).is_err());
+        // before the first representable date
+        assert!(hot_start_of(1_000_000_000, now).is_err());
+
+        // the start of the hot tier is in it
+        assert_eq!(cut_to_hot_tier(hot_start, 0, hot_start), HotTierCut::Hot);
+        assert_eq!(
+            cut_to_hot_tier(hot_start - 1, 0, hot_start),
+            HotTierCut::Partial(hot_start)
+        );
+        assert_eq!(
+            cut_to_hot_tier(hot_start - day, hot_start + 1, hot_start),
+            HotTierCut::Partial(hot_start)
+        );
+        // the end is exclusive, a range ending at the start is all cold
+        assert_eq!(
+            cut_to_hot_tier(hot_start - day, hot_start, hot_start),
+            HotTierCut::Cold
+        );
+    }
+
+    #[test]
+    fn test_validate_archive() {
+        let mut settings = StreamSettings::default();
+        assert!(validate_archive(&settings, false).is_ok());
+        settings.archive = true;
+        assert!(matches!(
+            validate_archive(&settings, false),
+            Err(ServiceError::BadRequest(_))
+        ));
+        assert!(validate_archive(&settings, true).is_ok());
+    }
+
+    #[test]
+    fn test_reserve_new_name() {
+        let mut job = StreamRenameJob {
+            new_name: "new_logs".to_string(),
+            ..Default::default()
+        };
+        // a stream created with the name since the rename was asked
+        assert!(reserve_new_name(&mut job, true).is_err());
+        assert!(!job.reserved);
+
+        assert_eq!(reserve_new_name(&mut job, false), Ok(true));
+        assert!(job.reserved);
+        // a resumed job finds the schema it copied under the new name
+        assert_eq!(reserve_new_name(&mut job, true), Ok(false));
+    }
+
+    #[test]
+    fn test_label_filters_and_groups() {
+        let stream = |name: &str, labels: &[(&str, &str)]| {
+            let mut stream = stream_res(name, StreamType::Logs, Schema::empty(), None);
+            stream.settings.labels = labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            stream
+        };
+        let streams = vec![
+            stream("checkout", &[("team", "payments"), ("env", "prod")]),
+            stream("ledger", &[("team", "payments"), ("env", "dev")]),
+            stream("search", &[("team", "discovery"), ("env", "prod")]),
+            stream("scratch", &[]),
+        ];
+
+        let filters = parse_label_filters("team:payments, env:prod").unwrap();
+        let filtered = filter_by_labels(streams.clone(), &filters);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].name, "checkout");
+        assert!(parse_label_filters("team").is_err());
+
+        let groups = group_by_label(streams, "team");
+        let values = groups.iter().map(|g| g.value.as_str()).collect::<Vec<_>>();
+        assert_eq!(values, vec!["discovery", "payments", ""]);
+        assert_eq!(groups[1].list.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_check_stale_owners() {
+        let owner = |user: &str| StreamSettings {
+            owner: Some(config::meta::stream::StreamOwner {
+                user: user.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        USERS.insert(
+            "owners_org/alice@example.com".to_string(),
+            crate::common::meta::user::User {
+                email: "alice@example.com".to_string(),
+                first_name: "alice".to_string(),
+                last_name: "".to_string(),
+                password: "pass#123".to_string(),
+                salt: String::new(),
+                token: String::new(),
+                rum_token: None,
+                role: crate::common::meta::user::UserRole::Member,
+                org: "owners_org".to_string(),
+                is_external: false,
+            },
+        );
+        let mut w = STREAM_SETTINGS.write().await;
+        w.insert(
+            "owners_org/logs/kept".to_string(),
+            owner("alice@example.com"),
+        );
+        w.insert("owners_org/logs/left".to_string(), owner("bob@example.com"));
+        drop(w);
+
+        let stale = check_stale_owners().await;
+        assert!(stale.contains(&"owners_org/logs/left".to_string()));
+        assert!(!stale.contains(&"owners_org/logs/kept".to_string()));
+        assert!(is_stale_owner(
+            "owners_org",
+            owner("bob@example.com").owner.as_ref()
+        ));
+        assert!(!is_stale_owner("owners_org", None));
+    }
+
+    #[test]
+    fn test_is_internal_stream() {
+        assert!(!is_internal_stream("default", StreamType::Logs, "k8s"));
+        assert!(!is_internal_stream("default", StreamType::Logs, "usage"));
+        assert!(is_internal_stream("default", StreamType::Metadata, "k8s"));
+        assert!(is_internal_stream("default", StreamType::Index, "k8s"));
+        assert!(is_internal_stream(
+            &CONFIG.common.usage_org,
+            StreamType::Logs,
+            "usage"
+        ));
+        assert!(!is_internal_stream(
+            &CONFIG.common.usage_org,
+            StreamType::Logs,
+            "k8s"
+        ));
+    }
+
+    #[test]
+    fn test_is_permitted_stream() {
+        let permitted = vec!["logs:web".to_string(), "metrics:_all_default".to_string()];
+        assert!(is_permitted_stream(
+            "default",
+            StreamType::Logs,
+            "web",
+            &permitted
+        ));
+        assert!(!is_permitted_stream(
+            "default",
+            StreamType::Logs,
+            "db",
+            &permitted
+        ));
+        assert!(is_permitted_stream(
+            "default",
+            StreamType::Metrics,
+            "up",
+            &permitted
+        ));
+        assert!(!is_permitted_stream(
+            "default",
+            StreamType::Traces,
+            "web",
+            &permitted
+        ));
+        assert!(is_permitted_stream(
+            "default",
+            StreamType::EnrichmentTables,
+            "ips",
+            &permitted
+        ));
+    }
+
     #[test]
     fn test_stream_res() {
         let stats = StreamStats::default();
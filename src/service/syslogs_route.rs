@@ -33,6 +33,8 @@ use crate::{
 
 #[tracing::instrument(skip_all)]
 pub async fn create_route(mut route: SyslogRoute) -> Result<HttpResponse, io::Error> {
+    // the hostname is set by the sender, it only picks between the routes of the
+    // source subnet
     if route.org_id.trim().is_empty()
         || route.stream_name.trim().is_empty()
         || route.subnets.is_empty()
@@ -46,17 +48,34 @@ pub async fn create_route(mut route: SyslogRoute) -> Result<HttpResponse, io::Er
         let existing_subnets = &existing_route.subnets;
         let new_subnets = &route.subnets;
 
-        if existing_subnets.iter().any(|existing_subnet| {
-            new_subnets
-                .iter()
-                .any(|subnet| subnets_overlap(existing_subnet, subnet))
-        }) {
+        // routes of an org on overlapping subnets are told apart by their hostnames,
+        // a sender never picks the org with its hostname
+        let by_hostname = existing_route.org_id == route.org_id
+            && (!route.hostnames.is_empty() || !existing_route.hostnames.is_empty());
+        if !by_hostname
+            && existing_subnets.iter().any(|existing_subnet| {
+                new_subnets
+                    .iter()
+                    .any(|subnet| subnets_overlap(existing_subnet, subnet))
+            })
+        {
             return Ok(Response::BadRequest(format!(
                 "Provided subnet/s overlap with existing subnet/s for organization {}",
                 &existing_route.org_id
             ))
             .into());
         }
+        if let Some(hostname) = route
+            .hostnames
+            .iter()
+            .find(|h| existing_route.hostnames.contains(h))
+        {
+            return Ok(Response::BadRequest(format!(
+                "Hostname pattern {hostname} is already routed for organization {}",
+                &existing_route.org_id
+            ))
+            .into());
+        }
     }
 
     route.id = ider::generate();
@@ -72,9 +91,10 @@ pub async fn update_route(id: &str, route: &mut SyslogRoute) -> Result<HttpRespo
     if route.org_id.trim().is_empty()
         && route.stream_name.trim().is_empty()
         && route.subnets.is_empty()
+        && route.hostnames.is_empty()
     {
         return Ok(Response::BadRequest(
-            "Please provide stream name/org_id/subnets for route to update".to_owned(),
+            "Please provide stream name/org_id/subnets/hostnames for route to update".to_owned(),
         )
         .into());
     }
@@ -98,6 +118,9 @@ pub async fn update_route(id: &str, route: &mut SyslogRoute) -> Result<HttpRespo
     if route.subnets.is_empty() {
         route.subnets = old_route.subnets.clone();
     }
+    if route.hostnames.is_empty() {
+        route.hostnames = old_route.hostnames.clone();
+    }
 
     if route == &old_route {
         return Ok(HttpResponse::Ok().json(route));
@@ -0,0 +1,432 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Threat intel indicator lists. The ingested logs records are checked against
+//! the indicators of the lists of their org, a record hitting one is tagged
+//! with the `threat_intel_match` and `threat_intel_indicator` fields and the
+//! destinations of the list are notified.
+
+use std::{net::IpAddr, str::FromStr, sync::Arc};
+
+use chrono::Utc;
+use config::{
+    utils::json::{self, Map, Value},
+    RwHashMap, CONFIG,
+};
+use hashbrown::HashSet;
+use ipnetwork::IpNetwork;
+use once_cell::sync::Lazy;
+
+use crate::{
+    common::{
+        infra::config::THREAT_INTEL_LISTS,
+        meta::threat_intel::{
+            IndicatorHit, IndicatorList, IndicatorType, THREAT_INTEL_INDICATOR_FIELD,
+            THREAT_INTEL_MATCH_FIELD,
+        },
+    },
+    service::{alerts, db, error::ServiceError},
+};
+
+/// Lists ready to match, by `org/name`.
+static MATCHERS: Lazy<RwHashMap<String, Arc<Matcher>>> = Lazy::new(Default::default);
+/// Indicators fetched from the feed of a list, by `org/name`.
+static FEED_INDICATORS: Lazy<RwHashMap<String, Vec<String>>> = Lazy::new(Default::default);
+/// Last time a hit was sent, by `org/name/indicator`, in microseconds.
+static NOTIFIED_HITS: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
+
+#[derive(Debug)]
+struct Matcher {
+    list: IndicatorList,
+    values: HashSet<String>,
+    networks: Vec<IpNetwork>,
+}
+
+impl Matcher {
+    fn new(list: IndicatorList, feed: &[String]) -> Self {
+        let mut values = HashSet::new();
+        let mut networks = Vec::new();
+        for indicator in list.indicators.iter().chain(feed.iter()) {
+            let indicator = indicator.trim().to_lowercase();
+            if indicator.is_empty() {
+                continue;
+            }
+            if list.indicator_type == IndicatorType::Ip {
+                // addresses are kept in their canonical form
+                if let Ok(ip) = IpAddr::from_str(&indicator) {
+                    values.insert(ip.to_string());
+                } else if let Ok(network) = IpNetwork::from_str(&indicator) {
+                    networks.push(network);
+                } else {
+                    log::debug!("[THREAT_INTEL] invalid ip indicator {indicator}");
+                }
+                continue;
+            }
+            values.insert(indicator);
+        }
+        Self {
+            list,
+            values,
+            networks,
+        }
+    }
+
+    /// The indicator hit by the value.
+    fn check(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        match self.list.indicator_type {
+            IndicatorType::Ip => {
+                let ip = IpAddr::from_str(value).ok()?;
+                let ip_str = ip.to_string();
+                if self.values.contains(&ip_str) {
+                    return Some(ip_str);
+                }
+                self.networks
+                    .iter()
+                    .find(|n| n.contains(ip))
+                    .map(|n| n.to_string())
+            }
+            IndicatorType::Domain => {
+                let value = value.to_lowercase();
+                // urls hit by their host
+                let host = match url::Url::parse(&value) {
+                    Ok(url) if value.contains("://") => url.host_str()?.to_string(),
+                    _ => value,
+                };
+                let mut domain = host.trim_end_matches('.');
+                loop {
+                    if self.values.contains(domain) {
+                        return Some(domain.to_string());
+                    }
+                    domain = domain.split_once('.')?.1;
+                }
+            }
+            IndicatorType::Hash => {
+                let value = value.to_lowercase();
+                self.values.contains(&value).then_some(value)
+            }
+        }
+    }
+}
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut list: IndicatorList,
+    create: bool,
+) -> Result<(), ServiceError> {
+    if !name.is_empty() {
+        list.name = name.to_string();
+    }
+    list.name = list.name.trim().to_string();
+    validate(&list).map_err(ServiceError::bad_request)?;
+    for dest in list.destinations.iter() {
+        if db::alerts::destinations::get(org_id, dest).await.is_err() {
+            return Err(ServiceError::bad_request(format!(
+                "Destination [{dest}] not found"
+            )));
+        }
+    }
+
+    match db::threat_intel::get(org_id, &list.name).await {
+        Ok(_) if create => {
+            return Err(ServiceError::bad_request("Indicator list already exists"));
+        }
+        Err(_) if !create => {
+            return Err(ServiceError::not_found("Indicator list not found"));
+        }
+        _ => {}
+    }
+    db::threat_intel::set(org_id, &list)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<IndicatorList, ServiceError> {
+    db::threat_intel::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("Indicator list not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<IndicatorList>, ServiceError> {
+    db::threat_intel::list(org_id)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    if db::threat_intel::get(org_id, name).await.is_err() {
+        return Err(ServiceError::not_found("Indicator list not found"));
+    }
+    db::threat_intel::delete(org_id, name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+fn validate(list: &IndicatorList) -> Result<(), String> {
+    if list.name.is_empty() {
+        return Err("Indicator list name is required".to_string());
+    }
+    if list.name.contains('/') {
+        return Err("Indicator list name cannot contain '/'".to_string());
+    }
+    if list.indicators.is_empty() && list.feed_url.is_empty() {
+        return Err("Indicator list needs indicators or a feed_url".to_string());
+    }
+    if !list.feed_url.is_empty() {
+        match url::Url::parse(&list.feed_url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            _ => return Err(format!("Feed url [{}] is invalid", list.feed_url)),
+        }
+    }
+    for indicator in list.indicators.iter() {
+        let indicator = indicator.trim();
+        let valid = match list.indicator_type {
+            IndicatorType::Ip => {
+                IpAddr::from_str(indicator).is_ok() || IpNetwork::from_str(indicator).is_ok()
+            }
+            IndicatorType::Domain => !indicator.is_empty() && !indicator.contains(['/', ' ', ':']),
+            IndicatorType::Hash => {
+                !indicator.is_empty() && indicator.chars().all(|c| c.is_ascii_hexdigit())
+            }
+        };
+        if !valid {
+            return Err(format!("Indicator [{indicator}] is invalid"));
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds the matcher of the list `org/name` from the cached list and the
+/// indicators of its feed, drops it when the list was deleted.
+pub fn compile(key: &str) {
+    let Some(list) = THREAT_INTEL_LISTS.get(key).map(|v| v.value().clone()) else {
+        MATCHERS.remove(key);
+        FEED_INDICATORS.remove(key);
+        return;
+    };
+    let feed = FEED_INDICATORS
+        .get(key)
+        .map(|v| v.value().clone())
+        .unwrap_or_default();
+    MATCHERS.insert(key.to_string(), Arc::new(Matcher::new(list, &feed)));
+}
+
+/// Fetches the feeds of all the lists, returns how many were updated.
+pub async fn refresh_feeds() -> usize {
+    let lists = THREAT_INTEL_LISTS
+        .iter()
+        .filter(|v| v.value().enabled && !v.value().feed_url.is_empty())
+        .map(|v| (v.key().to_string(), v.value().feed_url.clone()))
+        .collect::<Vec<_>>();
+    let mut updated = 0;
+    for (key, feed_url) in lists {
+        match fetch_feed(&feed_url).await {
+            Ok(indicators) => {
+                FEED_INDICATORS.insert(key.clone(), indicators);
+                compile(&key);
+                updated += 1;
+            }
+            Err(e) => log::error!("[THREAT_INTEL] fetch feed of {key} error: {e}"),
+        }
+    }
+    updated
+}
+
+async fn fetch_feed(feed_url: &str) -> Result<Vec<String>, anyhow::Error> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(
+            CONFIG.limit.threat_intel_feed_timeout,
+        ))
+        .build()?;
+    let resp = client.get(feed_url).send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!("feed status: {}", resp.status()));
+    }
+    Ok(parse_feed(&resp.text().await?))
+}
+
+fn parse_feed(body: &str) -> Vec<String> {
+    body.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Tags the record with the lists and indicators it hits, called for every
+/// logs record before its schema is checked.
+pub fn tag(org_id: &str, stream_name: &str, record: &mut Map<String, Value>) {
+    if MATCHERS.is_empty() {
+        return;
+    }
+    let prefix = format!("{org_id}/");
+    let matchers = MATCHERS
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix) && v.value().list.applies_to(stream_name))
+        .map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    if matchers.is_empty() {
+        return;
+    }
+
+    let mut lists = Vec::new();
+    let mut indicators = Vec::new();
+    for matcher in matchers {
+        let hit = if matcher.list.fields.is_empty() {
+            record.iter().find_map(|(field, value)| {
+                value
+                    .as_str()
+                    .and_then(|v| matcher.check(v))
+                    .map(|indicator| (field.to_string(), indicator))
+            })
+        } else {
+            matcher.list.fields.iter().find_map(|field| {
+                record
+                    .get(field)
+                    .and_then(|v| v.as_str())
+                    .and_then(|v| matcher.check(v))
+                    .map(|indicator| (field.to_string(), indicator))
+            })
+        };
+        let Some((field, indicator)) = hit else {
+            continue;
+        };
+        lists.push(matcher.list.name.clone());
+        indicators.push(indicator.clone());
+        if !matcher.list.destinations.is_empty() {
+            let timestamp = record
+                .get(&CONFIG.common.column_timestamp)
+                .and_then(|v| v.as_i64())
+                .unwrap_or_else(|| Utc::now().timestamp_micros());
+            notify(
+                matcher,
+                IndicatorHit {
+                    org_id: org_id.to_string(),
+                    list: matcher.list.name.clone(),
+                    indicator_type: matcher.list.indicator_type,
+                    indicator,
+                    stream_name: stream_name.to_string(),
+                    field,
+                    timestamp,
+                },
+            );
+        }
+    }
+    if !lists.is_empty() {
+        record.insert(THREAT_INTEL_MATCH_FIELD.to_string(), lists.join(",").into());
+        record.insert(
+            THREAT_INTEL_INDICATOR_FIELD.to_string(),
+            indicators.join(",").into(),
+        );
+    }
+}
+
+/// Sends the hit to the destinations of the list, an indicator of a list is
+/// only sent once per `ZO_THREAT_INTEL_ALERT_INTERVAL`.
+fn notify(matcher: Arc<Matcher>, hit: IndicatorHit) {
+    let now = Utc::now().timestamp_micros();
+    let key = format!("{}/{}/{}", hit.org_id, matcher.list.name, hit.indicator);
+    let interval = CONFIG.limit.threat_intel_alert_interval * 1_000_000;
+    if NOTIFIED_HITS
+        .get(&key)
+        .is_some_and(|last| now - *last < interval)
+    {
+        return;
+    }
+    NOTIFIED_HITS.insert(key, now);
+    tokio::task::spawn(async move {
+        let msg = json::to_string(&hit).unwrap();
+        let subject = format!("Threat intel - {}", hit.list);
+        for dest in matcher.list.destinations.iter() {
+            if let Err(e) =
+                alerts::send_raw_notification(&hit.org_id, dest, &subject, msg.clone()).await
+            {
+                log::error!(
+                    "[THREAT_INTEL] send hit of list {}/{} to {dest} error: {e}",
+                    hit.org_id,
+                    hit.list
+                );
+            }
+        }
+    });
+}
+
+/// Drops the notified hits older than the alert interval.
+pub fn clean_notified_hits() {
+    let since =
+        Utc::now().timestamp_micros() - CONFIG.limit.threat_intel_alert_interval * 1_000_000;
+    NOTIFIED_HITS.retain(|_, last| *last >= since);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(indicator_type: IndicatorType, indicators: &[&str]) -> IndicatorList {
+        IndicatorList {
+            name: "bad".to_string(),
+            description: "".to_string(),
+            indicator_type,
+            indicators: indicators.iter().map(|v| v.to_string()).collect(),
+            feed_url: "".to_string(),
+            fields: vec![],
+            streams: vec![],
+            destinations: vec![],
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_matcher_check() {
+        let ip = Matcher::new(
+            list(IndicatorType::Ip, &["10.1.2.3"]),
+            &["192.168.0.0/16".to_string()],
+        );
+        assert_eq!(ip.check("10.1.2.3"), Some("10.1.2.3".to_string()));
+        assert_eq!(ip.check("192.168.7.7"), Some("192.168.0.0/16".to_string()));
+        assert_eq!(ip.check("10.1.2.4"), None);
+        assert_eq!(ip.check("not an ip"), None);
+
+        let domain = Matcher::new(list(IndicatorType::Domain, &["Evil.com"]), &[]);
+        assert_eq!(domain.check("evil.com"), Some("evil.com".to_string()));
+        assert_eq!(domain.check("cdn.EVIL.com"), Some("evil.com".to_string()));
+        assert_eq!(
+            domain.check("https://a.evil.com/login?x=1"),
+            Some("evil.com".to_string())
+        );
+        assert_eq!(domain.check("notevil.com"), None);
+
+        let hash = Matcher::new(
+            list(IndicatorType::Hash, &["D41D8CD98F00B204E9800998ECF8427E"]),
+            &[],
+        );
+        assert!(hash.check("d41d8cd98f00b204e9800998ecf8427e").is_some());
+    }
+
+    #[test]
+    fn test_validate_and_parse_feed() {
+        assert!(validate(&list(IndicatorType::Ip, &["10.0.0.0/8", "::1"])).is_ok());
+        assert!(validate(&list(IndicatorType::Ip, &["10.0.0.300"])).is_err());
+        assert!(validate(&list(IndicatorType::Hash, &["xyz"])).is_err());
+        assert!(validate(&list(IndicatorType::Domain, &[])).is_err());
+
+        let feed = "# updated daily\n1.2.3.4\n\n5.6.7.8 # scanner\n";
+        assert_eq!(parse_feed(feed), vec!["1.2.3.4", "5.6.7.8"]);
+    }
+}
@@ -84,6 +84,7 @@ pub async fn handle_trace_request(
     }
 
     if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        crate::service::webhooks::notify_quota_exceeded(org_id);
         return Ok(HttpResponse::Forbidden().json(MetaHttpResponse::error(
             http::StatusCode::FORBIDDEN.into(),
             format!("Quota exceeded for this organization [{}]", org_id),
@@ -109,6 +110,14 @@ pub async fn handle_trace_request(
         Some(name) => format_stream_name(name),
         None => "default".to_owned(),
     };
+    if db::compact::rename::is_renaming(org_id, StreamType::Traces, &traces_stream_name) {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(MetaHttpResponse::error(
+                http::StatusCode::SERVICE_UNAVAILABLE.into(),
+                format!("stream [{traces_stream_name}] is being renamed"),
+            )),
+        );
+    }
 
     let stream_schema = stream_schema_exists(
         org_id,
@@ -0,0 +1,199 @@
+// Copyright 2024 Zinc Labs Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Org level webhooks notified of the stream lifecycle events, so that
+//! external catalogs and chatops stay in sync with the streams.
+
+use chrono::Utc;
+use config::{utils::json, CONFIG};
+
+use crate::{
+    common::{
+        infra::config::{WEBHOOKS, WEBHOOK_QUOTA_EVENTS},
+        meta::webhooks::{StreamEvent, StreamEventType, Webhook},
+    },
+    service::{db, error::ServiceError},
+};
+
+pub async fn save(
+    org_id: &str,
+    name: &str,
+    mut webhook: Webhook,
+    create: bool,
+) -> Result<(), ServiceError> {
+    if !name.is_empty() {
+        webhook.name = name.to_string();
+    }
+    webhook.name = webhook.name.trim().to_string();
+    validate(&webhook).map_err(ServiceError::bad_request)?;
+
+    match db::webhooks::get(org_id, &webhook.name).await {
+        Ok(_) if create => {
+            return Err(ServiceError::bad_request("Webhook already exists"));
+        }
+        Err(_) if !create => {
+            return Err(ServiceError::not_found("Webhook not found"));
+        }
+        _ => {}
+    }
+    db::webhooks::set(org_id, &webhook)
+        .await
+        .map_err(ServiceError::from)
+}
+
+pub async fn get(org_id: &str, name: &str) -> Result<Webhook, ServiceError> {
+    db::webhooks::get(org_id, name)
+        .await
+        .map_err(|_| ServiceError::not_found("Webhook not found"))
+}
+
+pub async fn list(org_id: &str) -> Result<Vec<Webhook>, ServiceError> {
+    db::webhooks::list(org_id).await.map_err(ServiceError::from)
+}
+
+pub async fn delete(org_id: &str, name: &str) -> Result<(), ServiceError> {
+    if db::webhooks::get(org_id, name).await.is_err() {
+        return Err(ServiceError::not_found("Webhook not found"));
+    }
+    db::webhooks::delete(org_id, name)
+        .await
+        .map_err(ServiceError::from)
+}
+
+fn validate(webhook: &Webhook) -> Result<(), String> {
+    if webhook.name.is_empty() {
+        return Err("Webhook name is required".to_string());
+    }
+    if webhook.name.contains('/') {
+        return Err("Webhook name cannot contain '/'".to_string());
+    }
+    match url::Url::parse(&webhook.url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => return Err(format!("Webhook url [{}] is invalid", webhook.url)),
+    }
+    if webhook.headers.keys().any(|k| k.trim().is_empty()) {
+        return Err("Webhook header names can't be empty".to_string());
+    }
+    Ok(())
+}
+
+/// Sends the event to the webhooks of the org subscribed to it. Delivery
+/// happens in the background, failures are only logged so they never fail
+/// the change which raised the event.
+pub fn notify(event: StreamEvent) {
+    if WEBHOOKS.is_empty() {
+        return;
+    }
+    let prefix = format!("{}/", event.org_id);
+    let webhooks = WEBHOOKS
+        .iter()
+        .filter(|v| v.key().starts_with(&prefix) && v.value().subscribes(event.event))
+        .map(|v| v.value().clone())
+        .collect::<Vec<_>>();
+    if webhooks.is_empty() {
+        return;
+    }
+    tokio::task::spawn(async move {
+        for webhook in webhooks {
+            if let Err(e) = send(&webhook, &event).await {
+                log::error!(
+                    "[WEBHOOKS] send {} event of org {} to {} error: {e}",
+                    event.event,
+                    event.org_id,
+                    webhook.name
+                );
+            }
+        }
+    });
+}
+
+/// Raised by every rejected ingestion request, so the event of an org is
+/// only sent once per `ZO_WEBHOOK_QUOTA_EVENT_INTERVAL`.
+pub fn notify_quota_exceeded(org_id: &str) {
+    let now = Utc::now().timestamp_micros();
+    let interval = CONFIG.limit.webhook_quota_event_interval * 1_000_000;
+    if WEBHOOK_QUOTA_EVENTS
+        .get(org_id)
+        .is_some_and(|last| now - *last < interval)
+    {
+        return;
+    }
+    WEBHOOK_QUOTA_EVENTS.insert(org_id.to_string(), now);
+    notify(StreamEvent {
+        event: StreamEventType::QuotaExceeded,
+        org_id: org_id.to_string(),
+        stream_type: None,
+        stream_name: None,
+        timestamp: now,
+        details: json::json!({
+            "message": format!("Quota exceeded for this organization [{org_id}]")
+        }),
+    });
+}
+
+async fn send(webhook: &Webhook, event: &StreamEvent) -> Result<(), anyhow::Error> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(webhook.skip_tls_verify)
+        .timeout(std::time::Duration::from_secs(CONFIG.limit.webhook_timeout))
+        .build()?;
+    let mut req = client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-OpenObserve-Event", event.event.as_str());
+    for (key, value) in webhook.headers.iter() {
+        req = req.header(key, value);
+    }
+    let resp = req.body(json::to_string(event)?).send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow::anyhow!("sent error status: {}", resp.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(name: &str, url: &str) -> Webhook {
+        Webhook {
+            name: name.to_string(),
+            url: url.to_string(),
+            headers: Default::default(),
+            events: vec![StreamEventType::StreamDeleted],
+            skip_tls_verify: false,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&webhook("catalog", "https://catalog.example.com/hook")).is_ok());
+        assert!(validate(&webhook("", "https://catalog.example.com/hook")).is_err());
+        assert!(validate(&webhook("a/b", "https://catalog.example.com/hook")).is_err());
+        assert!(validate(&webhook("catalog", "ftp://catalog.example.com")).is_err());
+        assert!(validate(&webhook("catalog", "not a url")).is_err());
+    }
+
+    #[test]
+    fn test_subscribes() {
+        let mut hook = webhook("catalog", "https://catalog.example.com/hook");
+        assert!(hook.subscribes(StreamEventType::StreamDeleted));
+        assert!(!hook.subscribes(StreamEventType::SchemaChanged));
+        hook.events.clear();
+        assert!(hook.subscribes(StreamEventType::SchemaChanged));
+        hook.enabled = false;
+        assert!(!hook.subscribes(StreamEventType::StreamDeleted));
+    }
+}
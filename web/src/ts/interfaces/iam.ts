@@ -10,7 +10,8 @@ export type PermissionType =
   | "AllowGet"
   | "AllowList"
   | "AllowPost"
-  | "AllowPut";
+  | "AllowPut"
+  | "AllowDecrypt";
 
 export interface Permission {
   object: string; // stream:geo or stream:org_id
@@ -49,6 +50,10 @@ export interface Resource {
       show: boolean;
       value: boolean | null;
     };
+    AllowDecrypt?: {
+      show: boolean;
+      value: boolean | null;
+    };
   };
   display_name: string;
   type: "Type";
@@ -92,6 +97,10 @@ export interface Entity {
       show: boolean;
       value: boolean | null;
     };
+    AllowDecrypt?: {
+      show: boolean;
+      value: boolean | null;
+    };
   };
   display_name: string;
   entities?: Entity[];